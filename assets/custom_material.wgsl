@@ -1,7 +1,7 @@
 // The definition of TilemapVertexOutput is in src/render/shaders/common.wgsl
 // Don't be afraid of reading the original shader code if you are not familiar with it!
 // They are already filled with comments and easy to understand.
-#import bevy_entitiles::common::TilemapVertexOutput;
+#import bevy_entitiles::common::{TilemapVertexOutput, is_clipped};
 
 @group(1) @binding(0)
 var<uniform> speed_and_time: vec2<f32>;
@@ -9,6 +9,11 @@ var<uniform> speed_and_time: vec2<f32>;
 // The fragment entry name of your shader must be tilemap_fragment
 @fragment
 fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
+    // Respect the `TilemapClip` of the tilemap.
+    if is_clipped(input.world_position, input.position.xy) {
+        discard;
+    }
+
     // Infact the tilemap has 4 layers. Here we only sample the top layer.
     // If you want to see how the original shader looks like, you can check src/render/shaders/tilemap.wgsl
     let tex_color = textureSample(bevy_entitiles::common::color_texture,
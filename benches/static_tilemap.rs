@@ -0,0 +1,121 @@
+//! Measure the per frame cost of static and animated tilemaps without a gpu.
+//!
+//! ```text
+//! cargo bench --bench static_tilemap
+//! ```
+//!
+//! Animations and the global tint are evaluated in the shader, so once the tiles
+//! are spawned, no tile is touched again. This panics if any tile is changed
+//! or any material is modified after the first frame.
+//!
+//! **Notice**: There's no render app here, so only the main world is measured.
+//! Extraction and uploading are not covered.
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    asset::{AssetEvent, AssetPlugin},
+    ecs::{
+        event::EventReader,
+        query::Changed,
+        system::{Commands, Query, ResMut, Resource, RunSystemOnce},
+    },
+    math::{IVec2, UVec2},
+    prelude::App,
+    MinimalPlugins,
+};
+use bevy_entitiles::{
+    math::GridRect,
+    prelude::*,
+    render::{material::StandardTilemapMaterial, tint::global_tint_updater},
+    tilemap::tile::Tile,
+};
+
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 100;
+
+#[derive(Resource, Default)]
+struct Touched {
+    tiles: usize,
+    materials: usize,
+}
+
+fn count_touched(
+    tiles_query: Query<(), Changed<Tile>>,
+    mut material_events: EventReader<AssetEvent<StandardTilemapMaterial>>,
+    mut touched: ResMut<Touched>,
+) {
+    touched.tiles += tiles_query.iter().count();
+    touched.materials += material_events.read().count();
+}
+
+fn setup(size: u32) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), EntiTilesCorePlugin))
+        .insert_resource(TilemapGlobalTint::new(ColorCurve::day_night(), 10.))
+        .init_resource::<Touched>()
+        .add_systems(bevy::app::Update, (global_tint_updater, count_touched));
+
+    app.world_mut()
+        .run_system_once(move |mut commands: Commands| {
+            let entity = commands.spawn_empty().id();
+            let mut storage = TilemapStorage::new(32, entity);
+            let mut animations = TilemapAnimations::default();
+            let animation = animations.register(RawTileAnimation::from_atlas_indices(0, 0..4, 10));
+
+            // Half static, half animated.
+            storage.fill_rect(
+                &mut commands,
+                GridRect::new(IVec2::ZERO, UVec2::new(size, size / 2)),
+                TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
+            );
+            storage.fill_rect(
+                &mut commands,
+                GridRect::new(IVec2::new(0, (size / 2) as i32), UVec2::new(size, size / 2)),
+                TileBuilder::new().with_animation(animation),
+            );
+
+            commands.entity(entity).insert((storage, animations));
+        });
+
+    // Let the spawning settle.
+    for _ in 0..WARMUP_FRAMES {
+        app.update();
+    }
+    *app.world_mut().resource_mut::<Touched>() = Touched::default();
+    app
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>12} {:>14} {:>16}",
+        "tiles", "frame", "changed tiles", "material events"
+    );
+
+    for size in [100, 500, 1000] {
+        let mut app = setup(size);
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            app.update();
+        }
+        let frame = start.elapsed() / FRAMES;
+
+        let tiles = app.world_mut().query::<&Tile>().iter(app.world()).count();
+        let touched = app.world().resource::<Touched>();
+        println!(
+            "{:>10} {:>12} {:>14} {:>16}",
+            tiles,
+            format_duration(frame),
+            touched.tiles,
+            touched.materials
+        );
+
+        assert_eq!(touched.tiles, 0, "Static tiles are changed on the cpu.");
+        assert_eq!(touched.materials, 0, "Materials are modified on the cpu.");
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.)
+}
@@ -12,6 +12,7 @@ fn main() {
             EntiTilesHelpersPlugin::default(),
         ))
         .add_systems(Startup, setup)
+        .add_systems(Update, play_once)
         .run();
 }
 
@@ -40,20 +41,19 @@ fn setup(
         ..Default::default()
     };
 
-    let anim_a = tilemap.animations.register(RawTileAnimation {
-        fps: 2,
-        #[cfg(not(feature = "atlas"))]
-        sequence: vec![0, 1, 2, 3],
-        #[cfg(feature = "atlas")]
-        sequence: vec![(0, 0), (0, 1), (0, 2), (0, 3)],
-    });
-    let anim_b = tilemap.animations.register(RawTileAnimation {
-        fps: 3,
-        #[cfg(not(feature = "atlas"))]
-        sequence: vec![0, 1, 2],
-        #[cfg(feature = "atlas")]
-        sequence: vec![(0, 0), (0, 1), (0, 2)],
-    });
+    let anim_a = tilemap
+        .animations
+        .register(RawTileAnimation::from_atlas_indices(0, [0, 1, 2, 3], 2));
+    let anim_b = tilemap
+        .animations
+        .register(RawTileAnimation::from_atlas_indices(0, [0, 1, 2], 3));
+    let anim_c = tilemap.animations.register(
+        RawTileAnimation::from_atlas_indices(0, [0, 1, 2, 3], 4)
+            .with_mode(TileAnimationMode::PingPong),
+    );
+    let anim_once = tilemap.animations.register(
+        RawTileAnimation::from_atlas_indices(0, [0, 1, 2, 3], 4).with_mode(TileAnimationMode::Once),
+    );
 
     tilemap.storage.fill_rect(
         &mut commands,
@@ -67,5 +67,40 @@ fn setup(
         TileBuilder::new().with_animation(anim_b),
     );
 
-    commands.entity(entity).insert(tilemap);
+    tilemap.storage.fill_rect(
+        &mut commands,
+        GridRect::new(IVec2::new(10, 0), UVec2 { x: 10, y: 10 }),
+        TileBuilder::new().with_animation(anim_c),
+    );
+
+    commands
+        .entity(entity)
+        .insert((tilemap, OnceAnimation(anim_once)));
+}
+
+#[derive(Component)]
+struct OnceAnimation(TileAnimation);
+
+// Press space to "open the chests" in the top right corner.
+fn play_once(
+    mut commands: Commands,
+    tilemaps_query: Query<(&TilemapStorage, &OnceAnimation)>,
+    input: Res<ButtonInput<KeyCode>>,
+) {
+    if !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+
+    for (storage, anim) in &tilemaps_query {
+        for x in 10..20 {
+            for y in 10..20 {
+                if let Some(tile) = storage.get(IVec2::new(x, y)) {
+                    commands.entity(tile).insert(OneShotTileAnimation::new(
+                        anim.0,
+                        vec![TileLayer::no_flip(3)],
+                    ));
+                }
+            }
+        }
+    }
 }
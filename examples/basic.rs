@@ -113,6 +113,7 @@ fn setup(
         storage: TilemapStorage::new(32, entity),
         material: materials.add(StandardTilemapMaterial {
             tint: TOMATO.into(),
+            ..Default::default()
         }),
         textures: textures.add(TilemapTextures::single(
             TilemapTexture::new(
@@ -53,7 +53,12 @@ fn setup(
 
     // Which means we need to first detect the chunks that are intersected with the detect aabb,
     // and if every one is visible, then do nothing else load/generate chunks that are intersected with the update aabb.
-    commands.spawn((Camera2dBundle::default(), CameraChunkUpdater::new(1.3, 2.2)));
+    commands.spawn((
+        Camera2dBundle::default(),
+        CameraChunkUpdater::new(1.3, 2.2)
+            .with_unload_scale(2.6)
+            .with_keep_alive(1.),
+    ));
 
     let entity = commands.spawn_empty().id();
     let mut tilemap = StandardTilemapBundle {
@@ -103,6 +108,7 @@ fn setup(
                 Some(PhysicsTile {
                     rigid_body: true,
                     friction: Some(0.2),
+                    ..Default::default()
                 })
             } else {
                 None
@@ -51,13 +51,7 @@ fn main() {
             ignore_unregistered_entities: true,
             animation_mapper: HashMap::from([(
                 470,
-                RawTileAnimation {
-                    #[cfg(feature = "atlas")]
-                    sequence: vec![(0, 469), (0, 446), (0, 447)],
-                    #[cfg(not(feature = "atlas"))]
-                    sequence: vec![469, 446, 447],
-                    fps: 3,
-                },
+                RawTileAnimation::from_atlas_indices(0, [469, 446, 447], 3),
             )]),
             ..Default::default()
         })
@@ -72,6 +66,7 @@ fn main() {
                         PhysicsTile {
                             rigid_body: true,
                             friction: Some(0.9),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -79,6 +74,7 @@ fn main() {
                         PhysicsTile {
                             rigid_body: true,
                             friction: Some(0.1),
+                            ..Default::default()
                         },
                     ),
                 ])),
@@ -125,6 +121,9 @@ macro_rules! level_control {
                 level: LdtkLevel::Identifier($level.into()),
                 mode: LdtkLevelLoaderMode::Tilemap,
                 trans_ovrd: None,
+                z_ovrd: None,
+                layer_filter: Default::default(),
+                layer_overrides: Default::default(),
             }));
         }
     };
@@ -47,6 +47,7 @@ fn main() {
                         PhysicsTile {
                             rigid_body: true,
                             friction: Some(0.5),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -54,6 +55,7 @@ fn main() {
                         PhysicsTile {
                             rigid_body: true,
                             friction: Some(0.8),
+                            ..Default::default()
                         },
                     ),
                 ])),
@@ -82,7 +84,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn(Camera2dBundle::default());
     let file = asset_server.load("ldtk/wfc_source.ldtk");
 
-    let rules = WfcRules::from_file("examples/ldtk_wfc_config.ron", TilemapType::Square);
+    let rules = WfcRules::from_file("examples/ldtk_wfc_config.ron", TilemapType::Square).unwrap();
     commands.spawn((
         WfcRunner::new(
             TilemapType::Square,
@@ -170,6 +172,9 @@ fn load_level(
                 level: LdtkLevel::Identifier(ident),
                 mode: LdtkLevelLoaderMode::Tilemap,
                 trans_ovrd: Some(wfc_manager.get_translation(l.0.as_ivec2(), Vec2::splat(8.))),
+                z_ovrd: None,
+                layer_filter: Default::default(),
+                layer_overrides: Default::default(),
             }));
         }
         commands.entity(e).despawn();
@@ -71,12 +71,7 @@ fn setup(
     let queue = (0..100).into_iter().map(|_| {
         (
             commands.spawn_empty().id(),
-            PathFinder {
-                origin: IVec2::ZERO,
-                dest: IVec2::splat(499),
-                allow_diagonal: false,
-                max_steps: None,
-            },
+            PathFinder::new(IVec2::ZERO, IVec2::splat(499), TilemapType::Isometric),
         )
     });
 
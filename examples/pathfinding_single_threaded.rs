@@ -67,14 +67,8 @@ fn setup(
     let queue = (0..20).into_iter().map(|_| {
         (
             commands.spawn_empty().id(),
-            PathFinder {
-                origin: IVec2::ZERO,
-                dest: IVec2::splat(99),
-                allow_diagonal: false,
-                max_steps: None,
-                max_steps_per_frame: 1000,
-                tilemap_ty: TilemapType::Square,
-            },
+            PathFinder::new(IVec2::ZERO, IVec2::splat(99), TilemapType::Isometric)
+                .with_max_steps_per_frame(1000),
         )
     });
 
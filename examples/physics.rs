@@ -37,6 +37,7 @@ fn setup(
         PhysicsTile {
             rigid_body: false,
             friction: None,
+            ..Default::default()
         },
     );
 
@@ -45,6 +46,7 @@ fn setup(
         PhysicsTile {
             rigid_body: true,
             friction: Some(0.8),
+            ..Default::default()
         },
         false,
     );
@@ -123,6 +125,7 @@ fn setup(
                 PhysicsTile {
                     rigid_body: true,
                     friction: Some(0.1),
+                    ..Default::default()
                 },
             ),
             (
@@ -130,6 +133,7 @@ fn setup(
                 PhysicsTile {
                     rigid_body: true,
                     friction: Some(0.4),
+                    ..Default::default()
                 },
             ),
         ]
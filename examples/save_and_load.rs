@@ -77,6 +77,7 @@ fn setup(
         PhysicsTile {
             rigid_body: true,
             friction: Some(0.5),
+            ..Default::default()
         },
     );
     physics_tilemap.fill_rect(
@@ -84,6 +85,7 @@ fn setup(
         PhysicsTile {
             rigid_body: false,
             friction: None,
+            ..Default::default()
         },
         true,
     );
@@ -1,14 +1,20 @@
+//! Usage: `cargo run --example stress_test --release -- [map size] [chunk size]`
+//!
+//! The map size defaults to 1000 and the chunk size defaults to 32.
+
 use bevy::{
     app::{App, PluginGroup, Startup},
     asset::{AssetServer, Assets},
     core_pipeline::core_2d::Camera2dBundle,
-    ecs::system::{Commands, Res, ResMut},
+    diagnostic::LogDiagnosticsPlugin,
+    ecs::system::{Commands, Res, ResMut, Resource},
     math::{IVec2, UVec2, Vec2},
     render::render_resource::FilterMode,
     window::{PresentMode, Window, WindowPlugin},
     DefaultPlugins,
 };
 use bevy_entitiles::{
+    diagnostics::TilemapDiagnosticsPlugin,
     math::GridRect,
     render::{cull::FrustumCulling, material::StandardTilemapMaterial},
     tilemap::{
@@ -25,7 +31,22 @@ use helpers::EntiTilesHelpersPlugin;
 
 mod helpers;
 
+#[derive(Resource)]
+struct StressTestConfig {
+    map_size: u32,
+    chunk_size: u32,
+}
+
 fn main() {
+    let mut args = std::env::args().skip(1).map(|arg| {
+        arg.parse::<u32>()
+            .expect("Expected positive integers as arguments!")
+    });
+    let config = StressTestConfig {
+        map_size: args.next().unwrap_or(1000),
+        chunk_size: args.next().unwrap_or(32),
+    };
+
     App::new()
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
@@ -37,7 +58,10 @@ fn main() {
             }),
             EntiTilesPlugin,
             EntiTilesHelpersPlugin { inspector: false },
+            TilemapDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
         ))
+        .insert_resource(config)
         .add_systems(Startup, setup)
         .insert_resource(FrustumCulling(false))
         .run();
@@ -48,6 +72,7 @@ fn setup(
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<StandardTilemapMaterial>>,
     mut textures: ResMut<Assets<TilemapTextures>>,
+    config: Res<StressTestConfig>,
 ) {
     commands.spawn(Camera2dBundle::default());
 
@@ -56,7 +81,7 @@ fn setup(
         tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
         slot_size: TilemapSlotSize(Vec2::new(16., 16.)),
         ty: TilemapType::Square,
-        storage: TilemapStorage::new(32, entity),
+        storage: TilemapStorage::new(config.chunk_size, entity),
         material: materials.add(StandardTilemapMaterial::default()),
         textures: textures.add(TilemapTextures::single(
             TilemapTexture::new(
@@ -70,7 +95,10 @@ fn setup(
 
     tilemap.storage.fill_rect(
         &mut commands,
-        GridRect::new(IVec2::splat(-500), UVec2::splat(1000)),
+        GridRect::new(
+            IVec2::splat(-(config.map_size as i32) / 2),
+            UVec2::splat(config.map_size),
+        ),
         TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
     );
 
@@ -29,6 +29,7 @@ fn main() {
             ignore_unregisterd_objects: true,
             ignore_unregisterd_custom_tiles: true,
             z_index: 0.,
+            ..Default::default()
         })
         .register_tiled_object::<BlockBundle>("BlockBundle")
         .register_tiled_object::<PlainBlockBundle>("PlainBlockBundle")
@@ -84,6 +85,8 @@ macro_rules! map_switching {
             $event.send(TiledMapEvent::Load(TiledMapLoader {
                 map: $tiled_maps[$map].id(),
                 trans_ovrd: None,
+                z_ovrd: None,
+                layer_filter: None,
             }));
         }
     };
@@ -25,13 +25,10 @@ fn setup(
 
     let entity = commands.spawn_empty().id();
 
-    let rules = WfcRules::from_file("examples/wfc_config.ron", TilemapType::Square);
+    let rules = WfcRules::from_file("examples/wfc_config.ron", TilemapType::Square).unwrap();
 
     commands.entity(entity).insert((
-        #[cfg(not(feature = "atlas"))]
-        WfcSource::from_atlas_indices(&rules),
-        #[cfg(feature = "atlas")]
-        WfcSource::from_atlas_indices(&rules, 0),
+        WfcSource::from_texture_atlas(&rules, 0),
         WfcRunner::new(
             TilemapType::Square,
             rules,
@@ -39,7 +36,7 @@ fn setup(
             Some(0),
         )
         // use weights OR custom_sampler
-        // .with_weights("examples/wfc_weights.ron".to_string())
+        // .with_weights("examples/wfc_weights.ron".to_string()).unwrap()
         .with_retrace_settings(Some(8), Some(1000000)),
         StandardTilemapBundle {
             tile_render_size: TileRenderSize(Vec2::new(16., 16.)),
@@ -96,10 +96,11 @@ fn setup(mut commands: Commands, mut materials: ResMut<Assets<StandardTilemapMat
 
     // let entity = commands.spawn_empty().id();
 
-    // let rules = WfcRules::from_file("examples/wfc_config.ron", TilemapType::Square);
+    // let rules = WfcRules::from_file("examples/wfc_config.ron", TilemapType::Square).unwrap();
 
     // commands.entity(entity).insert((
-    //     WfcSource::from_pattern_path(PATTERNS_PATH.to_string(), PREFIX.to_string(), &rules, None),
+    //     WfcSource::from_pattern_path(PATTERNS_PATH.to_string(), PREFIX.to_string(), &rules, None)
+    //         .unwrap(),
     //     WfcRunner::new(
     //         TilemapType::Square,
     //         rules,
@@ -0,0 +1,501 @@
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc};
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Query, ResMut},
+        world::Ref,
+    },
+    math::{IVec2, Vec2},
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::Image,
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    math::GridRect,
+    tilemap::{map::TilemapStorage, tile::Tile},
+};
+
+pub type DistanceFieldBlocker = Arc<dyn Fn(&Tile) -> bool + Send + Sync>;
+
+/// The distance from every tile in `area` to the nearest blocked tile.
+///
+/// By default, every tile that exists is blocked. Use [`DistanceField::with_blocker`]
+/// to decide it according to the tile instead.
+///
+/// Insert this to a tilemap, and it will be updated when the tilemap changes.
+/// Distances are euclidean and measured in tiles.
+///
+/// Only the tiles around the changed ones are updated, by propagating the changes
+/// outwards from them. A finite [`DistanceField::with_max_distance`] also stops the
+/// propagation there, which keeps the updates cheap on large open areas.
+///
+/// **Notice**: Removed tiles can't be located directly, so the area is scanned
+/// to find them when tiles are removed from the tilemap. Nothing is recalculated
+/// for the tiles that are not changed though.
+#[derive(Component, Clone)]
+pub struct DistanceField {
+    pub(crate) area: GridRect,
+    pub(crate) max_distance: f32,
+    pub(crate) blocker: Option<DistanceFieldBlocker>,
+    pub(crate) image: Option<Handle<Image>>,
+    pub(crate) data: Vec<f32>,
+    pub(crate) blocked: Vec<bool>,
+    /// The squared distance to the nearest blocked tile, and its linear index.
+    pub(crate) nearest: Vec<(u32, Option<u32>)>,
+    pub(crate) dirty: bool,
+}
+
+impl DistanceField {
+    pub fn new(area: GridRect) -> Self {
+        Self {
+            area,
+            max_distance: f32::MAX,
+            blocker: None,
+            image: None,
+            data: vec![f32::MAX; area.size()],
+            blocked: vec![false; area.size()],
+            nearest: vec![(u32::MAX, None); area.size()],
+            dirty: true,
+        }
+    }
+
+    /// Clamp the distances to `max_distance`.
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Decide whether a tile is blocked. Tiles that don't exist are never blocked.
+    pub fn with_blocker(mut self, blocker: impl Fn(&Tile) -> bool + Send + Sync + 'static) -> Self {
+        self.blocker = Some(Arc::new(blocker));
+        self
+    }
+
+    /// Write the distances into this image as well, so they can be used in shaders.
+    ///
+    /// The image is `R32Float` and has the same size as the area.
+    /// The first row of the image is the top row of the area.
+    pub fn with_image(mut self, image: Handle<Image>) -> Self {
+        self.image = Some(image);
+        self
+    }
+
+    #[inline]
+    pub fn area(&self) -> GridRect {
+        self.area
+    }
+
+    /// Force recalculating the field.
+    #[inline]
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Get the distance at `index`. Returns `None` if it's outside the area.
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<f32> {
+        self.linear_index(index).map(|i| self.data[i])
+    }
+
+    /// The direction that the distance increases fastest at `index`.
+    /// Useful for steering away from blocked tiles.
+    pub fn gradient(&self, index: IVec2) -> Option<Vec2> {
+        let center = self.get(index)?;
+        let sample = |offset: IVec2| self.get(index + offset).unwrap_or(center);
+
+        Some(Vec2::new(
+            (sample(IVec2::X) - sample(IVec2::NEG_X)) / 2.,
+            (sample(IVec2::Y) - sample(IVec2::NEG_Y)) / 2.,
+        ))
+    }
+
+    /// Iterate over the tiles that are at least `distance` away from any blocked tile.
+    pub fn iter_at_least(&self, distance: f32) -> impl Iterator<Item = IVec2> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(move |(_, d)| **d >= distance)
+            .map(|(i, _)| self.index_of(i))
+    }
+
+    fn linear_index(&self, index: IVec2) -> Option<usize> {
+        if !self.area.contains(index) {
+            return None;
+        }
+
+        let local = index - self.area.origin;
+        Some((local.y * self.area.extent.x as i32 + local.x) as usize)
+    }
+
+    fn index_of(&self, linear_index: usize) -> IVec2 {
+        self.area.origin + self.local_of(linear_index)
+    }
+
+    fn local_of(&self, linear_index: usize) -> IVec2 {
+        let width = self.area.extent.x as usize;
+        IVec2::new((linear_index % width) as i32, (linear_index / width) as i32)
+    }
+
+    fn neighbours(&self, linear_index: usize) -> impl Iterator<Item = usize> {
+        let local = self.local_of(linear_index);
+        let extent = self.area.extent.as_ivec2();
+        [
+            IVec2::new(-1, -1),
+            IVec2::new(0, -1),
+            IVec2::new(1, -1),
+            IVec2::new(-1, 0),
+            IVec2::new(1, 0),
+            IVec2::new(-1, 1),
+            IVec2::new(0, 1),
+            IVec2::new(1, 1),
+        ]
+        .into_iter()
+        .map(move |offset| local + offset)
+        .filter(move |n| n.cmpge(IVec2::ZERO).all() && n.cmplt(extent).all())
+        .map(move |n| (n.y * extent.x + n.x) as usize)
+    }
+
+    fn squared_distance(&self, a: usize, b: usize) -> u32 {
+        (self.local_of(a) - self.local_of(b)).length_squared() as u32
+    }
+
+    /// Forget everything so the field can be built from scratch.
+    fn reset(&mut self) {
+        self.blocked.fill(false);
+        self.nearest.fill((u32::MAX, None));
+        self.data.fill(self.max_distance);
+    }
+
+    /// Set the blocked state of the cells, and propagate the changes to the cells around them,
+    /// using the dynamic brushfire algorithm from *Improved updating of Euclidean distance maps
+    /// and Voronoi diagrams* by Lau, Sprunk and Burgard.
+    ///
+    /// Returns the cells whose distance is changed.
+    fn propagate(&mut self, changes: impl IntoIterator<Item = (usize, bool)>) -> Vec<usize> {
+        let max_squared = self.max_distance * self.max_distance;
+        let mut open = BinaryHeap::new();
+        let mut raising = HashSet::new();
+        let mut touched = Vec::new();
+
+        for (cell, blocked) in changes {
+            if self.blocked[cell] == blocked {
+                continue;
+            }
+
+            self.blocked[cell] = blocked;
+            if blocked {
+                self.nearest[cell] = (0, Some(cell as u32));
+            } else {
+                self.nearest[cell] = (u32::MAX, None);
+                raising.insert(cell);
+            }
+            touched.push(cell);
+            open.push(Reverse((0, cell)));
+        }
+
+        while let Some(Reverse((_, cell))) = open.pop() {
+            if raising.remove(&cell) {
+                // Clear the cells that were closest to a removed blocker,
+                // and let the valid cells around them lower them again.
+                for n in self.neighbours(cell).collect::<Vec<_>>() {
+                    let (distance, Some(site)) = self.nearest[n] else {
+                        continue;
+                    };
+                    if raising.contains(&n) {
+                        continue;
+                    }
+
+                    if !self.blocked[site as usize] {
+                        self.nearest[n] = (u32::MAX, None);
+                        raising.insert(n);
+                        touched.push(n);
+                    }
+                    open.push(Reverse((distance, n)));
+                }
+            } else if let (_, Some(site)) = self.nearest[cell] {
+                if !self.blocked[site as usize] {
+                    continue;
+                }
+
+                for n in self.neighbours(cell).collect::<Vec<_>>() {
+                    if raising.contains(&n) {
+                        continue;
+                    }
+
+                    let distance = self.squared_distance(site as usize, n);
+                    if distance < self.nearest[n].0 && distance as f32 <= max_squared {
+                        self.nearest[n] = (distance, Some(site));
+                        touched.push(n);
+                        open.push(Reverse((distance, n)));
+                    }
+                }
+            }
+        }
+
+        touched.sort_unstable();
+        touched.dedup();
+        for cell in &touched {
+            self.data[*cell] = match self.nearest[*cell] {
+                (distance, Some(_)) => (distance as f32).sqrt().min(self.max_distance),
+                _ => self.max_distance,
+            };
+        }
+        touched
+    }
+
+    /// Write the distances of `cells` into the image, or create it if it doesn't exist.
+    fn write_image(&self, images: &mut Assets<Image>, cells: &[usize]) {
+        let Some(handle) = &self.image else {
+            return;
+        };
+
+        let Some(image) = images
+            .get_mut(handle)
+            .filter(|image| image.data.len() == self.data.len() * 4)
+        else {
+            images.insert(handle, self.to_image());
+            return;
+        };
+
+        let height = self.area.extent.y as usize;
+        let width = self.area.extent.x as usize;
+        for cell in cells {
+            let local = self.local_of(*cell);
+            // The first row of the image is the top row of the area.
+            let offset = ((height - 1 - local.y as usize) * width + local.x as usize) * 4;
+            image.data[offset..offset + 4].copy_from_slice(&self.data[*cell].to_le_bytes());
+        }
+    }
+
+    fn to_image(&self) -> Image {
+        let width = self.area.extent.x as usize;
+        let data = self
+            .data
+            .chunks(width)
+            .rev()
+            .flatten()
+            .flat_map(|d| d.to_le_bytes())
+            .collect();
+
+        Image::new(
+            Extent3d {
+                width: self.area.extent.x,
+                height: self.area.extent.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::R32Float,
+            RenderAssetUsages::default(),
+        )
+    }
+}
+
+/// Calculate the exact euclidean distance transform using the algorithm
+/// from *Distance Transforms of Sampled Functions* by Felzenszwalb and Huttenlocher.
+///
+/// Returns the distance of each cell to the nearest blocked cell.
+pub fn distance_transform(width: usize, height: usize, blocked: &[bool]) -> Vec<f32> {
+    // Anything larger than the squared diagonal works as infinity here.
+    let inf = ((width * width + height * height) as f32 + 1.) * 2.;
+    let mut field = blocked
+        .iter()
+        .map(|b| if *b { 0. } else { inf })
+        .collect::<Vec<_>>();
+
+    let mut buffer = Vec::with_capacity(width.max(height));
+    for x in 0..width {
+        buffer.clear();
+        buffer.extend((0..height).map(|y| field[y * width + x]));
+        distance_transform_1d(&buffer)
+            .into_iter()
+            .enumerate()
+            .for_each(|(y, d)| field[y * width + x] = d);
+    }
+    for y in 0..height {
+        let row = &mut field[y * width..(y + 1) * width];
+        let transformed = distance_transform_1d(row);
+        row.copy_from_slice(&transformed);
+    }
+
+    field
+        .into_iter()
+        .map(|d| if d >= inf { f32::MAX } else { d.sqrt() })
+        .collect()
+}
+
+fn distance_transform_1d(f: &[f32]) -> Vec<f32> {
+    let n = f.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // Locations of parabolas in lower envelope and the boundaries between them.
+    let mut v = vec![0; n];
+    let mut z = vec![0.; n + 1];
+    let mut k = 0;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+
+    let intersect = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2. * q as f32 - 2. * p as f32)
+    };
+
+    for q in 1..n {
+        let mut s = intersect(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersect(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    (0..n)
+        .map(|q| {
+            while z[k + 1] < q as f32 {
+                k += 1;
+            }
+            let d = q as f32 - v[k] as f32;
+            d * d + f[v[k]]
+        })
+        .collect()
+}
+
+pub fn distance_field_updater(
+    mut tilemaps_query: Query<(Entity, Ref<TilemapStorage>, &mut DistanceField)>,
+    tiles_query: Query<&Tile>,
+    changed_tiles_query: Query<&Tile, Changed<Tile>>,
+    mut removed_tiles: RemovedComponents<Tile>,
+    mut images: Option<ResMut<Assets<Image>>>,
+) {
+    let tiles_removed = removed_tiles.read().count() > 0;
+
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(entity, storage, mut field)| {
+            let is_blocked = |field: &DistanceField, cell: usize| {
+                let Some(tile) = storage.get(field.index_of(cell)) else {
+                    return false;
+                };
+
+                match &field.blocker {
+                    Some(blocker) => tiles_query.get(tile).is_ok_and(|t| blocker(t)),
+                    None => true,
+                }
+            };
+
+            let rebuild = field.dirty;
+            let changes = if rebuild {
+                field.reset();
+                (0..field.area.size())
+                    .filter(|cell| is_blocked(&field, *cell))
+                    .map(|cell| (cell, true))
+                    .collect::<Vec<_>>()
+            } else if tiles_removed && storage.is_changed() {
+                (0..field.area.size())
+                    .map(|cell| (cell, is_blocked(&field, cell)))
+                    .filter(|(cell, blocked)| field.blocked[*cell] != *blocked)
+                    .collect()
+            } else {
+                changed_tiles_query
+                    .iter()
+                    .filter(|t| t.tilemap_id == entity)
+                    .filter_map(|t| field.linear_index(t.index))
+                    .map(|cell| (cell, is_blocked(&field, cell)))
+                    .collect()
+            };
+
+            if !rebuild && changes.is_empty() {
+                return;
+            }
+
+            field.dirty = false;
+            let touched = field.propagate(changes);
+
+            // Images are not available in headless apps.
+            if let Some(images) = images.as_mut().filter(|_| rebuild || !touched.is_empty()) {
+                if rebuild {
+                    let all = (0..field.area.size()).collect::<Vec<_>>();
+                    field.write_image(images, &all);
+                } else {
+                    field.write_image(images, &touched);
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2};
+
+    use crate::math::GridRect;
+
+    use super::{distance_transform, DistanceField};
+
+    #[test]
+    fn test_distance_transform() {
+        #[rustfmt::skip]
+        let blocked = [
+            true,  false, false, false,
+            false, false, false, false,
+            false, false, false, true,
+        ];
+
+        let field = distance_transform(4, 3, &blocked);
+        assert_eq!(field[0], 0.);
+        assert_eq!(field[1], 1.);
+        assert_eq!(field[5], 2f32.sqrt());
+        assert_eq!(field[3], 2.);
+        assert_eq!(field[11], 0.);
+        assert_eq!(field[8], 2.);
+
+        assert!(distance_transform(2, 2, &[false; 4])
+            .iter()
+            .all(|d| *d == f32::MAX));
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let (width, height) = (16, 12);
+        let mut field = DistanceField::new(GridRect::new(IVec2::ZERO, UVec2::new(16, 12)));
+        let mut blocked = vec![false; width * height];
+        let mut apply = |field: &mut DistanceField, changes: Vec<(usize, bool)>| {
+            changes.iter().for_each(|(cell, b)| blocked[*cell] = *b);
+            let touched = field.propagate(changes);
+            assert_eq!(field.data, distance_transform(width, height, &blocked));
+            touched.len()
+        };
+
+        field.reset();
+        apply(
+            &mut field,
+            vec![(0, true), (37, true), (100, true), (191, true)],
+        );
+        // Add and remove blockers, including the ones other tiles are closest to.
+        apply(&mut field, vec![(37, false), (120, true)]);
+        apply(&mut field, vec![(0, false), (191, false), (5, true)]);
+        apply(&mut field, vec![(100, false), (120, false), (5, false)]);
+        assert!(field.data.iter().all(|d| *d == f32::MAX));
+
+        // Changes only propagate as far as the max distance.
+        let mut field = DistanceField::new(GridRect::new(IVec2::ZERO, UVec2::new(16, 12)))
+            .with_max_distance(1.5);
+        field.reset();
+        field.propagate([(0, true)]);
+        assert_eq!(field.propagate([(100, true)]).len(), 9);
+    }
+}
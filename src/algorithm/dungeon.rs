@@ -0,0 +1,598 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    math::{IVec2, UVec2},
+    reflect::Reflect,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    math::GridRect,
+    serializing::pattern::TilemapPattern,
+    tilemap::{algorithm::path::PathTile, tile::TileBuilder},
+};
+
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::{DataPhysicsTilemap, PhysicsTile, SerializablePhysicsSource};
+
+const NEIGHBOURS_8: [IVec2; 8] = [
+    IVec2::new(-1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(1, 0),
+    IVec2::new(-1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(1, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum DungeonCell {
+    Wall,
+    Floor,
+}
+
+/// A generated dungeon. `(0, 0)` is the bottom left corner.
+///
+/// Use [`DungeonLayout::to_pattern`] to turn it into tiles.
+#[derive(Debug, Clone, Reflect)]
+pub struct DungeonLayout {
+    pub(crate) size: UVec2,
+    pub(crate) cells: Vec<DungeonCell>,
+    pub(crate) rooms: Vec<GridRect>,
+}
+
+impl DungeonLayout {
+    /// Create a layout that is filled with walls.
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            cells: vec![DungeonCell::Wall; (size.x * size.y) as usize],
+            rooms: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// The rooms in the dungeon. Caves don't have rooms.
+    #[inline]
+    pub fn rooms(&self) -> &[GridRect] {
+        &self.rooms
+    }
+
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<DungeonCell> {
+        self.linear_index(index).map(|i| self.cells[i])
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: IVec2, cell: DungeonCell) {
+        if let Some(i) = self.linear_index(index) {
+            self.cells[i] = cell;
+        }
+    }
+
+    /// Returns true if there's no floor at `index`, including outside the layout.
+    #[inline]
+    pub fn is_wall(&self, index: IVec2) -> bool {
+        self.get(index) != Some(DungeonCell::Floor)
+    }
+
+    /// Returns true if `index` is a wall that touches some floor, including diagonally.
+    pub fn is_boundary_wall(&self, index: IVec2) -> bool {
+        self.get(index) == Some(DungeonCell::Wall)
+            && NEIGHBOURS_8.iter().any(|n| !self.is_wall(index + *n))
+    }
+
+    pub fn fill_rect(&mut self, area: GridRect, cell: DungeonCell) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set(IVec2 { x, y }, cell);
+            }
+        }
+    }
+
+    /// Iterate over all the cells along with their indices.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, DungeonCell)> + '_ {
+        self.cells.iter().enumerate().map(|(i, cell)| {
+            (
+                IVec2::new(
+                    (i % self.size.x as usize) as i32,
+                    (i / self.size.x as usize) as i32,
+                ),
+                *cell,
+            )
+        })
+    }
+
+    /// Convert the layout into a pattern, which can be applied using
+    /// `TilemapStorage::fill_with_buffer` or used in wfc.
+    pub fn to_pattern(&self, theme: &DungeonTheme) -> TilemapPattern {
+        let mut pattern = TilemapPattern::new(theme.label.clone());
+        let aabb = GridRect::new(IVec2::ZERO, self.size);
+
+        self.iter().for_each(|(index, cell)| match cell {
+            DungeonCell::Floor => {
+                if let Some(floor) = &theme.floor {
+                    pattern.tiles.set(index, floor.clone());
+                }
+                pattern.path_tiles.set(
+                    index,
+                    PathTile {
+                        cost: theme.floor_cost,
+                    },
+                );
+            }
+            DungeonCell::Wall => {
+                if let Some(wall) = &theme.wall {
+                    if !theme.boundary_walls_only || self.is_boundary_wall(index) {
+                        pattern.tiles.set(index, wall.clone());
+                    }
+                }
+            }
+        });
+
+        pattern.tiles.aabb = aabb;
+        pattern.path_tiles.aabb = aabb;
+
+        #[cfg(feature = "physics")]
+        if let Some(wall_physics) = &theme.wall_physics {
+            pattern.physics_tiles =
+                SerializablePhysicsSource::Data(DataPhysicsTilemap::new_flipped(
+                    IVec2::ZERO,
+                    self.cells
+                        .iter()
+                        .map(|c| match c {
+                            DungeonCell::Wall => 1,
+                            DungeonCell::Floor => 0,
+                        })
+                        .collect(),
+                    self.size,
+                    0,
+                    [(1, wall_physics.clone())].into_iter().collect(),
+                ));
+        }
+
+        pattern
+    }
+
+    fn linear_index(&self, index: IVec2) -> Option<usize> {
+        if index.x < 0
+            || index.y < 0
+            || index.x >= self.size.x as i32
+            || index.y >= self.size.y as i32
+        {
+            return None;
+        }
+
+        Some((index.y * self.size.x as i32 + index.x) as usize)
+    }
+
+    fn carve_corridor(&mut self, from: IVec2, to: IVec2, horizontal_first: bool) {
+        let corner = if horizontal_first {
+            IVec2::new(to.x, from.y)
+        } else {
+            IVec2::new(from.x, to.y)
+        };
+
+        self.fill_rect(
+            GridRect::from_min_max(from.min(corner), from.max(corner)),
+            DungeonCell::Floor,
+        );
+        self.fill_rect(
+            GridRect::from_min_max(corner.min(to), corner.max(to)),
+            DungeonCell::Floor,
+        );
+    }
+
+    /// Turn all the floors that are not connected to the largest floor region into walls.
+    fn keep_largest_region(&mut self) {
+        let mut region_ids = vec![usize::MAX; self.cells.len()];
+        let mut largest = None;
+        let mut largest_size = 0;
+        let mut queue = VecDeque::new();
+
+        for start in 0..self.cells.len() {
+            if self.cells[start] != DungeonCell::Floor || region_ids[start] != usize::MAX {
+                continue;
+            }
+
+            let mut size = 0;
+            region_ids[start] = start;
+            queue.push_back(start);
+
+            while let Some(cur) = queue.pop_front() {
+                size += 1;
+                let cur_index = IVec2::new(
+                    (cur % self.size.x as usize) as i32,
+                    (cur / self.size.x as usize) as i32,
+                );
+
+                [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                    .into_iter()
+                    .filter_map(|dir| self.linear_index(cur_index + dir))
+                    .for_each(|next| {
+                        if self.cells[next] == DungeonCell::Floor && region_ids[next] == usize::MAX
+                        {
+                            region_ids[next] = start;
+                            queue.push_back(next);
+                        }
+                    });
+            }
+
+            if size > largest_size {
+                largest_size = size;
+                largest = Some(start);
+            }
+        }
+
+        self.cells
+            .iter_mut()
+            .zip(region_ids)
+            .for_each(|(cell, region)| {
+                if Some(region) != largest {
+                    *cell = DungeonCell::Wall;
+                }
+            });
+    }
+}
+
+/// Decides what tiles the cells of a [`DungeonLayout`] become.
+///
+/// Floors are always walkable in the path layer, and walls are not.
+#[derive(Debug, Clone)]
+pub struct DungeonTheme {
+    pub label: Option<String>,
+    pub floor: Option<TileBuilder>,
+    pub wall: Option<TileBuilder>,
+    /// Only place walls that touch floors, and leave the solid rock empty.
+    pub boundary_walls_only: bool,
+    /// The path finding cost of floors.
+    pub floor_cost: u32,
+    /// Spawn colliders for walls using this.
+    #[cfg(feature = "physics")]
+    pub wall_physics: Option<PhysicsTile>,
+}
+
+impl DungeonTheme {
+    pub fn new(floor: TileBuilder, wall: TileBuilder) -> Self {
+        Self {
+            label: None,
+            floor: Some(floor),
+            wall: Some(wall),
+            boundary_walls_only: true,
+            floor_cost: 1,
+            #[cfg(feature = "physics")]
+            wall_physics: None,
+        }
+    }
+}
+
+/// Generates rooms connected with corridors using binary space partitioning.
+#[derive(Debug, Clone, Reflect)]
+pub struct BspDungeon {
+    pub size: UVec2,
+    /// Partitions won't be split if they would become smaller than this.
+    pub min_partition_size: UVec2,
+    pub min_room_size: UVec2,
+    pub max_depth: u32,
+    pub seed: Option<u64>,
+}
+
+impl BspDungeon {
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            min_partition_size: UVec2::splat(10),
+            min_room_size: UVec2::splat(4),
+            max_depth: 6,
+            seed: None,
+        }
+    }
+
+    pub fn with_min_partition_size(mut self, size: UVec2) -> Self {
+        self.min_partition_size = size;
+        self
+    }
+
+    pub fn with_min_room_size(mut self, size: UVec2) -> Self {
+        self.min_room_size = size;
+        self
+    }
+
+    pub fn with_max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn generate(&self) -> DungeonLayout {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut layout = DungeonLayout::new(self.size);
+
+        // Keep the outermost cells as walls.
+        let area = GridRect::new(IVec2::ONE, self.size.saturating_sub(UVec2::splat(2)));
+        if area.extent.cmpgt(UVec2::ZERO).all() {
+            self.partition(&mut layout, &mut rng, area, 0);
+        }
+
+        layout
+    }
+
+    /// Returns the rooms in this partition.
+    fn partition(
+        &self,
+        layout: &mut DungeonLayout,
+        rng: &mut StdRng,
+        area: GridRect,
+        depth: u32,
+    ) -> Vec<GridRect> {
+        let can_split = area.extent.cmpge(self.min_partition_size * 2);
+        let split_x = match (can_split.x, can_split.y) {
+            _ if depth >= self.max_depth => None,
+            (true, true) => Some(if area.extent.x as f32 > area.extent.y as f32 * 1.25 {
+                true
+            } else if area.extent.y as f32 > area.extent.x as f32 * 1.25 {
+                false
+            } else {
+                rng.gen_bool(0.5)
+            }),
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+
+        let Some(split_x) = split_x else {
+            let room = self.place_room(rng, area);
+            layout.fill_rect(room, DungeonCell::Floor);
+            layout.rooms.push(room);
+            return vec![room];
+        };
+
+        let (lhs, rhs) = if split_x {
+            let at = rng
+                .gen_range(self.min_partition_size.x..=area.extent.x - self.min_partition_size.x);
+            (
+                GridRect::new(area.origin, UVec2::new(at, area.extent.y)),
+                GridRect::new(
+                    area.origin + IVec2::new(at as i32, 0),
+                    UVec2::new(area.extent.x - at, area.extent.y),
+                ),
+            )
+        } else {
+            let at = rng
+                .gen_range(self.min_partition_size.y..=area.extent.y - self.min_partition_size.y);
+            (
+                GridRect::new(area.origin, UVec2::new(area.extent.x, at)),
+                GridRect::new(
+                    area.origin + IVec2::new(0, at as i32),
+                    UVec2::new(area.extent.x, area.extent.y - at),
+                ),
+            )
+        };
+
+        let mut lhs_rooms = self.partition(layout, rng, lhs, depth + 1);
+        let rhs_rooms = self.partition(layout, rng, rhs, depth + 1);
+
+        // Connect the closest pair of rooms between the two halves.
+        if let Some((from, to)) = lhs_rooms
+            .iter()
+            .flat_map(|l| rhs_rooms.iter().map(move |r| (center(l), center(r))))
+            .min_by_key(|(l, r)| (*l - *r).abs().element_sum())
+        {
+            layout.carve_corridor(from, to, split_x);
+        }
+
+        lhs_rooms.extend(rhs_rooms);
+        lhs_rooms
+    }
+
+    fn place_room(&self, rng: &mut StdRng, area: GridRect) -> GridRect {
+        // Leave a wall between rooms in adjacent partitions.
+        let available = area.extent.saturating_sub(UVec2::ONE).max(UVec2::ONE);
+        let min = self.min_room_size.min(available).max(UVec2::ONE);
+        let extent = UVec2::new(
+            rng.gen_range(min.x..=available.x),
+            rng.gen_range(min.y..=available.y),
+        );
+        let offset = UVec2::new(
+            rng.gen_range(0..=available.x - extent.x),
+            rng.gen_range(0..=available.y - extent.y),
+        );
+
+        GridRect::new(area.origin + offset.as_ivec2(), extent)
+    }
+}
+
+/// Generates natural looking caves using cellular automata.
+#[derive(Debug, Clone, Reflect)]
+pub struct CaveDungeon {
+    pub size: UVec2,
+    /// The chance of a cell being a wall initially.
+    pub fill_probability: f64,
+    pub iterations: u32,
+    /// A floor becomes a wall if it has at least this many walls around.
+    pub birth_limit: u32,
+    /// A wall stays a wall if it has at least this many walls around.
+    pub survival_limit: u32,
+    /// Remove the floors that are not reachable from the largest cave.
+    pub connected: bool,
+    pub seed: Option<u64>,
+}
+
+impl CaveDungeon {
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            fill_probability: 0.45,
+            iterations: 5,
+            birth_limit: 5,
+            survival_limit: 4,
+            connected: true,
+            seed: None,
+        }
+    }
+
+    pub fn with_fill_probability(mut self, probability: f64) -> Self {
+        self.fill_probability = probability;
+        self
+    }
+
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_limits(mut self, birth_limit: u32, survival_limit: u32) -> Self {
+        self.birth_limit = birth_limit;
+        self.survival_limit = survival_limit;
+        self
+    }
+
+    pub fn with_connected(mut self, connected: bool) -> Self {
+        self.connected = connected;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn generate(&self) -> DungeonLayout {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut layout = DungeonLayout::new(self.size);
+        let is_border = |index: IVec2| {
+            index.x == 0
+                || index.y == 0
+                || index.x == self.size.x as i32 - 1
+                || index.y == self.size.y as i32 - 1
+        };
+
+        layout.cells = (0..layout.cells.len())
+            .map(|_| {
+                if rng.gen_bool(self.fill_probability.clamp(0., 1.)) {
+                    DungeonCell::Wall
+                } else {
+                    DungeonCell::Floor
+                }
+            })
+            .collect();
+
+        for _ in 0..self.iterations {
+            layout.cells = layout
+                .iter()
+                .map(|(index, cell)| {
+                    if is_border(index) {
+                        return DungeonCell::Wall;
+                    }
+
+                    let walls = NEIGHBOURS_8
+                        .iter()
+                        .filter(|n| layout.is_wall(index + **n))
+                        .count() as u32;
+                    let limit = match cell {
+                        DungeonCell::Wall => self.survival_limit,
+                        DungeonCell::Floor => self.birth_limit,
+                    };
+
+                    if walls >= limit {
+                        DungeonCell::Wall
+                    } else {
+                        DungeonCell::Floor
+                    }
+                })
+                .collect();
+        }
+
+        layout.cells = layout
+            .iter()
+            .map(|(index, cell)| {
+                if is_border(index) {
+                    DungeonCell::Wall
+                } else {
+                    cell
+                }
+            })
+            .collect();
+
+        if self.connected {
+            layout.keep_largest_region();
+        }
+
+        layout
+    }
+}
+
+#[inline]
+fn center(rect: &GridRect) -> IVec2 {
+    (rect.origin + rect.dest) / 2
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2};
+
+    use super::{BspDungeon, CaveDungeon, DungeonCell, DungeonLayout};
+
+    fn floors_connected(layout: &DungeonLayout) -> bool {
+        let floors = layout
+            .iter()
+            .filter(|(_, c)| *c == DungeonCell::Floor)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let Some(start) = floors.first() else {
+            return true;
+        };
+
+        let mut visited = vec![*start];
+        let mut stack = vec![*start];
+        while let Some(cur) = stack.pop() {
+            [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                .into_iter()
+                .map(|d| cur + d)
+                .filter(|n| !layout.is_wall(*n))
+                .for_each(|n| {
+                    if !visited.contains(&n) {
+                        visited.push(n);
+                        stack.push(n);
+                    }
+                });
+        }
+
+        visited.len() == floors.len()
+    }
+
+    #[test]
+    fn test_bsp_dungeon() {
+        let layout = BspDungeon::new(UVec2::new(64, 48)).with_seed(7).generate();
+
+        assert!(layout.rooms().len() > 1);
+        assert!(floors_connected(&layout));
+        // The border is always wall.
+        assert!(
+            (0..64).all(|x| layout.is_wall(IVec2::new(x, 0)) && layout.is_wall(IVec2::new(x, 47)))
+        );
+    }
+
+    #[test]
+    fn test_cave_dungeon() {
+        let layout = CaveDungeon::new(UVec2::new(48, 48)).with_seed(3).generate();
+
+        assert!(layout.iter().any(|(_, c)| c == DungeonCell::Floor));
+        assert!(floors_connected(&layout));
+    }
+}
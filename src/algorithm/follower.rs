@@ -0,0 +1,182 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventReader, EventWriter},
+        system::{Commands, Query, Res},
+    },
+    math::{IVec2, Vec2},
+    reflect::Reflect,
+    time::Time,
+    transform::components::Transform,
+    utils::HashMap,
+};
+
+use crate::{
+    algorithm::pathfinding::{Path, PathBlocked, PathFinder, PathFindingQueue, PathTilemaps},
+    tilemap::{
+        coordinates,
+        map::{TilePivot, TilemapSlotSize, TilemapTransform, TilemapType},
+    },
+};
+
+/// Events sent by [`PathFollower`]s.
+#[derive(Event, Debug, Clone, Copy, Reflect)]
+pub enum PathFollowerEvent {
+    /// The follower reached a waypoint on its path.
+    WaypointReached(Entity, IVec2),
+    /// The follower reached the destination. The `Path` is removed.
+    Completed(Entity),
+    /// The next waypoint is not walkable anymore. The `Path` is removed.
+    Blocked(Entity, IVec2),
+}
+
+/// Moves the `Transform` of the entity along its [`Path`] at a constant speed.
+///
+/// Insert this along with the `Path`, or on the requester of a [`PathFinder`]
+/// so it starts moving once the path is found.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct PathFollower {
+    /// In world units per second.
+    pub speed: f32,
+    /// Added to the position of each waypoint. For example, use half of the slot size
+    /// to walk through the center of the tiles on square tilemaps.
+    ///
+    /// This is in the space of the tilemap, so it's scaled and rotated by the `TilemapTransform`.
+    pub offset: Vec2,
+    /// Request a new path to the same destination once any tile on the path is blocked.
+    /// See [`PathBlocked`].
+    pub repath_on_blocked: bool,
+    /// Used when requesting new paths.
+    pub allow_diagonal: bool,
+    pub(crate) last_reached: Option<IVec2>,
+}
+
+impl PathFollower {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            offset: Vec2::ZERO,
+            repath_on_blocked: false,
+            allow_diagonal: false,
+            last_reached: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_repath_on_blocked(mut self, allow_diagonal: bool) -> Self {
+        self.repath_on_blocked = true;
+        self.allow_diagonal = allow_diagonal;
+        self
+    }
+
+    /// The last waypoint this follower reached.
+    #[inline]
+    pub fn last_reached(&self) -> Option<IVec2> {
+        self.last_reached
+    }
+}
+
+pub fn path_follower(
+    mut commands: Commands,
+    mut followers_query: Query<(Entity, &mut PathFollower, &mut Path, &mut Transform)>,
+    tilemaps_query: Query<(
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+    )>,
+    mut queues_query: Query<&mut PathFindingQueue>,
+    path_tilemaps: Res<PathTilemaps>,
+    time: Res<Time>,
+    mut blocked_event: EventReader<PathBlocked>,
+    mut follower_event: EventWriter<PathFollowerEvent>,
+) {
+    let blocked_paths = blocked_event
+        .read()
+        .map(|ev| (ev.entity, ev.at))
+        .collect::<HashMap<_, _>>();
+
+    followers_query
+        .iter_mut()
+        .for_each(|(entity, mut follower, mut path, mut transform)| {
+            let Ok((ty, tilemap_transform, pivot, slot_size)) = tilemaps_query.get(path.tilemap())
+            else {
+                return;
+            };
+
+            // Don't wait until reaching the blocked tile if we are going to find another path.
+            let mut blocked = follower
+                .repath_on_blocked
+                .then(|| blocked_paths.get(&entity).copied())
+                .flatten();
+            let mut distance = follower.speed * time.delta_seconds();
+
+            while blocked.is_none() && !path.is_arrived() {
+                let target = path.cur_target();
+                let walkable = path_tilemaps
+                    .with(path.tilemap(), |t| t.get(target).is_some())
+                    .unwrap_or(true);
+                if !walkable {
+                    blocked = Some(target);
+                    break;
+                }
+
+                let waypoint = coordinates::index_to_world(
+                    target,
+                    *ty,
+                    tilemap_transform,
+                    pivot.0,
+                    slot_size.0,
+                ) + tilemap_transform.transform_vector(follower.offset);
+                let to_waypoint = waypoint - transform.translation.truncate();
+                let length = to_waypoint.length();
+
+                if length > distance {
+                    let delta = to_waypoint / length * distance;
+                    transform.translation += delta.extend(0.);
+                    return;
+                }
+
+                distance -= length;
+                transform.translation = waypoint.extend(transform.translation.z);
+                follower.last_reached = Some(target);
+                path.step();
+                follower_event.send(PathFollowerEvent::WaypointReached(entity, target));
+            }
+
+            commands.entity(entity).remove::<Path>();
+
+            let Some(at) = blocked else {
+                follower_event.send(PathFollowerEvent::Completed(entity));
+                return;
+            };
+
+            follower_event.send(PathFollowerEvent::Blocked(entity, at));
+            if !follower.repath_on_blocked {
+                return;
+            }
+
+            let finder = PathFinder::new(
+                follower.last_reached.unwrap_or(path.origin()),
+                path.dest(),
+                *ty,
+            )
+            .with_diagonal(follower.allow_diagonal);
+
+            match queues_query.get_mut(path.tilemap()) {
+                Ok(mut queue) => queue.schedule(entity, finder),
+                Err(_) => {
+                    commands
+                        .entity(path.tilemap())
+                        .insert(PathFindingQueue::new_with_schedules(
+                            [(entity, finder)].into_iter(),
+                        ));
+                }
+            }
+        });
+}
@@ -1,47 +1,84 @@
 use bevy::{
     app::App,
+    asset::AssetApp,
+    ecs::schedule::IntoSystemConfigs,
     prelude::{Plugin, Update},
 };
 
 use crate::algorithm::{
-    pathfinding::{Path, PathTilemaps},
-    wfc::{WfcData, WfcElement, WfcHistory, WfcSource},
+    dungeon::{BspDungeon, CaveDungeon, DungeonCell, DungeonLayout},
+    follower::{PathFollower, PathFollowerEvent},
+    pathfinding::{Path, PathAlgorithm, PathBlocked, PathHeuristic, PathResultOrder, PathTilemaps},
+    wfc::{WfcData, WfcElement, WfcHistory, WfcRules, WfcRulesLoader, WfcSource},
 };
 
+#[cfg(feature = "ldtk")]
+use crate::ldtk::resources::LdtkPatterns;
+#[cfg(feature = "ldtk")]
+use bevy::ecs::schedule::common_conditions::resource_exists;
+
+pub mod distance;
+pub mod dungeon;
+pub mod follower;
 pub mod pathfinding;
+pub mod reachable;
+pub mod search;
 pub mod wfc;
 
 pub struct EntiTilesAlgorithmPlugin;
 
 impl Plugin for EntiTilesAlgorithmPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<Path>();
+        app.register_type::<Path>()
+            .register_type::<PathResultOrder>()
+            .register_type::<PathHeuristic>()
+            .register_type::<PathAlgorithm>()
+            .register_type::<PathBlocked>()
+            .register_type::<PathFollower>()
+            .register_type::<PathFollowerEvent>();
+
+        app.register_type::<DungeonCell>()
+            .register_type::<DungeonLayout>()
+            .register_type::<BspDungeon>()
+            .register_type::<CaveDungeon>();
 
         app.register_type::<WfcElement>()
             .register_type::<WfcHistory>()
             .register_type::<WfcData>()
-            .register_type::<WfcSource>();
+            .register_type::<WfcSource>()
+            .register_type::<WfcRules>();
+
+        app.init_asset::<WfcRules>()
+            .init_asset_loader::<WfcRulesLoader>();
 
-        app.init_resource::<PathTilemaps>();
+        app.init_resource::<PathTilemaps>()
+            .add_event::<PathFollowerEvent>()
+            .add_event::<PathBlocked>();
 
         app.add_systems(
             Update,
             (
+                distance::distance_field_updater,
                 pathfinding::pathfinding_scheduler,
+                // Lockstep results depend on the paths scheduled in the same frame.
                 #[cfg(feature = "multi-threaded")]
-                pathfinding::path_assigner,
+                pathfinding::path_assigner.after(pathfinding::pathfinding_scheduler),
                 #[cfg(not(feature = "multi-threaded"))]
                 pathfinding::path_finding_single_threaded,
+                (pathfinding::path_blocked_detector, follower::path_follower).chain(),
                 #[cfg(feature = "multi-threaded")]
                 wfc::wave_function_collapse,
                 #[cfg(feature = "multi-threaded")]
                 wfc::wfc_data_assigner,
                 #[cfg(not(feature = "multi-threaded"))]
                 wfc::wave_function_collapse_single_threaded,
+                wfc::wfc_pattern_assets_resolver,
                 wfc::wfc_applier,
+                // Not available in headless apps.
                 #[cfg(feature = "ldtk")]
-                wfc::ldtk_wfc_helper,
+                wfc::ldtk_wfc_helper.run_if(resource_exists::<LdtkPatterns>),
             ),
-        );
+        )
+        .observe(pathfinding::path_tilemaps_unloader);
     }
 }
@@ -2,24 +2,39 @@ use std::{cmp::Ordering, collections::BinaryHeap};
 
 use bevy::{
     ecs::{
+        change_detection::DetectChangesMut,
         entity::EntityHashMap,
-        system::{Commands, Query, Res, Resource},
+        event::{Event, EventWriter},
+        observer::Trigger,
+        system::{Commands, Query, Res, ResMut, Resource},
     },
-    math::IVec2,
+    math::{IVec2, Vec2},
     prelude::{Component, Entity},
     reflect::Reflect,
     utils::{Entry, HashMap, HashSet},
 };
 
 use crate::{
-    math::ext::{ManhattanDistance, TileIndex},
-    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+    algorithm::search::{bidirectional_search, jump_point_search},
+    math::{
+        ext::{ManhattanDistance, TileIndex},
+        raycast::{hex_distance, TileLineMode},
+    },
+    tilemap::{
+        algorithm::path::PathTilemap,
+        coordinates,
+        despawn::EverythingUnloaded,
+        map::{TilemapTransform, TilemapType},
+    },
 };
 
 #[cfg(feature = "multi-threaded")]
 use bevy::tasks::{AsyncComputeTaskPool, Task};
 #[cfg(feature = "multi-threaded")]
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
 
 #[derive(Resource, Default)]
 pub struct PathTilemaps {
@@ -47,7 +62,8 @@ impl PathTilemaps {
     }
 
     #[inline]
-    pub fn insert(&mut self, tilemap: Entity, path_tilemap: PathTilemap) {
+    pub fn insert(&mut self, tilemap: Entity, mut path_tilemap: PathTilemap) {
+        path_tilemap.removed = Some(Vec::new());
         self.tilemaps
             .insert(tilemap, Arc::new(Mutex::new(path_tilemap)));
     }
@@ -56,6 +72,22 @@ impl PathTilemaps {
     pub fn remove(&mut self, tilemap: Entity) {
         self.tilemaps.remove(&tilemap);
     }
+
+    /// Access the path tilemap without caring about whether `multi-threaded` is enabled.
+    #[inline]
+    pub fn with<R>(&self, tilemap: Entity, f: impl FnOnce(&PathTilemap) -> R) -> Option<R> {
+        self.lock(tilemap).map(|t| f(&t))
+    }
+
+    /// Mutably access the path tilemap without caring about whether `multi-threaded` is enabled.
+    #[inline]
+    pub fn with_mut<R>(
+        &mut self,
+        tilemap: Entity,
+        f: impl FnOnce(&mut PathTilemap) -> R,
+    ) -> Option<R> {
+        self.lock(tilemap).map(|mut t| f(&mut t))
+    }
 }
 
 #[cfg(not(feature = "multi-threaded"))]
@@ -71,7 +103,8 @@ impl PathTilemaps {
     }
 
     #[inline]
-    pub fn insert(&mut self, tilemap: Entity, path_tilemap: PathTilemap) {
+    pub fn insert(&mut self, tilemap: Entity, mut path_tilemap: PathTilemap) {
+        path_tilemap.removed = Some(Vec::new());
         self.tilemaps.insert(tilemap, path_tilemap);
     }
 
@@ -79,6 +112,49 @@ impl PathTilemaps {
     pub fn remove(&mut self, tilemap: Entity) {
         self.tilemaps.remove(&tilemap);
     }
+
+    /// Access the path tilemap without caring about whether `multi-threaded` is enabled.
+    #[inline]
+    pub fn with<R>(&self, tilemap: Entity, f: impl FnOnce(&PathTilemap) -> R) -> Option<R> {
+        self.get(tilemap).map(f)
+    }
+
+    /// Mutably access the path tilemap without caring about whether `multi-threaded` is enabled.
+    #[inline]
+    pub fn with_mut<R>(
+        &mut self,
+        tilemap: Entity,
+        f: impl FnOnce(&mut PathTilemap) -> R,
+    ) -> Option<R> {
+        self.get_mut(tilemap).map(f)
+    }
+}
+
+impl PathTilemaps {
+    /// Take the tiles removed from each path tilemap since the last call.
+    pub(crate) fn take_removed(&mut self) -> Vec<(Entity, Vec<IVec2>)> {
+        let tilemaps = self.tilemaps.keys().copied().collect::<Vec<_>>();
+        tilemaps
+            .into_iter()
+            .filter_map(|tilemap| {
+                self.with_mut(tilemap, |t| t.removed.as_mut().map(std::mem::take))
+                    .flatten()
+                    .filter(|removed| !removed.is_empty())
+                    .map(|removed| (tilemap, removed))
+            })
+            .collect()
+    }
+}
+
+/// Sent when a tile on the remaining part of a [`Path`] is removed from the path tilemap,
+/// or covered by a collider of the [`PhysicsTilemap`](crate::tilemap::physics::PhysicsTilemap)
+/// on the same tilemap.
+#[derive(Event, Debug, Clone, Copy, Reflect)]
+pub struct PathBlocked {
+    /// The entity that owns the path.
+    pub entity: Entity,
+    /// The first blocked tile on the path.
+    pub at: IVec2,
 }
 
 #[derive(Component, Reflect)]
@@ -87,44 +163,216 @@ pub struct PathFinder {
     pub dest: IVec2,
     pub allow_diagonal: bool,
     pub max_steps: Option<u32>,
+    pub smoothing: bool,
+    pub heuristic: PathHeuristic,
+    pub heuristic_weight: f32,
+    pub algorithm: PathAlgorithm,
+    pub tilemap_ty: TilemapType,
     #[cfg(not(feature = "multi-threaded"))]
     pub max_steps_per_frame: u32,
-    #[cfg(not(feature = "multi-threaded"))]
-    pub tilemap_ty: TilemapType,
+}
+
+impl PathFinder {
+    /// Create a pathfinder that works under both single-threaded and multi-threaded mode.
+    /// `tilemap_ty` should be the [`TilemapType`] of the tilemap to find the path on.
+    ///
+    /// Use the `with_*` methods to tweak the rest of the settings.
+    pub fn new(origin: IVec2, dest: IVec2, tilemap_ty: TilemapType) -> Self {
+        Self {
+            origin,
+            dest,
+            tilemap_ty,
+            allow_diagonal: false,
+            max_steps: None,
+            smoothing: false,
+            heuristic: PathHeuristic::Auto,
+            heuristic_weight: 1.,
+            algorithm: PathAlgorithm::AStar,
+            #[cfg(not(feature = "multi-threaded"))]
+            max_steps_per_frame: 1000,
+        }
+    }
+
+    pub fn with_diagonal(mut self, allow_diagonal: bool) -> Self {
+        self.allow_diagonal = allow_diagonal;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: Option<u32>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Smooth the path using [`Path::smooth`] once it's found.
+    pub fn with_smoothing(mut self, smoothing: bool) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn with_heuristic(mut self, heuristic: PathHeuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// Multiply the heuristic by `weight`, which is known as weighted A*.
+    ///
+    /// Weights larger than 1 explore much less tiles on large maps,
+    /// but the path found might cost up to `weight` times as much as the best one.
+    pub fn with_heuristic_weight(mut self, weight: f32) -> Self {
+        self.heuristic_weight = weight;
+        self
+    }
+
+    pub fn with_algorithm(mut self, algorithm: PathAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// **Notice**: This only takes effect when `multi-threaded` is disabled.
+    #[allow(unused_mut, unused_variables)]
+    pub fn with_max_steps_per_frame(mut self, max_steps_per_frame: u32) -> Self {
+        #[cfg(not(feature = "multi-threaded"))]
+        {
+            self.max_steps_per_frame = max_steps_per_frame;
+        }
+        self
+    }
+}
+
+/// The algorithm used to find the path.
+///
+/// **Notice**: Hexagonal tilemaps always use `AStar`. Other algorithms find the whole path
+/// in one go, so `max_steps_per_frame` is ignored when `multi-threaded` is disabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum PathAlgorithm {
+    #[default]
+    AStar,
+    /// Jump point search, which is much faster on large open maps.
+    /// See [`jump_point_search`].
+    ///
+    /// **Notice**: Tile costs are ignored, so only use this when all the tiles cost the same.
+    JumpPoint,
+    /// Search from both ends at the same time. See [`bidirectional_search`].
+    Bidirectional,
+}
+
+/// The estimated cost from a tile to the destination.
+///
+/// **Notice**: Heuristics assume every tile costs at least 1.
+/// The path found might not be the best one if some tiles cost less.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum PathHeuristic {
+    /// Pick the one that matches the tilemap type and whether diagonal moves are allowed.
+    #[default]
+    Auto,
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+    /// The number of steps between two tiles on hexagonal tilemaps.
+    Hexagonal,
+}
+
+impl PathHeuristic {
+    pub fn distance(self, from: IVec2, to: IVec2, ty: TilemapType, allow_diagonal: bool) -> f32 {
+        let d = (to - from).abs();
+        match self {
+            PathHeuristic::Auto => match ty {
+                TilemapType::Hexagonal(_) => PathHeuristic::Hexagonal,
+                _ if allow_diagonal => PathHeuristic::Chebyshev,
+                _ => PathHeuristic::Manhattan,
+            }
+            .distance(from, to, ty, allow_diagonal),
+            PathHeuristic::Manhattan => from.manhattan_distance(to) as f32,
+            PathHeuristic::Chebyshev => d.max_element() as f32,
+            PathHeuristic::Euclidean => d.as_vec2().length(),
+            PathHeuristic::Hexagonal => hex_distance(from, to) as f32,
+        }
+    }
+}
+
+/// The order to apply the found paths in.
+///
+/// **Notice**: This only takes effect when `multi-threaded` is enabled,
+/// as paths are always found one by one in request order otherwise.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum PathResultOrder {
+    /// Apply the paths as soon as they are found.
+    #[default]
+    Unordered,
+    /// Apply the paths in the order they are requested.
+    /// Paths that are found earlier are buffered until all the previous ones are found.
+    RequestOrder,
+    /// Apply exactly this number of paths every frame in request order,
+    /// and wait for the tasks if they are not finished yet.
+    ///
+    /// This makes the results the same on every machine, which is required by lockstep games.
+    Lockstep(usize),
 }
 
 #[derive(Component)]
 pub struct PathFindingQueue {
-    pub(crate) finders: EntityHashMap<PathFinder>,
+    /// The pathfinders and their request numbers.
+    pub(crate) finders: EntityHashMap<(u64, PathFinder)>,
+    pub(crate) next_request: u64,
+    pub(crate) result_order: PathResultOrder,
+    #[cfg(feature = "multi-threaded")]
+    pub(crate) tasks: EntityHashMap<(u64, Task<Option<Path>>)>,
+    /// Paths that are found but not applied yet, keyed by request numbers.
     #[cfg(feature = "multi-threaded")]
-    pub(crate) tasks: EntityHashMap<Task<Path>>,
+    pub(crate) completed: BTreeMap<u64, (Entity, Option<Path>)>,
 }
 
 impl PathFindingQueue {
     pub fn new_with_schedules(schedules: impl Iterator<Item = (Entity, PathFinder)>) -> Self {
-        PathFindingQueue {
-            finders: schedules.collect(),
+        let mut queue = PathFindingQueue {
+            finders: EntityHashMap::default(),
+            next_request: 0,
+            result_order: PathResultOrder::default(),
             #[cfg(feature = "multi-threaded")]
             tasks: EntityHashMap::default(),
-        }
+            #[cfg(feature = "multi-threaded")]
+            completed: BTreeMap::default(),
+        };
+        schedules.for_each(|(requester, finder)| queue.schedule(requester, finder));
+        queue
     }
 
-    #[cfg(feature = "multi-threaded")]
+    /// Set the order to apply the found paths in. See [`PathResultOrder`].
+    pub fn with_result_order(mut self, result_order: PathResultOrder) -> Self {
+        self.result_order = result_order;
+        self
+    }
+
+    /// Returns true if there's neither scheduled pathfinders nor running tasks.
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.tasks.is_empty()
+        #[cfg(feature = "multi-threaded")]
+        return self.finders.is_empty() && self.tasks.is_empty() && self.completed.is_empty();
+        #[cfg(not(feature = "multi-threaded"))]
+        return self.finders.is_empty();
     }
 
+    /// Find a path for `requester`. The [`Path`] is inserted to it once found,
+    /// and nothing is inserted if the destination can't be reached.
     #[inline]
     pub fn schedule(&mut self, requester: Entity, pathfinder: PathFinder) {
-        self.finders.insert(requester, pathfinder);
+        self.finders
+            .insert(requester, (self.next_request, pathfinder));
+        self.next_request += 1;
+    }
+
+    #[inline]
+    pub fn result_order(&self) -> PathResultOrder {
+        self.result_order
     }
 }
 
+/// The targets from the origin to the destination. The origin itself is not included.
 #[derive(Component, Clone, Reflect)]
 pub struct Path {
     path: Vec<IVec2>,
     current_step: usize,
+    origin: IVec2,
     tilemap: Entity,
 }
 
@@ -151,9 +399,102 @@ impl Path {
         self.tilemap
     }
 
+    /// Where the path starts. It's not included in the targets.
+    #[inline]
+    pub fn origin(&self) -> IVec2 {
+        self.origin
+    }
+
+    /// The last target, or the origin if the path is empty.
+    #[inline]
+    pub fn dest(&self) -> IVec2 {
+        self.path.last().copied().unwrap_or(self.origin)
+    }
+
     pub fn iter(&self) -> std::slice::Iter<IVec2> {
         self.path.iter()
     }
+
+    /// Iterate over the remaining targets in world space.
+    pub fn world_waypoints(
+        &self,
+        ty: TilemapType,
+        transform: &TilemapTransform,
+        pivot: Vec2,
+        slot_size: Vec2,
+    ) -> impl Iterator<Item = Vec2> + '_ {
+        let transform = *transform;
+        self.path[self.current_step.min(self.path.len())..]
+            .iter()
+            .map(move |index| coordinates::index_to_world(*index, ty, &transform, pivot, slot_size))
+    }
+
+    /// Remove the remaining targets that can be skipped by walking straight,
+    /// so agents won't zig-zag between tiles.
+    ///
+    /// A target is skipped if the next kept one is in sight, see [`PathTilemap::line_of_sight`].
+    /// Costs are ignored, and lines passing exactly through a corner require both tiles next to it.
+    pub fn smooth(&mut self, path_tilemap: &PathTilemap, ty: TilemapType) {
+        let start = self.current_step.min(self.path.len());
+        let remaining = &self.path[start..];
+        if remaining.len() < 3 {
+            return;
+        }
+
+        let mut smoothed = vec![remaining[0]];
+        let mut anchor = 0;
+        while anchor < remaining.len() - 1 {
+            let mut next = anchor + 1;
+            while next + 1 < remaining.len()
+                && path_tilemap.line_of_sight(
+                    remaining[anchor],
+                    remaining[next + 1],
+                    ty,
+                    TileLineMode::Supercover,
+                )
+            {
+                next += 1;
+            }
+
+            smoothed.push(remaining[next]);
+            anchor = next;
+        }
+
+        self.path.truncate(start);
+        self.path.extend(smoothed);
+    }
+}
+
+pub fn path_tilemaps_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut path_tilemaps: ResMut<PathTilemaps>,
+) {
+    path_tilemaps.tilemaps.clear();
+}
+
+pub fn path_blocked_detector(
+    mut path_tilemaps: ResMut<PathTilemaps>,
+    paths_query: Query<(Entity, &Path)>,
+    mut blocked_event: EventWriter<PathBlocked>,
+) {
+    // Avoid triggering change detection every frame.
+    let removed = path_tilemaps.bypass_change_detection().take_removed();
+    if removed.is_empty() {
+        return;
+    }
+
+    paths_query.iter().for_each(|(entity, path)| {
+        let Some((_, removed)) = removed.iter().find(|(t, _)| *t == path.tilemap) else {
+            return;
+        };
+
+        if let Some(at) = path.path[path.current_step.min(path.path.len())..]
+            .iter()
+            .find(|index| removed.contains(index))
+        {
+            blocked_event.send(PathBlocked { entity, at: *at });
+        }
+    });
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -174,26 +515,26 @@ impl PartialOrd for PathNode {
 impl Ord for PathNode {
     fn cmp(&self, other: &Self) -> Ordering {
         other
-            .g_cost
-            .cmp(&self.g_cost)
+            .weight()
+            .cmp(&self.weight())
             .then(other.h_cost.cmp(&self.h_cost))
     }
 }
 
 impl PathNode {
-    pub fn new(index: IVec2, g_cost: u32, dest: IVec2, cost_to_pass: u32) -> Self {
+    pub fn new(index: IVec2, g_cost: u32, h_cost: u32, cost_to_pass: u32) -> Self {
         PathNode {
             index,
             parent: None,
             g_cost,
-            h_cost: dest.manhattan_distance(index),
+            h_cost,
             cost_to_pass,
         }
     }
 
     #[inline]
     pub fn weight(&self) -> u32 {
-        self.g_cost + self.h_cost
+        self.g_cost.saturating_add(self.h_cost)
     }
 }
 
@@ -210,6 +551,10 @@ pub struct PathGrid {
     pub all_nodes: HashMap<IVec2, PathNode>,
     pub steps: u32,
     pub max_steps: Option<u32>,
+    pub smoothing: bool,
+    pub heuristic: PathHeuristic,
+    pub heuristic_weight: f32,
+    pub algorithm: PathAlgorithm,
     #[cfg(feature = "multi-threaded")]
     pub path_tilemap: Arc<Mutex<PathTilemap>>,
     #[cfg(not(feature = "multi-threaded"))]
@@ -223,14 +568,13 @@ impl PathGrid {
         finder: PathFinder,
         requester: Entity,
         tilemap: Entity,
-        tilemap_ty: TilemapType,
         #[cfg(feature = "multi-threaded")] path_tilemap: Arc<Mutex<PathTilemap>>,
     ) -> Self {
         PathGrid {
             requester,
             tilemap,
             allow_diagonal: finder.allow_diagonal,
-            tilemap_ty,
+            tilemap_ty: finder.tilemap_ty,
             origin: finder.origin,
             dest: finder.dest,
             to_explore: BinaryHeap::new(),
@@ -238,6 +582,10 @@ impl PathGrid {
             all_nodes: HashMap::new(),
             steps: 0,
             max_steps: finder.max_steps,
+            smoothing: finder.smoothing,
+            heuristic: finder.heuristic,
+            heuristic_weight: finder.heuristic_weight,
+            algorithm: finder.algorithm,
             #[cfg(feature = "multi-threaded")]
             path_tilemap,
             #[cfg(not(feature = "multi-threaded"))]
@@ -247,13 +595,22 @@ impl PathGrid {
         }
     }
 
+    /// The weighted heuristic from `index` to the destination.
+    pub fn h_cost(&self, index: IVec2) -> u32 {
+        let distance =
+            self.heuristic
+                .distance(index, self.dest, self.tilemap_ty, self.allow_diagonal);
+        (distance * self.heuristic_weight).floor() as u32
+    }
+
     #[cfg(feature = "multi-threaded")]
     pub fn get_or_register(&mut self, index: IVec2) -> Option<PathNode> {
         if let Some(node) = self.all_nodes.get(&index) {
             Some(node.clone())
         } else {
+            let h_cost = self.h_cost(index);
             self.path_tilemap.lock().unwrap().get(index).map(|tile| {
-                let new = PathNode::new(index, u32::MAX, self.dest, tile.cost);
+                let new = PathNode::new(index, u32::MAX, h_cost, tile.cost);
                 self.all_nodes.insert(index, new);
                 new
             })
@@ -269,12 +626,13 @@ impl PathGrid {
         if let Some(node) = self.all_nodes.get(&index) {
             Some(*node)
         } else {
+            let h_cost = self.h_cost(index);
             path_tilemaps
                 .get(self.tilemap)
                 .unwrap()
                 .get(index)
                 .map(|tile| {
-                    let new = PathNode::new(index, u32::MAX, self.dest, tile.cost);
+                    let new = PathNode::new(index, u32::MAX, h_cost, tile.cost);
                     self.all_nodes.insert(index, new);
                     new
                 })
@@ -301,7 +659,27 @@ impl PathGrid {
 
     #[allow(unused)]
     pub fn find_path(&mut self, path_tilemaps: Option<&PathTilemaps>) {
-        let origin = PathNode::new(self.origin, 0, self.dest, 0);
+        if self.algorithm != PathAlgorithm::AStar
+            && !matches!(self.tilemap_ty, TilemapType::Hexagonal(_))
+        {
+            #[cfg(feature = "multi-threaded")]
+            let path_tilemap = self.path_tilemap.clone();
+            #[cfg(feature = "multi-threaded")]
+            let path = self.search(&path_tilemap.lock().unwrap());
+            #[cfg(not(feature = "multi-threaded"))]
+            let path = self.search(path_tilemaps.unwrap().get(self.tilemap).unwrap());
+
+            if let Some(path) = path {
+                self.register_path(&path);
+            }
+            #[cfg(not(feature = "multi-threaded"))]
+            {
+                self.is_done = true;
+            }
+            return;
+        }
+
+        let origin = PathNode::new(self.origin, 0, self.h_cost(self.origin), 0);
         self.to_explore.push(origin.clone());
         self.all_nodes.insert(self.origin, origin);
 
@@ -362,60 +740,110 @@ impl PathGrid {
         }
     }
 
-    pub fn collect_path(&self) -> Path {
+    fn search(&self, path_tilemap: &PathTilemap) -> Option<Vec<IVec2>> {
+        match self.algorithm {
+            PathAlgorithm::AStar => None,
+            PathAlgorithm::JumpPoint => jump_point_search(
+                path_tilemap,
+                self.origin,
+                self.dest,
+                self.allow_diagonal,
+                self.max_steps,
+            ),
+            PathAlgorithm::Bidirectional => bidirectional_search(
+                path_tilemap,
+                self.origin,
+                self.dest,
+                self.allow_diagonal,
+                self.max_steps,
+                |from, to| {
+                    let distance =
+                        self.heuristic
+                            .distance(from, to, self.tilemap_ty, self.allow_diagonal);
+                    (distance * self.heuristic_weight).floor() as u32
+                },
+            ),
+        }
+    }
+
+    /// Register the nodes along `path`, so it can be collected like the ones found by A*.
+    fn register_path(&mut self, path: &[IVec2]) {
+        let mut parent = None;
+        for (g_cost, index) in path.iter().enumerate() {
+            let mut node = PathNode::new(*index, g_cost as u32, 0, 0);
+            node.parent = parent;
+            self.all_nodes.insert(*index, node);
+            parent = Some(*index);
+        }
+    }
+
+    /// Collect the path, or `None` if the destination is not reached.
+    pub fn collect_path(&self) -> Option<Path> {
         let mut path = Path {
             path: vec![],
             current_step: 0,
+            origin: self.origin,
             tilemap: self.tilemap,
         };
-        let mut current = self.all_nodes.get(&self.dest).unwrap();
+        let mut current = self.all_nodes.get(&self.dest)?;
         while current.index != self.origin {
             path.path.push(current.index);
-            current = self.all_nodes.get(&current.parent.unwrap()).unwrap();
+            current = self.all_nodes.get(&current.parent?)?;
+        }
+        path.path.reverse();
+        Some(path)
+    }
+
+    /// Collect the path, and smooth it if required.
+    pub fn collect_smoothed_path(&self, path_tilemap: &PathTilemap) -> Option<Path> {
+        let mut path = self.collect_path()?;
+        if self.smoothing {
+            path.smooth(path_tilemap, self.tilemap_ty);
         }
-        path
+        Some(path)
     }
 }
 
 #[cfg(feature = "multi-threaded")]
 pub fn pathfinding_scheduler(
-    mut queues_query: Query<(Entity, &TilemapType, &mut PathFindingQueue)>,
+    mut queues_query: Query<(Entity, &mut PathFindingQueue)>,
     path_tilemaps: Res<PathTilemaps>,
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
-    queues_query
-        .iter_mut()
-        .for_each(|(tilemap, ty, mut queue)| {
-            let mut tasks = Vec::new();
-            let path_tilemap = path_tilemaps.get(tilemap).unwrap();
-            queue.finders.drain().for_each(|(requester, finder)| {
-                let ty = *ty;
+    queues_query.iter_mut().for_each(|(tilemap, mut queue)| {
+        let mut tasks = Vec::new();
+        let path_tilemap = path_tilemaps.get(tilemap).unwrap();
+        queue
+            .finders
+            .drain()
+            .for_each(|(requester, (request, finder))| {
                 let path_tilemap = path_tilemap.clone();
                 let task = thread_pool.spawn(async move {
-                    let mut grid = PathGrid::new(finder, requester, tilemap, ty, path_tilemap);
+                    let mut grid = PathGrid::new(finder, requester, tilemap, path_tilemap);
                     grid.find_path(None);
-                    grid.collect_path()
+                    let path_tilemap = grid.path_tilemap.lock().unwrap();
+                    grid.collect_smoothed_path(&path_tilemap)
                 });
-                tasks.push((requester, task));
+                tasks.push((requester, (request, task)));
             });
-            queue.tasks.extend(tasks);
-        });
+        queue.tasks.extend(tasks);
+    });
 }
 
 #[cfg(not(feature = "multi-threaded"))]
 pub fn pathfinding_scheduler(
     mut commands: Commands,
-    mut queues_query: Query<(Entity, &TilemapType, &mut PathFindingQueue)>,
+    mut queues_query: Query<(Entity, &mut PathFindingQueue)>,
 ) {
-    queues_query
-        .iter_mut()
-        .for_each(|(tilemap, ty, mut queue)| {
-            queue.finders.drain().for_each(|(requester, finder)| {
-                commands
-                    .entity(requester)
-                    .insert(PathGrid::new(finder, requester, tilemap, *ty));
-            });
+    queues_query.iter_mut().for_each(|(tilemap, mut queue)| {
+        let mut finders = queue.finders.drain().collect::<Vec<_>>();
+        finders.sort_unstable_by_key(|(_, (request, _))| *request);
+        finders.into_iter().for_each(|(requester, (_, finder))| {
+            commands
+                .entity(requester)
+                .insert(PathGrid::new(finder, requester, tilemap));
         });
+    });
 }
 
 #[cfg(not(feature = "multi-threaded"))]
@@ -430,7 +858,11 @@ pub fn path_finding_single_threaded(
 
     cur_task.find_path(Some(&path_tilemaps));
     if cur_task.is_done {
-        commands.entity(requester).insert(cur_task.collect_path());
+        if let Some(path) =
+            cur_task.collect_smoothed_path(path_tilemaps.get(cur_task.tilemap).unwrap())
+        {
+            commands.entity(requester).insert(path);
+        }
         commands.entity(requester).remove::<PathGrid>();
     }
 }
@@ -438,15 +870,240 @@ pub fn path_finding_single_threaded(
 #[cfg(feature = "multi-threaded")]
 pub fn path_assigner(mut commands: Commands, mut queues_query: Query<&mut PathFindingQueue>) {
     queues_query.iter_mut().for_each(|mut queue| {
-        let mut completed = Vec::new();
-        queue.tasks.iter_mut().for_each(|(requester, task)| {
-            if let Some(path) = bevy::tasks::block_on(futures_lite::future::poll_once(task)) {
-                commands.entity(*requester).insert(path);
-                completed.push(*requester);
-            }
-        });
-        completed.iter().for_each(|requester| {
+        let queue = &mut *queue;
+
+        if let PathResultOrder::Lockstep(paths_per_frame) = queue.result_order {
+            let mut requests = queue
+                .tasks
+                .iter()
+                .map(|(requester, (request, _))| (*request, *requester))
+                .collect::<Vec<_>>();
+            requests.sort_unstable();
+            requests
+                .into_iter()
+                .take(paths_per_frame)
+                .for_each(|(_, requester)| {
+                    let (_, task) = queue.tasks.remove(&requester).unwrap();
+                    if let Some(path) = bevy::tasks::block_on(task) {
+                        commands.entity(requester).insert(path);
+                    }
+                });
+            return;
+        }
+
+        let mut finished = Vec::new();
+        queue
+            .tasks
+            .iter_mut()
+            .for_each(|(requester, (request, task))| {
+                if let Some(path) = bevy::tasks::block_on(futures_lite::future::poll_once(task)) {
+                    queue.completed.insert(*request, (*requester, path));
+                    finished.push(*requester);
+                }
+            });
+        finished.iter().for_each(|requester| {
             queue.tasks.remove(requester);
         });
+
+        // Paths can only be applied when all the previous requests are done.
+        let oldest_pending = match queue.result_order {
+            PathResultOrder::RequestOrder => queue
+                .tasks
+                .values()
+                .map(|(request, _)| *request)
+                .chain(queue.finders.values().map(|(request, _)| *request))
+                .min(),
+            _ => None,
+        };
+
+        while let Some(entry) = queue.completed.first_entry() {
+            if oldest_pending.is_some_and(|oldest| oldest < *entry.key()) {
+                break;
+            }
+            if let (requester, Some(path)) = entry.remove() {
+                commands.entity(requester).insert(path);
+            }
+        }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::entity::Entity,
+        math::{IVec2, UVec2},
+    };
+
+    use crate::{
+        math::{raycast::TileLineMode, GridRect},
+        tilemap::{
+            algorithm::path::{PathTile, PathTilemap},
+            map::TilemapType,
+        },
+    };
+
+    use super::{Path, PathAlgorithm, PathFinder, PathGrid, PathHeuristic, PathTilemaps};
+
+    fn find_path(finder: PathFinder, path_tilemap: PathTilemap) -> Option<Path> {
+        let mut path_tilemaps = PathTilemaps::default();
+        path_tilemaps.insert(Entity::PLACEHOLDER, path_tilemap);
+
+        #[cfg(feature = "multi-threaded")]
+        let mut grid = PathGrid::new(
+            finder,
+            Entity::PLACEHOLDER,
+            Entity::PLACEHOLDER,
+            path_tilemaps.get(Entity::PLACEHOLDER).unwrap(),
+        );
+        #[cfg(not(feature = "multi-threaded"))]
+        let mut grid = PathGrid::new(
+            finder.with_max_steps_per_frame(u32::MAX),
+            Entity::PLACEHOLDER,
+            Entity::PLACEHOLDER,
+        );
+
+        grid.find_path(Some(&path_tilemaps));
+        grid.collect_path()
+    }
+
+    #[test]
+    fn test_take_removed() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            GridRect::new(IVec2::ZERO, UVec2::splat(4)),
+            PathTile { cost: 1 },
+        );
+        // Not tracked until inserted.
+        path_tilemap.remove(IVec2::ZERO);
+        assert!(path_tilemap.removed.is_none());
+
+        let mut path_tilemaps = PathTilemaps::default();
+        path_tilemaps.insert(Entity::PLACEHOLDER, path_tilemap);
+        path_tilemaps.with_mut(Entity::PLACEHOLDER, |t| {
+            t.remove(IVec2::ONE);
+            // Not a tile, so nothing is removed.
+            t.remove(IVec2::splat(8));
+        });
+
+        assert_eq!(
+            path_tilemaps.take_removed(),
+            vec![(Entity::PLACEHOLDER, vec![IVec2::ONE])]
+        );
+        assert!(path_tilemaps.take_removed().is_empty());
+    }
+
+    #[test]
+    fn test_path_order() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            GridRect::new(IVec2::ZERO, UVec2::new(6, 1)),
+            PathTile { cost: 1 },
+        );
+
+        for algorithm in [
+            PathAlgorithm::AStar,
+            PathAlgorithm::JumpPoint,
+            PathAlgorithm::Bidirectional,
+        ] {
+            let path = find_path(
+                PathFinder::new(IVec2::ZERO, IVec2::new(5, 0), TilemapType::Square)
+                    .with_algorithm(algorithm),
+                path_tilemap.clone(),
+            )
+            .unwrap();
+            // From the start to the goal, and the origin is excluded.
+            assert_eq!(
+                path.iter().copied().collect::<Vec<_>>(),
+                (1..=5).map(|x| IVec2::new(x, 0)).collect::<Vec<_>>(),
+            );
+            assert_eq!(path.cur_target(), IVec2::X);
+            assert_eq!(path.dest(), IVec2::new(5, 0));
+        }
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            GridRect::new(IVec2::ZERO, UVec2::new(6, 1)),
+            PathTile { cost: 1 },
+        );
+        path_tilemap.remove(IVec2::new(3, 0));
+
+        for algorithm in [
+            PathAlgorithm::AStar,
+            PathAlgorithm::JumpPoint,
+            PathAlgorithm::Bidirectional,
+        ] {
+            let finder = PathFinder::new(IVec2::ZERO, IVec2::new(5, 0), TilemapType::Square)
+                .with_algorithm(algorithm);
+            assert!(find_path(finder, path_tilemap.clone()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_heuristics() {
+        let (from, to) = (IVec2::ZERO, IVec2::new(3, 4));
+        let square = TilemapType::Square;
+
+        assert_eq!(
+            PathHeuristic::Manhattan.distance(from, to, square, false),
+            7.
+        );
+        assert_eq!(
+            PathHeuristic::Chebyshev.distance(from, to, square, false),
+            4.
+        );
+        assert_eq!(
+            PathHeuristic::Euclidean.distance(from, to, square, false),
+            5.
+        );
+        assert_eq!(PathHeuristic::Auto.distance(from, to, square, false), 7.);
+        assert_eq!(PathHeuristic::Auto.distance(from, to, square, true), 4.);
+        assert_eq!(
+            PathHeuristic::Auto.distance(from, IVec2::new(3, -1), TilemapType::Hexagonal(8), false),
+            4.
+        );
+    }
+
+    #[test]
+    fn test_path_smoothing() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            GridRect::new(IVec2::ZERO, UVec2::splat(5)),
+            PathTile { cost: 1 },
+        );
+        path_tilemap.remove(IVec2::new(2, 2));
+
+        let line_of_sight = |from, to| {
+            path_tilemap.line_of_sight(from, to, TilemapType::Square, TileLineMode::Supercover)
+        };
+        assert!(line_of_sight(IVec2::ZERO, IVec2::new(4, 1)));
+        assert!(!line_of_sight(IVec2::ZERO, IVec2::splat(4)));
+        assert!(!line_of_sight(IVec2::new(2, 0), IVec2::new(2, 4)));
+
+        // A staircase along the bottom and right edge.
+        let mut path = Path {
+            path: vec![
+                IVec2::new(0, 0),
+                IVec2::new(1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(3, 0),
+                IVec2::new(4, 0),
+                IVec2::new(4, 1),
+                IVec2::new(4, 2),
+                IVec2::new(4, 3),
+                IVec2::new(4, 4),
+            ],
+            current_step: 0,
+            origin: IVec2::ZERO,
+            tilemap: Entity::PLACEHOLDER,
+        };
+        path.smooth(&path_tilemap, TilemapType::Square);
+
+        assert_eq!(path.path.first(), Some(&IVec2::ZERO));
+        assert_eq!(path.path.last(), Some(&IVec2::splat(4)));
+        assert!(path.path.len() < 9);
+        assert!(path.path.windows(2).all(|w| line_of_sight(w[0], w[1])));
+    }
+}
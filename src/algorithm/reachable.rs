@@ -0,0 +1,220 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    math::IVec2,
+    prelude::Entity,
+    reflect::Reflect,
+    utils::{Entry, HashMap},
+};
+
+use crate::{
+    algorithm::pathfinding::PathTilemaps,
+    math::ext::TileIndex,
+    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+};
+
+#[cfg(feature = "multi-threaded")]
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+
+/// The tiles that can be reached from `origin` within some cost,
+/// along with the cumulative cost to reach each of them. Also known as a dijkstra map.
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct ReachableArea {
+    pub(crate) origin: IVec2,
+    pub(crate) max_cost: u32,
+    /// The cumulative cost and the previous tile.
+    pub(crate) tiles: HashMap<IVec2, (u32, IVec2)>,
+}
+
+impl ReachableArea {
+    #[inline]
+    pub fn origin(&self) -> IVec2 {
+        self.origin
+    }
+
+    #[inline]
+    pub fn max_cost(&self) -> u32 {
+        self.max_cost
+    }
+
+    /// The cumulative cost to reach `index`. Returns `None` if it's not reachable.
+    #[inline]
+    pub fn cost(&self, index: IVec2) -> Option<u32> {
+        self.tiles.get(&index).map(|(cost, _)| *cost)
+    }
+
+    #[inline]
+    pub fn contains(&self, index: IVec2) -> bool {
+        self.tiles.contains_key(&index)
+    }
+
+    /// The number of reachable tiles, including the origin.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Iterate over the reachable tiles and their cumulative costs.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, u32)> + '_ {
+        self.tiles.iter().map(|(index, (cost, _))| (*index, *cost))
+    }
+
+    /// The cheapest path from the origin to `dest`, both included.
+    pub fn path_to(&self, dest: IVec2) -> Option<Vec<IVec2>> {
+        let mut cur = dest;
+        let mut path = vec![dest];
+        while cur != self.origin {
+            cur = self.tiles.get(&cur)?.1;
+            path.push(cur);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Find all the tiles that can be reached from `origin` with a cumulative cost
+/// no more than `max_cost`.
+///
+/// The cost of a step is the cost of the tile stepped onto, the same as pathfinding.
+pub fn reachable_area(
+    path_tilemap: &PathTilemap,
+    ty: TilemapType,
+    origin: IVec2,
+    max_cost: u32,
+    allow_diagonal: bool,
+) -> ReachableArea {
+    let mut area = ReachableArea {
+        origin,
+        max_cost,
+        tiles: HashMap::default(),
+    };
+    let mut to_explore = BinaryHeap::new();
+
+    area.tiles.insert(origin, (0, origin));
+    to_explore.push(Reverse((0, origin.x, origin.y)));
+
+    while let Some(Reverse((cost, x, y))) = to_explore.pop() {
+        let current = IVec2::new(x, y);
+        if area.tiles[&current].0 < cost {
+            continue;
+        }
+
+        current
+            .neighbours(ty, allow_diagonal)
+            .into_iter()
+            .flatten()
+            .for_each(|neighbour| {
+                let Some(tile) = path_tilemap.get(neighbour) else {
+                    return;
+                };
+                let new_cost = cost.saturating_add(tile.cost);
+                if new_cost > max_cost {
+                    return;
+                }
+
+                match area.tiles.entry(neighbour) {
+                    Entry::Occupied(mut e) => {
+                        if e.get().0 <= new_cost {
+                            return;
+                        }
+                        e.insert((new_cost, current));
+                    }
+                    Entry::Vacant(e) => {
+                        e.insert((new_cost, current));
+                    }
+                }
+                to_explore.push(Reverse((new_cost, neighbour.x, neighbour.y)));
+            });
+    }
+
+    area
+}
+
+impl PathTilemaps {
+    /// Find the reachable area on `tilemap` synchronously. See [`reachable_area`].
+    ///
+    /// Returns `None` if `tilemap` doesn't have a path tilemap.
+    /// Use `reachable_area_task` for large areas if `multi-threaded` is enabled.
+    pub fn reachable_area(
+        &self,
+        tilemap: Entity,
+        ty: TilemapType,
+        origin: IVec2,
+        max_cost: u32,
+        allow_diagonal: bool,
+    ) -> Option<ReachableArea> {
+        self.with(tilemap, |path_tilemap| {
+            reachable_area(path_tilemap, ty, origin, max_cost, allow_diagonal)
+        })
+    }
+
+    /// Find the reachable area on `tilemap` in the async compute task pool.
+    ///
+    /// Returns `None` if `tilemap` doesn't have a path tilemap.
+    #[cfg(feature = "multi-threaded")]
+    pub fn reachable_area_task(
+        &self,
+        tilemap: Entity,
+        ty: TilemapType,
+        origin: IVec2,
+        max_cost: u32,
+        allow_diagonal: bool,
+    ) -> Option<Task<ReachableArea>> {
+        let path_tilemap = self.get(tilemap)?;
+        Some(AsyncComputeTaskPool::get().spawn(async move {
+            reachable_area(
+                &path_tilemap.lock().unwrap(),
+                ty,
+                origin,
+                max_cost,
+                allow_diagonal,
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2};
+
+    use crate::{
+        math::GridRect,
+        tilemap::{
+            algorithm::path::{PathTile, PathTilemap},
+            map::TilemapType,
+        },
+    };
+
+    use super::reachable_area;
+
+    #[test]
+    fn test_reachable_area() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            GridRect::new(IVec2::ZERO, UVec2::splat(5)),
+            PathTile { cost: 1 },
+        );
+        path_tilemap.set(IVec2::new(1, 0), PathTile { cost: 3 });
+        path_tilemap.remove(IVec2::new(0, 1));
+
+        let area = reachable_area(&path_tilemap, TilemapType::Square, IVec2::ZERO, 3, false);
+        assert_eq!(area.cost(IVec2::ZERO), Some(0));
+        assert_eq!(area.cost(IVec2::new(1, 0)), Some(3));
+        assert!(!area.contains(IVec2::new(0, 1)));
+        assert!(!area.contains(IVec2::new(2, 0)));
+        assert_eq!(
+            area.path_to(IVec2::new(1, 0)),
+            Some(vec![IVec2::ZERO, IVec2::new(1, 0)])
+        );
+
+        let area = reachable_area(&path_tilemap, TilemapType::Square, IVec2::ZERO, 5, false);
+        assert_eq!(area.cost(IVec2::new(1, 1)), Some(4));
+        assert!(!area.contains(IVec2::new(0, 2)));
+        assert_eq!(area.path_to(IVec2::new(1, 1)).map(|p| p.len()), Some(3));
+    }
+}
@@ -0,0 +1,401 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{math::IVec2, utils::HashMap};
+
+use crate::{
+    math::ext::TileIndex,
+    tilemap::{algorithm::path::PathTilemap, map::TilemapType},
+};
+
+/// Find a path using jump point search. Returns the path from `origin` to `dest`, both included.
+///
+/// Instead of visiting every tile, only the tiles where the path might turn are visited,
+/// which makes it much faster than A* on large open maps.
+///
+/// **Notice**: Tile costs are ignored, and only square and isometric tilemaps are supported.
+pub fn jump_point_search(
+    path_tilemap: &PathTilemap,
+    origin: IVec2,
+    dest: IVec2,
+    allow_diagonal: bool,
+    max_steps: Option<u32>,
+) -> Option<Vec<IVec2>> {
+    let walkable = |index: IVec2| path_tilemap.get(index).is_some();
+    if !walkable(dest) {
+        return None;
+    }
+
+    let distance = |from: IVec2, to: IVec2| {
+        let d = (to - from).abs();
+        if allow_diagonal {
+            d.max_element() as u32
+        } else {
+            (d.x + d.y) as u32
+        }
+    };
+
+    // The cost and the previous jump point.
+    let mut nodes = HashMap::<IVec2, (u32, IVec2)>::default();
+    let mut to_explore = BinaryHeap::new();
+    let mut steps = 0;
+    nodes.insert(origin, (0, origin));
+    to_explore.push(Reverse((distance(origin, dest), 0, origin.x, origin.y)));
+
+    while let Some(Reverse((_, cost, x, y))) = to_explore.pop() {
+        let current = IVec2::new(x, y);
+        if current == dest {
+            return Some(expand_jump_points(&nodes, origin, dest));
+        }
+        let (best_cost, parent) = nodes[&current];
+        if best_cost < cost {
+            continue;
+        }
+
+        steps += 1;
+        if max_steps.is_some_and(|max_steps| steps > max_steps) {
+            return None;
+        }
+
+        for dir in pruned_directions(current, parent, allow_diagonal, &walkable) {
+            let Some(jump_point) = jump(current, dir, dest, allow_diagonal, &walkable) else {
+                continue;
+            };
+
+            let new_cost = cost + distance(current, jump_point);
+            if nodes
+                .get(&jump_point)
+                .is_some_and(|(old_cost, _)| *old_cost <= new_cost)
+            {
+                continue;
+            }
+            nodes.insert(jump_point, (new_cost, current));
+            to_explore.push(Reverse((
+                new_cost + distance(jump_point, dest),
+                new_cost,
+                jump_point.x,
+                jump_point.y,
+            )));
+        }
+    }
+
+    None
+}
+
+/// The directions worth exploring when arriving at `current` from `parent`.
+fn pruned_directions(
+    current: IVec2,
+    parent: IVec2,
+    allow_diagonal: bool,
+    walkable: &impl Fn(IVec2) -> bool,
+) -> Vec<IVec2> {
+    if current == parent {
+        return IVec2::ZERO
+            .neighbours(TilemapType::Square, allow_diagonal)
+            .into_iter()
+            .flatten()
+            .collect();
+    }
+
+    let d = (current - parent).signum();
+    let mut dirs = vec![d];
+
+    if allow_diagonal {
+        if d.x != 0 && d.y != 0 {
+            dirs.extend([IVec2::new(d.x, 0), IVec2::new(0, d.y)]);
+            if !walkable(current - IVec2::new(d.x, 0)) {
+                dirs.push(IVec2::new(-d.x, d.y));
+            }
+            if !walkable(current - IVec2::new(0, d.y)) {
+                dirs.push(IVec2::new(d.x, -d.y));
+            }
+        } else {
+            let side = IVec2::new(d.y, d.x);
+            for side in [side, -side] {
+                if !walkable(current + side) {
+                    dirs.push(d + side);
+                }
+            }
+        }
+    } else if d.x != 0 {
+        // Paths go vertically first, so only turn when the way above or below is blocked.
+        for side in [IVec2::Y, IVec2::NEG_Y] {
+            if !walkable(current - d + side) {
+                dirs.push(side);
+            }
+        }
+    } else {
+        dirs.extend([IVec2::X, IVec2::NEG_X]);
+    }
+
+    dirs
+}
+
+/// Walk from `from` towards `dir` until reaching a tile where the path might turn.
+fn jump(
+    from: IVec2,
+    dir: IVec2,
+    dest: IVec2,
+    allow_diagonal: bool,
+    walkable: &impl Fn(IVec2) -> bool,
+) -> Option<IVec2> {
+    let mut current = from;
+
+    loop {
+        current += dir;
+        if !walkable(current) {
+            return None;
+        }
+        if current == dest {
+            return Some(current);
+        }
+
+        if allow_diagonal {
+            if dir.x != 0 && dir.y != 0 {
+                let (h, v) = (IVec2::new(dir.x, 0), IVec2::new(0, dir.y));
+                if (!walkable(current - h) && walkable(current - h + v))
+                    || (!walkable(current - v) && walkable(current - v + h))
+                    || jump(current, h, dest, allow_diagonal, walkable).is_some()
+                    || jump(current, v, dest, allow_diagonal, walkable).is_some()
+                {
+                    return Some(current);
+                }
+            } else {
+                let side = IVec2::new(dir.y, dir.x);
+                if [side, -side]
+                    .into_iter()
+                    .any(|side| !walkable(current + side) && walkable(current + side + dir))
+                {
+                    return Some(current);
+                }
+            }
+        } else if dir.x != 0 {
+            if [IVec2::Y, IVec2::NEG_Y]
+                .into_iter()
+                .any(|side| walkable(current + side) && !walkable(current - dir + side))
+            {
+                return Some(current);
+            }
+        } else if jump(current, IVec2::X, dest, allow_diagonal, walkable).is_some()
+            || jump(current, IVec2::NEG_X, dest, allow_diagonal, walkable).is_some()
+        {
+            return Some(current);
+        }
+    }
+}
+
+/// Fill the tiles between the jump points.
+fn expand_jump_points(
+    nodes: &HashMap<IVec2, (u32, IVec2)>,
+    origin: IVec2,
+    dest: IVec2,
+) -> Vec<IVec2> {
+    let mut path = vec![dest];
+    let mut current = dest;
+
+    while current != origin {
+        let parent = nodes[&current].1;
+        let step = (parent - current).signum();
+        while current != parent {
+            current += step;
+            path.push(current);
+        }
+    }
+
+    path.reverse();
+    path
+}
+
+/// Find a path by searching from both `origin` and `dest`, and stop when they meet.
+/// Returns the path from `origin` to `dest`, both included.
+///
+/// `heuristic` estimates the cost between two tiles.
+///
+/// **Notice**: Only square and isometric tilemaps are supported.
+pub fn bidirectional_search(
+    path_tilemap: &PathTilemap,
+    origin: IVec2,
+    dest: IVec2,
+    allow_diagonal: bool,
+    max_steps: Option<u32>,
+    heuristic: impl Fn(IVec2, IVec2) -> u32,
+) -> Option<Vec<IVec2>> {
+    let cost_of = |index: IVec2| path_tilemap.get(index).map(|tile| tile.cost);
+    cost_of(dest)?;
+    if origin == dest {
+        return Some(vec![origin]);
+    }
+
+    // The cost and the previous tile, from the origin and to the destination respectively.
+    let mut forward = HashMap::<IVec2, (u32, IVec2)>::default();
+    let mut backward = HashMap::<IVec2, (u32, IVec2)>::default();
+    let mut forward_to_explore = BinaryHeap::new();
+    let mut backward_to_explore = BinaryHeap::new();
+    forward.insert(origin, (0, origin));
+    backward.insert(dest, (0, dest));
+    forward_to_explore.push(Reverse((heuristic(origin, dest), 0, origin.x, origin.y)));
+    backward_to_explore.push(Reverse((heuristic(dest, origin), 0, dest.x, dest.y)));
+
+    // The cost of the best path found so far and where the searches meet.
+    let mut best: Option<(u32, IVec2)> = None;
+    let mut steps = 0;
+
+    while let (Some(Reverse(f)), Some(Reverse(b))) =
+        (forward_to_explore.peek(), backward_to_explore.peek())
+    {
+        if best.is_some_and(|(cost, _)| cost <= f.0.max(b.0)) {
+            break;
+        }
+
+        steps += 1;
+        if max_steps.is_some_and(|max_steps| steps > max_steps) {
+            return None;
+        }
+
+        let is_forward = forward_to_explore.len() <= backward_to_explore.len();
+        let (to_explore, nodes, others, target) = if is_forward {
+            (&mut forward_to_explore, &mut forward, &backward, dest)
+        } else {
+            (&mut backward_to_explore, &mut backward, &forward, origin)
+        };
+
+        let Reverse((_, cost, x, y)) = to_explore.pop().unwrap();
+        let current = IVec2::new(x, y);
+        if nodes[&current].0 < cost {
+            continue;
+        }
+
+        for neighbour in current
+            .neighbours(TilemapType::Square, allow_diagonal)
+            .into_iter()
+            .flatten()
+        {
+            let Some(neighbour_cost) = cost_of(neighbour) else {
+                continue;
+            };
+            // Stepping onto a tile costs the cost of that tile, so searching backwards
+            // costs the tile stepped from.
+            let step_cost = if is_forward {
+                neighbour_cost
+            } else {
+                cost_of(current).unwrap()
+            };
+
+            let new_cost = cost.saturating_add(step_cost);
+            if nodes
+                .get(&neighbour)
+                .is_some_and(|(old_cost, _)| *old_cost <= new_cost)
+            {
+                continue;
+            }
+            nodes.insert(neighbour, (new_cost, current));
+            to_explore.push(Reverse((
+                new_cost.saturating_add(heuristic(neighbour, target)),
+                new_cost,
+                neighbour.x,
+                neighbour.y,
+            )));
+
+            if let Some((other_cost, _)) = others.get(&neighbour) {
+                let total = new_cost.saturating_add(*other_cost);
+                if best.map_or(true, |(cost, _)| total < cost) {
+                    best = Some((total, neighbour));
+                }
+            }
+        }
+    }
+
+    let (_, meet) = best?;
+    let mut path = vec![meet];
+    let mut current = meet;
+    while current != origin {
+        current = forward[&current].1;
+        path.push(current);
+    }
+    path.reverse();
+
+    current = meet;
+    while current != dest {
+        current = backward[&current].1;
+        path.push(current);
+    }
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2};
+
+    use crate::{
+        algorithm::reachable::reachable_area,
+        math::GridRect,
+        tilemap::{
+            algorithm::path::{PathTile, PathTilemap},
+            map::TilemapType,
+        },
+    };
+
+    use super::{bidirectional_search, jump_point_search};
+
+    #[test]
+    fn test_search() {
+        let mut path_tilemap = PathTilemap::new();
+        path_tilemap.fill_path_rect(
+            GridRect::new(IVec2::ZERO, UVec2::splat(12)),
+            PathTile { cost: 1 },
+        );
+        // Two walls with gaps on opposite sides.
+        for y in 0..10 {
+            path_tilemap.remove(IVec2::new(4, y));
+            path_tilemap.remove(IVec2::new(8, 11 - y));
+        }
+
+        let (origin, dest) = (IVec2::new(1, 2), IVec2::new(10, 9));
+        let is_valid = |path: &Vec<IVec2>, allow_diagonal: bool| {
+            path.first() == Some(&origin)
+                && path.last() == Some(&dest)
+                && path.windows(2).all(|w| {
+                    let d = (w[1] - w[0]).abs();
+                    path_tilemap.get(w[1]).is_some()
+                        && if allow_diagonal {
+                            d.max_element() == 1
+                        } else {
+                            d.x + d.y == 1
+                        }
+                })
+        };
+
+        for allow_diagonal in [false, true] {
+            let best = reachable_area(
+                &path_tilemap,
+                TilemapType::Square,
+                origin,
+                u32::MAX,
+                allow_diagonal,
+            )
+            .cost(dest)
+            .unwrap() as usize;
+
+            let path =
+                jump_point_search(&path_tilemap, origin, dest, allow_diagonal, None).unwrap();
+            assert!(is_valid(&path, allow_diagonal));
+            assert_eq!(path.len() - 1, best);
+
+            let path =
+                bidirectional_search(&path_tilemap, origin, dest, allow_diagonal, None, |a, b| {
+                    let d = (b - a).abs();
+                    if allow_diagonal {
+                        d.max_element() as u32
+                    } else {
+                        (d.x + d.y) as u32
+                    }
+                })
+                .unwrap();
+            assert!(is_valid(&path, allow_diagonal));
+            assert_eq!(path.len() - 1, best);
+        }
+
+        path_tilemap.remove(dest);
+        assert!(jump_point_search(&path_tilemap, origin, dest, true, None).is_none());
+    }
+}
@@ -2,13 +2,15 @@
 use std::{collections::VecDeque, path::Path};
 
 use bevy::{
-    asset::Assets,
-    ecs::{entity::Entity, system::ResMut},
-    log::warn,
-    math::IVec2,
+    asset::{io::Reader, Asset, AssetLoader, Assets, AsyncReadExt, Handle, LoadContext},
+    ecs::{
+        entity::Entity,
+        system::{Res, ResMut},
+    },
+    log::{error, warn},
+    math::{IVec2, Vec2},
     prelude::{Commands, Component, Query, UVec2},
     reflect::Reflect,
-    render::render_resource::FilterMode,
     utils::{HashMap, HashSet},
 };
 use rand::{
@@ -16,6 +18,8 @@ use rand::{
     rngs::StdRng,
     Rng, SeedableRng,
 };
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::{
     algorithm::pathfinding::PathTilemaps,
@@ -25,13 +29,13 @@ use crate::{
     tilemap::{
         algorithm::path::PathTilemap,
         bundles::StandardPureColorTilemapBundle,
+        coordinates,
         map::{
-            TileRenderSize, TilemapAnimations, TilemapName, TilemapSlotSize, TilemapStorage,
-            TilemapTexture, TilemapTextures, TilemapTransform, TilemapType,
+            EntiTilesDefaults, TileRenderSize, TilemapAnimations, TilemapName, TilemapSlotSize,
+            TilemapStorage, TilemapTexture, TilemapTextures, TilemapTransform, TilemapType,
         },
-        tile::{TileBuilder, TileLayer},
+        tile::{TileBuilder, TileFlip, TileLayer},
     },
-    DEFAULT_CHUNK_SIZE,
 };
 
 #[cfg(feature = "physics")]
@@ -53,46 +57,97 @@ const HEX_DIR: [&'static str; 6] = [
     "down_left",
 ];
 
-#[derive(Reflect)]
+#[derive(Debug, Error)]
+pub enum WfcLoadError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Ron error: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("Only 128 elements are supported, but found {0}")]
+    TooManyElements(usize),
+    #[error("Element {element} should have rules for {expected} directions, but found {found}")]
+    DirectionCountMismatch {
+        element: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Element {element}'s {dir} can be {another}, which doesn't exist")]
+    UnknownElement {
+        element: usize,
+        dir: &'static str,
+        another: u8,
+    },
+    #[error(
+        "Conflict in rules! {element}'s {dir} can be {another}, \
+        but {another}'s {another_dir} cannot be {element}"
+    )]
+    Conflict {
+        element: usize,
+        dir: &'static str,
+        another: usize,
+        another_dir: &'static str,
+    },
+    #[error("Pattern No.{index}[label = {label:?}]'s size is {size}, but the pattern size is {expected}")]
+    PatternSizeMismatch {
+        index: usize,
+        label: Option<String>,
+        size: usize,
+        expected: usize,
+    },
+    #[error("Expected {expected} patterns, but found {found}")]
+    PatternCountMismatch { expected: usize, found: usize },
+    #[error("Weights length not match! weights: {weights}, rules: {rules}")]
+    WeightsLengthMismatch { weights: usize, rules: usize },
+    #[error("Fixed cell {index} is out of the area or element {element} doesn't exist")]
+    InvalidFixedCell { index: UVec2, element: u8 },
+}
+
+/// The possible neighbours of each element.
+///
+/// Can be loaded as an asset from `.wfc.ron` files, see [`WfcRulesLoader`].
+#[derive(Asset, Reflect)]
 pub struct WfcRules(pub Vec<Vec<u128>>);
 
 impl WfcRules {
-    // TODO use asset_server
-    pub fn from_file(rule_path: &str, ty: TilemapType) -> Self {
-        let rule_vec: Vec<Vec<Vec<u8>>> =
-            ron::from_str(std::fs::read_to_string(rule_path).unwrap().as_str()).unwrap();
-
-        assert!(
-            rule_vec.len() <= 128,
-            "We only support 128 elements for now"
-        );
+    /// Load the rules from a ron file.
+    ///
+    /// Use [`WfcRules::from_ron`] on platforms without a file system like WASM.
+    pub fn from_file(rule_path: &str, ty: TilemapType) -> Result<Self, WfcLoadError> {
+        Self::from_ron(&std::fs::read_to_string(rule_path)?, ty)
+    }
 
-        let mut rule_set = Vec::with_capacity(rule_vec.len());
-        for tex_idx in 0..rule_vec.len() {
-            let mut tex_rule: Vec<Vec<u8>> = {
-                match ty {
-                    TilemapType::Hexagonal(_) => vec![vec![]; 6],
-                    _ => vec![vec![]; 4],
-                }
-            };
-            for dir in 0..tex_rule.len() {
-                for idx in rule_vec[tex_idx][dir].iter() {
-                    tex_rule[dir].push(*idx);
-                }
-            }
-            rule_set.push(tex_rule);
+    /// Parse the rules from a ron string, for example from `include_str!`.
+    pub fn from_ron(rules: &str, ty: TilemapType) -> Result<Self, WfcLoadError> {
+        Self::from_raw(ron::from_str(rules)?, ty)
+    }
+
+    /// Build the rules from the possible neighbours of each element in every direction.
+    pub fn from_raw(rule_vec: Vec<Vec<Vec<u8>>>, ty: TilemapType) -> Result<Self, WfcLoadError> {
+        if rule_vec.len() > 128 {
+            return Err(WfcLoadError::TooManyElements(rule_vec.len()));
         }
 
-        let mut rule = Vec::with_capacity(rule_set.len());
-        for tex_idx in 0..rule_set.len() {
-            let mut tex_rule = {
-                match ty {
-                    TilemapType::Hexagonal(_) => vec![0; 6],
-                    _ => vec![0; 4],
-                }
-            };
+        let dir_names = Self::dir_names(ty);
+        let mut rule = Vec::with_capacity(rule_vec.len());
+        for (tex_idx, tex_rule_vec) in rule_vec.iter().enumerate() {
+            if tex_rule_vec.len() != dir_names.len() {
+                return Err(WfcLoadError::DirectionCountMismatch {
+                    element: tex_idx,
+                    expected: dir_names.len(),
+                    found: tex_rule_vec.len(),
+                });
+            }
+
+            let mut tex_rule = vec![0; dir_names.len()];
             for dir in 0..tex_rule.len() {
-                for idx in rule_set[tex_idx][dir].iter() {
+                for idx in tex_rule_vec[dir].iter() {
+                    if *idx as usize >= rule_vec.len() {
+                        return Err(WfcLoadError::UnknownElement {
+                            element: tex_idx,
+                            dir: dir_names[dir],
+                            another: *idx,
+                        });
+                    }
                     tex_rule[dir] |= 1 << idx;
                 }
             }
@@ -100,37 +155,76 @@ impl WfcRules {
         }
 
         let res = Self(rule);
-        res.check_rules(ty);
-        res
+        res.check_rules(ty)?;
+        Ok(res)
     }
 
     /// Check if there are conflicts in the rules.
-    pub fn check_rules(&self, ty: TilemapType) {
-        let (total_dirs, dir_names) = match ty {
-            TilemapType::Hexagonal(_) => (6, HEX_DIR.to_vec()),
-            _ => (4, DIR.to_vec()),
-        };
-
-        self.0.iter().enumerate().for_each(|(this_idx, elem)| {
-            elem.iter().enumerate().for_each(|(dir, rule)| {
-                (0..128).into_iter().for_each(|another_idx| {
-                    if rule & (1 << another_idx) != 0 {
-                        assert_ne!(
-                            self.0[another_idx][total_dirs - dir - 1] & (1 << this_idx),
-                            0,
-                            "Conflict in rules! \
-                            {}'s {} can be {}, but {}'s {} cannot be {}!",
-                            this_idx,
-                            dir_names[dir],
-                            another_idx,
-                            another_idx,
-                            dir_names[total_dirs - dir - 1],
-                            this_idx
-                        );
+    pub fn check_rules(&self, ty: TilemapType) -> Result<(), WfcLoadError> {
+        let dir_names = Self::dir_names(ty);
+        let total_dirs = dir_names.len();
+
+        for (this_idx, elem) in self.0.iter().enumerate() {
+            for (dir, rule) in elem.iter().enumerate() {
+                for another_idx in 0..self.0.len() {
+                    if rule & (1 << another_idx) != 0
+                        && self.0[another_idx][total_dirs - dir - 1] & (1 << this_idx) == 0
+                    {
+                        return Err(WfcLoadError::Conflict {
+                            element: this_idx,
+                            dir: dir_names[dir],
+                            another: another_idx,
+                            another_dir: dir_names[total_dirs - dir - 1],
+                        });
                     }
-                });
-            });
-        });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn dir_names(ty: TilemapType) -> &'static [&'static str] {
+        match ty {
+            TilemapType::Hexagonal(_) => &HEX_DIR,
+            _ => &DIR,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct WfcRulesLoaderSettings {
+    /// Used to check the rules in the right directions.
+    pub ty: TilemapType,
+}
+
+/// Loads [`WfcRules`] with the extension `.wfc.ron`.
+///
+/// Rules are checked as a square tilemap by default.
+/// Change it using `AssetServer::load_with_settings` for hexagonal tilemaps.
+#[derive(Default)]
+pub struct WfcRulesLoader;
+
+impl AssetLoader for WfcRulesLoader {
+    type Asset = WfcRules;
+
+    type Settings = WfcRulesLoaderSettings;
+
+    type Error = WfcLoadError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).await?;
+        WfcRules::from_ron(&buf, settings.ty)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wfc.ron"]
     }
 }
 
@@ -159,6 +253,12 @@ pub enum WfcSource {
     SingleTile(Vec<TileBuilder>),
     MapPattern(PatternsLayer),
     MultiLayerMapPattern(PackedPatternLayers),
+    /// Will be turned into [`WfcSource::MapPattern`] once all the patterns are loaded.
+    PatternAssets {
+        patterns: Vec<Handle<TilemapPattern>>,
+        label: Option<String>,
+        texture: Option<TilemapTexture>,
+    },
     #[cfg(feature = "ldtk")]
     LdtkMapPattern {
         json: bevy::asset::AssetId<crate::ldtk::json::LdtkJson>,
@@ -172,23 +272,23 @@ impl WfcSource {
     /// The numbers you fill in the rules will be directly considered as the atlas indices
     #[cfg(feature = "atlas")]
     pub fn from_atlas_indices(conn_rules: &WfcRules, texture_index: u32) -> Self {
-        let tiles = (0..conn_rules.0.len())
-            .into_iter()
-            .map(|atlas_index| {
-                TileBuilder::new().with_layer(
-                    0,
-                    TileLayer::no_flip_at(texture_index as i32, atlas_index as i32),
-                )
-            })
-            .collect();
-        Self::SingleTile(tiles)
+        Self::from_texture_atlas(conn_rules, texture_index)
     }
     #[cfg(not(feature = "atlas"))]
     pub fn from_atlas_indices(conn_rules: &WfcRules) -> Self {
+        Self::from_texture_atlas(conn_rules, 0)
+    }
+
+    /// Same as `from_atlas_indices`, but has the same signature whether `atlas` feature
+    /// is enabled or not. `texture_index` is ignored when `atlas` feature is disabled.
+    pub fn from_texture_atlas(conn_rules: &WfcRules, texture_index: u32) -> Self {
         let tiles = (0..conn_rules.0.len())
             .into_iter()
             .map(|atlas_index| {
-                TileBuilder::new().with_layer(0, TileLayer::no_flip(atlas_index as i32))
+                TileBuilder::new().with_layer(
+                    0,
+                    TileLayer::new(texture_index as i32, atlas_index as i32, TileFlip::NONE),
+                )
             })
             .collect();
         Self::SingleTile(tiles)
@@ -210,50 +310,113 @@ impl WfcSource {
         prefix: String,
         conn_rules: &WfcRules,
         texture: Option<TilemapTexture>,
-    ) -> Self {
-        let n = conn_rules.0.len();
-        let mut patterns = Vec::with_capacity(n);
-
-        for idx in 0..n {
-            let ser_pattern: TilemapPattern = ron::from_str(
+    ) -> Result<Self, WfcLoadError> {
+        let patterns = (0..conn_rules.0.len())
+            .map(|idx| {
                 std::fs::read_to_string(
                     Path::new(&directory).join(format!("{}{}.ron", prefix, idx)),
                 )
-                .unwrap()
-                .as_str(),
-            )
-            .unwrap();
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::from_ron_patterns(
+            patterns.iter().map(|p| p.as_str()),
+            Some(prefix),
+            conn_rules,
+            texture,
+        )
+    }
+
+    /// Parse tilemap patterns from ron strings, one for each element in the rules.
+    pub fn from_ron_patterns<'a>(
+        patterns: impl IntoIterator<Item = &'a str>,
+        label: Option<String>,
+        conn_rules: &WfcRules,
+        texture: Option<TilemapTexture>,
+    ) -> Result<Self, WfcLoadError> {
+        let patterns = patterns
+            .into_iter()
+            .map(ron::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
 
-            let size = ser_pattern.tiles.aabb.size();
-            let label = ser_pattern.label.clone().unwrap_or("No label".to_string());
+        Self::from_patterns(patterns, label, conn_rules, texture)
+    }
+
+    /// Use the patterns as elements. They must have the same size.
+    pub fn from_patterns(
+        patterns: Vec<TilemapPattern>,
+        label: Option<String>,
+        conn_rules: &WfcRules,
+        texture: Option<TilemapTexture>,
+    ) -> Result<Self, WfcLoadError> {
+        if patterns.len() != conn_rules.0.len() {
+            return Err(WfcLoadError::PatternCountMismatch {
+                expected: conn_rules.0.len(),
+                found: patterns.len(),
+            });
+        }
+
+        Self::patterns_layer(patterns, label, texture)
+    }
+
+    /// Use the pattern assets as elements, one for each element in the rules.
+    ///
+    /// The source will wait until all of them are loaded.
+    pub fn from_pattern_assets(
+        patterns: Vec<Handle<TilemapPattern>>,
+        label: Option<String>,
+        texture: Option<TilemapTexture>,
+    ) -> Self {
+        Self::PatternAssets {
+            patterns,
+            label,
+            texture,
+        }
+    }
 
-            patterns.push(ser_pattern);
+    fn patterns_layer(
+        patterns: Vec<TilemapPattern>,
+        label: Option<String>,
+        texture: Option<TilemapTexture>,
+    ) -> Result<Self, WfcLoadError> {
+        if patterns.is_empty() {
+            return Err(WfcLoadError::PatternCountMismatch {
+                expected: 1,
+                found: 0,
+            });
+        }
 
-            assert_eq!(
-                size,
-                patterns[0].tiles.aabb.size(),
-                "Failed to load patterns! Pattern No.{}[label = {:?}]'s size is {}, \
-                but the pattern_size is {}",
-                idx,
-                label,
-                size,
-                patterns[0].tiles.aabb.size()
-            );
+        let expected = patterns[0].tiles.aabb.size();
+        if let Some((index, pattern)) = patterns
+            .iter()
+            .enumerate()
+            .find(|(_, p)| p.tiles.aabb.size() != expected)
+        {
+            return Err(WfcLoadError::PatternSizeMismatch {
+                index,
+                label: pattern.label.clone(),
+                size: pattern.tiles.aabb.size(),
+                expected,
+            });
         }
 
-        Self::MapPattern(PatternsLayer {
+        Ok(Self::MapPattern(PatternsLayer {
             pattern_size: patterns[0].tiles.aabb.extent,
             patterns,
             texture,
-            label: Some(prefix),
-        })
+            label,
+        }))
     }
 }
 
 /// The order of the directions in config should be: up, right, left, down.
+///
+/// The result is deterministic for a given `seed`, even across platforms,
+/// as long as the version of `rand` stays the same.
 #[derive(Component, Reflect)]
 pub struct WfcRunner {
     conn_rules: Vec<Vec<u128>>,
+    rules_asset: Option<Handle<WfcRules>>,
     mode: WfcMode,
     ty: TilemapType,
     sampler: Option<Box<dyn Fn(&WfcElement, &mut StdRng) -> u8 + Send + Sync>>,
@@ -262,13 +425,36 @@ pub struct WfcRunner {
     max_retrace_factor: u32,
     max_retrace_time: u32,
     max_history: usize,
+    fixed: HashMap<UVec2, u8>,
+    masked: HashSet<UVec2>,
 }
 
 impl WfcRunner {
     pub fn new(ty: TilemapType, rules: WfcRules, area: GridRect, seed: Option<u64>) -> Self {
+        Self::new_internal(ty, rules.0, None, area, seed)
+    }
+
+    /// Same as `new`, but the runner will wait until the rules are loaded.
+    pub fn from_rules_asset(
+        ty: TilemapType,
+        rules: Handle<WfcRules>,
+        area: GridRect,
+        seed: Option<u64>,
+    ) -> Self {
+        Self::new_internal(ty, Vec::new(), Some(rules), area, seed)
+    }
+
+    fn new_internal(
+        ty: TilemapType,
+        conn_rules: Vec<Vec<u128>>,
+        rules_asset: Option<Handle<WfcRules>>,
+        area: GridRect,
+        seed: Option<u64>,
+    ) -> Self {
         let size = area.size();
         Self {
-            conn_rules: rules.0,
+            conn_rules,
+            rules_asset,
             mode: WfcMode::NonWeighted,
             ty,
             sampler: None,
@@ -277,30 +463,103 @@ impl WfcRunner {
             max_retrace_factor: size.ilog10().clamp(2, 16),
             max_retrace_time: size.ilog10().clamp(2, 16) * 100,
             max_history: (size.ilog10().clamp(1, 8) * 20) as usize,
+            fixed: HashMap::default(),
+            masked: HashSet::default(),
         }
     }
 
-    /// Set the weights of the tiles.
+    /// Set the weights of the tiles from a ron file.
     /// The length of the weights should be the same as the length of the rule.
-    pub fn with_weights(mut self, weights_path: String) -> Self {
+    pub fn with_weights(self, weights_path: String) -> Result<Self, WfcLoadError> {
+        self.with_weights_ron(&std::fs::read_to_string(weights_path)?)
+    }
+
+    /// Same as `with_weights`, but parses the weights from a ron string.
+    pub fn with_weights_ron(self, weights: &str) -> Result<Self, WfcLoadError> {
+        self.with_weights_vec(ron::from_str(weights)?)
+    }
+
+    /// Same as `with_weights`, but takes the weights directly.
+    ///
+    /// **Notice**: If the rules are an asset, the length will be checked after it's loaded.
+    pub fn with_weights_vec(mut self, weights_vec: Vec<u8>) -> Result<Self, WfcLoadError> {
         assert_eq!(
             self.mode,
             WfcMode::NonWeighted,
             "You can only use one sampler or one weights vector"
         );
-        let weights_vec: Vec<u8> =
-            ron::from_str(std::fs::read_to_string(weights_path).unwrap().as_str()).unwrap();
-        assert_eq!(
-            weights_vec.len(),
-            self.conn_rules.len(),
-            "weights length not match! weights: {}, rules: {}",
-            weights_vec.len(),
-            self.conn_rules.len()
-        );
         self.mode = WfcMode::Weighted(weights_vec);
+        if self.rules_asset.is_none() {
+            self.check_weights()?;
+        }
+        Ok(self)
+    }
+
+    fn check_weights(&self) -> Result<(), WfcLoadError> {
+        match &self.mode {
+            WfcMode::Weighted(weights) if weights.len() != self.conn_rules.len() => {
+                Err(WfcLoadError::WeightsLengthMismatch {
+                    weights: weights.len(),
+                    rules: self.conn_rules.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Take the rules from the asset if this runner is waiting for it.
+    ///
+    /// Returns `false` if the rules are not loaded yet.
+    fn resolve_rules(&mut self, rules_assets: &Assets<WfcRules>) -> Result<bool, WfcLoadError> {
+        let Some(handle) = &self.rules_asset else {
+            return Ok(true);
+        };
+        let Some(rules) = rules_assets.get(handle) else {
+            return Ok(false);
+        };
+
+        self.conn_rules = rules.0.clone();
+        self.rules_asset = None;
+        self.check_weights()?;
+        self.check_fixed()?;
+        Ok(true)
+    }
+
+    /// Collapse the cell at `index` into `element` before running,
+    /// so the other cells will be generated around it.
+    /// `index` is relative to the origin of the area.
+    ///
+    /// **Notice**: If the rules are an asset, the element will be checked after it's loaded.
+    pub fn with_fixed(mut self, index: UVec2, element: u8) -> Result<Self, WfcLoadError> {
+        self.fixed.insert(index, element);
+        if self.rules_asset.is_none() {
+            self.check_fixed()?;
+        }
+        Ok(self)
+    }
+
+    /// Skip these cells, so non-rectangular areas can be generated.
+    /// Masked cells don't constrain their neighbours, and are `None` in [`WfcData`].
+    /// Indices are relative to the origin of the area.
+    ///
+    /// **Notice**: Fixed cells that are masked are ignored.
+    pub fn with_mask(mut self, masked: impl IntoIterator<Item = UVec2>) -> Self {
+        self.masked.extend(masked);
         self
     }
 
+    fn check_fixed(&self) -> Result<(), WfcLoadError> {
+        match self.fixed.iter().find(|(index, element)| {
+            index.cmpge(self.area.extent).any() || **element as usize >= self.conn_rules.len()
+        }) {
+            Some((index, element)) => Err(WfcLoadError::InvalidFixedCell {
+                index: *index,
+                element: *element,
+            }),
+            None => Ok(()),
+        }
+    }
+
     /// Set the custom sampler function.
     /// The function should accept `WfcTile`,`StdRng` and return a `u8` as the texture index.
     pub fn with_custom_sampler(
@@ -359,20 +618,36 @@ impl WfcRunner {
 pub struct WfcData {
     pub(crate) data: Vec<u8>,
     pub(crate) area: GridRect,
+    pub(crate) ty: TilemapType,
 }
 
 impl WfcData {
-    pub(crate) fn new(area: GridRect) -> Self {
+    /// Marks the cells skipped by [`WfcRunner::with_mask`].
+    pub(crate) const MASKED: u8 = u8::MAX;
+
+    pub(crate) fn new(area: GridRect, ty: TilemapType) -> Self {
         Self {
-            data: vec![0; area.size()],
+            data: vec![Self::MASKED; area.size()],
             area,
+            ty,
         }
     }
 
+    /// Get the element at `index`. Returns `None` if it's out of the area or masked.
     pub fn get(&self, index: UVec2) -> Option<u8> {
         self.data
             .get((index.y * self.area.extent.x + index.x) as usize)
             .cloned()
+            .filter(|e| *e != Self::MASKED)
+    }
+
+    /// Iterate over the elements that are not masked, along with their indices in `data`.
+    pub(crate) fn elements(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| **e != Self::MASKED)
+            .map(|(i, e)| (i, *e))
     }
 
     pub(crate) fn set(&mut self, index: UVec2, value: u8) {
@@ -388,19 +663,51 @@ impl WfcData {
             - self.area.origin
     }
 
+    /// Get the index of the bottom left tile of the pattern at `elem_index`.
+    #[inline]
+    pub fn pattern_origin(&self, elem_index: usize, pattern_size: UVec2) -> IVec2 {
+        self.elem_idx_to_grid(elem_index) * pattern_size.as_ivec2()
+    }
+
+    /// Get the translation of the pattern at `elem_index`, relative to the tilemap
+    /// the wfc runs on.
+    ///
+    /// Patterns are placed in the index space, so for isometric and hexagonal tilemaps
+    /// the offset is not simply `grid * pattern_size * slot_size`.
+    pub fn pattern_translation(
+        &self,
+        elem_index: usize,
+        pattern_size: UVec2,
+        slot_size: Vec2,
+    ) -> Vec2 {
+        coordinates::index_to_world(
+            self.pattern_origin(elem_index, pattern_size),
+            self.ty,
+            &TilemapTransform::default(),
+            Vec2::ZERO,
+            slot_size,
+        )
+    }
+
     #[allow(dead_code)]
     pub(crate) fn formatted_print(&self, flip: bool) {
         if flip {
             for y in (0..self.area.extent.y).rev() {
                 for x in 0..self.area.extent.x {
-                    print!("{:3} ", self.get(UVec2 { x, y }).unwrap());
+                    match self.get(UVec2 { x, y }) {
+                        Some(e) => print!("{:3} ", e),
+                        None => print!("  - "),
+                    }
                 }
                 println!();
             }
         } else {
             for y in 0..self.area.extent.y {
                 for x in 0..self.area.extent.x {
-                    print!("{:3} ", self.get(UVec2 { x, y }).unwrap());
+                    match self.get(UVec2 { x, y }) {
+                        Some(e) => print!("{:3} ", e),
+                        None => print!("  - "),
+                    }
                 }
                 println!();
             }
@@ -462,25 +769,36 @@ impl WfcGrid {
 
         for y in 0..runner.area.extent.y {
             for x in 0..runner.area.extent.x {
-                elements.insert(
-                    UVec2 { x, y },
-                    WfcElement {
-                        index: UVec2 { x, y },
-                        element_index: None,
-                        collapsed: false,
-                        psbs: (!0) >> (128 - max_psbs),
-                    },
-                );
+                let index = UVec2 { x, y };
+                if runner.masked.contains(&index) {
+                    continue;
+                }
 
-                uncollapsed.insert((max_psbs, UVec2 { x, y }));
+                let element = match runner.fixed.get(&index) {
+                    Some(e) => WfcElement {
+                        index,
+                        element_index: Some(*e),
+                        collapsed: true,
+                        psbs: 1 << e,
+                    },
+                    None => {
+                        uncollapsed.insert((max_psbs, index));
+                        WfcElement {
+                            index,
+                            element_index: None,
+                            collapsed: false,
+                            psbs: (!0) >> (128 - max_psbs),
+                        }
+                    }
+                };
+                elements.insert(index, element);
             }
         }
 
-        WfcGrid {
+        let mut grid = WfcGrid {
             mode: runner.mode.clone(),
             area: runner.area,
             conn_rules: runner.conn_rules.clone(),
-            uncollapsed,
             elements,
             history: vec![None; runner.max_history],
             cur_hist: 0,
@@ -489,12 +807,49 @@ impl WfcGrid {
                 Some(seed) => StdRng::seed_from_u64(seed),
                 None => StdRng::from_entropy(),
             },
-            remaining: runner.area.size(),
+            remaining: uncollapsed.len(),
+            uncollapsed,
             retrace_strength: 1,
             max_retrace_factor: runner.max_retrace_factor,
             max_retrace_time: runner.max_retrace_time,
             retraced_time: 0,
             sampler: runner.sampler.take(),
+        };
+        grid.constrain_fixed();
+        grid
+    }
+
+    /// Propagate the constraints of the fixed cells.
+    /// The grid fails if they conflict with each other.
+    fn constrain_fixed(&mut self) {
+        let mut fixed = self
+            .elements
+            .values()
+            .filter(|e| e.collapsed)
+            .map(|e| e.index)
+            .collect::<Vec<_>>();
+        fixed.sort_unstable_by_key(|index| (index.y, index.x));
+
+        for index in fixed {
+            // Collapsed neighbours are skipped when propagating, so check them here.
+            let rules = &self.conn_rules[self.elements[&index].element_index.unwrap() as usize];
+            let conflicted = index
+                .neighbours(self.ty, false)
+                .into_iter()
+                .enumerate()
+                .filter_map(|(dir, nei)| Some((dir, self.elements.get(&nei?)?)))
+                .any(|(dir, nei)| nei.collapsed && rules[dir] & nei.psbs == 0);
+
+            if conflicted {
+                self.retraced_time = self.max_retrace_time;
+                return;
+            }
+
+            // There's no history yet, so the grid fails if this fails.
+            self.constrain(index);
+            if self.retraced_time >= self.max_retrace_time {
+                return;
+            }
         }
     }
 
@@ -514,7 +869,7 @@ impl WfcGrid {
         let psb = match &self.mode {
             WfcMode::NonWeighted => {
                 let psb_vec = elem.get_psbs_vec();
-                psb_vec[self.rng.sample(Uniform::new(0, psb_vec.len()))]
+                psb_vec[self.rng.sample(Uniform::new(0, psb_vec.len() as u32)) as usize]
             }
             WfcMode::Weighted(w) => {
                 let psb_vec = elem.get_psbs_vec();
@@ -641,7 +996,10 @@ impl WfcGrid {
                 candidates.push(*index);
             }
         });
-        candidates[self.rng.sample(Uniform::new(0, candidates.len()))]
+        // The iteration order of `HashSet` is not guaranteed to be the same
+        // across platforms, so sort them to keep the result deterministic.
+        candidates.sort_unstable_by_key(|index| (index.y, index.x));
+        candidates[self.rng.sample(Uniform::new(0, candidates.len() as u32)) as usize]
     }
 
     pub fn generate_data(&mut self) -> Option<WfcData> {
@@ -649,7 +1007,7 @@ impl WfcGrid {
             return None;
         }
 
-        let mut data = WfcData::new(self.area);
+        let mut data = WfcData::new(self.area, self.ty);
         self.elements.drain().for_each(|(i, e)| {
             data.set(i, e.element_index.unwrap());
         });
@@ -665,9 +1023,14 @@ pub struct WfcTask(Task<Option<WfcData>>);
 pub fn wave_function_collapse(
     mut commands: Commands,
     mut runner_query: Query<(Entity, &mut WfcRunner), Without<WfcTask>>,
+    rules_assets: Option<Res<Assets<WfcRules>>>,
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
     runner_query.iter_mut().for_each(|(entity, mut runner)| {
+        if !wfc_rules_resolver(&mut commands, entity, &mut runner, rules_assets.as_deref()) {
+            return;
+        }
+
         let mut wfc_grid = WfcGrid::from_runner(&mut runner);
         let task = thread_pool.spawn(async move {
             while wfc_grid.remaining > 0 && wfc_grid.retraced_time < wfc_grid.max_retrace_time {
@@ -687,8 +1050,13 @@ pub fn wave_function_collapse(
 pub fn wave_function_collapse_single_threaded(
     mut commands: Commands,
     mut runner_query: Query<(Entity, &mut WfcRunner)>,
+    rules_assets: Option<Res<Assets<WfcRules>>>,
 ) {
     runner_query.iter_mut().for_each(|(entity, mut runner)| {
+        if !wfc_rules_resolver(&mut commands, entity, &mut runner, rules_assets.as_deref()) {
+            return;
+        }
+
         let mut wfc_grid = WfcGrid::from_runner(&mut runner);
 
         while wfc_grid.remaining > 0 && wfc_grid.retraced_time < wfc_grid.max_retrace_time {
@@ -703,6 +1071,72 @@ pub fn wave_function_collapse_single_threaded(
     });
 }
 
+/// Returns `true` if the runner is ready to run.
+fn wfc_rules_resolver(
+    commands: &mut Commands,
+    entity: Entity,
+    runner: &mut WfcRunner,
+    rules_assets: Option<&Assets<WfcRules>>,
+) -> bool {
+    if runner.rules_asset.is_none() {
+        return true;
+    }
+
+    let Some(rules_assets) = rules_assets else {
+        return false;
+    };
+
+    match runner.resolve_rules(rules_assets) {
+        Ok(ready) => ready,
+        Err(err) => {
+            error!("Failed to run wfc on {:?}: {}", entity, err);
+            commands.entity(entity).remove::<WfcRunner>();
+            false
+        }
+    }
+}
+
+/// Turn [`WfcSource::PatternAssets`] into [`WfcSource::MapPattern`] once the patterns are loaded.
+pub fn wfc_pattern_assets_resolver(
+    mut commands: Commands,
+    mut sources_query: Query<(Entity, &mut WfcSource)>,
+    patterns_assets: Option<Res<Assets<TilemapPattern>>>,
+) {
+    let Some(patterns_assets) = patterns_assets else {
+        return;
+    };
+
+    sources_query.iter_mut().for_each(|(entity, mut source)| {
+        let WfcSource::PatternAssets {
+            patterns,
+            label,
+            texture,
+        } = &*source
+        else {
+            return;
+        };
+
+        let Some(patterns) = patterns
+            .iter()
+            .map(|handle| patterns_assets.get(handle).cloned())
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+
+        match WfcSource::patterns_layer(patterns, label.clone(), texture.clone()) {
+            Ok(resolved) => *source = resolved,
+            Err(err) => {
+                error!(
+                    "Failed to use the patterns as wfc source on {:?}: {}",
+                    entity, err
+                );
+                commands.entity(entity).remove::<WfcSource>();
+            }
+        }
+    });
+}
+
 #[cfg(feature = "multi-threaded")]
 pub fn wfc_data_assigner(mut commands: Commands, mut tasks_query: Query<(Entity, &mut WfcTask)>) {
     tasks_query.iter_mut().for_each(|(entity, mut task)| {
@@ -729,6 +1163,7 @@ pub fn wfc_applier(
     mut path_tilemaps: ResMut<PathTilemaps>,
     mut textures_assets: ResMut<Assets<TilemapTextures>>,
     mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    defaults: Res<EntiTilesDefaults>,
     #[cfg(feature = "physics")] mut physics_tilemaps_query: Query<
         &mut crate::tilemap::physics::PhysicsTilemap,
     >,
@@ -740,8 +1175,8 @@ pub fn wfc_applier(
                     panic!("SingleTile source requires a tilemap on the entity!")
                 });
 
-                for (i, e) in wfc_data.data.iter().enumerate() {
-                    let ser_tile = tiles.get(*e as usize).unwrap();
+                for (i, e) in wfc_data.elements() {
+                    let ser_tile = tiles.get(e as usize).unwrap();
                     tilemap.set(
                         &mut commands,
                         wfc_data.elem_idx_to_grid(i),
@@ -759,18 +1194,16 @@ pub fn wfc_applier(
                     panic!("MapPattern source requires a tilemap on the entity!")
                 });
 
-                wfc_data.data.iter().enumerate().for_each(|(i, e)| {
-                    let p = &patterns.get(*e as usize);
+                wfc_data.elements().for_each(|(i, e)| {
+                    let p = &patterns.get(e as usize);
                     let origin =
                         (wfc_data.elem_idx_to_grid(i) + wfc_data.area.origin) * p.tiles.aabb.extent.as_ivec2();
                     tilemap.fill_with_buffer(&mut commands, origin, p.tiles.clone());
 
-                    if let Some(tilemap) = path_tilemaps.get_mut(entity) {
-                        #[cfg(feature = "multi-threaded")]
-                        tilemap.lock().unwrap().fill_with_buffer(origin, p.path_tiles.clone());
-                        #[cfg(not(feature = "multi-threaded"))]
-                        tilemap.fill_with_buffer(origin, p.path_tiles.clone());
-                    } else {
+                    if path_tilemaps
+                        .with_mut(entity, |tilemap| tilemap.fill_with_buffer(origin, p.path_tiles.clone()))
+                        .is_none()
+                    {
                         warn!("Skipping algorithm layers as the tilemap does not have a PathTilemap component!");
                     }
 
@@ -799,31 +1232,30 @@ pub fn wfc_applier(
                     warn!("MultiLayerMapPattern source does not require a tilemap storage on the entity!")
                 }
 
-                wfc_data.data.iter().enumerate().for_each(|(i, e)| {
-                    let slice = layered_patterns.get_element(*e as usize);
+                wfc_data.elements().for_each(|(i, e)| {
+                    let slice = layered_patterns.get_element(e as usize);
 
                     slice.element.iter().for_each(|(layer, texture)| {
-                        let pattern_size = slice.pattern_size.as_vec2();
                         let layer_entity = commands.spawn_empty().id();
 
                         let mut bundle = StandardPureColorTilemapBundle {
                             name: TilemapName(layer.label.clone().unwrap()),
-                            ty: TilemapType::Square,
-                            storage: TilemapStorage::new(DEFAULT_CHUNK_SIZE, layer_entity),
+                            ty: wfc_data.ty,
+                            storage: defaults.storage(layer_entity),
                             material: materials.add(StandardTilemapMaterial::default()),
                             ..Default::default()
                         };
 
                         if let Some(texture) = texture {
                             let mut bundle = bundle.convert_to_texture_bundle(
-                                textures_assets.add(TilemapTextures::single(texture.clone(), FilterMode::Nearest)),
+                                textures_assets.add(TilemapTextures::single(texture.clone(), defaults.filter_mode)),
                                 TilemapAnimations::default()
                             );
                             let tile_size = texture.desc.tile_size.as_vec2();
                             bundle.tile_render_size = TileRenderSize(tile_size);
                             bundle.slot_size = TilemapSlotSize(tile_size);
                             bundle.transform = TilemapTransform::from_translation(
-                                wfc_data.elem_idx_to_grid(i).as_vec2() * tile_size * pattern_size
+                                wfc_data.pattern_translation(i, slice.pattern_size, tile_size)
                             );
                             bundle.storage.fill_with_buffer(
                                 &mut commands,
@@ -839,7 +1271,7 @@ pub fn wfc_applier(
                                 panic!("MultiLayerMapPattern source for a pure color tilemap requires a TilemapSlotSize!")
                             });
                             bundle.transform = TilemapTransform::from_translation(
-                                wfc_data.elem_idx_to_grid(i).as_vec2() * bundle.slot_size.0 * pattern_size
+                                wfc_data.pattern_translation(i, slice.pattern_size, bundle.slot_size.0)
                             );
                             bundle.storage.fill_with_buffer(
                                 &mut commands,
@@ -876,7 +1308,6 @@ pub fn wfc_applier(
 
                 commands.entity(entity).despawn();
             }
-            #[cfg(feature = "ldtk")]
             _ => {}
         },
     );
@@ -911,6 +1342,9 @@ pub fn ldtk_wfc_helper(
                                     ),
                                     mode: crate::ldtk::events::LdtkLevelLoaderMode::MapPattern,
                                     trans_ovrd: None,
+                                    z_ovrd: None,
+                                    layer_filter: Default::default(),
+                                    layer_overrides: Default::default(),
                                 },
                             )
                         }));
@@ -922,14 +1356,17 @@ pub fn ldtk_wfc_helper(
 
                 match mode {
                     LdtkWfcMode::SingleMap => {
-                        data.data.iter().enumerate().for_each(|(i, e)| {
-                            let mut bg = patterns.backgrounds[*e as usize].clone().unwrap();
-                            let ptn_idx = data.elem_idx_to_grid(i);
+                        data.elements().for_each(|(i, e)| {
+                            let mut bg = patterns.backgrounds[e as usize].clone().unwrap();
                             let ptn_render_size = bg.sprite.custom_size.unwrap();
                             let z = bg.transform.translation.z;
                             bg.transform.translation = ((ptn_render_size / 2.)
-                                + ptn_idx.as_vec2() * ptn_render_size)
-                                .extend(z);
+                                + data.pattern_translation(
+                                    i,
+                                    patterns.pattern_size,
+                                    ptn_render_size / patterns.pattern_size.as_vec2(),
+                                ))
+                            .extend(z);
                             commands.spawn(bg);
                         });
 
@@ -951,3 +1388,139 @@ pub fn ldtk_wfc_helper(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2, Vec2};
+
+    use crate::{math::GridRect, tilemap::map::TilemapType};
+
+    use super::{WfcData, WfcGrid, WfcLoadError, WfcRules, WfcRunner};
+
+    #[test]
+    fn test_load_rules() {
+        let rules = WfcRules::from_ron("[[[0], [0], [0], [0]]]", TilemapType::Square).unwrap();
+        assert_eq!(rules.0, vec![vec![1; 4]]);
+
+        assert!(matches!(
+            WfcRules::from_ron("[[[0], [0]]]", TilemapType::Square),
+            Err(WfcLoadError::DirectionCountMismatch { .. })
+        ));
+        assert!(matches!(
+            WfcRules::from_ron(
+                "[[[0, 1], [0], [0], [0]], [[1], [1], [1], [1]]]",
+                TilemapType::Square
+            ),
+            Err(WfcLoadError::Conflict { .. })
+        ));
+        assert!(matches!(
+            WfcRules::from_ron("[[[0]", TilemapType::Square),
+            Err(WfcLoadError::Ron(_))
+        ));
+    }
+
+    // Grass, sand and water. Water and grass can't be adjacent.
+    const RULES: &str = "[
+        [[0, 1], [0, 1], [0, 1], [0, 1]],
+        [[0, 1, 2], [0, 1, 2], [0, 1, 2], [0, 1, 2]],
+        [[1, 2], [1, 2], [1, 2], [1, 2]],
+    ]";
+
+    fn runner(extent: UVec2) -> WfcRunner {
+        WfcRunner::new(
+            TilemapType::Square,
+            WfcRules::from_ron(RULES, TilemapType::Square).unwrap(),
+            GridRect::new(IVec2::ZERO, extent),
+            Some(42),
+        )
+    }
+
+    fn run(mut runner: WfcRunner) -> Option<WfcData> {
+        let mut grid = WfcGrid::from_runner(&mut runner);
+        while grid.remaining > 0 && grid.retraced_time < grid.max_retrace_time {
+            grid.collapse();
+        }
+        grid.generate_data()
+    }
+
+    #[test]
+    fn test_deterministic_collapse() {
+        let run = || run(runner(UVec2::new(8, 4))).unwrap().data;
+
+        // Update this only if the algorithm or the version of `rand` changes.
+        #[rustfmt::skip]
+        let golden = vec![
+            1, 0, 0, 0, 1, 1, 2, 1,
+            1, 0, 1, 1, 2, 1, 1, 1,
+            0, 0, 1, 1, 2, 1, 1, 2,
+            0, 1, 1, 1, 1, 0, 0, 1,
+        ];
+        assert_eq!(run(), golden);
+        assert_eq!(run(), golden);
+    }
+
+    #[test]
+    fn test_constrained_collapse() {
+        let extent = UVec2::new(6, 6);
+        let masked = (0..6).map(|y| UVec2::new(3, y)).collect::<Vec<_>>();
+        let data = run(runner(extent)
+            .with_fixed(UVec2::new(1, 1), 2)
+            .unwrap()
+            .with_fixed(UVec2::new(3, 0), 2)
+            .unwrap()
+            .with_mask(masked.clone()))
+        .unwrap();
+
+        assert_eq!(data.get(UVec2::new(1, 1)), Some(2));
+        assert!(masked.iter().all(|i| data.get(*i).is_none()));
+        for y in 0..extent.y {
+            for x in 0..extent.x - 1 {
+                let pair = (data.get(UVec2::new(x, y)), data.get(UVec2::new(x + 1, y)));
+                assert!(!matches!(pair, (Some(0), Some(2)) | (Some(2), Some(0))));
+            }
+        }
+
+        // Water next to grass.
+        let conflicted = runner(extent)
+            .with_fixed(UVec2::new(0, 0), 0)
+            .unwrap()
+            .with_fixed(UVec2::new(1, 0), 2)
+            .unwrap();
+        assert!(run(conflicted).is_none());
+
+        assert!(matches!(
+            runner(extent).with_fixed(UVec2::new(6, 0), 0),
+            Err(WfcLoadError::InvalidFixedCell { .. })
+        ));
+        assert!(matches!(
+            runner(extent).with_fixed(UVec2::ZERO, 3),
+            Err(WfcLoadError::InvalidFixedCell { .. })
+        ));
+    }
+    #[test]
+    fn test_pattern_translation() {
+        let area = GridRect::new(IVec2::ZERO, UVec2::new(2, 2));
+        let pattern_size = UVec2::new(4, 2);
+        let slot_size = Vec2::new(32., 16.);
+
+        let square = WfcData::new(area, TilemapType::Square);
+        assert_eq!(
+            square.pattern_translation(3, pattern_size, slot_size),
+            Vec2::new(128., 32.)
+        );
+
+        // Index (4, 2) in an isometric tilemap.
+        let isometric = WfcData::new(area, TilemapType::Isometric);
+        assert_eq!(
+            isometric.pattern_translation(3, pattern_size, slot_size),
+            Vec2::new(32., 48.)
+        );
+
+        // Index (4, 2) in a hexagonal tilemap with legs of 8.
+        let hexagonal = WfcData::new(area, TilemapType::Hexagonal(8));
+        assert_eq!(
+            hexagonal.pattern_translation(3, pattern_size, slot_size),
+            Vec2::new(96., 24.)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Generate Rust code from the definitions of a LDtk project.
+//!
+//! ```text
+//! cargo run --bin ldtk-codegen --features ldtk -- assets/ldtk/grid_vania.ldtk src/grid_vania.rs
+//! ```
+//!
+//! Prints the code if the output path is omitted.
+//! See [`bevy_entitiles::ldtk::codegen`] for what's generated.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use bevy_entitiles::ldtk::{codegen::generate, json::LdtkJson};
+
+fn main() -> ExitCode {
+    let mut paths = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--help" | "-h" => {
+                println!("Usage: ldtk-codegen <project.ldtk> [out.rs]");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let (ldtk, out) = match paths.as_slice() {
+        [ldtk] => (ldtk, None),
+        [ldtk, out] => (ldtk, Some(out)),
+        _ => {
+            eprintln!("Usage: ldtk-codegen <project.ldtk> [out.rs]");
+            return ExitCode::from(2);
+        }
+    };
+
+    let json = match std::fs::read(ldtk)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_slice::<LdtkJson>(&s).map_err(|e| e.to_string()))
+    {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to read the project {}: {}", ldtk.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let code = generate(&json);
+    match out {
+        Some(out) => {
+            if let Err(e) = std::fs::write(out, code) {
+                eprintln!("Failed to write {}: {}", out.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{}", code),
+    }
+
+    ExitCode::SUCCESS
+}
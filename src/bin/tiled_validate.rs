@@ -0,0 +1,91 @@
+//! Validate Tiled maps and tilesets before running the app.
+//!
+//! ```text
+//! cargo run --bin tiled-validate --features tiled,serializing -- \
+//!     --manifest classes.ron assets/tiled/tilemaps/*.tmx assets/tiled/tilesets/*.tsx
+//! ```
+//!
+//! The manifest is a ron file listing the registered classes:
+//!
+//! ```text
+//! (
+//!     objects: ["PlayerBundle", "DetectAreaBundle"],
+//!     custom_tiles: ["TileBundle"],
+//!     trigger_layers: ["Doors"],
+//! )
+//! ```
+//!
+//! Exits with `1` if any issue is found.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use bevy_entitiles::tiled::validate::{validate_map, validate_tileset, TiledValidationManifest};
+
+fn main() -> ExitCode {
+    let mut manifest = None;
+    let mut files = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--manifest" | "-m" => {
+                let Some(path) = args.next() else {
+                    eprintln!("Missing the path after {}", arg);
+                    return ExitCode::from(2);
+                };
+                manifest = Some(PathBuf::from(path));
+            }
+            "--help" | "-h" => {
+                println!("Usage: tiled-validate [--manifest <classes.ron>] <files.tmx|tsx>...");
+                return ExitCode::SUCCESS;
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    let manifest = match manifest {
+        Some(path) => match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str::<TiledValidationManifest>(&s).map_err(|e| e.to_string()))
+        {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Failed to read the manifest {}: {}", path.display(), e);
+                return ExitCode::from(2);
+            }
+        },
+        None => TiledValidationManifest::default(),
+    };
+
+    let mut failed = false;
+    for file in files {
+        let result = match file.extension().and_then(|e| e.to_str()) {
+            Some("tmx") => validate_map(&file, &manifest),
+            Some("tsx") => validate_tileset(&file, &manifest),
+            _ => {
+                eprintln!("{}: not a tmx or tsx file, skipped", file.display());
+                continue;
+            }
+        };
+
+        match result {
+            Ok(issues) if issues.is_empty() => println!("{}: ok", file.display()),
+            Ok(issues) => {
+                failed = true;
+                issues
+                    .iter()
+                    .for_each(|issue| println!("{}: {}", file.display(), issue));
+            }
+            Err(e) => {
+                failed = true;
+                println!("{}: {}", file.display(), e);
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
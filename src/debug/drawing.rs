@@ -1,27 +1,50 @@
 use bevy::{
-    color::palettes::css::{BLUE, GREEN, RED},
-    ecs::system::Query,
+    color::{
+        palettes::css::{BLUE, GREEN, RED},
+        LinearRgba,
+    },
+    ecs::{
+        entity::Entity,
+        system::{Query, Res},
+    },
     gizmos::gizmos::Gizmos,
     math::{Rect, Vec2},
+    utils::HashMap,
 };
 
 use crate::{
+    debug::DebugRenderChunks,
     math::{ext::RectFromTilemap, CameraAabb2d},
-    tilemap::map::{
-        TilePivot, TilemapAabbs, TilemapAxisFlip, TilemapSlotSize, TilemapStorage,
-        TilemapTransform, TilemapType,
+    tilemap::{
+        map::{
+            TilePivot, TilemapAabbs, TilemapAxisFlip, TilemapSlotSize, TilemapStorage,
+            TilemapTransform, TilemapType,
+        },
+        territory::{TerritoryBorders, TerritoryTilemap},
     },
 };
 
 #[cfg(feature = "algorithm")]
-use crate::algorithm::pathfinding::Path;
+use crate::{algorithm::pathfinding::Path, debug::PathfindingDebugConfig};
+#[cfg(feature = "algorithm")]
+use bevy::math::IVec2;
 
 #[cfg(feature = "serializing")]
 use crate::math::ext::RectTransformation;
 
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::{DataPhysicsTilemap, PhysicsTilemap, PhysicsTilemapFollow};
+
+/// Draw the chunks colored by their states:
+///
+/// - Green: Resident.
+/// - Red: Resident, but there's no render chunk for it.
+/// - Orange: Scheduled for save.
+/// - Blue: Scheduled for load.
 pub fn draw_chunk_aabb(
     mut gizmos: Gizmos,
     tilemaps: Query<(
+        Entity,
         &TilemapType,
         &TilePivot,
         &TilemapAxisFlip,
@@ -29,11 +52,46 @@ pub fn draw_chunk_aabb(
         &TilemapTransform,
         &TilemapStorage,
     )>,
+    render_chunks: Option<Res<DebugRenderChunks>>,
+    #[cfg(feature = "serializing")] save_cache: Option<
+        Res<crate::serializing::chunk::save::ChunkSaveCache>,
+    >,
+    #[cfg(feature = "serializing")] load_cache: Option<
+        Res<crate::serializing::chunk::load::ChunkLoadCache>,
+    >,
 ) {
-    for (ty, tile_pivot, axis_flip, slot_size, transform, storage) in tilemaps.iter() {
-        storage.storage.chunks.keys().for_each(|chunk| {
+    let render_chunks = render_chunks.as_ref().map(|c| c.0.read().unwrap());
+
+    for (entity, ty, tile_pivot, axis_flip, slot_size, transform, storage) in tilemaps.iter() {
+        let rendered = render_chunks.as_ref().map(|c| c.get(&entity));
+        #[cfg_attr(not(feature = "serializing"), allow(unused_mut))]
+        let mut chunks = storage
+            .storage
+            .chunks
+            .keys()
+            .map(|chunk| {
+                let is_rendered = rendered.map_or(true, |r| r.is_some_and(|r| r.contains(chunk)));
+                (*chunk, if is_rendered { GREEN } else { RED })
+            })
+            .collect::<HashMap<_, _>>();
+
+        #[cfg(feature = "serializing")]
+        if let Some(layers) = save_cache.as_ref().and_then(|c| c.0.get(&entity)) {
+            layers.values().flatten().for_each(|(chunk, _)| {
+                chunks.insert(*chunk, bevy::color::palettes::css::ORANGE);
+            });
+        }
+
+        #[cfg(feature = "serializing")]
+        if let Some(layers) = load_cache.as_ref().and_then(|c| c.0.get(&entity)) {
+            layers.values().flatten().for_each(|chunk| {
+                chunks.insert(*chunk, BLUE);
+            });
+        }
+
+        chunks.into_iter().for_each(|(chunk, color)| {
             let aabb = Rect::from_tilemap(
-                *chunk,
+                chunk,
                 storage.storage.chunk_size,
                 *ty,
                 tile_pivot.0,
@@ -45,7 +103,7 @@ pub fn draw_chunk_aabb(
                 aabb.center(),
                 0.,
                 Vec2::new(aabb.width(), aabb.height()),
-                GREEN,
+                color,
             );
         });
     }
@@ -62,9 +120,23 @@ pub fn draw_tilemap_aabb(mut gizmos: Gizmos, tilemaps: Query<&TilemapAabbs>) {
     });
 }
 
+/// The world position of the center of a slot.
+#[cfg(feature = "algorithm")]
+fn slot_center(
+    index: IVec2,
+    ty: TilemapType,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+) -> Vec2 {
+    crate::tilemap::coordinates::index_to_world(index, ty, transform, pivot, slot_size)
+        + transform.transform_vector(slot_size / 2.)
+}
+
 #[cfg(feature = "algorithm")]
 pub fn draw_path(
     mut gizmos: Gizmos,
+    config: Res<PathfindingDebugConfig>,
     path_query: Query<&Path>,
     tilemaps: Query<(
         &TilemapType,
@@ -73,23 +145,213 @@ pub fn draw_path(
         &TilemapSlotSize,
     )>,
 ) {
+    if !config.paths {
+        return;
+    }
+
     for path in path_query.iter() {
-        let (ty, transform, pivot, slot_size) = tilemaps.get(path.tilemap()).unwrap();
+        let Ok((ty, transform, pivot, slot_size)) = tilemaps.get(path.tilemap()) else {
+            continue;
+        };
+        let center = |index: &IVec2| slot_center(*index, *ty, transform, pivot.0, slot_size.0);
+
+        gizmos.linestrip_2d(
+            std::iter::once(path.origin())
+                .chain(path.iter().copied())
+                .map(|index| center(&index)),
+            bevy::color::palettes::css::GREEN_YELLOW,
+        );
+        gizmos.circle_2d(
+            center(&path.dest()),
+            (slot_size.0 * transform.scale.abs()).min_element() / 4.,
+            bevy::color::palettes::css::GREEN_YELLOW,
+        );
+    }
+}
+
+/// Draw the explored tiles in gray and the ones to explore in yellow.
+#[cfg(all(feature = "algorithm", not(feature = "multi-threaded")))]
+pub fn draw_path_queries(
+    mut gizmos: Gizmos,
+    config: Res<PathfindingDebugConfig>,
+    grids_query: Query<&crate::algorithm::pathfinding::PathGrid>,
+    tilemaps: Query<(
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+    )>,
+) {
+    use bevy::color::palettes::css::{GRAY, YELLOW};
+
+    if !config.queries {
+        return;
+    }
+
+    for grid in grids_query.iter() {
+        let Ok((ty, transform, pivot, slot_size)) = tilemaps.get(grid.tilemap) else {
+            continue;
+        };
+        let center = |index: IVec2| slot_center(index, *ty, transform, pivot.0, slot_size.0);
+        let radius = (slot_size.0 * transform.scale.abs()).min_element() / 6.;
+
+        grid.all_nodes
+            .values()
+            .filter(|node| node.g_cost != u32::MAX)
+            .for_each(|node| {
+                gizmos.circle_2d(center(node.index), radius, GRAY);
+            });
+        grid.to_explore.iter().for_each(|node| {
+            gizmos.circle_2d(center(node.index), radius, YELLOW);
+        });
+        gizmos.line_2d(center(grid.origin), center(grid.dest), YELLOW);
+    }
+}
+
+#[cfg(feature = "algorithm")]
+pub fn draw_path_costs(
+    mut gizmos: Gizmos,
+    config: Res<PathfindingDebugConfig>,
+    path_tilemaps: Option<Res<crate::algorithm::pathfinding::PathTilemaps>>,
+    tilemaps: Query<(
+        bevy::ecs::entity::Entity,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+    )>,
+) {
+    use bevy::color::{palettes::css::LIME, Mix};
 
-        for node in path.iter() {
-            gizmos.circle_2d(
-                crate::tilemap::coordinates::index_to_world(
-                    *node,
+    let Some(path_tilemaps) = path_tilemaps else {
+        return;
+    };
+    if !config.costs {
+        return;
+    }
+
+    for (entity, ty, transform, pivot, slot_size) in tilemaps.iter() {
+        path_tilemaps.with(entity, |path_tilemap| {
+            path_tilemap.storage.chunked_iter_some().for_each(
+                |(chunk_index, in_chunk_index, tile)| {
+                    let index = path_tilemap
+                        .storage
+                        .inverse_transform_index(chunk_index, in_chunk_index);
+                    let t = tile.cost as f32 / config.max_cost.max(1) as f32;
+                    let outline = crate::tilemap::coordinates::get_tile_collider_world(
+                        index,
+                        *ty,
+                        bevy::math::UVec2::ONE,
+                        transform,
+                        pivot.0,
+                        slot_size.0,
+                    );
+                    gizmos.linestrip_2d(
+                        outline.iter().chain(outline.first()).copied(),
+                        LinearRgba::from(LIME).mix(&LinearRgba::RED, t.min(1.)),
+                    );
+                },
+            );
+        });
+    }
+}
+
+/// Draw the outlines of the physics colliders.
+///
+/// Rigid bodies are orange, sensors are aqua and the others are yellow.
+/// Colliders and data tilemaps that are not spawned yet are gray.
+#[cfg(feature = "physics")]
+pub fn draw_physics_colliders(
+    mut gizmos: Gizmos,
+    physics_tilemaps_query: Query<(
+        &PhysicsTilemap,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+        bevy::ecs::query::Has<PhysicsTilemapFollow>,
+        bevy::ecs::query::Has<crate::tilemap::map::SyncWithGlobalTransform>,
+    )>,
+    data_tilemaps_query: Query<(
+        &DataPhysicsTilemap,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+    )>,
+    colliders_query: Query<(
+        bevy::ecs::query::Has<avian2d::prelude::RigidBody>,
+        bevy::ecs::query::Has<avian2d::prelude::Sensor>,
+    )>,
+) {
+    use bevy::color::palettes::css::{AQUA, GRAY, ORANGE, YELLOW};
+
+    physics_tilemaps_query.iter().for_each(
+        |(physics_tilemap, ty, transform, pivot, slot_size, follow, synced)| {
+            physics_tilemap.storage.chunked_iter_some().for_each(
+                |(chunk_index, in_chunk_index, collider)| {
+                    let Some(packed) = physics_tilemap.data.get_elem(
+                        physics_tilemap
+                            .storage
+                            .inverse_transform_index(chunk_index, in_chunk_index),
+                    ) else {
+                        return;
+                    };
+                    let color = match colliders_query.get(*collider) {
+                        Ok((_, true)) => AQUA,
+                        Ok((true, false)) => ORANGE,
+                        _ => YELLOW,
+                    };
+
+                    // Colliders of following tilemaps are relative to the tilemap,
+                    // and already scaled.
+                    let verts = packed.collider.as_verts().iter().map(|v| {
+                        if follow || synced {
+                            transform.apply_translation(transform.apply_rotation(*v))
+                        } else {
+                            *v
+                        }
+                    });
+                    gizmos.linestrip_2d(verts.clone().chain(verts.take(1)), color);
+                },
+            );
+
+            physics_tilemap.spawn_queue.iter().for_each(|(aabb, _, _)| {
+                let verts = crate::tilemap::coordinates::get_tile_collider_world(
+                    aabb.origin,
                     *ty,
+                    aabb.extent,
                     transform,
                     pivot.0,
                     slot_size.0,
-                ),
-                10.,
-                bevy::color::palettes::css::GREEN_YELLOW,
-            );
-        }
-    }
+                );
+                gizmos.linestrip_2d(verts.iter().chain(verts.first()).copied(), GRAY);
+            });
+        },
+    );
+
+    data_tilemaps_query
+        .iter()
+        .for_each(|(data_tilemap, ty, transform, pivot, slot_size)| {
+            for y in 0..data_tilemap.size.y {
+                for x in 0..data_tilemap.size.x {
+                    let index = bevy::math::UVec2 { x, y };
+                    if data_tilemap.get_or_air(index) == data_tilemap.air {
+                        continue;
+                    }
+
+                    let verts = crate::tilemap::coordinates::get_tile_collider_world(
+                        index.as_ivec2() + data_tilemap.origin,
+                        *ty,
+                        bevy::math::UVec2::ONE,
+                        transform,
+                        pivot.0,
+                        slot_size.0,
+                    );
+                    gizmos.linestrip_2d(verts.iter().chain(verts.first()).copied(), GRAY);
+                }
+            }
+        });
 }
 
 pub fn draw_axis(mut gizmos: Gizmos) {
@@ -108,19 +370,40 @@ pub fn draw_camera_aabb(mut gizmos: Gizmos, camera_aabb: Query<&CameraAabb2d>) {
     });
 }
 
+pub fn draw_territory_borders(
+    mut gizmos: Gizmos,
+    tilemaps_query: Query<(&TerritoryTilemap, &TerritoryBorders)>,
+) {
+    tilemaps_query.iter().for_each(|(territory, borders)| {
+        borders.iter().for_each(|(owner, loops)| {
+            let color = territory.owner_color(*owner).unwrap_or(LinearRgba::WHITE);
+            loops.iter().for_each(|l| {
+                gizmos.linestrip_2d(l.iter().chain(l.first()).copied(), color);
+            });
+        });
+    });
+}
+
 #[cfg(feature = "serializing")]
 pub fn draw_updater_aabbs(
     mut gizmos: Gizmos,
-    cameras_query: Query<(
-        &CameraAabb2d,
+    updaters_query: Query<(
+        bevy::ecs::entity::Entity,
         &crate::tilemap::chunking::camera::CameraChunkUpdater,
     )>,
+    cameras_query: Query<&CameraAabb2d>,
 ) {
-    cameras_query.iter().for_each(|(cam_aabb, cam_updater)| {
+    updaters_query.iter().for_each(|(entity, cam_updater)| {
+        let Ok(cam_aabb) = cameras_query.get(cam_updater.camera().unwrap_or(entity)) else {
+            return;
+        };
+
         let detect_aabb =
             cam_aabb.with_scale(Vec2::splat(cam_updater.detect_scale), Vec2::splat(0.5));
         let update_aabb =
             cam_aabb.with_scale(Vec2::splat(cam_updater.update_scale), Vec2::splat(0.5));
+        let unload_aabb =
+            cam_aabb.with_scale(Vec2::splat(cam_updater.unload_scale), Vec2::splat(0.5));
 
         gizmos.rect_2d(
             detect_aabb.center(),
@@ -134,5 +417,11 @@ pub fn draw_updater_aabbs(
             Vec2::new(update_aabb.width(), update_aabb.height()),
             bevy::color::palettes::css::SILVER,
         );
+        gizmos.rect_2d(
+            unload_aabb.center(),
+            0.,
+            Vec2::new(unload_aabb.width(), unload_aabb.height()),
+            bevy::color::palettes::css::GRAY,
+        );
     });
 }
@@ -0,0 +1,174 @@
+use std::fmt::Write;
+
+use bevy::{
+    color::palettes::css::WHITE,
+    ecs::{
+        entity::Entity,
+        query::With,
+        system::{Query, ResMut, Resource},
+    },
+    gizmos::gizmos::Gizmos,
+    math::{IVec2, UVec2},
+    render::camera::Camera,
+    transform::components::GlobalTransform,
+    window::{PrimaryWindow, Window},
+};
+
+use crate::tilemap::{
+    coordinates,
+    map::{TilePivot, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType},
+    tile::{Tile, TileTexture},
+};
+
+#[cfg(feature = "algorithm")]
+use crate::algorithm::pathfinding::PathTilemaps;
+#[cfg(feature = "algorithm")]
+use bevy::ecs::system::Res;
+
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::PhysicsTilemap;
+
+/// Inspect the tile under the cursor.
+///
+/// The hovered tile is outlined, and the information of it is logged at the debug level
+/// when the hovered tile changes. Use [`TileInspector::info`] to show the information
+/// in your own UI.
+///
+/// Disabled by default, set [`TileInspector::enabled`] to turn it on.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TileInspector {
+    pub enabled: bool,
+    pub(crate) hovered: Option<(Entity, IVec2)>,
+    pub(crate) info: String,
+}
+
+impl TileInspector {
+    /// The tilemap and the index of the hovered tile.
+    #[inline]
+    pub fn hovered(&self) -> Option<(Entity, IVec2)> {
+        self.hovered
+    }
+
+    /// The information of the hovered tile. Empty if nothing is hovered.
+    #[inline]
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+}
+
+pub fn tile_inspector(
+    mut gizmos: Gizmos,
+    mut inspector: ResMut<TileInspector>,
+    windows_query: Query<&Window, With<PrimaryWindow>>,
+    cameras_query: Query<(&Camera, &GlobalTransform)>,
+    tilemaps_query: Query<(
+        Entity,
+        &TilemapStorage,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+    )>,
+    tiles_query: Query<&Tile>,
+    #[cfg(feature = "algorithm")] path_tilemaps: Option<Res<PathTilemaps>>,
+    #[cfg(feature = "physics")] physics_tilemaps_query: Query<&PhysicsTilemap>,
+) {
+    if !inspector.enabled {
+        return;
+    }
+
+    let cursor = windows_query
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .and_then(|cursor| {
+            cameras_query
+                .iter()
+                .find(|(camera, _)| camera.is_active)
+                .and_then(|(camera, transform)| camera.viewport_to_world_2d(transform, cursor))
+        });
+
+    // The topmost tilemap that has a tile under the cursor.
+    let hovered = cursor.and_then(|cursor| {
+        tilemaps_query
+            .iter()
+            .filter_map(|(entity, storage, ty, transform, pivot, slot_size)| {
+                let index =
+                    coordinates::world_to_index(cursor, *ty, transform, pivot.0, slot_size.0);
+                storage
+                    .get(index)
+                    .map(|tile| (entity, index, tile, ty, transform, pivot, slot_size))
+            })
+            .max_by(|a, b| a.4.z_index.total_cmp(&b.4.z_index))
+    });
+
+    let Some((tilemap, index, tile, ty, transform, pivot, slot_size)) = hovered else {
+        inspector.hovered = None;
+        inspector.info.clear();
+        return;
+    };
+
+    let outline = coordinates::get_tile_collider_world(
+        index,
+        *ty,
+        UVec2::ONE,
+        transform,
+        pivot.0,
+        slot_size.0,
+    );
+    gizmos.linestrip_2d(outline.iter().chain(outline.first()).copied(), WHITE);
+
+    if inspector.hovered == Some((tilemap, index)) {
+        return;
+    }
+
+    let mut info = format!("Tile {} on tilemap {} ({})", index, tilemap, tile);
+    if let Ok(tile) = tiles_query.get(tile) {
+        match &tile.texture {
+            TileTexture::Static(layers) => {
+                layers.iter().enumerate().for_each(|(i, layer)| {
+                    #[cfg(feature = "atlas")]
+                    let _ = write!(info, "\nLayer {}: texture {},", i, layer.texture_index);
+                    #[cfg(not(feature = "atlas"))]
+                    let _ = write!(info, "\nLayer {}:", i);
+                    let _ = write!(
+                        info,
+                        " atlas {}, flip {:#04b}",
+                        layer.atlas_index,
+                        layer.flip.bits()
+                    );
+                });
+            }
+            TileTexture::Animated(anim) => {
+                let _ = write!(
+                    info,
+                    "\nAnimation: start {}, length {}, fps {}, {:?}",
+                    anim.start, anim.length, anim.fps, anim.mode
+                );
+            }
+        }
+        let _ = write!(info, "\nTint {:?}, emissive {}", tile.tint, tile.emissive);
+    }
+
+    #[cfg(feature = "algorithm")]
+    if let Some(cost) = path_tilemaps
+        .as_ref()
+        .and_then(|p| p.with(tilemap, |t| t.get(index).map(|t| t.cost)))
+    {
+        let _ = write!(info, "\nPath cost {:?}", cost);
+    }
+
+    #[cfg(feature = "physics")]
+    if let Ok(physics_tilemap) = physics_tilemaps_query.get(tilemap) {
+        if let Some(collider) = physics_tilemap.get(index) {
+            let _ = write!(info, "\nCollider {}", collider);
+        }
+        if let Some(data) = physics_tilemap.get_data(index) {
+            let _ = write!(info, "\nPhysics data {}", data);
+        }
+    }
+
+    bevy::log::debug!("{}", info);
+    inspector.hovered = Some((tilemap, index));
+    inspector.info = info;
+}
@@ -1,10 +1,21 @@
+use std::sync::{Arc, RwLock};
+
 use bevy::{
     app::{Plugin, Update},
-    ecs::system::Resource,
-    math::Vec2,
+    ecs::{
+        entity::EntityHashMap,
+        schedule::IntoSystemConfigs,
+        system::{Res, Resource},
+    },
+    math::{IVec2, Vec2},
+    render::{Render, RenderApp, RenderSet},
+    utils::HashSet,
 };
 
+use crate::render::chunk::RenderChunkStorage;
+
 pub mod drawing;
+pub mod inspector;
 
 pub struct EntiTilesDebugPlugin;
 
@@ -17,15 +28,87 @@ impl Plugin for EntiTilesDebugPlugin {
                 drawing::draw_tilemap_aabb,
                 drawing::draw_axis,
                 drawing::draw_camera_aabb,
-                // #[cfg(feature = "algorithm")]
-                // drawing::draw_path,
+                drawing::draw_territory_borders,
+                inspector::tile_inspector,
+                #[cfg(feature = "algorithm")]
+                drawing::draw_path,
+                #[cfg(all(feature = "algorithm", not(feature = "multi-threaded")))]
+                drawing::draw_path_queries,
+                #[cfg(feature = "algorithm")]
+                drawing::draw_path_costs,
                 #[cfg(feature = "serializing")]
                 drawing::draw_updater_aabbs,
+                #[cfg(feature = "physics")]
+                drawing::draw_physics_colliders,
             ),
         );
 
         #[cfg(feature = "debug")]
-        app.init_resource::<CameraAabbScale>();
+        app.init_resource::<CameraAabbScale>()
+            .init_resource::<inspector::TileInspector>();
+
+        #[cfg(feature = "algorithm")]
+        app.init_resource::<PathfindingDebugConfig>();
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            let render_chunks = DebugRenderChunks::default();
+            render_app
+                .add_systems(Render, collect_render_chunks.in_set(RenderSet::Cleanup))
+                .insert_resource(render_chunks.clone());
+            app.insert_resource(render_chunks);
+        }
+    }
+}
+
+/// The chunks that exist in the render world, shared with the main world
+/// so the missing render chunks can be drawn.
+///
+/// **Notice**: This is one frame behind, so newly added chunks may be shown as missing for a frame.
+#[derive(Resource, Default, Clone)]
+pub struct DebugRenderChunks(pub(crate) Arc<RwLock<EntityHashMap<HashSet<IVec2>>>>);
+
+pub fn collect_render_chunks(
+    render_chunks: Res<RenderChunkStorage>,
+    debug_render_chunks: Res<DebugRenderChunks>,
+) {
+    let mut chunks = debug_render_chunks.0.write().unwrap();
+    chunks.clear();
+    chunks.extend(
+        render_chunks
+            .value
+            .iter()
+            .map(|(tilemap, c)| (*tilemap, c.value.keys().copied().collect())),
+    );
+}
+
+/// Controls what to draw for pathfinding.
+#[cfg(feature = "algorithm")]
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PathfindingDebugConfig {
+    /// Draw every `Path` as a polyline.
+    pub paths: bool,
+    /// Draw the open and closed sets of the queries that are not finished.
+    ///
+    /// **Notice**: This only works when `multi-threaded` is disabled,
+    /// as the tasks can't be inspected.
+    pub queries: bool,
+    /// Color the path tiles by their costs, from green to red.
+    ///
+    /// **Notice**: Every path tile is drawn, which can be slow for large tilemaps.
+    pub costs: bool,
+    /// Tiles costing this or more are red in the heat map.
+    pub max_cost: u32,
+}
+
+#[cfg(feature = "algorithm")]
+impl Default for PathfindingDebugConfig {
+    fn default() -> Self {
+        Self {
+            paths: true,
+            queries: true,
+            costs: false,
+            max_cost: 10,
+        }
     }
 }
 
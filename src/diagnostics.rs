@@ -0,0 +1,130 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    ecs::{
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Query, Res, ResMut, Resource},
+    },
+    render::{ExtractSchedule, Render, RenderApp, RenderSet},
+    utils::Instant,
+};
+
+use crate::{
+    render::extract,
+    tilemap::{map::TilemapStorage, tile::Tile},
+};
+
+/// Publishes the statistics of tilemaps into bevy diagnostics.
+///
+/// Add `LogDiagnosticsPlugin` to see them in the console.
+///
+/// **Notice**: Render statistics are one frame behind.
+pub struct TilemapDiagnosticsPlugin;
+
+impl TilemapDiagnosticsPlugin {
+    /// The number of tiles.
+    pub const TILE_COUNT: DiagnosticPath = DiagnosticPath::const_new("tilemap/tile_count");
+    /// The number of chunks in all the tilemap storages.
+    pub const CHUNK_COUNT: DiagnosticPath = DiagnosticPath::const_new("tilemap/chunk_count");
+    /// The number of render chunks whose meshes are rebuilt in the frame.
+    pub const DIRTY_CHUNKS: DiagnosticPath = DiagnosticPath::const_new("tilemap/dirty_chunks");
+    /// The bytes of the meshes and buffers uploaded to the GPU in the frame.
+    pub const UPLOADED_BYTES: DiagnosticPath = DiagnosticPath::const_new("tilemap/uploaded_bytes");
+    /// The time spent extracting the changed tiles, in milliseconds.
+    pub const EXTRACTION_TIME: DiagnosticPath =
+        DiagnosticPath::const_new("tilemap/extraction_time");
+}
+
+impl Plugin for TilemapDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(Self::TILE_COUNT))
+            .register_diagnostic(Diagnostic::new(Self::CHUNK_COUNT))
+            .register_diagnostic(Diagnostic::new(Self::DIRTY_CHUNKS))
+            .register_diagnostic(Diagnostic::new(Self::UPLOADED_BYTES).with_suffix("B"))
+            .register_diagnostic(Diagnostic::new(Self::EXTRACTION_TIME).with_suffix("ms"))
+            .add_systems(Update, tilemap_diagnostics);
+
+        let shared = SharedTilemapRenderStats::default();
+        app.insert_resource(shared.clone());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .add_systems(
+                ExtractSchedule,
+                (
+                    begin_extraction.before(extract::extract_tiles),
+                    end_extraction.after(extract::extract_tiles),
+                ),
+            )
+            .add_systems(Render, share_render_stats.in_set(RenderSet::Cleanup))
+            .insert_resource(shared)
+            .init_resource::<TilemapRenderStats>();
+    }
+}
+
+/// The statistics collected in the render world during a frame.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TilemapRenderStats {
+    pub dirty_chunks: u32,
+    pub uploaded_bytes: u64,
+    pub extraction_time: Duration,
+    pub(crate) extraction_start: Option<Instant>,
+}
+
+/// The render statistics of the last frame, shared between the main and render world.
+#[derive(Resource, Default, Clone)]
+pub struct SharedTilemapRenderStats(pub(crate) Arc<Mutex<TilemapRenderStats>>);
+
+pub fn begin_extraction(mut stats: ResMut<TilemapRenderStats>) {
+    *stats = TilemapRenderStats {
+        extraction_start: Some(Instant::now()),
+        ..Default::default()
+    };
+}
+
+pub fn end_extraction(mut stats: ResMut<TilemapRenderStats>) {
+    if let Some(start) = stats.extraction_start.take() {
+        stats.extraction_time = start.elapsed();
+    }
+}
+
+pub fn share_render_stats(stats: Res<TilemapRenderStats>, shared: Res<SharedTilemapRenderStats>) {
+    *shared.0.lock().unwrap() = stats.clone();
+}
+
+pub fn tilemap_diagnostics(
+    mut diagnostics: Diagnostics,
+    tiles_query: Query<(), With<Tile>>,
+    storages_query: Query<&TilemapStorage>,
+    shared: Res<SharedTilemapRenderStats>,
+) {
+    diagnostics.add_measurement(&TilemapDiagnosticsPlugin::TILE_COUNT, || {
+        tiles_query.iter().len() as f64
+    });
+    diagnostics.add_measurement(&TilemapDiagnosticsPlugin::CHUNK_COUNT, || {
+        storages_query
+            .iter()
+            .map(|storage| storage.storage.chunks.len())
+            .sum::<usize>() as f64
+    });
+
+    let stats = shared.0.lock().unwrap().clone();
+    diagnostics.add_measurement(&TilemapDiagnosticsPlugin::DIRTY_CHUNKS, || {
+        stats.dirty_chunks as f64
+    });
+    diagnostics.add_measurement(&TilemapDiagnosticsPlugin::UPLOADED_BYTES, || {
+        stats.uploaded_bytes as f64
+    });
+    diagnostics.add_measurement(&TilemapDiagnosticsPlugin::EXTRACTION_TIME, || {
+        stats.extraction_time.as_secs_f64() * 1000.
+    });
+}
@@ -0,0 +1,343 @@
+//! Conversions from `bevy_ecs_tilemap`, so existing projects can migrate incrementally
+//! or use both crates during the transition.
+//!
+//! Use [`ImportEcsTilemap`] to convert a whole tilemap, or the functions in this module
+//! to convert the parts you need.
+
+use bevy::{
+    asset::{Assets, Handle},
+    color::LinearRgba,
+    ecs::{
+        entity::Entity,
+        system::Commands,
+        world::{Command, CommandQueue, World},
+    },
+    log::error,
+    math::{IVec2, UVec2, Vec2},
+    render::{render_resource::FilterMode, texture::Image},
+    transform::components::Transform,
+};
+use bevy_ecs_tilemap::{
+    map::{
+        HexCoordSystem, IsoCoordSystem, TilemapGridSize, TilemapTexture as EcsTilemapTexture,
+        TilemapTileSize, TilemapType as EcsTilemapType,
+    },
+    tiles::{
+        TileColor, TileFlip as EcsTileFlip, TilePos, TileStorage, TileTextureIndex, TileVisible,
+    },
+};
+
+use crate::{
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            TilePivot, TileRenderSize, TilemapSlotSize, TilemapStorage, TilemapTexture,
+            TilemapTextureDescriptor, TilemapTextures, TilemapTransform, TilemapType,
+        },
+        tile::{TileBuilder, TileFlip, TileLayer},
+    },
+    DEFAULT_CHUNK_SIZE,
+};
+
+/// Convert the tilemap type.
+///
+/// Returns `None` for staggered isometric and column based hexagonal tilemaps,
+/// as there are no equivalents.
+pub fn convert_type(ty: &EcsTilemapType, grid_size: &TilemapGridSize) -> Option<TilemapType> {
+    match ty {
+        EcsTilemapType::Square => Some(TilemapType::Square),
+        EcsTilemapType::Isometric(IsoCoordSystem::Diamond) => Some(TilemapType::Isometric),
+        EcsTilemapType::Hexagon(
+            HexCoordSystem::Row | HexCoordSystem::RowEven | HexCoordSystem::RowOdd,
+        ) => Some(TilemapType::Hexagonal((grid_size.y / 2.) as u32)),
+        _ => None,
+    }
+}
+
+/// Convert a tile position into an index, so that the tile is rendered at the same place.
+pub fn convert_index(pos: &TilePos, ty: &EcsTilemapType) -> Option<IVec2> {
+    let (x, y) = (pos.x as i32, pos.y as i32);
+    match ty {
+        EcsTilemapType::Square => Some(IVec2::new(x, y)),
+        // The x axis of `bevy_ecs_tilemap` points to the bottom right.
+        EcsTilemapType::Isometric(IsoCoordSystem::Diamond) => Some(IVec2::new(y, -x)),
+        // Our hexagonal tilemaps use axial coordinates with the y axis pointing to the top left.
+        EcsTilemapType::Hexagon(HexCoordSystem::Row) => Some(IVec2::new(x + y, y)),
+        EcsTilemapType::Hexagon(HexCoordSystem::RowOdd) => {
+            Some(IVec2::new(x - (y - (y & 1)) / 2 + y, y))
+        }
+        EcsTilemapType::Hexagon(HexCoordSystem::RowEven) => {
+            Some(IVec2::new(x - (y + (y & 1)) / 2 + y, y))
+        }
+        _ => None,
+    }
+}
+
+/// The inverse of [`convert_index`].
+///
+/// Returns `None` if the tile position would be negative.
+pub fn convert_index_back(index: IVec2, ty: &EcsTilemapType) -> Option<TilePos> {
+    let (x, y) = match ty {
+        EcsTilemapType::Square => (index.x, index.y),
+        EcsTilemapType::Isometric(IsoCoordSystem::Diamond) => (-index.y, index.x),
+        EcsTilemapType::Hexagon(HexCoordSystem::Row) => (index.x - index.y, index.y),
+        EcsTilemapType::Hexagon(HexCoordSystem::RowOdd) => {
+            let q = index.x - index.y;
+            (q + (index.y - (index.y & 1)) / 2, index.y)
+        }
+        EcsTilemapType::Hexagon(HexCoordSystem::RowEven) => {
+            let q = index.x - index.y;
+            (q + (index.y + (index.y & 1)) / 2, index.y)
+        }
+        _ => return None,
+    };
+
+    (x >= 0 && y >= 0).then(|| TilePos {
+        x: x as u32,
+        y: y as u32,
+    })
+}
+
+/// Convert the texture.
+///
+/// Returns `None` if the images are not loaded yet, or the texture is a `TextureContainer`.
+pub fn convert_texture(
+    texture: &EcsTilemapTexture,
+    tile_size: &TilemapTileSize,
+    image_assets: &Assets<Image>,
+    filter_mode: FilterMode,
+) -> Option<TilemapTextures> {
+    let tile_size = UVec2::new(tile_size.x as u32, tile_size.y as u32);
+    let convert = |handle: &Handle<Image>| {
+        image_assets.get(handle).map(|image| {
+            TilemapTexture::new(
+                handle.clone(),
+                TilemapTextureDescriptor::new(image.size(), tile_size),
+            )
+        })
+    };
+
+    let textures = match texture {
+        EcsTilemapTexture::Single(handle) => vec![convert(handle)?],
+        EcsTilemapTexture::Vector(handles) => {
+            handles.iter().map(convert).collect::<Option<Vec<_>>>()?
+        }
+        _ => return None,
+    };
+
+    Some(TilemapTextures::new(textures, filter_mode))
+}
+
+/// Convert a tile.
+///
+/// **Notice**: Diagonal flipping is not supported and will be ignored.
+pub fn convert_tile(
+    texture: &EcsTilemapTexture,
+    texture_index: &TileTextureIndex,
+    flip: Option<&EcsTileFlip>,
+    color: Option<&TileColor>,
+) -> TileBuilder {
+    let mut tile_flip = TileFlip::NONE;
+    if let Some(flip) = flip {
+        tile_flip.set(TileFlip::HORIZONTAL, flip.x);
+        tile_flip.set(TileFlip::VERTICAL, flip.y);
+    }
+
+    // Each image in a `Vector` texture is a single tile.
+    let layer = match texture {
+        EcsTilemapTexture::Vector(_) if cfg!(feature = "atlas") => {
+            TileLayer::new(texture_index.0 as i32, 0, tile_flip)
+        }
+        _ => TileLayer::new(0, texture_index.0 as i32, tile_flip),
+    };
+
+    let builder = TileBuilder::new().with_layer(0, layer);
+    match color {
+        Some(color) => builder.with_tint(LinearRgba::from(color.0)),
+        None => builder,
+    }
+}
+
+/// Spawn a tilemap from a `bevy_ecs_tilemap` tilemap.
+///
+/// The source tilemap is kept unless [`ImportEcsTilemap::despawn_source`] is called,
+/// so both can exist during the transition.
+///
+/// **Notice**: The images of the source tilemap must be loaded, otherwise nothing will happen.
+pub struct ImportEcsTilemap {
+    source: Entity,
+    target: Option<Entity>,
+    filter_mode: FilterMode,
+    despawn_source: bool,
+}
+
+impl ImportEcsTilemap {
+    pub fn new(source: Entity) -> Self {
+        Self {
+            source,
+            target: None,
+            filter_mode: FilterMode::Nearest,
+            despawn_source: false,
+        }
+    }
+
+    /// Insert the tilemap into `target` instead of spawning a new entity.
+    pub fn with_target(mut self, target: Entity) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_filter_mode(mut self, filter_mode: FilterMode) -> Self {
+        self.filter_mode = filter_mode;
+        self
+    }
+
+    /// Despawn the source tilemap and its tiles after importing.
+    pub fn despawn_source(mut self) -> Self {
+        self.despawn_source = true;
+        self
+    }
+}
+
+impl Command for ImportEcsTilemap {
+    fn apply(self, world: &mut World) {
+        let Ok((tile_storage, ecs_ty, grid_size, tile_size, ecs_texture, source_transform)) = world
+            .query::<(
+                &TileStorage,
+                &EcsTilemapType,
+                &TilemapGridSize,
+                &TilemapTileSize,
+                &EcsTilemapTexture,
+                Option<&Transform>,
+            )>()
+            .get(world, self.source)
+        else {
+            error!("{:?} is not a bevy_ecs_tilemap tilemap!", self.source);
+            return;
+        };
+
+        let Some(ty) = convert_type(ecs_ty, grid_size) else {
+            error!("Tilemap type {:?} is not supported!", ecs_ty);
+            return;
+        };
+
+        let Some(textures) = convert_texture(
+            ecs_texture,
+            tile_size,
+            world.resource::<Assets<Image>>(),
+            self.filter_mode,
+        ) else {
+            error!(
+                "The texture of {:?} is not loaded or not supported!",
+                self.source
+            );
+            return;
+        };
+
+        let tile_entities = tile_storage.iter().flatten().copied().collect::<Vec<_>>();
+        let tiles = tile_entities
+            .iter()
+            .filter_map(|tile| {
+                let tile = world.get_entity(*tile)?;
+                if tile.get::<TileVisible>().is_some_and(|v| !v.0) {
+                    return None;
+                }
+
+                Some((
+                    convert_index(tile.get::<TilePos>()?, ecs_ty)?,
+                    convert_tile(
+                        ecs_texture,
+                        tile.get::<TileTextureIndex>()?,
+                        tile.get::<EcsTileFlip>(),
+                        tile.get::<TileColor>(),
+                    ),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let transform = source_transform
+            .map(|t| TilemapTransform {
+                translation: t.translation.truncate(),
+                z_index: t.translation.z,
+                ..Default::default()
+            })
+            .unwrap_or_default();
+        let slot_size = Vec2::new(grid_size.x, grid_size.y);
+        let tile_render_size = Vec2::new(tile_size.x, tile_size.y);
+
+        let target = self.target.unwrap_or_else(|| world.spawn_empty().id());
+        let mut storage = TilemapStorage::new(DEFAULT_CHUNK_SIZE, target);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        for (index, tile) in tiles {
+            storage.set(&mut commands, index, tile);
+        }
+        if self.despawn_source {
+            tile_entities
+                .into_iter()
+                .for_each(|tile| commands.entity(tile).despawn());
+            commands.entity(self.source).despawn();
+        }
+        queue.apply(world);
+
+        let textures = world
+            .resource_mut::<Assets<TilemapTextures>>()
+            .add(textures);
+        let material = world
+            .resource_mut::<Assets<StandardTilemapMaterial>>()
+            .add(StandardTilemapMaterial::default());
+        world.entity_mut(target).insert(StandardTilemapBundle {
+            ty,
+            storage,
+            transform,
+            tile_render_size: TileRenderSize(tile_render_size),
+            slot_size: TilemapSlotSize(slot_size),
+            // `bevy_ecs_tilemap` places the center of the tiles at their positions.
+            tile_pivot: TilePivot(Vec2::splat(0.5)),
+            textures,
+            material,
+            ..Default::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::IVec2;
+    use bevy_ecs_tilemap::{
+        map::{HexCoordSystem, IsoCoordSystem, TilemapType as EcsTilemapType},
+        tiles::TilePos,
+    };
+
+    use super::{convert_index, convert_index_back};
+
+    #[test]
+    fn test_index_round_trip() {
+        for ty in [
+            EcsTilemapType::Square,
+            EcsTilemapType::Isometric(IsoCoordSystem::Diamond),
+            EcsTilemapType::Hexagon(HexCoordSystem::Row),
+            EcsTilemapType::Hexagon(HexCoordSystem::RowOdd),
+            EcsTilemapType::Hexagon(HexCoordSystem::RowEven),
+        ] {
+            for y in 0..4 {
+                for x in 0..4 {
+                    let pos = TilePos { x, y };
+                    let index = convert_index(&pos, &ty).unwrap();
+                    let back = convert_index_back(index, &ty).unwrap();
+                    assert_eq!((back.x, back.y), (x, y), "{:?}", ty);
+                }
+            }
+        }
+
+        // Odd rows are shifted to the right.
+        assert_eq!(
+            convert_index(
+                &TilePos { x: 0, y: 1 },
+                &EcsTilemapType::Hexagon(HexCoordSystem::RowOdd)
+            ),
+            Some(IVec2::new(1, 1))
+        );
+    }
+}
@@ -1,16 +1,27 @@
 use bevy::{
     app::App,
     ecs::{bundle::Bundle, component::Component},
+    reflect::{FromReflect, GetTypeRegistration, Reflect, TypePath},
 };
 
-use crate::ldtk::traits::{
-    LdtkEntity, LdtkEntityRegistry, LdtkEntityTag, LdtkEntityTagRegistry, PhantomLdtkEntity,
-    PhantomLdtkEntityTag,
+use crate::ldtk::{
+    persistence::LdtkPersistentComponents,
+    traits::{
+        LdtkEntity, LdtkEntityRegistry, LdtkEntityTag, LdtkEntityTagRegistry, PhantomLdtkEntity,
+        PhantomLdtkEntityTag,
+    },
 };
 
 pub trait LdtkApp {
     fn register_ldtk_entity<T: LdtkEntity + Bundle>(&mut self, ident: &str) -> &mut App;
     fn register_ldtk_entity_tag<T: LdtkEntityTag + Component>(&mut self, tag: &str) -> &mut App;
+    /// Capture this component of [`PersistentLdtkEntity`](crate::ldtk::persistence::PersistentLdtkEntity)s
+    /// when their level unloads, and restore it when the level loads again.
+    fn register_persistent_component<
+        T: Component + Reflect + FromReflect + TypePath + GetTypeRegistration,
+    >(
+        &mut self,
+    ) -> &mut App;
 }
 
 impl LdtkApp for App {
@@ -49,4 +60,15 @@ impl LdtkApp for App {
 
         self
     }
+
+    fn register_persistent_component<
+        T: Component + Reflect + FromReflect + TypePath + GetTypeRegistration,
+    >(
+        &mut self,
+    ) -> &mut App {
+        self.world_mut()
+            .get_resource_or_insert_with(LdtkPersistentComponents::default)
+            .register::<T>();
+        self.register_type::<T>()
+    }
 }
@@ -0,0 +1,194 @@
+//! Generate Rust code from the definitions of a LDtk project, so identifiers
+//! are checked by the compiler instead of being typed as strings.
+//!
+//! Run the `ldtk-codegen` binary whenever the project changes, and keep the output in your crate:
+//!
+//! ```text
+//! cargo run --bin ldtk-codegen --features ldtk -- assets/ldtk/grid_vania.ldtk src/grid_vania.rs
+//! ```
+//!
+//! ```ignore
+//! mod grid_vania;
+//!
+//! app.register_ldtk_entity::<Player>(grid_vania::entities::PLAYER);
+//! ```
+//!
+//! The generated file contains:
+//! - `levels`, `layers` and `entities` modules with a `&str` const for each identifier.
+//! - An enum deriving [`LdtkEnum`](crate::ldtk::traits::LdtkEnum) for each LDtk enum,
+//! including the external ones.
+
+use std::fmt::Write;
+
+use bevy::utils::HashSet;
+
+use crate::ldtk::json::{definitions::EnumDef, LdtkJson};
+
+/// Generate the Rust code for the definitions of `json`.
+pub fn generate(json: &LdtkJson) -> String {
+    let mut code =
+        String::from("// Generated by bevy_entitiles from a LDtk project. Do not edit.\n");
+
+    let levels = json
+        .levels
+        .iter()
+        .chain(json.worlds.iter().flat_map(|world| world.levels.iter()))
+        .map(|level| level.identifier.as_str());
+    write_consts(&mut code, "levels", levels);
+
+    let layers = json
+        .defs
+        .layers
+        .iter()
+        .map(|layer| layer.identifier.as_str());
+    write_consts(&mut code, "layers", layers);
+
+    let entities = json
+        .defs
+        .entities
+        .iter()
+        .map(|entity| entity.identifier.as_str());
+    write_consts(&mut code, "entities", entities);
+
+    json.defs
+        .enums
+        .iter()
+        .chain(json.defs.external_enums.iter())
+        .for_each(|def| write_enum(&mut code, def));
+
+    code
+}
+
+fn write_consts<'a>(code: &mut String, module: &str, identifiers: impl Iterator<Item = &'a str>) {
+    let mut names = HashSet::new();
+
+    writeln!(code, "\n#[allow(dead_code)]\npub mod {} {{", module).unwrap();
+    for identifier in identifiers {
+        let name = to_screaming_snake_case(identifier);
+        if names.insert(name.clone()) {
+            writeln!(code, "    pub const {}: &str = {:?};", name, identifier).unwrap();
+        }
+    }
+    code.push_str("}\n");
+}
+
+fn write_enum(code: &mut String, def: &EnumDef) {
+    let mut names = HashSet::new();
+
+    writeln!(
+        code,
+        "\n#[derive(bevy_entitiles_derive::LdtkEnum, bevy::reflect::Reflect, \
+        Debug, Clone, Copy, PartialEq, Eq, Hash)]\n\
+        #[wrapper_derive(bevy::reflect::Reflect, Default)]\n\
+        pub enum {} {{",
+        to_pascal_case(&def.identifier)
+    )
+    .unwrap();
+    for value in &def.values {
+        let name = to_pascal_case(&value.id);
+        if !names.insert(name.clone()) {
+            continue;
+        }
+
+        if name != value.id {
+            writeln!(code, "    #[ldtk_name = {:?}]", value.id).unwrap();
+        }
+        writeln!(code, "    {},", name).unwrap();
+    }
+    code.push_str("}\n");
+}
+
+/// Split the identifier into words at underscores, spaces and lower to upper case changes.
+fn words(identifier: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+
+    for c in identifier.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            prev_lower = false;
+            continue;
+        }
+
+        if c.is_ascii_uppercase() && prev_lower {
+            words.push(std::mem::take(&mut word));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Identifiers can't start with digits, so prefix them with an underscore.
+fn make_ident(mut name: String) -> String {
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn to_screaming_snake_case(identifier: &str) -> String {
+    make_ident(
+        words(identifier)
+            .iter()
+            .map(|w| w.to_ascii_uppercase())
+            .collect::<Vec<_>>()
+            .join("_"),
+    )
+}
+
+fn to_pascal_case(identifier: &str) -> String {
+    make_ident(
+        words(identifier)
+            .iter()
+            .map(|w| {
+                let mut chars = w.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ldtk::json::LdtkJson;
+
+    use super::{generate, to_pascal_case, to_screaming_snake_case};
+
+    #[test]
+    fn test_codegen() {
+        assert_eq!(
+            to_screaming_snake_case("PhysicsColliders"),
+            "PHYSICS_COLLIDERS"
+        );
+        assert_eq!(to_screaming_snake_case("Wall_shadows"), "WALL_SHADOWS");
+        assert_eq!(to_screaming_snake_case("Sewers2"), "SEWERS2");
+        assert_eq!(to_pascal_case("Green_gem"), "GreenGem");
+        assert_eq!(to_pascal_case("GoldNuggets"), "GoldNuggets");
+        assert_eq!(to_pascal_case("2nd floor"), "_2ndFloor");
+
+        let json = serde_json::from_slice::<LdtkJson>(
+            &std::fs::read("assets/ldtk/grid_vania.ldtk").unwrap(),
+        )
+        .unwrap();
+        let code = generate(&json);
+
+        assert!(code.contains("pub const CROSS_ROADS: &str = \"Cross_roads\";"));
+        assert!(code.contains("pub const PHYSICS_COLLIDERS: &str = \"PhysicsColliders\";"));
+        assert!(code.contains("pub const PLAYER: &str = \"Player\";"));
+        assert!(code.contains("pub enum ItemType {"));
+        assert!(code.contains("    #[ldtk_name = \"Green_gem\"]\n    GreenGem,"));
+        assert!(code.contains("    GoldNuggets,"));
+        assert!(!code.contains("\"GoldNuggets\""));
+    }
+}
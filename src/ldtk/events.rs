@@ -1,8 +1,11 @@
 use std::fmt::Display;
 
-use bevy::{asset::AssetId, ecs::event::Event, math::Vec2, reflect::Reflect};
+use bevy::{asset::AssetId, ecs::event::Event, math::Vec2, reflect::Reflect, utils::HashMap};
 
-use crate::ldtk::{components::LevelIid, json::LdtkJson};
+use crate::{
+    ldtk::{components::LevelIid, json::LdtkJson},
+    utils::filter::LayerFilter,
+};
 
 #[derive(Event, Clone)]
 pub enum LdtkLevelEvent {
@@ -24,6 +27,33 @@ pub struct LdtkLevelLoader {
     pub mode: LdtkLevelLoaderMode,
     /// Override the original tilemap translation or not.
     pub trans_ovrd: Option<Vec2>,
+    /// Place the level at this z instead of allocating from [`TilemapZOrder`](crate::tilemap::map::TilemapZOrder).
+    pub z_ovrd: Option<f32>,
+    /// The layers to load, so decoration layers can be skipped in builds that don't need them.
+    pub layer_filter: LayerFilter,
+    /// Override the properties of layers, using the layer identifiers as keys.
+    pub layer_overrides: HashMap<String, LdtkLayerOverride>,
+}
+
+/// Properties that replace the ones in the LDtk file when loading a layer.
+#[derive(Reflect, Debug, Clone, Default)]
+pub struct LdtkLayerOverride {
+    pub opacity: Option<f32>,
+    /// The name of a material registered using
+    /// [`LayerMaterialApp::register_layer_material`](crate::render::material::LayerMaterialApp::register_layer_material).
+    pub material: Option<String>,
+}
+
+impl LdtkLayerOverride {
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = Some(opacity);
+        self
+    }
+
+    pub fn with_material(mut self, material: impl Into<String>) -> Self {
+        self.material = Some(material.into());
+        self
+    }
 }
 
 #[derive(Reflect, Clone)]
@@ -39,6 +39,10 @@ pub struct LayerDef {
     /// Opacity of the layer (0 to 1.0)
     pub display_opacity: f32,
 
+    /// User defined documentation for this element to provide help/tips to level designers.
+    #[serde(default)]
+    pub doc: Option<String>,
+
     /// Width and height of the grid in pixels
     pub grid_size: i32,
 
@@ -88,6 +92,22 @@ pub struct LayerDef {
     pub uid: i32,
 }
 
+impl LayerDef {
+    /// The material assigned to this layer.
+    ///
+    /// Write a line like `material = water` (quotes are optional) in the doc of the layer,
+    /// and register the material using `register_layer_material`.
+    pub fn material(&self) -> Option<&str> {
+        self.doc.as_ref()?.lines().find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            if key.trim() != "material" {
+                return None;
+            }
+            Some(value.trim().trim_matches('"'))
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
 pub enum LayerType {
     IntGrid,
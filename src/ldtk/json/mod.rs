@@ -126,6 +126,12 @@ pub struct LdtkJson {
     /// If you want to start supporting this future update easily,
     /// please refer to this documentation: https://github.com/deepnight/ldtk/issues/231
     pub worlds: Vec<World>,
+
+    /// The asset source this file is loaded from. `None` for the default source.
+    ///
+    /// Not a part of the LDtk format. Tilesets and backgrounds are loaded from the same source.
+    #[serde(skip)]
+    pub source: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
@@ -0,0 +1,60 @@
+use bevy::{ecs::system::Resource, math::IVec2, reflect::Reflect, utils::HashMap};
+
+use crate::{
+    ldtk::json::{definitions::LayerType, level::LayerInstance},
+    tilemap::audio::{TileAudioRegion, TilemapAudioRegions},
+};
+
+/// Designate an IntGrid layer as the audio layer. Cells with the same int grid value
+/// become one region on the `parent` tilemap, emitting the sound in `sources`.
+#[derive(Debug, Resource, Clone, Reflect)]
+pub struct LdtkAudioLayer {
+    pub identifier: String,
+    pub parent: String,
+    pub sources: HashMap<i32, LdtkAudioSource>,
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct LdtkAudioSource {
+    /// The path to the audio, relative to the assets folder.
+    pub source: String,
+    pub range: f32,
+    pub volume: f32,
+}
+
+pub fn analyze_audio_layer(layer: &LayerInstance, audio: &LdtkAudioLayer) -> TilemapAudioRegions {
+    if layer.ty != LayerType::IntGrid {
+        panic!(
+            "The audio layer {:?} is not an IntGrid layer!",
+            layer.identifier
+        );
+    }
+
+    let mut tiles = HashMap::<i32, Vec<IVec2>>::default();
+    layer
+        .int_grid_csv
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| audio.sources.contains_key(*value))
+        .for_each(|(i, value)| {
+            let (x, y) = (i as i32 % layer.c_wid, i as i32 / layer.c_wid);
+            tiles.entry(*value).or_default().push(IVec2::new(x, -y - 1));
+        });
+
+    let mut values = tiles.keys().copied().collect::<Vec<_>>();
+    values.sort_unstable();
+    TilemapAudioRegions::new(
+        values
+            .into_iter()
+            .map(|value| {
+                let source = &audio.sources[&value];
+                TileAudioRegion::tiles(
+                    source.source.clone(),
+                    tiles.remove(&value).unwrap(),
+                    source.range,
+                )
+                .with_volume(source.volume)
+            })
+            .collect(),
+    )
+}
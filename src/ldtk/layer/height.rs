@@ -0,0 +1,81 @@
+use bevy::{
+    ecs::{entity::Entity, system::Resource},
+    math::{UVec2, Vec2},
+    reflect::Reflect,
+    utils::HashMap,
+};
+
+use crate::{
+    ldtk::json::{definitions::LayerType, level::LayerInstance},
+    tilemap::height::InsertHeightMap,
+};
+
+/// Designate an IntGrid layer as the height layer. It won't be spawned as a tilemap,
+/// but converted into a [`TilemapHeightMap`](crate::tilemap::height::TilemapHeightMap)
+/// on the level entity.
+#[derive(Debug, Resource, Clone, Reflect)]
+pub struct LdtkHeightLayer {
+    pub identifier: String,
+    /// The height of each int grid value. Values that are not in here
+    /// use `value * scale`.
+    pub heights: Option<HashMap<i32, f32>>,
+    pub scale: f32,
+}
+
+impl LdtkHeightLayer {
+    pub fn new(identifier: impl Into<String>) -> Self {
+        Self {
+            identifier: identifier.into(),
+            heights: None,
+            scale: 1.,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_heights(mut self, heights: HashMap<i32, f32>) -> Self {
+        self.heights = Some(heights);
+        self
+    }
+}
+
+pub fn analyze_height_layer(
+    layer: &LayerInstance,
+    height: &LdtkHeightLayer,
+    translation: Vec2,
+) -> InsertHeightMap {
+    if layer.ty != LayerType::IntGrid {
+        panic!(
+            "The height layer {:?} is not an IntGrid layer!",
+            layer.identifier
+        );
+    }
+
+    let heights = layer
+        .int_grid_csv
+        .iter()
+        .map(|value| {
+            height
+                .heights
+                .as_ref()
+                .and_then(|h| h.get(value).copied())
+                .unwrap_or(*value as f32 * height.scale)
+        })
+        .collect();
+
+    InsertHeightMap {
+        // Assigned when the level is spawned.
+        target: Entity::PLACEHOLDER,
+        heights,
+        size: UVec2::new(layer.c_wid as u32, layer.c_hei as u32),
+        origin: translation
+            + Vec2::new(
+                layer.px_total_offset_x as f32,
+                -layer.px_total_offset_y as f32,
+            ),
+        tile_size: Vec2::splat(layer.grid_size as f32),
+    }
+}
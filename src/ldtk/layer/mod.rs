@@ -7,6 +7,7 @@ use bevy::{
     },
     math::{IVec2, Vec2},
     prelude::{Component, SpatialBundle},
+    render::render_resource::FilterMode,
     sprite::SpriteBundle,
     transform::components::Transform,
     utils::HashMap,
@@ -24,18 +25,19 @@ use crate::{
         traits::{LdtkEntityRegistry, LdtkEntityTagRegistry},
     },
     math::GridRect,
-    render::material::StandardTilemapMaterial,
-    serializing::pattern::TilemapPattern,
+    render::material::{LayerMaterialRegistry, StandardTilemapMaterial},
+    serializing::{pattern::TilemapPattern, version::SAVE_FORMAT_VERSION},
     tilemap::{
         buffers::TileBuffer,
         bundles::StandardTilemapBundle,
+        height::InsertHeightMap,
         map::{
-            TileRenderSize, TilemapLayerOpacities, TilemapName, TilemapSlotSize, TilemapStorage,
-            TilemapTexture, TilemapTextures, TilemapTransform, TilemapType,
+            TileRenderSize, TilemapLayerOpacities, TilemapName, TilemapParallax, TilemapSlotSize,
+            TilemapStorage, TilemapTexture, TilemapTextures, TilemapTransform, TilemapType,
         },
         tile::{TileBuilder, TileFlip, TileLayer, TileTexture},
+        trigger::TileTriggerTilemap,
     },
-    DEFAULT_CHUNK_SIZE,
 };
 
 #[cfg(feature = "algorithm")]
@@ -49,10 +51,14 @@ use crate::tilemap::physics::{DataPhysicsTilemap, SerializablePhysicsSource};
 #[cfg(feature = "physics")]
 use bevy::math::UVec2;
 
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod height;
 #[cfg(feature = "algorithm")]
 pub mod path;
 #[cfg(feature = "physics")]
 pub mod physics;
+pub mod trigger;
 
 #[derive(Debug, Clone)]
 pub struct PackedLdtkEntity {
@@ -122,7 +128,19 @@ pub struct LdtkLayers {
     pub tilesets: HashMap<i32, TilemapTexture>,
     pub translation: Vec2,
     pub base_z_index: f32,
+    /// The z distance between layers.
+    pub z_spacing: f32,
+    pub chunk_size: u32,
+    pub filter_mode: FilterMode,
     pub background: SpriteBundle,
+    /// Names of the materials assigned to layers, indexed by the layer index.
+    pub layer_materials: HashMap<usize, String>,
+    /// Opacities that replace the ones in the file, indexed by the layer index.
+    pub layer_opacities: HashMap<usize, LayerOpacity>,
+    /// Parallax factors of layers, indexed by the layer index.
+    pub layer_parallax: HashMap<usize, Vec2>,
+    /// Total pixel offsets of layers, indexed by the layer index.
+    pub layer_offsets: HashMap<usize, IVec2>,
     #[cfg(feature = "algorithm")]
     pub path_layer: Option<(
         path::LdtkPathLayer,
@@ -130,6 +148,13 @@ pub struct LdtkLayers {
     )>,
     #[cfg(feature = "physics")]
     pub physics_layer: Option<(physics::LdtkPhysicsLayer, Vec<i32>, UVec2)>,
+    pub height_layer: Option<InsertHeightMap>,
+    pub trigger_layer: Option<(trigger::LdtkTriggerLayer, TileTriggerTilemap)>,
+    #[cfg(feature = "audio")]
+    pub audio_layer: Option<(
+        audio::LdtkAudioLayer,
+        crate::tilemap::audio::TilemapAudioRegions,
+    )>,
 }
 
 impl LdtkLayers {
@@ -141,6 +166,9 @@ impl LdtkLayers {
         ldtk_assets: &LdtkAssets,
         translation: Vec2,
         base_z_index: f32,
+        z_spacing: f32,
+        chunk_size: u32,
+        filter_mode: FilterMode,
         ty: LdtkLevelLoaderMode,
         background: SpriteBundle,
     ) -> Self {
@@ -153,12 +181,23 @@ impl LdtkLayers {
             tilesets: ldtk_assets.tilesets.clone(),
             translation,
             base_z_index,
+            z_spacing,
+            chunk_size,
+            filter_mode,
             background,
             ty,
+            layer_materials: HashMap::default(),
+            layer_opacities: HashMap::default(),
+            layer_parallax: HashMap::default(),
+            layer_offsets: HashMap::default(),
             #[cfg(feature = "algorithm")]
             path_layer: None,
             #[cfg(feature = "physics")]
             physics_layer: None,
+            height_layer: None,
+            trigger_layer: None,
+            #[cfg(feature = "audio")]
+            audio_layer: None,
         }
     }
 
@@ -174,17 +213,25 @@ impl LdtkLayers {
         self.try_create_new_layer(layer_index, layer);
 
         let (pattern, texture, _, _) = self.layers[layer_index].as_mut().unwrap();
-        let tile_size = texture.desc.tile_size;
+        let tile_size = texture.desc.tile_size.as_ivec2();
+        // Tiles can be larger than the grid, they are anchored at the bottom left slot.
+        let grid_size = layer.grid_size;
+        let bottom = tile.px[1] + tile_size.y;
         let tile_index = IVec2 {
-            x: tile.px[0] / tile_size.x as i32,
+            x: tile.px[0].div_euclid(grid_size),
             y: match mode {
-                LdtkLevelLoaderMode::Tilemap => -tile.px[1] / tile_size.y as i32 - 1,
+                LdtkLevelLoaderMode::Tilemap => (-bottom).div_euclid(grid_size),
                 LdtkLevelLoaderMode::MapPattern => {
-                    patterns.pattern_size.y as i32 - tile.px[1] / tile_size.y as i32 - 1
+                    patterns.pattern_size.y as i32 - bottom.div_euclid(grid_size)
                 }
             },
         };
         let atlas_index = tile.tile_id;
+        let flip = TileFlip::from_bits((tile.flip.reverse_bits() >> 30 & 0b11) as u32).unwrap();
+        let mut tile_layer = TileLayer::new(0, atlas_index, flip);
+        if tile_size != IVec2::splat(grid_size) {
+            tile_layer = tile_layer.with_render_scale(tile_size.as_vec2() / grid_size as f32);
+        }
 
         if let Some(ser_tile) = pattern.tiles.get_mut(tile_index) {
             let TileTexture::Static(tile_layers) = &mut ser_tile.texture else {
@@ -193,10 +240,7 @@ impl LdtkLayers {
                     tile_index
                 );
             };
-            tile_layers.push(TileLayer {
-                atlas_index,
-                ..Default::default()
-            });
+            tile_layers.push(tile_layer);
         } else {
             let mut builder = TileBuilder::new().with_tint(LinearRgba::new(1., 1., 1., tile.alpha));
             builder = {
@@ -204,16 +248,7 @@ impl LdtkLayers {
                     let animation = pattern.animations.register(anim.clone());
                     builder.with_animation(animation)
                 } else {
-                    let flip = tile.flip.reverse_bits() >> 30 & 0b11;
-                    builder.with_layer(
-                        0,
-                        TileLayer {
-                            #[cfg(feature = "atlas")]
-                            texture_index: 0,
-                            atlas_index,
-                            flip: TileFlip::from_bits(flip as u32).unwrap(),
-                        },
-                    )
+                    builder.with_layer(0, tile_layer)
                 }
             };
 
@@ -221,6 +256,33 @@ impl LdtkLayers {
         }
     }
 
+    pub fn assign_layer_material(&mut self, layer_index: usize, material: impl Into<String>) {
+        self.layer_materials.insert(layer_index, material.into());
+    }
+
+    /// Use `opacity` instead of the one in the file.
+    pub fn assign_layer_opacity(&mut self, layer_index: usize, opacity: LayerOpacity) {
+        self.layer_opacities.insert(layer_index, opacity);
+    }
+
+    /// Make the layer scroll at a different speed than the camera.
+    /// Layers with a zero factor won't have a [`TilemapParallax`].
+    ///
+    /// **Notice**: `parallaxScaling` is ignored. Use [`TilemapTransform::scale`](crate::tilemap::map::TilemapTransform::scale)
+    /// if the layer needs to be scaled.
+    pub fn assign_layer_parallax(&mut self, layer_index: usize, factor: Vec2) {
+        if factor != Vec2::ZERO {
+            self.layer_parallax.insert(layer_index, factor);
+        }
+    }
+
+    /// Offset the layer by `offset` pixels. Positive y goes down like in LDtk.
+    pub fn assign_layer_offset(&mut self, layer_index: usize, offset: IVec2) {
+        if offset != IVec2::ZERO {
+            self.layer_offsets.insert(layer_index, offset);
+        }
+    }
+
     pub fn set_entity(&mut self, entity: PackedLdtkEntity) {
         self.entities.push(entity);
     }
@@ -243,6 +305,7 @@ impl LdtkLayers {
 
         self.layers[layer_index] = Some((
             TilemapPattern {
+                version: SAVE_FORMAT_VERSION,
                 label: Some(layer.identifier.clone()),
                 tiles: TileBuffer {
                     aabb,
@@ -262,7 +325,10 @@ impl LdtkLayers {
             },
             tileset,
             LayerIid(layer.iid.clone()),
-            layer.opacity,
+            self.layer_opacities
+                .get(&layer_index)
+                .copied()
+                .unwrap_or(layer.opacity),
         ));
     }
 
@@ -276,6 +342,7 @@ impl LdtkLayers {
         ldtk_assets: &LdtkAssets,
         asset_server: &AssetServer,
         material_assets: &mut Assets<StandardTilemapMaterial>,
+        material_registry: &LayerMaterialRegistry,
         textures_assets: &mut Assets<TilemapTextures>,
         #[cfg(feature = "algorithm")] path_tilemaps: &mut PathTilemaps,
     ) {
@@ -304,17 +371,24 @@ impl LdtkLayers {
                     .filter_map(|(i, e)| if let Some(e) = e { Some((i, e)) } else { None })
                     .for_each(|(index, (pattern, texture, iid, opacity))| {
                         let tilemap_entity = commands.spawn_empty().id();
+                        // Tiles larger than the grid have their own render scale.
+                        let grid_size =
+                            Vec2::splat(self.level.layer_instances[index].grid_size as f32);
                         let mut tilemap = StandardTilemapBundle {
                             name: TilemapName(pattern.label.clone().unwrap()),
                             ty: TilemapType::Square,
-                            tile_render_size: TileRenderSize(texture.desc.tile_size.as_vec2()),
-                            slot_size: TilemapSlotSize(texture.desc.tile_size.as_vec2()),
+                            tile_render_size: TileRenderSize(grid_size),
+                            slot_size: TilemapSlotSize(grid_size),
                             textures: textures_assets
-                                .add(TilemapTextures::single(texture.clone(), config.filter_mode)),
-                            storage: TilemapStorage::new(DEFAULT_CHUNK_SIZE, tilemap_entity),
+                                .add(TilemapTextures::single(texture.clone(), self.filter_mode)),
+                            storage: TilemapStorage::new(self.chunk_size, tilemap_entity),
                             transform: TilemapTransform {
-                                translation: self.translation,
-                                z_index: self.base_z_index - index as f32 - 1.,
+                                translation: self.translation
+                                    + self
+                                        .layer_offsets
+                                        .get(&index)
+                                        .map_or(Vec2::ZERO, |o| Vec2::new(o.x as f32, -o.y as f32)),
+                                z_index: self.base_z_index - (index + 1) as f32 * self.z_spacing,
                                 ..Default::default()
                             },
                             material: material_assets.add(StandardTilemapMaterial::default()),
@@ -335,8 +409,9 @@ impl LdtkLayers {
                                     PathTilemap {
                                         storage: ChunkedStorage::from_mapper(
                                             path_tilemap.clone(),
-                                            DEFAULT_CHUNK_SIZE,
+                                            self.chunk_size,
                                         ),
+                                        removed: None,
                                     },
                                 );
                             }
@@ -357,9 +432,41 @@ impl LdtkLayers {
                             }
                         }
 
+                        if let Some((trigger_layer, trigger)) = &self.trigger_layer {
+                            if tilemap.name.0 == trigger_layer.parent {
+                                commands.entity(tilemap_entity).insert(trigger.clone());
+                            }
+                        }
+
+                        #[cfg(feature = "audio")]
+                        if let Some((audio_layer, audio_regions)) = &self.audio_layer {
+                            if tilemap.name.0 == audio_layer.parent {
+                                commands
+                                    .entity(tilemap_entity)
+                                    .insert(audio_regions.clone());
+                            }
+                        }
+
                         commands
                             .entity(tilemap_entity)
                             .insert((tilemap, iid.clone()));
+                        // LDtk scrolls layers relative to the center of the level.
+                        if let Some(factor) = self.layer_parallax.get(&index) {
+                            commands.entity(tilemap_entity).insert(
+                                TilemapParallax::new(*factor).with_origin(
+                                    self.translation
+                                        + Vec2::new(
+                                            self.level.px_wid as f32,
+                                            -self.level.px_hei as f32,
+                                        ) / 2.,
+                                ),
+                            );
+                        }
+                        material_registry.apply_or_default(
+                            commands,
+                            tilemap_entity,
+                            self.layer_materials.get(&index).map(String::as_str),
+                        );
                         layers.insert(iid, tilemap_entity);
                     });
 
@@ -378,6 +485,11 @@ impl LdtkLayers {
                     },
                     LevelIid(self.level.iid.clone()),
                 ));
+
+                if let Some(mut height_layer) = self.height_layer.take() {
+                    height_layer.target = self.level_entity;
+                    commands.add(height_layer);
+                }
             }
             LdtkLevelLoaderMode::MapPattern => {
                 self.layers
@@ -437,6 +549,27 @@ impl LdtkLayers {
         self.path_layer = Some((path, tilemap));
     }
 
+    #[cfg(feature = "audio")]
+    pub fn assign_audio_layer(
+        &mut self,
+        audio_layer: audio::LdtkAudioLayer,
+        regions: crate::tilemap::audio::TilemapAudioRegions,
+    ) {
+        self.audio_layer = Some((audio_layer, regions));
+    }
+
+    pub fn assign_trigger_layer(
+        &mut self,
+        trigger_layer: trigger::LdtkTriggerLayer,
+        trigger: TileTriggerTilemap,
+    ) {
+        self.trigger_layer = Some((trigger_layer, trigger));
+    }
+
+    pub fn assign_height_layer(&mut self, height_layer: InsertHeightMap) {
+        self.height_layer = Some(height_layer);
+    }
+
     #[cfg(feature = "physics")]
     pub fn assign_physics_layer(
         &mut self,
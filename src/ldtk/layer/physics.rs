@@ -7,5 +7,6 @@ pub struct LdtkPhysicsLayer {
     pub identifier: String,
     pub parent: String,
     pub air: i32,
+    /// The physics settings like friction and collision layers of each int grid value.
     pub tiles: Option<HashMap<i32, PhysicsTile>>,
 }
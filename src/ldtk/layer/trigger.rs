@@ -0,0 +1,12 @@
+use bevy::{ecs::system::Resource, reflect::Reflect};
+
+/// Designate an IntGrid layer as the trigger layer. It's loaded into a
+/// [`TileTriggerTilemap`](crate::tilemap::trigger::TileTriggerTilemap) on the `parent` tilemap,
+/// and the int grid values are used as region ids.
+#[derive(Debug, Resource, Clone, Reflect)]
+pub struct LdtkTriggerLayer {
+    pub identifier: String,
+    pub parent: String,
+    /// The int grid value of tiles that don't belong to any region.
+    pub empty: i32,
+}
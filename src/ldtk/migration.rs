@@ -0,0 +1,152 @@
+//! Translate the iids in saves after the LDtk project is edited.
+//!
+//! Iids can change when levels, layers or entities are recreated in LDtk,
+//! so saves that reference them would break. Save the [`LdtkIidTable`] of the project
+//! along with your game data, and use [`LdtkIidTable::remapper`] to translate the stale
+//! iids into the current ones when loading.
+
+use bevy::{
+    asset::AssetId, ecs::system::Resource, prelude::Deref, reflect::Reflect, utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ldtk::{
+    components::{EntityIid, LayerIid, LevelIid},
+    json::{level::Level, LdtkJson},
+};
+
+/// The iids of an LDtk project, keyed by stable keys made of identifiers.
+///
+/// - Levels: `level`
+/// - Layers: `level/layer`
+/// - Entities: `level/layer/entity#n`, where `n` is the order of the entity among
+/// the ones with the same identifier in the layer.
+///
+/// **Notice**: Layers and entities of external levels are not included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Reflect)]
+pub struct LdtkIidTable {
+    pub levels: HashMap<String, String>,
+    pub layers: HashMap<String, String>,
+    pub entities: HashMap<String, String>,
+}
+
+impl LdtkIidTable {
+    pub fn new(json: &LdtkJson) -> Self {
+        let mut table = Self::default();
+        json.levels
+            .iter()
+            .chain(json.worlds.iter().flat_map(|world| world.levels.iter()))
+            .for_each(|level| table.add_level(level));
+        table
+    }
+
+    fn add_level(&mut self, level: &Level) {
+        self.levels
+            .insert(level.identifier.clone(), level.iid.clone());
+
+        for layer in &level.layer_instances {
+            let layer_key = format!("{}/{}", level.identifier, layer.identifier);
+            self.layers.insert(layer_key.clone(), layer.iid.clone());
+
+            let mut ordinals = HashMap::<&str, usize>::default();
+            for entity in &layer.entity_instances {
+                let ordinal = ordinals.entry(&entity.identifier).or_default();
+                self.entities.insert(
+                    format!("{}/{}#{}", layer_key, entity.identifier, ordinal),
+                    entity.iid.clone(),
+                );
+                *ordinal += 1;
+            }
+        }
+    }
+
+    /// Create a remapper that translates the iids in `stale` into the ones in this table.
+    ///
+    /// Iids whose keys no longer exist are not remapped.
+    pub fn remapper(&self, stale: &LdtkIidTable) -> LdtkIidRemapper {
+        let mut mapper = HashMap::default();
+        for (stale, current) in [
+            (&stale.levels, &self.levels),
+            (&stale.layers, &self.layers),
+            (&stale.entities, &self.entities),
+        ] {
+            mapper.extend(stale.iter().filter_map(|(key, stale_iid)| {
+                current
+                    .get(key)
+                    .filter(|iid| *iid != stale_iid)
+                    .map(|iid| (stale_iid.clone(), iid.clone()))
+            }));
+        }
+        LdtkIidRemapper(mapper)
+    }
+}
+
+/// Translates stale iids into the current ones. Created by [`LdtkIidTable::remapper`].
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct LdtkIidRemapper(pub(crate) HashMap<String, String>);
+
+impl LdtkIidRemapper {
+    /// Get the current iid. Returns `iid` itself if it doesn't need remapping.
+    pub fn remap<'a>(&'a self, iid: &'a str) -> &'a str {
+        self.0.get(iid).map(|s| s.as_str()).unwrap_or(iid)
+    }
+
+    #[inline]
+    pub fn remap_entity(&self, iid: &EntityIid) -> EntityIid {
+        EntityIid(self.remap(iid).to_string())
+    }
+
+    #[inline]
+    pub fn remap_layer(&self, iid: &LayerIid) -> LayerIid {
+        LayerIid(self.remap(iid).to_string())
+    }
+
+    #[inline]
+    pub fn remap_level(&self, iid: &LevelIid) -> LevelIid {
+        LevelIid(self.remap(iid).to_string())
+    }
+
+    /// The number of iids that changed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The iid tables of the loaded LDtk files. They are updated whenever the files are (re)loaded.
+#[derive(Resource, Default, Deref)]
+pub struct LdtkIidTables(pub(crate) HashMap<AssetId<LdtkJson>, LdtkIidTable>);
+
+#[cfg(test)]
+mod test {
+    use super::LdtkIidTable;
+
+    #[test]
+    fn test_remap_iids() {
+        let mut stale = LdtkIidTable::default();
+        stale.levels.insert("Level_0".into(), "a".into());
+        stale
+            .entities
+            .insert("Level_0/Entities/Player#0".into(), "b".into());
+        stale
+            .entities
+            .insert("Level_0/Entities/Chest#0".into(), "c".into());
+
+        let mut current = stale.clone();
+        current
+            .entities
+            .insert("Level_0/Entities/Player#0".into(), "d".into());
+        current.entities.remove("Level_0/Entities/Chest#0");
+
+        let remapper = current.remapper(&stale);
+        assert_eq!(remapper.len(), 1);
+        assert_eq!(remapper.remap("b"), "d");
+        assert_eq!(remapper.remap("a"), "a");
+        assert_eq!(remapper.remap("c"), "c");
+    }
+}
@@ -5,13 +5,15 @@ use bevy::{
     asset::{load_internal_asset, AssetApp, AssetEvent, AssetId, AssetServer, Assets, Handle},
     ecs::{
         entity::Entity,
+        observer::Trigger,
         query::{Added, With},
         system::{Commands, NonSend, ParallelCommands, Query, Res, ResMut},
     },
+    hierarchy::DespawnRecursiveExt,
     log::{error, info, warn},
-    math::{UVec2, Vec2},
+    math::{IVec2, UVec2, Vec2},
     prelude::{EventReader, Local},
-    render::{mesh::Mesh, render_resource::Shader},
+    render::{mesh::Mesh, render_resource::Shader, RenderApp},
     sprite::{Material2dPlugin, Sprite, SpriteBundle, TextureAtlasLayout},
     transform::components::Transform,
     utils::Entry,
@@ -31,6 +33,11 @@ use crate::{
             EntityRef, GridPoint, LdtkColor, LdtkJson, Toc, World, WorldLayout,
         },
         layer::{LdtkLayers, PackedLdtkEntity},
+        migration::{LdtkIidTable, LdtkIidTables},
+        persistence::{
+            CapturePersistentEntities, LdtkPersistentComponents, LdtkPersistentStates,
+            PersistentLdtkEntity,
+        },
         resources::{
             LdtkAdditionalLayers, LdtkAssets, LdtkGlobalEntityRegistry, LdtkJsonLoader,
             LdtkJsonToAssets, LdtkLevelConfig, LdtkLevelIdentifierToIid, LdtkLoadedLevels,
@@ -39,36 +46,62 @@ use crate::{
         sprite::{AtlasRect, LdtkEntityMaterial, NineSliceBorders, SpriteMesh},
         traits::{LdtkEntityRegistry, LdtkEntityTagRegistry},
     },
-    render::material::StandardTilemapMaterial,
-    tilemap::map::{TilemapStorage, TilemapTextures},
+    render::material::{init_material_asset, LayerMaterialRegistry, StandardTilemapMaterial},
+    tilemap::{
+        despawn::EverythingUnloaded,
+        map::{EntiTilesDefaults, TilemapStorage, TilemapTextures, TilemapZOrder},
+        trigger::TileTriggerTilemap,
+    },
+    utils::{
+        asset::{self, init_asset_if_missing},
+        filter::LayerFilter,
+    },
 };
 
 #[cfg(feature = "algorithm")]
 use crate::algorithm::pathfinding::PathTilemaps;
 
 pub mod app_ext;
+pub mod codegen;
 pub mod components;
 pub mod events;
 pub mod json;
 pub mod layer;
+pub mod migration;
+pub mod persistence;
 pub mod resources;
 pub mod sprite;
 pub mod traits;
 
 pub const ENTITY_SPRITE_SHADER: Handle<Shader> = Handle::weak_from_u128(89874656485416351634163551);
 
+/// The z distance between layers if neither [`TilemapZOrder::layer_step`]
+/// nor [`EntiTilesDefaults::z_spacing`] is set.
+pub const LAYER_Z_SPACING: f32 = 1.;
+
 pub struct EntiTilesLdtkPlugin;
 
 impl Plugin for EntiTilesLdtkPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        load_internal_asset!(
-            app,
-            ENTITY_SPRITE_SHADER,
-            "entity_sprite.wgsl",
-            Shader::from_wgsl
-        );
+        // Entity sprites are only rendered if the renderer exists,
+        // the assets they use are still needed to spawn them in headless apps.
+        if app.get_sub_app(RenderApp).is_some() {
+            load_internal_asset!(
+                app,
+                ENTITY_SPRITE_SHADER,
+                "entity_sprite.wgsl",
+                Shader::from_wgsl
+            );
+            app.add_plugins(Material2dPlugin::<LdtkEntityMaterial>::default());
+        } else {
+            init_asset_if_missing::<LdtkEntityMaterial>(app);
+        }
+        init_asset_if_missing::<Mesh>(app);
+        init_asset_if_missing::<TextureAtlasLayout>(app);
+
+        init_material_asset::<StandardTilemapMaterial>(app);
 
-        app.add_plugins(Material2dPlugin::<LdtkEntityMaterial>::default())
+        app.init_resource::<LayerMaterialRegistry>()
             .add_systems(
                 Update,
                 (
@@ -79,8 +112,11 @@ impl Plugin for EntiTilesLdtkPlugin {
                     global_entity_registerer,
                     ldtk_temp_tranform_applier,
                     apply_ldtk_layers,
+                    persistence::persistent_entity_restorer,
+                    persistence::persistent_entity_remover,
                 ),
             )
+            .observe(ldtk_unloader)
             .insert_non_send_resource(LdtkEntityRegistry::default())
             .init_asset::<LdtkJson>()
             .init_asset::<LdtkAssets>()
@@ -93,13 +129,19 @@ impl Plugin for EntiTilesLdtkPlugin {
             .init_resource::<LdtkTocs>()
             .init_resource::<LdtkGlobalEntityRegistry>()
             .init_resource::<LdtkLevelIdentifierToIid>()
+            .init_resource::<LdtkIidTables>()
+            .init_resource::<LdtkPersistentComponents>()
+            .init_resource::<LdtkPersistentStates>()
             .add_event::<LdtkLevelEvent>()
             .register_type::<LdtkLoadedLevel>()
             .register_type::<GlobalEntity>()
+            .register_type::<PersistentLdtkEntity>()
+            .register_type::<LayerFilter>()
             .register_type::<EntityIid>()
             .register_type::<LayerIid>()
             .register_type::<LevelIid>()
             .register_type::<WorldIid>()
+            .register_type::<LdtkIidTable>()
             .register_type::<AtlasRect>()
             .register_type::<LdtkEntityMaterial>()
             .register_type::<NineSliceBorders>()
@@ -121,7 +163,9 @@ impl Plugin for EntiTilesLdtkPlugin {
             .register_type::<LdtkAdditionalLayers>()
             .register_type::<LdtkAssets>()
             .register_type::<LdtkPatterns>()
-            .register_type::<LdtkGlobalEntityRegistry>();
+            .register_type::<LdtkGlobalEntityRegistry>()
+            .register_type::<layer::height::LdtkHeightLayer>()
+            .register_type::<layer::trigger::LdtkTriggerLayer>();
 
         #[cfg(feature = "algorithm")]
         {
@@ -133,6 +177,12 @@ impl Plugin for EntiTilesLdtkPlugin {
         {
             app.register_type::<layer::physics::LdtkPhysicsLayer>();
         }
+
+        #[cfg(feature = "audio")]
+        {
+            app.register_type::<layer::audio::LdtkAudioLayer>()
+                .register_type::<layer::audio::LdtkAudioSource>();
+        }
     }
 }
 
@@ -170,6 +220,7 @@ pub fn ldtk_asset_events_handler(
     mut material_assets: ResMut<Assets<LdtkEntityMaterial>>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     mut identifier_to_id: ResMut<LdtkLevelIdentifierToIid>,
+    mut iid_tables: ResMut<LdtkIidTables>,
 ) {
     for ev in asset_event.read() {
         match ev {
@@ -191,11 +242,13 @@ pub fn ldtk_asset_events_handler(
                         .map(|level| (level.identifier.clone(), LevelIid(level.iid.clone())))
                         .collect(),
                 );
+                iid_tables.0.insert(*id, LdtkIidTable::new(json));
             }
             AssetEvent::Removed { id } => {
                 if let Some(asset) = json_to_assets.get(id) {
                     assets.remove(asset);
                 }
+                iid_tables.0.remove(id);
             }
             AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
         }
@@ -209,6 +262,7 @@ pub fn unload_ldtk_level(
     mut level_events: EventReader<LdtkLevelEvent>,
     mut loaded_levels: ResMut<LdtkLoadedLevels>,
     identifier_to_iid: Res<LdtkLevelIdentifierToIid>,
+    mut z_order: ResMut<TilemapZOrder>,
 ) {
     for ev in level_events.read() {
         let LdtkLevelEvent::Unload(unloader) = ev else {
@@ -233,8 +287,17 @@ pub fn unload_ldtk_level(
             continue;
         };
 
+        commands.add(CapturePersistentEntities(
+            level
+                .entities
+                .iter()
+                .filter(|(iid, _)| !global_entities.contains_key(*iid))
+                .map(|(_, e)| *e)
+                .collect(),
+        ));
         level.unload(&mut commands, &global_entities);
         commands.entity(entity).despawn();
+        z_order.release(entity);
     }
 }
 
@@ -267,6 +330,36 @@ pub fn unload_ldtk_layer(
     });
 }
 
+/// Despawn all the levels and global entities. Layers are despawned as tilemaps.
+pub fn ldtk_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut commands: Commands,
+    levels_query: Query<(Entity, &LdtkLoadedLevel)>,
+    mut loaded_levels: ResMut<LdtkLoadedLevels>,
+    mut global_entities: ResMut<LdtkGlobalEntityRegistry>,
+    mut persistent_states: ResMut<LdtkPersistentStates>,
+) {
+    let mut despawn = |entity| {
+        if let Some(e) = commands.get_entity(entity) {
+            e.despawn_recursive();
+        }
+    };
+
+    levels_query.iter().for_each(|(entity, level)| {
+        level
+            .entities
+            .iter()
+            .filter(|(iid, _)| !global_entities.contains_key(*iid))
+            .for_each(|(_, e)| despawn(*e));
+        despawn(level.background);
+        despawn(entity);
+    });
+    global_entities.0.drain().for_each(|(_, e)| despawn(e));
+
+    loaded_levels.0.clear();
+    persistent_states.clear();
+}
+
 pub fn load_ldtk_level(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -280,6 +373,8 @@ pub fn load_ldtk_level(
     json_to_assets: Res<LdtkJsonToAssets>,
     mut loaded_levels: ResMut<LdtkLoadedLevels>,
     mut retry_queue: Local<Vec<LdtkLevelEvent>>,
+    defaults: Res<EntiTilesDefaults>,
+    mut z_order: ResMut<TilemapZOrder>,
 ) {
     let mut retry = Vec::new();
 
@@ -321,6 +416,8 @@ pub fn load_ldtk_level(
             &mut patterns,
             &global_entities,
             &mut loaded_levels,
+            &defaults,
+            &mut z_order,
         );
         info!("Successfully loaded level. {}", loader.level);
     }
@@ -341,6 +438,8 @@ fn load_levels(
     patterns: &mut LdtkPatterns,
     global_entities: &LdtkGlobalEntityRegistry,
     loaded_levels: &mut LdtkLoadedLevels,
+    defaults: &EntiTilesDefaults,
+    z_order: &mut TilemapZOrder,
 ) {
     let Some((level_index, level)) = (match &loader.level {
         LdtkLevel::Identifier(ident) => ldtk_data
@@ -384,7 +483,21 @@ fn load_levels(
         y: level.px_hei as u32,
     };
 
-    let background = load_background(level, translation, level_px, asset_server, config);
+    // The background is at the bottom of the range, and the first layer is at the top.
+    let layers = level.layer_instances.len();
+    let z_spacing = z_order.layer_step(defaults, LAYER_Z_SPACING);
+    let z_from = z_order.base + config.z_index;
+    let z_start = z_order.allocate_from(level_entity, z_from, layers, z_spacing, loader.z_ovrd);
+    let z_index = z_start + (layers + 1) as f32 * z_spacing;
+    let background = load_background(
+        level,
+        translation,
+        level_px,
+        asset_server,
+        config,
+        z_start,
+        ldtk_data.source.as_deref(),
+    );
 
     let mut ldtk_layers = LdtkLayers::new(
         level_entity,
@@ -393,12 +506,90 @@ fn load_levels(
         assets_id,
         &ldtk_assets,
         translation,
-        config.z_index,
+        z_index,
+        z_spacing,
+        defaults.chunk_size,
+        if config.use_default_filter_mode {
+            defaults.filter_mode
+        } else {
+            config.filter_mode
+        },
         loader.mode,
         background,
     );
 
     for (layer_index, layer) in level.layer_instances.iter().enumerate() {
+        if !loader.layer_filter.selects(&layer.identifier, "") {
+            continue;
+        }
+
+        let layer_def = ldtk_data
+            .defs
+            .layers
+            .iter()
+            .find(|def| def.uid == layer.layer_def_uid);
+
+        if let Some(material) = layer_def.and_then(|def| def.material()) {
+            ldtk_layers.assign_layer_material(layer_index, material);
+        }
+
+        if let Some(layer_override) = loader.layer_overrides.get(&layer.identifier) {
+            if let Some(material) = &layer_override.material {
+                ldtk_layers.assign_layer_material(layer_index, material.clone());
+            }
+            if let Some(opacity) = layer_override.opacity {
+                ldtk_layers.assign_layer_opacity(layer_index, opacity);
+            }
+        }
+
+        if let Some(def) = layer_def {
+            ldtk_layers.assign_layer_parallax(
+                layer_index,
+                Vec2::new(def.parallax_factor_x, def.parallax_factor_y),
+            );
+        }
+        ldtk_layers.assign_layer_offset(
+            layer_index,
+            IVec2::new(layer.px_total_offset_x, layer.px_total_offset_y),
+        );
+
+        if let Some(height) = addi_layers.height_layer.as_ref() {
+            if layer.identifier == height.identifier {
+                ldtk_layers.assign_height_layer(layer::height::analyze_height_layer(
+                    layer,
+                    height,
+                    translation,
+                ));
+                continue;
+            }
+        }
+
+        if let Some(trigger) = addi_layers.trigger_layer.as_ref() {
+            if layer.identifier == trigger.identifier {
+                ldtk_layers.assign_trigger_layer(
+                    trigger.clone(),
+                    TileTriggerTilemap::from_data(
+                        IVec2::new(0, -layer.c_hei),
+                        &layer.int_grid_csv,
+                        layer.c_wid as u32,
+                        trigger.empty,
+                    ),
+                );
+                continue;
+            }
+        }
+
+        #[cfg(feature = "audio")]
+        if let Some(audio) = addi_layers.audio_layer.as_ref() {
+            if layer.identifier == audio.identifier {
+                ldtk_layers.assign_audio_layer(
+                    audio.clone(),
+                    layer::audio::analyze_audio_layer(layer, audio),
+                );
+                continue;
+            }
+        }
+
         #[cfg(feature = "algorithm")]
         if let Some(path) = addi_layers.path_layer.as_ref() {
             if layer.identifier == path.identifier {
@@ -444,11 +635,15 @@ fn load_background(
     level_px: UVec2,
     asset_server: &AssetServer,
     config: &LdtkLevelConfig,
+    z_index: f32,
+    source: Option<&str>,
 ) -> SpriteBundle {
-    let texture = level
-        .bg_rel_path
-        .as_ref()
-        .map(|path| asset_server.load(Path::new(&config.asset_path_prefix).join(path)));
+    let texture = level.bg_rel_path.as_ref().map(|path| {
+        asset_server.load(asset::with_source(
+            Path::new(&config.asset_path_prefix).join(path),
+            source,
+        ))
+    });
 
     SpriteBundle {
         sprite: Sprite {
@@ -460,7 +655,7 @@ fn load_background(
         transform: Transform::from_xyz(
             level_px.x as f32 / 2. + translation.x,
             -(level_px.y as f32) / 2. + translation.y,
-            config.z_index as f32 - level.layer_instances.len() as f32 - 1.,
+            z_index,
         ),
         ..Default::default()
     }
@@ -500,9 +695,10 @@ fn load_layer(
                     iid,
                     transform: LdtkTempTransform {
                         level_translation: translation,
-                        z_index: config.z_index as f32
-                            - layer_index as f32
-                            - (1. - (order as f32 / layer.entity_instances.len() as f32)),
+                        z_index: ldtk_layers.base_z_index
+                            - (layer_index as f32 + 1.
+                                - order as f32 / layer.entity_instances.len() as f32)
+                                * ldtk_layers.z_spacing,
                     },
                 };
                 ldtk_layers.set_entity(packed_entity);
@@ -537,6 +733,7 @@ fn apply_ldtk_layers(
     ldtk_assets: Res<Assets<LdtkAssets>>,
     asset_server: Res<AssetServer>,
     mut material_assets: ResMut<Assets<StandardTilemapMaterial>>,
+    material_registry: Res<LayerMaterialRegistry>,
     mut textures_assets: ResMut<Assets<TilemapTextures>>,
     #[cfg(feature = "algorithm")] mut path_tilemaps: ResMut<PathTilemaps>,
 ) {
@@ -554,6 +751,7 @@ fn apply_ldtk_layers(
             ldtk_assets,
             &asset_server,
             &mut material_assets,
+            &material_registry,
             &mut textures_assets,
             #[cfg(feature = "algorithm")]
             &mut path_tilemaps,
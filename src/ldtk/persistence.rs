@@ -0,0 +1,374 @@
+//! Capture the state of LDtk entities when their level is unloaded,
+//! and restore it when the level is loaded again.
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityHashMap},
+        query::Added,
+        reflect::AppTypeRegistry,
+        removal_detection::RemovedComponents,
+        system::{Commands, Query, ResMut, Resource},
+        world::{Command, EntityRef, EntityWorldMut, World},
+    },
+    hierarchy::DespawnRecursiveExt,
+    log::warn,
+    reflect::{
+        serde::{TypedReflectDeserializer, TypedReflectSerializer},
+        FromReflect, Reflect, TypePath, TypeRegistry,
+    },
+    utils::{HashMap, HashSet},
+};
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+
+use crate::ldtk::components::EntityIid;
+
+/// Add this to an LDtk entity to keep the registered components across level reloads.
+///
+/// Use [`LdtkApp::register_persistent_component`](crate::ldtk::app_ext::LdtkApp::register_persistent_component)
+/// to choose which components are captured.
+///
+/// **Notice**: Unlike [`GlobalEntity`](crate::ldtk::components::GlobalEntity),
+/// the entity itself is still despawned when the level unloads. If it's despawned
+/// while the level is loaded, it won't be spawned again.
+#[derive(Component, Debug, Default, Clone, Copy, Reflect)]
+pub struct PersistentLdtkEntity;
+
+type CaptureFn = fn(&EntityRef) -> Option<Box<dyn Reflect>>;
+type RestoreFn = fn(&mut EntityWorldMut, Option<&dyn Reflect>);
+
+/// The components captured from persistent entities, by their type paths.
+#[derive(Resource, Default)]
+pub struct LdtkPersistentComponents(pub(crate) HashMap<String, (CaptureFn, RestoreFn)>);
+
+impl LdtkPersistentComponents {
+    /// **Notice**: `T` also needs to be registered in the `AppTypeRegistry`.
+    pub fn register<T: Component + Reflect + FromReflect + TypePath>(&mut self) {
+        self.0.insert(
+            T::type_path().to_string(),
+            (
+                |entity| entity.get::<T>().map(|c| c.clone_value()),
+                |entity, value| match value {
+                    Some(value) => {
+                        if let Some(c) = T::from_reflect(value) {
+                            entity.insert(c);
+                        }
+                    }
+                    None => {
+                        entity.remove::<T>();
+                    }
+                },
+            ),
+        );
+    }
+}
+
+/// The captured states of persistent entities that are unloaded.
+///
+/// This can be saved along with the game, the components are stored in ron.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct LdtkPersistentStates {
+    /// The components of each entity by their type paths.
+    /// `None` means the component was removed.
+    pub(crate) states: HashMap<String, HashMap<String, Option<String>>>,
+    /// Persistent entities that were despawned while their level was loaded.
+    pub(crate) removed: HashSet<String>,
+    /// Persistent entities that are currently spawned.
+    #[serde(skip)]
+    pub(crate) spawned: EntityHashMap<String>,
+}
+
+impl LdtkPersistentStates {
+    /// Returns `true` if the entity has a captured state or was despawned.
+    #[inline]
+    pub fn contains(&self, iid: &EntityIid) -> bool {
+        self.states.contains_key(&iid.0) || self.removed.contains(&iid.0)
+    }
+
+    /// Returns `true` if the entity was despawned while its level was loaded.
+    #[inline]
+    pub fn is_removed(&self, iid: &EntityIid) -> bool {
+        self.removed.contains(&iid.0)
+    }
+
+    /// Get the captured component of type `T`.
+    pub fn get<T: Component + FromReflect + TypePath>(
+        &self,
+        iid: &EntityIid,
+        registry: &TypeRegistry,
+    ) -> Option<T> {
+        let value = self.states.get(&iid.0)?.get(T::type_path())?.as_ref()?;
+        deserialize(value, T::type_path(), registry).and_then(|v| T::from_reflect(v.as_ref()))
+    }
+
+    /// Discard the captured state, so the entity is reset when its level loads next time.
+    #[inline]
+    pub fn forget(&mut self, iid: &EntityIid) {
+        self.states.remove(&iid.0);
+        self.removed.remove(&iid.0);
+    }
+
+    /// Discard all the captured states.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.states.clear();
+        self.removed.clear();
+        self.spawned.clear();
+    }
+}
+
+fn serialize(value: &dyn Reflect, registry: &TypeRegistry) -> Option<String> {
+    ron::to_string(&TypedReflectSerializer::new(value, registry))
+        .map_err(|e| warn!("Failed to capture {}: {}", value.reflect_type_path(), e))
+        .ok()
+}
+
+fn deserialize(value: &str, type_path: &str, registry: &TypeRegistry) -> Option<Box<dyn Reflect>> {
+    let Some(registration) = registry.get_with_type_path(type_path) else {
+        warn!(
+            "Failed to restore {}: The type is not registered.",
+            type_path
+        );
+        return None;
+    };
+
+    ron::Deserializer::from_str(value)
+        .map_err(|e| e.to_string())
+        .and_then(|mut de| {
+            TypedReflectDeserializer::new(registration, registry)
+                .deserialize(&mut de)
+                .map_err(|e| e.to_string())
+        })
+        .map_err(|e| warn!("Failed to restore {}: {}", type_path, e))
+        .ok()
+}
+
+/// Capture the states of the persistent entities among `entities`.
+pub struct CapturePersistentEntities(pub Vec<Entity>);
+
+impl Command for CapturePersistentEntities {
+    fn apply(self, world: &mut World) {
+        let (Some(components), Some(registry)) = (
+            world.get_resource::<LdtkPersistentComponents>(),
+            world.get_resource::<AppTypeRegistry>(),
+        ) else {
+            return;
+        };
+        let registry = registry.read();
+
+        let captured = self
+            .0
+            .into_iter()
+            .filter_map(|entity| {
+                let entity_ref = world.get_entity(entity)?;
+                if !entity_ref.contains::<PersistentLdtkEntity>() {
+                    return None;
+                }
+
+                let iid = entity_ref.get::<EntityIid>()?.0.clone();
+                let state = components
+                    .0
+                    .iter()
+                    .filter_map(|(ty, (capture, _))| match capture(&entity_ref) {
+                        Some(value) => serialize(value.as_ref(), &registry)
+                            .map(|value| (ty.clone(), Some(value))),
+                        None => Some((ty.clone(), None)),
+                    })
+                    .collect();
+                Some((entity, iid, state))
+            })
+            .collect::<Vec<_>>();
+        drop(registry);
+
+        let mut states = world.get_resource_or_insert_with(LdtkPersistentStates::default);
+        for (entity, iid, state) in captured {
+            states.spawned.remove(&entity);
+            states.states.insert(iid, state);
+        }
+    }
+}
+
+/// Restore the captured state to `entity`.
+pub struct RestorePersistentEntity {
+    pub entity: Entity,
+    pub state: HashMap<String, Option<String>>,
+}
+
+impl Command for RestorePersistentEntity {
+    fn apply(self, world: &mut World) {
+        let (Some(components), Some(registry)) = (
+            world.get_resource::<LdtkPersistentComponents>(),
+            world.get_resource::<AppTypeRegistry>(),
+        ) else {
+            return;
+        };
+        let registry = registry.read();
+
+        let restorers = self
+            .state
+            .iter()
+            .filter_map(|(ty, value)| {
+                let (_, restore) = components.0.get(ty)?;
+                match value {
+                    Some(value) => deserialize(value, ty, &registry).map(|v| (*restore, Some(v))),
+                    None => Some((*restore, None)),
+                }
+            })
+            .collect::<Vec<_>>();
+        drop(registry);
+
+        let Some(mut entity) = world.get_entity_mut(self.entity) else {
+            return;
+        };
+        for (restore, value) in restorers {
+            restore(&mut entity, value.as_deref());
+        }
+    }
+}
+
+pub fn persistent_entity_restorer(
+    mut commands: Commands,
+    entities_query: Query<(Entity, &EntityIid), Added<PersistentLdtkEntity>>,
+    mut states: ResMut<LdtkPersistentStates>,
+) {
+    entities_query.iter().for_each(|(entity, iid)| {
+        if states.removed.contains(&iid.0) {
+            commands.entity(entity).despawn_recursive();
+            return;
+        }
+
+        states.spawned.insert(entity, iid.0.clone());
+        if let Some(state) = states.states.remove(&iid.0) {
+            commands.add(RestorePersistentEntity { entity, state });
+        }
+    });
+}
+
+/// Remember the persistent entities despawned by the game, so they stay despawned.
+///
+/// Entities despawned by unloading their levels are captured before, so they're not tracked anymore.
+pub fn persistent_entity_remover(
+    mut removed: RemovedComponents<PersistentLdtkEntity>,
+    iids_query: Query<&EntityIid>,
+    mut states: ResMut<LdtkPersistentStates>,
+) {
+    for entity in removed.read() {
+        let Some(iid) = states.spawned.remove(&entity) else {
+            continue;
+        };
+
+        // Only the marker is removed, the entity is not persistent anymore.
+        if iids_query.contains(entity) {
+            continue;
+        }
+        states.removed.insert(iid);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            component::Component,
+            reflect::AppTypeRegistry,
+            system::RunSystemOnce,
+            world::{Command, World},
+        },
+        reflect::Reflect,
+    };
+
+    use crate::ldtk::components::EntityIid;
+
+    use super::{
+        persistent_entity_remover, persistent_entity_restorer, CapturePersistentEntities,
+        LdtkPersistentComponents, LdtkPersistentStates, PersistentLdtkEntity,
+    };
+
+    #[derive(Component, Reflect, Default, Debug, PartialEq)]
+    struct Door {
+        open: bool,
+    }
+
+    #[derive(Component, Reflect, Default, Debug, PartialEq)]
+    struct Loot;
+
+    fn init_world() -> World {
+        let mut world = World::new();
+        let mut registry = LdtkPersistentComponents::default();
+        registry.register::<Door>();
+        registry.register::<Loot>();
+        world.insert_resource(registry);
+        let type_registry = AppTypeRegistry::default();
+        type_registry.write().register::<Door>();
+        type_registry.write().register::<Loot>();
+        world.insert_resource(type_registry);
+        world.init_resource::<LdtkPersistentStates>();
+        world
+    }
+
+    fn reload(world: &mut World, iid: &EntityIid) -> bevy::ecs::entity::Entity {
+        let entity = world
+            .spawn((iid.clone(), PersistentLdtkEntity, Door::default(), Loot))
+            .id();
+        world.run_system_once(persistent_entity_restorer);
+        world.flush();
+        entity
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut world = init_world();
+
+        let iid = EntityIid("door".to_string());
+        let door = reload(&mut world, &iid);
+        world.entity_mut(door).insert(Door { open: true });
+        world.entity_mut(door).remove::<Loot>();
+        let plain = world
+            .spawn((EntityIid("plain".to_string()), Door { open: true }))
+            .id();
+
+        CapturePersistentEntities(vec![door, plain]).apply(&mut world);
+        world.despawn(door);
+        world.despawn(plain);
+        world.run_system_once(persistent_entity_remover);
+
+        let states = world.resource::<LdtkPersistentStates>();
+        let registry = world.resource::<AppTypeRegistry>().read();
+        assert_eq!(
+            states.get::<Door>(&iid, &registry),
+            Some(Door { open: true })
+        );
+        assert!(!states.is_removed(&iid));
+        assert!(!states.contains(&EntityIid("plain".to_string())));
+
+        // The states survive saving and loading.
+        let saved = ron::to_string(states).unwrap();
+        drop(registry);
+        world.insert_resource(ron::from_str::<LdtkPersistentStates>(&saved).unwrap());
+
+        // Spawned with the default components, then restored.
+        let door = reload(&mut world, &iid);
+        assert_eq!(world.get::<Door>(door), Some(&Door { open: true }));
+        assert_eq!(world.get::<Loot>(door), None);
+        assert!(!world.resource::<LdtkPersistentStates>().contains(&iid));
+    }
+
+    #[test]
+    fn test_persistent_removal() {
+        let mut world = init_world();
+
+        let iid = EntityIid("chest".to_string());
+        let chest = reload(&mut world, &iid);
+        world.despawn(chest);
+        world.run_system_once(persistent_entity_remover);
+        assert!(world.resource::<LdtkPersistentStates>().is_removed(&iid));
+
+        // Stays despawned after reloading.
+        let chest = reload(&mut world, &iid);
+        assert!(world.get_entity(chest).is_none());
+
+        world.resource_mut::<LdtkPersistentStates>().forget(&iid);
+        let chest = reload(&mut world, &iid);
+        assert!(world.get_entity(chest).is_some());
+    }
+}
@@ -30,6 +30,7 @@ use crate::{
         map::{TilemapTexture, TilemapTextureDescriptor},
         tile::RawTileAnimation,
     },
+    utils::asset,
 };
 
 /// All the patterns loaded from the LDtk file.
@@ -185,7 +186,7 @@ impl LdtkAssets {
     ) -> Self {
         let mut instance = Self::default();
         instance.load_texture(config, ldtk_data, asset_server, atlas_layouts);
-        instance.load_entities(config, ldtk_data, material_assets, mesh_assets);
+        instance.load_entities(ldtk_data, material_assets, mesh_assets);
         instance
     }
 
@@ -201,7 +202,10 @@ impl LdtkAssets {
                 return;
             };
 
-            let texture = asset_server.load(Path::new(&config.asset_path_prefix).join(path));
+            let texture = asset_server.load(asset::with_source(
+                Path::new(&config.asset_path_prefix).join(path),
+                ldtk_data.source.as_deref(),
+            ));
             let desc = TilemapTextureDescriptor {
                 size: UVec2 {
                     x: tileset.px_wid as u32,
@@ -211,8 +215,11 @@ impl LdtkAssets {
                     x: tileset.tile_grid_size as u32,
                     y: tileset.tile_grid_size as u32,
                 },
+                padding: 0,
+                margin: tileset.padding as u32,
+                spacing: tileset.spacing as u32,
             };
-            let texture = TilemapTexture { texture, desc };
+            let texture = TilemapTexture::new(texture, desc);
 
             self.tilesets.insert(tileset.uid, texture.clone());
             self.atlas_handles
@@ -222,7 +229,6 @@ impl LdtkAssets {
 
     fn load_entities(
         &mut self,
-        config: &LdtkLevelConfig,
         ldtk_data: &LdtkJson,
         material_assets: &mut Assets<LdtkEntityMaterial>,
         mesh_assets: &mut Assets<Mesh>,
@@ -274,7 +280,7 @@ impl LdtkAssets {
                     .map(|(index, entity)| {
                         (
                             entity.identifier.clone(),
-                            (ldtk_data.defs.entities.len() - index) as f32 + config.z_index as f32,
+                            (ldtk_data.defs.entities.len() - index) as f32,
                         )
                     })
                     .collect::<HashMap<String, f32>>();
@@ -316,8 +322,19 @@ impl LdtkWfcManager {
     }
 
     /// Calculate the translation of the given level index.
+    ///
+    /// The levels are placed according to the `TilemapType` the wfc runs on.
     pub fn get_translation(&self, level_index: IVec2, slot_size: Vec2) -> Vec2 {
-        (level_index.as_vec2() + Vec2::Y) * self.pattern_size.as_vec2() * slot_size
+        crate::tilemap::coordinates::index_to_world(
+            (level_index + IVec2::Y) * self.pattern_size.as_ivec2(),
+            self.wfc_data
+                .as_ref()
+                .map(|data| data.ty)
+                .unwrap_or_default(),
+            &crate::tilemap::map::TilemapTransform::default(),
+            Vec2::ZERO,
+            slot_size,
+        )
     }
 }
 
@@ -336,10 +353,14 @@ impl LdtkTocs {
 
 /// The additional layers of the LDtk file.
 ///
-/// This includes path layer and physics layer. Entitiles will generate these layers
-/// acoording to the LDtk file.
+/// This includes height layer, trigger layer, audio layer, path layer and physics layer.
+/// Entitiles will generate these layers acoording to the LDtk file.
 #[derive(Resource, Default, Reflect)]
 pub struct LdtkAdditionalLayers {
+    pub height_layer: Option<super::layer::height::LdtkHeightLayer>,
+    pub trigger_layer: Option<super::layer::trigger::LdtkTriggerLayer>,
+    #[cfg(feature = "audio")]
+    pub audio_layer: Option<super::layer::audio::LdtkAudioLayer>,
     #[cfg(feature = "algorithm")]
     pub path_layer: Option<super::layer::path::LdtkPathLayer>,
     #[cfg(feature = "physics")]
@@ -352,6 +373,13 @@ pub struct LdtkLevelConfig {
     pub asset_path_prefix: String,
     #[reflect(ignore)]
     pub filter_mode: FilterMode,
+    /// Use [`EntiTilesDefaults::filter_mode`](crate::tilemap::map::EntiTilesDefaults::filter_mode)
+    /// instead of `filter_mode`.
+    pub use_default_filter_mode: bool,
+    /// Offsets the z range of the levels from [`TilemapZOrder::base`](crate::tilemap::map::TilemapZOrder::base).
+    ///
+    /// **Notice**: This is the bottom of the range, where the background is.
+    /// The layers are placed above it.
     pub z_index: f32,
     /// Map a certain texture index to a animation.
     pub animation_mapper: HashMap<u32, RawTileAnimation>,
@@ -415,11 +443,13 @@ impl AssetLoader for LdtkJsonLoader {
         &'a self,
         reader: &'a mut Reader<'_>,
         _settings: &'a Self::Settings,
-        _load_context: &'a mut LoadContext<'_>,
+        load_context: &'a mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf).await?;
-        serde_json::from_slice(&buf).map_err(Into::into)
+        let mut json = serde_json::from_slice::<LdtkJson>(&buf)?;
+        json.source = asset::source_of(load_context.asset_path());
+        Ok(json)
     }
 }
 
@@ -1,4 +1,7 @@
-use bevy::prelude::Plugin;
+use bevy::{
+    app::PluginGroupBuilder,
+    prelude::{Plugin, PluginGroup},
+};
 use math::EntiTilesMathPlugin;
 use render::{
     material::{EntiTilesMaterialPlugin, StandardTilemapMaterial},
@@ -11,6 +14,9 @@ use tilemap::EntiTilesTilemapPlugin;
 pub mod algorithm;
 #[cfg(feature = "debug")]
 pub mod debug;
+pub mod diagnostics;
+#[cfg(feature = "ecs_tilemap")]
+pub mod ecs_tilemap;
 #[cfg(feature = "ldtk")]
 pub mod ldtk;
 pub mod math;
@@ -18,71 +24,339 @@ pub mod render;
 #[cfg(feature = "serializing")]
 pub mod serializing;
 pub mod shaders;
+#[cfg(feature = "sprite_sheet")]
+pub mod sprite_sheet;
 #[cfg(feature = "tiled")]
 pub mod tiled;
 pub mod tilemap;
+#[cfg(feature = "ui")]
+pub mod ui;
 pub mod utils;
 
 pub const MAX_LAYER_COUNT: usize = 4;
 pub const DEFAULT_CHUNK_SIZE: u32 = 16;
 
+/// The prelude of this crate.
+///
+/// Importing `prelude::*` gives you everything from all the enabled subsystems.
+/// If you only use part of the crate, import the sub-preludes like [`prelude::tilemap`] instead.
+///
+/// The sub-prelude for saving and loading is `prelude::serializing`, as a module named `serde`
+/// would clash with the `serde` crate when glob imported.
 pub mod prelude {
-    #[cfg(feature = "algorithm")]
-    pub use crate::algorithm::{
-        pathfinding::{Path, PathFinder, PathFindingQueue, PathTilemaps},
-        wfc::{WfcRules, WfcRunner, WfcSource},
-    };
-    #[cfg(feature = "ldtk")]
-    pub use crate::ldtk::{
-        app_ext::LdtkApp,
-        components::{EntityIid, LayerIid, LevelIid, WorldIid},
-        events::{
-            LdtkLevel, LdtkLevelEvent, LdtkLevelLoader, LdtkLevelLoaderMode, LdtkLevelUnloader,
-        },
-        json::LdtkJson,
-        resources::{LdtkAssets, LdtkLevelConfig, LdtkLoadedLevels},
+    pub use self::v1::{
+        chunking, diagnostics, diff, effects, picking, replay, replication, territory, tilemap,
+        trigger, tween,
     };
-    pub use crate::math::GridRect;
+
+    #[cfg(feature = "algorithm")]
+    pub use self::v1::algo;
+    #[cfg(feature = "audio")]
+    pub use self::v1::audio;
     #[cfg(feature = "baking")]
-    pub use crate::render::bake::{BakedTilemap, TilemapBaker};
-    pub use crate::render::material::{
-        EntiTilesMaterialPlugin, StandardTilemapMaterial, TilemapMaterial,
-    };
+    pub use self::v1::baking;
+    #[cfg(feature = "ecs_tilemap")]
+    pub use self::v1::ecs_tilemap;
+    #[cfg(feature = "ldtk")]
+    pub use self::v1::ldtk;
+    #[cfg(feature = "physics")]
+    pub use self::v1::physics;
     #[cfg(feature = "serializing")]
-    pub use crate::serializing::{
-        chunk::{
-            load::{ChunkLoadCache, ChunkLoadConfig},
-            save::{ChunkSaveCache, ChunkSaveConfig},
-        },
-        map::{
-            load::TilemapLoader,
-            save::{TilemapSaver, TilemapSaverMode},
-            TilemapLayer,
-        },
-    };
+    pub use self::v1::serializing;
+    #[cfg(feature = "sprite_sheet")]
+    pub use self::v1::sprite_sheet;
     #[cfg(feature = "tiled")]
-    pub use crate::tiled::resources::TiledLoadConfig;
+    pub use self::v1::tiled;
+    #[cfg(feature = "ui")]
+    pub use self::v1::ui;
+    #[cfg(feature = "weather")]
+    pub use self::v1::weather;
+
+    pub use self::v1::chunking::*;
+    pub use self::v1::diagnostics::*;
+    pub use self::v1::diff::*;
+    pub use self::v1::effects::*;
+    pub use self::v1::picking::*;
+    pub use self::v1::replay::*;
+    pub use self::v1::replication::*;
+    pub use self::v1::territory::*;
+    pub use self::v1::tilemap::*;
+    pub use self::v1::trigger::*;
+    pub use self::v1::tween::*;
+
     #[cfg(feature = "algorithm")]
-    pub use crate::tilemap::algorithm::path::{PathTile, PathTilemap};
+    pub use self::v1::algo::*;
+    #[cfg(feature = "audio")]
+    pub use self::v1::audio::*;
+    #[cfg(feature = "baking")]
+    pub use self::v1::baking::*;
+    #[cfg(feature = "ecs_tilemap")]
+    pub use self::v1::ecs_tilemap::*;
+    #[cfg(feature = "ldtk")]
+    pub use self::v1::ldtk::*;
     #[cfg(feature = "physics")]
-    pub use crate::tilemap::physics::{
-        DataPhysicsTilemap, PhysicsTile, PhysicsTileSpawn, PhysicsTilemap,
-    };
-    pub use crate::tilemap::{
-        bundles::MaterialTilemapBundle,
-        bundles::{StandardPureColorTilemapBundle, StandardTilemapBundle},
-        chunking::camera::{CameraChunkUpdater, CameraChunkUpdation},
-        map::{
-            TilePivot, TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName,
-            TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
-            TilemapTextures, TilemapTransform, TilemapType,
-        },
-        tile::{
-            LayerUpdater, RawTileAnimation, TileBuilder, TileLayer, TileLayerPosition, TileUpdater,
-        },
-    };
-    pub use crate::EntiTilesPlugin;
-    pub use bevy::render::render_resource::FilterMode;
+    pub use self::v1::physics::*;
+    #[cfg(feature = "serializing")]
+    pub use self::v1::serializing::*;
+    #[cfg(feature = "sprite_sheet")]
+    pub use self::v1::sprite_sheet::*;
+    #[cfg(feature = "tiled")]
+    pub use self::v1::tiled::*;
+    #[cfg(feature = "ui")]
+    pub use self::v1::ui::*;
+    #[cfg(feature = "weather")]
+    pub use self::v1::weather::*;
+
+    /// Sub-preludes split by subsystem, which are also re-exported in [`prelude`](self).
+    ///
+    /// Items in a versioned prelude won't be removed or renamed within the same version,
+    /// new versions will be added instead.
+    pub mod v1 {
+        /// Tilemaps, tiles, rendering and materials.
+        pub mod tilemap {
+            pub use crate::math::GridRect;
+            pub use crate::render::chunk::{ChunkUnload, TilemapRenderBackend, UnloadRenderChunk};
+            pub use crate::render::clip::{TilemapClip, TilemapClipShape, TilemapClipSpace};
+            pub use crate::render::cull::ChunkVisibilityChanged;
+            pub use crate::render::material::{
+                EntiTilesMaterialPlugin, LayerMaterialApp, LayerMaterialRegistry,
+                StandardTilemapMaterial, TilemapMaterial,
+            };
+            pub use crate::render::tint::{ColorCurve, TilemapGlobalTint};
+            pub use crate::render::warmup::TilemapWarmup;
+            pub use crate::render::EntiTilesRendererPlugin;
+            pub use crate::shaders::EntiTilesShaderPlugin;
+            pub use crate::shaders::TilemapCoordsUniform;
+            pub use crate::tilemap::{
+                bundles::MaterialTilemapBundle,
+                bundles::{StandardPureColorTilemapBundle, StandardTilemapBundle},
+                command::{
+                    TilemapCommand, TilemapCommandApplied, TilemapCommandConfig,
+                    TilemapCommandEvent, TilemapCommandFailed,
+                },
+                despawn::{EntiTilesCommands, EverythingUnloaded, UnloadEverything},
+                map::{
+                    EntiTilesDefaults, OffscreenAnimation, SyncWithGlobalTransform, TilePivot,
+                    TileRenderSize, TilemapAnimationLod, TilemapAnimations, TilemapLayerOpacities,
+                    TilemapName, TilemapParallax, TilemapSampler, TilemapSlotSize, TilemapStorage,
+                    TilemapTexture, TilemapTextureDescriptor, TilemapTextures, TilemapTransform,
+                    TilemapType, TilemapZOrder,
+                },
+                tile::{
+                    LayerUpdater, OneShotTileAnimation, RawTileAnimation, TileAnimation,
+                    TileAnimationMode, TileBuilder, TileLayer, TileLayerPosition, TileUpdater,
+                },
+            };
+            pub use crate::tilemap::{EntiTilesTilemapPlugin, EntiTilesTilemapSystems};
+            pub use crate::{
+                EntiTilesCorePlugin, EntiTilesPlugin, EntiTilesPlugins, EntiTilesRenderPlugins,
+                EntiTilesTilemapPlugins,
+            };
+            pub use bevy::render::render_resource::FilterMode;
+        }
+
+        /// Baking tilemaps into images, chunk thumbnails and png exporting.
+        #[cfg(feature = "baking")]
+        pub mod baking {
+            pub use crate::render::bake::{BakedTilemap, TilemapBaker};
+            pub use crate::render::export::{
+                export_to_image, export_to_png, ExportTilemapPng, TilemapExportError,
+            };
+            pub use crate::render::lod::{ChunkThumbnail, TilemapLod};
+        }
+
+        /// Loading chunks around cameras, hibernation and random ticks.
+        pub mod chunking {
+            pub use crate::tilemap::chunking::{
+                camera::{CameraChunkSetUpdation, CameraChunkUpdater, CameraChunkUpdation},
+                hibernate::{HibernatedChunk, TilemapHibernation},
+                random_tick::{RandomTick, RandomTicker},
+            };
+        }
+
+        /// Tilemap statistics.
+        pub mod diagnostics {
+            pub use crate::diagnostics::TilemapDiagnosticsPlugin;
+        }
+
+        /// Recording the changes of tilemaps as diffs.
+        pub mod diff {
+            pub use crate::tilemap::diff::{TilemapDiff, TilemapDiffRecorder, TilemapLayerDiff};
+        }
+
+        /// Post processing effects, shadows and height maps.
+        pub mod effects {
+            pub use crate::render::post_processing::{
+                TilemapPostEffect, TilemapPostEffectApp, TilemapPostEffectStage,
+            };
+            pub use crate::render::shadow::TilemapShadow;
+            pub use crate::tilemap::height::TilemapHeightMap;
+        }
+
+        /// Finding tiles by rays and lines.
+        pub mod picking {
+            pub use crate::math::raycast::{tile_line, tile_raycast, TileLineMode};
+            pub use crate::tilemap::picking::{TilemapHit, TilemapRaycast};
+        }
+
+        /// Recording and replaying the commands applied to tilemaps.
+        pub mod replay {
+            pub use crate::tilemap::replay::{TilemapPlayer, TilemapRecorder, TilemapRecording};
+        }
+
+        /// Streaming tile changes to other peers.
+        pub mod replication {
+            pub use crate::tilemap::replication::{TileChangeOp, TileChangeOps, TileChangeStream};
+        }
+
+        /// Ownership overlays and their borders.
+        pub mod territory {
+            pub use crate::tilemap::territory::{
+                TerritoryBorders, TerritoryOverlay, TerritoryTilemap,
+            };
+        }
+
+        /// Regions that send events when actors enter or exit them.
+        pub mod trigger {
+            pub use crate::tilemap::trigger::{
+                RegionEntered, RegionExited, TileTriggerActor, TileTriggerTilemap,
+            };
+        }
+
+        /// Tweening tilemap properties.
+        pub mod tween {
+            pub use crate::tilemap::tween::{
+                TilemapEasing, TilemapTween, TilemapTweenFinished, TilemapTweenTarget,
+                TilemapTweens,
+            };
+        }
+
+        /// Sounds played in tile regions.
+        #[cfg(feature = "audio")]
+        pub mod audio {
+            pub use crate::tilemap::audio::{
+                EntiTilesAudioPlugin, TileAudioArea, TileAudioRegion, TilemapAudioRegions,
+            };
+        }
+
+        /// Rain and snow clipped by the tilemaps.
+        #[cfg(feature = "weather")]
+        pub mod weather {
+            pub use crate::render::weather::{
+                EntiTilesWeatherPlugin, UpdateWeatherRoofMask, WeatherKind, WeatherRoofed,
+                WeatherSettings,
+            };
+        }
+
+        /// Pathfinding, wfc and path tilemaps.
+        #[cfg(feature = "algorithm")]
+        pub mod algo {
+            pub use crate::algorithm::{
+                distance::DistanceField,
+                dungeon::{BspDungeon, CaveDungeon, DungeonLayout, DungeonTheme},
+                follower::{PathFollower, PathFollowerEvent},
+                pathfinding::{
+                    Path, PathAlgorithm, PathBlocked, PathFinder, PathFindingQueue, PathHeuristic,
+                    PathResultOrder, PathTilemaps,
+                },
+                reachable::ReachableArea,
+                wfc::{WfcRules, WfcRunner, WfcSource},
+                EntiTilesAlgorithmPlugin,
+            };
+            pub use crate::tilemap::algorithm::path::{PathTile, PathTilemap};
+        }
+
+        /// Migrating from `bevy_ecs_tilemap`.
+        #[cfg(feature = "ecs_tilemap")]
+        pub mod ecs_tilemap {
+            pub use crate::ecs_tilemap::ImportEcsTilemap;
+        }
+
+        /// LDtk importing.
+        #[cfg(feature = "ldtk")]
+        pub mod ldtk {
+            pub use crate::ldtk::{
+                app_ext::LdtkApp,
+                components::{EntityIid, LayerIid, LevelIid, WorldIid},
+                events::{
+                    LdtkLayerOverride, LdtkLevel, LdtkLevelEvent, LdtkLevelLoader,
+                    LdtkLevelLoaderMode, LdtkLevelUnloader,
+                },
+                json::LdtkJson,
+                migration::{LdtkIidRemapper, LdtkIidTable, LdtkIidTables},
+                persistence::{LdtkPersistentStates, PersistentLdtkEntity},
+                resources::{LdtkAssets, LdtkLevelConfig, LdtkLoadedLevels},
+                EntiTilesLdtkPlugin,
+            };
+            pub use crate::utils::filter::LayerFilter;
+        }
+
+        /// Physics tilemaps.
+        #[cfg(feature = "physics")]
+        pub mod physics {
+            pub use crate::tilemap::physics::{
+                DataPhysicsTilemap, EntiTilesPhysicsTilemapPlugin, PhysicsCollider,
+                PhysicsCollisionLayers, PhysicsTile, PhysicsTileIndex, PhysicsTileSpawn,
+                PhysicsTilemap, PhysicsTilemapFollow,
+            };
+        }
+
+        /// Saving and loading tilemaps and chunks.
+        #[cfg(feature = "serializing")]
+        pub mod serializing {
+            pub use crate::serializing::{
+                chunk::{
+                    backend::{
+                        ChunkIo, ChunkIoBackend, FileSystemChunkBackend, MemoryChunkBackend,
+                        PackedChunkBackend,
+                    },
+                    load::{ChunkLoadCache, ChunkLoadConfig},
+                    persistence::AutoChunkPersistence,
+                    save::{ChunkSaveCache, ChunkSaveConfig},
+                },
+                map::{
+                    load::TilemapLoader,
+                    save::{TilemapSaver, TilemapSaverMode},
+                    TilemapLayer,
+                },
+                material::SerializableMaterial,
+                prefab::{
+                    PrefabApp, PrefabEntity, PrefabLayer, PrefabSpawned, SpawnPrefab, TilemapPrefab,
+                },
+                version::{SaveFormat, SaveMigrationApp, SaveMigrations, SAVE_FORMAT_VERSION},
+                EntiTilesSerializingPlugin,
+            };
+        }
+
+        /// TexturePacker and Aseprite sprite sheet importing.
+        #[cfg(feature = "sprite_sheet")]
+        pub mod sprite_sheet {
+            pub use crate::sprite_sheet::{
+                EntiTilesSpriteSheetPlugin, SpriteSheet, SpriteSheetLoaderSettings,
+            };
+        }
+
+        /// Tiled importing.
+        #[cfg(feature = "tiled")]
+        pub mod tiled {
+            pub use crate::tiled::{
+                app_ext::TiledApp,
+                components::{TiledLayerInfo, TiledLayerType, TiledLoadedTilemap},
+                events::{TiledMapEvent, TiledMapLoader, TiledMapUnloader},
+                resources::{PackedTiledTilemap, TiledLoadConfig, TiledLoadedMaps},
+                EntiTilesTiledPlugin,
+            };
+            pub use crate::utils::filter::LayerFilter;
+        }
+
+        /// Tilemaps inside bevy_ui nodes.
+        #[cfg(feature = "ui")]
+        pub mod ui {
+            pub use crate::ui::{
+                EntiTilesUiPlugin, UiTile, UiTileAnimation, UiTilePanel, UiTilemap,
+            };
+        }
+    }
 }
 
 #[cfg(all(
@@ -94,26 +368,161 @@ compile_error!(
     and `multi-threaded` feature is disabled."
 );
 
+/// Everything that doesn't require rendering: tilemap storage, math, algorithms,
+/// physics, audio and serializing.
+///
+/// These plugins never touch the `RenderApp`, so they work in headless apps
+/// like dedicated servers, as long as `AssetPlugin` is added.
+pub struct EntiTilesCorePlugin;
+
+impl PluginGroup for EntiTilesCorePlugin {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(EntiTilesTilemapPlugin)
+            .add(EntiTilesMathPlugin);
+
+        #[cfg(feature = "algorithm")]
+        let group = group.add(algorithm::EntiTilesAlgorithmPlugin);
+        #[cfg(feature = "physics")]
+        let group = group.add(tilemap::physics::EntiTilesPhysicsTilemapPlugin);
+        #[cfg(feature = "audio")]
+        let group = group.add(tilemap::audio::EntiTilesAudioPlugin);
+        #[cfg(feature = "serializing")]
+        let group = group.add(serializing::EntiTilesSerializingPlugin::<
+            StandardTilemapMaterial,
+        >::default());
+
+        group
+    }
+}
+
+/// The plugins that render tilemaps.
+pub struct EntiTilesRenderPlugins;
+
+impl PluginGroup for EntiTilesRenderPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(EntiTilesRendererPlugin)
+            .add(EntiTilesMaterialPlugin::<StandardTilemapMaterial>::default())
+            .add(EntiTilesShaderPlugin);
+
+        #[cfg(feature = "debug")]
+        let group = group.add(debug::EntiTilesDebugPlugin);
+        #[cfg(feature = "weather")]
+        let group = group.add(render::weather::EntiTilesWeatherPlugin);
+
+        group
+    }
+}
+
+/// The plugins that are required to create and render tilemaps, which are
+/// [`EntiTilesCorePlugin`] and [`EntiTilesRenderPlugins`].
+///
+/// Use this instead of [`EntiTilesPlugins`] if you don't need the importers or ui tilemaps,
+/// and add their plugins (like `EntiTilesLdtkPlugin`) separately.
+pub struct EntiTilesTilemapPlugins;
+
+impl PluginGroup for EntiTilesTilemapPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add_group(EntiTilesCorePlugin)
+            .add_group(EntiTilesRenderPlugins)
+    }
+}
+
+/// Every plugin of this crate, which are [`EntiTilesTilemapPlugins`] along with the importers,
+/// sprite sheets and ui tilemaps. Each of them can be disabled or replaced individually.
+///
+/// Plugins here don't rely on each other except [`EntiTilesTilemapPlugin`], which
+/// is required by all the others. For example, to save and load tilemaps on a server:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_entitiles::prelude::*;
+/// App::new().add_plugins((
+///     MinimalPlugins,
+///     AssetPlugin::default(),
+///     EntiTilesPlugins
+///         .build()
+///         .disable::<EntiTilesRendererPlugin>()
+///         .disable::<EntiTilesMaterialPlugin<StandardTilemapMaterial>>()
+///         .disable::<EntiTilesShaderPlugin>(),
+/// ));
+/// ```
+pub struct EntiTilesPlugins;
+
+impl PluginGroup for EntiTilesPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>().add_group(EntiTilesTilemapPlugins);
+
+        #[cfg(feature = "ldtk")]
+        let group = group.add(ldtk::EntiTilesLdtkPlugin);
+        #[cfg(feature = "tiled")]
+        let group = group.add(tiled::EntiTilesTiledPlugin);
+        #[cfg(feature = "sprite_sheet")]
+        let group = group.add(sprite_sheet::EntiTilesSpriteSheetPlugin);
+        #[cfg(feature = "ui")]
+        let group = group.add(ui::EntiTilesUiPlugin);
+
+        group
+    }
+}
+
+/// All the plugins with the default settings. Same as [`EntiTilesPlugins`].
 pub struct EntiTilesPlugin;
 
 impl Plugin for EntiTilesPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_plugins((
-            EntiTilesTilemapPlugin,
+        app.add_plugins(EntiTilesPlugins);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, PluginGroup},
+        asset::AssetPlugin,
+        MinimalPlugins,
+    };
+
+    use crate::{
+        render::{
+            material::{EntiTilesMaterialPlugin, StandardTilemapMaterial},
             EntiTilesRendererPlugin,
-            EntiTilesMaterialPlugin::<StandardTilemapMaterial>::default(),
-            EntiTilesMathPlugin,
-            EntiTilesShaderPlugin,
-            #[cfg(feature = "debug")]
-            debug::EntiTilesDebugPlugin,
-            #[cfg(feature = "algorithm")]
-            algorithm::EntiTilesAlgorithmPlugin,
-            #[cfg(feature = "serializing")]
-            serializing::EntiTilesSerializingPlugin::<StandardTilemapMaterial>::default(),
-            #[cfg(feature = "ldtk")]
-            ldtk::EntiTilesLdtkPlugin,
-            #[cfg(feature = "tiled")]
-            tiled::EntiTilesTiledPlugin,
-        ));
+        },
+        shaders::EntiTilesShaderPlugin,
+        EntiTilesPlugins,
+    };
+
+    /// The importers are kept, as they shouldn't need the renderer to load maps.
+    #[test]
+    fn test_headless_plugins() {
+        let group = EntiTilesPlugins
+            .build()
+            .disable::<EntiTilesRendererPlugin>()
+            .disable::<EntiTilesMaterialPlugin<StandardTilemapMaterial>>()
+            .disable::<EntiTilesShaderPlugin>();
+        #[cfg(feature = "debug")]
+        let group = group.disable::<crate::debug::EntiTilesDebugPlugin>();
+        #[cfg(feature = "weather")]
+        let group = group.disable::<crate::render::weather::EntiTilesWeatherPlugin>();
+        #[cfg(feature = "sprite_sheet")]
+        let group = group.disable::<crate::sprite_sheet::EntiTilesSpriteSheetPlugin>();
+        #[cfg(feature = "ui")]
+        let group = group.disable::<crate::ui::EntiTilesUiPlugin>();
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), group));
+        app.update();
+        app.update();
+
+        #[cfg(feature = "ldtk")]
+        assert!(app
+            .world()
+            .contains_resource::<crate::ldtk::resources::LdtkLoadedLevels>());
+        #[cfg(feature = "tiled")]
+        assert!(app
+            .world()
+            .contains_resource::<crate::tiled::resources::TiledLoadedMaps>());
     }
 }
@@ -16,6 +16,7 @@ use bevy::{
 use crate::math::ext::RectTransformation;
 
 pub mod ext;
+pub mod raycast;
 
 pub struct EntiTilesMathPlugin;
 
@@ -24,7 +25,8 @@ impl Plugin for EntiTilesMathPlugin {
         app.add_systems(Update, (camera_aabb_adder, camera_aabb_updater));
 
         app.register_type::<GridRect>()
-            .register_type::<CameraAabb2d>();
+            .register_type::<CameraAabb2d>()
+            .register_type::<raycast::TileLineMode>();
     }
 }
 
@@ -50,8 +52,8 @@ pub fn camera_aabb_updater(
             Added<OrthographicProjection>,
         )>,
     >,
-    #[cfg(feature = "debug")] camera_aabb_scale: bevy::ecs::system::Res<
-        crate::debug::CameraAabbScale,
+    #[cfg(feature = "debug")] camera_aabb_scale: Option<
+        bevy::ecs::system::Res<crate::debug::CameraAabbScale>,
     >,
 ) {
     cameras_query.iter_mut().for_each(|(entity, proj, trans)| {
@@ -62,7 +64,12 @@ pub fn camera_aabb_updater(
                 max: proj.area.max,
             }
             .with_translation(trans.translation.xy())
-            .with_scale(camera_aabb_scale.0, bevy::math::Vec2::splat(0.5)),
+            .with_scale(
+                camera_aabb_scale
+                    .as_ref()
+                    .map_or(bevy::math::Vec2::ONE, |s| s.0),
+                bevy::math::Vec2::splat(0.5),
+            ),
         ));
         #[cfg(not(feature = "debug"))]
         commands.entity(entity).insert(CameraAabb2d(
@@ -75,7 +82,7 @@ pub fn camera_aabb_updater(
     });
 }
 
-#[derive(Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct GridRect {
     pub origin: IVec2,
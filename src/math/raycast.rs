@@ -0,0 +1,249 @@
+use std::cmp::Ordering;
+
+use bevy::{
+    math::{IVec2, Vec2, Vec3},
+    reflect::Reflect,
+};
+
+use crate::tilemap::map::TilemapType;
+
+#[cfg(feature = "algorithm")]
+use crate::tilemap::algorithm::path::PathTilemap;
+
+/// How to pick the tiles a line passes through.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum TileLineMode {
+    /// Every tile the line touches. When the line passes exactly through a corner,
+    /// both tiles beside it are included, so rays can't slip between two diagonal walls.
+    #[default]
+    Supercover,
+    /// Only one tile per step along the major axis. Cheaper and thinner,
+    /// but rays may pass between two diagonal walls.
+    Bresenham,
+}
+
+/// The tiles that the line from `from` to `to` passes through, both ends included.
+///
+/// Isometric tilemaps use the same indices as square ones, so they are handled the same way.
+pub fn tile_line(from: IVec2, to: IVec2, ty: TilemapType, mode: TileLineMode) -> Vec<IVec2> {
+    match ty {
+        TilemapType::Hexagonal(_) => hex_line(from, to, mode),
+        _ => match mode {
+            TileLineMode::Supercover => supercover_line(from, to),
+            TileLineMode::Bresenham => bresenham_line(from, to),
+        },
+    }
+}
+
+/// Cast a ray from `from` to `to` and return the first tile that is blocked.
+/// `from` itself is never checked.
+///
+/// Returns `None` if nothing blocks the way, which means `to` is visible from `from`.
+pub fn tile_raycast(
+    from: IVec2,
+    to: IVec2,
+    ty: TilemapType,
+    mode: TileLineMode,
+    blocked: impl Fn(IVec2) -> bool,
+) -> Option<IVec2> {
+    tile_line(from, to, ty, mode)
+        .into_iter()
+        .skip(1)
+        .find(|index| blocked(*index))
+}
+
+fn supercover_line(from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let delta = (to - from).abs();
+    let step = (to - from).signum();
+    let mut cur = from;
+    let (mut x, mut y) = (0, 0);
+    let mut line = vec![from];
+
+    while x < delta.x || y < delta.y {
+        // Compare where the line crosses the next vertical and horizontal edge.
+        match ((1 + 2 * x) * delta.y).cmp(&((1 + 2 * y) * delta.x)) {
+            Ordering::Less => {
+                cur.x += step.x;
+                x += 1;
+            }
+            Ordering::Greater => {
+                cur.y += step.y;
+                y += 1;
+            }
+            Ordering::Equal => {
+                line.push(cur + IVec2::new(step.x, 0));
+                line.push(cur + IVec2::new(0, step.y));
+                cur += step;
+                x += 1;
+                y += 1;
+            }
+        }
+        line.push(cur);
+    }
+
+    line
+}
+
+fn bresenham_line(from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let delta = (to - from).abs() * IVec2::new(1, -1);
+    let step = (to - from).signum();
+    let mut cur = from;
+    let mut err = delta.x + delta.y;
+    let mut line = vec![from];
+
+    while cur != to {
+        let e2 = err * 2;
+        if e2 >= delta.y {
+            err += delta.y;
+            cur.x += step.x;
+        }
+        if e2 <= delta.x {
+            err += delta.x;
+            cur.y += step.y;
+        }
+        line.push(cur);
+    }
+
+    line
+}
+
+fn hex_line(from: IVec2, to: IVec2, mode: TileLineMode) -> Vec<IVec2> {
+    let n = hex_distance(from, to);
+    if n == 0 {
+        return vec![from];
+    }
+
+    // Nudge the line a little so it never lies exactly on the edges.
+    // Supercover takes both sides when they are different.
+    let nudge = Vec2::new(1e-3, 2e-3);
+    let (a, b) = (from.as_vec2(), to.as_vec2());
+    let mut line = vec![from];
+
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let lhs = hex_round(a.lerp(b, t) + nudge);
+        if mode == TileLineMode::Supercover {
+            let rhs = hex_round(a.lerp(b, t) - nudge);
+            if rhs != lhs && !line.contains(&rhs) {
+                line.push(rhs);
+            }
+        }
+        if !line.contains(&lhs) {
+            line.push(lhs);
+        }
+    }
+
+    line
+}
+
+/// The neighbours of a hexagonal tile are `±(1, 0)`, `±(0, 1)` and `±(1, 1)`.
+#[inline]
+pub(crate) fn hex_distance(a: IVec2, b: IVec2) -> i32 {
+    let d = b - a;
+    d.x.abs().max(d.y.abs()).max((d.x - d.y).abs())
+}
+
+pub(crate) fn hex_round(index: Vec2) -> IVec2 {
+    let cube = Vec3::new(index.x, -index.y, index.y - index.x);
+    let mut rounded = cube.round();
+    let diff = (rounded - cube).abs();
+
+    if diff.x > diff.y && diff.x > diff.z {
+        rounded.x = -rounded.y - rounded.z;
+    } else if diff.y > diff.z {
+        rounded.y = -rounded.x - rounded.z;
+    }
+
+    IVec2::new(rounded.x as i32, -rounded.y as i32)
+}
+
+#[cfg(feature = "algorithm")]
+impl PathTilemap {
+    /// Cast a ray on this path tilemap. Tiles that are not walkable block the ray.
+    ///
+    /// See [`tile_raycast`].
+    pub fn raycast(
+        &self,
+        from: IVec2,
+        to: IVec2,
+        ty: TilemapType,
+        mode: TileLineMode,
+    ) -> Option<IVec2> {
+        tile_raycast(from, to, ty, mode, |index| self.get(index).is_none())
+    }
+
+    /// Returns true if `to` can be seen from `from`.
+    #[inline]
+    pub fn line_of_sight(
+        &self,
+        from: IVec2,
+        to: IVec2,
+        ty: TilemapType,
+        mode: TileLineMode,
+    ) -> bool {
+        self.raycast(from, to, ty, mode).is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::IVec2;
+
+    use crate::tilemap::map::TilemapType;
+
+    use super::{tile_line, tile_raycast, TileLineMode};
+
+    #[test]
+    fn test_tile_line() {
+        let line = tile_line(
+            IVec2::ZERO,
+            IVec2::splat(2),
+            TilemapType::Square,
+            TileLineMode::Supercover,
+        );
+        assert_eq!(line.len(), 7);
+        assert!(line.contains(&IVec2::X) && line.contains(&IVec2::Y));
+
+        let line = tile_line(
+            IVec2::ZERO,
+            IVec2::splat(2),
+            TilemapType::Square,
+            TileLineMode::Bresenham,
+        );
+        assert_eq!(line, vec![IVec2::ZERO, IVec2::ONE, IVec2::splat(2)]);
+
+        let line = tile_line(
+            IVec2::ZERO,
+            IVec2::new(3, 1),
+            TilemapType::Hexagonal(8),
+            TileLineMode::Bresenham,
+        );
+        assert_eq!(line.len(), 4);
+        assert_eq!(line.last(), Some(&IVec2::new(3, 1)));
+        assert!(line
+            .windows(2)
+            .all(|w| super::hex_distance(w[0], w[1]) == 1));
+
+        let blocked = |index: IVec2| index == IVec2::new(2, 0);
+        assert_eq!(
+            tile_raycast(
+                IVec2::ZERO,
+                IVec2::new(4, 0),
+                TilemapType::Isometric,
+                TileLineMode::Supercover,
+                blocked
+            ),
+            Some(IVec2::new(2, 0))
+        );
+        assert_eq!(
+            tile_raycast(
+                IVec2::ZERO,
+                IVec2::new(0, 4),
+                TilemapType::Square,
+                TileLineMode::Supercover,
+                blocked
+            ),
+            None
+        );
+    }
+}
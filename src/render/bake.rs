@@ -106,47 +106,28 @@ pub fn tilemap_baker(
         let target_size = tilemap_aabb.size().as_uvec2() * textures.textures[0].desc.size;
         let mut bake_target = vec![0; (target_size.x * target_size.y * 4) as usize];
 
-        tiles.into_iter().for_each(|(tile_index, tile)| {
-            let mut rel_index = (tile_index - tilemap_aabb.min).as_uvec2();
-            rel_index.y = tilemap_aabb.size().y as u32 - rel_index.y;
-
-            match &tile.texture {
-                TileTexture::Static(layers) => layers
-                    .iter()
-                    .rev()
-                    .take(MAX_LAYER_COUNT)
-                    .enumerate()
-                    .filter_map(|(i, l)| {
-                        if l.texture_index >= 0 {
-                            Some((opacities.0[i], l))
-                        } else {
-                            None
-                        }
-                    })
-                    .for_each(|(opacity, layer)| {
-                        set_tile(
-                            &textures.textures,
-                            &texture_images,
-                            rel_index,
-                            target_size,
-                            &mut bake_target,
-                            layer,
-                            opacity,
-                        );
-                    }),
-                TileTexture::Animated(_) => {
+        let tiles = tiles
+            .into_iter()
+            .filter(|(tile_index, tile)| {
+                let animated = matches!(tile.texture, TileTexture::Animated(_));
+                if animated {
                     warn!("Skipping animated tile at {:?}", tile_index);
                 }
-            };
-
-            set_tile_tint(
-                textures.textures[0].desc.tile_size,
-                rel_index,
-                target_size,
-                &mut bake_target,
-                tile.tint,
-            );
-        });
+                !animated
+            })
+            .map(|(tile_index, tile)| {
+                let mut rel_index = (tile_index - tilemap_aabb.min).as_uvec2();
+                rel_index.y = tilemap_aabb.size().y as u32 - rel_index.y;
+                (rel_index, tile)
+            });
+        bake_tiles(
+            textures,
+            &texture_images,
+            opacities,
+            tiles,
+            target_size,
+            &mut bake_target,
+        );
 
         let baked_tilemap = BakedTilemap {
             size_px: target_size,
@@ -176,6 +157,55 @@ pub fn tilemap_baker(
     }
 }
 
+/// Draw the static tiles into an rgba buffer of `target_size`.
+/// `rel_index` of each tile is the slot it's drawn on, from the top left.
+pub(crate) fn bake_tiles<'a>(
+    textures: &TilemapTextures,
+    texture_images: &[&Image],
+    opacities: &TilemapLayerOpacities,
+    tiles: impl Iterator<Item = (UVec2, &'a Tile)>,
+    target_size: UVec2,
+    bake_target: &mut Vec<u8>,
+) {
+    tiles.for_each(|(rel_index, tile)| {
+        let TileTexture::Static(layers) = &tile.texture else {
+            return;
+        };
+
+        layers
+            .iter()
+            .rev()
+            .take(MAX_LAYER_COUNT)
+            .enumerate()
+            .filter_map(|(i, l)| {
+                if l.texture_index >= 0 {
+                    Some((opacities.0[i], l))
+                } else {
+                    None
+                }
+            })
+            .for_each(|(opacity, layer)| {
+                set_tile(
+                    &textures.textures,
+                    texture_images,
+                    rel_index,
+                    target_size,
+                    bake_target,
+                    layer,
+                    opacity,
+                );
+            });
+
+        set_tile_tint(
+            textures.textures[0].desc.tile_size,
+            rel_index,
+            target_size,
+            bake_target,
+            tile.tint,
+        );
+    });
+}
+
 fn set_tile(
     textures: &[TilemapTexture],
     texture_images: &[&Image],
@@ -1,6 +1,7 @@
 use bevy::{
+    color::ColorToComponents,
     ecs::entity::EntityHashMap,
-    math::Vec4,
+    math::{Mat2, Vec4},
     prelude::{Res, ResMut, Resource, Vec2},
     render::{
         render_resource::{DynamicUniformBuffer, GpuArrayBuffer, ShaderType},
@@ -9,13 +10,24 @@ use bevy::{
     time::Time,
 };
 
-use crate::{render::extract::TilemapInstances, tilemap::map::TilemapType};
+use crate::{
+    render::{clip::TilemapClip, extract::TilemapInstances, tint::TilemapGlobalTint},
+    tilemap::map::TilemapType,
+};
 
 #[derive(ShaderType, Clone, Copy)]
 pub struct TilemapUniform {
     // For memory alignment
     pub rotation: Vec4,
     pub layer_opacities: Vec4,
+    /// See [`TilemapGlobalTint`].
+    pub global_tint: Vec4,
+    /// The columns of the skew matrix of [`TilemapShadow`](crate::render::shadow::TilemapShadow),
+    /// only used when drawing shadows.
+    pub shadow_skew: Vec4,
+    pub shadow_tint: Vec4,
+    /// The min and max of [`TilemapClip::rect`].
+    pub clip_rect: Vec4,
     pub tile_render_size: Vec2,
     pub translation: Vec2,
     pub slot_size: Vec2,
@@ -23,6 +35,7 @@ pub struct TilemapUniform {
     pub axis_dir: Vec2,
     pub hex_legs: f32,
     pub time: f32,
+    pub clip_flags: u32,
 }
 
 #[cfg(feature = "atlas")]
@@ -31,14 +44,20 @@ pub struct GpuTilemapTextureDescriptor {
     pub tile_uv_size: Vec2,
     pub uv_scale: Vec2,
     pub tile_count: bevy::math::UVec2,
-    #[cfg(target_arch = "wasm32")]
-    pub _padding: [u32; 2],
+    pub padding_uv: Vec2,
+    pub margin_uv: Vec2,
+    pub spacing_uv: Vec2,
+    pub tile_scale: Vec2,
+    /// Uniform arrays need a stride of multiples of 16 bytes.
+    pub _padding: Vec2,
 }
 
 #[derive(Default)]
 pub struct SharedTilemapBuffers {
     pub uniform: DynamicUniformBuffer<TilemapUniform>,
     pub indices: EntityHashMap<u32>,
+    /// The uniforms for drawing the [`TilemapShadow`](crate::render::shadow::TilemapShadow)s.
+    pub shadow_indices: EntityHashMap<u32>,
 }
 
 pub struct UnsharedTilemapBuffers {
@@ -72,43 +91,78 @@ pub fn prepare_tilemap_buffers(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
     time: Res<Time>,
+    global_tint: Option<Res<TilemapGlobalTint>>,
+    mut stats: Option<ResMut<crate::diagnostics::TilemapRenderStats>>,
     #[cfg(feature = "atlas")] textures_assets: Res<
         bevy::render::render_asset::RenderAssets<crate::tilemap::map::TilemapTextures>,
     >,
 ) {
     tilemap_buffers.shared.uniform.clear();
+    tilemap_buffers.shared.shadow_indices.clear();
+    let global_tint = global_tint.map_or(Vec4::ONE, |t| t.tint.to_vec4());
+    // Nothing is written if there are no tilemaps.
+    let mut uniform_bytes = 0;
 
     for (entity, tilemap) in tilemap_instances.iter() {
-        let index = tilemap_buffers.shared.uniform.push(&TilemapUniform {
+        let (clip_rect, clip_flags) = TilemapClip::as_uniform(tilemap.clip.as_ref());
+        let uniform = TilemapUniform {
             translation: tilemap.transform.translation,
-            rotation: Vec4::from_array(tilemap.transform.get_rotation_matrix().to_cols_array()),
+            rotation: Vec4::from_array(tilemap.transform.get_matrix().to_cols_array()),
             tile_render_size: tilemap.tile_render_size,
             slot_size: tilemap.slot_size,
             pivot: tilemap.tile_pivot,
             layer_opacities: tilemap.layer_opacities,
+            global_tint,
             axis_dir: tilemap.axis_flip.as_vec2(),
             hex_legs: match tilemap.ty {
                 TilemapType::Hexagonal(legs) => legs as f32,
                 _ => 0.,
             },
-            time: time.elapsed_seconds(),
-        });
+            time: tilemap
+                .animation_time
+                .unwrap_or_else(|| time.elapsed_seconds()),
+            shadow_skew: Vec4::from_array(Mat2::IDENTITY.to_cols_array()),
+            shadow_tint: Vec4::ZERO,
+            clip_rect,
+            clip_flags,
+        };
+        let index = tilemap_buffers.shared.uniform.push(&uniform);
         tilemap_buffers.shared.indices.insert(*entity, index);
+        uniform_bytes = index as u64 + TilemapUniform::min_size().get();
+
+        if let Some(shadow) = tilemap.shadow {
+            let index = tilemap_buffers.shared.uniform.push(&TilemapUniform {
+                translation: uniform.translation + shadow.offset,
+                shadow_skew: Vec4::from_array(shadow.skew.to_cols_array()),
+                shadow_tint: shadow.tint.to_vec4(),
+                ..uniform
+            });
+            tilemap_buffers.shared.shadow_indices.insert(*entity, index);
+            uniform_bytes = index as u64 + TilemapUniform::min_size().get();
+        }
 
         let unshared = tilemap_buffers
             .unshared
             .entry(*entity)
             .or_insert_with(|| UnsharedTilemapBuffers::new(&render_device));
         if let Some(anim) = &tilemap.changed_animations {
+            unshared.animation.clear();
             for data in &anim.0 {
                 #[cfg(not(target_arch = "wasm32"))]
                 unshared.animation.push(*data);
                 #[cfg(target_arch = "wasm32")]
-                unshared.animation.push(bevy::math::IVec4::new(*data, 0, 0, 0));
+                unshared
+                    .animation
+                    .push(bevy::math::IVec4::new(*data, 0, 0, 0));
             }
             unshared
                 .animation
                 .write_buffer(&render_device, &render_queue);
+            if let Some(stats) = stats.as_mut() {
+                // Each frame is padded to a `IVec4` on wasm.
+                let frame_size = if cfg!(target_arch = "wasm32") { 16 } else { 4 };
+                stats.uploaded_bytes += anim.0.len() as u64 * frame_size;
+            }
         }
 
         #[cfg(feature = "atlas")]
@@ -118,9 +172,13 @@ pub fn prepare_tilemap_buffers(
 
                 for (i, t) in textures.textures.iter().enumerate() {
                     unshared.texture_desc.push(GpuTilemapTextureDescriptor {
-                        tile_count: t.desc.size / t.desc.tile_size,
+                        tile_count: t.desc.tile_count(),
                         tile_uv_size: t.desc.tile_size.as_vec2() / t.desc.size.as_vec2(),
                         uv_scale: textures.uv_scales[i],
+                        padding_uv: Vec2::splat(t.desc.padding as f32) / t.desc.size.as_vec2(),
+                        margin_uv: Vec2::splat(t.desc.margin as f32) / t.desc.size.as_vec2(),
+                        spacing_uv: Vec2::splat(t.desc.spacing as f32) / t.desc.size.as_vec2(),
+                        tile_scale: textures.tile_scales[i],
                         ..Default::default()
                     });
                 }
@@ -136,4 +194,7 @@ pub fn prepare_tilemap_buffers(
         .shared
         .uniform
         .write_buffer(&render_device, &render_queue);
+    if let Some(stats) = stats.as_mut() {
+        stats.uploaded_bytes += uniform_bytes;
+    }
 }
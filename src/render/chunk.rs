@@ -1,31 +1,37 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, ops::Range};
 
 use bevy::{
     asset::Handle,
     color::ColorToComponents,
     ecs::{component::Component, entity::EntityHashMap, event::Event},
-    math::{IVec2, IVec4, Rect},
+    math::{IVec2, IVec4, Rect, UVec4, Vec2},
     prelude::{Entity, Mesh, Res, ResMut, Resource, Vec3, Vec4},
     reflect::Reflect,
     render::{
         mesh::{BaseMeshPipelineKey, GpuBufferInfo, GpuMesh, Indices, MeshVertexBufferLayouts},
         render_asset::RenderAssetUsages,
-        render_resource::{BufferInitDescriptor, BufferUsages, IndexFormat, PrimitiveTopology},
-        renderer::RenderDevice,
+        render_resource::{
+            Buffer, BufferDescriptor, BufferInitDescriptor, BufferUsages, IndexFormat,
+            PrimitiveTopology,
+        },
+        renderer::{RenderDevice, RenderQueue},
     },
+    utils::HashSet,
 };
 use indexmap::{map::Entry, IndexMap};
 use rayon::iter::ParallelIterator;
 
 use crate::{
+    diagnostics::TilemapRenderStats,
     math::ext::{DivToFloor, RectFromTilemap},
     render::{
         extract::{ExtractedTile, ExtractedTilemap, TilemapInstances},
         material::TilemapMaterial,
-        TILEMAP_MESH_ATTR_ATLAS_INDICES, TILEMAP_MESH_ATTR_COLOR, TILEMAP_MESH_ATTR_INDEX,
+        TILEMAP_MESH_ATTR_ATLAS_INDICES, TILEMAP_MESH_ATTR_COLOR, TILEMAP_MESH_ATTR_EMISSIVE,
+        TILEMAP_MESH_ATTR_INDEX, TILEMAP_MESH_ATTR_LAYER_SCALES,
     },
     tilemap::{
-        map::{TilemapTextures, TilemapType},
+        map::{TilemapTextures, TilemapTransform, TilemapType},
         tile::{Tile, TileTexture},
     },
     MAX_LAYER_COUNT,
@@ -34,6 +40,10 @@ use crate::{
 #[cfg(feature = "atlas")]
 use super::TILEMAP_MESH_ATTR_TEX_INDICES;
 
+/// The render chunks to unload of a tilemap, extracted from [`ChunkUnload`]s.
+///
+/// This lives on the tilemap entities in the render world, and only lasts for one frame.
+/// Send [`ChunkUnload`] in the main world instead of inserting this.
 #[derive(Component, Default, Debug, Clone, Reflect)]
 pub struct UnloadRenderChunk(pub Vec<IVec2>);
 
@@ -51,6 +61,33 @@ pub enum RenderChunkSort {
     YReverseThenXReverse,
 }
 
+/// How the tiles of a tilemap are drawn. Insert this to a tilemap to change it.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum TilemapRenderBackend {
+    /// Build a mesh for each chunk.
+    ///
+    /// Changing a tile rebuilds and uploads the mesh of the whole chunk,
+    /// which is fine for tilemaps that are rarely modified.
+    #[default]
+    Mesh,
+    /// Draw each tile as an instance of a quad. The tiles are stored in one buffer
+    /// per tilemap, and each chunk owns a fixed range of it.
+    ///
+    /// Changing tiles only uploads the modified part of their chunks,
+    /// which suits tilemaps modified every frame, like falling sand games.
+    ///
+    /// **Notice**: Empty slots in the chunks are still drawn as collapsed quads,
+    /// so sparse tilemaps are cheaper to draw with [`TilemapRenderBackend::Mesh`].
+    Instanced,
+}
+
+/// Send this to drop the render chunk at `index` of `tilemap`, and free its gpu buffers.
+///
+/// The tiles in the main world are not touched, so usually they are removed along with it
+/// using [`TilemapStorage::remove_chunk`](crate::tilemap::map::TilemapStorage::remove_chunk).
+/// If they are kept, the chunk stays hidden until its tiles change or
+/// [`TilemapStorage::mark_chunk_dirty`](crate::tilemap::map::TilemapStorage::mark_chunk_dirty)
+/// is called.
 #[derive(Event, Debug, Clone)]
 pub struct ChunkUnload {
     pub tilemap: Entity,
@@ -61,13 +98,80 @@ pub struct ChunkUnload {
 pub struct MeshTileData {
     // When the third and forth component of index are not -1,
     // it means this tile is a animated tile
-    // So the zw components are the start index and the length of the animation sequence
+    // So the zw components are the start index of the animation sequence
+    // and the bits of the time when the animation started.
+    // The z component is `EMPTY_INSTANCE` for empty slots of instanced chunks.
     pub index: IVec4,
     // 4 layers
     #[cfg(feature = "atlas")]
     pub texture_indices: IVec4,
     pub atlas_indices: IVec4,
+    /// The render scale of each layer, packed by [`pack_render_scale`].
+    pub layer_scales: UVec4,
     pub tint: Vec4,
+    pub emissive: f32,
+}
+
+impl MeshTileData {
+    /// Marks the empty slots of instanced chunks, which are collapsed in the vertex shader.
+    pub const EMPTY_INSTANCE: i32 = -2;
+
+    /// The size of each instance in bytes, the layout is the same as the vertices of the meshes.
+    pub fn instance_size(is_pure_color: bool) -> usize {
+        // position + index + color + emissive
+        let mut size = 12 + 16 + 16 + 4;
+        if !is_pure_color {
+            // atlas indices + layer render scales
+            size += 16 + 16;
+            #[cfg(feature = "atlas")]
+            {
+                // texture indices
+                size += 16;
+            }
+        }
+        size
+    }
+
+    /// Write the instance data of `tile` in the order of the vertex attributes,
+    /// or an empty slot if it's `None`.
+    pub fn write_instance(tile: Option<&Self>, is_pure_color: bool, bytes: &mut Vec<u8>) {
+        let empty = MeshTileData {
+            index: IVec4::new(0, 0, Self::EMPTY_INSTANCE, -1),
+            #[cfg(feature = "atlas")]
+            texture_indices: IVec4::NEG_ONE,
+            atlas_indices: IVec4::NEG_ONE,
+            layer_scales: UVec4::ZERO,
+            tint: Vec4::ZERO,
+            emissive: 0.,
+        };
+        let tile = tile.unwrap_or(&empty);
+        let mut write = |values: &[u32]| {
+            values
+                .iter()
+                .for_each(|v| bytes.extend_from_slice(&v.to_ne_bytes()))
+        };
+
+        write(&Vec3::ZERO.to_array().map(f32::to_bits));
+        write(&tile.index.to_array().map(|i| i as u32));
+        write(&tile.tint.to_array().map(f32::to_bits));
+        if !is_pure_color {
+            write(&tile.atlas_indices.to_array().map(|i| i as u32));
+            #[cfg(feature = "atlas")]
+            write(&tile.texture_indices.to_array().map(|i| i as u32));
+        }
+        write(&[tile.emissive.to_bits()]);
+        if !is_pure_color {
+            write(&tile.layer_scales.to_array());
+        }
+    }
+}
+
+/// Pack the render scale into 8.8 fixed point numbers, x in the low 16 bits.
+pub fn pack_render_scale(scale: Vec2) -> u32 {
+    let scale = (scale.clamp(Vec2::ZERO, Vec2::splat(255.)) * 256.)
+        .round()
+        .as_uvec2();
+    scale.x | (scale.y << 16)
 }
 
 #[derive(Clone)]
@@ -81,6 +185,10 @@ pub struct TilemapRenderChunk {
     pub tiles: Vec<Option<MeshTileData>>,
     pub mesh: Mesh,
     pub gpu_mesh: Option<GpuMesh>,
+    /// The position of the chunk in the instance buffer of the tilemap, in chunks.
+    pub instance_slot: u32,
+    /// The tiles that need to be uploaded when using [`TilemapRenderBackend::Instanced`].
+    pub dirty_instances: Range<usize>,
     pub aabb: Rect,
 }
 
@@ -99,6 +207,8 @@ impl TilemapRenderChunk {
             ),
             gpu_mesh: None,
             dirty_mesh: true,
+            instance_slot: 0,
+            dirty_instances: 0..(tilemap.chunk_size * tilemap.chunk_size) as usize,
             aabb: Rect::from_tilemap(
                 index,
                 tilemap.chunk_size,
@@ -125,9 +235,11 @@ impl TilemapRenderChunk {
         #[cfg(feature = "atlas")]
         let mut texture_indices = Vec::with_capacity(len * 4);
         let mut atlas_indices = Vec::with_capacity(len * 4);
+        let mut layer_scales = Vec::with_capacity(len * 4);
         let mut grid_indices = Vec::with_capacity(len * 4);
         let mut vertex_indices = Vec::with_capacity(len * 6);
         let mut color = Vec::with_capacity(len * 4);
+        let mut emissive = Vec::with_capacity(len * 4);
 
         for tile_data in self.tiles.iter() {
             if let Some(tile) = tile_data {
@@ -146,6 +258,7 @@ impl TilemapRenderChunk {
                         tile.atlas_indices,
                         tile.atlas_indices,
                     ]);
+                    layer_scales.extend_from_slice(&[tile.layer_scales; 4]);
                 }
 
                 let pos = Vec3::ZERO;
@@ -164,6 +277,7 @@ impl TilemapRenderChunk {
 
                 grid_indices.extend_from_slice(&[tile.index, tile.index, tile.index, tile.index]);
                 color.extend_from_slice(&[tile.tint, tile.tint, tile.tint, tile.tint]);
+                emissive.extend_from_slice(&[tile.emissive; 4]);
             }
         }
 
@@ -172,9 +286,13 @@ impl TilemapRenderChunk {
         self.mesh
             .insert_attribute(TILEMAP_MESH_ATTR_INDEX, grid_indices);
         self.mesh.insert_attribute(TILEMAP_MESH_ATTR_COLOR, color);
+        self.mesh
+            .insert_attribute(TILEMAP_MESH_ATTR_EMISSIVE, emissive);
         if !is_pure_color {
             self.mesh
                 .insert_attribute(TILEMAP_MESH_ATTR_ATLAS_INDICES, atlas_indices);
+            self.mesh
+                .insert_attribute(TILEMAP_MESH_ATTR_LAYER_SCALES, layer_scales);
             #[cfg(feature = "atlas")]
             {
                 self.mesh
@@ -221,6 +339,12 @@ impl TilemapRenderChunk {
 
     /// Set a tile in the chunk. Overwrites the previous tile.
     pub fn set_tile(&mut self, index: usize, tile: Option<&ExtractedTile>) {
+        self.dirty_instances = if self.dirty_instances.is_empty() {
+            index..index + 1
+        } else {
+            self.dirty_instances.start.min(index)..self.dirty_instances.end.max(index + 1)
+        };
+
         let Some(tile) = tile else {
             self.tiles[index] = None;
             self.dirty_mesh = true;
@@ -230,6 +354,7 @@ impl TilemapRenderChunk {
         #[cfg(feature = "atlas")]
         let mut texture_indices = IVec4::NEG_ONE;
         let mut atlas_indices = IVec4::NEG_ONE;
+        let mut layer_scales = UVec4::splat(pack_render_scale(Vec2::ONE));
         let tile_index = {
             match &tile.texture {
                 TileTexture::Static(_) => IVec4::new(tile.index.x, tile.index.y, -1, -1),
@@ -237,7 +362,7 @@ impl TilemapRenderChunk {
                     tile.index.x,
                     tile.index.y,
                     anim.start as i32,
-                    anim.length as i32,
+                    anim.start_time.to_bits() as i32,
                 ),
             }
         };
@@ -256,6 +381,9 @@ impl TilemapRenderChunk {
                     // Shift 29 bits but not 30 because it's a signed integer,
                     // and we need to identify if the layer is empty or not according to the sign.
                     atlas_indices[i] = t.atlas_index | (flip << 29);
+                    if let Some(scale) = t.render_scale {
+                        layer_scales[i] = pack_render_scale(scale);
+                    }
                 });
         }
 
@@ -264,17 +392,54 @@ impl TilemapRenderChunk {
             #[cfg(feature = "atlas")]
             texture_indices,
             atlas_indices,
+            layer_scales,
             tint: tile.tint.to_vec4(),
+            emissive: tile.emissive,
         });
         self.dirty_mesh = true;
     }
 }
 
+/// The properties of a tilemap that the chunk aabbs are calculated from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkAabbSource {
+    pub chunk_size: u32,
+    pub ty: TilemapType,
+    pub tile_pivot: Vec2,
+    pub axis_flip: u32,
+    pub slot_size: Vec2,
+    pub transform: TilemapTransform,
+}
+
+impl ChunkAabbSource {
+    pub fn new(tilemap: &ExtractedTilemap) -> Self {
+        Self {
+            chunk_size: tilemap.chunk_size,
+            ty: tilemap.ty,
+            tile_pivot: tilemap.tile_pivot,
+            axis_flip: tilemap.axis_flip.bits(),
+            slot_size: tilemap.slot_size,
+            transform: tilemap.transform,
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct TilemapRenderChunks {
     pub tilemap: Entity,
     pub value: IndexMap<IVec2, TilemapRenderChunk>,
     pub is_dirty: bool,
+    /// The chunks whose meshes or instances need to be uploaded.
+    /// Tilemaps without dirty chunks are skipped entirely when preparing.
+    pub dirty_chunks: HashSet<IVec2>,
+    pub backend: TilemapRenderBackend,
+    /// The tiles of all the chunks when using [`TilemapRenderBackend::Instanced`].
+    pub instance_buffer: Option<Buffer>,
+    /// The number of slots ever allocated in the instance buffer.
+    pub(crate) instance_slots: u32,
+    pub(crate) free_instance_slots: Vec<u32>,
+    /// What the aabbs of the chunks were calculated from last time.
+    pub(crate) aabb_source: Option<ChunkAabbSource>,
 }
 
 impl TilemapRenderChunks {
@@ -283,6 +448,12 @@ impl TilemapRenderChunks {
             tilemap,
             value: Default::default(),
             is_dirty: true,
+            dirty_chunks: Default::default(),
+            backend: Default::default(),
+            instance_buffer: None,
+            instance_slots: 0,
+            free_instance_slots: Vec::new(),
+            aabb_source: None,
         }
     }
 
@@ -291,21 +462,111 @@ impl TilemapRenderChunks {
         match self.value.entry(chunk_index) {
             Entry::Occupied(_) => {}
             Entry::Vacant(e) => {
-                e.insert(TilemapRenderChunk::from_index(chunk_index, tilemap));
+                let mut chunk = TilemapRenderChunk::from_index(chunk_index, tilemap);
+                chunk.instance_slot = self.free_instance_slots.pop().unwrap_or_else(|| {
+                    self.instance_slots += 1;
+                    self.instance_slots - 1
+                });
+                e.insert(chunk);
                 self.is_dirty = true;
+                self.dirty_chunks.insert(chunk_index);
             }
         }
     }
 
     #[inline]
     pub fn remove_chunk(&mut self, index: IVec2) -> Option<TilemapRenderChunk> {
-        self.value.shift_remove(&index)
+        self.dirty_chunks.remove(&index);
+        let chunk = self.value.shift_remove(&index)?;
+        self.free_instance_slots.push(chunk.instance_slot);
+        Some(chunk)
+    }
+
+    /// Switch the backend, and upload all the chunks again if it's changed.
+    pub fn set_backend(&mut self, backend: TilemapRenderBackend) {
+        if self.backend == backend {
+            return;
+        }
+
+        self.backend = backend;
+        self.instance_buffer = None;
+        self.value.values_mut().for_each(|c| {
+            c.gpu_mesh = None;
+            c.dirty_mesh = true;
+            c.dirty_instances = 0..c.tiles.len();
+        });
+        self.dirty_chunks.extend(self.value.keys());
+    }
+
+    /// Upload the modified tiles of the dirty chunks to the instance buffer,
+    /// which is reallocated if there are more chunks than it can hold.
+    pub fn prepare_instances(
+        &mut self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        is_pure_color: bool,
+        mut stats: Option<&mut TilemapRenderStats>,
+    ) {
+        let Some(chunk_area) = self.value.values().next().map(|c| c.tiles.len() as u64) else {
+            self.dirty_chunks.clear();
+            return;
+        };
+        let stride = MeshTileData::instance_size(is_pure_color) as u64;
+
+        let mut dirty_chunks = std::mem::take(&mut self.dirty_chunks);
+        let required = self.instance_slots as u64 * chunk_area * stride;
+        if self
+            .instance_buffer
+            .as_ref()
+            .map_or(true, |b| b.size() < required)
+        {
+            self.instance_buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                label: Some("tilemap_instance_buffer"),
+                size: self.instance_slots.next_power_of_two() as u64 * chunk_area * stride,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+
+            // Nothing is copied from the old buffer.
+            self.value
+                .values_mut()
+                .for_each(|c| c.dirty_instances = 0..c.tiles.len());
+            dirty_chunks.extend(self.value.keys());
+        }
+        let buffer = self.instance_buffer.as_ref().unwrap();
+
+        let mut bytes = Vec::new();
+        for index in dirty_chunks {
+            let Some(c) = self.value.get_mut(&index) else {
+                continue;
+            };
+            let range = std::mem::take(&mut c.dirty_instances);
+            if range.is_empty() {
+                continue;
+            }
+
+            bytes.clear();
+            c.tiles[range.clone()]
+                .iter()
+                .for_each(|t| MeshTileData::write_instance(t.as_ref(), is_pure_color, &mut bytes));
+            render_queue.write_buffer(
+                buffer,
+                (c.instance_slot as u64 * chunk_area + range.start as u64) * stride,
+                &bytes,
+            );
+
+            if let Some(stats) = stats.as_mut() {
+                stats.dirty_chunks += 1;
+                stats.uploaded_bytes += bytes.len() as u64;
+            }
+        }
     }
 
     #[inline]
     pub fn set_tile(&mut self, tile: &Tile) {
         if let Some(c) = self.value.get_mut(&tile.chunk_index) {
             c.set_tile(tile.in_chunk_index, Some(tile));
+            self.dirty_chunks.insert(tile.chunk_index);
         }
     }
 
@@ -313,6 +574,7 @@ impl TilemapRenderChunks {
     pub fn remove_tile(&mut self, chunk_index: IVec2, in_chunk_index: usize) {
         if let Some(c) = self.value.get_mut(&chunk_index) {
             c.set_tile(in_chunk_index, None);
+            self.dirty_chunks.insert(chunk_index);
         }
     }
 
@@ -365,14 +627,77 @@ impl RenderChunkStorage {
 pub fn prepare_chunks<M: TilemapMaterial>(
     tilemap_instances: Res<TilemapInstances>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
     mut render_chunks: ResMut<RenderChunkStorage>,
+    mut stats: Option<ResMut<TilemapRenderStats>>,
 ) {
-    for tilemap in tilemap_instances.keys() {
-        if let Some(chunks) = render_chunks.value.get_mut(tilemap) {
-            chunks
-                .value
-                .values_mut()
-                .for_each(|c| c.try_update_mesh(&render_device));
+    for (entity, tilemap) in tilemap_instances.iter() {
+        let Some(chunks) = render_chunks.value.get_mut(entity) else {
+            continue;
+        };
+        chunks.set_backend(tilemap.backend);
+        // Static tilemaps don't have to be touched at all.
+        if chunks.dirty_chunks.is_empty() {
+            continue;
+        }
+
+        if chunks.backend == TilemapRenderBackend::Instanced {
+            chunks.prepare_instances(
+                &render_device,
+                &render_queue,
+                tilemap.texture.is_none(),
+                stats.as_deref_mut(),
+            );
+            continue;
+        }
+
+        let dirty_chunks = std::mem::take(&mut chunks.dirty_chunks);
+        for index in dirty_chunks {
+            let Some(c) = chunks.value.get_mut(&index) else {
+                continue;
+            };
+            let is_dirty = c.dirty_mesh;
+            c.try_update_mesh(&render_device);
+
+            if let (true, Some(stats), Some(mesh)) = (is_dirty, stats.as_mut(), &c.gpu_mesh) {
+                stats.dirty_chunks += 1;
+                stats.uploaded_bytes += mesh.vertex_buffer.size();
+                if let GpuBufferInfo::Indexed { buffer, .. } = &mesh.buffer_info {
+                    stats.uploaded_bytes += buffer.size();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::Vec2;
+
+    use super::{pack_render_scale, MeshTileData};
+
+    #[test]
+    fn test_pack_render_scale() {
+        assert_eq!(pack_render_scale(Vec2::ONE), 256 | (256 << 16));
+        assert_eq!(pack_render_scale(Vec2::new(2., 3.)), 512 | (768 << 16));
+        assert_eq!(
+            pack_render_scale(Vec2::new(0.5, 1000.)),
+            128 | (65280 << 16)
+        );
+    }
+
+    #[test]
+    fn test_write_instance() {
+        for is_pure_color in [true, false] {
+            let mut bytes = Vec::new();
+            MeshTileData::write_instance(None, is_pure_color, &mut bytes);
+            MeshTileData::write_instance(None, is_pure_color, &mut bytes);
+            let size = MeshTileData::instance_size(is_pure_color);
+            assert_eq!(bytes.len(), size * 2);
+
+            // The z component of the grid index, right after the position.
+            let flag = i32::from_ne_bytes(bytes[size + 20..size + 24].try_into().unwrap());
+            assert_eq!(flag, MeshTileData::EMPTY_INSTANCE);
         }
     }
 }
@@ -0,0 +1,130 @@
+//! Crop tilemaps to a rectangle or an ellipse, for things like minimaps and reveal transitions.
+
+use bevy::{
+    ecs::component::Component,
+    math::{Rect, Vec4},
+    reflect::Reflect,
+};
+
+/// The space [`TilemapClip::rect`] is in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum TilemapClipSpace {
+    #[default]
+    World,
+    /// Physical pixels, from the top left corner of the viewport.
+    Screen,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum TilemapClipShape {
+    #[default]
+    Rect,
+    /// The ellipse inscribed in the rect.
+    Ellipse,
+}
+
+/// Insert this to a tilemap to only render the part inside the clip area.
+///
+/// Pixels outside are discarded in the fragment shader, and chunks entirely
+/// outside a world space clip area are culled.
+///
+/// **Notice**: Culled chunks are considered invisible, see
+/// [`ChunkVisibilityChanged`](crate::render::cull::ChunkVisibilityChanged).
+/// And custom fragment shaders have to call `is_clipped` from `bevy_entitiles::common`
+/// and discard the clipped fragments themselves, like `assets/custom_material.wgsl`.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapClip {
+    pub rect: Rect,
+    pub space: TilemapClipSpace,
+    pub shape: TilemapClipShape,
+    /// Render the part outside the clip area instead.
+    pub inverted: bool,
+}
+
+impl TilemapClip {
+    pub fn world(rect: Rect) -> Self {
+        Self {
+            rect,
+            space: TilemapClipSpace::World,
+            shape: TilemapClipShape::Rect,
+            inverted: false,
+        }
+    }
+
+    pub fn screen(rect: Rect) -> Self {
+        Self {
+            space: TilemapClipSpace::Screen,
+            ..Self::world(rect)
+        }
+    }
+
+    pub fn with_shape(mut self, shape: TilemapClipShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    /// Returns `false` if nothing inside the world space `aabb` will be rendered.
+    pub fn may_intersect(&self, aabb: Rect) -> bool {
+        if self.space == TilemapClipSpace::Screen {
+            return true;
+        }
+
+        if self.inverted {
+            // Tiles are rendered outside the ellipse, even if they are inside the rect.
+            self.shape == TilemapClipShape::Ellipse
+                || !(aabb.min.cmpge(self.rect.min).all() && aabb.max.cmple(self.rect.max).all())
+        } else {
+            !self.rect.intersect(aabb).is_empty()
+        }
+    }
+
+    /// The rect and the flags passed to the shader.
+    pub(crate) fn as_uniform(clip: Option<&Self>) -> (Vec4, u32) {
+        let Some(clip) = clip else {
+            return (Vec4::ZERO, 0);
+        };
+
+        // See `is_clipped` in the shader.
+        let mut flags = 1;
+        if clip.space == TilemapClipSpace::Screen {
+            flags |= 1 << 1;
+        }
+        if clip.shape == TilemapClipShape::Ellipse {
+            flags |= 1 << 2;
+        }
+        if clip.inverted {
+            flags |= 1 << 3;
+        }
+        let (min, max) = (clip.rect.min, clip.rect.max);
+        (Vec4::new(min.x, min.y, max.x, max.y), flags)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{Rect, Vec2};
+
+    use super::{TilemapClip, TilemapClipShape};
+
+    #[test]
+    fn test_clip_culling() {
+        let clip = TilemapClip::world(Rect::new(0., 0., 100., 100.));
+        assert!(clip.may_intersect(Rect::new(90., 90., 120., 120.)));
+        assert!(!clip.may_intersect(Rect::new(110., 0., 120., 10.)));
+
+        let clip = clip.with_inverted(true);
+        assert!(!clip.may_intersect(Rect::new(10., 10., 20., 20.)));
+        assert!(clip.may_intersect(Rect::new(90., 90., 120., 120.)));
+        assert!(clip
+            .with_shape(TilemapClipShape::Ellipse)
+            .may_intersect(Rect::new(10., 10., 20., 20.)));
+
+        let clip = TilemapClip::screen(Rect::from_center_size(Vec2::ZERO, Vec2::ONE));
+        assert!(clip.may_intersect(Rect::new(110., 0., 120., 10.)));
+    }
+}
@@ -1,15 +1,23 @@
-// TODO Frustum culling!!!
+// TODO Frustum culling for whole tilemaps.
 #![allow(unused)]
+use std::sync::{Arc, Mutex};
+
 use bevy::{
-    ecs::system::{Res, Resource},
+    ecs::{
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::{Res, Resource},
+    },
+    math::{IVec2, Rect},
     prelude::{Query, ResMut},
+    reflect::Reflect,
     render::view::ViewVisibility,
 };
 
 use crate::{
-    math::CameraAabb2d,
+    math::{ext::RectFromTilemap, CameraAabb2d},
     render::{
-        chunk::RenderChunkStorage,
+        chunk::{ChunkAabbSource, RenderChunkStorage},
         extract::{ExtractedView, TilemapInstances},
     },
     tilemap::map::TilemapAabbs,
@@ -42,37 +50,88 @@ pub fn cull_tilemaps(
     // });
 }
 
+/// Sent when a chunk enters or leaves all the cameras, so gameplay can
+/// pause the things in off-screen chunks.
+///
+/// Chunks are considered visible when they are created.
+///
+/// **Notice**: These are sent from the render world, so they are one frame behind,
+/// and not sent at all when `FrustumCulling` is disabled.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct ChunkVisibilityChanged {
+    pub tilemap: Entity,
+    pub chunk: IVec2,
+    pub visible: bool,
+}
+
+/// The visibility changes of the last frame, shared between the main and render world.
+#[derive(Resource, Default, Clone)]
+pub struct SharedChunkVisibility(pub(crate) Arc<Mutex<Vec<ChunkVisibilityChanged>>>);
+
 pub fn cull_chunks(
     tilemaps: Res<TilemapInstances>,
     mut render_chunk_storage: ResMut<RenderChunkStorage>,
     cameras: Query<&ExtractedView>,
     culling: Res<FrustumCulling>,
+    shared: Res<SharedChunkVisibility>,
 ) {
-    if !culling.0 {
-        return;
+    let mut changes = Vec::new();
+
+    for (tilemap, instance) in tilemaps.iter() {
+        let Some(chunks) = render_chunk_storage.value.get_mut(tilemap) else {
+            continue;
+        };
+
+        // Tilemaps can move, so the aabbs need to be recalculated.
+        // New chunks calculate their own, so only do this when the tilemap changed.
+        let source = ChunkAabbSource::new(instance);
+        let changed = chunks.aabb_source != Some(source);
+        chunks.aabb_source = Some(source);
+
+        for (chunk_index, chunk) in chunks.value.iter_mut() {
+            if changed {
+                chunk.aabb = Rect::from_tilemap(
+                    *chunk_index,
+                    instance.chunk_size,
+                    instance.ty,
+                    instance.tile_pivot,
+                    instance.axis_flip,
+                    instance.slot_size,
+                    instance.transform,
+                );
+            }
+
+            let visible = !culling.0
+                || (cameras.is_empty()
+                    || cameras
+                        .iter()
+                        .any(|camera| !camera.intersect(chunk.aabb).is_empty()))
+                    && instance
+                        .clip
+                        .map_or(true, |clip| clip.may_intersect(chunk.aabb));
+
+            if chunk.visible != visible {
+                chunk.visible = visible;
+                if culling.0 {
+                    changes.push(ChunkVisibilityChanged {
+                        tilemap: *tilemap,
+                        chunk: *chunk_index,
+                        visible,
+                    });
+                }
+            }
+        }
     }
 
-    // tilemaps.keys().for_each(|tilemap| {
-    //     render_chunk_storage
-    //         .get_or_insert_chunks(*tilemap)
-    //         .value
-    //         .values_mut()
-    //         .for_each(|c| {
-    //             c.visible = false;
-    //         });
-    // });
+    if !changes.is_empty() {
+        shared.0.lock().unwrap().extend(changes);
+    }
+}
 
-    // cameras.iter().for_each(|cam_aabb| {
-    //     tilemaps.keys().for_each(|tilemap| {
-    //         render_chunk_storage
-    //             .get_or_insert_chunks(*tilemap)
-    //             .value
-    //             .values_mut()
-    //             .for_each(|c| {
-    //                 if !c.aabb.intersect(cam_aabb.0).is_empty() {
-    //                     c.visible = true;
-    //                 }
-    //             });
-    //     });
-    // });
+pub fn send_chunk_visibility_events(
+    shared: Res<SharedChunkVisibility>,
+    mut events: EventWriter<ChunkVisibilityChanged>,
+) {
+    let changes = std::mem::take(&mut *shared.0.lock().unwrap());
+    events.send_batch(changes);
 }
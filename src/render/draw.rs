@@ -20,7 +20,7 @@ use bevy::{
 use crate::render::{
     binding::TilemapBindGroups,
     buffer::TilemapBuffers,
-    chunk::RenderChunkStorage,
+    chunk::{RenderChunkStorage, TilemapRenderBackend},
     extract::{TilemapInstances, TilemapMaterialIds},
     material::TilemapMaterial,
 };
@@ -41,6 +41,22 @@ pub type DrawTilemapNonTextured<M> = (
     DrawTileMesh<M>,
 );
 
+pub type DrawTilemapShadowTextured<M> = (
+    SetItemPipeline,
+    SetTilemapShadowUniformBufferBindGroup<0, M>,
+    SetTilemapMaterialBindGroup<1, M>,
+    SetTilemapColorTextureBindGroup<2, M>,
+    SetTilemapArrayBufferBindGroup<3, M>,
+    DrawTileMesh<M>,
+);
+
+pub type DrawTilemapShadowNonTextured<M> = (
+    SetItemPipeline,
+    SetTilemapShadowUniformBufferBindGroup<0, M>,
+    SetTilemapMaterialBindGroup<1, M>,
+    DrawTileMesh<M>,
+);
+
 #[derive(Default)]
 pub struct SetTilemapUniformBufferBindGroup<const I: usize, M: TilemapMaterial>(PhantomData<M>);
 impl<const I: usize, M: TilemapMaterial> RenderCommand<Transparent2d>
@@ -81,6 +97,50 @@ impl<const I: usize, M: TilemapMaterial> RenderCommand<Transparent2d>
     }
 }
 
+/// Same as [`SetTilemapUniformBufferBindGroup`], but binds the uniform of the
+/// [`TilemapShadow`](crate::render::shadow::TilemapShadow).
+#[derive(Default)]
+pub struct SetTilemapShadowUniformBufferBindGroup<const I: usize, M: TilemapMaterial>(
+    PhantomData<M>,
+);
+impl<const I: usize, M: TilemapMaterial> RenderCommand<Transparent2d>
+    for SetTilemapShadowUniformBufferBindGroup<I, M>
+{
+    type Param = (SRes<TilemapBindGroups<M>>, SRes<TilemapBuffers>);
+
+    type ViewQuery = Read<ViewUniformOffset>;
+
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        item: &Transparent2d,
+        view_uniform_offset: ROQueryItem<'w, Self::ViewQuery>,
+        _entity: Option<ROQueryItem<'w, Self::ItemQuery>>,
+        (bind_groups, tilemap_buffers): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        if let (Some(tilemap_uniform_bind_group), Some(index)) = (
+            bind_groups.into_inner().uniform_buffer.as_ref(),
+            tilemap_buffers.shared.shadow_indices.get(&item.entity),
+        ) {
+            pass.set_bind_group(
+                I,
+                tilemap_uniform_bind_group,
+                &[*index, view_uniform_offset.offset],
+            );
+            RenderCommandResult::Success
+        } else {
+            warn!(
+                "Failed to draw the shadow of tilemap {}: Failed to get tilemap uniform bind group! \
+                Skipping rendering this frame.",
+                item.entity
+            );
+            RenderCommandResult::Failure
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct SetTilemapMaterialBindGroup<const I: usize, M: TilemapMaterial>(PhantomData<M>);
 impl<const I: usize, M: TilemapMaterial> RenderCommand<Transparent2d>
@@ -215,7 +275,23 @@ impl<M: TilemapMaterial> RenderCommand<Transparent2d> for DrawTileMesh<M> {
         render_chunks: SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
-        if let Some(chunks) = render_chunks.into_inner().get_chunks(item.entity) {
+        let Some(chunks) = render_chunks.into_inner().get_chunks(item.entity) else {
+            return RenderCommandResult::Success;
+        };
+
+        if chunks.backend == TilemapRenderBackend::Instanced {
+            let Some(instance_buffer) = &chunks.instance_buffer else {
+                return RenderCommandResult::Success;
+            };
+
+            pass.set_vertex_buffer(0, instance_buffer.slice(..));
+            for chunk in chunks.value.values().filter(|c| c.visible) {
+                // Each chunk owns `tiles.len()` instances, and each tile is 2 triangles.
+                let area = chunk.tiles.len() as u32;
+                let start = chunk.instance_slot * area;
+                pass.draw(0..6, start..start + area);
+            }
+        } else {
             for chunk in chunks.value.values() {
                 if !chunk.visible {
                     continue;
@@ -0,0 +1,247 @@
+//! Export tilemaps as images on the cpu, for things like minimaps,
+//! marketing shots or golden images in CI.
+
+use std::path::PathBuf;
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        entity::Entity,
+        world::{Command, World},
+    },
+    log::error,
+    math::{IVec2, UVec2},
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{BevyDefault, Image},
+    },
+};
+use thiserror::Error;
+
+use crate::{
+    math::GridRect,
+    render::bake::bake_tiles,
+    tilemap::{
+        map::{TilemapLayerOpacities, TilemapStorage, TilemapTextures, TilemapType},
+        tile::Tile,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum TilemapExportError {
+    #[error("Entity {0:?} is not a tilemap")]
+    NotTilemap(Entity),
+    #[error("Only square tilemaps can be exported")]
+    NotSquare,
+    #[error("Textures of tilemap {0:?} are not loaded or not kept in the main world")]
+    TexturesNotLoaded(Entity),
+    #[error("Tilemap {0:?} has no tiles to export")]
+    Empty(Entity),
+    #[error("Failed to write the image: {0}")]
+    Write(String),
+}
+
+/// Draw the tiles of `tilemap` inside `bounds` into an image, one texel per texture pixel.
+/// The whole tilemap is exported if `bounds` is `None`.
+///
+/// **Notice**: Animated tiles are skipped. The textures must be kept in the main world,
+/// which means their `RenderAssetUsages` should contain `MAIN_WORLD`.
+pub fn export_to_image(
+    world: &World,
+    tilemap: Entity,
+    bounds: Option<GridRect>,
+) -> Result<Image, TilemapExportError> {
+    let entity = world
+        .get_entity(tilemap)
+        .ok_or(TilemapExportError::NotTilemap(tilemap))?;
+    let (Some(storage), Some(ty), Some(opacities), Some(textures)) = (
+        entity.get::<TilemapStorage>(),
+        entity.get::<TilemapType>(),
+        entity.get::<TilemapLayerOpacities>(),
+        entity.get::<Handle<TilemapTextures>>(),
+    ) else {
+        return Err(TilemapExportError::NotTilemap(tilemap));
+    };
+    if *ty != TilemapType::Square {
+        return Err(TilemapExportError::NotSquare);
+    }
+
+    let textures = world
+        .get_resource::<Assets<TilemapTextures>>()
+        .and_then(|assets| assets.get(textures))
+        .filter(|t| !t.textures.is_empty())
+        .ok_or(TilemapExportError::TexturesNotLoaded(tilemap))?;
+    let texture_images = world
+        .get_resource::<Assets<Image>>()
+        .and_then(|assets| {
+            textures
+                .textures
+                .iter()
+                .map(|tex| assets.get(tex.handle()))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or(TilemapExportError::TexturesNotLoaded(tilemap))?;
+
+    let tiles = storage
+        .iter_sorted()
+        .filter(|(index, _)| bounds.map_or(true, |b| b.contains(*index)))
+        .filter_map(|(index, entity)| world.get::<Tile>(entity).map(|tile| (index, tile)))
+        .collect::<Vec<_>>();
+    let bounds = match bounds {
+        Some(bounds) => bounds,
+        None => tiles
+            .iter()
+            .map(|(index, _)| *index)
+            .reduce(IVec2::min)
+            .zip(tiles.iter().map(|(index, _)| *index).reduce(IVec2::max))
+            .map(|(min, max)| GridRect::from_min_max(min, max))
+            .ok_or(TilemapExportError::Empty(tilemap))?,
+    };
+
+    let target_size = bounds.extent * textures.textures[0].desc().tile_size;
+    let mut bake_target = vec![0; (target_size.x * target_size.y * 4) as usize];
+    bake_tiles(
+        textures,
+        &texture_images,
+        opacities,
+        tiles.into_iter().map(|(index, tile)| {
+            (
+                UVec2::new(
+                    (index.x - bounds.origin.x) as u32,
+                    (bounds.dest.y - index.y) as u32,
+                ),
+                tile,
+            )
+        }),
+        target_size,
+        &mut bake_target,
+    );
+
+    Ok(Image::new(
+        Extent3d {
+            width: target_size.x,
+            height: target_size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bake_target,
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::all(),
+    ))
+}
+
+/// Export the tilemap using [`export_to_image`] and save it as a png file.
+pub fn export_to_png(
+    world: &World,
+    tilemap: Entity,
+    bounds: Option<GridRect>,
+    path: impl Into<PathBuf>,
+) -> Result<(), TilemapExportError> {
+    export_to_image(world, tilemap, bounds)?
+        .try_into_dynamic()
+        .map_err(|e| TilemapExportError::Write(e.to_string()))?
+        .save(path.into())
+        .map_err(|e| TilemapExportError::Write(e.to_string()))
+}
+
+/// Export the tilemap into a png file. Use this if you don't have access to the `World`.
+///
+/// Errors are logged.
+pub struct ExportTilemapPng {
+    pub tilemap: Entity,
+    pub bounds: Option<GridRect>,
+    pub path: PathBuf,
+}
+
+impl Command for ExportTilemapPng {
+    fn apply(self, world: &mut World) {
+        if let Err(e) = export_to_png(world, self.tilemap, self.bounds, self.path) {
+            error!("Failed to export tilemap {:?}: {}", self.tilemap, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::Assets,
+        ecs::{
+            system::Commands,
+            world::{CommandQueue, World},
+        },
+        math::{IVec2, UVec2},
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+            texture::{BevyDefault, Image},
+        },
+    };
+
+    use crate::tilemap::{
+        map::{
+            TilemapLayerOpacities, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
+            TilemapTextures, TilemapType,
+        },
+        tile::{TileBuilder, TileLayer},
+    };
+
+    use super::export_to_image;
+
+    #[test]
+    fn test_export() {
+        let mut world = World::new();
+        let mut images = Assets::<Image>::default();
+        let texture = images.add(Image::new(
+            Extent3d {
+                width: 2,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![255, 0, 0, 255, 0, 0, 255, 255],
+            TextureFormat::bevy_default(),
+            RenderAssetUsages::all(),
+        ));
+        let mut textures_assets = Assets::<TilemapTextures>::default();
+        let textures = textures_assets.add(TilemapTextures::single(
+            TilemapTexture::new(
+                texture,
+                TilemapTextureDescriptor::new(UVec2::new(2, 1), UVec2::ONE),
+            ),
+            FilterMode::Nearest,
+        ));
+        world.insert_resource(images);
+        world.insert_resource(textures_assets);
+
+        let tilemap = world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(16, tilemap);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        storage.set(
+            &mut commands,
+            IVec2::new(0, 0),
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
+        );
+        storage.set(
+            &mut commands,
+            IVec2::new(1, 1),
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(1)),
+        );
+        queue.apply(&mut world);
+        world.entity_mut(tilemap).insert((
+            storage,
+            TilemapType::Square,
+            TilemapLayerOpacities::default(),
+            textures,
+        ));
+
+        let image = export_to_image(&world, tilemap, None).unwrap();
+        assert_eq!(image.size(), UVec2::splat(2));
+        // Blue at the top right, red at the bottom left.
+        assert_eq!(
+            image.data,
+            vec![0, 0, 0, 0, 0, 0, 255, 255, 255, 0, 0, 255, 0, 0, 0, 0]
+        );
+    }
+}
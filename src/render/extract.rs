@@ -16,17 +16,20 @@ use bevy::{
 use crate::{
     math::CameraAabb2d,
     render::{
-        chunk::{ChunkUnload, RenderChunkSort, UnloadRenderChunk},
+        chunk::{ChunkUnload, RenderChunkSort, TilemapRenderBackend, UnloadRenderChunk},
+        clip::TilemapClip,
         cull::FrustumCulling,
+        shadow::TilemapShadow,
+        tint::TilemapGlobalTint,
     },
     tilemap::{
         despawn::{DespawnedTile, DespawnedTilemap},
         map::{
-            TilePivot, TileRenderSize, TilemapAnimations, TilemapAxisFlip, TilemapLayerOpacities,
-            TilemapName, TilemapSlotSize, TilemapStorage, TilemapTextures, TilemapTransform,
-            TilemapType,
+            TilePivot, TileRenderSize, TilemapAnimationLod, TilemapAnimations, TilemapAxisFlip,
+            TilemapLayerOpacities, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTextures,
+            TilemapTransform, TilemapType,
         },
-        tile::Tile,
+        tile::{Tile, TileTexture},
     },
 };
 
@@ -34,6 +37,11 @@ pub type TilemapInstances = ExtractedInstances<ExtractedTilemap>;
 
 pub type TilemapMaterialIds<M> = ExtractedInstances<AssetId<M>>;
 
+#[cfg(feature = "baking")]
+type ExtractedLod = Option<Read<crate::render::lod::TilemapLod>>;
+#[cfg(not(feature = "baking"))]
+type ExtractedLod = ();
+
 #[derive(Component, Debug)]
 pub struct ExtractedTilemap {
     pub name: String,
@@ -46,7 +54,13 @@ pub struct ExtractedTilemap {
     pub axis_flip: TilemapAxisFlip,
     pub texture: Option<Handle<TilemapTextures>>,
     pub changed_animations: Option<TilemapAnimations>,
+    pub animation_time: Option<f32>,
     pub chunk_size: u32,
+    pub backend: TilemapRenderBackend,
+    pub shadow: Option<TilemapShadow>,
+    pub clip: Option<TilemapClip>,
+    /// The chunk thumbnails are rendered instead.
+    pub lod_active: bool,
 }
 
 impl ExtractInstance for ExtractedTilemap {
@@ -62,6 +76,13 @@ impl ExtractInstance for ExtractedTilemap {
         Read<TilemapStorage>,
         Option<Read<Handle<TilemapTextures>>>,
         Option<Ref<'static, TilemapAnimations>>,
+        Option<Read<TilemapAnimationLod>>,
+        (
+            Option<Read<TilemapRenderBackend>>,
+            Option<Read<TilemapShadow>>,
+            Option<Read<TilemapClip>>,
+        ),
+        ExtractedLod,
     );
 
     type QueryFilter = ();
@@ -79,6 +100,9 @@ impl ExtractInstance for ExtractedTilemap {
             storage,
             texture,
             animations,
+            animation_lod,
+            (backend, shadow, clip),
+            lod,
         ) = item;
         assert_ne!(
             storage.tilemap,
@@ -102,7 +126,18 @@ impl ExtractInstance for ExtractedTilemap {
                 .as_ref()
                 .is_some_and(|a| a.is_changed())
                 .then(|| animations.unwrap().clone()),
+            animation_time: animation_lod.map(|lod| lod.elapsed()),
             chunk_size: storage.storage.chunk_size,
+            backend: backend.copied().unwrap_or_default(),
+            shadow: shadow.copied(),
+            clip: clip.copied(),
+            #[cfg(feature = "baking")]
+            lod_active: lod.is_some_and(|lod| lod.is_active()),
+            #[cfg(not(feature = "baking"))]
+            lod_active: {
+                let () = lod;
+                false
+            },
         })
     }
 }
@@ -111,34 +146,48 @@ pub type ExtractedTile = Tile;
 
 pub type ExtractedView = CameraAabb2d;
 
+/// Only the tiles changed since the last extraction are extracted.
+/// Animations are played on the gpu, so animated tiles are not changed every frame.
+///
+/// The start time of animations is delayed by the lag of the chunk clock
+/// if the tilemap has a [`TilemapAnimationLod`].
 pub fn extract_tiles(
     mut commands: Commands,
     tiles_query: Extract<Query<(Entity, &Tile), Changed<Tile>>>,
+    lods_query: Extract<Query<&TilemapAnimationLod>>,
 ) {
-    commands.insert_or_spawn_batch(
-        tiles_query
-            .iter()
-            .map(|(entity, tile)| {
-                (
-                    entity,
-                    ExtractedTile {
-                        tilemap_id: tile.tilemap_id,
-                        chunk_index: tile.chunk_index,
-                        in_chunk_index: tile.in_chunk_index,
-                        index: tile.index,
-                        texture: tile.texture.clone(),
-                        tint: tile.tint,
-                    },
-                )
-            })
-            .collect::<Vec<_>>(),
-    );
+    let changed_tiles = tiles_query
+        .iter()
+        .map(|(entity, tile)| {
+            let mut texture = tile.texture.clone();
+            if let (TileTexture::Animated(anim), Ok(lod)) =
+                (&mut texture, lods_query.get(tile.tilemap_id))
+            {
+                anim.start_time += lod.chunk_lag(tile.chunk_index);
+            }
+
+            (
+                entity,
+                ExtractedTile {
+                    tilemap_id: tile.tilemap_id,
+                    chunk_index: tile.chunk_index,
+                    in_chunk_index: tile.in_chunk_index,
+                    index: tile.index,
+                    texture,
+                    tint: tile.tint,
+                    emissive: tile.emissive,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if !changed_tiles.is_empty() {
+        commands.insert_or_spawn_batch(changed_tiles);
+    }
 }
 
-pub fn extract_view(
-    mut commands: Commands,
-    cameras: Extract<Query<(Entity, &CameraAabb2d), Changed<CameraAabb2d>>>,
-) {
+/// Render world entities are cleared every frame, so all the cameras are extracted.
+pub fn extract_view(mut commands: Commands, cameras: Extract<Query<(Entity, &CameraAabb2d)>>) {
     commands.insert_or_spawn_batch(
         cameras
             .iter()
@@ -164,9 +213,13 @@ pub fn extract_resources(
     mut commands: Commands,
     frustum_culling: Extract<Res<FrustumCulling>>,
     sort_config: Extract<Res<RenderChunkSort>>,
+    global_tint: Extract<Res<TilemapGlobalTint>>,
 ) {
     commands.insert_resource(FrustumCulling(frustum_culling.0));
     commands.insert_resource(sort_config.clone());
+    if global_tint.is_changed() {
+        commands.insert_resource(global_tint.clone());
+    }
 }
 
 pub fn extract_despawned_tilemaps(
@@ -0,0 +1,591 @@
+//! Golden image tests for the renderer.
+//!
+//! Small tilemaps are rendered headlessly into textures, read back to the cpu
+//! and compared against the pngs committed under `assets/golden`.
+//!
+//! Run `cargo test --features render-tests golden` on a machine with a GPU.
+//! Set `ENTITILES_BLESS=1` to (re)generate the goldens after an intended change,
+//! and review the pngs before committing them. The rendered images of the failed
+//! cases are written into `target/golden` for comparison.
+//!
+//! The CI doesn't have a GPU and skips the `render-tests` feature, so these
+//! tests only run locally. Run them before merging any change to rendering.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    app::App,
+    asset::{AssetPlugin, Assets, Handle},
+    core::{FrameCountPlugin, TaskPoolPlugin, TypeRegistrationPlugin},
+    core_pipeline::{core_2d::Camera2dBundle, CorePipelinePlugin},
+    ecs::{
+        schedule::IntoSystemConfigs,
+        system::{Commands, Res, Resource},
+        world::CommandQueue,
+    },
+    hierarchy::HierarchyPlugin,
+    math::{IVec2, UVec2, Vec2},
+    prelude::{Camera, ClearColorConfig, Color, Transform},
+    render::{
+        camera::RenderTarget,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_resource::{
+            BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, FilterMode,
+            ImageCopyBuffer, ImageDataLayout, Maintain, MapMode, TextureDimension, TextureFormat,
+            TextureUsages,
+        },
+        renderer::{render_system, RenderDevice, RenderQueue},
+        settings::WgpuSettings,
+        texture::{BevyDefault, GpuImage, Image, ImagePlugin},
+        view::Msaa,
+        Render, RenderApp, RenderPlugin, RenderSet,
+    },
+    sprite::SpritePlugin,
+    time::{TimePlugin, TimeUpdateStrategy},
+    transform::TransformPlugin,
+    window::{ExitCondition, WindowPlugin},
+};
+
+use crate::{
+    render::{chunk::TilemapRenderBackend, material::StandardTilemapMaterial},
+    tilemap::{
+        bundles::StandardTilemapBundle,
+        map::{
+            OffscreenAnimation, TileRenderSize, TilemapAnimationLod, TilemapAnimations,
+            TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
+            TilemapTextures, TilemapType,
+        },
+        tile::{RawTileAnimation, TileAnimation, TileBuilder},
+    },
+    EntiTilesPlugin,
+};
+
+/// Frames to render before reading the target back, so textures and pipelines are ready.
+const WARMUP_FRAMES: usize = 8;
+const BLESS_VAR: &str = "ENTITILES_BLESS";
+
+/// A tilemap to render and compare against the golden image called `name`.
+///
+/// The tile render size and slot size are both `tile_size`.
+pub struct GoldenCase {
+    pub name: String,
+    pub ty: TilemapType,
+    pub tile_size: UVec2,
+    pub target_size: UVec2,
+    /// The center of the camera in world space.
+    pub camera: Vec2,
+    /// The time animations are rendered at.
+    pub elapsed: f32,
+    pub tiles: Vec<(IVec2, TileBuilder)>,
+    pub animations: TilemapAnimations,
+    pub chunk_size: u32,
+    pub backend: TilemapRenderBackend,
+}
+
+impl GoldenCase {
+    pub fn new(name: impl Into<String>, ty: TilemapType, tile_size: UVec2) -> Self {
+        Self {
+            name: name.into(),
+            ty,
+            tile_size,
+            target_size: UVec2::splat(64),
+            camera: Vec2::ZERO,
+            elapsed: 0.,
+            tiles: Vec::new(),
+            animations: TilemapAnimations::default(),
+            chunk_size: 16,
+            backend: TilemapRenderBackend::Mesh,
+        }
+    }
+
+    pub fn with_target_size(mut self, target_size: UVec2) -> Self {
+        self.target_size = target_size;
+        self
+    }
+
+    pub fn with_camera(mut self, camera: Vec2) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    pub fn with_elapsed(mut self, elapsed: f32) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: TilemapRenderBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_tile(mut self, index: IVec2, tile: TileBuilder) -> Self {
+        self.tiles.push((index, tile));
+        self
+    }
+
+    /// Register an animation on the tilemap of this case.
+    pub fn register_animation(&mut self, anim: RawTileAnimation) -> TileAnimation {
+        self.animations.register(anim)
+    }
+}
+
+/// The texture used by all the cases, `2x2` tiles of `tile_size`.
+///
+/// Each tile has a different color, and a white quarter at its top left
+/// so flips are visible.
+pub fn golden_texture(tile_size: UVec2) -> Image {
+    const COLORS: [[u8; 4]; 4] = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 0, 255],
+    ];
+
+    let size = tile_size * 2;
+    let mut data = Vec::with_capacity((size.x * size.y * 4) as usize);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let tile = UVec2::new(x, y) / tile_size;
+            let local = UVec2::new(x, y) % tile_size;
+            if local.x < tile_size.x / 2 && local.y < tile_size.y / 2 {
+                data.extend_from_slice(&[255; 4]);
+            } else {
+                data.extend_from_slice(&COLORS[(tile.y * 2 + tile.x) as usize]);
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::all(),
+    )
+}
+
+#[derive(Resource, Clone, ExtractResource)]
+struct GoldenTarget {
+    image: Handle<Image>,
+    data: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+/// Render the case headlessly and read the result back.
+pub fn render_case(case: GoldenCase) -> Image {
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        TypeRegistrationPlugin,
+        FrameCountPlugin,
+        TimePlugin,
+        TransformPlugin,
+        HierarchyPlugin,
+        WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+        },
+        AssetPlugin::default(),
+        RenderPlugin {
+            render_creation: WgpuSettings::default().into(),
+            synchronous_pipeline_compilation: true,
+        },
+        ImagePlugin::default_nearest(),
+        CorePipelinePlugin,
+        SpritePlugin,
+        EntiTilesPlugin,
+        ExtractResourcePlugin::<GoldenTarget>::default(),
+    ))
+    .insert_resource(Msaa::Off)
+    // Freeze the clock, animations are played at `case.elapsed`.
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+
+    app.sub_app_mut(RenderApp).add_systems(
+        Render,
+        golden_target_reader
+            .in_set(RenderSet::Render)
+            .after(render_system),
+    );
+
+    let target = GoldenTarget {
+        image: add_render_target(&mut app, case.target_size),
+        data: Default::default(),
+    };
+    app.insert_resource(target.clone());
+    spawn_case(&mut app, case, target.image.clone());
+
+    app.finish();
+    app.cleanup();
+    for _ in 0..WARMUP_FRAMES {
+        app.update();
+    }
+
+    let size = app
+        .world()
+        .resource::<Assets<Image>>()
+        .get(&target.image)
+        .unwrap()
+        .size();
+    let data = target
+        .data
+        .lock()
+        .unwrap()
+        .take()
+        .expect("The render target is not read back. Is there a GPU available?");
+
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::all(),
+    )
+}
+
+fn add_render_target(app: &mut App, size: UVec2) -> Handle<Image> {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::all(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+    app.world_mut().resource_mut::<Assets<Image>>().add(image)
+}
+
+fn spawn_case(app: &mut App, case: GoldenCase, target: Handle<Image>) {
+    let world = app.world_mut();
+    let texture = world
+        .resource_mut::<Assets<Image>>()
+        .add(golden_texture(case.tile_size));
+    let textures = world
+        .resource_mut::<Assets<TilemapTextures>>()
+        .add(TilemapTextures::single(
+            TilemapTexture::new(
+                texture,
+                TilemapTextureDescriptor::new(case.tile_size * 2, case.tile_size),
+            ),
+            FilterMode::Nearest,
+        ));
+    let material = world
+        .resource_mut::<Assets<StandardTilemapMaterial>>()
+        .add(StandardTilemapMaterial::default());
+
+    world.spawn(Camera2dBundle {
+        camera: Camera {
+            target: RenderTarget::Image(target),
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..Default::default()
+        },
+        transform: Transform::from_translation(case.camera.extend(999.9)),
+        ..Default::default()
+    });
+
+    let entity = world.spawn_empty().id();
+    let mut storage = TilemapStorage::new(case.chunk_size, entity);
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    for (index, tile) in case.tiles {
+        storage.set(&mut commands, index, tile);
+    }
+    queue.apply(world);
+
+    world.entity_mut(entity).insert((
+        StandardTilemapBundle {
+            tile_render_size: TileRenderSize(case.tile_size.as_vec2()),
+            slot_size: TilemapSlotSize(case.tile_size.as_vec2()),
+            ty: case.ty,
+            storage,
+            material,
+            textures,
+            animations: case.animations,
+            ..Default::default()
+        },
+        case.backend,
+        TilemapAnimationLod {
+            offscreen: OffscreenAnimation::Pause,
+            elapsed: case.elapsed,
+            ..Default::default()
+        },
+    ));
+}
+
+fn golden_target_reader(
+    target: Res<GoldenTarget>,
+    images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let Some(image) = images.get(&target.image) else {
+        return;
+    };
+
+    let row_bytes = image.size.x as usize * 4;
+    let padded_row_bytes = RenderDevice::align_copy_bytes_per_row(row_bytes);
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("golden_readback_buffer"),
+        size: (padded_row_bytes * image.size.y as usize) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("golden_readback_encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        image.texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes as u32),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width: image.size.x,
+            height: image.size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |_| {});
+    render_device.poll(Maintain::Wait);
+
+    let data = slice
+        .get_mapped_range()
+        .chunks(padded_row_bytes)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect();
+    buffer.unmap();
+    *target.data.lock().unwrap() = Some(data);
+}
+
+/// The number of pixels whose channels differ by more than `tolerance`.
+///
+/// Returns `None` if the sizes don't match.
+pub fn count_mismatches(image: &Image, golden: &Image, tolerance: u8) -> Option<usize> {
+    if image.size() != golden.size() {
+        return None;
+    }
+
+    Some(
+        image
+            .data
+            .chunks(4)
+            .zip(golden.data.chunks(4))
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .any(|(a, b)| a.abs_diff(*b) > tolerance)
+            })
+            .count(),
+    )
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets/golden")
+        .join(format!("{}.png", name))
+}
+
+fn save_png(image: Image, path: &Path) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    image.try_into_dynamic().unwrap().save(path).unwrap();
+}
+
+fn load_png(path: &Path) -> Option<Image> {
+    let png = image::open(path).ok()?;
+    Some(Image::from_dynamic(png, true, RenderAssetUsages::all()))
+}
+
+/// Render the case and compare it against its golden image.
+/// The golden image is written instead if `ENTITILES_BLESS` is set.
+///
+/// **Notice**: This panics on mismatches, as it's meant to be used in tests.
+pub fn assert_golden(case: GoldenCase) {
+    let name = case.name.clone();
+    let image = render_case(case);
+    let path = golden_path(&name);
+
+    if std::env::var_os(BLESS_VAR).is_some() {
+        save_png(image, &path);
+        return;
+    }
+
+    let Some(golden) = load_png(&path) else {
+        panic!(
+            "Golden image {:?} is missing, run with {}=1 to generate it",
+            path, BLESS_VAR
+        );
+    };
+
+    match count_mismatches(&image, &golden, 1) {
+        Some(0) => {}
+        mismatches => {
+            let actual = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("target/golden")
+                .join(format!("{}.png", name));
+            save_png(image, &actual);
+            match mismatches {
+                Some(n) => panic!(
+                    "{} pixels of {} differ from the golden image, see {:?}",
+                    n, name, actual
+                ),
+                None => panic!(
+                    "The size of {} differs from the golden image, see {:?}",
+                    name, actual
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2, Vec2};
+
+    use crate::{
+        render::chunk::TilemapRenderBackend,
+        tilemap::{
+            map::TilemapType,
+            tile::{RawTileAnimation, TileAnimationMode, TileBuilder, TileLayer},
+        },
+    };
+
+    use super::{assert_golden, GoldenCase};
+
+    fn with_flips(case: GoldenCase) -> GoldenCase {
+        case.with_tile(
+            IVec2::new(0, 0),
+            TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
+        )
+        .with_tile(
+            IVec2::new(1, 0),
+            TileBuilder::new().with_layer(0, TileLayer::flip_h(1)),
+        )
+        .with_tile(
+            IVec2::new(0, 1),
+            TileBuilder::new().with_layer(0, TileLayer::flip_v(2)),
+        )
+        .with_tile(
+            IVec2::new(1, 1),
+            TileBuilder::new().with_layer(0, TileLayer::flip_both(3)),
+        )
+    }
+
+    #[test]
+    fn golden_square() {
+        assert_golden(with_flips(
+            GoldenCase::new("square", TilemapType::Square, UVec2::splat(16))
+                .with_camera(Vec2::splat(16.)),
+        ));
+    }
+
+    #[test]
+    fn golden_isometric() {
+        assert_golden(with_flips(
+            GoldenCase::new("isometric", TilemapType::Isometric, UVec2::new(32, 16))
+                .with_target_size(UVec2::new(96, 64))
+                .with_camera(Vec2::new(16., 16.)),
+        ));
+    }
+
+    #[test]
+    fn golden_hexagonal() {
+        assert_golden(with_flips(
+            GoldenCase::new("hexagonal", TilemapType::Hexagonal(8), UVec2::new(16, 16))
+                .with_camera(Vec2::new(16., 16.)),
+        ));
+    }
+
+    #[test]
+    fn golden_animation() {
+        let mut case = GoldenCase::new("animation", TilemapType::Square, UVec2::splat(16))
+            .with_target_size(UVec2::new(48, 16))
+            .with_camera(Vec2::new(24., 8.))
+            // The loop is at frame 2, the ping pong is going back at frame 1
+            // and the once stays at its last frame.
+            .with_elapsed(1.25);
+        let looping =
+            case.register_animation(RawTileAnimation::from_atlas_indices(0, [0, 1, 2, 3], 2));
+        let ping_pong = case.register_animation(
+            RawTileAnimation::from_atlas_indices(0, [0, 1, 2, 3], 4)
+                .with_mode(TileAnimationMode::PingPong),
+        );
+        let once = case.register_animation(
+            RawTileAnimation::from_atlas_indices(0, [0, 1, 2], 4)
+                .with_mode(TileAnimationMode::Once),
+        );
+
+        assert_golden(
+            case.with_tile(IVec2::new(0, 0), TileBuilder::new().with_animation(looping))
+                .with_tile(
+                    IVec2::new(1, 0),
+                    TileBuilder::new().with_animation(ping_pong),
+                )
+                .with_tile(IVec2::new(2, 0), TileBuilder::new().with_animation(once)),
+        );
+    }
+
+    /// `6x4` tiles in `3x2` chunks of `2x2`, with an empty slot in all chunks but one.
+    fn multi_chunk(backend: TilemapRenderBackend) -> GoldenCase {
+        let mut case = GoldenCase::new("multi_chunk", TilemapType::Square, UVec2::splat(16))
+            .with_target_size(UVec2::new(96, 64))
+            .with_camera(Vec2::new(48., 32.))
+            .with_chunk_size(2)
+            .with_backend(backend);
+        for y in 0..4 {
+            for x in 0..6 {
+                if (x + 2 * y) % 5 == 0 {
+                    continue;
+                }
+                let atlas_index = (x + y) % 4;
+                let layer = if x % 2 == 1 {
+                    TileLayer::flip_h(atlas_index)
+                } else {
+                    TileLayer::no_flip(atlas_index)
+                };
+                case = case.with_tile(IVec2::new(x, y), TileBuilder::new().with_layer(0, layer));
+            }
+        }
+        case
+    }
+
+    #[test]
+    fn golden_multi_chunk_mesh() {
+        assert_golden(multi_chunk(TilemapRenderBackend::Mesh));
+    }
+
+    #[test]
+    fn golden_multi_chunk_instanced() {
+        assert_golden(multi_chunk(TilemapRenderBackend::Instanced));
+    }
+}
@@ -0,0 +1,345 @@
+//! Render zoomed out tilemaps using a baked thumbnail for each chunk,
+//! instead of the tile meshes.
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, With},
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{IVec2, UVec2},
+    reflect::Reflect,
+    render::{
+        camera::{Camera, OrthographicProjection},
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{BevyDefault, Image},
+        view::Visibility,
+    },
+    sprite::{Anchor, Sprite, SpriteBundle},
+    transform::components::Transform,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    render::bake::bake_tiles,
+    tilemap::{
+        coordinates,
+        map::{
+            TilePivot, TilemapLayerOpacities, TilemapSlotSize, TilemapStorage, TilemapTextures,
+            TilemapTransform, TilemapType,
+        },
+        tile::Tile,
+    },
+};
+
+/// Add this to a tilemap to render each chunk as a single quad when zoomed out.
+///
+/// The thumbnails are baked lazily when the lod is activated for the first time,
+/// and rebaked when tiles in the chunk change.
+///
+/// **Notice**: Only square tilemaps are supported. Animated tiles are not baked,
+/// and removing tiles won't update the thumbnails until other tiles in that chunk change.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapLod {
+    /// Switch to the thumbnails when the `OrthographicProjection::scale`
+    /// of every camera is larger than this.
+    pub threshold: f32,
+    /// The texels of each tile in the thumbnails.
+    pub resolution: u32,
+    pub(crate) active: bool,
+    #[reflect(ignore)]
+    pub(crate) dirty: HashSet<IVec2>,
+    #[reflect(ignore)]
+    pub(crate) thumbnails: HashMap<IVec2, (Entity, Handle<Image>)>,
+}
+
+impl TilemapLod {
+    pub fn new(threshold: f32, resolution: u32) -> Self {
+        Self {
+            threshold,
+            resolution,
+            active: false,
+            dirty: HashSet::default(),
+            thumbnails: HashMap::default(),
+        }
+    }
+
+    /// Returns `true` if the thumbnails are rendered instead of the tiles.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// The baked thumbnail of a chunk.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct ChunkThumbnail {
+    pub tilemap: Entity,
+    pub chunk: IVec2,
+}
+
+pub fn lod_chunk_marker(
+    tiles_query: Query<&Tile, Changed<Tile>>,
+    mut lods_query: Query<&mut TilemapLod>,
+) {
+    if lods_query.is_empty() {
+        return;
+    }
+
+    tiles_query.iter().for_each(|tile| {
+        if let Ok(mut lod) = lods_query.get_mut(tile.tilemap_id) {
+            if lod.thumbnails.contains_key(&tile.chunk_index) {
+                lod.dirty.insert(tile.chunk_index);
+            }
+        }
+    });
+}
+
+pub fn lod_switcher(
+    mut lods_query: Query<&mut TilemapLod>,
+    cameras_query: Query<&OrthographicProjection, With<Camera>>,
+) {
+    let min_scale = cameras_query
+        .iter()
+        .map(|proj| proj.scale)
+        .min_by(|a, b| a.total_cmp(b));
+
+    lods_query.iter_mut().for_each(|mut lod| {
+        let active = min_scale.is_some_and(|scale| scale > lod.threshold);
+        if lod.active != active {
+            lod.active = active;
+        }
+    });
+}
+
+pub fn chunk_thumbnail_baker(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(
+        Entity,
+        &mut TilemapLod,
+        &TilemapStorage,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+        &TilemapLayerOpacities,
+        &Handle<TilemapTextures>,
+    )>,
+    mut thumbnails_query: Query<&mut Visibility, With<ChunkThumbnail>>,
+    tiles_query: Query<&Tile>,
+    mut image_assets: ResMut<Assets<Image>>,
+    textures_assets: Res<Assets<TilemapTextures>>,
+) {
+    for (tilemap, mut lod, storage, ty, transform, pivot, slot_size, opacities, textures) in
+        &mut tilemaps_query
+    {
+        if *ty != TilemapType::Square {
+            continue;
+        }
+
+        let visibility = if lod.active {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        lod.thumbnails.values().for_each(|(thumbnail, _)| {
+            if let Ok(mut v) = thumbnails_query.get_mut(*thumbnail) {
+                if *v != visibility {
+                    *v = visibility;
+                }
+            }
+        });
+
+        // Thumbnails of the unloaded or removed chunks.
+        let chunks = &storage.storage.chunks;
+        let removed = lod
+            .thumbnails
+            .keys()
+            .filter(|c| !chunks.contains_key(*c))
+            .copied()
+            .collect::<Vec<_>>();
+        for chunk in removed {
+            let (thumbnail, _) = lod.thumbnails.remove(&chunk).unwrap();
+            commands.entity(thumbnail).despawn();
+        }
+
+        if !lod.active {
+            continue;
+        }
+
+        let Some(textures) = textures_assets.get(textures) else {
+            continue;
+        };
+        let Some(texture_images) = textures
+            .textures
+            .iter()
+            .map(|tex| image_assets.get(tex.handle()))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        if textures.textures.is_empty() {
+            continue;
+        }
+
+        let chunk_size = storage.storage.chunk_size;
+        let tile_size = textures.textures[0].desc.tile_size;
+        let resolution = UVec2::splat(lod.resolution.max(1)).min(tile_size);
+        let mut baked = Vec::new();
+
+        for (chunk_index, chunk) in chunks {
+            if lod.thumbnails.contains_key(chunk_index) && !lod.dirty.contains(chunk_index) {
+                continue;
+            }
+
+            let target_size = tile_size * chunk_size;
+            let mut bake_target = vec![0; (target_size.x * target_size.y * 4) as usize];
+            let tiles = chunk.iter().enumerate().filter_map(|(i, t)| {
+                let tile = tiles_query.get((*t)?).ok()?;
+                let i = i as u32;
+                Some((
+                    UVec2::new(i % chunk_size, chunk_size - 1 - i / chunk_size),
+                    tile,
+                ))
+            });
+            bake_tiles(
+                textures,
+                &texture_images,
+                opacities,
+                tiles,
+                target_size,
+                &mut bake_target,
+            );
+
+            baked.push((
+                *chunk_index,
+                downsample(&bake_target, target_size, resolution * chunk_size),
+                resolution * chunk_size,
+            ));
+        }
+
+        for (chunk_index, data, size) in baked {
+            let image = Image::new(
+                Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                data,
+                TextureFormat::bevy_default(),
+                RenderAssetUsages::RENDER_WORLD,
+            );
+
+            if let Some((_, handle)) = lod.thumbnails.get(&chunk_index) {
+                if let Some(old) = image_assets.get_mut(handle) {
+                    *old = image;
+                }
+                continue;
+            }
+
+            let handle = image_assets.add(image);
+            let origin = coordinates::index_to_world(
+                chunk_index * chunk_size as i32,
+                *ty,
+                transform,
+                pivot.0,
+                slot_size.0,
+            );
+            let mut thumbnail_transform: Transform = (*transform).into();
+            thumbnail_transform.translation = origin.extend(transform.z_index);
+
+            let thumbnail = commands
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            custom_size: Some(slot_size.0 * chunk_size as f32),
+                            anchor: Anchor::BottomLeft,
+                            ..Default::default()
+                        },
+                        texture: handle.clone(),
+                        transform: thumbnail_transform,
+                        visibility,
+                        ..Default::default()
+                    },
+                    ChunkThumbnail {
+                        tilemap,
+                        chunk: chunk_index,
+                    },
+                ))
+                .id();
+            lod.thumbnails.insert(chunk_index, (thumbnail, handle));
+        }
+
+        lod.dirty.clear();
+    }
+}
+
+/// Despawn the thumbnails of the tilemaps that are despawned or no longer have a lod.
+pub fn chunk_thumbnail_cleaner(
+    mut commands: Commands,
+    thumbnails_query: Query<(Entity, &ChunkThumbnail)>,
+    lods_query: Query<&TilemapLod>,
+) {
+    thumbnails_query.iter().for_each(|(entity, thumbnail)| {
+        if !lods_query.contains(thumbnail.tilemap) {
+            commands.entity(entity).despawn();
+        }
+    });
+}
+
+/// Shrink an rgba image by averaging the texels.
+fn downsample(data: &[u8], size: UVec2, target_size: UVec2) -> Vec<u8> {
+    if size == target_size {
+        return data.to_vec();
+    }
+
+    let mut result = vec![0; (target_size.x * target_size.y * 4) as usize];
+    for y in 0..target_size.y {
+        for x in 0..target_size.x {
+            let min = UVec2::new(x, y) * size / target_size;
+            let max = (UVec2::new(x + 1, y + 1) * size / target_size).max(min + 1);
+            let mut sum = [0u32; 4];
+            for sy in min.y..max.y {
+                for sx in min.x..max.x {
+                    let i = ((sy * size.x + sx) * 4) as usize;
+                    (0..4).for_each(|c| sum[c] += data[i + c] as u32);
+                }
+            }
+
+            let count = ((max.x - min.x) * (max.y - min.y)).max(1);
+            let i = ((y * target_size.x + x) * 4) as usize;
+            (0..4).for_each(|c| result[i + c] = (sum[c] / count) as u8);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::UVec2;
+
+    use super::downsample;
+
+    #[test]
+    fn test_downsample() {
+        #[rustfmt::skip]
+        let data = [
+            0, 0, 0, 0,     4, 8, 12, 16,
+            8, 16, 24, 32,  12, 24, 36, 48,
+        ];
+
+        assert_eq!(
+            downsample(&data, UVec2::new(2, 2), UVec2::ONE),
+            vec![6, 12, 18, 24]
+        );
+        assert_eq!(
+            downsample(&data, UVec2::new(2, 2), UVec2::new(2, 1)),
+            vec![4, 8, 12, 16, 8, 16, 24, 32]
+        );
+    }
+}
@@ -1,11 +1,17 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, sync::Arc};
 
 use bevy::{
     app::{App, Plugin},
-    asset::{Asset, AssetApp, AssetId},
+    asset::{Asset, AssetApp, AssetId, Assets, Handle},
     color::LinearRgba,
     core_pipeline::core_2d::Transparent2d,
-    ecs::{schedule::IntoSystemConfigs, system::SystemParamItem},
+    ecs::{
+        entity::Entity,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Resource, SystemParamItem},
+        world::World,
+    },
+    log::warn,
     prelude::{Deref, DerefMut},
     reflect::TypePath,
     render::{
@@ -18,14 +24,18 @@ use bevy::{
         },
         Render, RenderApp, RenderSet,
     },
+    utils::HashMap,
 };
 
 use crate::render::{
     binding::{self, TilemapBindGroups},
     chunk::{self},
-    draw::{DrawTilemapNonTextured, DrawTilemapTextured},
+    draw::{
+        DrawTilemapNonTextured, DrawTilemapShadowNonTextured, DrawTilemapShadowTextured,
+        DrawTilemapTextured,
+    },
     pipeline::EntiTilesPipeline,
-    prepare, queue,
+    prepare, queue, warmup,
 };
 
 #[derive(Default)]
@@ -36,10 +46,13 @@ impl<M: TilemapMaterial> Plugin for EntiTilesMaterialPlugin<M> {
         app.add_plugins((
             ExtractInstancesPlugin::<AssetId<M>>::new(),
             RenderAssetPlugin::<ExtractedTilemapMaterialWrapper<M>>::default(),
-        ))
-        .init_asset::<M>();
+        ));
+
+        init_material_asset::<M>(app);
 
-        let render_app = app.sub_app_mut(RenderApp);
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
 
         render_app
             .add_systems(
@@ -66,14 +79,22 @@ impl<M: TilemapMaterial> Plugin for EntiTilesMaterialPlugin<M> {
                 Render,
                 prepare::sort_chunks::<M>.in_set(RenderSet::PrepareResources),
             )
-            .add_systems(Render, queue::queue_tilemaps::<M>.in_set(RenderSet::Queue))
+            .add_systems(
+                Render,
+                (queue::queue_tilemaps::<M>, warmup::warmup_pipelines::<M>)
+                    .in_set(RenderSet::Queue),
+            )
             .init_resource::<TilemapBindGroups<M>>()
             .add_render_command::<Transparent2d, DrawTilemapTextured<M>>()
-            .add_render_command::<Transparent2d, DrawTilemapNonTextured<M>>();
+            .add_render_command::<Transparent2d, DrawTilemapNonTextured<M>>()
+            .add_render_command::<Transparent2d, DrawTilemapShadowTextured<M>>()
+            .add_render_command::<Transparent2d, DrawTilemapShadowNonTextured<M>>();
     }
 
     fn finish(&self, app: &mut bevy::prelude::App) {
-        let render_app = app.sub_app_mut(RenderApp);
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
 
         render_app
             .init_resource::<EntiTilesPipeline<M>>()
@@ -81,6 +102,14 @@ impl<M: TilemapMaterial> Plugin for EntiTilesMaterialPlugin<M> {
     }
 }
 
+/// Initialize `Assets<M>` if no other plugin has done it, so tilemaps can be
+/// created and serialized without the renderer.
+pub(crate) fn init_material_asset<M: TilemapMaterial>(app: &mut App) {
+    if !app.world().contains_resource::<Assets<M>>() {
+        app.init_asset::<M>();
+    }
+}
+
 pub trait TilemapMaterial: Default + Asset + AsBindGroup + TypePath + Clone {
     fn vertex_shader() -> ShaderRef {
         super::TILEMAP_SHADER.into()
@@ -141,3 +170,205 @@ impl TilemapMaterial for StandardTilemapMaterial {
         super::TILEMAP_SHADER.into()
     }
 }
+
+type LayerMaterialFactory = Arc<dyn Fn(&mut World, Entity) + Send + Sync>;
+
+/// Materials that can be assigned to tilemap layers by name.
+///
+/// Loaders (LDtk and Tiled) will look up this registry when a layer is marked
+/// with a material, and replace the `StandardTilemapMaterial` with the registered one.
+/// Use [`LayerMaterialApp::register_layer_material`] to register.
+///
+/// Layers without a material use the default one if it's registered using
+/// [`LayerMaterialApp::register_default_layer_material`].
+#[derive(Resource, Default, Clone)]
+pub struct LayerMaterialRegistry {
+    materials: HashMap<String, LayerMaterialFactory>,
+    default: Option<LayerMaterialFactory>,
+}
+
+impl LayerMaterialRegistry {
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.materials.contains_key(name)
+    }
+
+    /// Returns `true` if a default material is registered.
+    #[inline]
+    pub fn has_default(&self) -> bool {
+        self.default.is_some()
+    }
+
+    /// Replace the material of `tilemap` with the one registered as `name`.
+    ///
+    /// Returns false if the material is not registered.
+    pub fn apply(&self, commands: &mut Commands, tilemap: Entity, name: &str) -> bool {
+        let Some(factory) = self.materials.get(name).cloned() else {
+            warn!(
+                "Material {} is not registered! The material of the tilemap is not replaced.",
+                name
+            );
+            return false;
+        };
+
+        commands.add(move |world: &mut World| factory(world, tilemap));
+        true
+    }
+
+    /// Replace the material of `tilemap` with the one registered as `name`,
+    /// or the default one if `name` is `None` or not registered.
+    ///
+    /// Returns false if the standard material is kept.
+    pub fn apply_or_default(
+        &self,
+        commands: &mut Commands,
+        tilemap: Entity,
+        name: Option<&str>,
+    ) -> bool {
+        let factory = match name {
+            Some(name) => self.materials.get(name).or_else(|| {
+                warn!(
+                    "Material {} is not registered! Using the {} material instead.",
+                    name,
+                    if self.has_default() {
+                        "default"
+                    } else {
+                        "standard"
+                    }
+                );
+                self.default.as_ref()
+            }),
+            None => self.default.as_ref(),
+        };
+
+        let Some(factory) = factory.cloned() else {
+            return false;
+        };
+        commands.add(move |world: &mut World| factory(world, tilemap));
+        true
+    }
+}
+
+fn replace_standard_material<M: TilemapMaterial>(world: &mut World, tilemap: Entity, material: M) {
+    let handle = world.resource_mut::<Assets<M>>().add(material);
+    if let Some(mut tilemap) = world.get_entity_mut(tilemap) {
+        tilemap
+            .remove::<Handle<StandardTilemapMaterial>>()
+            .insert(handle);
+    }
+}
+
+pub trait LayerMaterialApp {
+    /// Register a material that can be assigned to layers in LDtk/Tiled files.
+    ///
+    /// **Notice**: You still need to add `EntiTilesMaterialPlugin::<M>` to render it.
+    fn register_layer_material<M: TilemapMaterial>(
+        &mut self,
+        name: &str,
+        factory: impl Fn() -> M + Send + Sync + 'static,
+    ) -> &mut Self;
+
+    /// Use `M` for all the layers in LDtk/Tiled files that don't have a material assigned,
+    /// so projects using custom materials can load authored maps directly.
+    ///
+    /// The factory receives the standard material the loader created,
+    /// so properties like the layer tint can be carried over.
+    ///
+    /// **Notice**: You still need to add `EntiTilesMaterialPlugin::<M>` to render it.
+    fn register_default_layer_material<M: TilemapMaterial>(
+        &mut self,
+        factory: impl Fn(&StandardTilemapMaterial) -> M + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl LayerMaterialApp for App {
+    fn register_layer_material<M: TilemapMaterial>(
+        &mut self,
+        name: &str,
+        factory: impl Fn() -> M + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(LayerMaterialRegistry::default)
+            .materials
+            .insert(
+                name.to_string(),
+                Arc::new(move |world: &mut World, tilemap: Entity| {
+                    replace_standard_material(world, tilemap, factory());
+                }),
+            );
+        self
+    }
+
+    fn register_default_layer_material<M: TilemapMaterial>(
+        &mut self,
+        factory: impl Fn(&StandardTilemapMaterial) -> M + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(LayerMaterialRegistry::default)
+            .default = Some(Arc::new(move |world: &mut World, tilemap: Entity| {
+            let standard = world
+                .get::<Handle<StandardTilemapMaterial>>(tilemap)
+                .and_then(|handle| {
+                    world
+                        .resource::<Assets<StandardTilemapMaterial>>()
+                        .get(handle)
+                })
+                .cloned()
+                .unwrap_or_default();
+            replace_standard_material(world, tilemap, factory(&standard));
+        }));
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::App,
+        asset::{Assets, Handle},
+        color::LinearRgba,
+        ecs::{system::Commands, world::CommandQueue},
+    };
+
+    use super::{LayerMaterialApp, LayerMaterialRegistry, StandardTilemapMaterial};
+
+    #[test]
+    fn test_default_layer_material() {
+        let mut app = App::new();
+        app.init_resource::<Assets<StandardTilemapMaterial>>()
+            .register_default_layer_material(|standard| StandardTilemapMaterial {
+                tint: standard.tint * 0.5,
+                ..Default::default()
+            });
+
+        let world = app.world_mut();
+        let authored =
+            world
+                .resource_mut::<Assets<StandardTilemapMaterial>>()
+                .add(StandardTilemapMaterial {
+                    tint: LinearRgba::WHITE,
+                    ..Default::default()
+                });
+        let tilemap = world.spawn(authored.clone()).id();
+
+        let registry = world.resource::<LayerMaterialRegistry>().clone();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        // Not registered, falls back to the default one.
+        assert!(registry.apply_or_default(&mut commands, tilemap, Some("lit")));
+        queue.apply(world);
+
+        let handle = world
+            .get::<Handle<StandardTilemapMaterial>>(tilemap)
+            .unwrap();
+        assert_ne!(*handle, authored);
+        assert_eq!(
+            world
+                .resource::<Assets<StandardTilemapMaterial>>()
+                .get(handle)
+                .unwrap()
+                .tint,
+            LinearRgba::WHITE * 0.5
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use bevy::{
-    app::{App, PostUpdate, Update},
+    app::{App, PostUpdate, PreUpdate, Update},
     asset::load_internal_asset,
     ecs::schedule::IntoSystemConfigs,
     prelude::{Handle, Plugin, Shader},
@@ -13,10 +13,16 @@ use bevy::{
 use crate::{
     render::{
         buffer::TilemapBuffers,
-        chunk::{ChunkUnload, RenderChunkSort, RenderChunkStorage, UnloadRenderChunk},
-        cull::FrustumCulling,
+        chunk::{
+            ChunkUnload, RenderChunkSort, RenderChunkStorage, TilemapRenderBackend,
+            UnloadRenderChunk,
+        },
+        clip::{TilemapClip, TilemapClipShape, TilemapClipSpace},
+        cull::{ChunkVisibilityChanged, FrustumCulling, SharedChunkVisibility},
         extract::ExtractedTilemap,
+        shadow::TilemapShadow,
         texture::TilemapTexturesStorage,
+        tint::TilemapGlobalTint,
     },
     tilemap::map::TilemapTextures,
 };
@@ -26,14 +32,27 @@ pub mod bake;
 pub mod binding;
 pub mod buffer;
 pub mod chunk;
+pub mod clip;
 pub mod cull;
 pub mod draw;
+#[cfg(feature = "baking")]
+pub mod export;
 pub mod extract;
+#[cfg(feature = "render-tests")]
+pub mod golden;
+#[cfg(feature = "baking")]
+pub mod lod;
 pub mod material;
 pub mod pipeline;
+pub mod post_processing;
 pub mod prepare;
 pub mod queue;
+pub mod shadow;
 pub mod texture;
+pub mod tint;
+pub mod warmup;
+#[cfg(feature = "weather")]
+pub mod weather;
 
 pub const SQUARE: Handle<Shader> = Handle::weak_from_u128(54311635145631);
 pub const ISOMETRIC: Handle<Shader> = Handle::weak_from_u128(45522415151365135);
@@ -50,6 +69,10 @@ pub const TILEMAP_MESH_ATTR_ATLAS_INDICES: MeshVertexAttribute =
 #[cfg(feature = "atlas")]
 pub const TILEMAP_MESH_ATTR_TEX_INDICES: MeshVertexAttribute =
     MeshVertexAttribute::new("TextureIndex", 51541634, VertexFormat::Sint32x4);
+pub const TILEMAP_MESH_ATTR_EMISSIVE: MeshVertexAttribute =
+    MeshVertexAttribute::new("Emissive", 51541635, VertexFormat::Float32);
+pub const TILEMAP_MESH_ATTR_LAYER_SCALES: MeshVertexAttribute =
+    MeshVertexAttribute::new("LayerScale", 51541636, VertexFormat::Uint32x4);
 
 #[derive(Default)]
 pub struct EntiTilesRendererPlugin;
@@ -71,9 +94,23 @@ impl Plugin for EntiTilesRendererPlugin {
         app.add_systems(
             Update,
             (
-                texture::set_texture_usage,
+                (
+                    texture::extrude_tilemap_textures,
+                    texture::set_texture_usage,
+                )
+                    .chain(),
+                warmup::set_warmup_texture_usage,
+                tint::global_tint_updater,
                 #[cfg(feature = "baking")]
                 bake::tilemap_baker,
+                #[cfg(feature = "baking")]
+                (
+                    lod::lod_chunk_marker,
+                    lod::lod_switcher,
+                    lod::chunk_thumbnail_baker,
+                    lod::chunk_thumbnail_cleaner,
+                )
+                    .chain(),
             ),
         )
         .add_systems(
@@ -82,10 +119,21 @@ impl Plugin for EntiTilesRendererPlugin {
                 .in_set(VisibilitySystems::CheckVisibility)
                 .after(bevy::render::view::check_visibility::<()>),
         )
+        .add_systems(PreUpdate, cull::send_chunk_visibility_events)
         .init_resource::<FrustumCulling>()
         .init_resource::<RenderChunkSort>()
+        .init_resource::<material::LayerMaterialRegistry>()
+        .init_resource::<TilemapGlobalTint>()
         .register_type::<UnloadRenderChunk>()
+        .register_type::<TilemapRenderBackend>()
+        .register_type::<TilemapShadow>()
+        .register_type::<TilemapClip>()
+        .register_type::<TilemapClipSpace>()
+        .register_type::<TilemapClipShape>()
+        .register_type::<TilemapGlobalTint>()
         .add_event::<ChunkUnload>()
+        .add_event::<ChunkVisibilityChanged>()
+        .register_type::<ChunkVisibilityChanged>()
         .add_plugins((
             RenderAssetPlugin::<TilemapTextures>::default(),
             ExtractInstancesPlugin::<ExtractedTilemap>::new(),
@@ -94,12 +142,20 @@ impl Plugin for EntiTilesRendererPlugin {
         #[cfg(feature = "baking")]
         {
             use bake::{BakedTilemap, TilemapBaker};
+            use lod::{ChunkThumbnail, TilemapLod};
 
             app.register_type::<TilemapBaker>()
-                .register_type::<BakedTilemap>();
+                .register_type::<BakedTilemap>()
+                .register_type::<TilemapLod>()
+                .register_type::<ChunkThumbnail>();
         }
 
-        let render_app = app.sub_app_mut(RenderApp);
+        let shared_visibility = SharedChunkVisibility::default();
+        app.insert_resource(shared_visibility.clone());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
 
         render_app
             .add_systems(
@@ -111,12 +167,14 @@ impl Plugin for EntiTilesRendererPlugin {
                     extract::extract_resources,
                     extract::extract_despawned_tilemaps,
                     extract::extract_despawned_tiles,
+                    warmup::extract_warmup,
                 ),
             )
             .add_systems(
                 Render,
                 (
                     texture::schedule_tilemap_texture_preparation,
+                    warmup::prepare_warmup_textures,
                     texture::prepare_tilemap_textures,
                     texture::queue_tilemap_textures,
                     buffer::prepare_tilemap_buffers,
@@ -128,6 +186,7 @@ impl Plugin for EntiTilesRendererPlugin {
             .init_resource::<RenderChunkSort>()
             .init_resource::<RenderChunkStorage>()
             .init_resource::<TilemapTexturesStorage>()
-            .init_resource::<TilemapBuffers>();
+            .init_resource::<TilemapBuffers>()
+            .insert_resource(shared_visibility);
     }
 }
@@ -15,12 +15,15 @@ use bevy::{
         },
         renderer::RenderDevice,
         texture::BevyDefault,
-        view::ViewUniform,
+        view::{ViewTarget, ViewUniform},
     },
 };
 
 use crate::{
-    render::{buffer::TilemapUniform, material::TilemapMaterial},
+    render::{
+        buffer::TilemapUniform, chunk::TilemapRenderBackend, material::TilemapMaterial,
+        TILEMAP_SHADER,
+    },
     tilemap::map::TilemapType,
 };
 
@@ -45,12 +48,45 @@ pub struct EntiTilesPipelineKey {
     pub msaa: u32,
     pub map_type: TilemapType,
     pub is_pure_color: bool,
+    pub hdr: bool,
+    pub backend: TilemapRenderBackend,
+    /// Draw the [`TilemapShadow`](crate::render::shadow::TilemapShadow) instead of the tiles.
+    pub shadow: bool,
     #[cfg(target_arch = "wasm32")]
     pub anim_seq_len: u32,
     #[cfg(target_arch = "wasm32")]
     pub tex_desc_len: u32,
 }
 
+impl EntiTilesPipelineKey {
+    pub fn new(
+        msaa: u32,
+        map_type: TilemapType,
+        is_pure_color: bool,
+        hdr: bool,
+        backend: TilemapRenderBackend,
+        shadow: bool,
+        #[cfg(target_arch = "wasm32")] render_device: &RenderDevice,
+    ) -> Self {
+        Self {
+            msaa,
+            map_type,
+            is_pure_color,
+            hdr,
+            backend,
+            shadow,
+            #[cfg(target_arch = "wasm32")]
+            anim_seq_len: GpuArrayBuffer::<bevy::math::IVec4>::batch_size(render_device).unwrap(),
+            #[cfg(target_arch = "wasm32")]
+            tex_desc_len:
+                GpuArrayBuffer::<crate::render::buffer::GpuTilemapTextureDescriptor>::batch_size(
+                    render_device,
+                )
+                .unwrap(),
+        }
+    }
+}
+
 impl<M: TilemapMaterial> FromWorld for EntiTilesPipeline<M> {
     fn from_world(world: &mut World) -> Self {
         let asset_server = world.resource::<AssetServer>();
@@ -138,6 +174,15 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
         );
         #[cfg(feature = "atlas")]
         shader_defs.push("ATLAS".into());
+        if key.hdr {
+            shader_defs.push("HDR".into());
+        }
+        if key.backend == TilemapRenderBackend::Instanced {
+            shader_defs.push("INSTANCED".into());
+        }
+        if key.shadow {
+            shader_defs.push("SHADOW".into());
+        }
         #[cfg(target_arch = "wasm32")]
         {
             shader_defs.push("WASM".into());
@@ -165,8 +210,20 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
             vtx_fmt.push(VertexFormat::Sint32x4);
         }
 
-        let vertex_layout =
-            VertexBufferLayout::from_vertex_formats(VertexStepMode::Vertex, vtx_fmt);
+        // emissive
+        vtx_fmt.push(VertexFormat::Float32);
+
+        if !key.is_pure_color {
+            // layer render scales
+            vtx_fmt.push(VertexFormat::Uint32x4);
+        }
+
+        // Instances have the same layout as the vertices of the meshes.
+        let step_mode = match key.backend {
+            TilemapRenderBackend::Mesh => VertexStepMode::Vertex,
+            TilemapRenderBackend::Instanced => VertexStepMode::Instance,
+        };
+        let vertex_layout = VertexBufferLayout::from_vertex_formats(step_mode, vtx_fmt);
 
         let mut layout = vec![
             // group(0)
@@ -182,22 +239,34 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
             layout.push(self.array_buffers_layout.clone());
         }
 
+        // Custom shaders don't know about `SHADOW`, so shadows are always drawn
+        // with the built-in ones, which don't read the material.
+        let (vertex_shader, fragment_shader) = if key.shadow {
+            (TILEMAP_SHADER, TILEMAP_SHADER)
+        } else {
+            (self.vertex_shader.clone(), self.fragment_shader.clone())
+        };
+
         let mut desc = RenderPipelineDescriptor {
             label: Some("tilemap_pipeline".into()),
             layout,
             push_constant_ranges: vec![],
             vertex: VertexState {
-                shader: self.vertex_shader.clone(),
+                shader: vertex_shader,
                 shader_defs: shader_defs.clone(),
                 entry_point: "tilemap_vertex".into(),
                 buffers: vec![vertex_layout],
             },
             fragment: Some(FragmentState {
-                shader: self.fragment_shader.clone(),
+                shader: fragment_shader,
                 shader_defs: shader_defs.clone(),
                 entry_point: "tilemap_fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: TextureFormat::bevy_default(),
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
                     blend: Some(BlendState::ALPHA_BLENDING),
                     write_mask: ColorWrites::ALL,
                 })],
@@ -219,7 +288,9 @@ impl<M: TilemapMaterial> SpecializedRenderPipeline for EntiTilesPipeline<M> {
             },
         };
 
-        M::specialize(&mut desc);
+        if !key.shadow {
+            M::specialize(&mut desc);
+        }
 
         desc
     }
@@ -0,0 +1,359 @@
+//! Fullscreen post processing effects, like heat haze, rain or crt.
+//!
+//! Implement [`TilemapPostEffect`] for the settings of your effect, register it using
+//! [`TilemapPostEffectApp::add_tilemap_post_effect`] and insert the settings to 2d cameras.
+//! The fragment shader can use the shared bindings from `entitiles::post_effect`:
+//!
+//! ```wgsl
+//! #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+//! #import entitiles::post_effect::{globals, sample_screen}
+//!
+//! struct HeatHaze {
+//!     strength: f32,
+//! }
+//!
+//! @group(0) @binding(2) var<uniform> settings: HeatHaze;
+//!
+//! @fragment
+//! fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+//!     let offset = sin(in.uv.y * 80. + globals.time * 4.) * settings.strength;
+//!     return sample_screen(in.uv + vec2(offset, 0.));
+//! }
+//! ```
+
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, SubApp},
+    asset::{AssetServer, Handle},
+    core_pipeline::{
+        core_2d::graph::{Core2d, Node2d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    },
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{QueryItem, With},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::{FromWorld, World},
+    },
+    render::{
+        extract_component::{
+            ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
+            UniformComponentPlugin,
+        },
+        globals::{GlobalsBuffer, GlobalsUniform},
+        render_graph::{
+            InternedRenderLabel, NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel,
+            ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            encase::internal::WriteInto,
+            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId,
+            ColorTargetState, ColorWrites, FragmentState, MultisampleState, Operations,
+            PipelineCache, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, Shader,
+            ShaderRef, ShaderStages, ShaderType, SpecializedRenderPipeline,
+            SpecializedRenderPipelines, TextureFormat, TextureSampleType,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::{ExtractedView, ViewTarget},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+/// Where the effect is inserted into the 2d render graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TilemapPostEffectStage {
+    /// Before tonemapping, colors are still in linear hdr space on hdr cameras.
+    BeforeTonemapping,
+    /// After tonemapping, right before bevy's own post processing like fxaa.
+    #[default]
+    AfterTonemapping,
+}
+
+impl TilemapPostEffectStage {
+    fn nodes(self) -> (Node2d, Node2d) {
+        match self {
+            TilemapPostEffectStage::BeforeTonemapping => (Node2d::EndMainPass, Node2d::Tonemapping),
+            TilemapPostEffectStage::AfterTonemapping => {
+                (Node2d::Tonemapping, Node2d::EndMainPassPostProcessing)
+            }
+        }
+    }
+}
+
+/// The settings of a post processing effect. It's uploaded to `@group(0) @binding(2)`.
+///
+/// The other bindings are shared by all the effects, see `entitiles::post_effect`.
+pub trait TilemapPostEffect: ExtractComponent<Out = Self> + ShaderType + WriteInto + Clone {
+    /// The fragment shader, the entry point should be `fragment`.
+    fn fragment_shader() -> ShaderRef;
+
+    fn stage() -> TilemapPostEffectStage {
+        TilemapPostEffectStage::default()
+    }
+}
+
+pub trait TilemapPostEffectApp {
+    /// Register a post processing effect. It's applied to the 2d cameras with `T`.
+    ///
+    /// Effects in the same stage are applied in the order they are added.
+    fn add_tilemap_post_effect<T: TilemapPostEffect>(&mut self) -> &mut Self;
+}
+
+impl TilemapPostEffectApp for App {
+    fn add_tilemap_post_effect<T: TilemapPostEffect>(&mut self) -> &mut Self {
+        self.add_plugins(TilemapPostEffectPlugin::<T>::default())
+    }
+}
+
+pub struct TilemapPostEffectPlugin<T: TilemapPostEffect>(PhantomData<T>);
+
+impl<T: TilemapPostEffect> Default for TilemapPostEffectPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: TilemapPostEffect> Plugin for TilemapPostEffectPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ExtractComponentPlugin::<T>::default(),
+            UniformComponentPlugin::<T>::default(),
+        ));
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        add_post_effect_node::<TilemapPostEffectNode<T>>(
+            render_app,
+            T::stage(),
+            TilemapPostEffectLabel(std::any::type_name::<T>()),
+        );
+        render_app.add_systems(
+            Render,
+            prepare_tilemap_post_effect_pipelines::<T>.in_set(RenderSet::Prepare),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<TilemapPostEffectPipeline<T>>()
+            .init_resource::<SpecializedRenderPipelines<TilemapPostEffectPipeline<T>>>();
+    }
+}
+
+/// Add a fullscreen node to the 2d render graph, chained after the last effect in `stage`.
+pub(crate) fn add_post_effect_node<N: ViewNode + FromWorld + Send + Sync + 'static>(
+    render_app: &mut SubApp,
+    stage: TilemapPostEffectStage,
+    label: impl RenderLabel,
+) {
+    let (start, end) = stage.nodes();
+    let previous = render_app
+        .world_mut()
+        .get_resource_or_insert_with(TilemapPostEffectOrder::default)
+        .push(stage, label.intern())
+        .unwrap_or(start.intern());
+
+    render_app
+        .add_render_graph_node::<ViewNodeRunner<N>>(Core2d, label.intern())
+        .add_render_graph_edges(Core2d, (previous, label.intern(), end.intern()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, RenderLabel)]
+pub struct TilemapPostEffectLabel(pub &'static str);
+
+/// The effects added so far, so the next one can be chained after the last one in its stage.
+#[derive(Resource, Default)]
+struct TilemapPostEffectOrder(Vec<(TilemapPostEffectStage, InternedRenderLabel)>);
+
+impl TilemapPostEffectOrder {
+    /// Returns the previous effect in the stage.
+    fn push(
+        &mut self,
+        stage: TilemapPostEffectStage,
+        label: InternedRenderLabel,
+    ) -> Option<InternedRenderLabel> {
+        let previous = self
+            .0
+            .iter()
+            .rev()
+            .find(|(s, _)| *s == stage)
+            .map(|(_, l)| *l);
+        self.0.push((stage, label));
+        previous
+    }
+}
+
+#[derive(Resource)]
+pub struct TilemapPostEffectPipeline<T: TilemapPostEffect> {
+    pub layout: BindGroupLayout,
+    pub sampler: Sampler,
+    pub shader: Handle<Shader>,
+    marker: PhantomData<T>,
+}
+
+impl<T: TilemapPostEffect> FromWorld for TilemapPostEffectPipeline<T> {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "tilemap_post_effect_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<T>(true),
+                    uniform_buffer::<GlobalsUniform>(false),
+                ),
+            ),
+        );
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+
+        let shader = match T::fragment_shader() {
+            ShaderRef::Default => panic!(
+                "Post effect {} doesn't have a fragment shader!",
+                std::any::type_name::<T>()
+            ),
+            ShaderRef::Handle(handle) => handle,
+            ShaderRef::Path(path) => world.resource::<AssetServer>().load(path),
+        };
+
+        Self {
+            layout,
+            sampler,
+            shader,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: TilemapPostEffect> SpecializedRenderPipeline for TilemapPostEffectPipeline<T> {
+    /// Whether the view is hdr.
+    type Key = bool;
+
+    fn specialize(&self, hdr: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("tilemap_post_effect_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: if hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TilemapPostEffectPipelineId<T: TilemapPostEffect>(
+    pub CachedRenderPipelineId,
+    PhantomData<T>,
+);
+
+pub fn prepare_tilemap_post_effect_pipelines<T: TilemapPostEffect>(
+    mut commands: Commands,
+    views_query: Query<(Entity, &ExtractedView), With<T>>,
+    pipeline: Res<TilemapPostEffectPipeline<T>>,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<TilemapPostEffectPipeline<T>>>,
+) {
+    views_query.iter().for_each(|(entity, view)| {
+        let id = pipelines.specialize(&pipeline_cache, &pipeline, view.hdr);
+        commands
+            .entity(entity)
+            .insert(TilemapPostEffectPipelineId::<T>(id, PhantomData));
+    });
+}
+
+pub struct TilemapPostEffectNode<T: TilemapPostEffect>(PhantomData<T>);
+
+impl<T: TilemapPostEffect> FromWorld for TilemapPostEffectNode<T> {
+    fn from_world(_world: &mut World) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: TilemapPostEffect> ViewNode for TilemapPostEffectNode<T> {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static TilemapPostEffectPipelineId<T>,
+        &'static DynamicUniformIndex<T>,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view_target, pipeline_id, uniform_index): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let pipeline = world.resource::<TilemapPostEffectPipeline<T>>();
+        let (Some(render_pipeline), Some(settings), Some(globals)) = (
+            world
+                .resource::<PipelineCache>()
+                .get_render_pipeline(pipeline_id.0),
+            world
+                .resource::<ComponentUniforms<T>>()
+                .uniforms()
+                .binding(),
+            world.resource::<GlobalsBuffer>().buffer.binding(),
+        ) else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "tilemap_post_effect_bind_group",
+            &pipeline.layout,
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &pipeline.sampler,
+                settings,
+                globals,
+            )),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("tilemap_post_effect_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[uniform_index.index()]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
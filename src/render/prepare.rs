@@ -15,7 +15,7 @@ use crate::{
 };
 
 pub fn prepare_tiles<M: TilemapMaterial>(
-    extracted_tiles: Query<&mut ExtractedTile>,
+    extracted_tiles: Query<&ExtractedTile>,
     mut render_chunks: ResMut<RenderChunkStorage>,
     tilemap_instances: Res<TilemapInstances>,
 ) {
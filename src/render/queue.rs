@@ -7,18 +7,22 @@ use bevy::{
         camera::ExtractedCamera,
         render_phase::{DrawFunctions, PhaseItemExtraIndex, ViewSortedRenderPhases},
         render_resource::{PipelineCache, SpecializedRenderPipelines},
+        view::ExtractedView,
     },
 };
 
 use crate::render::{
-    draw::{DrawTilemapNonTextured, DrawTilemapTextured},
+    draw::{
+        DrawTilemapNonTextured, DrawTilemapShadowNonTextured, DrawTilemapShadowTextured,
+        DrawTilemapTextured,
+    },
     extract::{TilemapInstances, TilemapMaterialIds},
     material::TilemapMaterial,
     pipeline::{EntiTilesPipeline, EntiTilesPipelineKey},
 };
 
 pub fn queue_tilemaps<M: TilemapMaterial>(
-    mut views_query: Query<Entity, With<ExtractedCamera>>,
+    mut views_query: Query<(Entity, &ExtractedView), With<ExtractedCamera>>,
     pipeline_cache: Res<PipelineCache>,
     draw_functions: Res<DrawFunctions<Transparent2d>>,
     mut sp_entitiles_pipeline: ResMut<SpecializedRenderPipelines<EntiTilesPipeline<M>>>,
@@ -29,7 +33,7 @@ pub fn queue_tilemaps<M: TilemapMaterial>(
     material_ids: Res<TilemapMaterialIds<M>>,
     #[cfg(target_arch = "wasm32")] render_device: Res<bevy::render::renderer::RenderDevice>,
 ) {
-    for view_entity in views_query.iter_mut() {
+    for (view_entity, view) in views_query.iter_mut() {
         let Some(transparent_phase) = transparent_phase.get_mut(&view_entity) else {
             continue;
         };
@@ -37,54 +41,54 @@ pub fn queue_tilemaps<M: TilemapMaterial>(
         // TODO optimize this
         let mut tilemaps = tilemap_instances
             .iter()
-            .filter(|(t, _)| material_ids.contains_key(*t))
+            .filter(|(t, m)| material_ids.contains_key(*t) && !m.lod_active)
             .collect::<Vec<_>>();
         radsort::sort_by_key(&mut tilemaps, |(_, m)| m.transform.z_index);
 
         for (entity, tilemap) in tilemaps {
-            let pipeline =
-                sp_entitiles_pipeline.specialize(
+            for shadow in [true, false] {
+                if shadow && tilemap.shadow.is_none() {
+                    continue;
+                }
+
+                let pipeline = sp_entitiles_pipeline.specialize(
                     &pipeline_cache,
                     &entitiles_pipeline,
-                    EntiTilesPipelineKey {
-                        msaa: msaa.samples(),
-                        map_type: tilemap.ty,
-                        is_pure_color: tilemap.texture.is_none(),
-                        #[cfg(target_arch = "wasm32")]
-                        anim_seq_len: bevy::render::render_resource::GpuArrayBuffer::<
-                            bevy::math::IVec4,
-                        >::batch_size(&render_device)
-                        .unwrap(),
+                    EntiTilesPipelineKey::new(
+                        msaa.samples(),
+                        tilemap.ty,
+                        tilemap.texture.is_none(),
+                        view.hdr,
+                        tilemap.backend,
+                        shadow,
                         #[cfg(target_arch = "wasm32")]
-                        tex_desc_len: bevy::render::render_resource::GpuArrayBuffer::<
-                            crate::render::buffer::GpuTilemapTextureDescriptor,
-                        >::batch_size(&render_device)
-                        .unwrap(),
-                    },
+                        &render_device,
+                    ),
                 );
 
-            let draw_function = {
-                if tilemap.texture.is_none() {
-                    draw_functions
-                        .read()
-                        .get_id::<DrawTilemapNonTextured<M>>()
-                        .unwrap()
-                } else {
-                    draw_functions
-                        .read()
-                        .get_id::<DrawTilemapTextured<M>>()
-                        .unwrap()
+                let draw_functions = draw_functions.read();
+                let draw_function = match (tilemap.texture.is_none(), shadow) {
+                    (true, false) => draw_functions.get_id::<DrawTilemapNonTextured<M>>(),
+                    (false, false) => draw_functions.get_id::<DrawTilemapTextured<M>>(),
+                    (true, true) => draw_functions.get_id::<DrawTilemapShadowNonTextured<M>>(),
+                    (false, true) => draw_functions.get_id::<DrawTilemapShadowTextured<M>>(),
                 }
-            };
+                .unwrap();
 
-            transparent_phase.add(Transparent2d {
-                sort_key: FloatOrd(tilemap.transform.z_index as f32),
-                entity: *entity,
-                pipeline,
-                draw_function,
-                batch_range: 0..1,
-                extra_index: PhaseItemExtraIndex::NONE,
-            });
+                // Shadows are drawn between the tilemap and the ones below it.
+                let z_index = match tilemap.shadow {
+                    Some(s) if shadow => tilemap.transform.z_index - s.z_offset,
+                    _ => tilemap.transform.z_index,
+                };
+                transparent_phase.add(Transparent2d {
+                    sort_key: FloatOrd(z_index),
+                    entity: *entity,
+                    pipeline,
+                    draw_function,
+                    batch_range: 0..1,
+                    extra_index: PhaseItemExtraIndex::NONE,
+                });
+            }
         }
     }
 }
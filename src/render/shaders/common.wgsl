@@ -5,9 +5,13 @@ struct TilemapTextureDescriptor {
     tile_uv_size: vec2f,
     uv_scale: vec2f,
     tile_count: vec2u,
-#ifdef WASM
-    _padding: vec2u,
-#endif // WASM
+    padding_uv: vec2f,
+    margin_uv: vec2f,
+    spacing_uv: vec2f,
+    // Relative to the smallest tile size among the textures.
+    tile_scale: vec2f,
+    // Uniform arrays need a stride of multiples of 16 bytes.
+    _padding: vec2f,
 }
 
 struct TilemapVertexInput {
@@ -18,10 +22,20 @@ struct TilemapVertexInput {
     // So the zw components are the start index and the length of the animation sequence.
     @location(1) index: vec4i,
     @location(2) tint: vec4f,
-#ifndef PURE_COLOR
+    // Locations are assigned in order, so the emissive intensity
+    // comes after whatever attributes exist.
+#ifdef PURE_COLOR
+    @location(3) emissive: f32,
+#else // PURE_COLOR
     @location(3) atlas_indices: vec4i,
 #ifdef ATLAS
     @location(4) texture_indices: vec4i,
+    @location(5) emissive: f32,
+    // The render scale of each layer, see `pack_render_scale`.
+    @location(6) layer_scales: vec4u,
+#else // ATLAS
+    @location(4) emissive: f32,
+    @location(5) layer_scales: vec4u,
 #endif // ATLAS
 #endif // PURE_COLOR
 }
@@ -29,6 +43,8 @@ struct TilemapVertexInput {
 struct TilemapVertexOutput {
     @builtin(position) position: vec4f,
     @location(0) tint: vec4f,
+    @location(5) emissive: f32,
+    @location(7) world_position: vec2f,
 #ifndef PURE_COLOR
     @location(1) uv: vec2f,
     @location(2) atlas_indices: vec4i,
@@ -37,6 +53,7 @@ struct TilemapVertexOutput {
 #ifdef ATLAS
     @location(4) texture_indices: vec4i,
 #endif // ATLAS
+    @location(6) layer_scales: vec4u,
 #endif // PURE_COLOR
 }
 
@@ -44,6 +61,12 @@ struct Tilemap {
     // For memory alignment
     rot_mat: vec4f,
     layer_opacities: vec4f,
+    global_tint: vec4f,
+    // The columns of the skew matrix and the color of `TilemapShadow`.
+    shadow_skew: vec4f,
+    shadow_tint: vec4f,
+    // See `TilemapClip::as_uniform`.
+    clip_rect: vec4f,
     tile_render_size: vec2f,
     translation: vec2f,
     slot_size: vec2f,
@@ -52,6 +75,7 @@ struct Tilemap {
     // // this value will only be meaningful when the tilemap is hexagonal!
     hex_legs: f32,
     time: f32,
+    clip_flags: u32,
 }
 
 struct StandardTilemapUniform {
@@ -92,3 +116,24 @@ var<storage> texture_descs: array<TilemapTextureDescriptor>;
 #endif // WASM
 #endif // ATLAS
 #endif // PURE_COLOR
+
+// See `TilemapClip`. Custom fragment shaders should discard the clipped fragments as well.
+fn is_clipped(world_position: vec2<f32>, frag_coord: vec2<f32>) -> bool {
+    let flags = tilemap.clip_flags;
+    if (flags & 1u) == 0u {
+        return false;
+    }
+
+    // Screen space.
+    let position = select(world_position, frag_coord - view.viewport.xy, (flags & 2u) != 0u);
+    let min = tilemap.clip_rect.xy;
+    let max = tilemap.clip_rect.zw;
+    var inside = all(position >= min) && all(position <= max);
+    // Ellipse.
+    if (flags & 4u) != 0u {
+        let d = (position - (min + max) / 2.) / ((max - min) / 2.);
+        inside = dot(d, d) <= 1.;
+    }
+    // Inverted.
+    return inside == ((flags & 8u) != 0u);
+}
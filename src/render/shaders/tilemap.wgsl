@@ -1,6 +1,6 @@
 #import bevy_entitiles::common::{
     TilemapVertexInput, TilemapVertexOutput,
-    tilemap, view, material, anim_seqs, texture_descs
+    tilemap, view, material, anim_seqs, texture_descs, is_clipped
 }
 
 // Here the three different imports are for the three different tilemap types.
@@ -17,10 +17,76 @@
     #import bevy_entitiles::hexagonal::get_mesh_origin
 #endif
 
+// See `TileAnimationMode`.
+fn get_anim_frame(raw_frame: i32, length: i32, mode: i32) -> i32 {
+    switch mode {
+        // PingPong
+        case 1: {
+            let period = max(length * 2 - 2, 1);
+            let frame = raw_frame % period;
+            return select(frame, period - frame, frame >= length);
+        }
+        // Once
+        case 2: {
+            return min(raw_frame, length - 1);
+        }
+        // Loop
+        default: {
+            return raw_frame % length;
+        }
+    }
+}
+
+// The corner of the tile that the vertex belongs to.
+fn get_corner(v_index: u32) -> u32 {
+#ifdef INSTANCED
+    // Instances are drawn without index buffers, so follow the order of the mesh indices.
+    var corners = array<u32, 6>(0u, 1u, 3u, 1u, 2u, 3u);
+    return corners[v_index % 6u];
+#else // INSTANCED
+    return v_index % 4u;
+#endif // INSTANCED
+}
+
+#ifndef PURE_COLOR
+// See `pack_render_scale`.
+fn unpack_render_scale(packed: u32) -> vec2<f32> {
+    return vec2<f32>(f32(packed & 0xFFFFu), f32(packed >> 16u)) / 256.;
+}
+
+// The render scale of a layer, relative to `tile_render_size`.
+fn get_layer_scale(texture_index: i32, packed_scale: u32) -> vec2<f32> {
+#ifdef ATLAS
+    return unpack_render_scale(packed_scale) * texture_descs[texture_index].tile_scale;
+#else // ATLAS
+    return unpack_render_scale(packed_scale);
+#endif // ATLAS
+}
+
+// The largest scale among the layers, which is the size of the whole tile.
+fn get_tile_scale(atlas_indices: vec4<i32>, texture_indices: vec4<i32>, layer_scales: vec4<u32>) -> vec2<f32> {
+    var scale = vec2<f32>(0., 0.);
+    for (var i = 0u; i < 4u; i++) {
+        if atlas_indices[i] >= 0 && texture_indices[i] >= 0 {
+            scale = max(scale, get_layer_scale(texture_indices[i], layer_scales[i]));
+        }
+    }
+    return select(scale, vec2<f32>(1., 1.), scale == vec2<f32>(0., 0.));
+}
+#endif // PURE_COLOR
+
 @vertex
 fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
     var output: TilemapVertexOutput;
+#ifdef INSTANCED
+    // Empty slots of the chunk, see `MeshTileData::EMPTY_INSTANCE`.
+    if input.index.z == -2 {
+        output.position = vec4<f32>(0., 0., 0., 0.);
+        return output;
+    }
+#endif // INSTANCED
     var mesh_origin = get_mesh_origin(input);
+    let corner = get_corner(input.v_index);
     
     var translations = array<vec2<f32>, 4>(
         vec2<f32>(0., 0.),
@@ -29,16 +95,10 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
         vec2<f32>(1., 0.),
     );
 
-    var position_model = (translations[input.v_index % 4u] - tilemap.pivot)
-                          * tilemap.tile_render_size + mesh_origin;
-    var position_world = vec4<f32>(
-        position_model.x * tilemap.rot_mat.xy + position_model.y * tilemap.rot_mat.zw + tilemap.translation, 0., 1.
-    );
-
-    output.position = view.clip_from_world * position_world;
-    // output.position = view.clip_from_world * vec4f(translations[input.v_index % 4u] * tilemap.tile_render_size , 0., 1.);
-    // output.position = view.clip_from_world * vec4f(translations[input.v_index % 4u] * 10. , 0., 1.);
+    // Tiles from textures with larger tile sizes are rendered larger.
+    var tile_scale = vec2<f32>(1., 1.);
     output.tint = input.tint;
+    output.emissive = input.emissive;
 
 #ifndef PURE_COLOR
 #ifdef ATLAS
@@ -56,29 +116,34 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
         vec2<f32>(1., 1.),
     );
 #endif // ATLAS
-    output.uv = uvs[(input.v_index) % 4u];
+    output.uv = uvs[corner];
     output.anim_flag = input.index.z;
 
     if input.index.z != -1 {
         // Means that this tile is a animated tile
         let start = input.index.z;
-        let length = input.index.w;
-        // The number before the start index is the fps.
+        let start_time = bitcast<f32>(input.index.w);
+        // The three numbers before the start index are length, mode and fps.
         // See `register` function in TilemapAnimations.
 #ifdef WASM
+        let length = anim_seqs[start - 3][0];
+        let mode = anim_seqs[start - 2][0];
         let fps = f32(anim_seqs[start - 1][0]);
-        var frame = i32(tilemap.time * fps) % length;
+#else // WASM
+        let length = anim_seqs[start - 3];
+        let mode = anim_seqs[start - 2];
+        let fps = f32(anim_seqs[start - 1]);
+#endif // WASM
+        let frame = get_anim_frame(i32(max(tilemap.time - start_time, 0.) * fps), length, mode);
+
+#ifdef WASM
 #ifdef ATLAS
         output.texture_indices[0] = anim_seqs[start + frame * 2][0];
         output.atlas_indices[0] = anim_seqs[start + frame * 2 + 1][0];
 #else // ATLAS
         output.atlas_indices[0] = anim_seqs[start + frame][0];
 #endif // ATLAS
-
 #else // WASM
-        let fps = f32(anim_seqs[start - 1]);
-        var frame = i32(tilemap.time * fps) % length;
-
 #ifdef ATLAS
         output.texture_indices[0] = anim_seqs[start + frame * 2];
         output.atlas_indices[0] = anim_seqs[start + frame * 2 + 1];
@@ -86,24 +151,65 @@ fn tilemap_vertex(input: TilemapVertexInput) -> TilemapVertexOutput {
         output.atlas_indices[0] = anim_seqs[start + frame];
 #endif // ATLAS
 #endif // WASM
+#ifdef ATLAS
+        tile_scale = get_layer_scale(output.texture_indices[0], input.layer_scales[0]);
+#else // ATLAS
+        tile_scale = get_layer_scale(0, input.layer_scales[0]);
+#endif // ATLAS
     } else {
         output.atlas_indices = input.atlas_indices;
 #ifdef ATLAS
         output.texture_indices = input.texture_indices;
+        tile_scale = get_tile_scale(input.atlas_indices, input.texture_indices, input.layer_scales);
+#else // ATLAS
+        tile_scale = get_tile_scale(input.atlas_indices, vec4<i32>(0), input.layer_scales);
 #endif // ATLAS
     }
+    output.layer_scales = input.layer_scales;
 #endif // PURE_COLOR
 
+    var position_tile = (translations[corner] - tilemap.pivot) * tilemap.tile_render_size * tile_scale;
+#ifdef SHADOW
+    // Skew the tile around its pivot.
+    position_tile = mat2x2<f32>(tilemap.shadow_skew.xy, tilemap.shadow_skew.zw) * position_tile;
+#endif // SHADOW
+    var position_model = position_tile + mesh_origin;
+    var position_world = vec4<f32>(
+        position_model.x * tilemap.rot_mat.xy + position_model.y * tilemap.rot_mat.zw + tilemap.translation, 0., 1.
+    );
+
+    output.position = view.clip_from_world * position_world;
+    output.world_position = position_world.xy;
+    // output.position = view.clip_from_world * vec4f(translations[input.v_index % 4u] * tilemap.tile_render_size , 0., 1.);
+    // output.position = view.clip_from_world * vec4f(translations[input.v_index % 4u] * 10. , 0., 1.);
+
     return output;
 }
 
 @fragment
 fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
+    if is_clipped(input.world_position, input.position.xy) {
+        discard;
+    }
+
 #ifdef PURE_COLOR
-    return input.tint;
+#ifdef SHADOW
+    return vec4<f32>(tilemap.shadow_tint.rgb, input.tint.a * tilemap.shadow_tint.a);
+#else // SHADOW
+    return apply_emissive(input.tint, input.emissive);
+#endif // SHADOW
     // return vec4f(1.);
 #else // PURE_COLOR
     var color = vec4<f32>(0., 0., 0., 0.);
+#ifdef ATLAS
+    let texture_indices = input.texture_indices;
+#else // ATLAS
+    let texture_indices = vec4<i32>(0);
+#endif // ATLAS
+    var tile_scale = get_tile_scale(input.atlas_indices, texture_indices, input.layer_scales);
+    if input.anim_flag != -1 {
+        tile_scale = get_layer_scale(texture_indices[0], input.layer_scales[0]);
+    }
 
     // Sample the 4 layers.
     for (var i = 0u; i < 4u; i++) {
@@ -124,6 +230,17 @@ fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
 #endif // ATLAS
 
         var uv = input.uv;
+        // Smaller layers only cover part of the tile, around the pivot.
+        let layer_scale = tile_scale / get_layer_scale(texture_indices[i], input.layer_scales[i]);
+        if any(layer_scale != vec2<f32>(1., 1.)) {
+            // The y axis of the uv is flipped.
+            let pos = (vec2<f32>(uv.x, 1. - uv.y) - tilemap.pivot) * layer_scale + tilemap.pivot;
+            if any(pos < vec2<f32>(0., 0.)) || any(pos > vec2<f32>(1., 1.)) {
+                continue;
+            }
+            uv = vec2<f32>(pos.x, 1. - pos.y);
+        }
+
         // Flip the uv if needed.
         if (flip & 2) != 0 {
             uv.x = 1. - uv.x;
@@ -139,7 +256,9 @@ fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
         // If `atlas` feature is enabled, we need to calculate the uv.
         let tile_index = vec2<f32>(f32(atlas_index % (*desc).tile_count.x),
                                    f32(atlas_index / (*desc).tile_count.x));
-        let atlas_uv = (tile_index + uv) * (*desc).tile_uv_size * (*desc).uv_scale;
+        let tile_stride = (*desc).tile_uv_size + 2. * (*desc).padding_uv + (*desc).spacing_uv;
+        let atlas_uv = ((*desc).margin_uv + tile_index * tile_stride + (*desc).padding_uv
+                        + uv * (*desc).tile_uv_size) * (*desc).uv_scale;
         let tex_color = textureSample(bevy_entitiles::common::color_texture,
                                       bevy_entitiles::common::color_texture_sampler,
                                       atlas_uv, texture_index);
@@ -158,7 +277,22 @@ fn tilemap_fragment(input: TilemapVertexOutput) -> @location(0) vec4<f32> {
             break;
         }
     }
-    // Apply the tint of the tile and the tilemap.
-    return color * input.tint * material.color;
+#ifdef SHADOW
+    // Only the shape of the tiles is kept.
+    return vec4<f32>(tilemap.shadow_tint.rgb, color.a * input.tint.a * tilemap.shadow_tint.a);
+#else // SHADOW
+    // Apply the tint of the tile, the tilemap and the global tint.
+    return apply_emissive(color * input.tint * material.color * tilemap.global_tint, input.emissive);
+#endif // SHADOW
 #endif // PURE_COLOR
 }
+
+fn apply_emissive(color: vec4<f32>, emissive: f32) -> vec4<f32> {
+#ifdef HDR
+    // Values above 1 will be picked up by bloom.
+    return vec4<f32>(color.rgb * (1. + emissive), color.a);
+#else // HDR
+    // No way to glow without hdr, just brighten the color.
+    return vec4<f32>(saturate(color.rgb * (1. + emissive)), color.a);
+#endif // HDR
+}
@@ -0,0 +1,84 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_render::view::View
+#import entitiles::post_effect::{globals, sample_screen}
+
+struct Weather {
+    color: vec4<f32>,
+    velocity: vec2<f32>,
+    mask_origin: vec2<f32>,
+    mask_size: vec2<f32>,
+    // 0: clear, 1: rain, 2: snow
+    kind: u32,
+    intensity: f32,
+    particle_spacing: f32,
+    shelter_height: f32,
+    has_mask: u32,
+}
+
+@group(0) @binding(2) var<uniform> weather: Weather;
+@group(0) @binding(4) var<uniform> view: View;
+@group(0) @binding(5) var mask_texture: texture_2d<f32>;
+
+// How many layers of particles with different depth.
+const LAYERS: u32 = 3u;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+fn is_sheltered(uv: vec2<f32>) -> bool {
+    if weather.has_mask == 0u {
+        return false;
+    }
+
+    let ndc = vec2<f32>(uv.x * 2. - 1., 1. - uv.y * 2.);
+    let world = (view.world_from_clip * vec4<f32>(ndc, 0., 1.)).xy;
+    let mask_uv = vec2<f32>(world.x - weather.mask_origin.x, weather.mask_origin.y - world.y)
+                  / weather.mask_size;
+    if any(mask_uv < vec2<f32>(0.)) || any(mask_uv >= vec2<f32>(1.)) {
+        return false;
+    }
+
+    let dim = vec2<f32>(textureDimensions(mask_texture));
+    let height = textureLoad(mask_texture, vec2<i32>(mask_uv * dim), 0).r;
+    return height >= weather.shelter_height;
+}
+
+fn particles(pixel: vec2<f32>) -> f32 {
+    // Screen space y points down.
+    let velocity = weather.velocity * vec2<f32>(1., -1.);
+    let direction = normalize(velocity + vec2<f32>(0., 1e-4));
+    var amount = 0.;
+
+    for (var i = 0u; i < LAYERS; i += 1u) {
+        // Further layers are smaller and slower.
+        let depth = 1. + f32(i) * 0.6;
+        let p = (pixel - velocity / depth * globals.time) / weather.particle_spacing * depth;
+        let cell = floor(p) + f32(i) * 37.;
+        if hash(cell) > weather.intensity {
+            continue;
+        }
+
+        let d = fract(p) - 0.5 - (vec2<f32>(hash(cell + 3.1), hash(cell + 7.7)) - 0.5) * 0.6;
+        if weather.kind == 1u {
+            let along = dot(d, direction);
+            let across = dot(d, vec2<f32>(-direction.y, direction.x));
+            amount += smoothstep(0.04, 0., abs(across)) * smoothstep(0.35, 0., abs(along)) / depth;
+        } else {
+            amount += smoothstep(0.12, 0.06, length(d)) / depth;
+        }
+    }
+
+    return amount;
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_screen(in.uv);
+    if weather.kind == 0u || is_sheltered(in.uv) {
+        return color;
+    }
+
+    let alpha = clamp(particles(in.position.xy) * weather.color.a, 0., 1.);
+    return vec4<f32>(mix(color.rgb, weather.color.rgb, alpha), color.a);
+}
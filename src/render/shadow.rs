@@ -0,0 +1,70 @@
+//! Fake 2d shadows of trees and walls, rendered from the same chunks as the tilemap.
+
+use bevy::{
+    color::LinearRgba,
+    ecs::component::Component,
+    math::{Mat2, Vec2},
+    reflect::Reflect,
+};
+
+pub const DEFAULT_SHADOW_Z_OFFSET: f32 = 1e-3;
+
+/// Insert this to a tilemap to render a skewed and flat colored copy of it
+/// right behind it, using the same meshes, so it only costs one extra draw.
+///
+/// Each tile is skewed around its pivot, so tall tiles like trees lean away from
+/// the point they stand on.
+///
+/// **Notice**: The shadow is culled along with the chunks of the tilemap,
+/// so shadows stretching out of the visible chunks may be cut off at the edges of the screen.
+/// And it's always drawn with the built-in shaders, the shaders and `specialize` of custom
+/// [`TilemapMaterial`](crate::render::material::TilemapMaterial)s are ignored.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct TilemapShadow {
+    /// Applied to each tile in tilemap space, before the rotation of the tilemap.
+    pub skew: Mat2,
+    /// The color of the shadow. The alpha of the tiles is multiplied by its alpha.
+    pub tint: LinearRgba,
+    /// Added to the translation of the tilemap.
+    pub offset: Vec2,
+    /// Subtracted from the z index of the tilemap, so the shadow is drawn right behind it.
+    /// Keep it smaller than the distance to the tilemaps below.
+    pub z_offset: f32,
+}
+
+impl Default for TilemapShadow {
+    fn default() -> Self {
+        Self {
+            skew: Mat2::IDENTITY,
+            tint: LinearRgba::new(0., 0., 0., 0.5),
+            offset: Vec2::ZERO,
+            z_offset: DEFAULT_SHADOW_Z_OFFSET,
+        }
+    }
+}
+
+impl TilemapShadow {
+    pub fn new(skew: Mat2, tint: LinearRgba, offset: Vec2) -> Self {
+        Self {
+            skew,
+            tint,
+            offset,
+            z_offset: DEFAULT_SHADOW_Z_OFFSET,
+        }
+    }
+
+    pub fn with_z_offset(mut self, z_offset: f32) -> Self {
+        self.z_offset = z_offset;
+        self
+    }
+
+    /// Lean the tiles horizontally by `shear` times their height,
+    /// and squash them vertically by `height`, like shadows cast by a low sun.
+    pub fn sheared(shear: f32, height: f32, tint: LinearRgba, offset: Vec2) -> Self {
+        Self::new(
+            Mat2::from_cols(Vec2::X, Vec2::new(shear, height)),
+            tint,
+            offset,
+        )
+    }
+}
@@ -5,13 +5,14 @@ use bevy::{
         query::With,
         system::{Commands, Query, Res, ResMut, Resource},
     },
+    log::error,
     prelude::Image,
     render::{
         render_asset::RenderAssets,
         render_resource::{
-            AddressMode, Extent3d, ImageCopyTexture, Origin3d, SamplerDescriptor, TextureAspect,
-            TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-            TextureViewDescriptor, TextureViewDimension,
+            Extent3d, ImageCopyTexture, Origin3d, TextureAspect, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+            TextureViewDimension,
         },
         renderer::{RenderDevice, RenderQueue},
         texture::{BevyDefault, GpuImage},
@@ -49,6 +50,51 @@ impl TilemapTexturesStorage {
             || self.queue_queue.contains(handle)
             || self.prepare_queue.contains(handle)
     }
+
+    /// Returns `true` if the texture array is created and all the textures are copied into it.
+    pub fn is_ready(&self, handle: &Handle<TilemapTextures>) -> bool {
+        self.textures.contains_key(handle)
+            && !self.queue_queue.contains(handle)
+            && !self.prepare_queue.contains(handle)
+    }
+}
+
+pub fn extrude_tilemap_textures(
+    mut image_assets: ResMut<Assets<Image>>,
+    mut textures_assets: ResMut<Assets<TilemapTextures>>,
+) {
+    let to_extrude = textures_assets
+        .iter()
+        .filter(|(_, t)| t.is_extruding())
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+
+    for id in to_extrude {
+        // Wait until all the images are loaded, otherwise the asset is modified every frame.
+        if textures_assets
+            .get(id)
+            .unwrap()
+            .textures
+            .iter()
+            .any(|t| t.extrusion > 0 && image_assets.get(t.handle()).is_none())
+        {
+            continue;
+        }
+
+        let textures = textures_assets.get_mut(id).unwrap();
+        for tex in textures.textures.iter_mut().filter(|t| t.extrusion > 0) {
+            match tex.extrude(image_assets.get(tex.handle()).unwrap()) {
+                Ok((image, desc)) => {
+                    tex.texture = image_assets.add(image);
+                    tex.desc = desc;
+                }
+                // Use the original texture instead.
+                Err(e) => error!("Failed to extrude the tilemap texture: {}", e),
+            }
+            tex.extrusion = 0;
+        }
+        textures.update_layout();
+    }
 }
 
 pub fn set_texture_usage(
@@ -116,7 +162,10 @@ pub fn prepare_tilemap_textures(
     let to_prepare = texture_storage.prepare_queue.drain().collect::<Vec<_>>();
 
     for textures_handle in &to_prepare {
-        let Some(textures) = textures_assets.get(textures_handle) else {
+        let Some(textures) = textures_assets
+            .get(textures_handle)
+            .filter(|t| !t.is_extruding())
+        else {
             texture_storage
                 .prepare_queue
                 .insert(textures_handle.clone());
@@ -146,20 +195,7 @@ pub fn prepare_tilemap_textures(
             view_formats: &[],
         });
 
-        let sampler = render_device.create_sampler(&SamplerDescriptor {
-            label: Some("tilemap_texture_array_sampler"),
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: textures.filter_mode,
-            min_filter: textures.filter_mode,
-            mipmap_filter: textures.filter_mode,
-            lod_min_clamp: 0.,
-            lod_max_clamp: f32::MAX,
-            compare: None,
-            anisotropy_clamp: 1,
-            border_color: None,
-        });
+        let sampler = render_device.create_sampler(&textures.sampler.descriptor());
 
         let texture_view = texture.create_view(&TextureViewDescriptor {
             label: Some("tilemap_texture_array_view"),
@@ -227,39 +263,37 @@ pub fn queue_tilemap_textures(
                 continue;
             }
 
-            let tile_count = desc.size / desc.tile_size;
             let array_gpu_image = texture_storage.textures.get(textures_handle).unwrap();
 
-            for index_y in 0..tile_count.y {
-                for index_x in 0..tile_count.x {
-                    command_encoder.copy_texture_to_texture(
-                        ImageCopyTexture {
-                            texture: &raw_gpu_image.texture,
-                            mip_level: 0,
-                            origin: Origin3d {
-                                x: index_x * desc.tile_size.x,
-                                y: index_y * desc.tile_size.y,
-                                z: 0,
-                            },
-                            aspect: TextureAspect::All,
-                        },
-                        ImageCopyTexture {
-                            texture: &array_gpu_image.texture,
-                            mip_level: 0,
-                            origin: Origin3d {
-                                x: 0,
-                                y: 0,
-                                z: index_x + index_y * tile_count.x + start_index,
-                            },
-                            aspect: TextureAspect::All,
+            for index in 0..texture.tile_count() {
+                let origin = texture.get_atlas_urect(index).min;
+                command_encoder.copy_texture_to_texture(
+                    ImageCopyTexture {
+                        texture: &raw_gpu_image.texture,
+                        mip_level: 0,
+                        origin: Origin3d {
+                            x: origin.x,
+                            y: origin.y,
+                            z: 0,
                         },
-                        Extent3d {
-                            width: desc.tile_size.x,
-                            height: desc.tile_size.y,
-                            depth_or_array_layers: 1,
+                        aspect: TextureAspect::All,
+                    },
+                    ImageCopyTexture {
+                        texture: &array_gpu_image.texture,
+                        mip_level: 0,
+                        origin: Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: index + start_index,
                         },
-                    );
-                }
+                        aspect: TextureAspect::All,
+                    },
+                    Extent3d {
+                        width: desc.tile_size.x,
+                        height: desc.tile_size.y,
+                        depth_or_array_layers: 1,
+                    },
+                );
             }
         }
     }
@@ -280,7 +314,10 @@ pub fn prepare_tilemap_textures(
     let to_prepare = texture_storage.prepare_queue.drain().collect::<Vec<_>>();
 
     for textures_handle in &to_prepare {
-        let Some(textures) = textures_assets.get(textures_handle) else {
+        let Some(textures) = textures_assets
+            .get(textures_handle)
+            .filter(|t| !t.is_extruding())
+        else {
             texture_storage
                 .prepare_queue
                 .insert(textures_handle.clone());
@@ -309,20 +346,7 @@ pub fn prepare_tilemap_textures(
             view_formats: &[],
         });
 
-        let sampler = render_device.create_sampler(&SamplerDescriptor {
-            label: Some("tilemap_texture_array_sampler"),
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: textures.filter_mode,
-            min_filter: textures.filter_mode,
-            mipmap_filter: textures.filter_mode,
-            lod_min_clamp: 0.,
-            lod_max_clamp: f32::MAX,
-            compare: None,
-            anisotropy_clamp: 1,
-            border_color: None,
-        });
+        let sampler = render_device.create_sampler(&textures.sampler.descriptor());
 
         let texture_view = texture.create_view(&TextureViewDescriptor {
             label: Some("tilemap_texture_array_view"),
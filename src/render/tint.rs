@@ -0,0 +1,198 @@
+use bevy::{
+    color::{ColorToComponents, LinearRgba},
+    ecs::{
+        change_detection::DetectChangesMut,
+        system::{Res, ResMut, Resource},
+    },
+    reflect::Reflect,
+    time::Time,
+};
+
+/// A looped curve of colors.
+///
+/// Keyframes are placed in `[0, 1)`, and the curve wraps around,
+/// so the last keyframe will be interpolated towards the first one.
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct ColorCurve {
+    keyframes: Vec<(f32, LinearRgba)>,
+}
+
+impl ColorCurve {
+    pub fn new(keyframes: impl IntoIterator<Item = (f32, LinearRgba)>) -> Self {
+        keyframes
+            .into_iter()
+            .fold(Self::default(), |curve, (t, color)| {
+                curve.with_keyframe(t, color)
+            })
+    }
+
+    /// A simple day/night cycle, starting at midnight.
+    pub fn day_night() -> Self {
+        Self::new([
+            (0., LinearRgba::rgb(0.25, 0.3, 0.55)),
+            (0.25, LinearRgba::rgb(1., 0.7, 0.55)),
+            (0.35, LinearRgba::WHITE),
+            (0.65, LinearRgba::WHITE),
+            (0.75, LinearRgba::rgb(1., 0.6, 0.45)),
+            (0.85, LinearRgba::rgb(0.35, 0.35, 0.6)),
+        ])
+    }
+
+    /// Add a keyframe at `t`. `t` will be wrapped into `[0, 1)`.
+    pub fn with_keyframe(mut self, t: f32, color: LinearRgba) -> Self {
+        let t = t.rem_euclid(1.);
+        let index = self.keyframes.partition_point(|(k, _)| *k < t);
+        self.keyframes.insert(index, (t, color));
+        self
+    }
+
+    #[inline]
+    pub fn keyframes(&self) -> &[(f32, LinearRgba)] {
+        &self.keyframes
+    }
+
+    /// Sample the curve at `t`, smoothly interpolating between the keyframes.
+    ///
+    /// Returns white if there's no keyframe.
+    pub fn sample(&self, t: f32) -> LinearRgba {
+        let (Some(first), Some(last)) = (self.keyframes.first(), self.keyframes.last()) else {
+            return LinearRgba::WHITE;
+        };
+
+        let t = t.rem_euclid(1.);
+        let next = self.keyframes.partition_point(|(k, _)| *k <= t);
+        let ((t0, c0), (t1, c1)) = if next == 0 {
+            ((last.0 - 1., last.1), *first)
+        } else if next == self.keyframes.len() {
+            (*last, (first.0 + 1., first.1))
+        } else {
+            (self.keyframes[next - 1], self.keyframes[next])
+        };
+
+        let span = t1 - t0;
+        if span <= f32::EPSILON {
+            return c1;
+        }
+
+        smooth_lerp(c0, c1, (t - t0) / span)
+    }
+}
+
+/// Interpolate between two colors using smoothstep.
+pub fn smooth_lerp(from: LinearRgba, to: LinearRgba, t: f32) -> LinearRgba {
+    let t = t.clamp(0., 1.);
+    let t = t * t * (3. - 2. * t);
+    LinearRgba::from_vec4(from.to_vec4().lerp(to.to_vec4(), t))
+}
+
+/// The tint applied to all the tilemaps using the built-in shader,
+/// on top of the tint of tiles and materials.
+///
+/// If `curve` is set, `tint` will be driven by it and advance every frame,
+/// which is useful for day/night cycles.
+///
+/// The tint is passed to the gpu along with the other per tilemap uniforms,
+/// so neither the tiles nor the materials are touched when it changes.
+#[derive(Resource, Debug, Clone, Reflect)]
+pub struct TilemapGlobalTint {
+    pub tint: LinearRgba,
+    pub curve: Option<ColorCurve>,
+    /// How long a full cycle of the curve takes, in seconds.
+    pub period: f32,
+    /// The current position on the curve, in `[0, 1)`.
+    pub progress: f32,
+    pub paused: bool,
+}
+
+impl Default for TilemapGlobalTint {
+    fn default() -> Self {
+        Self {
+            tint: LinearRgba::WHITE,
+            curve: None,
+            period: 60.,
+            progress: 0.,
+            paused: false,
+        }
+    }
+}
+
+impl TilemapGlobalTint {
+    pub fn new(curve: ColorCurve, period: f32) -> Self {
+        Self {
+            tint: curve.sample(0.),
+            curve: Some(curve),
+            period,
+            ..Default::default()
+        }
+    }
+
+    /// Jump to a certain position on the curve.
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = progress.rem_euclid(1.);
+        if let Some(curve) = &self.curve {
+            self.tint = curve.sample(self.progress);
+        }
+    }
+}
+
+pub fn global_tint_updater(mut global_tint: ResMut<TilemapGlobalTint>, time: Res<Time>) {
+    if global_tint.paused || global_tint.period <= 0. {
+        return;
+    }
+    let Some(curve) = &global_tint.curve else {
+        return;
+    };
+
+    let progress =
+        (global_tint.progress + time.delta_seconds() / global_tint.period).rem_euclid(1.);
+    let tint = curve.sample(progress);
+
+    // Only mark the resource as changed when the tint actually changes.
+    global_tint.bypass_change_detection().progress = progress;
+    if global_tint.tint != tint {
+        global_tint.tint = tint;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::{
+        color::LinearRgba,
+        ecs::{system::RunSystemOnce, world::World},
+        time::Time,
+    };
+
+    use super::{global_tint_updater, ColorCurve, TilemapGlobalTint};
+
+    #[test]
+    fn test_global_tint_change_detection() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.insert_resource(TilemapGlobalTint::new(
+            ColorCurve::new([(0., LinearRgba::WHITE), (0.5, LinearRgba::BLACK)]),
+            4.,
+        ));
+
+        let advance = |world: &mut World| {
+            world.clear_trackers();
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs(1));
+            world.run_system_once(global_tint_updater);
+            world.is_resource_changed::<TilemapGlobalTint>()
+        };
+
+        assert!(advance(&mut world));
+        assert_eq!(world.resource::<TilemapGlobalTint>().progress, 0.25);
+
+        // Stays the same with a flat curve.
+        world.insert_resource(TilemapGlobalTint::new(
+            ColorCurve::new([(0., LinearRgba::WHITE)]),
+            4.,
+        ));
+        assert!(!advance(&mut world));
+        assert_eq!(world.resource::<TilemapGlobalTint>().progress, 0.25);
+    }
+}
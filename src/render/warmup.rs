@@ -0,0 +1,211 @@
+//! Prepare textures and pipelines before the first tilemap is spawned.
+//!
+//! Insert a [`TilemapWarmup`] during a loading screen, and spawn the tilemaps
+//! after [`TilemapWarmup::is_finished`] returns `true`.
+
+use std::{
+    any::TypeId,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    asset::{AssetId, Assets, Handle},
+    ecs::system::{Commands, Res, ResMut, Resource},
+    prelude::{DetectChanges, Image, Msaa},
+    render::{
+        render_resource::{PipelineCache, SpecializedRenderPipelines, TextureUsages},
+        Extract,
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    render::{
+        chunk::TilemapRenderBackend,
+        material::TilemapMaterial,
+        pipeline::{EntiTilesPipeline, EntiTilesPipelineKey},
+        texture::TilemapTexturesStorage,
+    },
+    tilemap::map::{TilemapTextures, TilemapType},
+};
+
+/// A pipeline to compile ahead of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TilemapPipelineWarmup {
+    pub(crate) material: TypeId,
+    pub map_type: TilemapType,
+    pub textured: bool,
+    pub hdr: bool,
+    pub backend: TilemapRenderBackend,
+}
+
+#[derive(Debug, Default)]
+pub struct TilemapWarmupProgress {
+    pub(crate) textures: HashSet<AssetId<TilemapTextures>>,
+    pub(crate) pipelines: HashSet<TilemapPipelineWarmup>,
+}
+
+/// Uploads the textures and compiles the pipelines listed here,
+/// so the first frame that renders the tilemaps won't stall.
+///
+/// **Notice**: Pipelines are compiled with the current `Msaa` settings.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct TilemapWarmup {
+    pub(crate) textures: Vec<Handle<TilemapTextures>>,
+    pub(crate) pipelines: Vec<TilemapPipelineWarmup>,
+    pub(crate) progress: Arc<Mutex<TilemapWarmupProgress>>,
+}
+
+impl TilemapWarmup {
+    pub fn with_textures(mut self, textures: Handle<TilemapTextures>) -> Self {
+        self.textures.push(textures);
+        self
+    }
+
+    /// Compile the pipeline for tilemaps using material `M`.
+    /// Set `textured` to `false` for pure color tilemaps.
+    pub fn with_pipeline<M: TilemapMaterial>(
+        self,
+        map_type: TilemapType,
+        textured: bool,
+        hdr: bool,
+    ) -> Self {
+        self.with_pipeline_backend::<M>(map_type, textured, hdr, TilemapRenderBackend::Mesh)
+    }
+
+    /// Same as [`TilemapWarmup::with_pipeline`], but for tilemaps using `backend`.
+    pub fn with_pipeline_backend<M: TilemapMaterial>(
+        mut self,
+        map_type: TilemapType,
+        textured: bool,
+        hdr: bool,
+        backend: TilemapRenderBackend,
+    ) -> Self {
+        self.pipelines.push(TilemapPipelineWarmup {
+            material: TypeId::of::<M>(),
+            map_type,
+            textured,
+            hdr,
+            backend,
+        });
+        self
+    }
+
+    /// The ratio of the textures and pipelines that are ready, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        let total = self.textures.len() + self.pipelines.len();
+        if total == 0 {
+            return 1.;
+        }
+
+        let progress = self.progress.lock().unwrap();
+        (progress.textures.len() + progress.pipelines.len()) as f32 / total as f32
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.progress() >= 1.
+    }
+}
+
+pub fn set_warmup_texture_usage(
+    warmup: Option<Res<TilemapWarmup>>,
+    mut image_assets: ResMut<Assets<Image>>,
+    textures_assets: Res<Assets<TilemapTextures>>,
+) {
+    let Some(warmup) = warmup.filter(|w| !w.is_finished()) else {
+        return;
+    };
+
+    // Same as `set_texture_usage`, the textures need to be copied into the texture array.
+    for textures in warmup
+        .textures
+        .iter()
+        .filter_map(|t| textures_assets.get(t))
+    {
+        for tex in &textures.textures {
+            let missing_usage = image_assets.get(tex.handle()).is_some_and(|image| {
+                !image
+                    .texture_descriptor
+                    .usage
+                    .contains(TextureUsages::COPY_SRC)
+            });
+
+            if missing_usage {
+                image_assets
+                    .get_mut(tex.handle())
+                    .unwrap()
+                    .texture_descriptor
+                    .usage
+                    .set(TextureUsages::COPY_SRC, true);
+            }
+        }
+    }
+}
+
+pub fn extract_warmup(mut commands: Commands, warmup: Extract<Option<Res<TilemapWarmup>>>) {
+    match warmup.as_ref() {
+        Some(warmup) if warmup.is_changed() => {
+            commands.insert_resource(TilemapWarmup::clone(warmup))
+        }
+        Some(_) => {}
+        None => commands.remove_resource::<TilemapWarmup>(),
+    }
+}
+
+pub fn prepare_warmup_textures(
+    warmup: Option<Res<TilemapWarmup>>,
+    mut texture_storage: ResMut<TilemapTexturesStorage>,
+) {
+    let Some(warmup) = warmup else {
+        return;
+    };
+
+    let mut progress = warmup.progress.lock().unwrap();
+    for handle in &warmup.textures {
+        if texture_storage.is_ready(handle) {
+            progress.textures.insert(handle.id());
+        } else if !texture_storage.contains(handle) {
+            texture_storage.insert(handle.clone());
+        }
+    }
+}
+
+pub fn warmup_pipelines<M: TilemapMaterial>(
+    warmup: Option<Res<TilemapWarmup>>,
+    pipeline_cache: Res<PipelineCache>,
+    mut sp_entitiles_pipeline: ResMut<SpecializedRenderPipelines<EntiTilesPipeline<M>>>,
+    entitiles_pipeline: Res<EntiTilesPipeline<M>>,
+    msaa: Res<Msaa>,
+    #[cfg(target_arch = "wasm32")] render_device: Res<bevy::render::renderer::RenderDevice>,
+) {
+    let Some(warmup) = warmup else {
+        return;
+    };
+
+    let mut progress = warmup.progress.lock().unwrap();
+    for pipeline in &warmup.pipelines {
+        if pipeline.material != TypeId::of::<M>() || progress.pipelines.contains(pipeline) {
+            continue;
+        }
+
+        let id = sp_entitiles_pipeline.specialize(
+            &pipeline_cache,
+            &entitiles_pipeline,
+            EntiTilesPipelineKey::new(
+                msaa.samples(),
+                pipeline.map_type,
+                !pipeline.textured,
+                pipeline.hdr,
+                pipeline.backend,
+                false,
+                #[cfg(target_arch = "wasm32")]
+                &render_device,
+            ),
+        );
+
+        if pipeline_cache.get_render_pipeline(id).is_some() {
+            progress.pipelines.insert(*pipeline);
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! Screen space rain and snow.
+//!
+//! Set [`WeatherSettings`] to start the weather. Areas covered by
+//! [`WeatherSettings::mask`] stay dry, use [`UpdateWeatherRoofMask`] to create the mask
+//! from tiles with [`WeatherRoofed`], or use an authored [`TilemapHeightMap`] directly.
+//! The mask is removed by [`UnloadEverything`](crate::tilemap::despawn::UnloadEverything).
+
+use bevy::{
+    app::{App, Plugin},
+    asset::{load_internal_asset, Assets, Handle},
+    color::{ColorToComponents, LinearRgba},
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        observer::Trigger,
+        query::QueryItem,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::{Command, FromWorld, World},
+    },
+    log::warn,
+    math::{IVec2, UVec2, Vec2, Vec4},
+    reflect::Reflect,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        globals::{GlobalsBuffer, GlobalsUniform},
+        render_asset::RenderAssets,
+        render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode},
+        render_resource::{
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId,
+            ColorTargetState, ColorWrites, FragmentState, MultisampleState, Operations,
+            PipelineCache, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, Shader,
+            ShaderStages, ShaderType, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            TextureFormat, TextureSampleType, UniformBuffer,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{BevyDefault, FallbackImage, GpuImage, Image},
+        view::{ExtractedView, ViewTarget, ViewUniform, ViewUniformOffset, ViewUniforms},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::{
+    render::post_processing::{add_post_effect_node, TilemapPostEffectStage},
+    tilemap::{
+        coordinates,
+        despawn::EverythingUnloaded,
+        height::TilemapHeightMap,
+        map::{TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType},
+    },
+};
+
+pub const WEATHER_SHADER: Handle<Shader> = Handle::weak_from_u128(15643185431687431);
+
+pub struct EntiTilesWeatherPlugin;
+
+impl Plugin for EntiTilesWeatherPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            WEATHER_SHADER,
+            "shaders/weather.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins(ExtractResourcePlugin::<WeatherSettings>::default())
+            .init_resource::<WeatherSettings>()
+            .register_type::<WeatherSettings>()
+            .register_type::<WeatherKind>()
+            .register_type::<WeatherRoofed>()
+            .observe(weather_mask_unloader);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        add_post_effect_node::<WeatherNode>(
+            render_app,
+            TilemapPostEffectStage::AfterTonemapping,
+            WeatherLabel,
+        );
+        render_app.add_systems(
+            Render,
+            (prepare_weather_uniform, prepare_weather_pipelines).in_set(RenderSet::Prepare),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<WeatherPipeline>()
+            .init_resource::<SpecializedRenderPipelines<WeatherPipeline>>()
+            .init_resource::<WeatherUniformBuffer>();
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum WeatherKind {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+}
+
+/// The weather of all the 2d cameras.
+///
+/// **Notice**: The weather is applied after all the post effects added before
+/// [`EntiTilesWeatherPlugin`] in the same stage.
+#[derive(Resource, ExtractResource, Debug, Clone, Reflect)]
+pub struct WeatherSettings {
+    pub kind: WeatherKind,
+    /// How much of the particles are visible, from 0 to 1.
+    pub intensity: f32,
+    /// The falling velocity in pixels per second.
+    pub velocity: Vec2,
+    /// The size of the area each particle lives in, in pixels.
+    pub particle_spacing: f32,
+    /// The color of the particles, alpha is the opacity.
+    pub color: LinearRgba,
+    /// Places where the height is not less than [`WeatherSettings::shelter_height`]
+    /// don't receive rain or snow.
+    pub mask: Option<TilemapHeightMap>,
+    pub shelter_height: f32,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            intensity: 0.,
+            velocity: Vec2::ZERO,
+            particle_spacing: 32.,
+            color: LinearRgba::WHITE,
+            mask: None,
+            shelter_height: 0.5,
+        }
+    }
+}
+
+impl WeatherSettings {
+    pub fn rain(intensity: f32) -> Self {
+        Self {
+            kind: WeatherKind::Rain,
+            intensity,
+            velocity: Vec2::new(60., -900.),
+            particle_spacing: 24.,
+            color: LinearRgba::new(0.7, 0.75, 0.9, 0.6),
+            ..Default::default()
+        }
+    }
+
+    pub fn snow(intensity: f32) -> Self {
+        Self {
+            kind: WeatherKind::Snow,
+            intensity,
+            velocity: Vec2::new(20., -80.),
+            particle_spacing: 32.,
+            color: LinearRgba::new(1., 1., 1., 0.9),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_mask(mut self, mask: TilemapHeightMap) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+}
+
+/// Tiles with this component are considered indoors when creating the mask
+/// using [`UpdateWeatherRoofMask`].
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+pub struct WeatherRoofed;
+
+/// Create the mask of [`WeatherSettings`] from the tiles with [`WeatherRoofed`] in `tilemap`.
+///
+/// **Notice**: Only works for square tilemaps, rotation and axis flipping are ignored.
+pub struct UpdateWeatherRoofMask {
+    pub tilemap: Entity,
+}
+
+impl Command for UpdateWeatherRoofMask {
+    fn apply(self, world: &mut World) {
+        let Some((storage, ty, transform, slot_size)) =
+            world.get_entity(self.tilemap).and_then(|e| {
+                Some((
+                    e.get::<TilemapStorage>()?,
+                    e.get::<TilemapType>()?,
+                    e.get::<TilemapTransform>()?,
+                    e.get::<TilemapSlotSize>()?,
+                ))
+            })
+        else {
+            warn!("Entity {:?} is not a tilemap", self.tilemap);
+            return;
+        };
+        if *ty != TilemapType::Square {
+            warn!("Only square tilemaps can be used as weather masks");
+            return;
+        }
+
+        let roofed = storage
+            .iter_sorted()
+            .filter(|(_, tile)| world.get::<WeatherRoofed>(*tile).is_some())
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let (Some(min), Some(max)) = (
+            roofed.iter().copied().reduce(IVec2::min),
+            roofed.iter().copied().reduce(IVec2::max),
+        ) else {
+            world.resource_mut::<WeatherSettings>().mask = None;
+            return;
+        };
+
+        let size = (max - min + IVec2::ONE).as_uvec2();
+        let mut heights = vec![0.; (size.x * size.y) as usize];
+        roofed.into_iter().for_each(|index| {
+            let texel = UVec2::new((index.x - min.x) as u32, (max.y - index.y) as u32);
+            heights[(texel.y * size.x + texel.x) as usize] = 1.;
+        });
+        let origin = coordinates::index_to_world(
+            IVec2::new(min.x, max.y + 1),
+            *ty,
+            transform,
+            Vec2::ZERO,
+            slot_size.0,
+        );
+        let tile_size = slot_size.0;
+
+        let texture = world
+            .resource_mut::<Assets<Image>>()
+            .add(TilemapHeightMap::create_texture(&heights, size));
+        world.resource_mut::<WeatherSettings>().mask = Some(TilemapHeightMap {
+            texture,
+            size,
+            origin,
+            tile_size,
+        });
+    }
+}
+
+pub fn weather_mask_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut settings: ResMut<WeatherSettings>,
+) {
+    settings.mask = None;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, RenderLabel)]
+pub struct WeatherLabel;
+
+#[derive(ShaderType, Default)]
+pub struct WeatherUniform {
+    pub color: Vec4,
+    pub velocity: Vec2,
+    pub mask_origin: Vec2,
+    pub mask_size: Vec2,
+    pub kind: u32,
+    pub intensity: f32,
+    pub particle_spacing: f32,
+    pub shelter_height: f32,
+    pub has_mask: u32,
+}
+
+#[derive(Resource, Default)]
+pub struct WeatherUniformBuffer(UniformBuffer<WeatherUniform>);
+
+pub fn prepare_weather_uniform(
+    settings: Res<WeatherSettings>,
+    mut buffer: ResMut<WeatherUniformBuffer>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    buffer.0.set(WeatherUniform {
+        color: settings.color.to_vec4(),
+        velocity: settings.velocity,
+        mask_origin: settings.mask.as_ref().map(|m| m.origin).unwrap_or_default(),
+        mask_size: settings
+            .mask
+            .as_ref()
+            .map(|m| m.size.as_vec2() * m.tile_size)
+            .unwrap_or_default(),
+        kind: settings.kind as u32,
+        intensity: settings.intensity,
+        particle_spacing: settings.particle_spacing,
+        shelter_height: settings.shelter_height,
+        has_mask: settings.mask.is_some() as u32,
+    });
+    buffer.0.write_buffer(&render_device, &render_queue);
+}
+
+#[derive(Resource)]
+pub struct WeatherPipeline {
+    pub layout: BindGroupLayout,
+    pub sampler: Sampler,
+}
+
+impl FromWorld for WeatherPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "weather_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<WeatherUniform>(false),
+                    uniform_buffer::<GlobalsUniform>(false),
+                    uniform_buffer::<ViewUniform>(true),
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                ),
+            ),
+        );
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+
+        Self { layout, sampler }
+    }
+}
+
+impl SpecializedRenderPipeline for WeatherPipeline {
+    /// Whether the view is hdr.
+    type Key = bool;
+
+    fn specialize(&self, hdr: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("weather_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: WEATHER_SHADER,
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: if hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct WeatherPipelineId(pub CachedRenderPipelineId);
+
+pub fn prepare_weather_pipelines(
+    mut commands: Commands,
+    views_query: Query<(Entity, &ExtractedView)>,
+    settings: Res<WeatherSettings>,
+    pipeline: Res<WeatherPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<WeatherPipeline>>,
+) {
+    if settings.kind == WeatherKind::Clear {
+        return;
+    }
+
+    views_query.iter().for_each(|(entity, view)| {
+        let id = pipelines.specialize(&pipeline_cache, &pipeline, view.hdr);
+        commands.entity(entity).insert(WeatherPipelineId(id));
+    });
+}
+
+#[derive(Default)]
+pub struct WeatherNode;
+
+impl ViewNode for WeatherNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewUniformOffset,
+        &'static WeatherPipelineId,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view_target, view_offset, pipeline_id): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let settings = world.resource::<WeatherSettings>();
+        if settings.kind == WeatherKind::Clear {
+            return Ok(());
+        }
+
+        let pipeline = world.resource::<WeatherPipeline>();
+        let (Some(render_pipeline), Some(weather), Some(globals), Some(view)) = (
+            world
+                .resource::<PipelineCache>()
+                .get_render_pipeline(pipeline_id.0),
+            world.resource::<WeatherUniformBuffer>().0.binding(),
+            world.resource::<GlobalsBuffer>().buffer.binding(),
+            world.resource::<ViewUniforms>().uniforms.binding(),
+        ) else {
+            return Ok(());
+        };
+        let mask = settings
+            .mask
+            .as_ref()
+            .and_then(|m| world.resource::<RenderAssets<GpuImage>>().get(&m.texture))
+            .unwrap_or(&world.resource::<FallbackImage>().d2);
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "weather_bind_group",
+            &pipeline.layout,
+            &BindGroupEntries::sequential((
+                post_process.source,
+                &pipeline.sampler,
+                weather,
+                globals,
+                view,
+                &mask.texture_view,
+            )),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("weather_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[view_offset.offset]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::Assets,
+        ecs::{
+            system::Commands,
+            world::{Command, CommandQueue, World},
+        },
+        math::{IVec2, UVec2, Vec2},
+        render::texture::Image,
+    };
+
+    use crate::tilemap::{
+        map::{TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType},
+        tile::TileBuilder,
+    };
+
+    use super::{UpdateWeatherRoofMask, WeatherRoofed, WeatherSettings};
+
+    #[test]
+    fn test_roof_mask() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<WeatherSettings>();
+
+        let tilemap = world.spawn_empty().id();
+        let mut storage = TilemapStorage::new(16, tilemap);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        for index in [IVec2::new(1, 1), IVec2::new(2, 1), IVec2::new(3, 2)] {
+            storage.set(&mut commands, index, TileBuilder::new());
+        }
+        queue.apply(&mut world);
+        for index in [IVec2::new(1, 1), IVec2::new(3, 2)] {
+            world
+                .entity_mut(storage.get(index).unwrap())
+                .insert(WeatherRoofed);
+        }
+        world.entity_mut(tilemap).insert((
+            storage,
+            TilemapType::Square,
+            TilemapTransform::default(),
+            TilemapSlotSize(Vec2::splat(16.)),
+        ));
+
+        UpdateWeatherRoofMask { tilemap }.apply(&mut world);
+
+        let mask = world.resource::<WeatherSettings>().mask.clone().unwrap();
+        assert_eq!(mask.size, UVec2::new(3, 2));
+        assert_eq!(mask.origin, Vec2::new(16., 48.));
+        let image = world
+            .resource::<Assets<Image>>()
+            .get(&mask.texture)
+            .unwrap();
+        let heights = image
+            .data
+            .chunks(4)
+            .map(|h| f32::from_le_bytes(h.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        // The first row is the top one.
+        assert_eq!(heights, vec![0., 0., 1., 1., 0., 0.]);
+    }
+}
@@ -0,0 +1,586 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock, RwLock, Weak},
+};
+
+use bevy::{ecs::system::Resource, log::error, utils::HashMap};
+use memmap2::Mmap;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::serializing::version::{SaveFormat, SaveMigrations, Versioned};
+
+/// Where chunks are saved to and loaded from.
+///
+/// `path` is made up of `ChunkSaveConfig::path`/`ChunkLoadConfig::path`,
+/// the tilemap name, the layer folder and the chunk file name.
+pub trait ChunkIoBackend: Send + Sync + 'static {
+    fn write(&self, path: &Path, data: String) -> io::Result<()>;
+
+    /// Returns `Ok(None)` if the chunk was never saved.
+    fn read(&self, path: &Path) -> io::Result<Option<String>>;
+}
+
+/// Saves chunks as files. This is the default backend except on wasm32.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct FileSystemChunkBackend;
+
+impl ChunkIoBackend for FileSystemChunkBackend {
+    fn write(&self, path: &Path, data: String) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, data)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Option<String>> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Keeps chunks in memory, so they are lost after the app exits.
+///
+/// This is the default backend on wasm32 when `wasm-storage` feature is disabled.
+#[derive(Default, Debug)]
+pub struct MemoryChunkBackend {
+    chunks: RwLock<HashMap<PathBuf, String>>,
+}
+
+impl ChunkIoBackend for MemoryChunkBackend {
+    fn write(&self, path: &Path, data: String) -> io::Result<()> {
+        self.chunks
+            .write()
+            .map_err(|err| io::Error::other(err.to_string()))?
+            .insert(path.to_path_buf(), data);
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Option<String>> {
+        Ok(self
+            .chunks
+            .read()
+            .map_err(|err| io::Error::other(err.to_string()))?
+            .get(path)
+            .cloned())
+    }
+}
+
+/// Packs the chunks of each tilemap into a single `<tilemap name>.chunks` file, next to
+/// where the tilemap folder would be when using [`FileSystemChunkBackend`].
+///
+/// Each chunk is stored as a blob along with its key. On native platforms, the file is
+/// memory-mapped and the offset table is built from the record headers when it's opened,
+/// so loading a chunk only touches its own blob instead of parsing a whole file or
+/// walking through directories.
+///
+/// **Notice**: Saving a chunk again appends a new blob and leaves the old one in the file.
+/// Call [`PackedChunkBackend::compact`] occasionally to reclaim the space.
+///
+/// **Notice**: Don't modify the files from elsewhere while they're open.
+/// Backends share the opened files with each other, so it's fine to use more than one.
+#[derive(Default, Debug)]
+pub struct PackedChunkBackend {
+    containers: RwLock<HashMap<PathBuf, Arc<Mutex<ChunkContainer>>>>,
+}
+
+type SharedContainers = Mutex<HashMap<PathBuf, Weak<Mutex<ChunkContainer>>>>;
+
+/// The containers opened by all the backends, so a file is never opened twice.
+static OPEN_CONTAINERS: OnceLock<SharedContainers> = OnceLock::new();
+
+impl PackedChunkBackend {
+    /// Split `<root>/<tilemap>/<layer>/<chunk>.ron` into `<root>/<tilemap>.chunks`
+    /// and `<layer>/<chunk>.ron`.
+    fn locate(path: &Path) -> io::Result<(PathBuf, String)> {
+        let chunk = path.file_name();
+        let layer = path.parent().and_then(|p| p.file_name());
+        let tilemap = path.parent().and_then(|p| p.parent());
+        match (tilemap, layer, chunk) {
+            (Some(tilemap), Some(layer), Some(chunk)) => Ok((
+                Self::container_path(tilemap),
+                format!("{}/{}", layer.to_string_lossy(), chunk.to_string_lossy()),
+            )),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not a chunk path", path),
+            )),
+        }
+    }
+
+    fn container_path(tilemap: &Path) -> PathBuf {
+        let mut path = tilemap.as_os_str().to_owned();
+        path.push(".chunks");
+        path.into()
+    }
+
+    fn container(&self, path: &Path) -> io::Result<Arc<Mutex<ChunkContainer>>> {
+        if let Some(container) = self
+            .containers
+            .read()
+            .map_err(|err| io::Error::other(err.to_string()))?
+            .get(path)
+        {
+            return Ok(container.clone());
+        }
+
+        let mut containers = self
+            .containers
+            .write()
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        if let Some(container) = containers.get(path) {
+            return Ok(container.clone());
+        }
+
+        let container = Self::open_shared(path)?;
+        containers.insert(path.to_path_buf(), container.clone());
+        Ok(container)
+    }
+
+    /// Reuse the container if another backend has opened it.
+    fn open_shared(path: &Path) -> io::Result<Arc<Mutex<ChunkContainer>>> {
+        let mut shared = OPEN_CONTAINERS
+            .get_or_init(Default::default)
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        if let Some(container) = shared.get(path).and_then(Weak::upgrade) {
+            return Ok(container);
+        }
+
+        let container = Arc::new(Mutex::new(ChunkContainer::open(path)?));
+        shared.retain(|_, container| container.strong_count() > 0);
+        shared.insert(path.to_path_buf(), Arc::downgrade(&container));
+        Ok(container)
+    }
+
+    /// Rewrite the container of `tilemap` with only the latest blob of each chunk.
+    ///
+    /// `path` is the same as `ChunkSaveConfig::path`. The backend in [`ChunkIo`] sees
+    /// the compacted file too, as the opened files are shared between backends.
+    pub fn compact(&self, path: &str, tilemap: &str) -> io::Result<()> {
+        let container = self.container(&Self::container_path(&Path::new(path).join(tilemap)))?;
+        let mut container = container
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        container.compact()
+    }
+}
+
+impl ChunkIoBackend for PackedChunkBackend {
+    fn write(&self, path: &Path, data: String) -> io::Result<()> {
+        let (container, key) = Self::locate(path)?;
+        self.container(&container)?
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()))?
+            .write(key, data.as_bytes())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Option<String>> {
+        let (container, key) = Self::locate(path)?;
+        if !container.exists() {
+            return Ok(None);
+        }
+
+        let Some(data) = self
+            .container(&container)?
+            .lock()
+            .map_err(|err| io::Error::other(err.to_string()))?
+            .read(&key)?
+        else {
+            return Ok(None);
+        };
+        String::from_utf8(data)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// A file made up of a magic number followed by records of
+/// `key length (u16) | key | blob length (u32) | blob`, in little endian.
+///
+/// The file is memory-mapped if the platform supports it, otherwise it's read with seeking.
+#[derive(Debug)]
+struct ChunkContainer {
+    path: PathBuf,
+    file: File,
+    map: Option<Mmap>,
+    /// The offset and the length of the latest blob of each key.
+    offsets: HashMap<String, (u64, u32)>,
+    end: u64,
+}
+
+impl ChunkContainer {
+    const MAGIC: &'static [u8; 8] = b"ETCHUNK1";
+
+    fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let len = file.metadata()?.len();
+
+        if len == 0 {
+            file.write_all(Self::MAGIC)?;
+        } else {
+            let mut magic = [0; 8];
+            file.read_exact(&mut magic)?;
+            if &magic != Self::MAGIC {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{:?} is not a chunk container", path),
+                ));
+            }
+        }
+
+        let mut container = Self {
+            path: path.to_path_buf(),
+            file,
+            map: None,
+            offsets: HashMap::default(),
+            end: Self::MAGIC.len() as u64,
+        };
+        container.build_offsets(len)?;
+        Ok(container)
+    }
+
+    /// Map the whole file into memory again, so the blobs appended since then are covered.
+    fn remap(&mut self) {
+        // SAFETY: The file is only modified through this container, which appends after
+        // the mapped range, and drops the map before truncating or replacing the file.
+        self.map = unsafe { Mmap::map(&self.file) }.ok();
+    }
+
+    fn build_offsets(&mut self, len: u64) -> io::Result<()> {
+        self.remap();
+        let fallback;
+        let data: &[u8] = match &self.map {
+            Some(map) => map,
+            None => {
+                fallback = std::fs::read(&self.path)?;
+                &fallback
+            }
+        };
+
+        // Only the headers are read, the blobs are skipped.
+        let mut end = self.end;
+        let mut offsets = HashMap::default();
+        loop {
+            let header = end as usize;
+            let Some(key_len) = data.get(header..header + 2) else {
+                break;
+            };
+            let key_len = u16::from_le_bytes([key_len[0], key_len[1]]) as usize;
+            let (Some(key), Some(blob_len)) = (
+                data.get(header + 2..header + 2 + key_len),
+                data.get(header + 2 + key_len..header + 6 + key_len),
+            ) else {
+                break;
+            };
+
+            let blob_len = u32::from_le_bytes([blob_len[0], blob_len[1], blob_len[2], blob_len[3]]);
+            let offset = end + 6 + key_len as u64;
+            // The last record is incomplete if the app exited while writing it.
+            if offset + blob_len as u64 > len {
+                break;
+            }
+
+            offsets.insert(
+                String::from_utf8(key.to_vec())
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+                (offset, blob_len),
+            );
+            end = offset + blob_len as u64;
+        }
+
+        self.offsets = offsets;
+        self.end = end;
+        if self.end < len {
+            self.map = None;
+            self.file.set_len(self.end)?;
+            self.remap();
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, key: String, blob: &[u8]) -> io::Result<()> {
+        let key_len = u16::try_from(key.len())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let blob_len = u32::try_from(blob.len())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let mut record = Vec::with_capacity(6 + key.len() + blob.len());
+        record.extend_from_slice(&key_len.to_le_bytes());
+        record.extend_from_slice(key.as_bytes());
+        record.extend_from_slice(&blob_len.to_le_bytes());
+        record.extend_from_slice(blob);
+
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&record)?;
+        self.file.flush()?;
+
+        let offset = self.end + 6 + key.len() as u64;
+        self.offsets.insert(key, (offset, blob_len));
+        self.end = offset + blob_len as u64;
+        Ok(())
+    }
+
+    fn read(&mut self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some((offset, len)) = self.offsets.get(key).copied() else {
+            return Ok(None);
+        };
+
+        let range = offset as usize..(offset + len as u64) as usize;
+        if self.map.as_ref().map_or(true, |map| map.len() < range.end) {
+            self.remap();
+        }
+        if let Some(blob) = self.map.as_ref().and_then(|map| map.get(range)) {
+            return Ok(Some(blob.to_vec()));
+        }
+
+        let mut blob = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut blob)?;
+        Ok(Some(blob))
+    }
+
+    fn compact(&mut self) -> io::Result<()> {
+        let temp_path = self.path.with_extension("chunks.tmp");
+        let mut compacted = Self {
+            path: temp_path.clone(),
+            file: File::create(&temp_path)?,
+            map: None,
+            offsets: HashMap::default(),
+            end: Self::MAGIC.len() as u64,
+        };
+        compacted.file.write_all(Self::MAGIC)?;
+
+        let keys = self.offsets.keys().cloned().collect::<Vec<_>>();
+        for key in keys {
+            if let Some(blob) = self.read(&key)? {
+                compacted.write(key, &blob)?;
+            }
+        }
+        compacted.file.sync_all()?;
+        drop(compacted);
+
+        // Some platforms can't replace a mapped file.
+        self.map = None;
+        std::fs::rename(&temp_path, &self.path)?;
+        *self = Self::open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Saves chunks into the `localStorage` of the browser, using the path as the key.
+///
+/// This is the default backend on wasm32 when `wasm-storage` feature is enabled.
+///
+/// **Notice**: Browsers usually limit `localStorage` to around 5MB per site,
+/// so keep `chunk_size` small or use a custom backend for huge worlds.
+#[cfg(all(target_arch = "wasm32", feature = "wasm-storage"))]
+#[derive(Debug, Clone)]
+pub struct LocalStorageChunkBackend {
+    pub prefix: String,
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-storage"))]
+impl Default for LocalStorageChunkBackend {
+    fn default() -> Self {
+        Self {
+            prefix: "bevy_entitiles/".to_string(),
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-storage"))]
+impl LocalStorageChunkBackend {
+    fn storage(&self) -> io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| io::Error::other("localStorage is not available"))
+    }
+
+    fn key(&self, path: &Path) -> String {
+        format!("{}{}", self.prefix, path.to_string_lossy())
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-storage"))]
+impl ChunkIoBackend for LocalStorageChunkBackend {
+    fn write(&self, path: &Path, data: String) -> io::Result<()> {
+        self.storage()?
+            .set_item(&self.key(path), &data)
+            .map_err(|err| io::Error::other(format!("{:?}", err)))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Option<String>> {
+        self.storage()?
+            .get_item(&self.key(path))
+            .map_err(|err| io::Error::other(format!("{:?}", err)))
+    }
+}
+
+/// The backend used to save and load chunks.
+/// Insert this resource to replace the default one.
+#[derive(Resource, Clone)]
+pub struct ChunkIo(pub Arc<dyn ChunkIoBackend>);
+
+impl Default for ChunkIo {
+    fn default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        return Self::new(FileSystemChunkBackend);
+
+        #[cfg(all(target_arch = "wasm32", feature = "wasm-storage"))]
+        return Self::new(LocalStorageChunkBackend::default());
+
+        #[cfg(all(target_arch = "wasm32", not(feature = "wasm-storage")))]
+        return Self::new(MemoryChunkBackend::default());
+    }
+}
+
+impl ChunkIo {
+    pub fn new(backend: impl ChunkIoBackend) -> Self {
+        Self(Arc::new(backend))
+    }
+
+    /// Serialize and write the object along with the format version. Errors are logged.
+    pub fn save<T: Serialize>(&self, path: &Path, object: &T) {
+        let data = match ron::to_string(&Versioned::new(object)) {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Failed to serialize chunk {:?}: {}", path, err);
+                return;
+            }
+        };
+
+        if let Err(err) = self.0.write(path, data) {
+            error!("Failed to save chunk {:?}: {}", path, err);
+        }
+    }
+
+    /// Read, upgrade and deserialize the object. Returns `None` if the chunk doesn't exist
+    /// or fails to load, the latter is also logged.
+    pub fn load<T: DeserializeOwned>(&self, path: &Path, migrations: &SaveMigrations) -> Option<T> {
+        let data = match self.0.read(path) {
+            Ok(data) => data?,
+            Err(err) => {
+                error!("Failed to load chunk {:?}: {}", path, err);
+                return None;
+            }
+        };
+
+        migrations
+            .load::<Versioned<T>>(SaveFormat::Chunk, &data)
+            .map(|versioned| versioned.data)
+            .map_err(|err| error!("Failed to deserialize chunk {:?}: {}", path, err))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, sync::Arc};
+
+    use bevy::math::IVec2;
+
+    use crate::{
+        serializing::version::SaveMigrations,
+        tilemap::{buffers::TileBuilderBuffer, tile::TileBuilder},
+    };
+
+    use super::{ChunkIo, MemoryChunkBackend, PackedChunkBackend};
+
+    #[test]
+    fn test_memory_backend() {
+        let io = ChunkIo::new(MemoryChunkBackend::default());
+        let migrations = SaveMigrations::default();
+        let path = Path::new("saves").join("map").join("0_0.ron");
+
+        assert!(io.load::<TileBuilderBuffer>(&path, &migrations).is_none());
+
+        let mut buffer = TileBuilderBuffer::new();
+        buffer.set(IVec2::new(3, 4), TileBuilder::new());
+        io.save(&path, &buffer);
+
+        let loaded = io.load::<TileBuilderBuffer>(&path, &migrations).unwrap();
+        assert!(loaded.get(IVec2::new(3, 4)).is_some());
+        assert!(loaded.get(IVec2::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_packed_backend() {
+        let root = std::env::temp_dir().join(format!("entitiles_packed_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let root_str = root.to_string_lossy().to_string();
+        let migrations = SaveMigrations::default();
+        let chunk = |layer: &str, name: &str| root.join("map").join(layer).join(name);
+        let buffer = |index: IVec2| {
+            let mut buffer = TileBuilderBuffer::new();
+            buffer.set(index, TileBuilder::new());
+            buffer
+        };
+
+        let backend = Arc::new(PackedChunkBackend::default());
+        let io = ChunkIo(backend.clone());
+        assert!(io
+            .load::<TileBuilderBuffer>(&chunk("tile_chunks", "0_0.ron"), &migrations)
+            .is_none());
+        assert!(!root.join("map.chunks").exists());
+
+        io.save(&chunk("tile_chunks", "0_0.ron"), &buffer(IVec2::ZERO));
+        io.save(&chunk("path_tile_chunks", "0_0.ron"), &buffer(IVec2::ONE));
+        io.save(&chunk("tile_chunks", "0_0.ron"), &buffer(IVec2::X));
+        assert!(root.join("map.chunks").is_file());
+        // Blobs appended after mapping are readable.
+        assert!(io
+            .load::<TileBuilderBuffer>(&chunk("tile_chunks", "0_0.ron"), &migrations)
+            .unwrap()
+            .get(IVec2::X)
+            .is_some());
+        assert!(!root.join("map").exists());
+
+        let size = std::fs::metadata(root.join("map.chunks")).unwrap().len();
+        backend.compact(&root_str, "map").unwrap();
+        assert!(std::fs::metadata(root.join("map.chunks")).unwrap().len() < size);
+
+        // Saved into the compacted file.
+        io.save(&chunk("tile_chunks", "1_0.ron"), &buffer(IVec2::Y));
+        // Other backends share the same file.
+        PackedChunkBackend::default()
+            .compact(&root_str, "map")
+            .unwrap();
+        io.save(&chunk("tile_chunks", "2_0.ron"), &buffer(IVec2::Y));
+        drop((io, backend));
+
+        // Reopen to rebuild the offset table from the file.
+        let io = ChunkIo::new(PackedChunkBackend::default());
+        for name in ["1_0.ron", "2_0.ron"] {
+            assert!(io
+                .load::<TileBuilderBuffer>(&chunk("tile_chunks", name), &migrations)
+                .unwrap()
+                .get(IVec2::Y)
+                .is_some());
+        }
+        let loaded = io
+            .load::<TileBuilderBuffer>(&chunk("tile_chunks", "0_0.ron"), &migrations)
+            .unwrap();
+        assert!(loaded.get(IVec2::X).is_some());
+        assert!(loaded.get(IVec2::ZERO).is_none());
+        assert!(io
+            .load::<TileBuilderBuffer>(&chunk("path_tile_chunks", "0_0.ron"), &migrations)
+            .unwrap()
+            .get(IVec2::ONE)
+            .is_some());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -14,7 +14,11 @@ use bevy::{
 
 use crate::{
     math::ext::ChunkIndex,
-    serializing::{chunk::TILE_CHUNKS_FOLDER, load_object, map::TilemapLayer},
+    serializing::{
+        chunk::{backend::ChunkIo, TILE_CHUNKS_FOLDER},
+        map::TilemapLayer,
+        version::SaveMigrations,
+    },
     tilemap::{
         buffers::TileBuilderBuffer,
         map::{TilemapName, TilemapStorage},
@@ -103,6 +107,8 @@ pub fn load_color_layer(
     >,
     config: Res<ChunkLoadConfig>,
     mut cache: ResMut<ChunkLoadCache>,
+    io: Res<ChunkIo>,
+    migrations: Res<SaveMigrations>,
 ) {
     tilemaps_query
         .iter_mut()
@@ -118,11 +124,12 @@ pub fn load_color_layer(
                     return;
                 };
 
-                let Ok(chunk) = load_object::<TileBuilderBuffer>(
+                let Some(chunk) = io.load::<TileBuilderBuffer>(
                     &Path::new(&config.path)
                         .join(&name.0)
-                        .join(TILE_CHUNKS_FOLDER),
-                    format!("{}.ron", chunk_index.chunk_file_name()).as_str(),
+                        .join(TILE_CHUNKS_FOLDER)
+                        .join(format!("{}.ron", chunk_index.chunk_file_name())),
+                    &migrations,
                 ) else {
                     return;
                 };
@@ -145,6 +152,7 @@ pub fn load_color_layer(
                                 index: chunk_origin + in_chunk_index,
                                 texture: tile.texture,
                                 tint: tile.tint,
+                                emissive: tile.emissive,
                             },
                         ));
                         entities[in_chunk_index_vec] = Some(e);
@@ -162,7 +170,9 @@ pub fn load_path_layer(
     tilemaps_query: Query<(Entity, &TilemapName), With<ScheduledLoadChunks>>,
     config: Res<ChunkLoadConfig>,
     mut cache: ResMut<ChunkLoadCache>,
-    path_tilemaps: Res<PathTilemaps>,
+    io: Res<ChunkIo>,
+    migrations: Res<SaveMigrations>,
+    mut path_tilemaps: ResMut<PathTilemaps>,
 ) {
     tilemaps_query.iter().for_each(|(entity, name)| {
         (0..config.chunks_per_frame).into_iter().for_each(|_| {
@@ -175,32 +185,28 @@ pub fn load_path_layer(
                 return;
             };
 
-            #[cfg(feature = "multi-threaded")]
-            let Some(path_tilemap) = path_tilemaps.lock(entity) else {
-                bevy::log::error!("PathTilemap not found for entity: {:?}, skipping.", entity);
-                return;
-            };
-            #[cfg(not(feature = "multi-threaded"))]
-            let Some(path_tilemap) = path_tilemaps.get(entity) else {
-                bevy::log::error!("PathTilemap not found for entity: {:?}, skipping.", entity);
-                return;
-            };
-
-            let Ok(chunk) = load_object::<PathTileBuffer>(
+            let Some(chunk) = io.load::<PathTileBuffer>(
                 &Path::new(&config.path)
                     .join(&name.0)
-                    .join(PATH_TILE_CHUNKS_FOLDER),
-                format!("{}.ron", chunk_index.chunk_file_name()).as_str(),
+                    .join(PATH_TILE_CHUNKS_FOLDER)
+                    .join(format!("{}.ron", chunk_index.chunk_file_name())),
+                &migrations,
             ) else {
                 return;
             };
 
-            let chunk_size = path_tilemap.storage.chunk_size as i32;
-            let mut c = vec![None; (chunk_size * chunk_size) as usize];
-            chunk.tiles.into_iter().for_each(|(in_chunk_index, tile)| {
-                c[(in_chunk_index.y * chunk_size + in_chunk_index.x) as usize] = Some(tile);
+            let loaded = path_tilemaps.with_mut(entity, |path_tilemap| {
+                let chunk_size = path_tilemap.storage.chunk_size as i32;
+                let mut c = vec![None; (chunk_size * chunk_size) as usize];
+                chunk.tiles.into_iter().for_each(|(in_chunk_index, tile)| {
+                    c[(in_chunk_index.y * chunk_size + in_chunk_index.x) as usize] = Some(tile);
+                });
+                path_tilemap.storage.set_chunk(chunk_index, c);
             });
-            path_tilemap.storage.get_chunk(chunk_index).replace(&c);
+
+            if loaded.is_none() {
+                bevy::log::error!("PathTilemap not found for entity: {:?}, skipping.", entity);
+            }
         });
     });
 }
@@ -214,6 +220,8 @@ pub fn load_physics_layer(
     >,
     config: Res<ChunkLoadConfig>,
     mut cache: ResMut<ChunkLoadCache>,
+    io: Res<ChunkIo>,
+    migrations: Res<SaveMigrations>,
 ) {
     tilemaps_query
         .iter_mut()
@@ -229,11 +237,12 @@ pub fn load_physics_layer(
                     return;
                 };
 
-                let Ok(chunk) = load_object::<PackedPhysicsTileBuffer>(
+                let Some(chunk) = io.load::<PackedPhysicsTileBuffer>(
                     &Path::new(&config.path)
                         .join(&name.0)
-                        .join(PHYSICS_TILE_CHUNKS_FOLDER),
-                    format!("{}.ron", chunk_index.chunk_file_name()).as_str(),
+                        .join(PHYSICS_TILE_CHUNKS_FOLDER)
+                        .join(format!("{}.ron", chunk_index.chunk_file_name())),
+                    &migrations,
                 ) else {
                     return;
                 };
@@ -247,9 +256,20 @@ pub fn load_physics_layer(
 
                 let mut new_chunk = vec![None; (chunk_size * chunk_size) as usize];
                 chunk.tiles.into_iter().for_each(|(in_chunk_index, tile)| {
+                    let index = chunk_index * chunk_size + in_chunk_index;
+                    let collider = tile.spawn_on_tilemap(&mut commands, entity, index);
                     new_chunk[(in_chunk_index.y * chunk_size + in_chunk_index.x) as usize] =
-                        Some(tile.spawn(&mut commands));
+                        Some(collider);
+                    physics_tilemap.colliders.insert(collider, index);
                 });
+                if let Some(old) = physics_tilemap.storage.remove_chunk(chunk_index) {
+                    old.into_iter().flatten().for_each(|collider| {
+                        physics_tilemap.colliders.remove(&collider);
+                        if let Some(mut collider) = commands.get_entity(collider) {
+                            collider.despawn();
+                        }
+                    });
+                }
                 physics_tilemap.storage.set_chunk(chunk_index, new_chunk);
             });
         });
@@ -2,17 +2,28 @@ use bevy::{
     app::{App, Plugin, Update},
     ecs::{
         entity::Entity,
+        observer::Trigger,
         query::With,
-        system::{ParallelCommands, Query, Res},
+        schedule::IntoSystemConfigs,
+        system::{ParallelCommands, Query, Res, ResMut},
     },
 };
 
-use crate::serializing::chunk::{
-    load::{ChunkLoadCache, ChunkLoadConfig, ScheduledLoadChunks},
-    save::{ChunkSaveCache, ChunkSaveConfig, ScheduledSaveChunks},
+use crate::{
+    serializing::{
+        chunk::{
+            backend::ChunkIo,
+            load::{ChunkLoadCache, ChunkLoadConfig, ScheduledLoadChunks},
+            save::{ChunkSaveCache, ChunkSaveConfig, ScheduledSaveChunks},
+        },
+        version::SaveMigrations,
+    },
+    tilemap::despawn::EverythingUnloaded,
 };
 
+pub mod backend;
 pub mod load;
+pub mod persistence;
 pub mod save;
 
 pub const TILE_CHUNKS_FOLDER: &str = "tile_chunks";
@@ -37,6 +48,9 @@ impl Plugin for EntiTilesChunkSerializingPlugin {
                 load::load_path_layer,
                 #[cfg(feature = "physics")]
                 load::load_physics_layer,
+                persistence::auto_chunk_persistence
+                    .after(load::load_color_layer)
+                    .before(save::save_color_layer),
                 chunk_tag_remover,
             ),
         );
@@ -44,13 +58,26 @@ impl Plugin for EntiTilesChunkSerializingPlugin {
         app.register_type::<ChunkSaveConfig>()
             .register_type::<ChunkLoadConfig>();
 
-        app.init_resource::<ChunkLoadCache>()
+        app.init_resource::<ChunkIo>()
+            .init_resource::<SaveMigrations>()
+            .init_resource::<ChunkLoadCache>()
             .init_resource::<ChunkLoadConfig>()
             .init_resource::<ChunkSaveCache>()
-            .init_resource::<ChunkSaveConfig>();
+            .init_resource::<ChunkSaveConfig>()
+            .observe(chunk_caches_unloader)
+            .observe(persistence::auto_chunk_persistence_unloader);
     }
 }
 
+fn chunk_caches_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut save_cache: ResMut<ChunkSaveCache>,
+    mut load_cache: ResMut<ChunkLoadCache>,
+) {
+    save_cache.0.clear();
+    load_cache.0.clear();
+}
+
 fn chunk_tag_remover(
     commands: ParallelCommands,
     saves_query: Query<Entity, With<ScheduledSaveChunks>>,
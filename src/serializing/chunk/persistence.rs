@@ -0,0 +1,351 @@
+//! Save chunks automatically when they leave the cameras, and load them back when they enter.
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::{Entity, EntityHashMap},
+        event::{EventReader, EventWriter},
+        observer::Trigger,
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Commands, Local, Query, ResMut},
+    },
+    math::IVec2,
+    utils::HashSet,
+};
+
+use crate::{
+    render::chunk::ChunkUnload,
+    serializing::{
+        chunk::{load::ChunkLoadCache, save::ChunkSaveCache},
+        map::TilemapLayer,
+    },
+    tilemap::{
+        chunking::camera::CameraChunkUpdation, despawn::EverythingUnloaded, map::TilemapStorage,
+        tile::Tile,
+    },
+};
+
+#[cfg(feature = "algorithm")]
+use crate::algorithm::pathfinding::PathTilemaps;
+
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::PhysicsTilemap;
+
+/// Add this to a tilemap to save the chunks that leave the cameras, and load them
+/// back when they enter again, according to the [`CameraChunkUpdation`]s.
+///
+/// Chunks that are not modified since they are loaded are unloaded without being saved.
+/// Tiles that are set, updated or removed are tracked automatically, call
+/// [`AutoChunkPersistence::mark_dirty`] for other modifications, like path or physics tiles.
+///
+/// **Notice**: Chunks are saved to [`ChunkSaveConfig::path`](crate::serializing::chunk::save::ChunkSaveConfig)
+/// and loaded from [`ChunkLoadConfig::path`](crate::serializing::chunk::load::ChunkLoadConfig),
+/// so they should be the same.
+#[derive(Component, Debug, Clone)]
+pub struct AutoChunkPersistence {
+    pub layers: TilemapLayer,
+    pub(crate) dirty: HashSet<IVec2>,
+    /// Chunks that are scheduled to load, the tiles spawned in them are not modifications.
+    pub(crate) loading: HashSet<IVec2>,
+}
+
+impl AutoChunkPersistence {
+    pub fn new(layers: TilemapLayer) -> Self {
+        Self {
+            layers,
+            dirty: HashSet::default(),
+            loading: HashSet::default(),
+        }
+    }
+
+    /// Returns `true` if the chunk will be saved when it leaves the cameras.
+    #[inline]
+    pub fn is_dirty(&self, chunk_index: IVec2) -> bool {
+        self.dirty.contains(&chunk_index)
+    }
+
+    /// Save the chunk when it leaves the cameras, even if no tile is modified.
+    #[inline]
+    pub fn mark_dirty(&mut self, chunk_index: IVec2) {
+        self.dirty.insert(chunk_index);
+    }
+}
+
+/// Nothing should be saved or loaded for the tilemaps that are being unloaded.
+pub fn auto_chunk_persistence_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut tilemaps_query: Query<&mut AutoChunkPersistence>,
+) {
+    tilemaps_query.iter_mut().for_each(|mut persistence| {
+        persistence.dirty.clear();
+        persistence.loading.clear();
+    });
+}
+
+pub fn auto_chunk_persistence(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(Entity, &mut TilemapStorage, &mut AutoChunkPersistence)>,
+    changed_tiles_query: Query<(Entity, &Tile), Changed<Tile>>,
+    mut removed_tiles: RemovedComponents<Tile>,
+    mut tile_chunks: Local<EntityHashMap<(Entity, IVec2)>>,
+    mut updation_events: EventReader<CameraChunkUpdation>,
+    mut chunk_unload: EventWriter<ChunkUnload>,
+    mut save_cache: ResMut<ChunkSaveCache>,
+    mut load_cache: ResMut<ChunkLoadCache>,
+    #[cfg(feature = "algorithm")] mut path_tilemaps: ResMut<PathTilemaps>,
+    #[cfg(feature = "physics")] mut physics_tilemaps_query: Query<&mut PhysicsTilemap>,
+) {
+    // Avoid scanning the tiles when no tilemap is persisted.
+    if tilemaps_query.is_empty() {
+        removed_tiles.clear();
+        updation_events.clear();
+        tile_chunks.clear();
+        return;
+    }
+
+    for entity in removed_tiles.read() {
+        let Some((tilemap, chunk_index)) = tile_chunks.remove(&entity) else {
+            continue;
+        };
+        let Ok((_, storage, mut persistence)) = tilemaps_query.get_mut(tilemap) else {
+            continue;
+        };
+
+        // Tiles in unloaded chunks are removed along with the chunk.
+        if storage.get_chunk(chunk_index).is_some() && !persistence.loading.contains(&chunk_index) {
+            persistence.dirty.insert(chunk_index);
+        }
+    }
+
+    for (entity, tile) in &changed_tiles_query {
+        let Ok((_, _, mut persistence)) = tilemaps_query.get_mut(tile.tilemap_id) else {
+            continue;
+        };
+
+        tile_chunks.insert(entity, (tile.tilemap_id, tile.chunk_index));
+        if !persistence.loading.contains(&tile.chunk_index) {
+            persistence.dirty.insert(tile.chunk_index);
+        }
+    }
+
+    for ev in updation_events.read() {
+        match *ev {
+            CameraChunkUpdation::Entered(tilemap, chunk_index) => {
+                let Ok((_, storage, mut persistence)) = tilemaps_query.get_mut(tilemap) else {
+                    continue;
+                };
+
+                // Still waiting to be saved, so keep it instead of loading.
+                if storage.get_chunk(chunk_index).is_some() {
+                    set_remove_after_save(&mut save_cache, tilemap, chunk_index, false);
+                    continue;
+                }
+
+                persistence.loading.insert(chunk_index);
+                load_cache.schedule(&mut commands, tilemap, persistence.layers, chunk_index);
+            }
+            CameraChunkUpdation::Left(tilemap, chunk_index) => {
+                let Ok((_, mut storage, mut persistence)) = tilemaps_query.get_mut(tilemap) else {
+                    continue;
+                };
+
+                if persistence.loading.remove(&chunk_index) {
+                    if let Some(layers) = load_cache.0.get_mut(&tilemap) {
+                        layers
+                            .values_mut()
+                            .for_each(|chunks| chunks.retain(|c| *c != chunk_index));
+                    }
+                }
+
+                if persistence.dirty.remove(&chunk_index) {
+                    save_cache.schedule(
+                        &mut commands,
+                        tilemap,
+                        persistence.layers,
+                        chunk_index,
+                        true,
+                    );
+                    continue;
+                }
+
+                if set_remove_after_save(&mut save_cache, tilemap, chunk_index, true) {
+                    continue;
+                }
+
+                // Nothing is modified, so just unload it.
+                if persistence.layers.contains(TilemapLayer::COLOR) {
+                    storage.remove_chunk(&mut commands, chunk_index);
+                    chunk_unload.send(ChunkUnload {
+                        tilemap,
+                        index: chunk_index,
+                    });
+                }
+
+                #[cfg(feature = "algorithm")]
+                if persistence.layers.contains(TilemapLayer::PATH) {
+                    path_tilemaps.with_mut(tilemap, |path_tilemap| {
+                        path_tilemap.storage.remove_chunk(chunk_index);
+                    });
+                }
+
+                #[cfg(feature = "physics")]
+                if persistence.layers.contains(TilemapLayer::PHYSICS) {
+                    if let Ok(mut physics_tilemap) = physics_tilemaps_query.get_mut(tilemap) {
+                        physics_tilemap.remove_chunk(&mut commands, chunk_index);
+                    }
+                }
+            }
+        }
+    }
+
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(entity, _, mut persistence)| {
+            if persistence.loading.is_empty() {
+                return;
+            }
+
+            let queued = load_cache.0.get(&entity);
+            persistence.loading.retain(|chunk_index| {
+                queued.is_some_and(|layers| {
+                    layers.values().any(|chunks| chunks.contains(chunk_index))
+                })
+            });
+        });
+}
+
+/// Returns `true` if the chunk is waiting to be saved.
+fn set_remove_after_save(
+    cache: &mut ChunkSaveCache,
+    tilemap: Entity,
+    chunk_index: IVec2,
+    remove_after_save: bool,
+) -> bool {
+    let Some(layers) = cache.0.get_mut(&tilemap) else {
+        return false;
+    };
+
+    let mut queued = false;
+    layers
+        .values_mut()
+        .flatten()
+        .filter(|(c, _)| *c == chunk_index)
+        .for_each(|(_, remove)| {
+            *remove = remove_after_save;
+            queued = true;
+        });
+    queued
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::{App, Update},
+        ecs::{
+            event::Events,
+            system::{Commands, Query, RunSystemOnce},
+        },
+        math::IVec2,
+    };
+
+    use crate::{
+        render::chunk::ChunkUnload,
+        serializing::{
+            chunk::{load::ChunkLoadCache, save::ChunkSaveCache},
+            map::TilemapLayer,
+        },
+        tilemap::{chunking::camera::CameraChunkUpdation, map::TilemapStorage, tile::TileBuilder},
+    };
+
+    use super::{auto_chunk_persistence, AutoChunkPersistence};
+
+    #[test]
+    fn test_auto_chunk_persistence() {
+        let mut app = App::new();
+        app.add_event::<CameraChunkUpdation>()
+            .add_event::<ChunkUnload>()
+            .init_resource::<ChunkSaveCache>()
+            .init_resource::<ChunkLoadCache>()
+            .add_systems(Update, auto_chunk_persistence);
+        #[cfg(feature = "algorithm")]
+        app.init_resource::<crate::algorithm::pathfinding::PathTilemaps>();
+
+        let tilemap = app.world_mut().spawn_empty().id();
+        app.world_mut().entity_mut(tilemap).insert((
+            TilemapStorage::new(4, tilemap),
+            AutoChunkPersistence::new(TilemapLayer::COLOR),
+        ));
+
+        let set_tile = move |app: &mut App, index: IVec2| {
+            app.world_mut().run_system_once(
+                move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                    storages_query.get_mut(tilemap).unwrap().set(
+                        &mut commands,
+                        index,
+                        TileBuilder::new(),
+                    );
+                },
+            );
+        };
+        let send = |app: &mut App, ev: CameraChunkUpdation| {
+            app.world_mut().send_event(ev);
+            app.update();
+        };
+        let persistence = |app: &App| {
+            app.world()
+                .get::<AutoChunkPersistence>(tilemap)
+                .unwrap()
+                .clone()
+        };
+
+        // Tiles set by users are modifications.
+        set_tile(&mut app, IVec2::new(1, 1));
+        app.update();
+        assert!(persistence(&app).is_dirty(IVec2::ZERO));
+
+        // Tiles loaded from the disk are not.
+        send(&mut app, CameraChunkUpdation::Entered(tilemap, IVec2::X));
+        assert!(app
+            .world_mut()
+            .resource_mut::<ChunkLoadCache>()
+            .pop_chunk(tilemap, TilemapLayer::COLOR)
+            .is_some_and(|c| c == IVec2::X));
+        set_tile(&mut app, IVec2::new(5, 1));
+        app.update();
+        assert!(!persistence(&app).is_dirty(IVec2::X));
+        assert!(persistence(&app).loading.is_empty());
+
+        // Clean chunks are unloaded without saving.
+        send(&mut app, CameraChunkUpdation::Left(tilemap, IVec2::X));
+        assert!(app
+            .world()
+            .get::<TilemapStorage>(tilemap)
+            .unwrap()
+            .get_chunk(IVec2::X)
+            .is_none());
+        assert_eq!(
+            app.world_mut()
+                .resource_mut::<Events<ChunkUnload>>()
+                .drain()
+                .count(),
+            1
+        );
+
+        // Dirty chunks are saved, and kept if they enter again before saved.
+        send(&mut app, CameraChunkUpdation::Left(tilemap, IVec2::ZERO));
+        assert!(!persistence(&app).is_dirty(IVec2::ZERO));
+        send(&mut app, CameraChunkUpdation::Entered(tilemap, IVec2::ZERO));
+        let mut save_cache = app.world_mut().resource_mut::<ChunkSaveCache>();
+        assert_eq!(
+            save_cache.pop_chunk(tilemap, TilemapLayer::COLOR),
+            Some((IVec2::ZERO, false))
+        );
+        assert!(app
+            .world()
+            .resource::<ChunkLoadCache>()
+            .0
+            .get(&tilemap)
+            .is_some_and(|layers| layers.values().all(|chunks| chunks.is_empty())));
+    }
+}
@@ -16,7 +16,10 @@ use bevy::{
 use crate::{
     math::{ext::ChunkIndex, GridRect},
     render::chunk::{ChunkUnload, UnloadRenderChunk},
-    serializing::{chunk::TILE_CHUNKS_FOLDER, map::TilemapLayer, save_object},
+    serializing::{
+        chunk::{backend::ChunkIo, TILE_CHUNKS_FOLDER},
+        map::TilemapLayer,
+    },
     tilemap::{
         buffers::TileBuilderBuffer,
         map::{TilemapName, TilemapStorage},
@@ -118,6 +121,7 @@ pub fn save_color_layer(
     mut chunk_unload: EventWriter<ChunkUnload>,
     config: Res<ChunkSaveConfig>,
     mut cache: ResMut<ChunkSaveCache>,
+    io: Res<ChunkIo>,
 ) {
     tilemaps_query
         .iter_mut()
@@ -161,9 +165,10 @@ pub fn save_color_layer(
                     })
                     .collect();
 
-                save_object(
-                    &map_path.join(TILE_CHUNKS_FOLDER),
-                    format!("{}.ron", chunk_index.chunk_file_name()).as_str(),
+                io.save(
+                    &map_path
+                        .join(TILE_CHUNKS_FOLDER)
+                        .join(format!("{}.ron", chunk_index.chunk_file_name())),
                     &TileBuilderBuffer {
                         tiles,
                         aabb: GridRect::new(IVec2::ZERO, UVec2::splat(storage.storage.chunk_size)),
@@ -186,8 +191,8 @@ pub fn save_path_layer(
     mut tilemaps_query: Query<(Entity, &TilemapName), With<ScheduledSaveChunks>>,
     config: Res<ChunkSaveConfig>,
     mut cache: ResMut<ChunkSaveCache>,
-    #[cfg(feature = "multi-threaded")] path_tilemaps: Res<PathTilemaps>,
-    #[cfg(not(feature = "multi-threaded"))] mut path_tilemaps: ResMut<PathTilemaps>,
+    io: Res<ChunkIo>,
+    mut path_tilemaps: ResMut<PathTilemaps>,
 ) {
     tilemaps_query.iter_mut().for_each(|(entity, name)| {
         let map_path = Path::new(&config.path).join(&name.0);
@@ -204,51 +209,47 @@ pub fn save_path_layer(
                 return;
             };
 
-            #[cfg(feature = "multi-threaded")]
-            let Some(mut path_tilemap) = path_tilemaps.lock(entity) else {
-                bevy::log::error!("PathTilemap not found for entity: {:?}, skipping.", entity);
-                return;
-            };
-            #[cfg(not(feature = "multi-threaded"))]
-            let Some(path_tilemap) = path_tilemaps.get_mut(entity) else {
-                bevy::log::error!("PathTilemap not found for entity: {:?}, skipping.", entity);
-                return;
-            };
-
-            let Some(chunk) = path_tilemap.storage.get_chunk(chunk_index) else {
-                return;
-            };
+            let saved = path_tilemaps.with_mut(entity, |path_tilemap| {
+                let Some(chunk) = path_tilemap.storage.get_chunk(chunk_index) else {
+                    return;
+                };
 
-            let tiles = chunk
-                .iter()
-                .enumerate()
-                .filter_map(|(index, tile)| {
-                    tile.map(|t| {
-                        (
-                            IVec2 {
-                                x: (index as u32 % path_tilemap.storage.chunk_size) as i32,
-                                y: (index as u32 / path_tilemap.storage.chunk_size) as i32,
-                            },
-                            t,
-                        )
+                let tiles = chunk
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, tile)| {
+                        tile.map(|t| {
+                            (
+                                IVec2 {
+                                    x: (index as u32 % path_tilemap.storage.chunk_size) as i32,
+                                    y: (index as u32 / path_tilemap.storage.chunk_size) as i32,
+                                },
+                                t,
+                            )
+                        })
                     })
-                })
-                .collect();
+                    .collect();
 
-            save_object(
-                &map_path.join(PATH_TILE_CHUNKS_FOLDER),
-                format!("{}.ron", chunk_index.chunk_file_name()).as_str(),
-                &PathTileBuffer {
-                    tiles,
-                    aabb: GridRect::from_min_max(
-                        IVec2::ZERO,
-                        IVec2::splat(path_tilemap.storage.chunk_size as i32),
-                    ),
-                },
-            );
+                io.save(
+                    &map_path
+                        .join(PATH_TILE_CHUNKS_FOLDER)
+                        .join(format!("{}.ron", chunk_index.chunk_file_name())),
+                    &PathTileBuffer {
+                        tiles,
+                        aabb: GridRect::from_min_max(
+                            IVec2::ZERO,
+                            IVec2::splat(path_tilemap.storage.chunk_size as i32),
+                        ),
+                    },
+                );
+
+                if remove_after_save {
+                    path_tilemap.storage.remove_chunk(chunk_index);
+                }
+            });
 
-            if remove_after_save {
-                path_tilemap.storage.remove_chunk(chunk_index);
+            if saved.is_none() {
+                bevy::log::error!("PathTilemap not found for entity: {:?}, skipping.", entity);
             }
         });
     });
@@ -263,6 +264,7 @@ pub fn save_physics_layer(
     >,
     config: Res<ChunkSaveConfig>,
     mut cache: ResMut<ChunkSaveCache>,
+    io: Res<ChunkIo>,
 ) {
     tilemaps_query
         .iter_mut()
@@ -301,9 +303,10 @@ pub fn save_physics_layer(
                     })
                     .collect();
 
-                save_object(
-                    &map_path.join(PHYSICS_TILE_CHUNKS_FOLDER),
-                    format!("{}.ron", chunk_index.chunk_file_name()).as_str(),
+                io.save(
+                    &map_path
+                        .join(PHYSICS_TILE_CHUNKS_FOLDER)
+                        .join(format!("{}.ron", chunk_index.chunk_file_name())),
                     &PackedPhysicsTileBuffer {
                         tiles,
                         aabb: GridRect::from_min_max(
@@ -9,19 +9,20 @@ use bevy::{
         system::{Commands, Query, Res, ResMut},
     },
     hierarchy::DespawnRecursiveExt,
+    log::error,
 };
-use serde::de::DeserializeOwned;
 
 use crate::{
-    render::material::TilemapMaterial,
     serializing::{
-        load_object,
+        load_versioned_object,
         map::{SerializedTilemap, TilemapLayer, TILEMAP_META, TILES},
+        material::SerializableMaterial,
+        version::{upgrade_animations, SaveFormat, SaveMigrations, Versioned},
     },
     tilemap::{
         chunking::storage::{ChunkedStorage, TileBuilderChunkedStorage},
         map::{TilemapStorage, TilemapTexture, TilemapTextures},
-        tile::Tile,
+        tile::{Tile, TileTexture},
     },
 };
 
@@ -55,45 +56,80 @@ pub struct TilemapLoader {
     pub layers: TilemapLayer,
 }
 
-pub fn load<M: TilemapMaterial + DeserializeOwned>(
+pub fn load<M: SerializableMaterial>(
     mut commands: Commands,
     tilemaps_query: Query<(Entity, &TilemapLoader)>,
     asset_server: Res<AssetServer>,
     mut textures_assets: ResMut<Assets<TilemapTextures>>,
     mut material_assets: ResMut<Assets<M>>,
+    migrations: Res<SaveMigrations>,
     #[cfg(feature = "algorithm")] mut path_tilemaps: ResMut<PathTilemaps>,
 ) {
     for (entity, loader) in tilemaps_query.iter() {
         let map_path = Path::new(&loader.path).join(&loader.map_name);
 
-        let Ok(ser_tilemap) = load_object::<SerializedTilemap<M>>(&map_path, TILEMAP_META) else {
+        let Ok(mut ser_tilemap) = load_versioned_object::<SerializedTilemap<M>>(
+            &map_path,
+            TILEMAP_META,
+            SaveFormat::Tilemap,
+            &migrations,
+        ) else {
             complete(&mut commands, entity, (), false);
             continue;
         };
 
         let texture = {
             if let Some((tex, filter_mode)) = &ser_tilemap.textures {
-                Some(TilemapTextures::new(
+                let textures = TilemapTextures::new(
                     tex.iter()
-                        .map(|tex| TilemapTexture {
-                            texture: asset_server.load(&tex.path),
-                            desc: tex.desc.clone().into(),
+                        .map(|tex| {
+                            TilemapTexture::new(asset_server.load(&tex.path), tex.desc.clone())
+                                .with_extrusion(tex.extrusion)
                         })
                         .collect(),
                     (*filter_mode).into(),
-                ))
+                );
+                Some(match ser_tilemap.sampler {
+                    Some(sampler) => textures.with_sampler(sampler.into()),
+                    None => textures,
+                })
             } else {
                 None
             }
         };
 
         // texture
-        let ser_tiles = if loader.layers.contains(TilemapLayer::COLOR) {
-            Some(load_object::<TileBuilderChunkedStorage>(&map_path, TILES))
+        let mut ser_tiles = if loader.layers.contains(TilemapLayer::COLOR) {
+            Some(
+                load_versioned_object::<Versioned<TileBuilderChunkedStorage>>(
+                    &map_path,
+                    TILES,
+                    SaveFormat::TilemapLayer,
+                    &migrations,
+                )
+                .map(|v| v.data),
+            )
         } else {
             None
         };
 
+        // Animations of version 0 are upgraded along with the tiles referring to them.
+        if let (0, Some(animations)) = (ser_tilemap.version, &mut ser_tilemap.animations) {
+            let tiles = ser_tiles
+                .iter_mut()
+                .flat_map(|tiles| tiles.iter_mut())
+                .flat_map(|tiles| tiles.iter_some_mut())
+                .filter_map(|tile| match &mut tile.texture {
+                    TileTexture::Animated(anim) => Some(anim),
+                    TileTexture::Static(_) => None,
+                });
+            if let Err(err) = upgrade_animations(animations, tiles) {
+                error!("Failed to upgrade the animations of {:?}: {}", map_path, err);
+                complete(&mut commands, entity, (), false);
+                continue;
+            }
+        }
+
         let mut storage = TilemapStorage {
             tilemap: entity,
             storage: ChunkedStorage::new(ser_tilemap.chunk_size),
@@ -126,6 +162,7 @@ pub fn load<M: TilemapMaterial + DeserializeOwned>(
                                 .inverse_transform_index(chunk_index, in_chunk_index),
                             texture: tile.texture.clone(),
                             tint: tile.tint,
+                            emissive: tile.emissive,
                         },
                     ));
                 });
@@ -136,7 +173,7 @@ pub fn load<M: TilemapMaterial + DeserializeOwned>(
             let mut bundle = ser_tilemap.into_tilemap(
                 entity,
                 textures_assets.add(tex),
-                material_assets.add(ser_tilemap.material.clone()),
+                material_assets.add(M::hydrate(&ser_tilemap.material, &asset_server)),
             );
             bundle.storage = storage;
             complete(&mut commands, entity, bundle, true);
@@ -146,10 +183,22 @@ pub fn load<M: TilemapMaterial + DeserializeOwned>(
             complete(&mut commands, entity, bundle, true);
         }
 
+        #[cfg(feature = "audio")]
+        if let Some(audio_regions) = &ser_tilemap.audio_regions {
+            commands.entity(entity).insert(audio_regions.clone());
+        }
+
         // algorithm
         #[cfg(feature = "algorithm")]
         if loader.layers.contains(TilemapLayer::PATH) {
-            let Ok(path_storage) = load_object::<PathTileChunkedStorage>(&map_path, PATH_TILES)
+            let Ok(Versioned {
+                data: path_storage, ..
+            }) = load_versioned_object::<Versioned<PathTileChunkedStorage>>(
+                &map_path,
+                PATH_TILES,
+                SaveFormat::TilemapLayer,
+                &migrations,
+            )
             else {
                 complete(&mut commands, entity, (), false);
                 continue;
@@ -159,6 +208,7 @@ pub fn load<M: TilemapMaterial + DeserializeOwned>(
                 entity,
                 PathTilemap {
                     storage: path_storage,
+                    removed: None,
                 },
             );
         }
@@ -166,30 +216,25 @@ pub fn load<M: TilemapMaterial + DeserializeOwned>(
         // physics
         #[cfg(feature = "physics")]
         if loader.layers.contains(TilemapLayer::PHYSICS) {
-            let Ok(physics_tiles) =
-                load_object::<PackedPhysicsTileChunkedStorage>(&map_path, PHYSICS_TILES)
+            let Ok(Versioned {
+                data: physics_tiles,
+                ..
+            }) = load_versioned_object::<Versioned<PackedPhysicsTileChunkedStorage>>(
+                &map_path,
+                PHYSICS_TILES,
+                SaveFormat::TilemapLayer,
+                &migrations,
+            )
             else {
                 complete(&mut commands, entity, (), false);
                 continue;
             };
 
-            let mut physics_storage = ChunkedStorage::new(ser_tilemap.chunk_size);
-
-            physics_tiles
-                .chunked_iter_some()
-                .for_each(|(chunk_index, in_chunk_index, tile)| {
-                    physics_storage.set_elem_precise(
-                        chunk_index,
-                        in_chunk_index,
-                        tile.spawn(&mut commands),
-                    );
-                });
-
-            commands.entity(entity).insert(PhysicsTilemap {
-                storage: physics_storage,
-                spawn_queue: Vec::new(),
-                data: physics_tiles,
-            });
+            // Colliders are respawned as they were saved, so tiles set at runtime
+            // keep their shapes and properties.
+            commands
+                .entity(entity)
+                .insert(PhysicsTilemap::from_packed(&physics_tiles));
         }
     }
 }
@@ -4,20 +4,21 @@ use bevy::{
     app::{App, Plugin, Update},
     asset::Handle,
     ecs::entity::Entity,
-    render::render_resource::FilterMode,
+    render::render_resource::{AddressMode, FilterMode},
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    render::material::TilemapMaterial,
-    serializing::map::save::TilemapSaver,
+    serializing::{
+        map::save::TilemapSaver, material::SerializableMaterial, version::SAVE_FORMAT_VERSION,
+    },
     tilemap::{
         bundles::{MaterialTilemapBundle, StandardPureColorTilemapBundle},
         chunking::storage::ChunkedStorage,
         map::{
             TilePivot, TileRenderSize, TilemapAnimations, TilemapLayerOpacities, TilemapName,
-            TilemapSlotSize, TilemapStorage, TilemapTextureDescriptor, TilemapTextures,
-            TilemapTransform, TilemapType,
+            TilemapSampler, TilemapSlotSize, TilemapStorage, TilemapTextureDescriptor,
+            TilemapTextures, TilemapTransform, TilemapType,
         },
         tile::TileBuilder,
     },
@@ -32,26 +33,30 @@ pub mod load;
 pub mod save;
 
 #[derive(Default)]
-pub struct EntiTilesTilemapSerializingPlugin<M: TilemapMaterial + Serialize + DeserializeOwned>(
-    PhantomData<M>,
-);
+pub struct EntiTilesTilemapSerializingPlugin<M: SerializableMaterial>(PhantomData<M>);
 
-impl<M: TilemapMaterial + Serialize + DeserializeOwned> Plugin
-    for EntiTilesTilemapSerializingPlugin<M>
-{
+impl<M: SerializableMaterial> Plugin for EntiTilesTilemapSerializingPlugin<M> {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, (save::save::<M>, load::load::<M>));
     }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct SerializedTilemapData<M: TilemapMaterial> {
+#[serde(bound = "")]
+pub struct SerializedTilemapData<M: SerializableMaterial> {
+    /// The format version, see [`SaveMigrations`](crate::serializing::version::SaveMigrations).
+    #[serde(default)]
+    pub version: u32,
     pub tilemap: SerializedTilemap<M>,
     pub tiles: Vec<TileBuilder>,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct SerializedTilemap<M: TilemapMaterial> {
+#[serde(bound = "")]
+pub struct SerializedTilemap<M: SerializableMaterial> {
+    /// The format version, see [`SaveMigrations`](crate::serializing::version::SaveMigrations).
+    #[serde(default)]
+    pub version: u32,
     pub name: TilemapName,
     pub tile_render_size: TileRenderSize,
     pub slot_size: TilemapSlotSize,
@@ -59,14 +64,21 @@ pub struct SerializedTilemap<M: TilemapMaterial> {
     pub tile_pivot: TilePivot,
     pub layer_opacities: TilemapLayerOpacities,
     pub tilemap_transform: TilemapTransform,
-    pub material: M,
+    /// The material dehydrated by [`SerializableMaterial::dehydrate`].
+    pub material: M::Serialized,
     pub textures: Option<(Vec<SerializedTilemapTexture>, SerializedFilterMode)>,
+    /// The full sampler of the textures. Older saves only have the filter mode in `textures`.
+    #[serde(default)]
+    pub sampler: Option<SerializedTilemapSampler>,
     pub animations: Option<TilemapAnimations>,
     pub layers: TilemapLayer,
     pub chunk_size: u32,
+    #[cfg(feature = "audio")]
+    #[serde(default)]
+    pub audio_regions: Option<crate::tilemap::audio::TilemapAudioRegions>,
 }
 
-impl<M: TilemapMaterial> SerializedTilemap<M> {
+impl<M: SerializableMaterial> SerializedTilemap<M> {
     pub fn from_tilemap(
         name: TilemapName,
         tile_render_size: TileRenderSize,
@@ -77,17 +89,19 @@ impl<M: TilemapMaterial> SerializedTilemap<M> {
         storage: TilemapStorage,
         tilemap_transform: TilemapTransform,
         texture: Option<TilemapTextures>,
-        material: M,
+        material: M::Serialized,
         animations: Option<TilemapAnimations>,
         saver: &TilemapSaver,
     ) -> Self {
         SerializedTilemap {
+            version: SAVE_FORMAT_VERSION,
             name: name.clone(),
             ty,
             tile_render_size,
             slot_size,
             tile_pivot,
             material,
+            sampler: texture.as_ref().map(|tex| tex.sampler.into()),
             textures: texture.map(|tex| {
                 (
                     tex.textures
@@ -95,10 +109,11 @@ impl<M: TilemapMaterial> SerializedTilemap<M> {
                         .zip(saver.texture_path.as_ref().unwrap())
                         .map(|(tex, path)| SerializedTilemapTexture {
                             path: path.clone(),
-                            desc: tex.desc.clone(),
+                            desc: tex.desc.unpadded(),
+                            extrusion: tex.desc.padding.max(tex.extrusion),
                         })
                         .collect(),
-                    tex.filter_mode.into(),
+                    tex.sampler.mag_filter.into(),
                 )
             }),
             layer_opacities,
@@ -106,9 +121,20 @@ impl<M: TilemapMaterial> SerializedTilemap<M> {
             layers: saver.layers,
             animations,
             chunk_size: storage.storage.chunk_size,
+            #[cfg(feature = "audio")]
+            audio_regions: None,
         }
     }
 
+    #[cfg(feature = "audio")]
+    pub fn with_audio_regions(
+        mut self,
+        audio_regions: Option<crate::tilemap::audio::TilemapAudioRegions>,
+    ) -> Self {
+        self.audio_regions = audio_regions;
+        self
+    }
+
     pub fn into_tilemap(
         &self,
         tilemap: Entity,
@@ -157,7 +183,11 @@ impl<M: TilemapMaterial> SerializedTilemap<M> {
 #[derive(Serialize, Deserialize)]
 pub struct SerializedTilemapTexture {
     pub path: String,
+    /// The descriptor of the original image.
     pub desc: TilemapTextureDescriptor,
+    /// The padding to extrude after loading.
+    #[serde(default)]
+    pub extrusion: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -184,6 +214,72 @@ impl Into<FilterMode> for SerializedFilterMode {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum SerializedAddressMode {
+    ClampToEdge = 0,
+    Repeat = 1,
+    MirrorRepeat = 2,
+    ClampToBorder = 3,
+}
+
+impl From<AddressMode> for SerializedAddressMode {
+    fn from(value: AddressMode) -> Self {
+        match value {
+            AddressMode::ClampToEdge => Self::ClampToEdge,
+            AddressMode::Repeat => Self::Repeat,
+            AddressMode::MirrorRepeat => Self::MirrorRepeat,
+            AddressMode::ClampToBorder => Self::ClampToBorder,
+        }
+    }
+}
+
+impl Into<AddressMode> for SerializedAddressMode {
+    fn into(self) -> AddressMode {
+        match self {
+            Self::ClampToEdge => AddressMode::ClampToEdge,
+            Self::Repeat => AddressMode::Repeat,
+            Self::MirrorRepeat => AddressMode::MirrorRepeat,
+            Self::ClampToBorder => AddressMode::ClampToBorder,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SerializedTilemapSampler {
+    pub address_mode_u: SerializedAddressMode,
+    pub address_mode_v: SerializedAddressMode,
+    pub mag_filter: SerializedFilterMode,
+    pub min_filter: SerializedFilterMode,
+    pub mipmap_filter: SerializedFilterMode,
+    pub anisotropy_clamp: u16,
+}
+
+impl From<TilemapSampler> for SerializedTilemapSampler {
+    fn from(value: TilemapSampler) -> Self {
+        Self {
+            address_mode_u: value.address_mode_u.into(),
+            address_mode_v: value.address_mode_v.into(),
+            mag_filter: value.mag_filter.into(),
+            min_filter: value.min_filter.into(),
+            mipmap_filter: value.mipmap_filter.into(),
+            anisotropy_clamp: value.anisotropy_clamp,
+        }
+    }
+}
+
+impl Into<TilemapSampler> for SerializedTilemapSampler {
+    fn into(self) -> TilemapSampler {
+        TilemapSampler {
+            address_mode_u: self.address_mode_u.into(),
+            address_mode_v: self.address_mode_v.into(),
+            mag_filter: self.mag_filter.into(),
+            min_filter: self.min_filter.into(),
+            mipmap_filter: self.mipmap_filter.into(),
+            anisotropy_clamp: self.anisotropy_clamp,
+        }
+    }
+}
+
 bitflags::bitflags! {
     #[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Clone, Copy, Debug)]
     pub struct TilemapLayer: u32 {
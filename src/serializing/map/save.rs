@@ -1,7 +1,7 @@
 use std::path::Path;
 
 use bevy::{
-    asset::{Assets, Handle},
+    asset::{AssetServer, Assets, Handle},
     ecs::{
         component::Component,
         entity::Entity,
@@ -9,12 +9,12 @@ use bevy::{
     },
     reflect::Reflect,
 };
-use serde::Serialize;
 
 use crate::{
-    render::material::TilemapMaterial,
     serializing::map::{SerializedTilemap, TilemapLayer, TILEMAP_META, TILES},
-    serializing::{pattern::TilemapPattern, save_object},
+    serializing::{
+        material::SerializableMaterial, pattern::TilemapPattern, save_object, version::Versioned,
+    },
     tilemap::{
         chunking::storage::ChunkedStorage,
         despawn::DespawnMe,
@@ -65,7 +65,7 @@ pub struct TilemapSaver {
     pub remove_after_save: bool,
 }
 
-pub fn save<M: TilemapMaterial + Serialize>(
+pub fn save<M: SerializableMaterial>(
     mut commands: Commands,
     mut tilemaps_query: Query<(
         Entity,
@@ -85,10 +85,14 @@ pub fn save<M: TilemapMaterial + Serialize>(
     tiles_query: Query<&Tile>,
     textures_assets: Res<Assets<TilemapTextures>>,
     material_assets: Res<Assets<M>>,
+    asset_server: Res<AssetServer>,
     #[cfg(feature = "algorithm")] path_tilemaps: Res<PathTilemaps>,
     #[cfg(feature = "physics")] physics_tilemaps_query: Query<
         &crate::tilemap::physics::PhysicsTilemap,
     >,
+    #[cfg(feature = "audio")] audio_regions_query: Query<
+        &crate::tilemap::audio::TilemapAudioRegions,
+    >,
 ) {
     for (
         entity,
@@ -110,7 +114,7 @@ pub fn save<M: TilemapMaterial + Serialize>(
         let map_path = map_dir.join(&name.0);
 
         if saver.mode == TilemapSaverMode::Tilemap {
-            let serialized_tilemap = SerializedTilemap::from_tilemap(
+            let serialized_tilemap = SerializedTilemap::<M>::from_tilemap(
                 name.clone(),
                 *tile_render_size,
                 *slot_size,
@@ -120,10 +124,16 @@ pub fn save<M: TilemapMaterial + Serialize>(
                 storage.clone(),
                 transform.clone(),
                 texture.and_then(|t| textures_assets.get(t)).cloned(),
-                material_assets.get(material).unwrap().clone(),
+                material_assets
+                    .get(material)
+                    .unwrap()
+                    .dehydrate(&asset_server),
                 animations.cloned(),
                 saver,
             );
+            #[cfg(feature = "audio")]
+            let serialized_tilemap = serialized_tilemap
+                .with_audio_regions(audio_regions_query.get(entity).ok().cloned());
             save_object(&map_path, TILEMAP_META, &serialized_tilemap);
         }
         let mut pattern = TilemapPattern::new(Some(name.0.clone()));
@@ -144,7 +154,9 @@ pub fn save<M: TilemapMaterial + Serialize>(
             );
 
             match saver.mode {
-                TilemapSaverMode::Tilemap => save_object(&map_path, TILES, &ser_tiles),
+                TilemapSaverMode::Tilemap => {
+                    save_object(&map_path, TILES, &Versioned::new(ser_tiles))
+                }
                 TilemapSaverMode::MapPattern => {
                     pattern.tiles.tiles = ser_tiles.into_mapper();
                     pattern.tiles.recalculate_rect();
@@ -155,36 +167,28 @@ pub fn save<M: TilemapMaterial + Serialize>(
         // algorithm
         #[cfg(feature = "algorithm")]
         if saver.layers.contains(TilemapLayer::PATH) {
-            loop {
-                #[cfg(feature = "multi-threaded")]
-                let Some(path_tilemap) = path_tilemaps.lock(entity) else {
-                    break;
-                };
-                #[cfg(not(feature = "multi-threaded"))]
-                let Some(path_tilemap) = path_tilemaps.get(entity) else {
-                    break;
-                };
-
-                match saver.mode {
-                    TilemapSaverMode::Tilemap => {
-                        save_object(&map_path, PATH_TILES, &path_tilemap.storage)
-                    }
-                    TilemapSaverMode::MapPattern => {
-                        pattern.path_tiles.tiles = path_tilemap.storage.clone().into_mapper();
-                        pattern.path_tiles.recalculate_rect();
-                    }
+            path_tilemaps.with(entity, |path_tilemap| match saver.mode {
+                TilemapSaverMode::Tilemap => save_object(
+                    &map_path,
+                    PATH_TILES,
+                    &Versioned::new(&path_tilemap.storage),
+                ),
+                TilemapSaverMode::MapPattern => {
+                    pattern.path_tiles.tiles = path_tilemap.storage.clone().into_mapper();
+                    pattern.path_tiles.recalculate_rect();
                 }
-                break;
-            }
+            });
         }
 
         #[cfg(feature = "physics")]
         if saver.layers.contains(TilemapLayer::PHYSICS) {
             if let Ok(physics_tilemap) = physics_tilemaps_query.get(entity) {
                 match saver.mode {
-                    TilemapSaverMode::Tilemap => {
-                        save_object(&map_path, PHYSICS_TILES, &physics_tilemap.data)
-                    }
+                    TilemapSaverMode::Tilemap => save_object(
+                        &map_path,
+                        PHYSICS_TILES,
+                        &Versioned::new(&physics_tilemap.data),
+                    ),
                     TilemapSaverMode::MapPattern => {
                         let mut buffer = PackedPhysicsTileBuffer::new();
                         buffer.tiles = physics_tilemap
@@ -0,0 +1,162 @@
+use bevy::asset::{Asset, AssetServer, Handle};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::render::material::TilemapMaterial;
+
+/// A material that can be saved by [`TilemapSaver`](crate::serializing::map::save::TilemapSaver)
+/// and loaded by [`TilemapLoader`](crate::serializing::map::load::TilemapLoader).
+///
+/// Every material that implements [`Serialize`] and [`DeserializeOwned`] is serializable
+/// as is. Implement this manually if your material holds handles, like textures, as
+/// handles only live in the current app. Use [`handle_to_path`] and [`path_to_handle`]
+/// to convert them into asset paths.
+///
+/// ```
+/// # use bevy::{prelude::*, render::render_resource::AsBindGroup};
+/// # use bevy_entitiles::{prelude::*, serializing::material::*};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Default, Asset, AsBindGroup, TypePath, Clone)]
+/// struct MyMaterial {
+///     #[texture(0)]
+///     #[sampler(1)]
+///     noise: Handle<Image>,
+/// }
+///
+/// impl TilemapMaterial for MyMaterial {}
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct SerializedMyMaterial {
+///     noise: Option<String>,
+/// }
+///
+/// impl SerializableMaterial for MyMaterial {
+///     type Serialized = SerializedMyMaterial;
+///
+///     fn dehydrate(&self, asset_server: &AssetServer) -> Self::Serialized {
+///         SerializedMyMaterial {
+///             noise: handle_to_path(&self.noise, asset_server),
+///         }
+///     }
+///
+///     fn hydrate(serialized: &Self::Serialized, asset_server: &AssetServer) -> Self {
+///         MyMaterial {
+///             noise: path_to_handle(serialized.noise.as_deref(), asset_server),
+///         }
+///     }
+/// }
+/// ```
+pub trait SerializableMaterial: TilemapMaterial {
+    type Serialized: Serialize + DeserializeOwned;
+
+    /// Convert the material into something that can be written to files.
+    fn dehydrate(&self, asset_server: &AssetServer) -> Self::Serialized;
+
+    /// Recreate the material from the saved data, loading the assets it references.
+    fn hydrate(serialized: &Self::Serialized, asset_server: &AssetServer) -> Self;
+}
+
+impl<M: TilemapMaterial + Serialize + DeserializeOwned> SerializableMaterial for M {
+    type Serialized = M;
+
+    fn dehydrate(&self, _asset_server: &AssetServer) -> Self::Serialized {
+        self.clone()
+    }
+
+    fn hydrate(serialized: &Self::Serialized, _asset_server: &AssetServer) -> Self {
+        serialized.clone()
+    }
+}
+
+/// Get the asset path of a handle.
+///
+/// Returns `None` if the asset is not loaded from a file, like images created at runtime.
+pub fn handle_to_path<A: Asset>(handle: &Handle<A>, asset_server: &AssetServer) -> Option<String> {
+    asset_server
+        .get_path(handle.id())
+        .map(|path| path.to_string())
+}
+
+/// Load the asset at `path`, or return the default handle if there's no path.
+pub fn path_to_handle<A: Asset>(path: Option<&str>, asset_server: &AssetServer) -> Handle<A> {
+    path.map(|path| asset_server.load(path.to_owned()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::App,
+        asset::{Asset, AssetApp, AssetPlugin, AssetServer, Handle},
+        core::TaskPoolPlugin,
+        reflect::TypePath,
+        render::{render_resource::AsBindGroup, texture::Image},
+    };
+    use serde::{Deserialize, Serialize};
+
+    use crate::render::material::{StandardTilemapMaterial, TilemapMaterial};
+
+    use super::{handle_to_path, path_to_handle, SerializableMaterial};
+
+    #[derive(Default, Asset, AsBindGroup, TypePath, Clone)]
+    struct TexturedMaterial {
+        texture: Handle<Image>,
+        lightmap: Handle<Image>,
+    }
+
+    impl TilemapMaterial for TexturedMaterial {}
+
+    #[derive(Serialize, Deserialize)]
+    struct SerializedTexturedMaterial {
+        texture: Option<String>,
+        lightmap: Option<String>,
+    }
+
+    impl SerializableMaterial for TexturedMaterial {
+        type Serialized = SerializedTexturedMaterial;
+
+        fn dehydrate(&self, asset_server: &AssetServer) -> Self::Serialized {
+            SerializedTexturedMaterial {
+                texture: handle_to_path(&self.texture, asset_server),
+                lightmap: handle_to_path(&self.lightmap, asset_server),
+            }
+        }
+
+        fn hydrate(serialized: &Self::Serialized, asset_server: &AssetServer) -> Self {
+            TexturedMaterial {
+                texture: path_to_handle(serialized.texture.as_deref(), asset_server),
+                lightmap: path_to_handle(serialized.lightmap.as_deref(), asset_server),
+            }
+        }
+    }
+
+    fn round_trip<M: SerializableMaterial>(material: &M, asset_server: &AssetServer) -> M {
+        let serialized = ron::to_string(&material.dehydrate(asset_server)).unwrap();
+        M::hydrate(&ron::from_str(&serialized).unwrap(), asset_server)
+    }
+
+    #[test]
+    fn test_material_round_trip() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .init_asset::<Image>();
+        let asset_server = app.world().resource::<AssetServer>().clone();
+
+        let material = TexturedMaterial {
+            texture: asset_server.load("textures/tiles.png"),
+            lightmap: Handle::default(),
+        };
+        let loaded = round_trip(&material, &asset_server);
+        assert_eq!(loaded.texture, material.texture);
+        assert_eq!(
+            handle_to_path(&loaded.texture, &asset_server).as_deref(),
+            Some("textures/tiles.png")
+        );
+        assert_eq!(loaded.lightmap, Handle::default());
+
+        let standard = StandardTilemapMaterial {
+            tint: bevy::color::LinearRgba::RED,
+            ..Default::default()
+        };
+        assert_eq!(round_trip(&standard, &asset_server).tint, standard.tint);
+    }
+}
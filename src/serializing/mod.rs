@@ -1,25 +1,36 @@
 use std::{fs::File, io::Write, marker::PhantomData, path::Path};
 
-use bevy::app::Plugin;
+use bevy::{app::Plugin, math::IVec2, utils::HashMap};
 use ron::error::SpannedError;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Serialize, Serializer};
 
-use crate::render::material::TilemapMaterial;
+use crate::{
+    render::material::init_material_asset,
+    serializing::{
+        material::SerializableMaterial,
+        version::{SaveFormat, SaveFormatError, SaveMigrations},
+    },
+};
 
 pub mod chunk;
 pub mod map;
+pub mod material;
 pub mod pattern;
+pub mod prefab;
+pub mod version;
 
 #[derive(Default)]
-pub struct EntiTilesSerializingPlugin<M: TilemapMaterial + Serialize + DeserializeOwned>(
-    PhantomData<M>,
-);
+pub struct EntiTilesSerializingPlugin<M: SerializableMaterial>(PhantomData<M>);
 
-impl<M: TilemapMaterial + Serialize + DeserializeOwned> Plugin for EntiTilesSerializingPlugin<M> {
+impl<M: SerializableMaterial> Plugin for EntiTilesSerializingPlugin<M> {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_plugins((
+        init_material_asset::<M>(app);
+
+        app.init_resource::<SaveMigrations>().add_plugins((
             chunk::EntiTilesChunkSerializingPlugin,
             map::EntiTilesTilemapSerializingPlugin::<M>::default(),
+            pattern::EntiTilesPatternSerializingPlugin,
+            prefab::EntiTilesPrefabPlugin,
         ));
     }
 }
@@ -33,9 +44,50 @@ pub fn save_object<T: Serialize>(path: &Path, file_name: &str, object: &T) {
         .unwrap_or_else(|err| panic!("{:?}", err));
 }
 
-pub fn load_object<T: for<'a> Deserialize<'a>>(
+/// Load the object without upgrading it, so it fails if the format changed.
+#[deprecated(
+    since = "0.12.0",
+    note = "Use `load_versioned_object` to upgrade old files"
+)]
+pub fn load_object<T: DeserializeOwned>(path: &Path, file_name: &str) -> Result<T, SpannedError> {
+    ron::from_str(std::fs::read_to_string(path.join(file_name))?.as_str())
+}
+
+/// Load the object and upgrade it to the current format version if it's outdated.
+pub fn load_versioned_object<T: DeserializeOwned>(
     path: &Path,
     file_name: &str,
-) -> Result<T, SpannedError> {
-    ron::from_str(std::fs::read_to_string(path.join(file_name))?.as_str())
+    format: SaveFormat,
+    migrations: &SaveMigrations,
+) -> Result<T, SaveFormatError> {
+    migrations.load(
+        format,
+        std::fs::read_to_string(path.join(file_name))?.as_str(),
+    )
+}
+
+/// Serialize a map with tile indices as keys sorted by their `y` and then `x`,
+/// so identical maps always produce identical output.
+///
+/// Use this with `#[serde(serialize_with = "...")]`.
+pub fn serialize_grid_map<V: Serialize, S: Serializer>(
+    map: &HashMap<IVec2, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(index, _)| (index.y, index.x));
+    serializer.collect_map(entries)
+}
+
+/// Serialize a map in the order of its keys,
+/// so identical maps always produce identical output.
+///
+/// Use this with `#[serde(serialize_with = "...")]`.
+pub fn serialize_sorted_map<K: Ord + Serialize, V: Serialize, S: Serializer>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    serializer.collect_map(entries)
 }
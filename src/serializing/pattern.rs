@@ -1,23 +1,53 @@
 use crate::{
+    math::GridRect,
     prelude::TilemapAnimations,
-    tilemap::{buffers::TileBuffer, map::TilemapTexture},
+    serializing::version::{SaveFormat, SaveFormatError, SaveMigrations, SAVE_FORMAT_VERSION},
+    tilemap::{
+        buffers::TileBuffer,
+        map::{TilemapStorage, TilemapTexture},
+        tile::Tile,
+    },
+};
+use bevy::{
+    app::{App, Plugin},
+    asset::{io::Reader, Asset, AssetApp, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::{
+        system::Query,
+        world::{FromWorld, World},
+    },
+    math::{IVec2, UVec2},
+    reflect::Reflect,
 };
-use bevy::{math::UVec2, reflect::Reflect};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::tilemap::buffers::TileBuilderBuffer;
 
 #[cfg(feature = "algorithm")]
-use crate::tilemap::buffers::PathTileBuffer;
+use crate::tilemap::{algorithm::path::PathTilemap, buffers::PathTileBuffer};
 
 #[cfg(feature = "physics")]
-use crate::tilemap::physics::SerializablePhysicsSource;
+use crate::tilemap::physics::{PhysicsTilemap, SerializablePhysicsSource};
+
+pub struct EntiTilesPatternSerializingPlugin;
+
+impl Plugin for EntiTilesPatternSerializingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveMigrations>()
+            .init_asset::<TilemapPattern>()
+            .init_asset_loader::<TilemapPatternLoader>();
+    }
+}
 
 /// A pattern of tiles.
 ///
 /// This includes the tiles, animations, and other data.
-#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
+/// It can also be loaded as an asset from `.pattern.ron` files.
+#[derive(Asset, Serialize, Deserialize, Debug, Clone, Reflect)]
 pub struct TilemapPattern {
+    /// The format version, see [`SaveMigrations`].
+    #[serde(default)]
+    pub version: u32,
     pub label: Option<String>,
     pub tiles: TileBuilderBuffer,
     pub animations: TilemapAnimations,
@@ -30,6 +60,7 @@ pub struct TilemapPattern {
 impl TilemapPattern {
     pub fn new(label: Option<String>) -> Self {
         TilemapPattern {
+            version: SAVE_FORMAT_VERSION,
             label,
             tiles: TileBuffer::new(),
             animations: TilemapAnimations::default(),
@@ -39,6 +70,117 @@ impl TilemapPattern {
             physics_tiles: SerializablePhysicsSource::Buffer(TileBuffer::new()),
         }
     }
+
+    /// Capture the tiles in `area` into a pattern, which is the inverse of
+    /// `TilemapStorage::fill_with_buffer`. Indices in the pattern are relative to `area.origin`.
+    ///
+    /// The pattern keeps the size of `area`, even if the tiles on its edges are empty.
+    ///
+    /// **Notice**: Animated tiles refer to the animations of the tilemap,
+    /// so remember to copy `TilemapAnimations` into `animations` if there are any.
+    pub fn from_storage(
+        storage: &TilemapStorage,
+        tiles_query: &Query<&Tile>,
+        area: GridRect,
+    ) -> Self {
+        let mut pattern = Self::new(None);
+        for index in Self::indices(area) {
+            if let Some(tile) = storage.get(index).and_then(|e| tiles_query.get(e).ok()) {
+                pattern.tiles.set(index - area.origin, tile.clone().into());
+            }
+        }
+        pattern.tiles.aabb = Self::local_area(area);
+        pattern
+    }
+
+    /// Capture the path tiles in `area`. Indices are relative to `area.origin`.
+    #[cfg(feature = "algorithm")]
+    pub fn with_path_tiles(mut self, path_tilemap: &PathTilemap, area: GridRect) -> Self {
+        for index in Self::indices(area) {
+            if let Some(tile) = path_tilemap.get(index) {
+                self.path_tiles.set(index - area.origin, *tile);
+            }
+        }
+        self.path_tiles.aabb = Self::local_area(area);
+        self
+    }
+
+    /// Capture the physics tiles in `area`. Indices are relative to `area.origin`.
+    #[cfg(feature = "physics")]
+    pub fn with_physics_tiles(mut self, physics_tilemap: &PhysicsTilemap, area: GridRect) -> Self {
+        let mut buffer = crate::tilemap::buffers::PackedPhysicsTileBuffer::new();
+        for index in Self::indices(area) {
+            if let Some(tile) = physics_tilemap.data.get_elem(index) {
+                let mut tile = tile.clone();
+                tile.parent -= area.origin;
+                buffer.set(index - area.origin, tile);
+            }
+        }
+        buffer.aabb = Self::local_area(area);
+        self.physics_tiles = SerializablePhysicsSource::Buffer(buffer);
+        self
+    }
+
+    fn local_area(area: GridRect) -> GridRect {
+        GridRect::new(IVec2::ZERO, area.extent)
+    }
+
+    fn indices(area: GridRect) -> impl Iterator<Item = IVec2> {
+        (area.origin.y..=area.dest.y)
+            .flat_map(move |y| (area.origin.x..=area.dest.x).map(move |x| IVec2::new(x, y)))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TilemapPatternLoaderError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Utf8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("Format error: {0}")]
+    Format(#[from] SaveFormatError),
+}
+
+/// Loads patterns saved as ron, with the extension `.pattern.ron`.
+///
+/// Patterns of older versions are upgraded using [`SaveMigrations`].
+pub struct TilemapPatternLoader {
+    migrations: SaveMigrations,
+}
+
+impl FromWorld for TilemapPatternLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            migrations: world
+                .get_resource_or_insert_with(SaveMigrations::default)
+                .clone(),
+        }
+    }
+}
+
+impl AssetLoader for TilemapPatternLoader {
+    type Asset = TilemapPattern;
+
+    type Settings = ();
+
+    type Error = TilemapPatternLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await?;
+        self.migrations
+            .load(SaveFormat::Pattern, std::str::from_utf8(&buf)?)
+            .map_err(Into::into)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pattern.ron"]
+    }
 }
 
 /// A layer of patterns. This can be used when performing wfc.
@@ -137,3 +279,64 @@ impl PackedPatternLayers {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            system::{Commands, Query, RunSystemOnce},
+            world::World,
+        },
+        math::{IVec2, UVec2},
+    };
+
+    use crate::{
+        math::GridRect,
+        tilemap::{
+            map::TilemapStorage,
+            tile::{Tile, TileBuilder, TileLayer, TileTexture},
+        },
+    };
+
+    use super::TilemapPattern;
+
+    #[test]
+    fn test_pattern_from_storage() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(4, tilemap));
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                let mut storage = storages_query.get_mut(tilemap).unwrap();
+                for x in 0..8 {
+                    let tile = TileBuilder::new().with_layer(0, TileLayer::no_flip(x));
+                    storage.set(&mut commands, IVec2::new(x, 2), tile);
+                }
+            },
+        );
+
+        let pattern = world.run_system_once(
+            move |tiles_query: Query<&Tile>, storages_query: Query<&TilemapStorage>| {
+                TilemapPattern::from_storage(
+                    storages_query.get(tilemap).unwrap(),
+                    &tiles_query,
+                    GridRect::new(IVec2::new(3, 1), UVec2::new(3, 3)),
+                )
+            },
+        );
+
+        assert_eq!(pattern.tiles.tiles.len(), 3);
+        let aabb = pattern.tiles.aabb();
+        assert_eq!(
+            (aabb.origin, aabb.extent),
+            (IVec2::ZERO, UVec2::new(3, 3))
+        );
+        assert!(matches!(
+            &pattern.tiles.get(IVec2::new(1, 1)).unwrap().texture,
+            TileTexture::Static(layers) if layers[0].atlas_index == 4
+        ));
+    }
+}
@@ -0,0 +1,405 @@
+//! Hand-authored map fragments that can be spawned into existing tilemaps,
+//! like rooms, traps and set pieces in procedurally generated worlds.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{io::Reader, Asset, AssetApp, AssetLoader, Assets, AsyncReadExt, Handle, LoadContext},
+    ecs::{
+        entity::Entity,
+        event::{Event, EventWriter},
+        observer::Trigger,
+        system::{Commands, EntityCommands, Query, Res, ResMut, Resource},
+        world::{Command, World},
+    },
+    log::warn,
+    math::IVec2,
+    reflect::Reflect,
+    transform::components::Transform,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    serializing::pattern::TilemapPattern,
+    tilemap::{
+        coordinates,
+        despawn::EverythingUnloaded,
+        map::{
+            TilePivot, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType,
+        },
+    },
+};
+
+#[cfg(feature = "algorithm")]
+use crate::algorithm::pathfinding::PathTilemaps;
+
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::{PhysicsTilemap, SerializablePhysicsSource};
+
+pub struct EntiTilesPrefabPlugin;
+
+impl Plugin for EntiTilesPrefabPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, prefab_spawner)
+            .init_asset::<TilemapPrefab>()
+            .init_asset_loader::<TilemapPrefabLoader>()
+            .init_resource::<PrefabSpawnQueue>()
+            .init_resource::<PrefabEntityRegistry>()
+            .add_event::<PrefabSpawned>()
+            .observe(prefab_queue_unloader);
+    }
+}
+
+/// A map fragment which contains several layers of tiles and the entities on them.
+///
+/// It can also be loaded as an asset from `.prefab.ron` files.
+#[derive(Asset, Serialize, Deserialize, Debug, Clone, Reflect)]
+pub struct TilemapPrefab {
+    pub label: Option<String>,
+    pub layers: Vec<PrefabLayer>,
+    #[serde(default)]
+    pub entities: Vec<PrefabEntity>,
+}
+
+/// A layer of a prefab, which will be spawned into the tilemap with the same [`TilemapName`].
+///
+/// **Notice**: Tile animations are not spawned, as they refer to the animations of the tilemap.
+#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
+pub struct PrefabLayer {
+    pub tilemap: String,
+    pub pattern: TilemapPattern,
+}
+
+/// An entity in a prefab. It's spawned using the spawner registered
+/// by [`PrefabApp::register_prefab_entity`].
+#[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
+pub struct PrefabEntity {
+    pub identifier: String,
+    /// The index relative to the prefab origin.
+    pub index: IVec2,
+    /// The tilemap used to calculate the translation. Defaults to the first layer.
+    #[serde(default)]
+    pub tilemap: Option<String>,
+    #[serde(default, serialize_with = "crate::serializing::serialize_sorted_map")]
+    pub properties: HashMap<String, String>,
+}
+
+/// Sent when a prefab has been spawned.
+#[derive(Event, Debug, Clone)]
+pub struct PrefabSpawned {
+    pub prefab: Handle<TilemapPrefab>,
+    pub origin: IVec2,
+    pub entities: Vec<Entity>,
+}
+
+/// The function that spawns a [`PrefabEntity`]. The entity is spawned with a `Transform`
+/// already, which is the center of the slot it's placed on.
+pub type PrefabEntitySpawner = fn(&mut EntityCommands, &PrefabEntity);
+
+#[derive(Resource, Default)]
+pub struct PrefabEntityRegistry(pub(crate) HashMap<String, PrefabEntitySpawner>);
+
+pub trait PrefabApp {
+    fn register_prefab_entity(&mut self, ident: &str, spawner: PrefabEntitySpawner) -> &mut Self;
+}
+
+impl PrefabApp for App {
+    fn register_prefab_entity(&mut self, ident: &str, spawner: PrefabEntitySpawner) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(PrefabEntityRegistry::default)
+            .0
+            .insert(ident.to_string(), spawner);
+        self
+    }
+}
+
+/// Spawn a prefab with its origin at `origin`. Use [`EntiTilesCommands::spawn_prefab`]
+/// to queue this.
+///
+/// The prefab is spawned once it's loaded and all the tilemaps it needs exist.
+///
+/// [`EntiTilesCommands::spawn_prefab`]: crate::tilemap::despawn::EntiTilesCommands::spawn_prefab
+#[derive(Debug, Clone)]
+pub struct SpawnPrefab {
+    pub prefab: Handle<TilemapPrefab>,
+    pub origin: IVec2,
+    /// Overrides the tilemaps found by names.
+    pub tilemaps: HashMap<String, Entity>,
+}
+
+impl SpawnPrefab {
+    pub fn new(prefab: Handle<TilemapPrefab>, origin: IVec2) -> Self {
+        Self {
+            prefab,
+            origin,
+            tilemaps: HashMap::default(),
+        }
+    }
+
+    /// Spawn the layer for `name` into `tilemap`, instead of the tilemap with that name.
+    pub fn with_tilemap(mut self, name: &str, tilemap: Entity) -> Self {
+        self.tilemaps.insert(name.to_string(), tilemap);
+        self
+    }
+}
+
+impl Command for SpawnPrefab {
+    fn apply(self, world: &mut World) {
+        world
+            .get_resource_or_insert_with(PrefabSpawnQueue::default)
+            .0
+            .push(self);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PrefabSpawnQueue(pub(crate) Vec<SpawnPrefab>);
+
+pub fn prefab_queue_unloader(_: Trigger<EverythingUnloaded>, mut queue: ResMut<PrefabSpawnQueue>) {
+    queue.0.clear();
+}
+
+pub fn prefab_spawner(
+    mut commands: Commands,
+    mut queue: ResMut<PrefabSpawnQueue>,
+    prefabs: Res<Assets<TilemapPrefab>>,
+    registry: Res<PrefabEntityRegistry>,
+    mut tilemaps_query: Query<(
+        Entity,
+        &TilemapName,
+        &mut TilemapStorage,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+    )>,
+    #[cfg(feature = "algorithm")] mut path_tilemaps: Option<ResMut<PathTilemaps>>,
+    #[cfg(feature = "physics")] mut physics_tilemaps_query: Query<&mut PhysicsTilemap>,
+    mut spawned_event: EventWriter<PrefabSpawned>,
+) {
+    if queue.0.is_empty() {
+        return;
+    }
+
+    let names = tilemaps_query
+        .iter()
+        .map(|(entity, name, ..)| (name.0.clone(), entity))
+        .collect::<HashMap<_, _>>();
+
+    queue.0.retain(|spawn| {
+        let Some(prefab) = prefabs.get(&spawn.prefab) else {
+            return true;
+        };
+
+        let find = |name: &String| {
+            spawn
+                .tilemaps
+                .get(name)
+                .or_else(|| names.get(name))
+                .copied()
+        };
+        let Some(layer_tilemaps) = prefab
+            .layers
+            .iter()
+            .map(|layer| find(&layer.tilemap))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return true;
+        };
+
+        for (layer, tilemap) in prefab.layers.iter().zip(layer_tilemaps.iter().copied()) {
+            let Ok((_, _, mut storage, ..)) = tilemaps_query.get_mut(tilemap) else {
+                continue;
+            };
+            storage.fill_with_buffer(&mut commands, spawn.origin, layer.pattern.tiles.clone());
+
+            #[cfg(feature = "algorithm")]
+            if let Some(path_tilemaps) = path_tilemaps.as_mut() {
+                path_tilemaps.with_mut(tilemap, |t| {
+                    t.fill_with_buffer(spawn.origin, layer.pattern.path_tiles.clone())
+                });
+            }
+
+            #[cfg(feature = "physics")]
+            if let (Ok(mut physics_tilemap), SerializablePhysicsSource::Buffer(buffer)) = (
+                physics_tilemaps_query.get_mut(tilemap),
+                &layer.pattern.physics_tiles,
+            ) {
+                physics_tilemap.fill_with_buffer_packed(spawn.origin, buffer.clone());
+            }
+        }
+
+        let mut entities = Vec::with_capacity(prefab.entities.len());
+        for prefab_entity in &prefab.entities {
+            let Some(spawner) = registry.0.get(&prefab_entity.identifier) else {
+                warn!(
+                    "Prefab entity {} is not registered!",
+                    prefab_entity.identifier
+                );
+                continue;
+            };
+
+            let tilemap = match &prefab_entity.tilemap {
+                Some(name) => find(name),
+                None => layer_tilemaps.first().copied(),
+            };
+            let translation = tilemap
+                .and_then(|t| tilemaps_query.get(t).ok())
+                .map(|(_, _, _, ty, transform, pivot, slot_size)| {
+                    let index = spawn.origin + prefab_entity.index;
+                    let world =
+                        coordinates::index_to_world(index, *ty, transform, pivot.0, slot_size.0);
+                    (world + transform.transform_vector(slot_size.0 / 2.)).extend(transform.z_index)
+                })
+                .unwrap_or_default();
+
+            let mut entity = commands.spawn(Transform::from_translation(translation));
+            spawner(&mut entity, prefab_entity);
+            entities.push(entity.id());
+        }
+
+        spawned_event.send(PrefabSpawned {
+            prefab: spawn.prefab.clone(),
+            origin: spawn.origin,
+            entities,
+        });
+        false
+    });
+}
+
+#[derive(Error, Debug)]
+pub enum TilemapPrefabLoaderError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Ron error: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+/// Loads prefabs saved as ron, with the extension `.prefab.ron`.
+#[derive(Default)]
+pub struct TilemapPrefabLoader;
+
+impl AssetLoader for TilemapPrefabLoader {
+    type Asset = TilemapPrefab;
+
+    type Settings = ();
+
+    type Error = TilemapPrefabLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await?;
+        ron::de::from_bytes(&buf).map_err(Into::into)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["prefab.ron"]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::Assets,
+        ecs::{
+            event::Events,
+            system::{Commands, RunSystemOnce},
+            world::World,
+        },
+        math::{IVec2, Vec2, Vec3},
+        transform::components::Transform,
+    };
+
+    use crate::{
+        serializing::pattern::TilemapPattern,
+        tilemap::{
+            despawn::EntiTilesCommands,
+            map::{
+                TilePivot, TilemapName, TilemapSlotSize, TilemapStorage, TilemapTransform,
+                TilemapType,
+            },
+            tile::TileBuilder,
+        },
+    };
+
+    use super::{
+        prefab_spawner, PrefabEntity, PrefabEntityRegistry, PrefabLayer, PrefabSpawnQueue,
+        PrefabSpawned, TilemapPrefab,
+    };
+
+    #[test]
+    fn test_spawn_prefab() {
+        let mut world = World::new();
+        world.init_resource::<Assets<TilemapPrefab>>();
+        world.init_resource::<PrefabSpawnQueue>();
+        world.init_resource::<Events<PrefabSpawned>>();
+        world.init_resource::<PrefabEntityRegistry>();
+        world
+            .resource_mut::<PrefabEntityRegistry>()
+            .0
+            .insert("trap".to_string(), |_, _| {});
+
+        let mut pattern = TilemapPattern::new(None);
+        pattern.tiles.set(IVec2::ZERO, TileBuilder::new());
+        pattern.tiles.set(IVec2::new(1, 0), TileBuilder::new());
+        pattern.tiles.recalculate_rect();
+        let prefab = world
+            .resource_mut::<Assets<TilemapPrefab>>()
+            .add(TilemapPrefab {
+                label: None,
+                layers: vec![PrefabLayer {
+                    tilemap: "ground".to_string(),
+                    pattern,
+                }],
+                entities: vec![PrefabEntity {
+                    identifier: "trap".to_string(),
+                    index: IVec2::new(1, 0),
+                    tilemap: None,
+                    properties: Default::default(),
+                }],
+            });
+
+        world.run_system_once(move |mut commands: Commands| {
+            commands.spawn_prefab(prefab.clone(), IVec2::new(4, 2));
+        });
+        // The tilemap doesn't exist yet, so the prefab stays in the queue.
+        world.run_system_once(prefab_spawner);
+        assert_eq!(world.resource::<PrefabSpawnQueue>().0.len(), 1);
+
+        let tilemap = world.spawn_empty().id();
+        world.entity_mut(tilemap).insert((
+            TilemapName("ground".to_string()),
+            TilemapStorage::new(4, tilemap),
+            TilemapType::Square,
+            TilemapTransform::default(),
+            TilePivot::default(),
+            TilemapSlotSize(Vec2::splat(16.)),
+        ));
+        world.run_system_once(prefab_spawner);
+        assert!(world.resource::<PrefabSpawnQueue>().0.is_empty());
+
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        assert!(storage.get(IVec2::new(4, 2)).is_some());
+        assert!(storage.get(IVec2::new(5, 2)).is_some());
+        assert!(storage.get(IVec2::new(3, 2)).is_none());
+
+        let spawned = world
+            .resource_mut::<Events<PrefabSpawned>>()
+            .drain()
+            .next()
+            .unwrap();
+        assert_eq!(
+            world
+                .get::<Transform>(spawned.entities[0])
+                .unwrap()
+                .translation,
+            Vec3::new(88., 40., 0.)
+        );
+    }
+}
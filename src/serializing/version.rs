@@ -0,0 +1,412 @@
+//! Format versions of the saved files, and the migrations that upgrade old saves.
+//!
+//! Every file written by this crate carries a `version` field. When a file of an older
+//! version is loaded, the migrations registered for that format are applied one
+//! version at a time until it reaches [`SAVE_FORMAT_VERSION`].
+
+use std::sync::{Arc, RwLock};
+
+use bevy::{app::App, ecs::system::Resource, utils::HashMap};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    serializing::pattern::TilemapPattern,
+    tilemap::{
+        map::TilemapAnimations,
+        tile::{TileAnimation, TileAnimationMode, TileTexture},
+    },
+};
+
+/// The version of the files written by this version of the crate.
+///
+/// - `0`: Files saved before versioning was introduced.
+/// - `1`: Added the version. Chunks and tilemap layers are wrapped into [`Versioned`].
+///   Each sequence in `TilemapAnimations` starts with its length and mode before the fps.
+/// - `2`: Tilemaps save the sampler of their textures.
+pub const SAVE_FORMAT_VERSION: u32 = 2;
+
+/// The kinds of files that can be saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaveFormat {
+    /// `tilemap.ron` saved by `TilemapSaver`.
+    Tilemap,
+    /// `tiles.ron`, `path_tiles.ron` and `physics_tiles.ron` saved by `TilemapSaver`.
+    TilemapLayer,
+    /// Chunks saved by `ChunkSaveCache`.
+    Chunk,
+    /// Patterns saved by `TilemapSaver` or loaded as assets.
+    Pattern,
+}
+
+/// Upgrade the ron of a file by one version.
+///
+/// The input is the whole file of version `n`, and the output should be
+/// the whole file of version `n + 1`. Return an error message if it's impossible.
+pub type SaveMigration = fn(&str) -> Result<String, String>;
+
+#[derive(Error, Debug)]
+pub enum SaveFormatError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Ron error: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error(
+        "The {format:?} file is of version {version}, which is newer than the supported \
+        version {SAVE_FORMAT_VERSION}. Please update the crate"
+    )]
+    TooNew { format: SaveFormat, version: u32 },
+    #[error("No migration is registered to upgrade {format:?} files from version {version}")]
+    MissingMigration { format: SaveFormat, version: u32 },
+    #[error("Failed to upgrade the {format:?} file from version {version}: {reason}")]
+    MigrationFailed {
+        format: SaveFormat,
+        version: u32,
+        reason: String,
+    },
+}
+
+/// Wraps the saved data which is not a struct owned by this crate, like chunks,
+/// to give it a version.
+///
+/// `version` is the version the file was saved with, as migrations only upgrade the data.
+#[derive(Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            version: SAVE_FORMAT_VERSION,
+            data,
+        }
+    }
+}
+
+/// Reads only the version of a file and skips everything else.
+#[derive(Deserialize)]
+struct VersionHeader {
+    #[serde(default)]
+    version: u32,
+}
+
+/// The migrations used to upgrade old files.
+///
+/// Clones of this resource share the same migrations, so migrations registered after
+/// startup are also used by the asset loaders.
+#[derive(Resource, Clone)]
+pub struct SaveMigrations(pub(crate) Arc<RwLock<HashMap<(SaveFormat, u32), SaveMigration>>>);
+
+impl Default for SaveMigrations {
+    fn default() -> Self {
+        let migrations = Self(Default::default());
+        // The version field defaults to 0. The animations of tilemaps are stored apart from
+        // their tiles, so `TilemapLoader` upgrades them with `upgrade_animations` instead.
+        migrations.register(SaveFormat::Tilemap, 0, |ron| Ok(ron.to_string()));
+        migrations.register(SaveFormat::Pattern, 0, upgrade_pattern);
+        // Chunks and layers are wrapped into `Versioned`.
+        migrations.register(SaveFormat::TilemapLayer, 0, wrap_versioned);
+        migrations.register(SaveFormat::Chunk, 0, wrap_chunk);
+        // The sampler of version 2 is optional, so nothing needs to change.
+        for format in [
+            SaveFormat::Tilemap,
+            SaveFormat::TilemapLayer,
+            SaveFormat::Chunk,
+            SaveFormat::Pattern,
+        ] {
+            migrations.register(format, 1, |ron| Ok(ron.to_string()));
+        }
+        migrations
+    }
+}
+
+impl SaveMigrations {
+    /// Register the migration that upgrades `format` files from `from_version`
+    /// to `from_version + 1`. Replaces the existing one if any.
+    pub fn register(&self, format: SaveFormat, from_version: u32, migration: SaveMigration) {
+        self.0
+            .write()
+            .unwrap()
+            .insert((format, from_version), migration);
+    }
+
+    /// Upgrade the file to [`SAVE_FORMAT_VERSION`] and deserialize it.
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        format: SaveFormat,
+        ron: &str,
+    ) -> Result<T, SaveFormatError> {
+        ron::from_str(&self.upgrade(format, ron)?).map_err(Into::into)
+    }
+
+    /// Upgrade the file to [`SAVE_FORMAT_VERSION`].
+    pub fn upgrade(&self, format: SaveFormat, ron: &str) -> Result<String, SaveFormatError> {
+        // Files that are not even structs are left to the deserializer to complain about.
+        let mut version = ron::from_str::<VersionHeader>(ron).map_or(0, |h| h.version);
+        if version > SAVE_FORMAT_VERSION {
+            return Err(SaveFormatError::TooNew { format, version });
+        }
+
+        let migrations = self.0.read().unwrap();
+        let mut ron = ron.to_string();
+        while version < SAVE_FORMAT_VERSION {
+            let migration = migrations
+                .get(&(format, version))
+                .ok_or(SaveFormatError::MissingMigration { format, version })?;
+            ron = migration(&ron).map_err(|reason| SaveFormatError::MigrationFailed {
+                format,
+                version,
+                reason,
+            })?;
+            version += 1;
+        }
+        Ok(ron)
+    }
+}
+
+fn wrap_versioned(ron: &str) -> Result<String, String> {
+    Ok(format!("(version: 0, data: {})", ron))
+}
+
+fn wrap_chunk(ron: &str) -> Result<String, String> {
+    // The animation buffer isn't saved with the chunks, so there's no way
+    // to tell where the animations are now.
+    if ron.contains("Animated(") {
+        return Err(
+            "Animated tiles in chunks of version 0 can't be upgraded, \
+            as the animations of their tilemap are not saved with them"
+                .to_string(),
+        );
+    }
+    wrap_versioned(ron)
+}
+
+fn upgrade_pattern(ron: &str) -> Result<String, String> {
+    let mut pattern = ron::from_str::<TilemapPattern>(ron).map_err(|err| err.to_string())?;
+    upgrade_animations(
+        &mut pattern.animations,
+        pattern
+            .tiles
+            .tiles
+            .values_mut()
+            .filter_map(|tile| match &mut tile.texture {
+                TileTexture::Animated(anim) => Some(anim),
+                TileTexture::Static(_) => None,
+            }),
+    )?;
+    ron::to_string(&pattern).map_err(|err| err.to_string())
+}
+
+/// Convert the animation buffer of version 0, which is
+/// `[0, fps, frames..., fps, frames..., ...]`, into the current layout,
+/// and move the `start`s of `animations` to where their sequences are now.
+///
+/// The old buffer doesn't store the lengths, so the sequences are found by the animations
+/// referring to them. Sequences no animation refers to are dropped.
+pub(crate) fn upgrade_animations<'a>(
+    buffer: &mut TilemapAnimations,
+    animations: impl IntoIterator<Item = &'a mut TileAnimation>,
+) -> Result<(), String> {
+    let mut animations = animations.into_iter().collect::<Vec<_>>();
+    let mut sequences = animations
+        .iter()
+        .map(|anim| (anim.start, anim.length))
+        .collect::<Vec<_>>();
+    sequences.sort_unstable();
+    sequences.dedup();
+
+    #[cfg(not(feature = "atlas"))]
+    let stride = 1;
+    #[cfg(feature = "atlas")]
+    let stride = 2;
+
+    let old = &buffer.0;
+    let mut upgraded = TilemapAnimations::default();
+    let mut starts = HashMap::default();
+    let mut end = 1;
+    for (start, length) in sequences {
+        let (start, length) = (start as usize, length as usize);
+        if start <= end || start + length * stride > old.len() {
+            return Err(format!(
+                "The animation starting at {} with {} frames is out of the buffer \
+                or overlaps with another one",
+                start, length
+            ));
+        }
+
+        let fps = old[start - 1];
+        upgraded.0.extend([
+            length as i32,
+            TileAnimationMode::Loop.as_shader_flag(),
+            fps,
+        ]);
+        starts.insert(start as u32, upgraded.0.len() as u32);
+        upgraded
+            .0
+            .extend_from_slice(&old[start..start + length * stride]);
+        end = start + length * stride;
+    }
+
+    for anim in animations.iter_mut() {
+        anim.start = starts[&anim.start];
+    }
+    *buffer = upgraded;
+    Ok(())
+}
+
+pub trait SaveMigrationApp {
+    /// Register the migration that upgrades `format` files from `from_version`
+    /// to `from_version + 1`.
+    fn add_save_migration(
+        &mut self,
+        format: SaveFormat,
+        from_version: u32,
+        migration: SaveMigration,
+    ) -> &mut Self;
+}
+
+impl SaveMigrationApp for App {
+    fn add_save_migration(
+        &mut self,
+        format: SaveFormat,
+        from_version: u32,
+        migration: SaveMigration,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(SaveMigrations::default)
+            .register(format, from_version, migration);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::IVec2;
+
+    use crate::{
+        serializing::pattern::TilemapPattern,
+        tilemap::{
+            buffers::TileBuilderBuffer,
+            chunking::storage::TileBuilderChunkedStorage,
+            map::TilemapAnimations,
+            tile::{RawTileAnimation, TileAnimation, TileBuilder, TileTexture},
+        },
+    };
+
+    use super::{
+        upgrade_animations, SaveFormat, SaveFormatError, SaveMigrations, Versioned,
+        SAVE_FORMAT_VERSION,
+    };
+
+    #[test]
+    fn test_migration() {
+        let migrations = SaveMigrations::default();
+
+        let mut buffer = TileBuilderBuffer::new();
+        buffer.set(bevy::math::IVec2::new(1, 2), TileBuilder::new());
+        let legacy = ron::to_string(&buffer).unwrap();
+        let current = ron::to_string(&Versioned::new(buffer)).unwrap();
+
+        let v1 = current.replacen(&format!("version:{}", SAVE_FORMAT_VERSION), "version:1", 1);
+        assert_ne!(v1, current);
+
+        for (ron, version) in [(legacy, 0), (v1, 1), (current, SAVE_FORMAT_VERSION)] {
+            let loaded = migrations
+                .load::<Versioned<TileBuilderBuffer>>(SaveFormat::Chunk, &ron)
+                .unwrap();
+            assert_eq!(loaded.version, version);
+            assert!(loaded.data.get(bevy::math::IVec2::new(1, 2)).is_some());
+        }
+
+        let future = format!("(version: {}, data: ())", SAVE_FORMAT_VERSION + 1);
+        assert!(matches!(
+            migrations.upgrade(SaveFormat::Chunk, &future),
+            Err(SaveFormatError::TooNew { version, .. }) if version == SAVE_FORMAT_VERSION + 1
+        ));
+
+        migrations.register(SaveFormat::Chunk, 0, |_| Err("corrupted".to_string()));
+        assert!(matches!(
+            migrations.clone().upgrade(SaveFormat::Chunk, "(tiles: {})"),
+            Err(SaveFormatError::MigrationFailed { version: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_animation_migration() {
+        let migrations = SaveMigrations::default();
+
+        let mut expected = TilemapAnimations::default();
+        let first = expected.register(RawTileAnimation::from_atlas_indices(0, 1..4, 10));
+        let second = expected.register(RawTileAnimation::from_atlas_indices(0, 4..6, 5));
+
+        // Version 0 had no length and mode before the fps.
+        let mut legacy = expected.clone();
+        for start in [second.start, first.start] {
+            legacy.0.drain(start as usize - 3..start as usize - 1);
+        }
+        let legacy_anim = |anim: TileAnimation, shift: u32| TileAnimation {
+            start: anim.start - shift,
+            ..anim
+        };
+        let legacy_tiles = [legacy_anim(first, 2), legacy_anim(second, 4)];
+        let animated = |tile: &TileBuilder| match tile.texture {
+            TileTexture::Animated(anim) => anim,
+            TileTexture::Static(_) => unreachable!(),
+        };
+
+        // Patterns hold both the animations and the tiles.
+        let mut pattern = TilemapPattern::new(None);
+        pattern.animations = legacy.clone();
+        for (x, anim) in legacy_tiles.into_iter().enumerate() {
+            pattern
+                .tiles
+                .set(IVec2::new(x as i32, 0), TileBuilder::new().with_animation(anim));
+        }
+        let ron = ron::to_string(&pattern)
+            .unwrap()
+            .replacen(&format!("version:{},", SAVE_FORMAT_VERSION), "", 1);
+        let loaded = migrations
+            .load::<TilemapPattern>(SaveFormat::Pattern, &ron)
+            .unwrap();
+        assert_eq!(loaded.animations.0, expected.0);
+        assert_eq!(animated(loaded.tiles.get(IVec2::ZERO).unwrap()), first);
+        assert_eq!(animated(loaded.tiles.get(IVec2::X).unwrap()), second);
+
+        // Tilemaps save the tiles apart, so they're upgraded together when loading.
+        let mut tiles = TileBuilderChunkedStorage::new(16);
+        for (x, anim) in legacy_tiles.into_iter().enumerate() {
+            tiles.set_elem(IVec2::new(x as i32, 0), TileBuilder::new().with_animation(anim));
+        }
+        let mut tiles = migrations
+            .load::<Versioned<TileBuilderChunkedStorage>>(
+                SaveFormat::TilemapLayer,
+                &ron::to_string(&tiles).unwrap(),
+            )
+            .unwrap()
+            .data;
+        let mut animations = legacy;
+        upgrade_animations(
+            &mut animations,
+            tiles
+                .iter_some_mut()
+                .filter_map(|tile| match &mut tile.texture {
+                    TileTexture::Animated(anim) => Some(anim),
+                    TileTexture::Static(_) => None,
+                }),
+        )
+        .unwrap();
+        assert_eq!(animations.0, expected.0);
+        assert_eq!(animated(tiles.get_elem(IVec2::ZERO).unwrap()), first);
+        assert_eq!(animated(tiles.get_elem(IVec2::X).unwrap()), second);
+
+        // Chunks don't have the animations to upgrade with.
+        let mut chunk = TileBuilderBuffer::new();
+        chunk.set(IVec2::ZERO, TileBuilder::new().with_animation(legacy_tiles[0]));
+        assert!(matches!(
+            migrations.upgrade(SaveFormat::Chunk, &ron::to_string(&chunk).unwrap()),
+            Err(SaveFormatError::MigrationFailed { version: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+// Stable module, import with `#import entitiles::math::{...}`.
+// Everything here is pure and doesn't depend on any binding.
+#define_import_path entitiles::math
+
+struct Aabb2d {
+    min: vec2<f32>,
+    max: vec2<f32>,
+}
+
+fn aabb_contains(aabb: Aabb2d, point: vec2<f32>) -> bool {
+    return all(point >= aabb.min) && all(point <= aabb.max);
+}
+
+fn aabb_intersects(lhs: Aabb2d, rhs: Aabb2d) -> bool {
+    return all(lhs.min <= rhs.max) && all(rhs.min <= lhs.max);
+}
+
+// Map `point` from `aabb` into `[0, 1]`, useful for sampling data textures.
+fn aabb_normalize(aabb: Aabb2d, point: vec2<f32>) -> vec2<f32> {
+    return (point - aabb.min) / (aabb.max - aabb.min);
+}
+
+// `rot_mat` is a column major 2x2 matrix, the same as `rot_mat` in tilemap uniforms.
+fn rotate(rot_mat: vec4<f32>, point: vec2<f32>) -> vec2<f32> {
+    return point.x * rot_mat.xy + point.y * rot_mat.zw;
+}
+
+// The inverse of `rotate`. The matrix may also contain a scale.
+fn inverse_rotate(rot_mat: vec4<f32>, point: vec2<f32>) -> vec2<f32> {
+    let det = rot_mat.x * rot_mat.w - rot_mat.z * rot_mat.y;
+    return vec2<f32>(
+        rot_mat.w * point.x - rot_mat.z * point.y,
+        rot_mat.x * point.y - rot_mat.y * point.x,
+    ) / det;
+}
+
+// Floored division, so negative indices are handled like `div_euclid` on the cpu.
+fn div_floor(lhs: vec2<i32>, rhs: vec2<i32>) -> vec2<i32> {
+    return vec2<i32>(floor(vec2<f32>(lhs) / vec2<f32>(rhs)));
+}
+
+fn rem_euclid(lhs: vec2<i32>, rhs: vec2<i32>) -> vec2<i32> {
+    return lhs - div_floor(lhs, rhs) * rhs;
+}
+
+// Bilinear interpolation between four corner values, `t` is in `[0, 1]`.
+// The corners are ordered as bottom left, bottom right, top right, top left.
+fn bilerp(corners: vec4<f32>, t: vec2<f32>) -> f32 {
+    let bottom = mix(corners.x, corners.y, t.x);
+    let top = mix(corners.w, corners.z, t.x);
+    return mix(bottom, top, t.y);
+}
@@ -1,12 +1,34 @@
 use bevy::{
     app::Plugin,
     asset::{load_internal_asset, Handle},
-    render::render_resource::Shader,
+    math::{Vec2, Vec4},
+    render::render_resource::{Shader, ShaderType},
+};
+
+use crate::tilemap::map::{
+    TilePivot, TileRenderSize, TilemapAxisFlip, TilemapSlotSize, TilemapTransform, TilemapType,
 };
 
 pub struct EntiTilesShaderPlugin;
 
 pub const MATH_SHADER: Handle<Shader> = Handle::weak_from_u128(68354165413586415);
+/// `#import entitiles::math::{...}`
+///
+/// Pure math helpers like aabbs, rotations and floored divisions.
+/// This import path is stable and can be used in your own shaders.
+pub const ENTITILES_MATH_SHADER: Handle<Shader> = Handle::weak_from_u128(14350654132168435);
+/// `#import entitiles::tilemap_coords::{...}`
+///
+/// Conversions between tile indices and world positions that match the renderer.
+/// Feed it with [`TilemapCoordsUniform`]. This import path is stable and can be
+/// used in your own shaders.
+pub const TILEMAP_COORDS_SHADER: Handle<Shader> = Handle::weak_from_u128(98431365413216854);
+/// `#import entitiles::post_effect::{...}`
+///
+/// The screen texture, sampler and globals shared by the effects added using
+/// [`TilemapPostEffectApp`](crate::render::post_processing::TilemapPostEffectApp).
+/// This import path is stable and can be used in your own shaders.
+pub const POST_EFFECT_SHADER: Handle<Shader> = Handle::weak_from_u128(76431532154687413);
 
 impl Plugin for EntiTilesShaderPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
@@ -16,5 +38,70 @@ impl Plugin for EntiTilesShaderPlugin {
             "math.wgsl",
             Shader::from_wgsl
         );
+
+        load_internal_asset!(
+            app,
+            ENTITILES_MATH_SHADER,
+            "entitiles_math.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            TILEMAP_COORDS_SHADER,
+            "tilemap_coords.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            POST_EFFECT_SHADER,
+            "post_effect.wgsl",
+            Shader::from_wgsl
+        );
+    }
+}
+
+/// The cpu side of `TilemapCoords` in `entitiles::tilemap_coords`.
+///
+/// Use it in your own materials or post processing to convert between
+/// tile indices and world positions on the gpu.
+#[derive(ShaderType, Debug, Clone, Copy, Default)]
+pub struct TilemapCoordsUniform {
+    pub rot_mat: Vec4,
+    pub translation: Vec2,
+    pub slot_size: Vec2,
+    pub tile_render_size: Vec2,
+    pub pivot: Vec2,
+    pub axis_dir: Vec2,
+    pub hex_legs: f32,
+    pub ty: u32,
+}
+
+impl TilemapCoordsUniform {
+    pub fn new(
+        ty: TilemapType,
+        transform: &TilemapTransform,
+        slot_size: &TilemapSlotSize,
+        tile_render_size: &TileRenderSize,
+        pivot: &TilePivot,
+        axis_flip: &TilemapAxisFlip,
+    ) -> Self {
+        let (ty, hex_legs) = match ty {
+            TilemapType::Square => (0, 0.),
+            TilemapType::Isometric => (1, 0.),
+            TilemapType::Hexagonal(legs) => (2, legs as f32),
+        };
+
+        Self {
+            rot_mat: Vec4::from_array(transform.get_matrix().to_cols_array()),
+            translation: transform.translation,
+            slot_size: slot_size.0,
+            tile_render_size: tile_render_size.0,
+            pivot: pivot.0,
+            axis_dir: axis_flip.as_vec2(),
+            hex_legs,
+            ty,
+        }
     }
 }
@@ -0,0 +1,15 @@
+// Stable module, import with `#import entitiles::post_effect::{...}`.
+// The bindings shared by the effects added using `add_tilemap_post_effect`.
+//
+// Declare the settings of your effect at `@group(0) @binding(2)`.
+#define_import_path entitiles::post_effect
+
+#import bevy_render::globals::Globals
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var screen_sampler: sampler;
+@group(0) @binding(3) var<uniform> globals: Globals;
+
+fn sample_screen(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(screen_texture, screen_sampler, uv);
+}
@@ -0,0 +1,120 @@
+// Stable module, import with `#import entitiles::tilemap_coords::{...}`.
+// Fill `TilemapCoords` using `TilemapCoordsUniform` on the cpu side.
+//
+// Positions match what the tilemap renderer produces, including axis flipping.
+#define_import_path entitiles::tilemap_coords
+
+#import entitiles::math::{rotate, inverse_rotate, div_floor, rem_euclid}
+
+const TILEMAP_SQUARE: u32 = 0u;
+const TILEMAP_ISOMETRIC: u32 = 1u;
+const TILEMAP_HEXAGONAL: u32 = 2u;
+
+struct TilemapCoords {
+    rot_mat: vec4<f32>,
+    translation: vec2<f32>,
+    slot_size: vec2<f32>,
+    tile_render_size: vec2<f32>,
+    pivot: vec2<f32>,
+    axis_dir: vec2<f32>,
+    hex_legs: f32,
+    ty: u32,
+}
+
+// The bottom left corner of the mesh of the tile at `index`, in tilemap space.
+fn tile_origin(coords: TilemapCoords, index: vec2<i32>) -> vec2<f32> {
+    let i = vec2<f32>(index) * coords.axis_dir;
+    let flip_offset = (1. - coords.axis_dir) / 2. * coords.slot_size;
+
+    switch coords.ty {
+        case TILEMAP_ISOMETRIC: {
+            let flipped = (1. - coords.axis_dir) / 4.;
+            return vec2<f32>(i.x - i.y, i.x + i.y) / 2. * coords.slot_size
+                   - (flipped.x + flipped.y) * vec2<f32>(0., coords.slot_size.y);
+        }
+        case TILEMAP_HEXAGONAL: {
+            return vec2<f32>(
+                coords.slot_size.x * (i.x - 0.5 * i.y),
+                (coords.slot_size.y + coords.hex_legs) / 2. * i.y,
+            ) - flip_offset;
+        }
+        default: {
+            return i * coords.slot_size - flip_offset;
+        }
+    }
+}
+
+fn local_to_world(coords: TilemapCoords, local: vec2<f32>) -> vec2<f32> {
+    return rotate(coords.rot_mat, local) + coords.translation;
+}
+
+fn world_to_local(coords: TilemapCoords, world: vec2<f32>) -> vec2<f32> {
+    return inverse_rotate(coords.rot_mat, world - coords.translation);
+}
+
+// A point on the tile at `index`. `uv` is in `[0, 1]`, where `(0, 0)` is the
+// bottom left corner of the tile mesh.
+fn tile_point_world(coords: TilemapCoords, index: vec2<i32>, uv: vec2<f32>) -> vec2<f32> {
+    let local = (uv - coords.pivot) * coords.tile_render_size + tile_origin(coords, index);
+    return local_to_world(coords, local);
+}
+
+fn tile_center_world(coords: TilemapCoords, index: vec2<i32>) -> vec2<f32> {
+    return tile_point_world(coords, index, vec2<f32>(0.5));
+}
+
+// The index of the tile whose slot contains `world`.
+//
+// For isometric and hexagonal tilemaps, this is the tile with the nearest slot center,
+// which is exact for regular shapes.
+fn world_to_index(coords: TilemapCoords, world: vec2<f32>) -> vec2<i32> {
+    // Where `tile_origin` would be if `world` was the center of a slot.
+    let origin = world_to_local(coords, world) + coords.pivot * coords.tile_render_size
+                 - coords.slot_size / 2.;
+    let flip_offset = (1. - coords.axis_dir) / 2. * coords.slot_size;
+
+    switch coords.ty {
+        case TILEMAP_ISOMETRIC: {
+            let flipped = (1. - coords.axis_dir) / 4.;
+            let q = (origin + (flipped.x + flipped.y) * vec2<f32>(0., coords.slot_size.y))
+                    / coords.slot_size * 2.;
+            let i = round(vec2<f32>(q.x + q.y, q.y - q.x) / 2.);
+            return vec2<i32>(i * coords.axis_dir);
+        }
+        case TILEMAP_HEXAGONAL: {
+            let p = origin + flip_offset;
+            let y = p.y * 2. / (coords.slot_size.y + coords.hex_legs);
+            let x = p.x / coords.slot_size.x + 0.5 * y;
+            return vec2<i32>(hex_round(vec2<f32>(x, y)) * coords.axis_dir);
+        }
+        default: {
+            return vec2<i32>(round((origin + flip_offset) / coords.slot_size) * coords.axis_dir);
+        }
+    }
+}
+
+// Round fractional axial hex coordinates to the nearest hex.
+fn hex_round(axial: vec2<f32>) -> vec2<f32> {
+    // Axial (x, y) in this crate maps to cube coordinates (x - y, y, -x).
+    let cube = vec3<f32>(axial.x - axial.y, axial.y, -axial.x);
+    var rounded = round(cube);
+    let diff = abs(rounded - cube);
+
+    if diff.x > diff.y && diff.x > diff.z {
+        rounded.x = -rounded.y - rounded.z;
+    } else if diff.y > diff.z {
+        rounded.y = -rounded.x - rounded.z;
+    } else {
+        rounded.z = -rounded.x - rounded.y;
+    }
+
+    return vec2<f32>(-rounded.z, rounded.y);
+}
+
+fn chunk_index(index: vec2<i32>, chunk_size: u32) -> vec2<i32> {
+    return div_floor(index, vec2<i32>(i32(chunk_size)));
+}
+
+fn in_chunk_index(index: vec2<i32>, chunk_size: u32) -> vec2<i32> {
+    return rem_euclid(index, vec2<i32>(i32(chunk_size)));
+}
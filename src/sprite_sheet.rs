@@ -0,0 +1,435 @@
+//! Import sprite sheets exported by TexturePacker or Aseprite as tilemap textures.
+//!
+//! Both tools export the same JSON layout, frames are either stored in a map
+//! (`JSON (Hash)`) or in an array (`JSON (Array)`). Aseprite additionally exports
+//! the frame durations and tags, which will be converted into animations.
+
+use bevy::{
+    app::{App, Plugin},
+    asset::{io::Reader, Asset, AssetApp, AssetLoader, AsyncReadExt, Handle, LoadContext},
+    math::UVec2,
+    reflect::TypePath,
+    render::texture::Image,
+    utils::HashMap,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    tilemap::{
+        map::{TilemapTexture, TilemapTextureDescriptor},
+        tile::{RawTileAnimation, TileAnimationMode},
+    },
+    utils::asset,
+};
+
+pub struct EntiTilesSpriteSheetPlugin;
+
+impl Plugin for EntiTilesSpriteSheetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SpriteSheet>()
+            .init_asset_loader::<SpriteSheetLoader>();
+    }
+}
+
+/// A sprite sheet with named frames and animations.
+///
+/// It can be loaded as an asset from `.sheet.json` files.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct SpriteSheet {
+    pub(crate) texture: TilemapTexture,
+    pub(crate) frames: HashMap<String, u32>,
+    pub(crate) animations: HashMap<String, SpriteSheetAnimation>,
+}
+
+impl SpriteSheet {
+    /// The texture of the sheet, which can be used in `TilemapTextures` directly.
+    #[inline]
+    pub fn texture(&self) -> &TilemapTexture {
+        &self.texture
+    }
+
+    /// Get the atlas index of a frame.
+    #[inline]
+    pub fn frame(&self, name: &str) -> Option<u32> {
+        self.frames.get(name).copied()
+    }
+
+    #[inline]
+    pub fn frames(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.frames
+            .iter()
+            .map(|(name, index)| (name.as_str(), *index))
+    }
+
+    /// Get an animation that can be registered in `TilemapAnimations`.
+    ///
+    /// `texture_index` is the index of this sheet in the `TilemapTextures`.
+    pub fn animation(&self, name: &str, texture_index: u32) -> Option<RawTileAnimation> {
+        self.animations.get(name).map(|anim| {
+            RawTileAnimation::from_atlas_indices(
+                texture_index,
+                anim.sequence.iter().copied(),
+                anim.fps,
+            )
+            .with_mode(anim.mode)
+        })
+    }
+
+    #[inline]
+    pub fn animation_names(&self) -> impl Iterator<Item = &str> {
+        self.animations.keys().map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SpriteSheetAnimation {
+    pub(crate) sequence: Vec<u32>,
+    pub(crate) fps: u32,
+    pub(crate) mode: TileAnimationMode,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SpriteSheetJson {
+    frames: SpriteSheetFrames,
+    meta: SpriteSheetMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpriteSheetFrames {
+    Hash(IndexMap<String, SpriteSheetFrame>),
+    Array(Vec<NamedSpriteSheetFrame>),
+}
+
+#[derive(Deserialize)]
+struct NamedSpriteSheetFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: SpriteSheetFrame,
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetFrame {
+    frame: SpriteSheetRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    /// Only exported by Aseprite, in milliseconds.
+    duration: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetMeta {
+    image: String,
+    size: SpriteSheetSize,
+    /// Only exported by Aseprite.
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<SpriteSheetTag>,
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Error, Debug)]
+pub enum SpriteSheetLoaderError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("The sprite sheet has no frames!")]
+    Empty,
+    #[error("Frame {0} is rotated or trimmed, which is not supported!")]
+    Unsupported(String),
+    #[error("Frame {0} has size {1}, but the other frames are {2}!")]
+    SizeMismatch(String, UVec2, UVec2),
+    #[error("Frame {0} is not aligned to the grid!")]
+    Misaligned(String),
+    #[error("Tag {0} refers to frames that don't exist!")]
+    InvalidTag(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SpriteSheetLoaderSettings {
+    /// The fps of the animations if the sheet doesn't contain frame durations.
+    pub fps: u32,
+}
+
+impl Default for SpriteSheetLoaderSettings {
+    fn default() -> Self {
+        Self { fps: 10 }
+    }
+}
+
+/// Loads TexturePacker and Aseprite sprite sheets, with the extension `.sheet.json`.
+///
+/// Frames must have the same size and be placed on a grid, optionally with the same padding
+/// around each of them, which is what TexturePacker's `extrude` produces.
+///
+/// Animations come from Aseprite tags. For sheets without tags, frames with a numeric suffix
+/// like `walk_01.png`, `walk_02.png` are grouped into an animation called `walk`.
+#[derive(Default)]
+pub struct SpriteSheetLoader;
+
+impl AssetLoader for SpriteSheetLoader {
+    type Asset = SpriteSheet;
+
+    type Settings = SpriteSheetLoaderSettings;
+
+    type Error = SpriteSheetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await?;
+        let json = serde_json::from_slice::<SpriteSheetJson>(&buf)?;
+        let source = asset::source_of(load_context.asset_path());
+        let image = load_context.load(asset::with_source(
+            load_context.path().parent().unwrap().join(&json.meta.image),
+            source.as_deref(),
+        ));
+
+        json.into_sprite_sheet(image, settings.fps)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sheet.json"]
+    }
+}
+
+impl SpriteSheetJson {
+    pub(crate) fn into_sprite_sheet(
+        self,
+        image: Handle<Image>,
+        default_fps: u32,
+    ) -> Result<SpriteSheet, SpriteSheetLoaderError> {
+        let frames = match self.frames {
+            SpriteSheetFrames::Hash(frames) => frames.into_iter().collect::<Vec<_>>(),
+            SpriteSheetFrames::Array(frames) => {
+                frames.into_iter().map(|f| (f.filename, f.frame)).collect()
+            }
+        };
+
+        let Some((_, first)) = frames.first() else {
+            return Err(SpriteSheetLoaderError::Empty);
+        };
+        let tile_size = UVec2::new(first.frame.w, first.frame.h);
+        let padding = frames
+            .iter()
+            .map(|(_, f)| f.frame.x.min(f.frame.y))
+            .min()
+            .unwrap();
+        let desc = TilemapTextureDescriptor {
+            size: UVec2::new(self.meta.size.w, self.meta.size.h),
+            tile_size,
+            padding,
+            ..Default::default()
+        };
+        let stride = desc.tile_stride();
+        let columns = desc.tile_count().x;
+
+        let mut indices = Vec::with_capacity(frames.len());
+        for (name, f) in &frames {
+            if f.rotated || f.trimmed {
+                return Err(SpriteSheetLoaderError::Unsupported(name.clone()));
+            }
+
+            let size = UVec2::new(f.frame.w, f.frame.h);
+            if size != tile_size {
+                return Err(SpriteSheetLoaderError::SizeMismatch(
+                    name.clone(),
+                    size,
+                    tile_size,
+                ));
+            }
+
+            let min = UVec2::new(f.frame.x, f.frame.y) - padding;
+            if min % stride != UVec2::ZERO {
+                return Err(SpriteSheetLoaderError::Misaligned(name.clone()));
+            }
+
+            let tile_index = min / stride;
+            indices.push(tile_index.y * columns + tile_index.x);
+        }
+
+        let animations = if self.meta.frame_tags.is_empty() {
+            group_numbered_frames(&frames, &indices, default_fps)
+        } else {
+            self.meta
+                .frame_tags
+                .into_iter()
+                .map(|tag| {
+                    if tag.from > tag.to || tag.to >= frames.len() {
+                        return Err(SpriteSheetLoaderError::InvalidTag(tag.name));
+                    }
+
+                    let mut sequence = indices[tag.from..=tag.to].to_vec();
+                    if tag.direction.ends_with("reverse") {
+                        sequence.reverse();
+                    }
+                    let mode = if tag.direction.starts_with("pingpong") {
+                        TileAnimationMode::PingPong
+                    } else {
+                        TileAnimationMode::Loop
+                    };
+
+                    let durations = frames[tag.from..=tag.to]
+                        .iter()
+                        .map(|(_, f)| f.duration)
+                        .collect::<Option<Vec<_>>>();
+                    let fps = match durations {
+                        Some(d) => {
+                            let total = d.iter().sum::<u32>().max(1) as f32;
+                            ((d.len() as f32 * 1000. / total).round() as u32).max(1)
+                        }
+                        None => default_fps,
+                    };
+
+                    Ok((
+                        tag.name,
+                        SpriteSheetAnimation {
+                            sequence,
+                            fps,
+                            mode,
+                        },
+                    ))
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        Ok(SpriteSheet {
+            texture: TilemapTexture::new(image, desc),
+            frames: frames
+                .into_iter()
+                .map(|(name, _)| name)
+                .zip(indices)
+                .collect(),
+            animations,
+        })
+    }
+}
+
+/// Group frames like `walk_01.png`, `walk_02.png` into an animation called `walk`.
+fn group_numbered_frames(
+    frames: &[(String, SpriteSheetFrame)],
+    indices: &[u32],
+    fps: u32,
+) -> HashMap<String, SpriteSheetAnimation> {
+    let mut groups = HashMap::<String, Vec<(u32, u32)>>::new();
+
+    for ((name, _), index) in frames.iter().zip(indices) {
+        let stem = name
+            .rsplit_once('.')
+            .map_or(name.as_str(), |(stem, _)| stem);
+        let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+        let Ok(number) = stem[prefix.len()..].parse::<u32>() else {
+            continue;
+        };
+
+        groups
+            .entry(prefix.trim_end_matches(['_', '-', ' ']).to_string())
+            .or_default()
+            .push((number, *index));
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, g)| g.len() > 1)
+        .map(|(name, mut g)| {
+            g.sort_by_key(|(number, _)| *number);
+            (
+                name,
+                SpriteSheetAnimation {
+                    sequence: g.into_iter().map(|(_, index)| index).collect(),
+                    fps,
+                    mode: TileAnimationMode::Loop,
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::UVec2;
+
+    use crate::tilemap::tile::TileAnimationMode;
+
+    use super::SpriteSheetJson;
+
+    #[test]
+    fn test_aseprite_sheet() {
+        let json = r#"{
+            "frames": {
+                "a 0.aseprite": { "frame": { "x": 1, "y": 1, "w": 16, "h": 16 }, "duration": 100 },
+                "a 1.aseprite": { "frame": { "x": 19, "y": 1, "w": 16, "h": 16 }, "duration": 100 },
+                "a 2.aseprite": { "frame": { "x": 1, "y": 19, "w": 16, "h": 16 }, "duration": 100 }
+            },
+            "meta": {
+                "image": "a.png",
+                "size": { "w": 36, "h": 36 },
+                "frameTags": [{ "name": "idle", "from": 0, "to": 2, "direction": "pingpong" }]
+            }
+        }"#;
+        let sheet = serde_json::from_str::<SpriteSheetJson>(json)
+            .unwrap()
+            .into_sprite_sheet(Default::default(), 10)
+            .unwrap();
+
+        assert_eq!(sheet.texture.desc.tile_size, UVec2::splat(16));
+        assert_eq!(sheet.texture.desc.padding, 1);
+        assert_eq!(sheet.frame("a 2.aseprite"), Some(2));
+
+        let idle = &sheet.animations["idle"];
+        assert_eq!(idle.sequence, vec![0, 1, 2]);
+        assert_eq!(idle.fps, 10);
+        assert_eq!(idle.mode, TileAnimationMode::PingPong);
+    }
+
+    #[test]
+    fn test_texture_packer_sheet() {
+        let json = r#"{
+            "frames": [
+                { "filename": "walk_02.png", "frame": { "x": 8, "y": 0, "w": 8, "h": 8 } },
+                { "filename": "walk_01.png", "frame": { "x": 0, "y": 8, "w": 8, "h": 8 } },
+                { "filename": "rock.png", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } }
+            ],
+            "meta": { "image": "b.png", "size": { "w": 16, "h": 16 } }
+        }"#;
+        let sheet = serde_json::from_str::<SpriteSheetJson>(json)
+            .unwrap()
+            .into_sprite_sheet(Default::default(), 12)
+            .unwrap();
+
+        assert_eq!(sheet.frame("rock.png"), Some(0));
+        assert_eq!(sheet.animations["walk"].sequence, vec![2, 1]);
+        assert_eq!(sheet.animations["walk"].fps, 12);
+        assert_eq!(sheet.animations.len(), 1);
+    }
+}
@@ -1,8 +1,15 @@
 use bevy::{
     ecs::{component::Component, entity::Entity, system::Commands},
+    math::Vec2,
+    reflect::Reflect,
     utils::HashMap,
 };
 
+use crate::tiled::{
+    events::TiledMapLoader,
+    xml::layer::{ColorTileLayer, ImageLayer, ObjectLayer},
+};
+
 #[derive(Component, Debug, Clone)]
 pub struct TiledUnloadLayer;
 
@@ -10,10 +17,39 @@ pub struct TiledUnloadLayer;
 pub struct TiledLoadedTilemap {
     pub name: String,
     pub layers: HashMap<u32, Entity>,
+    /// Layer names and their ids. If multiple layers have the same name, the first one is kept.
+    pub layer_names: HashMap<String, u32>,
     pub objects: HashMap<u32, Entity>,
+    /// The loader this map is loaded with, used to reload it when the map or its tilesets are modified.
+    pub(crate) loader: TiledMapLoader,
 }
 
 impl TiledLoadedTilemap {
+    #[inline]
+    pub fn get_layer(&self, id: u32) -> Option<Entity> {
+        self.layers.get(&id).copied()
+    }
+
+    /// Find the layer entity by the name in Tiled, like `Collision`.
+    #[inline]
+    pub fn get_layer_by_name(&self, name: &str) -> Option<Entity> {
+        self.layer_names
+            .get(name)
+            .and_then(|id| self.layers.get(id))
+            .copied()
+    }
+
+    pub(crate) fn insert_layer(
+        &mut self,
+        commands: &mut Commands,
+        entity: Entity,
+        info: TiledLayerInfo,
+    ) {
+        self.layer_names.entry(info.name.clone()).or_insert(info.id);
+        self.layers.insert(info.id, entity);
+        commands.entity(entity).insert(info);
+    }
+
     pub fn unload(&self, commands: &mut Commands) {
         self.layers.values().for_each(|e| {
             commands.entity(*e).insert(TiledUnloadLayer);
@@ -30,3 +66,70 @@ impl TiledLoadedTilemap {
 /// So they won't be unloaded when the tilemap is unloaded.
 #[derive(Component, Debug, Clone)]
 pub struct TiledGlobalObject;
+
+/// What a layer is loaded as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum TiledLayerType {
+    Tiles,
+    /// A tile layer with the `height` property.
+    Height,
+    /// An object layer listed in `TiledLoadConfig::trigger_object_layers`.
+    Trigger,
+    /// An object layer. The objects are spawned as separate entities,
+    /// which can be found in [`TiledLoadedTilemap::objects`].
+    Objects,
+    Image,
+}
+
+/// The properties of a layer in Tiled, inserted on each layer entity.
+///
+/// **Notice**: Changing these values doesn't affect the layer.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TiledLayerInfo {
+    pub id: u32,
+    pub name: String,
+    pub ty: TiledLayerType,
+    pub opacity: f32,
+    pub visible: bool,
+    /// The offset in pixels.
+    pub offset: Vec2,
+    pub parallax: Vec2,
+}
+
+impl TiledLayerInfo {
+    pub(crate) fn from_tiles(layer: &ColorTileLayer, ty: TiledLayerType) -> Self {
+        Self {
+            id: layer.id,
+            name: layer.name.clone(),
+            ty,
+            opacity: layer.opacity,
+            visible: layer.visible,
+            offset: Vec2::new(layer.offset_x, layer.offset_y),
+            parallax: Vec2::new(layer.parallax_x, layer.parallax_y),
+        }
+    }
+
+    pub(crate) fn from_objects(layer: &ObjectLayer, ty: TiledLayerType) -> Self {
+        Self {
+            id: layer.id,
+            name: layer.name.clone(),
+            ty,
+            opacity: layer.opacity,
+            visible: layer.visible,
+            offset: Vec2::new(layer.offset_x, layer.offset_y),
+            parallax: Vec2::new(layer.parallax_x, layer.parallax_y),
+        }
+    }
+
+    pub(crate) fn from_image(layer: &ImageLayer) -> Self {
+        Self {
+            id: layer.id,
+            name: layer.name.clone(),
+            ty: TiledLayerType::Image,
+            opacity: layer.opacity,
+            visible: layer.visible,
+            offset: Vec2::new(layer.offset_x, layer.offset_y),
+            parallax: Vec2::new(layer.parallax_x, layer.parallax_y),
+        }
+    }
+}
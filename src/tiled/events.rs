@@ -1,6 +1,6 @@
 use bevy::{asset::AssetId, math::Vec2, prelude::Event, reflect::Reflect};
 
-use crate::tiled::resources::PackedTiledTilemap;
+use crate::{tiled::resources::PackedTiledTilemap, utils::filter::LayerFilter};
 
 #[derive(Event, Clone)]
 pub enum TiledMapEvent {
@@ -8,11 +8,15 @@ pub enum TiledMapEvent {
     Unload(TiledMapUnloader),
 }
 
-#[derive(Reflect, Clone)]
+#[derive(Reflect, Debug, Clone)]
 pub struct TiledMapLoader {
     pub map: AssetId<PackedTiledTilemap>,
     /// Override the original tilemap translation or not.
     pub trans_ovrd: Option<Vec2>,
+    /// Place the map at this z instead of allocating from [`TilemapZOrder`](crate::tilemap::map::TilemapZOrder).
+    pub z_ovrd: Option<f32>,
+    /// Load these layers instead of the ones selected by [`TiledLoadConfig::layer_filter`](crate::tiled::resources::TiledLoadConfig::layer_filter).
+    pub layer_filter: Option<LayerFilter>,
 }
 
 #[derive(Reflect, Clone)]
@@ -1,26 +1,32 @@
 use bevy::{
     app::{Plugin, Update},
-    asset::{load_internal_asset, AssetApp, AssetEvent, AssetServer, Assets, Handle},
+    asset::{load_internal_asset, AssetApp, AssetEvent, AssetId, AssetServer, Assets, Handle},
     color::Color,
     ecs::{
         entity::Entity,
+        observer::Trigger,
         query::With,
+        schedule::IntoSystemConfigs,
         system::{Commands, NonSend, Query, Res, ResMut},
     },
+    hierarchy::DespawnRecursiveExt,
     log::{error, info, warn},
-    math::{IVec2, Vec2},
-    prelude::{EventReader, Local, SpatialBundle},
-    render::{mesh::Mesh, render_resource::Shader},
+    math::{IVec2, UVec2, Vec2},
+    prelude::{EventReader, EventWriter, Local, SpatialBundle},
+    render::{mesh::Mesh, render_resource::Shader, RenderApp},
     sprite::{Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
     transform::components::Transform,
-    utils::HashMap,
+    utils::{HashMap, HashSet},
 };
 
 use crate::{
-    render::material::StandardTilemapMaterial,
+    math::GridRect,
+    render::material::{init_material_asset, LayerMaterialRegistry, StandardTilemapMaterial},
     tiled::{
-        components::{TiledLoadedTilemap, TiledUnloadLayer},
-        events::TiledMapEvent,
+        components::{
+            TiledGlobalObject, TiledLayerInfo, TiledLayerType, TiledLoadedTilemap, TiledUnloadLayer,
+        },
+        events::{TiledMapEvent, TiledMapLoader, TiledMapUnloader},
         resources::{
             PackedTiledTilemap, TiledAssets, TiledCustomTileInstance, TiledLoadConfig,
             TiledLoadedMaps, TiledTilemapLoader, TiledTilemapToAssets, TiledTilesetLoader,
@@ -28,7 +34,7 @@ use crate::{
         sprite::TiledSpriteMaterial,
         traits::{TiledCustomTileRegistry, TiledObjectRegistry},
         xml::{
-            layer::{ColorTileLayerData, TiledLayer},
+            layer::{ColorTileLayer, ColorTileLayerData, ObjectDrawOrder, ObjectLayer, TiledLayer},
             tileset::TiledTileset,
             MapOrientation, TiledGroup,
         },
@@ -36,36 +42,58 @@ use crate::{
     tilemap::{
         buffers::TileBuilderBuffer,
         bundles::StandardTilemapBundle,
+        despawn::EverythingUnloaded,
+        height::InsertHeightMap,
         map::{
-            TilePivot, TileRenderSize, TilemapAxisFlip, TilemapName, TilemapSlotSize,
-            TilemapStorage, TilemapTextures, TilemapTransform, TilemapType,
+            EntiTilesDefaults, TilePivot, TileRenderSize, TilemapAxisFlip, TilemapName,
+            TilemapSlotSize, TilemapStorage, TilemapTextures, TilemapTransform, TilemapType,
+            TilemapZOrder,
         },
+        trigger::TileTriggerTilemap,
     },
-    DEFAULT_CHUNK_SIZE,
+    utils::{asset::init_asset_if_missing, filter::LayerFilter},
 };
 
+#[cfg(feature = "audio")]
+use crate::tilemap::audio::{TileAudioRegion, TilemapAudioRegions};
+
 pub mod app_ext;
 pub mod components;
 pub mod events;
 pub mod resources;
 pub mod sprite;
 pub mod traits;
+pub mod validate;
 pub mod xml;
 
 pub const TILED_SPRITE_SHADER: Handle<Shader> = Handle::weak_from_u128(13584136873461368486534);
 
+/// The z distance between layers if neither [`TilemapZOrder::layer_step`]
+/// nor [`EntiTilesDefaults::z_spacing`] is set.
+pub const LAYER_Z_SPACING: f32 = 0.1;
+
 pub struct EntiTilesTiledPlugin;
 
 impl Plugin for EntiTilesTiledPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        load_internal_asset!(
-            app,
-            TILED_SPRITE_SHADER,
-            "tiled_sprite.wgsl",
-            Shader::from_wgsl
-        );
+        // Object sprites are only rendered if the renderer exists,
+        // the assets they use are still needed to spawn them in headless apps.
+        if app.get_sub_app(RenderApp).is_some() {
+            load_internal_asset!(
+                app,
+                TILED_SPRITE_SHADER,
+                "tiled_sprite.wgsl",
+                Shader::from_wgsl
+            );
+            app.add_plugins(Material2dPlugin::<TiledSpriteMaterial>::default());
+        } else {
+            init_asset_if_missing::<TiledSpriteMaterial>(app);
+        }
+        init_asset_if_missing::<Mesh>(app);
+
+        init_material_asset::<StandardTilemapMaterial>(app);
 
-        app.add_plugins(Material2dPlugin::<TiledSpriteMaterial>::default())
+        app.init_resource::<LayerMaterialRegistry>()
             .add_event::<TiledMapEvent>()
             .init_asset::<PackedTiledTilemap>()
             .init_asset_loader::<TiledTilemapLoader>()
@@ -76,7 +104,10 @@ impl Plugin for EntiTilesTiledPlugin {
             .init_resource::<TiledTilemapToAssets>()
             .init_resource::<TiledLoadedMaps>()
             .register_type::<TiledLoadConfig>()
+            .register_type::<LayerFilter>()
             .register_type::<TiledAssets>()
+            .register_type::<TiledLayerType>()
+            .register_type::<TiledLayerInfo>()
             .add_systems(
                 Update,
                 (
@@ -84,15 +115,21 @@ impl Plugin for EntiTilesTiledPlugin {
                     unload_tiled_layer,
                     unload_tiled_tilemap,
                     load_tiled_xml,
-                ),
+                )
+                    .chain(),
             )
+            .observe(tiled_unloader)
             .init_non_send_resource::<TiledObjectRegistry>()
             .init_non_send_resource::<TiledCustomTileRegistry>();
     }
 }
 
+/// Rebuilds the [`TiledAssets`] of the maps that are modified, or use tilesets that are modified.
+///
+/// Loaded maps are reloaded with the same [`TiledMapLoader`], so their entities are respawned.
 pub fn tiled_asset_event_handler(
     mut asset_event: EventReader<AssetEvent<PackedTiledTilemap>>,
+    mut tileset_event: EventReader<AssetEvent<TiledTileset>>,
     xmls: Res<Assets<PackedTiledTilemap>>,
     mut assets: ResMut<Assets<TiledAssets>>,
     mut map_to_assets: ResMut<TiledTilemapToAssets>,
@@ -101,20 +138,20 @@ pub fn tiled_asset_event_handler(
     mut textures_assets: ResMut<Assets<TilemapTextures>>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     tileset_assets: Res<Assets<TiledTileset>>,
+    defaults: Res<EntiTilesDefaults>,
+    loaded_maps: Res<TiledLoadedMaps>,
+    tilemaps_query: Query<&TiledLoadedTilemap>,
+    mut map_events: EventWriter<TiledMapEvent>,
 ) {
+    let mut rebuild = Vec::new();
+    let mut reload = HashSet::new();
+
     for ev in asset_event.read() {
         match ev {
-            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
-                let map = xmls.get(*id).unwrap();
-                let asset = TiledAssets::new(
-                    map,
-                    &asset_server,
-                    &tileset_assets,
-                    &mut material_assets,
-                    &mut textures_assets,
-                    &mut mesh_assets,
-                );
-                map_to_assets.0.insert(*id, assets.add(asset));
+            AssetEvent::Added { id } => rebuild.push(*id),
+            AssetEvent::Modified { id } => {
+                rebuild.push(*id);
+                reload.insert(*id);
             }
             AssetEvent::Removed { id } => {
                 map_to_assets.0.remove(id);
@@ -122,6 +159,60 @@ pub fn tiled_asset_event_handler(
             AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
         }
     }
+
+    for ev in tileset_event.read() {
+        if let AssetEvent::Modified { id } = ev {
+            maps_using_tileset(&xmls, *id).for_each(|map| {
+                rebuild.push(map);
+                reload.insert(map);
+            });
+        }
+    }
+
+    let mut rebuilt = HashSet::new();
+    for id in rebuild {
+        if !rebuilt.insert(id) {
+            continue;
+        }
+        let Some(map) = xmls.get(id) else {
+            continue;
+        };
+
+        let asset = TiledAssets::new(
+            map,
+            &asset_server,
+            &tileset_assets,
+            &mut material_assets,
+            &mut textures_assets,
+            &mut mesh_assets,
+            defaults.filter_mode,
+        );
+        map_to_assets.0.insert(id, assets.add(asset));
+
+        if !reload.contains(&id) {
+            continue;
+        }
+        let Some(tilemap) = loaded_maps
+            .get(&id)
+            .and_then(|e| tilemaps_query.get(*e).ok())
+        else {
+            continue;
+        };
+
+        info!("Reloading modified map. {}", map.name);
+        map_events.send(TiledMapEvent::Unload(TiledMapUnloader { map: id }));
+        map_events.send(TiledMapEvent::Load(tilemap.loader.clone()));
+    }
+}
+
+/// The maps that reference the tileset.
+fn maps_using_tileset(
+    xmls: &Assets<PackedTiledTilemap>,
+    tileset: AssetId<TiledTileset>,
+) -> impl Iterator<Item = AssetId<PackedTiledTilemap>> + '_ {
+    xmls.iter()
+        .filter(move |(_, map)| map.tilesets.values().any(|h| h.id() == tileset))
+        .map(|(id, _)| id)
 }
 
 fn unload_tiled_tilemap(
@@ -129,6 +220,7 @@ fn unload_tiled_tilemap(
     tilemaps_query: Query<&TiledLoadedTilemap>,
     mut map_events: EventReader<TiledMapEvent>,
     mut loaded_maps: ResMut<TiledLoadedMaps>,
+    mut z_order: ResMut<TilemapZOrder>,
 ) {
     for ev in map_events.read() {
         let TiledMapEvent::Unload(unloader) = ev else {
@@ -143,6 +235,7 @@ fn unload_tiled_tilemap(
 
         tilemap.unload(&mut commands);
         commands.entity(entity.unwrap()).despawn();
+        z_order.release(entity.unwrap());
     }
 }
 
@@ -159,6 +252,34 @@ fn unload_tiled_layer(
     });
 }
 
+/// Despawn all the maps and global objects. Tilemap layers are despawned as tilemaps.
+fn tiled_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut commands: Commands,
+    maps_query: Query<(Entity, &TiledLoadedTilemap)>,
+    tilemaps_query: Query<(), With<TilemapStorage>>,
+    global_objects_query: Query<Entity, With<TiledGlobalObject>>,
+    mut loaded_maps: ResMut<TiledLoadedMaps>,
+) {
+    let mut despawn = |entity| {
+        if let Some(e) = commands.get_entity(entity) {
+            e.despawn_recursive();
+        }
+    };
+
+    maps_query.iter().for_each(|(entity, map)| {
+        map.layers
+            .values()
+            .chain(map.objects.values())
+            .filter(|e| !tilemaps_query.contains(**e) && !global_objects_query.contains(**e))
+            .for_each(|e| despawn(*e));
+        despawn(entity);
+    });
+    global_objects_query.iter().for_each(despawn);
+
+    loaded_maps.0.clear();
+}
+
 fn load_tiled_xml(
     mut commands: Commands,
     config: Res<TiledLoadConfig>,
@@ -166,12 +287,15 @@ fn load_tiled_xml(
     tiled_maps: Res<Assets<PackedTiledTilemap>>,
     asset_server: Res<AssetServer>,
     mut tilemap_material_assets: ResMut<Assets<StandardTilemapMaterial>>,
+    material_registry: Res<LayerMaterialRegistry>,
     object_registry: NonSend<TiledObjectRegistry>,
     custom_tiles_registry: NonSend<TiledCustomTileRegistry>,
     mut map_events: EventReader<TiledMapEvent>,
     map_to_assets: Res<TiledTilemapToAssets>,
     mut loaded_maps: ResMut<TiledLoadedMaps>,
     mut retry_queue: Local<Vec<TiledMapEvent>>,
+    defaults: Res<EntiTilesDefaults>,
+    mut z_order: ResMut<TilemapZOrder>,
 ) {
     let mut retry = Vec::new();
 
@@ -204,6 +328,15 @@ fn load_tiled_xml(
         }
 
         let map_entity = commands.spawn_empty().id();
+        let z_spacing = z_order.layer_step(&defaults, LAYER_Z_SPACING);
+        let z_from = z_order.base + config.z_index;
+        let z_start = z_order.allocate_from(
+            map_entity,
+            z_from,
+            count_layers(&map_data.xml.layers, &map_data.xml.groups),
+            z_spacing,
+            loader.z_ovrd,
+        );
         load_tiled_tilemap(
             &mut commands,
             &config,
@@ -214,6 +347,11 @@ fn load_tiled_xml(
             &custom_tiles_registry,
             map_entity,
             &mut tilemap_material_assets,
+            &material_registry,
+            &defaults,
+            z_start,
+            z_spacing,
+            loader,
         );
         info!("Successfully loaded map. {}", map_data.name);
         loaded_maps.0.insert(loader.map, map_entity);
@@ -232,19 +370,33 @@ fn load_tiled_tilemap(
     custom_tiles_registry: &TiledCustomTileRegistry,
     map_entity: Entity,
     tilemap_material_assets: &mut Assets<StandardTilemapMaterial>,
+    material_registry: &LayerMaterialRegistry,
+    defaults: &EntiTilesDefaults,
+    z_start: f32,
+    z_spacing: f32,
+    loader: &TiledMapLoader,
 ) {
+    let layer_filter = loader.layer_filter.as_ref().unwrap_or(&config.layer_filter);
     let mut loaded_map = TiledLoadedTilemap {
         name: map_data.name.clone(),
         layers: HashMap::default(),
+        layer_names: HashMap::default(),
         objects: HashMap::default(),
+        loader: loader.clone(),
     };
-    let mut z = config.z_index;
+    let mut z = z_start;
 
     map_data.xml.layers.iter().for_each(|layer| {
+        if !layer_filter.selects(layer.name(), layer.class()) {
+            z += z_spacing;
+            return;
+        }
+
         load_layer(
             commands,
             map_data,
             &mut z,
+            z_spacing,
             layer,
             tiled_assets,
             asset_server,
@@ -253,15 +405,24 @@ fn load_tiled_tilemap(
             config,
             &mut loaded_map,
             tilemap_material_assets,
+            material_registry,
+            defaults,
         )
     });
 
     map_data.xml.groups.iter().for_each(|group| {
+        let Some(layer_filter) = layer_filter.enter_group(&group.name, &group.class) else {
+            z += z_spacing * count_layers(&group.layers, &group.groups) as f32;
+            return;
+        };
+
         load_group(
             commands,
             map_data,
             &mut z,
+            z_spacing,
             group,
+            layer_filter,
             tiled_assets,
             asset_server,
             object_registry,
@@ -269,6 +430,8 @@ fn load_tiled_tilemap(
             config,
             &mut loaded_map,
             tilemap_material_assets,
+            material_registry,
+            defaults,
         )
     });
 
@@ -279,7 +442,9 @@ fn load_group(
     commands: &mut Commands,
     tiled_data: &PackedTiledTilemap,
     z: &mut f32,
+    z_spacing: f32,
     group: &TiledGroup,
+    layer_filter: &LayerFilter,
     tiled_assets: &TiledAssets,
     asset_server: &AssetServer,
     object_registry: &TiledObjectRegistry,
@@ -287,12 +452,20 @@ fn load_group(
     config: &TiledLoadConfig,
     loaded_map: &mut TiledLoadedTilemap,
     tilemap_material_assets: &mut Assets<StandardTilemapMaterial>,
+    material_registry: &LayerMaterialRegistry,
+    defaults: &EntiTilesDefaults,
 ) {
     group.layers.iter().for_each(|content| {
+        if !layer_filter.selects(content.name(), content.class()) {
+            *z += z_spacing;
+            return;
+        }
+
         load_layer(
             commands,
             tiled_data,
             z,
+            z_spacing,
             content,
             tiled_assets,
             asset_server,
@@ -301,15 +474,24 @@ fn load_group(
             config,
             loaded_map,
             tilemap_material_assets,
+            material_registry,
+            defaults,
         )
     });
 
     group.groups.iter().for_each(|group| {
+        let Some(layer_filter) = layer_filter.enter_group(&group.name, &group.class) else {
+            *z += z_spacing * count_layers(&group.layers, &group.groups) as f32;
+            return;
+        };
+
         load_group(
             commands,
             tiled_data,
             z,
+            z_spacing,
             group,
+            layer_filter,
             tiled_assets,
             asset_server,
             object_registry,
@@ -317,14 +499,156 @@ fn load_group(
             config,
             loaded_map,
             tilemap_material_assets,
+            material_registry,
+            defaults,
         )
     });
 }
 
+/// Convert a tile layer which has a `height` property into a [`TilemapHeightMap`](crate::tilemap::height::TilemapHeightMap).
+///
+/// The height of a tile is its index in the tileset multiplied by the value of the property.
+/// Empty tiles have the height of 0.
+fn load_height_layer(
+    commands: &mut Commands,
+    tiled_data: &PackedTiledTilemap,
+    layer: &ColorTileLayer,
+    scale: &str,
+    tiled_assets: &TiledAssets,
+    loaded_map: &mut TiledLoadedTilemap,
+) {
+    if tiled_data.xml.orientation != MapOrientation::Orthogonal {
+        warn!(
+            "Height layer {} is skipped as only orthogonal maps are supported.",
+            layer.name
+        );
+        return;
+    }
+    let ColorTileLayerData::Tiles(tiles) = &layer.data else {
+        warn!(
+            "Height layer {} is skipped as infinite maps are not supported.",
+            layer.name
+        );
+        return;
+    };
+    let scale = scale.parse::<f32>().unwrap_or_else(|_| {
+        warn!(
+            "Invalid height scale {:?} of layer {}, using 1 instead.",
+            scale, layer.name
+        );
+        1.
+    });
+
+    let heights = tiles
+        .content
+        .0
+        .iter()
+        .map(|tile_id| {
+            let tile_id = tile_id & 0x3FFF_FFFF;
+            if tile_id == 0 {
+                0.
+            } else {
+                (tile_id - tiled_assets.get_tileset_meta(tile_id).first_gid) as f32 * scale
+            }
+        })
+        .collect();
+
+    let entity = commands.spawn(TilemapName(layer.name.clone())).id();
+    commands.add(InsertHeightMap {
+        target: entity,
+        heights,
+        size: UVec2::new(layer.width, layer.height),
+        origin: Vec2::new(layer.offset_x as f32, layer.offset_y as f32),
+        tile_size: Vec2::new(
+            tiled_data.xml.tile_width as f32,
+            tiled_data.xml.tile_height as f32,
+        ),
+    });
+    loaded_map.insert_layer(
+        commands,
+        entity,
+        TiledLayerInfo::from_tiles(layer, TiledLayerType::Height),
+    );
+}
+
+/// Tile layers with an `audio` property emit that sound from all their tiles.
+/// `audio_range` and `audio_volume` are optional.
+#[cfg(feature = "audio")]
+fn layer_audio_regions(
+    layer: &ColorTileLayer,
+    buffer: &TileBuilderBuffer,
+    tile_size: Vec2,
+) -> Option<TilemapAudioRegions> {
+    let source = layer.properties.get("audio")?;
+    let parse = |name: &str, default: f32| {
+        layer
+            .properties
+            .get(name)
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(default)
+    };
+
+    Some(TilemapAudioRegions::new(vec![TileAudioRegion::tiles(
+        source,
+        buffer.iter_sorted().map(|(index, _)| index).collect(),
+        parse("audio_range", tile_size.max_element() * 8.),
+    )
+    .with_volume(parse("audio_volume", 1.))]))
+}
+
+/// Convert the objects on an object layer into a [`TileTriggerTilemap`].
+fn load_trigger_layer(
+    commands: &mut Commands,
+    tiled_data: &PackedTiledTilemap,
+    layer: &ObjectLayer,
+    loaded_map: &mut TiledLoadedTilemap,
+) {
+    if tiled_data.xml.orientation != MapOrientation::Orthogonal {
+        warn!(
+            "Trigger layer {} is skipped as only orthogonal maps are supported.",
+            layer.name
+        );
+        return;
+    }
+
+    let tile_size = Vec2::new(
+        tiled_data.xml.tile_width as f32,
+        tiled_data.xml.tile_height as f32,
+    );
+    let mut trigger = TileTriggerTilemap::new();
+    layer.objects.iter().for_each(|object| {
+        // Tiled uses y down coordinates, the same as the indices of the flipped tilemap.
+        let min = Vec2::new(object.x, object.y);
+        let max = min + Vec2::new(object.width, object.height);
+        let from = (min / tile_size).floor().as_ivec2();
+        let to = ((max / tile_size).ceil().as_ivec2() - 1).max(from);
+        trigger.fill_rect(GridRect::from_min_max(from, to), object.id);
+    });
+
+    let entity = commands
+        .spawn((
+            TilemapName(layer.name.clone()),
+            TilemapType::Square,
+            // The same as tile layers, so the regions stay on the tiles they cover.
+            TilemapTransform::from_translation(Vec2::new(layer.offset_x, layer.offset_y)),
+            TilePivot::default(),
+            TilemapSlotSize(tile_size),
+            TilemapAxisFlip::Y,
+            trigger,
+        ))
+        .id();
+    loaded_map.insert_layer(
+        commands,
+        entity,
+        TiledLayerInfo::from_objects(layer, TiledLayerType::Trigger),
+    );
+}
+
 fn load_layer(
     commands: &mut Commands,
     tiled_data: &PackedTiledTilemap,
     z: &mut f32,
+    z_spacing: f32,
     layer: &TiledLayer,
     tiled_assets: &TiledAssets,
     asset_server: &AssetServer,
@@ -333,11 +657,18 @@ fn load_layer(
     config: &TiledLoadConfig,
     loaded_map: &mut TiledLoadedTilemap,
     tilemap_material_assets: &mut Assets<StandardTilemapMaterial>,
+    material_registry: &LayerMaterialRegistry,
+    defaults: &EntiTilesDefaults,
 ) {
-    *z += 0.1;
+    *z += z_spacing;
 
     match layer {
         TiledLayer::Tiles(layer) => {
+            if let Some(scale) = layer.properties.get("height") {
+                load_height_layer(commands, tiled_data, layer, scale, tiled_assets, loaded_map);
+                return;
+            }
+
             let tile_size = Vec2::new(
                 tiled_data.xml.tile_width as f32,
                 tiled_data.xml.tile_height as f32,
@@ -377,7 +708,10 @@ fn load_layer(
                 ),
                 textures,
                 animations,
-                material: tilemap_material_assets.add(StandardTilemapMaterial { tint }),
+                material: tilemap_material_assets.add(StandardTilemapMaterial {
+                    tint,
+                    ..Default::default()
+                }),
                 axis_flip: match tiled_data.xml.orientation {
                     MapOrientation::Isometric => TilemapAxisFlip::all(),
                     _ => TilemapAxisFlip::Y,
@@ -390,7 +724,8 @@ fn load_layer(
             };
 
             let mut buffer = TileBuilderBuffer::new();
-            let mut tile_render_size: Option<TileRenderSize> = None;
+            // Tilesets with larger tiles are scaled up in the shader, see `TilemapTextures`.
+            let mut max_tile_size = Vec2::ZERO;
 
             match &layer.data {
                 ColorTileLayerData::Tiles(tiles) => {
@@ -399,14 +734,7 @@ fn load_layer(
                         .iter_decoded(layer_size, tiled_assets, &tiled_data)
                         .for_each(|(index, builder, trs, custom_tile)| {
                             buffer.set(index, builder);
-                            if let Some(t) = tile_render_size {
-                                assert_eq!(
-                                    t.0,
-                                    trs.0,
-                                    "Using tilesets that have different tile size, which is not supported currently."
-                                );
-                            }
-                            tile_render_size = Some(trs);
+                            max_tile_size = max_tile_size.max(trs.0);
                             if let Some(c) = custom_tile {
                                 custom_properties_tiles.push((index, c.clone()));
                             }
@@ -422,32 +750,27 @@ fn load_layer(
                             .iter_decoded(size, tiled_assets, &tiled_data)
                             .for_each(|(index, builder, trs, _)| {
                                 buffer.set(index + offset, builder);
-                                if let Some(t) = tile_render_size {
-                                    assert_eq!(
-                                        t.0,
-                                        trs.0,
-                                        "Using tilesets that have different tile size, which is not supported currently."
-                                    );
-                                }
-                                tile_render_size = Some(trs);
+                                max_tile_size = max_tile_size.max(trs.0);
                             });
                     });
                 }
             }
 
-            tilemap.tile_render_size = tile_render_size.unwrap_or_default();
+            tilemap.tile_render_size = TileRenderSize(tiled_assets.min_tile_size.as_vec2());
             tilemap.storage = TilemapStorage::new(
-                if tilemap.tile_render_size.0.y > tilemap.slot_size.0.y {
+                if max_tile_size.y > tilemap.slot_size.0.y {
                     warn!(
                         "Using chunk size 1 for layer {} as it looks like a 3d tilemap.",
                         layer.name
                     );
                     1
                 } else {
-                    DEFAULT_CHUNK_SIZE
+                    defaults.chunk_size
                 },
                 entity,
             );
+            #[cfg(feature = "audio")]
+            let audio_regions = layer_audio_regions(layer, &buffer, tile_size);
             tilemap
                 .storage
                 .fill_with_buffer(commands, IVec2::ZERO, buffer);
@@ -488,15 +811,30 @@ fn load_layer(
                     }
                 });
             commands.entity(entity).insert(tilemap);
-            loaded_map.layers.insert(layer.id, entity);
+            #[cfg(feature = "audio")]
+            if let Some(audio_regions) = audio_regions {
+                commands.entity(entity).insert(audio_regions);
+            }
+            material_registry.apply_or_default(commands, entity, layer.properties.get("material"));
+            loaded_map.insert_layer(
+                commands,
+                entity,
+                TiledLayerInfo::from_tiles(layer, TiledLayerType::Tiles),
+            );
         }
         TiledLayer::Objects(layer) => {
-            let num_objects = layer.objects.len();
+            if config.trigger_object_layers.contains(&layer.name) {
+                load_trigger_layer(commands, tiled_data, layer, loaded_map);
+                return;
+            }
+
+            let layer_z = *z;
+            let object_z = object_z(layer);
             layer
                 .objects
                 .iter()
-                .enumerate()
-                .for_each(|(index, object)| {
+                .zip(object_z)
+                .for_each(|(object, offset)| {
                     let Some(phantom) = object_registry.get(&object.ty) else {
                         if config.ignore_unregisterd_objects {
                             return;
@@ -521,17 +859,32 @@ fn load_layer(
                         asset_server,
                         tiled_assets,
                     );
+                    #[cfg(feature = "physics")]
+                    if let Some(physics_tile) = config.object_layer_physics.get(&layer.name) {
+                        physics_tile.insert(&mut entity);
+                    }
                     entity.insert(SpatialBundle {
                         transform: Transform::from_xyz(
                             object.x + object.width / 2.,
                             -object.y - object.height / 2.,
-                            *z + index as f32 / (num_objects + 1) as f32,
+                            config
+                                .object_z
+                                .as_ref()
+                                .map_or(layer_z + offset, |f| f(layer, object, layer_z + offset)),
                         ),
                         ..Default::default()
                     });
 
                     loaded_map.objects.insert(object.id, entity.id());
                 });
+
+            // The objects are not children of the layer, this entity only holds the info.
+            let entity = commands.spawn(TilemapName(layer.name.clone())).id();
+            loaded_map.insert_layer(
+                commands,
+                entity,
+                TiledLayerInfo::from_objects(layer, TiledLayerType::Objects),
+            );
         }
         TiledLayer::Image(layer) => {
             let ((mesh, z), material) = (
@@ -548,8 +901,231 @@ fn load_layer(
                 })
                 .id();
 
-            loaded_map.layers.insert(layer.id, entity);
+            loaded_map.insert_layer(commands, entity, TiledLayerInfo::from_image(layer));
         }
         TiledLayer::Other => {}
     }
 }
+
+/// The z offset of each object relative to the layer, according to the draw order.
+///
+/// Objects are drawn from the top to the bottom for [`ObjectDrawOrder::TopDown`],
+/// or in the order they appear for [`ObjectDrawOrder::Index`].
+fn object_z(layer: &ObjectLayer) -> Vec<f32> {
+    let num_objects = layer.objects.len();
+    let mut order = (0..num_objects).collect::<Vec<_>>();
+    if layer.draw_order == ObjectDrawOrder::TopDown {
+        // Stable, so objects with the same y are still in the order they appear.
+        order.sort_by(|a, b| layer.objects[*a].y.total_cmp(&layer.objects[*b].y));
+    }
+
+    let mut z = vec![0.; num_objects];
+    order.into_iter().enumerate().for_each(|(rank, index)| {
+        z[index] = rank as f32 / (num_objects + 1) as f32;
+    });
+    z
+}
+
+/// The number of layers including the ones in the groups.
+fn count_layers(layers: &[TiledLayer], groups: &[TiledGroup]) -> usize {
+    layers.len()
+        + groups
+            .iter()
+            .map(|group| count_layers(&group.layers, &group.groups))
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::{AssetId, Assets, Handle},
+        ecs::{
+            system::Commands,
+            world::{CommandQueue, World},
+        },
+        math::{IVec2, Vec2},
+        utils::HashMap,
+    };
+
+    use crate::{
+        tiled::{
+            components::{TiledLayerInfo, TiledLayerType, TiledLoadedTilemap},
+            events::TiledMapLoader,
+            resources::PackedTiledTilemap,
+            xml::{
+                layer::{ObjectDrawOrder, ObjectLayer},
+                TiledXml,
+            },
+        },
+        tilemap::{map::TilemapTransform, trigger::TileTriggerTilemap},
+    };
+
+    use super::{load_trigger_layer, maps_using_tileset, object_z};
+
+    #[test]
+    fn test_object_draw_order() {
+        let layer = |draw_order: &str| {
+            quick_xml::de::from_str::<ObjectLayer>(&format!(
+                r#"
+                <objectgroup id="2" name="Props" {}>
+                    <object id="1" x="0" y="64" width="16" height="16"/>
+                    <object id="2" x="0" y="16" width="16" height="16"/>
+                    <object id="3" x="0" y="32"/>
+                </objectgroup>
+                "#,
+                draw_order
+            ))
+            .unwrap()
+        };
+
+        let topdown = layer("");
+        assert_eq!(topdown.draw_order, ObjectDrawOrder::TopDown);
+        assert_eq!(object_z(&topdown), vec![0.5, 0., 0.25]);
+
+        let index = layer(r#"draworder="index""#);
+        assert_eq!(index.draw_order, ObjectDrawOrder::Index);
+        assert_eq!(object_z(&index), vec![0., 0.25, 0.5]);
+    }
+
+    #[test]
+    fn test_layer_info() {
+        let layer = quick_xml::de::from_str::<ObjectLayer>(
+            r#"
+            <objectgroup id="3" name="Collision" opacity="0.5" offsetx="4" parallaxy="0.25">
+                <object id="1" x="0" y="0"/>
+            </objectgroup>
+            "#,
+        )
+        .unwrap();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let entity = commands.spawn_empty().id();
+        let mut loaded_map = TiledLoadedTilemap {
+            name: "map".to_string(),
+            layers: HashMap::default(),
+            layer_names: HashMap::default(),
+            objects: HashMap::default(),
+            loader: TiledMapLoader {
+                map: AssetId::default(),
+                trans_ovrd: None,
+                z_ovrd: None,
+                layer_filter: None,
+            },
+        };
+        loaded_map.insert_layer(
+            &mut commands,
+            entity,
+            TiledLayerInfo::from_objects(&layer, TiledLayerType::Trigger),
+        );
+        // Layers with the same name keep the first one.
+        let other = commands.spawn_empty().id();
+        loaded_map.insert_layer(
+            &mut commands,
+            other,
+            TiledLayerInfo {
+                id: 4,
+                ..TiledLayerInfo::from_objects(&layer, TiledLayerType::Objects)
+            },
+        );
+        queue.apply(&mut world);
+
+        assert_eq!(loaded_map.get_layer_by_name("Collision"), Some(entity));
+        assert_eq!(loaded_map.get_layer(3), Some(entity));
+        assert_eq!(loaded_map.get_layer(4), Some(other));
+        assert_eq!(loaded_map.get_layer_by_name("Props"), None);
+        assert_eq!(
+            world.get::<TiledLayerInfo>(other).unwrap().ty,
+            TiledLayerType::Objects
+        );
+
+        let info = world.get::<TiledLayerInfo>(entity).unwrap();
+        assert_eq!(info.ty, TiledLayerType::Trigger);
+        assert_eq!(info.opacity, 0.5);
+        assert_eq!(info.offset, Vec2::new(4., 0.));
+        assert_eq!(info.parallax, Vec2::new(1., 0.25));
+    }
+
+    #[test]
+    fn test_trigger_layer_offset() {
+        let xml = quick_xml::de::from_str::<TiledXml>(
+            &std::fs::read_to_string("assets/tiled/tilemaps/orthogonal.tmx").unwrap(),
+        )
+        .unwrap();
+        let map = PackedTiledTilemap {
+            name: "map".to_string(),
+            path: Default::default(),
+            source: None,
+            xml,
+            tilesets: HashMap::default(),
+        };
+        let layer = quick_xml::de::from_str::<ObjectLayer>(
+            r#"
+            <objectgroup id="5" name="Doors" offsetx="4" offsety="-8">
+                <object id="2" x="8" y="0" width="8" height="8"/>
+            </objectgroup>
+            "#,
+        )
+        .unwrap();
+
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut loaded_map = TiledLoadedTilemap {
+            name: "map".to_string(),
+            layers: HashMap::default(),
+            layer_names: HashMap::default(),
+            objects: HashMap::default(),
+            loader: TiledMapLoader {
+                map: AssetId::default(),
+                trans_ovrd: None,
+                z_ovrd: None,
+                layer_filter: None,
+            },
+        };
+        load_trigger_layer(&mut commands, &map, &layer, &mut loaded_map);
+        queue.apply(&mut world);
+
+        let entity = loaded_map.get_layer(5).unwrap();
+        assert_eq!(
+            world.get::<TilemapTransform>(entity).unwrap().translation,
+            Vec2::new(4., -8.)
+        );
+        let trigger = world.get::<TileTriggerTilemap>(entity).unwrap();
+        assert_eq!(trigger.get(IVec2::new(1, 0)), Some(2));
+        assert_eq!(trigger.get(IVec2::ZERO), None);
+    }
+
+    #[test]
+    fn test_maps_using_tileset() {
+        let xml = quick_xml::de::from_str::<TiledXml>(
+            &std::fs::read_to_string("assets/tiled/tilemaps/orthogonal.tmx").unwrap(),
+        )
+        .unwrap();
+        let (shared, other) = (Handle::weak_from_u128(1), Handle::weak_from_u128(2));
+        let map = |tilesets: Vec<Handle<_>>| PackedTiledTilemap {
+            name: "map".to_string(),
+            path: Default::default(),
+            source: None,
+            xml: xml.clone(),
+            tilesets: tilesets
+                .into_iter()
+                .enumerate()
+                .map(|(i, h)| (i.to_string(), h))
+                .collect(),
+        };
+
+        let mut maps = Assets::<PackedTiledTilemap>::default();
+        let a = maps.add(map(vec![shared.clone()])).id();
+        let b = maps.add(map(vec![other.clone(), shared.clone()])).id();
+        maps.add(map(vec![other.clone()]));
+
+        let mut using = maps_using_tileset(&maps, shared.id()).collect::<Vec<_>>();
+        using.sort();
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(using, expected);
+        assert_eq!(maps_using_tileset(&maps, AssetId::default()).count(), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{f32::consts::PI, path::PathBuf};
+use std::{f32::consts::PI, path::PathBuf, sync::Arc};
 
 use bevy::{
     asset::{io::Reader, Asset, AssetId, AssetLoader, AssetServer, Assets, Handle, LoadContext},
@@ -23,30 +23,60 @@ use crate::{
         events::{TiledMapEvent, TiledMapUnloader},
         sprite::{SpriteUniform, TiledSpriteMaterial},
         xml::{
-            layer::TiledLayer, property::Components, tileset::TiledTileset, MapOrientation,
-            TiledGroup, TiledXml,
+            layer::{ObjectLayer, TiledLayer, TiledObjectInstance},
+            property::Components,
+            tileset::TiledTileset,
+            MapOrientation, TiledGroup, TiledXml,
         },
     },
     tilemap::{
         coordinates,
         map::{TilemapAnimations, TilemapTexture, TilemapTextureDescriptor, TilemapTextures},
-        tile::{RawTileAnimation, TileAnimation},
+        tile::{RawTileAnimation, TileAnimation, TileAnimationMode},
+    },
+    utils::{
+        asset::{self, AssetPath},
+        filter::LayerFilter,
     },
-    utils::asset::AssetPath,
 };
 
+/// Computes the z of an object from the layer, the object and the z
+/// computed according to the draw order of the layer.
+pub type TiledObjectZ = Arc<dyn Fn(&ObjectLayer, &TiledObjectInstance, f32) -> f32 + Send + Sync>;
+
 /// Configuration for loading tiled tilemaps.
 #[derive(Resource, Default, Reflect)]
 pub struct TiledLoadConfig {
+    /// Offsets the z range of the maps from [`TilemapZOrder::base`](crate::tilemap::map::TilemapZOrder::base).
     pub z_index: f32,
     pub ignore_unregisterd_objects: bool,
     pub ignore_unregisterd_custom_tiles: bool,
+    /// Object layers with these names are loaded into a
+    /// [`TileTriggerTilemap`](crate::tilemap::trigger::TileTriggerTilemap) instead of objects.
+    /// The tiles covered by each object become a region, using the object id as the region id.
+    pub trigger_object_layers: Vec<String>,
+    /// Physics settings like collision layers for objects on the object layers
+    /// with these names. Only objects that instantiate their shapes are affected.
+    #[cfg(feature = "physics")]
+    pub object_layer_physics: HashMap<String, crate::tilemap::physics::PhysicsTile>,
+    /// Override the z of objects, so they can sort against things like characters.
+    ///
+    /// By default, objects are placed between the layer and the next one, according to
+    /// the `draworder` of the layer.
+    #[reflect(ignore)]
+    pub object_z: Option<TiledObjectZ>,
+    /// The layers to load. Can be overridden by [`TiledMapLoader::layer_filter`](crate::tiled::events::TiledMapLoader::layer_filter).
+    pub layer_filter: LayerFilter,
 }
 
 #[derive(Asset, Debug, Clone, Reflect)]
 pub struct PackedTiledTilemap {
     pub name: String,
     pub path: PathBuf,
+    /// The asset source this map is loaded from. `None` for the default source.
+    ///
+    /// Tilesets and images are loaded from the same source.
+    pub source: Option<String>,
     pub xml: TiledXml,
     pub tilesets: HashMap<String, Handle<TiledTileset>>,
 }
@@ -78,6 +108,7 @@ impl AssetLoader for TiledTilemapLoader {
         let mut buf = String::new();
         reader.read_to_string(&mut buf).await?;
         let path = load_context.path().to_path_buf();
+        let source = asset::source_of(load_context.asset_path());
         let xml = quick_xml::de::from_str::<TiledXml>(&buf)?;
 
         Ok(PackedTiledTilemap {
@@ -88,11 +119,15 @@ impl AssetLoader for TiledTilemapLoader {
                 .map(|ts| {
                     (
                         ts.source.clone(),
-                        load_context.load(path.parent().unwrap().join(&ts.source)),
+                        load_context.load(asset::with_source(
+                            path.parent().unwrap().join(&ts.source),
+                            source.as_deref(),
+                        )),
                     )
                 })
                 .collect(),
             path,
+            source,
             xml,
         })
     }
@@ -176,6 +211,9 @@ pub struct TiledAssets {
     pub(crate) tilesets: Vec<PackedTiledTileset>,
     pub(crate) tileset_metas: Vec<TiledTilesetMeta>,
     pub(crate) tilemap_data: (Handle<TilemapTextures>, TilemapAnimations),
+    /// The tile size rendered at [`TileRenderSize`](crate::tilemap::map::TileRenderSize),
+    /// see [`TilemapTextures::min_tile_size`].
+    pub(crate) min_tile_size: UVec2,
     /// (mesh_handle, z)
     #[reflect(ignore)]
     pub(crate) image_layer_mesh: HashMap<u32, (Handle<Mesh>, f32)>,
@@ -232,9 +270,16 @@ impl TiledAssets {
         material_assets: &mut Assets<TiledSpriteMaterial>,
         textures_assets: &mut Assets<TilemapTextures>,
         mesh_assets: &mut Assets<Mesh>,
+        filter_mode: FilterMode,
     ) -> Self {
         let mut instance = Self::default();
-        instance.load_tilesets(map, asset_server, textures_assets, tileset_assets);
+        instance.load_tilesets(
+            map,
+            asset_server,
+            textures_assets,
+            tileset_assets,
+            filter_mode,
+        );
         instance.load_layers(map, asset_server, material_assets, mesh_assets);
         instance
     }
@@ -245,6 +290,7 @@ impl TiledAssets {
         asset_server: &AssetServer,
         textures_assets: &mut Assets<TilemapTextures>,
         tileset_assets: &Assets<TiledTileset>,
+        filter_mode: FilterMode,
     ) {
         let mut tilesets_records = HashMap::default();
 
@@ -279,7 +325,10 @@ impl TiledAssets {
                 .unwrap()
                 .join(&tileset_xml.image.source);
             let texture = TilemapTexture {
-                texture: asset_server.load(source_path.to_asset_path()),
+                texture: asset_server.load(asset::with_source(
+                    source_path.to_asset_path(),
+                    map.source.as_deref(),
+                )),
                 desc: TilemapTextureDescriptor {
                     size: UVec2 {
                         x: tileset_xml.image.width,
@@ -289,7 +338,11 @@ impl TiledAssets {
                         x: tileset_xml.tile_width,
                         y: tileset_xml.tile_height,
                     },
+                    padding: 0,
+                    margin: tileset_xml.margin,
+                    spacing: tileset_xml.spacing,
                 },
+                extrusion: 0,
             };
 
             tileset_xml
@@ -307,6 +360,7 @@ impl TiledAssets {
                                 .into_iter()
                                 .map(|frame| (texture_index as u32, frame.tile_id))
                                 .collect(),
+                            mode: TileAnimationMode::Loop,
                         });
                         animated_tiles.insert(atlas_index, anim);
                     }
@@ -339,10 +393,9 @@ impl TiledAssets {
 
         self.tileset_metas = metas;
 
-        self.tilemap_data = (
-            textures_assets.add(TilemapTextures::new(textures, FilterMode::Nearest)),
-            animations,
-        );
+        let textures = TilemapTextures::new(textures, filter_mode);
+        self.min_tile_size = textures.min_tile_size();
+        self.tilemap_data = (textures_assets.add(textures), animations);
     }
 
     fn load_layers(
@@ -422,7 +475,8 @@ impl TiledAssets {
                     .unwrap()
                     .join(&layer.image.source)
                     .to_asset_path();
-                let image = asset_server.load(image_path);
+                let image =
+                    asset_server.load(asset::with_source(image_path, map.source.as_deref()));
                 self.image_layer_materials.insert(
                     layer.id,
                     material_assets.add(TiledSpriteMaterial {
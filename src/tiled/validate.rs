@@ -0,0 +1,271 @@
+//! Check Tiled maps and tilesets for things that make the loader panic or
+//! behave unexpectedly, without running the app.
+//!
+//! This is what the `tiled-validate` binary uses.
+
+use std::path::Path;
+
+use bevy::utils::HashSet;
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::tiled::xml::{
+    layer::{ObjectShape, TiledLayer},
+    tileset::TiledTileset,
+    TiledGroup, TiledXml,
+};
+
+/// The classes registered using [`TiledApp`](crate::tiled::app_ext::TiledApp).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TiledValidationManifest {
+    /// Classes registered using `register_tiled_object`.
+    #[serde(default)]
+    pub objects: HashSet<String>,
+    /// Classes registered using `register_tiled_custom_tile`.
+    #[serde(default)]
+    pub custom_tiles: HashSet<String>,
+    /// Object layers loaded as triggers, whose objects don't need classes.
+    #[serde(default)]
+    pub trigger_layers: HashSet<String>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TiledValidationIssue {
+    #[error("Object {object} in layer {layer} has unregistered class {class:?}")]
+    UnknownObjectClass {
+        layer: String,
+        object: u32,
+        class: String,
+    },
+    #[error("Tile {tile} in tileset {tileset} has unregistered class {class:?}")]
+    UnknownCustomTileClass {
+        tileset: String,
+        tile: u32,
+        class: String,
+    },
+    #[error(
+        "Object {object} in layer {layer} is rotated by {rotation} degrees, \
+        which is ignored by its collider"
+    )]
+    RotatedCollisionObject {
+        layer: String,
+        object: u32,
+        rotation: f32,
+    },
+    #[error(
+        "Layer {layer} is encoded as {encoding:?} with {compression:?} compression, \
+        only uncompressed csv is supported"
+    )]
+    UnsupportedEncoding {
+        layer: String,
+        encoding: Option<String>,
+        compression: Option<String>,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum TiledValidationError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Xml error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("Xml attribute error: {0}")]
+    Attr(#[from] quick_xml::events::attributes::AttrError),
+    #[error("Failed to parse {path}: {error}")]
+    De {
+        path: String,
+        error: quick_xml::DeError,
+    },
+}
+
+/// Validate a tileset file.
+pub fn validate_tileset(
+    path: impl AsRef<Path>,
+    manifest: &TiledValidationManifest,
+) -> Result<Vec<TiledValidationIssue>, TiledValidationError> {
+    let tileset = parse::<TiledTileset>(path.as_ref())?;
+    Ok(tileset_issues(&tileset, manifest))
+}
+
+/// Validate a map file and all the tilesets it uses.
+pub fn validate_map(
+    path: impl AsRef<Path>,
+    manifest: &TiledValidationManifest,
+) -> Result<Vec<TiledValidationIssue>, TiledValidationError> {
+    let path = path.as_ref();
+    let mut issues = encoding_issues(&std::fs::read_to_string(path)?)?;
+    // The tile data can't be parsed at all.
+    if !issues.is_empty() {
+        return Ok(issues);
+    }
+
+    let map = parse::<TiledXml>(path)?;
+    for def in &map.tilesets {
+        let tileset =
+            parse::<TiledTileset>(&path.parent().unwrap_or(Path::new("")).join(&def.source))?;
+        issues.extend(tileset_issues(&tileset, manifest));
+    }
+
+    let mut layers = map.layers.iter().collect::<Vec<_>>();
+    let mut groups = map.groups.iter().collect::<Vec<&TiledGroup>>();
+    while let Some(group) = groups.pop() {
+        layers.extend(group.layers.iter());
+        groups.extend(group.groups.iter());
+    }
+
+    layers.into_iter().for_each(|layer| match layer {
+        TiledLayer::Objects(layer) => {
+            if manifest.trigger_layers.contains(&layer.name) {
+                return;
+            }
+
+            layer.objects.iter().for_each(|object| {
+                if !manifest.objects.contains(&object.ty) {
+                    issues.push(TiledValidationIssue::UnknownObjectClass {
+                        layer: layer.name.clone(),
+                        object: object.id,
+                        class: object.ty.clone(),
+                    });
+                }
+
+                // Only polygons are rotated when spawning colliders.
+                if object.rotation != 0.
+                    && matches!(object.shape, ObjectShape::Rect | ObjectShape::Ellipse)
+                {
+                    issues.push(TiledValidationIssue::RotatedCollisionObject {
+                        layer: layer.name.clone(),
+                        object: object.id,
+                        rotation: object.rotation,
+                    });
+                }
+            });
+        }
+        TiledLayer::Tiles(_) | TiledLayer::Image(_) | TiledLayer::Other => {}
+    });
+
+    Ok(issues)
+}
+
+fn parse<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, TiledValidationError> {
+    quick_xml::de::from_str(&std::fs::read_to_string(path)?).map_err(|error| {
+        TiledValidationError::De {
+            path: path.display().to_string(),
+            error,
+        }
+    })
+}
+
+fn tileset_issues(
+    tileset: &TiledTileset,
+    manifest: &TiledValidationManifest,
+) -> Vec<TiledValidationIssue> {
+    tileset
+        .special_tiles
+        .iter()
+        .filter(|tile| !tile.ty.is_empty() && !manifest.custom_tiles.contains(&tile.ty))
+        .map(|tile| TiledValidationIssue::UnknownCustomTileClass {
+            tileset: tileset.name.clone(),
+            tile: tile.id,
+            class: tile.ty.clone(),
+        })
+        .collect()
+}
+
+/// Scan the raw xml as the tile data is decoded while parsing.
+fn encoding_issues(xml: &str) -> Result<Vec<TiledValidationIssue>, TiledValidationError> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut layer = String::new();
+    let mut issues = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => match e.name().as_ref() {
+                b"layer" => {
+                    layer = e
+                        .try_get_attribute("name")?
+                        .map(|a| a.unescape_value().map(|v| v.to_string()))
+                        .transpose()?
+                        .unwrap_or_default();
+                }
+                b"data" => {
+                    let attr = |name: &str| -> Result<Option<String>, TiledValidationError> {
+                        Ok(e.try_get_attribute(name)?
+                            .map(|a| a.unescape_value().map(|v| v.to_string()))
+                            .transpose()?)
+                    };
+                    let (encoding, compression) = (attr("encoding")?, attr("compression")?);
+                    if encoding.as_deref() != Some("csv") || compression.is_some() {
+                        issues.push(TiledValidationIssue::UnsupportedEncoding {
+                            layer: layer.clone(),
+                            encoding,
+                            compression,
+                        });
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encoding_issues, validate_map, TiledValidationIssue, TiledValidationManifest};
+
+    #[test]
+    fn test_validate() {
+        let mut manifest = TiledValidationManifest::default();
+        manifest.objects.extend(
+            [
+                "DetectAreaBundle",
+                "PointMarker",
+                "PlainBlockBundle",
+                "BlockBundle",
+            ]
+            .map(String::from),
+        );
+
+        let issues = validate_map("assets/tiled/tilemaps/orthogonal.tmx", &manifest).unwrap();
+        assert!(issues.contains(&TiledValidationIssue::UnknownObjectClass {
+            layer: "Player".to_string(),
+            object: 10,
+            class: "PlayerBundle".to_string(),
+        }));
+        assert!(
+            issues.contains(&TiledValidationIssue::UnknownCustomTileClass {
+                tileset: "Tileset1".to_string(),
+                tile: 140,
+                class: "TileBundle".to_string(),
+            })
+        );
+        assert!(
+            issues.contains(&TiledValidationIssue::RotatedCollisionObject {
+                layer: "Tiles".to_string(),
+                object: 7,
+                rotation: 73.12,
+            })
+        );
+        assert!(!issues
+            .iter()
+            .any(|i| matches!(i, TiledValidationIssue::UnsupportedEncoding { .. })));
+
+        let issues = encoding_issues(
+            r#"<map><layer name="Ground"><data encoding="base64" compression="zlib">eJw=</data></layer>
+            <layer name="Walls"><data encoding="csv">1,2</data></layer></map>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            issues,
+            vec![TiledValidationIssue::UnsupportedEncoding {
+                layer: "Ground".to_string(),
+                encoding: Some("base64".to_string()),
+                compression: Some("zlib".to_string()),
+            }]
+        );
+    }
+}
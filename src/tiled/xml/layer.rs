@@ -15,7 +15,11 @@ use serde::{
 use crate::{
     tiled::{
         resources::{PackedTiledTilemap, TiledAssets, TiledCustomTileInstance},
-        xml::{default::*, property::Components, MapOrientation, TiledColor},
+        xml::{
+            default::*,
+            property::{Components, LayerProperties},
+            MapOrientation, TiledColor,
+        },
     },
     tilemap::{
         coordinates,
@@ -42,6 +46,28 @@ pub enum TiledLayer {
     Other,
 }
 
+impl TiledLayer {
+    /// The name of the layer. Empty for unsupported layers.
+    pub fn name(&self) -> &str {
+        match self {
+            TiledLayer::Tiles(layer) => &layer.name,
+            TiledLayer::Objects(layer) => &layer.name,
+            TiledLayer::Image(layer) => &layer.name,
+            TiledLayer::Other => "",
+        }
+    }
+
+    /// The class of the layer. Empty for unsupported layers.
+    pub fn class(&self) -> &str {
+        match self {
+            TiledLayer::Tiles(layer) => &layer.class,
+            TiledLayer::Objects(layer) => &layer.class,
+            TiledLayer::Image(layer) => &layer.class,
+            TiledLayer::Other => "",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
 pub struct ColorTileLayer {
     /// Unique ID of the layer (defaults to 0, with valid
@@ -56,6 +82,11 @@ pub struct ColorTileLayer {
     #[serde(rename = "@name")]
     pub name: String,
 
+    /// The class of the layer. (defaults to “”, since 1.9)
+    #[serde(rename = "@class")]
+    #[serde(default)]
+    pub class: String,
+
     /// The x coordinate of the layer in tiles.
     /// Defaults to 0 and can not be changed in Tiled.
     #[serde(rename = "@x")]
@@ -121,6 +152,13 @@ pub struct ColorTileLayer {
     #[serde(rename = "@height")]
     pub height: u32,
 
+    /// Custom properties of this layer.
+    ///
+    /// A property named `material` assigns a material registered
+    /// using `register_layer_material` to this layer.
+    #[serde(default)]
+    pub properties: LayerProperties,
+
     pub data: ColorTileLayerData,
 }
 
@@ -377,6 +415,11 @@ pub struct ObjectLayer {
     #[serde(rename = "@name")]
     pub name: String,
 
+    /// The class of the layer. (defaults to “”, since 1.9)
+    #[serde(rename = "@class")]
+    #[serde(default)]
+    pub class: String,
+
     /// The x coordinate of the layer in tiles.
     /// Defaults to 0 and can not be changed in Tiled.
     #[serde(rename = "@x")]
@@ -432,10 +475,25 @@ pub struct ObjectLayer {
     #[serde(default = "default_onef")]
     pub parallax_y: f32,
 
+    /// Whether the objects are drawn according to the order of
+    /// appearance (“index”) or sorted by their y-coordinate
+    /// (“topdown”). (defaults to “topdown”)
+    #[serde(rename = "@draworder")]
+    #[serde(default)]
+    pub draw_order: ObjectDrawOrder,
+
     #[serde(rename = "object")]
     pub objects: Vec<TiledObjectInstance>,
 }
 
+#[derive(Debug, Clone, Copy, Default, Reflect, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectDrawOrder {
+    #[default]
+    TopDown,
+    Index,
+}
+
 #[derive(Debug, Clone, Reflect, Serialize)]
 pub struct TiledObjectInstance {
     /// Unique ID of the object (defaults to 0,
@@ -716,6 +774,11 @@ pub struct ImageLayer {
     #[serde(rename = "@name")]
     pub name: String,
 
+    /// The class of the layer. (defaults to “”, since 1.9)
+    #[serde(rename = "@class")]
+    #[serde(default)]
+    pub class: String,
+
     /// The x coordinate of the layer in tiles.
     /// Defaults to 0 and can not be changed in Tiled.
     #[serde(rename = "@x")]
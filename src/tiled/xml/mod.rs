@@ -275,6 +275,11 @@ pub struct TiledGroup {
     #[serde(rename = "@name")]
     pub name: String,
 
+    /// The class of the layer. (defaults to “”, since 1.9)
+    #[serde(rename = "@class")]
+    #[serde(default)]
+    pub class: String,
+
     /// The x coordinate of the layer in tiles.
     /// Defaults to 0 and can not be changed in Tiled.
     #[serde(rename = "@x")]
@@ -12,6 +12,32 @@ pub struct Components {
     pub instances: Vec<ClassInstance>,
 }
 
+/// Plain properties attached to layers.
+///
+/// Only the name and the raw value are kept, class properties are ignored.
+#[derive(Debug, Default, Clone, Reflect, Serialize, Deserialize)]
+pub struct LayerProperties {
+    #[serde(rename = "property", default)]
+    pub instances: Vec<LayerProperty>,
+}
+
+impl LayerProperties {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.instances
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct LayerProperty {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@value", default)]
+    pub value: String,
+}
+
 #[derive(Debug, Clone, Reflect, Serialize)]
 pub struct ClassInstance {
     pub name: String,
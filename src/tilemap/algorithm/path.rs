@@ -22,15 +22,22 @@ impl Tiles for PathTile {}
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct PathTilemap {
     pub(crate) storage: PathTileChunkedStorage,
+    /// The tiles removed since the last check against the paths.
+    /// Only tracked once inserted into [`PathTilemaps`](crate::algorithm::pathfinding::PathTilemaps),
+    /// which drains it every frame.
+    #[cfg_attr(feature = "serializing", serde(skip))]
+    #[reflect(ignore)]
+    pub(crate) removed: Option<Vec<IVec2>>,
 }
 
 impl PathTilemap {
     /// Create a new path tilemap with default chunk size.
-    /// 
+    ///
     /// Use `new_with_chunk_size` to create a path tilemap with custom chunk size.
     pub fn new() -> Self {
         Self {
             storage: ChunkedStorage::default(),
+            removed: None,
         }
     }
 
@@ -38,6 +45,7 @@ impl PathTilemap {
     pub fn new_with_chunk_size(chunk_size: u32) -> Self {
         Self {
             storage: ChunkedStorage::new(chunk_size),
+            removed: None,
         }
     }
 
@@ -53,8 +61,16 @@ impl PathTilemap {
         self.storage.set_elem(index, tile)
     }
 
+    /// Remove the tile, which makes it not walkable.
+    ///
+    /// Agents whose paths pass through this tile will receive
+    /// [`PathBlocked`](crate::algorithm::pathfinding::PathBlocked) events.
     pub fn remove(&mut self, index: IVec2) -> Option<PathTile> {
-        self.storage.remove_elem(index)
+        let tile = self.storage.remove_elem(index);
+        if let (Some(removed), Some(_)) = (&mut self.removed, tile) {
+            removed.push(index);
+        }
+        tile
     }
 
     /// Set path-finding data using a custom function.
@@ -0,0 +1,418 @@
+//! Looping spatial sounds emitted by regions of tiles, like rivers, machines or ambient zones.
+//!
+//! Insert [`TilemapAudioRegions`] to a tilemap and a [`SpatialListener`] to your camera or player.
+//! Each region is played from its closest point to the listener once the listener is in range,
+//! and attenuated by Bevy's spatial audio.
+
+use bevy::{
+    app::{Plugin, Update},
+    asset::AssetServer,
+    audio::{
+        AudioBundle, AudioSinkPlayback, PlaybackSettings, SpatialAudioSink, SpatialListener, Volume,
+    },
+    ecs::{
+        change_detection::{DetectChanges, DetectChangesMut},
+        component::Component,
+        entity::Entity,
+        observer::Trigger,
+        query::{Changed, With},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res},
+        world::Ref,
+    },
+    math::{IVec2, Rect, Vec2},
+    reflect::Reflect,
+    transform::{
+        bundles::TransformBundle,
+        components::{GlobalTransform, Transform},
+    },
+};
+
+use crate::{
+    math::GridRect,
+    tilemap::{
+        coordinates,
+        despawn::EverythingUnloaded,
+        map::{TilemapAxisFlip, TilemapSlotSize, TilemapTransform, TilemapType},
+    },
+};
+
+pub struct EntiTilesAudioPlugin;
+
+impl Plugin for EntiTilesAudioPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            (audio_emitter_spawner, audio_emitter_updater).chain(),
+        )
+        .register_type::<TileAudioArea>()
+        .register_type::<TileAudioRegion>()
+        .register_type::<TilemapAudioRegions>()
+        .register_type::<TileAudioEmitter>()
+        .observe(audio_emitter_unloader);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileAudioArea {
+    Rect(GridRect),
+    Tiles(Vec<IVec2>),
+}
+
+/// A looping sound emitted by some tiles.
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileAudioRegion {
+    pub area: TileAudioArea,
+    /// The path to the audio, relative to the assets folder.
+    pub source: String,
+    /// The sound is paused if the listener is farther than this, in world space.
+    ///
+    /// **Notice**: The sound is attenuated by Bevy's spatial audio, not by this range.
+    /// Use [`SpatialScale`](bevy::audio::SpatialScale) to control how fast it fades out.
+    pub range: f32,
+    pub volume: f32,
+}
+
+impl TileAudioRegion {
+    pub fn rect(source: impl Into<String>, rect: GridRect, range: f32) -> Self {
+        Self {
+            area: TileAudioArea::Rect(rect),
+            source: source.into(),
+            range,
+            volume: 1.,
+        }
+    }
+
+    pub fn tiles(source: impl Into<String>, tiles: Vec<IVec2>, range: f32) -> Self {
+        Self {
+            area: TileAudioArea::Tiles(tiles),
+            source: source.into(),
+            range,
+            volume: 1.,
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// Get the world space aabbs of this region. A rect area has only one aabb.
+    ///
+    /// **Notice**: For isometric and hexagonal tilemaps, tiles are treated as their bounding boxes.
+    pub fn aabbs(
+        &self,
+        ty: TilemapType,
+        transform: &TilemapTransform,
+        axis_flip: TilemapAxisFlip,
+        slot_size: Vec2,
+    ) -> Vec<Rect> {
+        let aabb = |min: IVec2, max: IVec2| {
+            let (min, max) = (
+                coordinates::flip_index(min, axis_flip),
+                coordinates::flip_index(max, axis_flip),
+            );
+            let (min, max) = (min.min(max), min.max(max) + 1);
+            Rect::from_corners(
+                coordinates::index_to_world(min, ty, transform, Vec2::ZERO, slot_size),
+                coordinates::index_to_world(max, ty, transform, Vec2::ZERO, slot_size),
+            )
+        };
+
+        match &self.area {
+            TileAudioArea::Rect(rect) => {
+                if rect.extent.x > 0 && rect.extent.y > 0 {
+                    vec![aabb(rect.origin, rect.dest)]
+                } else {
+                    vec![]
+                }
+            }
+            TileAudioArea::Tiles(tiles) => tiles.iter().map(|index| aabb(*index, *index)).collect(),
+        }
+    }
+
+    /// Get the closest point in this region to `point` in world space.
+    ///
+    /// **Notice**: For isometric and hexagonal tilemaps, tiles are treated as their bounding boxes.
+    pub fn closest_point(
+        &self,
+        point: Vec2,
+        ty: TilemapType,
+        transform: &TilemapTransform,
+        axis_flip: TilemapAxisFlip,
+        slot_size: Vec2,
+    ) -> Option<Vec2> {
+        closest_point(&self.aabbs(ty, transform, axis_flip, slot_size), point)
+    }
+}
+
+fn closest_point(aabbs: &[Rect], point: Vec2) -> Option<Vec2> {
+    aabbs
+        .iter()
+        .map(|aabb| point.clamp(aabb.min, aabb.max))
+        .min_by(|a, b| {
+            a.distance_squared(point)
+                .total_cmp(&b.distance_squared(point))
+        })
+}
+
+/// The sound regions of a tilemap. Modifying this respawns all the emitters.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapAudioRegions {
+    pub regions: Vec<TileAudioRegion>,
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serializing", serde(skip))]
+    pub(crate) emitters: Vec<Entity>,
+}
+
+impl TilemapAudioRegions {
+    pub fn new(regions: Vec<TileAudioRegion>) -> Self {
+        Self {
+            regions,
+            emitters: Vec::new(),
+        }
+    }
+
+    pub fn with_region(mut self, region: TileAudioRegion) -> Self {
+        self.regions.push(region);
+        self
+    }
+}
+
+/// The entity playing the sound of a region.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TileAudioEmitter {
+    pub tilemap: Entity,
+    pub region: usize,
+    /// The aabbs of the region, updated when the tilemap moves.
+    #[reflect(ignore)]
+    pub(crate) aabbs: Vec<Rect>,
+    /// The union of `aabbs`.
+    #[reflect(ignore)]
+    pub(crate) bounds: Option<Rect>,
+}
+
+impl TileAudioEmitter {
+    fn update_aabbs(
+        &mut self,
+        region: &TileAudioRegion,
+        ty: TilemapType,
+        transform: &TilemapTransform,
+        axis_flip: TilemapAxisFlip,
+        slot_size: Vec2,
+    ) {
+        self.aabbs = region.aabbs(ty, transform, axis_flip, slot_size);
+        self.bounds = self.aabbs.iter().copied().reduce(|a, b| a.union(b));
+    }
+}
+
+pub fn audio_emitter_spawner(
+    mut commands: Commands,
+    mut tilemaps_query: Query<
+        (
+            Entity,
+            &mut TilemapAudioRegions,
+            &TilemapType,
+            &TilemapTransform,
+            &TilemapSlotSize,
+            Option<&TilemapAxisFlip>,
+        ),
+        Changed<TilemapAudioRegions>,
+    >,
+    asset_server: Res<AssetServer>,
+) {
+    tilemaps_query.iter_mut().for_each(
+        |(tilemap, mut regions, ty, transform, slot_size, axis_flip)| {
+            let regions = regions.bypass_change_detection();
+            regions.emitters.drain(..).for_each(|emitter| {
+                if let Some(mut e) = commands.get_entity(emitter) {
+                    e.despawn();
+                }
+            });
+
+            regions.emitters = regions
+                .regions
+                .iter()
+                .enumerate()
+                .map(|(index, region)| {
+                    let mut emitter = TileAudioEmitter {
+                        tilemap,
+                        region: index,
+                        aabbs: Vec::new(),
+                        bounds: None,
+                    };
+                    emitter.update_aabbs(
+                        region,
+                        *ty,
+                        transform,
+                        axis_flip.copied().unwrap_or_default(),
+                        slot_size.0,
+                    );
+
+                    commands
+                        .spawn((
+                            AudioBundle {
+                                source: asset_server.load(region.source.clone()),
+                                settings: PlaybackSettings {
+                                    paused: true,
+                                    ..PlaybackSettings::LOOP
+                                        .with_spatial(true)
+                                        .with_volume(Volume::new(0.))
+                                },
+                            },
+                            TransformBundle::default(),
+                            emitter,
+                        ))
+                        .id()
+                })
+                .collect();
+        },
+    );
+}
+
+pub fn audio_emitter_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut commands: Commands,
+    emitters_query: Query<Entity, With<TileAudioEmitter>>,
+) {
+    emitters_query.iter().for_each(|emitter| {
+        commands.entity(emitter).despawn();
+    });
+}
+
+pub fn audio_emitter_updater(
+    mut commands: Commands,
+    listeners_query: Query<&GlobalTransform, With<SpatialListener>>,
+    tilemaps_query: Query<(
+        &TilemapAudioRegions,
+        &TilemapType,
+        Ref<TilemapTransform>,
+        Ref<TilemapSlotSize>,
+        Option<Ref<TilemapAxisFlip>>,
+    )>,
+    mut emitters_query: Query<(
+        Entity,
+        &mut TileAudioEmitter,
+        &mut Transform,
+        Option<&SpatialAudioSink>,
+    )>,
+) {
+    let Some(listener) = listeners_query
+        .iter()
+        .next()
+        .map(|t| t.translation().truncate())
+    else {
+        return;
+    };
+
+    emitters_query
+        .iter_mut()
+        .for_each(|(entity, mut emitter, mut transform, sink)| {
+            let Ok((regions, ty, tilemap_transform, slot_size, axis_flip)) =
+                tilemaps_query.get(emitter.tilemap)
+            else {
+                commands.entity(entity).despawn();
+                return;
+            };
+            let Some(region) = regions.regions.get(emitter.region) else {
+                return;
+            };
+
+            if tilemap_transform.is_changed()
+                || slot_size.is_changed()
+                || axis_flip.as_ref().is_some_and(|f| f.is_changed())
+            {
+                emitter.update_aabbs(
+                    region,
+                    *ty,
+                    &tilemap_transform,
+                    axis_flip.map(|f| *f).unwrap_or_default(),
+                    slot_size.0,
+                );
+            }
+
+            // Only look for the closest tile when the listener is close to the region.
+            let in_range = emitter.bounds.is_some_and(|bounds| {
+                listener.clamp(bounds.min, bounds.max).distance(listener) <= region.range
+            });
+            let closest = in_range
+                .then(|| closest_point(&emitter.aabbs, listener))
+                .flatten()
+                .filter(|p| p.distance(listener) <= region.range);
+            if let Some(closest) = closest {
+                transform.translation = closest.extend(transform.translation.z);
+            }
+
+            let Some(sink) = sink else {
+                return;
+            };
+            if closest.is_none() {
+                if !sink.is_paused() {
+                    sink.pause();
+                }
+            } else if sink.is_paused() {
+                sink.set_volume(region.volume);
+                sink.play();
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{IVec2, UVec2, Vec2};
+
+    use crate::{
+        math::GridRect,
+        tilemap::map::{TilemapAxisFlip, TilemapTransform, TilemapType},
+    };
+
+    use super::TileAudioRegion;
+
+    #[test]
+    fn test_closest_point() {
+        let transform = TilemapTransform::default();
+        let slot_size = Vec2::splat(16.);
+
+        let river = TileAudioRegion::rect(
+            "river.ogg",
+            GridRect::new(IVec2::new(0, 2), UVec2::new(10, 2)),
+            100.,
+        );
+        let closest = |region: &TileAudioRegion, p: Vec2, flip: TilemapAxisFlip| {
+            region.closest_point(p, TilemapType::Square, &transform, flip, slot_size)
+        };
+        assert_eq!(
+            closest(&river, Vec2::new(40., 0.), TilemapAxisFlip::NONE),
+            Some(Vec2::new(40., 32.))
+        );
+        assert_eq!(
+            closest(&river, Vec2::new(40., 40.), TilemapAxisFlip::NONE),
+            Some(Vec2::new(40., 40.))
+        );
+        assert_eq!(
+            closest(&river, Vec2::new(40., 0.), TilemapAxisFlip::Y),
+            Some(Vec2::new(40., -32.))
+        );
+
+        let machines = TileAudioRegion::tiles(
+            "machine.ogg",
+            vec![IVec2::new(0, 0), IVec2::new(5, 0)],
+            100.,
+        );
+        assert_eq!(
+            closest(&machines, Vec2::new(100., 8.), TilemapAxisFlip::NONE),
+            Some(Vec2::new(96., 8.))
+        );
+        assert_eq!(
+            closest(
+                &TileAudioRegion::tiles("empty.ogg", vec![], 100.),
+                Vec2::ZERO,
+                TilemapAxisFlip::NONE
+            ),
+            None
+        );
+    }
+}
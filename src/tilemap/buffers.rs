@@ -23,6 +23,13 @@ pub type PackedPhysicsTileBuffer = TileBuffer<crate::tilemap::physics::PackedPhy
 #[derive(Debug, Clone, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileBuffer<T: Tiles> {
+    #[cfg_attr(
+        feature = "serializing",
+        serde(
+            serialize_with = "crate::serializing::serialize_grid_map",
+            bound(serialize = "T: serde::Serialize")
+        )
+    )]
     pub(crate) tiles: HashMap<IVec2, T>,
     pub(crate) aabb: GridRect,
 }
@@ -73,6 +80,15 @@ impl<T: Tiles> TileBuffer<T> {
         self.tiles.is_empty()
     }
 
+    /// Iterate over the tiles sorted by their `y` and then `x`.
+    ///
+    /// Unlike iterating over the inner map, the order is deterministic.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        let mut tiles = self.tiles.iter().map(|(i, t)| (*i, t)).collect::<Vec<_>>();
+        tiles.sort_unstable_by_key(|(index, _)| (index.y, index.x));
+        tiles.into_iter()
+    }
+
     #[inline]
     pub fn aabb(&self) -> GridRect {
         self.aabb
@@ -1,16 +1,16 @@
 use bevy::{
     ecs::{
+        change_detection::DetectChanges,
         component::Component,
         entity::Entity,
         event::{Event, EventWriter},
-        query::{Changed, Or},
-        system::Query,
+        system::{Query, Res},
+        world::Ref,
     },
-    math::{IVec2, Vec2},
+    math::{IVec2, Rect, Vec2},
     reflect::Reflect,
-    render::camera::OrthographicProjection,
-    transform::components::Transform,
-    utils::HashSet,
+    time::Time,
+    utils::{HashMap, HashSet},
 };
 
 use crate::{
@@ -25,12 +25,36 @@ pub enum CameraChunkUpdation {
     Left(Entity, IVec2),
 }
 
+/// An event that contains all the chunks entered and left in one updation
+/// of a single updater on a single tilemap.
+///
+/// It's sent along with [`CameraChunkUpdation`]s, prefer this one if you
+/// need to handle loading and unloading together, or have multiple updaters.
+#[derive(Event, Debug, Clone, Reflect)]
+pub struct CameraChunkSetUpdation {
+    pub updater: Entity,
+    pub tilemap: Entity,
+    pub entered: Vec<IVec2>,
+    pub left: Vec<IVec2>,
+}
+
 /// A component that is used to monitor the camera behavior.
+///
+/// It can be either inserted on the camera, or on any other entity using
+/// [`CameraChunkUpdater::with_camera`]. So you can have multiple updaters
+/// with different scales following one camera.
 #[derive(Component, Debug, Clone, Reflect)]
 pub struct CameraChunkUpdater {
+    pub(crate) camera: Option<Entity>,
     pub(crate) detect_scale: f32,
     pub(crate) update_scale: f32,
-    pub(crate) last_updation: HashSet<IVec2>,
+    pub(crate) unload_scale: f32,
+    pub(crate) keep_alive: f32,
+    pub(crate) threshold: f32,
+    pub(crate) last_aabb: Option<Rect>,
+    pub(crate) last_updation: HashMap<Entity, HashSet<IVec2>>,
+    /// Chunks that are outside the unload aabb, and when they left.
+    pub(crate) pending_left: HashMap<Entity, HashMap<IVec2, f32>>,
 }
 
 impl CameraChunkUpdater {
@@ -47,67 +71,199 @@ impl CameraChunkUpdater {
         );
 
         Self {
+            camera: None,
             detect_scale,
             update_scale,
-            last_updation: HashSet::new(),
+            unload_scale: update_scale,
+            keep_alive: 0.,
+            threshold: 0.5,
+            last_aabb: None,
+            last_updation: HashMap::new(),
+            pending_left: HashMap::new(),
         }
     }
+
+    /// Chunks will only be considered left when they are outside the camera aabb
+    /// scaled by `unload_scale`, instead of `update_scale`.
+    ///
+    /// This adds hysteresis between loading and unloading, so chunks won't
+    /// be loaded and unloaded repeatedly when the camera is moving around the boundary.
+    /// Defaults to `update_scale`.
+    pub fn with_unload_scale(mut self, unload_scale: f32) -> Self {
+        assert!(
+            unload_scale >= self.update_scale,
+            "unload_scale must be >= update_scale!"
+        );
+
+        self.unload_scale = unload_scale;
+        self
+    }
+
+    /// Chunks will only be considered left after staying outside the unload aabb
+    /// for `seconds`. Entering the unload aabb again during this time cancels it.
+    /// Defaults to 0.
+    pub fn with_keep_alive(mut self, seconds: f32) -> Self {
+        self.keep_alive = seconds;
+        self
+    }
+
+    /// Follow another camera instead of the entity this updater is attached to.
+    pub fn with_camera(mut self, camera: Entity) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    /// If the camera aabb moves or resizes more than `threshold` times its size
+    /// since the last updation, all the chunks will be diffed again, instead of
+    /// only checking if there's new chunk inside the detect aabb.
+    ///
+    /// This makes sure chunks are unloaded after teleporting or zooming in.
+    /// Defaults to 0.5.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    #[inline]
+    pub fn camera(&self) -> Option<Entity> {
+        self.camera
+    }
+
+    /// Chunks that are currently considered visible on `tilemap`.
+    #[inline]
+    pub fn visible_chunks(&self, tilemap: Entity) -> Option<&HashSet<IVec2>> {
+        self.last_updation.get(&tilemap)
+    }
+
+    fn exceeds_threshold(&self, aabb: Rect) -> bool {
+        let Some(last) = self.last_aabb else {
+            return true;
+        };
+
+        let tolerance = last.size().min_element() * self.threshold;
+        (aabb.min - last.min).abs().max_element() > tolerance
+            || (aabb.max - last.max).abs().max_element() > tolerance
+    }
 }
 
 pub fn camera_chunk_update(
-    mut camera_query: Query<
-        (&CameraAabb2d, &mut CameraChunkUpdater),
-        Or<(Changed<OrthographicProjection>, Changed<Transform>)>,
-    >,
-    mut tilemaps_query: Query<(Entity, &TilemapStorage)>,
+    mut updaters_query: Query<(Entity, &mut CameraChunkUpdater)>,
+    cameras_query: Query<Ref<CameraAabb2d>>,
+    tilemaps_query: Query<(Entity, &TilemapStorage)>,
+    time: Res<Time>,
     mut updation_event: EventWriter<CameraChunkUpdation>,
+    mut set_updation_event: EventWriter<CameraChunkSetUpdation>,
 ) {
-    camera_query
+    let now = time.elapsed_seconds();
+
+    updaters_query
         .iter_mut()
-        .for_each(|(cam_aabb, mut cam_updater)| {
-            tilemaps_query.iter_mut().for_each(|(entity, storage)| {
-                // When the detect aabb is intersected with a invisible chunk,
-                // all the chunks that are intercected with the update aabb must be visible.
-
-                // Which means we need to first detect the chunks that are intersected with the detect aabb,
-                // and if every one is visible, then do nothing else load/generate chunks that are intersected with the update aabb.
-
-                let detect_aabb =
-                    cam_aabb.with_scale(Vec2::splat(cam_updater.detect_scale), Vec2::splat(0.5));
-
-                let detected = storage
-                    .reserved
-                    .iter()
-                    .filter_map(|(chunk_index, aabb)| {
-                        if !detect_aabb.intersect(*aabb).is_empty() {
-                            Some(*chunk_index)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<HashSet<_>>();
-
-                if detected.is_subset(&cam_updater.last_updation) {
-                    return;
-                }
+        .for_each(|(updater_entity, mut cam_updater)| {
+            let Ok(cam_aabb) = cameras_query.get(cam_updater.camera.unwrap_or(updater_entity))
+            else {
+                return;
+            };
+
+            let cam_changed = cam_aabb.is_changed() || cam_updater.is_added();
+            if !cam_changed && cam_updater.pending_left.values().all(|p| p.is_empty()) {
+                return;
+            }
+
+            let cam_updater = &mut *cam_updater;
+            let full_diff = cam_changed && cam_updater.exceeds_threshold(**cam_aabb);
+            let detect_aabb =
+                cam_aabb.with_scale(Vec2::splat(cam_updater.detect_scale), Vec2::splat(0.5));
+            let update_aabb =
+                cam_aabb.with_scale(Vec2::splat(cam_updater.update_scale), Vec2::splat(0.5));
+            let unload_aabb =
+                cam_aabb.with_scale(Vec2::splat(cam_updater.unload_scale), Vec2::splat(0.5));
+
+            cam_updater
+                .last_updation
+                .retain(|tilemap, _| tilemaps_query.contains(*tilemap));
+            cam_updater
+                .pending_left
+                .retain(|tilemap, _| tilemaps_query.contains(*tilemap));
+
+            tilemaps_query.iter().for_each(|(entity, storage)| {
+                let last_updation = cam_updater.last_updation.entry(entity).or_default();
+                let pending_left = cam_updater.pending_left.entry(entity).or_default();
+                let is_outside = |chunk_index: &IVec2| {
+                    storage
+                        .reserved
+                        .get(chunk_index)
+                        .map_or(true, |aabb| unload_aabb.intersect(*aabb).is_empty())
+                };
+                let mut entered = Vec::new();
+
+                if cam_changed {
+                    // Chunks that are back inside the unload aabb are kept alive.
+                    pending_left.retain(|chunk_index, _| is_outside(chunk_index));
 
-                let update_aabb =
-                    cam_aabb.with_scale(Vec2::splat(cam_updater.update_scale), Vec2::splat(0.5));
+                    // When the detect aabb is intersected with a invisible chunk,
+                    // all the chunks that are intercected with the update aabb must be visible.
 
-                let mut cur_visible = HashSet::with_capacity(cam_updater.last_updation.len());
+                    // Which means we need to first detect the chunks that are intersected with the detect aabb,
+                    // and if every one is visible, then do nothing else load/generate chunks that are intersected with the update aabb.
 
-                storage.reserved.iter().for_each(|(chunk_index, aabb)| {
-                    if !update_aabb.intersect(*aabb).is_empty() {
-                        if !cam_updater.last_updation.contains(chunk_index) {
-                            updation_event.send(CameraChunkUpdation::Entered(entity, *chunk_index));
-                        }
-                        cur_visible.insert(*chunk_index);
-                    } else if cam_updater.last_updation.contains(chunk_index) {
-                        updation_event.send(CameraChunkUpdation::Left(entity, *chunk_index));
+                    // But if the camera moved too far, chunks that are not in the unload aabb
+                    // anymore must be unloaded, so we diff all of them.
+                    if full_diff
+                        || storage.reserved.iter().any(|(chunk_index, aabb)| {
+                            !detect_aabb.intersect(*aabb).is_empty()
+                                && !last_updation.contains(chunk_index)
+                        })
+                    {
+                        storage.reserved.iter().for_each(|(chunk_index, aabb)| {
+                            if !update_aabb.intersect(*aabb).is_empty()
+                                && last_updation.insert(*chunk_index)
+                            {
+                                entered.push(*chunk_index);
+                            }
+                        });
+
+                        last_updation.iter().for_each(|chunk_index| {
+                            if is_outside(chunk_index) {
+                                pending_left.entry(*chunk_index).or_insert(now);
+                            }
+                        });
                     }
+                }
+
+                let mut left = Vec::new();
+                pending_left.retain(|chunk_index, since| {
+                    if now - *since < cam_updater.keep_alive {
+                        return true;
+                    }
+
+                    last_updation.remove(chunk_index);
+                    left.push(*chunk_index);
+                    false
                 });
 
-                cam_updater.last_updation = cur_visible;
+                if left.is_empty() && entered.is_empty() {
+                    return;
+                }
+
+                updation_event.send_batch(
+                    left.iter()
+                        .map(|c| CameraChunkUpdation::Left(entity, *c))
+                        .chain(
+                            entered
+                                .iter()
+                                .map(|c| CameraChunkUpdation::Entered(entity, *c)),
+                        ),
+                );
+                set_updation_event.send(CameraChunkSetUpdation {
+                    updater: updater_entity,
+                    tilemap: entity,
+                    entered,
+                    left,
+                });
             });
+
+            if full_diff {
+                cam_updater.last_aabb = Some(**cam_aabb);
+            }
         });
 }
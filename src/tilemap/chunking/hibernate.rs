@@ -0,0 +1,375 @@
+//! Keep far chunks compressed in memory instead of as tile entities.
+//!
+//! This is an alternative to saving chunks to the disk for medium sized worlds,
+//! which are too large to keep every tile alive, but small enough to fit in memory.
+
+use bevy::{
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        event::EventWriter,
+        observer::Trigger,
+        system::{Commands, Query},
+        world::Ref,
+    },
+    math::{IVec2, UVec2},
+    reflect::Reflect,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    math::CameraAabb2d,
+    render::chunk::ChunkUnload,
+    tilemap::{
+        buffers::TileBuilderBuffer,
+        despawn::EverythingUnloaded,
+        map::TilemapStorage,
+        tile::{Tile, TileBuilder},
+    },
+};
+
+/// A chunk compressed into a palette of distinct tiles and runs of the same slots.
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct HibernatedChunk {
+    pub(crate) palette: Vec<TileBuilder>,
+    /// The length of each run, and the index in the palette. `None` for empty slots.
+    pub(crate) runs: Vec<(u32, Option<u32>)>,
+}
+
+impl HibernatedChunk {
+    /// Compress the slots of a chunk, in the same order as the storage.
+    pub fn compress(tiles: impl Iterator<Item = Option<TileBuilder>>) -> Self {
+        let mut chunk = HibernatedChunk::default();
+
+        for tile in tiles {
+            let entry = tile.map(|tile| match chunk.palette.iter().position(|t| *t == tile) {
+                Some(i) => i as u32,
+                None => {
+                    chunk.palette.push(tile);
+                    chunk.palette.len() as u32 - 1
+                }
+            });
+
+            match chunk.runs.last_mut() {
+                Some((len, last)) if *last == entry => *len += 1,
+                _ => chunk.runs.push((1, entry)),
+            }
+        }
+
+        chunk
+    }
+
+    /// Get the tile at `in_chunk_index` without decompressing the whole chunk.
+    pub fn get(&self, in_chunk_index: usize) -> Option<&TileBuilder> {
+        let mut start = 0;
+        for (len, entry) in &self.runs {
+            start += *len as usize;
+            if in_chunk_index < start {
+                return entry.map(|i| &self.palette[i as usize]);
+            }
+        }
+        None
+    }
+
+    /// Empty the slot at `in_chunk_index`.
+    pub fn remove(&mut self, in_chunk_index: usize) {
+        let len = self
+            .runs
+            .iter()
+            .map(|(len, _)| *len as usize)
+            .sum::<usize>();
+        *self = HibernatedChunk::compress((0..len).map(|slot| {
+            if slot == in_chunk_index {
+                None
+            } else {
+                self.get(slot).cloned()
+            }
+        }));
+    }
+
+    /// Iterate over the non-empty slots and their in chunk indices.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &TileBuilder)> {
+        let palette = &self.palette;
+        self.runs
+            .iter()
+            .scan(0, |start, (len, entry)| {
+                let range = *start..*start + *len as usize;
+                *start = range.end;
+                Some((range, *entry))
+            })
+            .filter_map(move |(range, entry)| {
+                entry.map(|i| range.map(move |slot| (slot, &palette[i as usize])))
+            })
+            .flatten()
+    }
+
+    /// The number of distinct tiles.
+    #[inline]
+    pub fn palette_len(&self) -> usize {
+        self.palette.len()
+    }
+
+    /// The number of runs.
+    #[inline]
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+}
+
+/// Add this to a tilemap to hibernate the chunks that are far from every camera.
+///
+/// Chunks whose aabbs are further than `radius` away from the aabbs of all cameras are
+/// compressed into [`HibernatedChunk`]s and their tile entities are despawned.
+/// They are restored when they get within the radius again, or you call [`TilemapHibernation::wake`].
+///
+/// Tiles in hibernated chunks are not in the [`TilemapStorage`], so use
+/// [`TilemapHibernation::get_and_wake`] and [`TilemapHibernation::remove`] to access them.
+/// Tiles set while hibernating are kept when the chunk wakes up.
+///
+/// **Notice**: Only the color layer is hibernated. Path and physics tiles are not affected.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapHibernation {
+    pub radius: f32,
+    #[reflect(ignore)]
+    pub(crate) chunks: HashMap<IVec2, HibernatedChunk>,
+    #[reflect(ignore)]
+    pub(crate) wake_queue: HashSet<IVec2>,
+}
+
+impl TilemapHibernation {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            chunks: HashMap::default(),
+            wake_queue: HashSet::default(),
+        }
+    }
+
+    /// Returns `true` if the chunk is compressed.
+    #[inline]
+    pub fn is_hibernating(&self, chunk_index: IVec2) -> bool {
+        self.chunks.contains_key(&chunk_index)
+    }
+
+    #[inline]
+    pub fn get_chunk(&self, chunk_index: IVec2) -> Option<&HibernatedChunk> {
+        self.chunks.get(&chunk_index)
+    }
+
+    /// Get a tile in a hibernated chunk without waking it up.
+    ///
+    /// Use [`TilemapStorage::get`] for tiles in the chunks that are awake.
+    pub fn get(&self, storage: &TilemapStorage, index: IVec2) -> Option<&TileBuilder> {
+        let (chunk_index, in_chunk_index) = storage.storage.transform_index(index);
+        self.chunks.get(&chunk_index)?.get(in_chunk_index)
+    }
+
+    /// Get a tile in a hibernated chunk, and restore the chunk next frame
+    /// so the tile is in the storage again.
+    pub fn get_and_wake(&mut self, storage: &TilemapStorage, index: IVec2) -> Option<TileBuilder> {
+        let (chunk_index, in_chunk_index) = storage.storage.transform_index(index);
+        let tile = self.chunks.get(&chunk_index)?.get(in_chunk_index).cloned();
+        self.wake(chunk_index);
+        tile
+    }
+
+    /// Remove a tile, whether its chunk is hibernating or not.
+    ///
+    /// Use this instead of [`TilemapStorage::remove`], otherwise tiles removed from
+    /// hibernated chunks come back when they wake up.
+    pub fn remove(&mut self, commands: &mut Commands, storage: &mut TilemapStorage, index: IVec2) {
+        let (chunk_index, in_chunk_index) = storage.storage.transform_index(index);
+        if let Some(chunk) = self.chunks.get_mut(&chunk_index) {
+            chunk.remove(in_chunk_index);
+        }
+        storage.remove(commands, index);
+    }
+
+    /// Restore the chunk next frame, even if it's out of the radius.
+    ///
+    /// It will be hibernated again when the cameras move if it's still out of the radius.
+    #[inline]
+    pub fn wake(&mut self, chunk_index: IVec2) {
+        if self.chunks.contains_key(&chunk_index) {
+            self.wake_queue.insert(chunk_index);
+        }
+    }
+}
+
+pub fn hibernation_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut tilemaps_query: Query<&mut TilemapHibernation>,
+) {
+    tilemaps_query.iter_mut().for_each(|mut hibernation| {
+        hibernation.chunks.clear();
+        hibernation.wake_queue.clear();
+    });
+}
+
+pub fn chunk_hibernator(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(Entity, &mut TilemapStorage, &mut TilemapHibernation)>,
+    tiles_query: Query<&Tile>,
+    cameras_query: Query<Ref<CameraAabb2d>>,
+    mut chunk_unload: EventWriter<ChunkUnload>,
+) {
+    let cam_changed = cameras_query.iter().any(|aabb| aabb.is_changed());
+
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(entity, mut storage, mut hibernation)| {
+            if !cam_changed && !hibernation.is_added() && hibernation.wake_queue.is_empty() {
+                return;
+            }
+
+            let radius = hibernation.radius;
+            let is_far = |chunk_index: &IVec2| {
+                storage.reserved.get(chunk_index).is_some_and(|aabb| {
+                    cameras_query
+                        .iter()
+                        .all(|cam| cam.inflate(radius).intersect(*aabb).is_empty())
+                })
+            };
+
+            let mut to_wake = std::mem::take(&mut hibernation.wake_queue);
+            let mut to_hibernate = Vec::new();
+            if cam_changed || hibernation.is_added() {
+                to_wake.extend(hibernation.chunks.keys().filter(|c| !is_far(c)));
+                to_hibernate.extend(storage.storage.chunks.keys().filter(|c| is_far(c)));
+            }
+
+            let chunk_size = storage.storage.chunk_size;
+            for chunk_index in to_hibernate {
+                let Some(chunk) = storage.get_chunk(chunk_index) else {
+                    continue;
+                };
+
+                let compressed = HibernatedChunk::compress(chunk.iter().map(|t| {
+                    t.and_then(|t| tiles_query.get(t).ok())
+                        .map(|tile| tile.clone().into())
+                }));
+                // Tiles set while hibernating are in the storage, so keep the old ones
+                // that are not overwritten.
+                let compressed = match hibernation.chunks.remove(&chunk_index) {
+                    Some(old) => merge(&old, compressed, chunk.len()),
+                    None => compressed,
+                };
+                hibernation.chunks.insert(chunk_index, compressed);
+
+                storage.remove_chunk(&mut commands, chunk_index);
+                chunk_unload.send(ChunkUnload {
+                    tilemap: entity,
+                    index: chunk_index,
+                });
+            }
+
+            for chunk_index in to_wake {
+                let Some(chunk) = hibernation.chunks.remove(&chunk_index) else {
+                    continue;
+                };
+
+                let origin = chunk_index * chunk_size as i32;
+                let mut buffer = TileBuilderBuffer::new();
+                chunk.iter().for_each(|(slot, tile)| {
+                    let index = UVec2::new(slot as u32 % chunk_size, slot as u32 / chunk_size);
+                    // Don't overwrite the tiles set while hibernating.
+                    if storage.get(origin + index.as_ivec2()).is_none() {
+                        buffer.set(index.as_ivec2(), tile.clone());
+                    }
+                });
+                storage.fill_with_buffer(&mut commands, origin, buffer);
+            }
+        });
+}
+
+/// Fill the empty slots of `new` with the tiles in `old`.
+fn merge(old: &HibernatedChunk, new: HibernatedChunk, len: usize) -> HibernatedChunk {
+    HibernatedChunk::compress((0..len).map(|slot| new.get(slot).or_else(|| old.get(slot)).cloned()))
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            event::Events,
+            system::{Commands, Query, RunSystemOnce},
+            world::World,
+        },
+        math::IVec2,
+    };
+
+    use crate::{
+        render::chunk::ChunkUnload,
+        tilemap::{
+            map::TilemapStorage,
+            tile::{TileBuilder, TileLayer},
+        },
+    };
+
+    use super::{chunk_hibernator, HibernatedChunk, TilemapHibernation};
+
+    #[test]
+    fn test_compression() {
+        let grass = TileBuilder::new().with_layer(0, TileLayer::no_flip(1));
+        let stone = TileBuilder::new().with_layer(0, TileLayer::no_flip(2));
+        let slots = [
+            Some(grass.clone()),
+            Some(grass.clone()),
+            None,
+            Some(stone.clone()),
+            Some(grass.clone()),
+            None,
+            None,
+        ];
+
+        let chunk = HibernatedChunk::compress(slots.iter().cloned());
+        assert_eq!(chunk.palette_len(), 2);
+        assert_eq!(chunk.run_count(), 5);
+
+        for (slot, tile) in slots.iter().enumerate() {
+            assert_eq!(chunk.get(slot), tile.as_ref());
+        }
+        assert_eq!(
+            chunk.iter().map(|(slot, _)| slot).collect::<Vec<_>>(),
+            vec![0, 1, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_remove_while_hibernating() {
+        let tile = TileBuilder::new().with_layer(0, TileLayer::no_flip(1));
+        let mut hibernation = TilemapHibernation::new(0.);
+        hibernation.chunks.insert(
+            IVec2::ZERO,
+            HibernatedChunk::compress((0..16).map(|slot| (slot < 2).then(|| tile.clone()))),
+        );
+
+        let mut world = World::new();
+        world.init_resource::<Events<ChunkUnload>>();
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert((TilemapStorage::new(4, tilemap), hibernation));
+
+        world.run_system_once(
+            move |mut commands: Commands,
+                  mut tilemaps_query: Query<(&mut TilemapStorage, &mut TilemapHibernation)>| {
+                let (mut storage, mut hibernation) = tilemaps_query.get_mut(tilemap).unwrap();
+                assert!(storage.get(IVec2::X).is_none());
+                hibernation.remove(&mut commands, &mut storage, IVec2::X);
+                assert!(hibernation.get(&storage, IVec2::X).is_none());
+                assert_eq!(hibernation.get_and_wake(&storage, IVec2::ZERO), Some(tile.clone()));
+            },
+        );
+        world.run_system_once(chunk_hibernator);
+
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        assert!(storage.get(IVec2::ZERO).is_some());
+        assert!(storage.get(IVec2::X).is_none());
+        assert!(!world
+            .get::<TilemapHibernation>(tilemap)
+            .unwrap()
+            .is_hibernating(IVec2::ZERO));
+    }
+}
@@ -1,2 +1,4 @@
 pub mod camera;
+pub mod hibernate;
+pub mod random_tick;
 pub mod storage;
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::{Commands, Query},
+    },
+    math::IVec2,
+    reflect::Reflect,
+};
+
+use crate::tilemap::map::TilemapStorage;
+
+/// An event that is sent when a tile is randomly ticked.
+///
+/// **Notice**: The tile may not exist, as the index is chosen from the whole chunk.
+#[derive(Event, Debug, Clone, Copy, Reflect)]
+pub struct RandomTick {
+    pub tilemap: Entity,
+    pub index: IVec2,
+    pub tile: Option<Entity>,
+}
+
+pub type RandomTickHandler = Arc<dyn Fn(&mut Commands, RandomTick) + Send + Sync>;
+
+/// Add this to a tilemap to randomly tick tiles in every loaded chunk, like
+/// random ticks in Minecraft. Useful for crop growth, grass spreading etc.
+///
+/// Ticks happen in `FixedUpdate`. If there's a handler, it will be called
+/// for every tick, otherwise [`RandomTick`] events will be sent.
+#[derive(Component, Clone)]
+pub struct RandomTicker {
+    /// How many tiles to tick in each chunk per tick.
+    pub rate: u32,
+    pub(crate) handler: Option<RandomTickHandler>,
+    pub(crate) rng: u64,
+}
+
+impl RandomTicker {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            handler: None,
+            rng: 0,
+        }
+    }
+
+    /// Make the ticks reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        // 0 means not seeded.
+        self.rng = seed.max(1);
+        self
+    }
+
+    /// Call `handler` instead of sending events.
+    pub fn with_handler(
+        mut self,
+        handler: impl Fn(&mut Commands, RandomTick) + Send + Sync + 'static,
+    ) -> Self {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// SplitMix64.
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+pub fn random_ticker(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(Entity, &TilemapStorage, &mut RandomTicker)>,
+    mut tick_event: EventWriter<RandomTick>,
+) {
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(entity, storage, mut ticker)| {
+            if ticker.rate == 0 {
+                return;
+            }
+
+            if ticker.rng == 0 {
+                ticker.rng = entity.to_bits();
+            }
+
+            let chunk_area = storage.storage.chunk_size.pow(2) as u64;
+            let handler = ticker.handler.clone();
+
+            storage
+                .storage
+                .chunks
+                .iter()
+                .for_each(|(chunk_index, chunk)| {
+                    for _ in 0..ticker.rate {
+                        let in_chunk_index = (ticker.next_u64() % chunk_area) as usize;
+                        let tick = RandomTick {
+                            tilemap: entity,
+                            index: storage
+                                .storage
+                                .inverse_transform_index(*chunk_index, in_chunk_index),
+                            tile: chunk.get(in_chunk_index).copied().flatten(),
+                        };
+
+                        match &handler {
+                            Some(handler) => handler(&mut commands, tick),
+                            None => {
+                                tick_event.send(tick);
+                            }
+                        }
+                    }
+                });
+        });
+}
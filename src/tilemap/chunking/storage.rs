@@ -26,6 +26,13 @@ pub type PackedPhysicsTileChunkedStorage =
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChunkedStorage<T: Debug + Clone + Reflect> {
     pub chunk_size: u32,
+    #[cfg_attr(
+        feature = "serializing",
+        serde(
+            serialize_with = "crate::serializing::serialize_grid_map",
+            bound(serialize = "T: serde::Serialize")
+        )
+    )]
     pub chunks: HashMap<IVec2, Vec<Option<T>>>,
 }
 
@@ -184,6 +191,31 @@ impl<T: Debug + Clone + Reflect> ChunkedStorage<T> {
         self.iter_mut().map(|o| o.as_mut()).flatten()
     }
 
+    /// Iterate over the elements in a deterministic order, unlike other iterators.
+    ///
+    /// Elements are sorted by their `y` and then `x`, regardless of which chunk they're in.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        let mut elems = self
+            .chunks
+            .iter()
+            .flat_map(|(chunk_index, chunk)| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(in_chunk_index, elem)| {
+                        elem.as_ref().map(|elem| {
+                            (
+                                self.inverse_transform_index(*chunk_index, in_chunk_index),
+                                elem,
+                            )
+                        })
+                    })
+            })
+            .collect::<Vec<_>>();
+        elems.sort_unstable_by_key(|(index, _)| (index.y, index.x));
+        elems.into_iter()
+    }
+
     #[inline]
     pub fn chunked_iter_some(&self) -> impl Iterator<Item = (ChunkIndex, InChunkIndex, &T)> {
         self.chunks
@@ -200,3 +232,51 @@ impl<T: Debug + Clone + Reflect> ChunkedStorage<T> {
             .flatten()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::math::IVec2;
+
+    use super::ChunkedStorage;
+
+    #[test]
+    fn test_iter_sorted() {
+        let indices = [
+            IVec2::new(5, 3),
+            IVec2::new(-2, 0),
+            IVec2::new(1, 0),
+            IVec2::new(0, -7),
+            IVec2::new(0, 1),
+            IVec2::new(4, 0),
+        ];
+
+        let mut forward = ChunkedStorage::new(4);
+        let mut backward = ChunkedStorage::new(4);
+        for (i, index) in indices.iter().enumerate() {
+            forward.set_elem(*index, i);
+        }
+        for (i, index) in indices.iter().enumerate().rev() {
+            backward.set_elem(*index, i);
+        }
+
+        let sorted = forward.iter_sorted().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            vec![
+                IVec2::new(0, -7),
+                IVec2::new(-2, 0),
+                IVec2::new(1, 0),
+                IVec2::new(4, 0),
+                IVec2::new(0, 1),
+                IVec2::new(5, 3),
+            ]
+        );
+        assert!(forward.iter_sorted().eq(backward.iter_sorted()));
+
+        #[cfg(feature = "serializing")]
+        assert_eq!(
+            ron::to_string(&forward).unwrap(),
+            ron::to_string(&backward).unwrap()
+        );
+    }
+}
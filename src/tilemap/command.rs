@@ -0,0 +1,402 @@
+//! Edit tilemaps through plain data.
+//!
+//! Modding or scripting layers can send [`TilemapCommandEvent`]s instead of accessing
+//! the ECS directly. Every command is validated against [`TilemapCommandConfig`] before
+//! it's applied, and the number of commands applied per frame is limited.
+//! Commands that can't be applied are reported through [`TilemapCommandFailed`],
+//! and the applied ones through [`TilemapCommandApplied`].
+
+use std::collections::VecDeque;
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        event::{Event, EventReader, EventWriter},
+        system::{Commands, Local, Query, Res, Resource},
+    },
+    log::warn,
+    math::IVec2,
+    reflect::Reflect,
+};
+use thiserror::Error;
+
+use crate::{
+    math::GridRect,
+    tilemap::{map::TilemapStorage, tile::TileBuilder},
+};
+
+#[cfg(feature = "algorithm")]
+use crate::{algorithm::pathfinding::PathTilemaps, tilemap::algorithm::path::PathTile};
+#[cfg(feature = "algorithm")]
+use bevy::ecs::system::ResMut;
+
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::{PhysicsTile, PhysicsTilemap};
+
+#[cfg(feature = "serializing")]
+use crate::serializing::pattern::TilemapPattern;
+#[cfg(feature = "serializing")]
+use bevy::{
+    asset::{Assets, Handle},
+    utils::HashMap,
+};
+
+/// An edit to a tilemap.
+#[derive(Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TilemapCommand {
+    SetTile {
+        index: IVec2,
+        tile: TileBuilder,
+    },
+    RemoveTile {
+        index: IVec2,
+    },
+    FillRect {
+        area: GridRect,
+        tile: TileBuilder,
+    },
+    /// Spawn a pattern registered in [`TilemapCommandConfig::patterns`],
+    /// with its origin at `origin`.
+    #[cfg(feature = "serializing")]
+    SpawnPattern {
+        pattern: String,
+        origin: IVec2,
+    },
+    #[cfg(feature = "algorithm")]
+    SetPathTile {
+        index: IVec2,
+        tile: PathTile,
+    },
+    #[cfg(feature = "algorithm")]
+    RemovePathTile {
+        index: IVec2,
+    },
+    #[cfg(feature = "physics")]
+    SetPhysicsTile {
+        index: IVec2,
+        tile: PhysicsTile,
+    },
+    #[cfg(feature = "physics")]
+    RemovePhysicsTile {
+        index: IVec2,
+    },
+}
+
+/// Send this to apply a [`TilemapCommand`] to `tilemap`.
+#[derive(Event, Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapCommandEvent {
+    pub tilemap: Entity,
+    pub command: TilemapCommand,
+}
+
+/// Sent when a [`TilemapCommandEvent`] is applied.
+///
+/// Read this instead of [`TilemapCommandEvent`]s to observe the edits,
+/// as commands can be rejected or delayed to the following frames.
+#[derive(Event, Debug, Clone)]
+pub struct TilemapCommandApplied(pub TilemapCommandEvent);
+
+/// Sent when a [`TilemapCommandEvent`] is rejected.
+#[derive(Event, Debug)]
+pub struct TilemapCommandFailed {
+    pub event: TilemapCommandEvent,
+    pub error: TilemapCommandError,
+}
+
+#[derive(Debug, Error)]
+pub enum TilemapCommandError {
+    #[error("Entity {0:?} is not a tilemap")]
+    NotTilemap(Entity),
+    #[error("Index {0} is out of the allowed bounds")]
+    OutOfBounds(IVec2),
+    #[error("Area of {0} tiles exceeds the limit")]
+    AreaTooLarge(usize),
+    #[error("Too many commands are queued")]
+    QueueFull,
+    #[error("Pattern `{0}` is not registered")]
+    UnknownPattern(String),
+    #[error("Pattern `{0}` is not loaded yet")]
+    PatternNotLoaded(String),
+    #[error("Tilemap {0:?} doesn't have a path tilemap")]
+    NoPathTilemap(Entity),
+    #[error("Tilemap {0:?} doesn't have a physics tilemap")]
+    NoPhysicsTilemap(Entity),
+}
+
+/// Limits for [`TilemapCommandEvent`]s.
+#[derive(Resource, Debug, Clone)]
+pub struct TilemapCommandConfig {
+    /// Commands beyond this are applied in the following frames.
+    pub commands_per_frame: usize,
+    /// Commands are rejected when this many commands are waiting.
+    pub max_queued: usize,
+    /// The maximum number of tiles a single command can affect.
+    pub max_area: usize,
+    /// Commands can only edit tiles inside this area if set.
+    pub bounds: Option<GridRect>,
+    /// The patterns that can be spawned, by name.
+    #[cfg(feature = "serializing")]
+    pub patterns: HashMap<String, Handle<TilemapPattern>>,
+}
+
+impl Default for TilemapCommandConfig {
+    fn default() -> Self {
+        Self {
+            commands_per_frame: 256,
+            max_queued: 4096,
+            max_area: 4096,
+            bounds: None,
+            #[cfg(feature = "serializing")]
+            patterns: HashMap::default(),
+        }
+    }
+}
+
+impl TilemapCommandConfig {
+    /// Check if `area` can be edited.
+    pub fn validate_area(&self, area: GridRect) -> Result<(), TilemapCommandError> {
+        if area.size() > self.max_area {
+            return Err(TilemapCommandError::AreaTooLarge(area.size()));
+        }
+
+        match self.bounds {
+            Some(bounds) if !bounds.contains(area.origin) => {
+                Err(TilemapCommandError::OutOfBounds(area.origin))
+            }
+            Some(bounds) if !bounds.contains(area.dest) => {
+                Err(TilemapCommandError::OutOfBounds(area.dest))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check if the tile at `index` can be edited.
+    #[inline]
+    pub fn validate_index(&self, index: IVec2) -> Result<(), TilemapCommandError> {
+        self.validate_area(GridRect::from_min_max(index, index))
+    }
+}
+
+pub fn tilemap_command_applier(
+    mut commands: Commands,
+    config: Res<TilemapCommandConfig>,
+    mut command_events: EventReader<TilemapCommandEvent>,
+    mut failed_events: EventWriter<TilemapCommandFailed>,
+    mut applied_events: EventWriter<TilemapCommandApplied>,
+    mut queue: Local<VecDeque<TilemapCommandEvent>>,
+    mut storages_query: Query<&mut TilemapStorage>,
+    #[cfg(feature = "serializing")] patterns: Option<Res<Assets<TilemapPattern>>>,
+    #[cfg(feature = "algorithm")] mut path_tilemaps: Option<ResMut<PathTilemaps>>,
+    #[cfg(feature = "physics")] mut physics_tilemaps_query: Query<&mut PhysicsTilemap>,
+) {
+    for ev in command_events.read() {
+        if queue.len() >= config.max_queued {
+            failed_events.send(TilemapCommandFailed {
+                event: ev.clone(),
+                error: TilemapCommandError::QueueFull,
+            });
+        } else {
+            queue.push_back(ev.clone());
+        }
+    }
+
+    let count = queue.len().min(config.commands_per_frame);
+    for ev in queue.drain(..count) {
+        let tilemap = ev.tilemap;
+        let Ok(mut storage) = storages_query.get_mut(tilemap) else {
+            failed_events.send(TilemapCommandFailed {
+                event: ev,
+                error: TilemapCommandError::NotTilemap(tilemap),
+            });
+            continue;
+        };
+
+        let result = match &ev.command {
+            TilemapCommand::SetTile { index, tile } => config.validate_index(*index).map(|_| {
+                storage.set(&mut commands, *index, tile.clone());
+            }),
+            TilemapCommand::RemoveTile { index } => config.validate_index(*index).map(|_| {
+                storage.remove(&mut commands, *index);
+            }),
+            TilemapCommand::FillRect { area, tile } => config.validate_area(*area).map(|_| {
+                storage.fill_rect(&mut commands, *area, tile.clone());
+            }),
+            #[cfg(feature = "serializing")]
+            TilemapCommand::SpawnPattern { pattern, origin } => config
+                .patterns
+                .get(pattern)
+                .ok_or_else(|| TilemapCommandError::UnknownPattern(pattern.clone()))
+                .and_then(|handle| {
+                    patterns
+                        .as_ref()
+                        .and_then(|p| p.get(handle))
+                        .ok_or_else(|| TilemapCommandError::PatternNotLoaded(pattern.clone()))
+                })
+                .and_then(|p| {
+                    let aabb = p.tiles.aabb();
+                    config
+                        .validate_area(GridRect::new(aabb.origin + *origin, aabb.extent))
+                        .map(|_| p)
+                })
+                .map(|p| {
+                    storage.fill_with_buffer(&mut commands, *origin, p.tiles.clone());
+
+                    #[cfg(feature = "algorithm")]
+                    if let Some(path_tilemaps) = path_tilemaps.as_mut() {
+                        path_tilemaps.with_mut(tilemap, |t| {
+                            t.fill_with_buffer(*origin, p.path_tiles.clone())
+                        });
+                    }
+
+                    #[cfg(feature = "physics")]
+                    if let (
+                        Ok(mut physics_tilemap),
+                        crate::tilemap::physics::SerializablePhysicsSource::Buffer(buffer),
+                    ) = (physics_tilemaps_query.get_mut(tilemap), &p.physics_tiles)
+                    {
+                        physics_tilemap.fill_with_buffer_packed(*origin, buffer.clone());
+                    }
+                }),
+            #[cfg(feature = "algorithm")]
+            TilemapCommand::SetPathTile { index, tile } => {
+                config.validate_index(*index).and_then(|_| {
+                    path_tilemaps
+                        .as_mut()
+                        .and_then(|p| p.with_mut(tilemap, |t| t.set(*index, *tile)))
+                        .ok_or(TilemapCommandError::NoPathTilemap(tilemap))
+                })
+            }
+            #[cfg(feature = "algorithm")]
+            TilemapCommand::RemovePathTile { index } => {
+                config.validate_index(*index).and_then(|_| {
+                    path_tilemaps
+                        .as_mut()
+                        .and_then(|p| {
+                            p.with_mut(tilemap, |t| {
+                                t.remove(*index);
+                            })
+                        })
+                        .ok_or(TilemapCommandError::NoPathTilemap(tilemap))
+                })
+            }
+            #[cfg(feature = "physics")]
+            TilemapCommand::SetPhysicsTile { index, tile } => {
+                config.validate_index(*index).and_then(|_| {
+                    physics_tilemaps_query
+                        .get_mut(tilemap)
+                        .map(|mut t| t.set(*index, tile.clone()))
+                        .map_err(|_| TilemapCommandError::NoPhysicsTilemap(tilemap))
+                })
+            }
+            #[cfg(feature = "physics")]
+            TilemapCommand::RemovePhysicsTile { index } => {
+                config.validate_index(*index).and_then(|_| {
+                    physics_tilemaps_query
+                        .get_mut(tilemap)
+                        .map(|mut t| t.remove(&mut commands, *index))
+                        .map_err(|_| TilemapCommandError::NoPhysicsTilemap(tilemap))
+                })
+            }
+        };
+
+        match result {
+            Ok(_) => {
+                applied_events.send(TilemapCommandApplied(ev));
+            }
+            Err(error) => {
+                warn!("Rejected tilemap command: {}", error);
+                failed_events.send(TilemapCommandFailed { event: ev, error });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{event::Events, system::RunSystemOnce, world::World},
+        math::{IVec2, UVec2},
+    };
+
+    use crate::{
+        math::GridRect,
+        tilemap::{
+            map::TilemapStorage,
+            tile::{TileBuilder, TileLayer},
+        },
+    };
+
+    use super::{
+        tilemap_command_applier, TilemapCommand, TilemapCommandApplied, TilemapCommandConfig,
+        TilemapCommandError, TilemapCommandEvent, TilemapCommandFailed,
+    };
+
+    #[test]
+    fn test_tilemap_commands() {
+        let mut world = World::new();
+        world.init_resource::<Events<TilemapCommandEvent>>();
+        world.init_resource::<Events<TilemapCommandFailed>>();
+        world.init_resource::<Events<TilemapCommandApplied>>();
+        world.insert_resource(TilemapCommandConfig {
+            commands_per_frame: 2,
+            max_area: 16,
+            bounds: Some(GridRect::new(IVec2::ZERO, UVec2::splat(8))),
+            ..Default::default()
+        });
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(16, tilemap));
+
+        let tile = TileBuilder::new().with_layer(0, TileLayer::no_flip(1));
+        let mut events = world.resource_mut::<Events<TilemapCommandEvent>>();
+        for command in [
+            TilemapCommand::SetTile {
+                index: IVec2::new(1, 1),
+                tile: tile.clone(),
+            },
+            TilemapCommand::SetTile {
+                index: IVec2::new(-1, 1),
+                tile: tile.clone(),
+            },
+            TilemapCommand::FillRect {
+                area: GridRect::new(IVec2::ZERO, UVec2::splat(5)),
+                tile: tile.clone(),
+            },
+        ] {
+            events.send(TilemapCommandEvent { tilemap, command });
+        }
+
+        world.run_system_once(tilemap_command_applier);
+
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        assert!(storage.get(IVec2::new(1, 1)).is_some());
+        assert!(storage.get(IVec2::new(-1, 1)).is_none());
+
+        let failed = world
+            .resource_mut::<Events<TilemapCommandFailed>>()
+            .drain()
+            .map(|ev| ev.error)
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            failed.as_slice(),
+            [TilemapCommandError::OutOfBounds(index)] if *index == IVec2::new(-1, 1)
+        ));
+        assert_eq!(
+            world
+                .resource_mut::<Events<TilemapCommandApplied>>()
+                .drain()
+                .count(),
+            1
+        );
+
+        let config = world.resource::<TilemapCommandConfig>();
+        assert!(matches!(
+            config.validate_area(GridRect::new(IVec2::ZERO, UVec2::splat(5))),
+            Err(TilemapCommandError::AreaTooLarge(25))
+        ));
+        assert!(config.validate_index(IVec2::new(7, 7)).is_ok());
+    }
+}
@@ -1,6 +1,9 @@
 use bevy::math::{IVec2, UVec2, Vec2};
 
-use crate::tilemap::map::{TilemapAxisFlip, TilemapTransform, TilemapType};
+use crate::{
+    math::raycast::hex_round,
+    tilemap::map::{TilemapAxisFlip, TilemapTransform, TilemapType},
+};
 
 /// Get the world position of the pivot of a slot.
 pub fn index_to_world(
@@ -30,6 +33,33 @@ pub fn index_to_world(
     })
 }
 
+/// Get the index of the slot that contains `world`.
+///
+/// For isometric and hexagonal tilemaps, this is the slot with the nearest center,
+/// which is exact for regular shapes.
+pub fn world_to_index(
+    world: Vec2,
+    ty: TilemapType,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+) -> IVec2 {
+    // Where `index_to_world` would be before transforming if `world` was the center of a slot.
+    let p = transform.inverse_transform_point(world) - slot_size / 2.;
+    match ty {
+        TilemapType::Square => (p / slot_size + pivot).round().as_ivec2(),
+        TilemapType::Isometric => {
+            let q = p / slot_size + pivot;
+            Vec2::new(q.x + q.y, q.y - q.x).round().as_ivec2()
+        }
+        TilemapType::Hexagonal(legs) => {
+            let y = p.y / ((slot_size.y + legs as f32) / 2.) + pivot.y;
+            let x = p.x / slot_size.x + pivot.x + 0.5 * y;
+            hex_round(Vec2::new(x, y))
+        }
+    }
+}
+
 /// Get the relative position of the pivot of a slot to the tilemap.
 pub fn index_to_rel(
     index: IVec2,
@@ -67,7 +97,7 @@ pub fn get_tile_collider(
             let up = index_to_world(size, ty, transform, pivot, slot_size);
             let left = index_to_world(IVec2::new(0, size.y), ty, transform, pivot, slot_size);
             let right = index_to_world(IVec2::new(size.x, 0), ty, transform, pivot, slot_size);
-            let offset = transform.apply_rotation(Vec2::new(slot_size.x / 2., 0.));
+            let offset = transform.transform_vector(Vec2::new(slot_size.x / 2., 0.));
 
             vec![down + offset, up + offset, right + offset, left + offset]
         }
@@ -199,6 +229,25 @@ pub fn get_tilemap_axis(ty: TilemapType, slot_size: Vec2, flip: TilemapAxisFlip)
     (x * flip.x, y * flip.y)
 }
 
+/// Mirror the index around the origin along the flipped axes, as [`TilemapAxisFlip`] does to the slots.
+///
+/// Apply this to the index from [`world_to_index`] to get the index on a flipped tilemap.
+/// It's its own inverse.
+pub fn flip_index(index: IVec2, flip: TilemapAxisFlip) -> IVec2 {
+    IVec2::new(
+        if flip.contains(TilemapAxisFlip::X) {
+            -index.x - 1
+        } else {
+            index.x
+        },
+        if flip.contains(TilemapAxisFlip::Y) {
+            -index.y - 1
+        } else {
+            index.y
+        },
+    )
+}
+
 /// Stagger mode for staggered tilemaps.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StaggerMode {
@@ -234,4 +283,41 @@ mod test {
         let size = calculate_map_size_staggered(size, slot_size, leg);
         assert_eq!(size, Vec2::new(112., 66.));
     }
+
+    #[test]
+    fn test_world_to_index() {
+        let slot_size = Vec2::new(32., 16.);
+        let quarter = TilemapTransform {
+            translation: Vec2::new(10., -30.),
+            rotation: crate::tilemap::map::TilemapRotation::Cw90,
+            ..Default::default()
+        };
+        let arbitrary = quarter.with_angle(0.6).with_scale(Vec2::new(1.5, 0.75));
+
+        for (ty, transform) in [
+            TilemapType::Square,
+            TilemapType::Isometric,
+            TilemapType::Hexagonal(8),
+        ]
+        .into_iter()
+        .flat_map(|ty| [(ty, quarter), (ty, arbitrary)])
+        {
+            for pivot in [Vec2::ZERO, Vec2::splat(0.5)] {
+                for index in [IVec2::ZERO, IVec2::new(3, -2), IVec2::new(-5, 7)] {
+                    let center = index_to_world(index, ty, &transform, pivot, slot_size)
+                        + transform.transform_vector(slot_size / 2.);
+                    assert_eq!(
+                        world_to_index(center, ty, &transform, pivot, slot_size),
+                        index
+                    );
+                    // Slightly off the center.
+                    let world = center + Vec2::new(3., -2.);
+                    assert_eq!(
+                        world_to_index(world, ty, &transform, pivot, slot_size),
+                        index
+                    );
+                }
+            }
+        }
+    }
 }
@@ -2,8 +2,10 @@ use bevy::{
     ecs::{
         component::Component,
         entity::Entity,
+        event::Event,
         query::With,
-        system::{Commands, ParallelCommands, Query},
+        system::{Commands, ParallelCommands, Query, RunSystemOnce},
+        world::{Command, World},
     },
     math::IVec2,
 };
@@ -81,3 +83,106 @@ pub fn despawn_physics_tilemaps(
         });
     });
 }
+
+/// Unloads everything spawned by this crate, including LDtk levels, Tiled maps,
+/// tilemaps, physics colliders, and clears the related caches.
+///
+/// Useful for going back to the main menu. Use [`EntiTilesCommands::unload_everything`]
+/// to queue this.
+///
+/// Tilemaps are despawned here, and [`EverythingUnloaded`] is triggered after that
+/// so the other parts of this crate can clear their own states.
+///
+/// **Notice**: Chunks that are scheduled to be saved but haven't been saved yet will be lost.
+/// And global LDtk entities and Tiled objects will be despawned as well.
+pub struct UnloadEverything;
+
+impl Command for UnloadEverything {
+    fn apply(self, world: &mut World) {
+        world.run_system_once(unload_everything);
+        world.trigger(EverythingUnloaded);
+    }
+}
+
+/// Triggered by [`UnloadEverything`] after all the tilemaps are despawned.
+///
+/// Observe this to clear the states related to tilemaps of your own.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EverythingUnloaded;
+
+pub trait EntiTilesCommands {
+    /// See [`UnloadEverything`].
+    fn unload_everything(&mut self);
+
+    /// See [`SpawnPrefab`](crate::serializing::prefab::SpawnPrefab).
+    #[cfg(feature = "serializing")]
+    fn spawn_prefab(
+        &mut self,
+        prefab: bevy::asset::Handle<crate::serializing::prefab::TilemapPrefab>,
+        origin: IVec2,
+    );
+}
+
+impl EntiTilesCommands for Commands<'_, '_> {
+    fn unload_everything(&mut self) {
+        self.add(UnloadEverything);
+    }
+
+    #[cfg(feature = "serializing")]
+    fn spawn_prefab(
+        &mut self,
+        prefab: bevy::asset::Handle<crate::serializing::prefab::TilemapPrefab>,
+        origin: IVec2,
+    ) {
+        self.add(crate::serializing::prefab::SpawnPrefab::new(prefab, origin));
+    }
+}
+
+fn unload_everything(
+    mut commands: Commands,
+    mut tilemaps_query: Query<&mut TilemapStorage>,
+    #[cfg(feature = "physics")] physics_tilemaps_query: Query<
+        Entity,
+        (
+            With<super::physics::PhysicsTilemap>,
+            bevy::ecs::query::Without<TilemapStorage>,
+        ),
+    >,
+) {
+    // LDtk and Tiled layers are tilemaps as well.
+    tilemaps_query.iter_mut().for_each(|mut storage| {
+        storage.despawn(&mut commands);
+    });
+
+    #[cfg(feature = "physics")]
+    physics_tilemaps_query.iter().for_each(|entity| {
+        commands.entity(entity).insert(DespawnMe);
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::world::{Command, World};
+
+    use crate::tilemap::map::{z_order_unloader, TilemapStorage, TilemapZOrder};
+
+    use super::{DespawnMe, UnloadEverything};
+
+    #[test]
+    fn test_unload_everything() {
+        let mut world = World::new();
+        world.init_resource::<TilemapZOrder>();
+        world.observe(z_order_unloader);
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(16, tilemap));
+        world
+            .resource_mut::<TilemapZOrder>()
+            .allocate(tilemap, 2, 1., None);
+
+        UnloadEverything.apply(&mut world);
+        assert!(world.entity(tilemap).contains::<DespawnMe>());
+        assert!(world.resource::<TilemapZOrder>().get(tilemap).is_none());
+    }
+}
@@ -0,0 +1,481 @@
+//! Record the tiles changed since tilemaps are loaded, and apply them on top of
+//! freshly loaded tilemaps.
+//!
+//! This is useful for save games of authored maps with destructible terrain, as only
+//! the changed tiles need to be saved instead of whole tilemaps.
+
+use bevy::{
+    ecs::{
+        change_detection::DetectChanges,
+        entity::{Entity, EntityHashMap},
+        event::EventReader,
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+        world::Ref,
+    },
+    math::IVec2,
+    reflect::Reflect,
+    utils::HashMap,
+};
+
+#[cfg(feature = "physics")]
+use crate::tilemap::physics::{PhysicsTile, PhysicsTilemap};
+use crate::tilemap::{
+    command::{TilemapCommand, TilemapCommandApplied},
+    map::{TilemapName, TilemapStorage},
+    tile::{Tile, TileBuilder},
+};
+#[cfg(feature = "algorithm")]
+use crate::{algorithm::pathfinding::PathTilemaps, tilemap::algorithm::path::PathTile};
+#[cfg(feature = "algorithm")]
+use bevy::log::warn;
+
+#[cfg(feature = "ldtk")]
+type TilemapKey = Option<&'static crate::ldtk::components::LayerIid>;
+#[cfg(not(feature = "ldtk"))]
+type TilemapKey = ();
+
+/// LDtk layers are identified by their iids, as the layers in different levels
+/// can share the same name. Other tilemaps are identified by [`TilemapName`].
+#[cfg(feature = "ldtk")]
+fn tilemap_key(name: &TilemapName, layer: Option<&crate::ldtk::components::LayerIid>) -> String {
+    layer
+        .map(|iid| iid.0.clone())
+        .unwrap_or_else(|| name.0.clone())
+}
+
+#[cfg(not(feature = "ldtk"))]
+fn tilemap_key(name: &TilemapName, _: ()) -> String {
+    name.0.clone()
+}
+
+/// The changed tiles of a single tilemap. `None` means the tile is removed.
+#[derive(Debug, Clone, Default, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapLayerDiff {
+    #[cfg_attr(
+        feature = "serializing",
+        serde(serialize_with = "crate::serializing::serialize_grid_map")
+    )]
+    pub tiles: HashMap<IVec2, Option<TileBuilder>>,
+    #[cfg(feature = "algorithm")]
+    #[cfg_attr(
+        feature = "serializing",
+        serde(serialize_with = "crate::serializing::serialize_grid_map")
+    )]
+    pub path_tiles: HashMap<IVec2, Option<PathTile>>,
+    #[cfg(feature = "physics")]
+    #[cfg_attr(
+        feature = "serializing",
+        serde(serialize_with = "crate::serializing::serialize_grid_map")
+    )]
+    pub physics_tiles: HashMap<IVec2, Option<PhysicsTile>>,
+}
+
+impl TilemapLayerDiff {
+    /// The number of changed tiles in all layers.
+    pub fn len(&self) -> usize {
+        let len = self.tiles.len();
+        #[cfg(feature = "algorithm")]
+        let len = len + self.path_tiles.len();
+        #[cfg(feature = "physics")]
+        let len = len + self.physics_tiles.len();
+        len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Overwrite the changes in `self` with the ones in `other`.
+    pub fn merge(&mut self, other: TilemapLayerDiff) {
+        self.tiles.extend(other.tiles);
+        #[cfg(feature = "algorithm")]
+        self.path_tiles.extend(other.path_tiles);
+        #[cfg(feature = "physics")]
+        self.physics_tiles.extend(other.physics_tiles);
+    }
+
+    /// Record the result of `command`.
+    ///
+    /// **Notice**: `SpawnPattern` commands are ignored, as the pattern may not be loaded.
+    /// Enable [`TilemapDiffRecorder::capture_tile_changes`] to record the spawned tiles.
+    pub fn record(&mut self, command: &TilemapCommand) {
+        match command {
+            TilemapCommand::SetTile { index, tile } => {
+                self.tiles.insert(*index, Some(tile.clone()));
+            }
+            TilemapCommand::RemoveTile { index } => {
+                self.tiles.insert(*index, None);
+            }
+            TilemapCommand::FillRect { area, tile } => {
+                for y in area.origin.y..=area.dest.y {
+                    for x in area.origin.x..=area.dest.x {
+                        self.tiles.insert(IVec2 { x, y }, Some(tile.clone()));
+                    }
+                }
+            }
+            #[cfg(feature = "serializing")]
+            TilemapCommand::SpawnPattern { .. } => {}
+            #[cfg(feature = "algorithm")]
+            TilemapCommand::SetPathTile { index, tile } => {
+                self.path_tiles.insert(*index, Some(*tile));
+            }
+            #[cfg(feature = "algorithm")]
+            TilemapCommand::RemovePathTile { index } => {
+                self.path_tiles.insert(*index, None);
+            }
+            #[cfg(feature = "physics")]
+            TilemapCommand::SetPhysicsTile { index, tile } => {
+                self.physics_tiles.insert(*index, Some(tile.clone()));
+            }
+            #[cfg(feature = "physics")]
+            TilemapCommand::RemovePhysicsTile { index } => {
+                self.physics_tiles.insert(*index, None);
+            }
+        }
+    }
+}
+
+/// The changed tiles of all tilemaps, keyed by the layer iids for LDtk layers,
+/// and [`TilemapName`]s for others.
+#[derive(Debug, Clone, Default, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapDiff {
+    #[cfg_attr(
+        feature = "serializing",
+        serde(serialize_with = "crate::serializing::serialize_sorted_map")
+    )]
+    pub tilemaps: HashMap<String, TilemapLayerDiff>,
+}
+
+impl TilemapDiff {
+    /// The number of changed tiles in all tilemaps.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tilemaps.values().map(|t| t.len()).sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&TilemapLayerDiff> {
+        self.tilemaps.get(key)
+    }
+
+    /// Overwrite the changes in `self` with the ones in `other`.
+    pub fn merge(&mut self, other: TilemapDiff) {
+        for (key, diff) in other.tilemaps {
+            self.tilemaps.entry(key).or_default().merge(diff);
+        }
+    }
+
+    /// Serialize into a compact ron string.
+    #[cfg(feature = "serializing")]
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::to_string(self)
+    }
+
+    #[cfg(feature = "serializing")]
+    pub fn from_ron(s: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(s)
+    }
+}
+
+/// Records the tiles changed since tilemaps are loaded, and applies the recorded
+/// changes again whenever a tilemap with the same key is loaded.
+///
+/// Commands sent as [`TilemapCommandEvent`](crate::tilemap::command::TilemapCommandEvent)s
+/// are always recorded while recording, once they are applied.
+///
+/// **Notice**: Tilemaps are matched by their keys, so tilemaps that are not from LDtk
+/// should have unique [`TilemapName`]s.
+#[derive(Resource, Debug, Default)]
+pub struct TilemapDiffRecorder {
+    /// Also record the color tiles that are changed or removed without commands,
+    /// like the ones edited through `TilemapStorage` directly.
+    ///
+    /// **Notice**: Chunks unloaded by chunk saving or hibernation are recorded as removed,
+    /// so don't enable this if you are using them.
+    pub capture_tile_changes: bool,
+    pub(crate) recording: bool,
+    pub(crate) diff: TilemapDiff,
+}
+
+impl TilemapDiffRecorder {
+    /// Start recording. The changes recorded before are kept.
+    #[inline]
+    pub fn start(&mut self) {
+        self.recording = true;
+    }
+
+    /// Stop recording. The recorded changes are still applied to reloaded tilemaps.
+    #[inline]
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// The changes recorded so far, including the applied ones.
+    #[inline]
+    pub fn diff(&self) -> &TilemapDiff {
+        &self.diff
+    }
+
+    /// Apply `diff`, usually loaded from a save file.
+    ///
+    /// The changes are applied to the tilemaps when they are loaded,
+    /// and replace the ones that are recorded before.
+    ///
+    /// **Notice**: Tilemaps that are already loaded are not affected.
+    /// So apply the diff before loading the levels.
+    #[inline]
+    pub fn apply(&mut self, diff: TilemapDiff) {
+        self.diff.merge(diff);
+    }
+
+    /// Discard all the recorded changes.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.diff.tilemaps.clear();
+    }
+}
+
+pub fn tilemap_diff_applier(
+    mut commands: Commands,
+    recorder: Res<TilemapDiffRecorder>,
+    mut tilemaps_query: Query<(Ref<TilemapName>, TilemapKey, &mut TilemapStorage)>,
+    #[cfg(feature = "algorithm")] mut path_tilemaps: Option<ResMut<PathTilemaps>>,
+    #[cfg(feature = "physics")] mut physics_tilemaps_query: Query<(
+        &TilemapName,
+        TilemapKey,
+        &mut PhysicsTilemap,
+    )>,
+) {
+    if recorder.diff.tilemaps.is_empty() {
+        return;
+    }
+
+    tilemaps_query
+        .iter_mut()
+        .filter(|(name, ..)| name.is_added())
+        .for_each(|(name, layer, mut storage)| {
+            let Some(diff) = recorder.diff.get(&tilemap_key(&name, layer)) else {
+                return;
+            };
+
+            diff.tiles.iter().for_each(|(index, tile)| match tile {
+                Some(tile) => storage.set(&mut commands, *index, tile.clone()),
+                None => {
+                    storage.remove(&mut commands, *index);
+                }
+            });
+
+            #[cfg(feature = "algorithm")]
+            if !diff.path_tiles.is_empty()
+                && path_tilemaps
+                    .as_mut()
+                    .and_then(|p| {
+                        p.with_mut(storage.tilemap, |t| {
+                            diff.path_tiles.iter().for_each(|(index, tile)| match tile {
+                                Some(tile) => t.set(*index, *tile),
+                                None => {
+                                    t.remove(*index);
+                                }
+                            })
+                        })
+                    })
+                    .is_none()
+            {
+                warn!(
+                    "Failed to apply the path tiles to {:?}: No path tilemap.",
+                    storage.tilemap
+                );
+            }
+        });
+
+    // Physics tilemaps can be created after the tilemaps, like the ones from LDtk.
+    #[cfg(feature = "physics")]
+    physics_tilemaps_query
+        .iter_mut()
+        .filter(|(.., physics_tilemap)| physics_tilemap.is_added())
+        .for_each(|(name, layer, mut physics_tilemap)| {
+            let Some(diff) = recorder.diff.get(&tilemap_key(&name, layer)) else {
+                return;
+            };
+
+            diff.physics_tiles
+                .iter()
+                .for_each(|(index, tile)| match tile {
+                    Some(tile) => physics_tilemap.set(*index, tile.clone()),
+                    None => physics_tilemap.remove(&mut commands, *index),
+                });
+        });
+}
+
+pub fn tilemap_diff_recorder(
+    mut recorder: ResMut<TilemapDiffRecorder>,
+    mut applied_events: EventReader<TilemapCommandApplied>,
+    tilemaps_query: Query<(Entity, Ref<TilemapName>, TilemapKey)>,
+    changed_tiles_query: Query<(Entity, &Tile), Changed<Tile>>,
+    mut removed_tiles: RemovedComponents<Tile>,
+    mut tile_indices: Local<EntityHashMap<(Entity, IVec2)>>,
+) {
+    if !recorder.recording {
+        applied_events.clear();
+        removed_tiles.clear();
+        tile_indices.clear();
+        return;
+    }
+
+    let keys = tilemaps_query
+        .iter()
+        .map(|(entity, name, layer)| (entity, (tilemap_key(&name, layer), name.is_added())))
+        .collect::<EntityHashMap<_>>();
+
+    for TilemapCommandApplied(ev) in applied_events.read() {
+        if let Some((key, _)) = keys.get(&ev.tilemap) {
+            recorder
+                .diff
+                .tilemaps
+                .entry(key.clone())
+                .or_default()
+                .record(&ev.command);
+        }
+    }
+
+    if !recorder.capture_tile_changes {
+        return;
+    }
+
+    // Tiles of despawned tilemaps are not recorded, as their keys are gone.
+    for entity in removed_tiles.read() {
+        let Some((tilemap, index)) = tile_indices.remove(&entity) else {
+            continue;
+        };
+        if let Some((key, _)) = keys.get(&tilemap) {
+            recorder
+                .diff
+                .tilemaps
+                .entry(key.clone())
+                .or_default()
+                .tiles
+                .insert(index, None);
+        }
+    }
+
+    for (entity, tile) in changed_tiles_query.iter() {
+        let Some((key, just_loaded)) = keys.get(&tile.tilemap_id) else {
+            continue;
+        };
+        tile_indices.insert(entity, (tile.tilemap_id, tile.index));
+
+        // Tiles spawned with the tilemap are authored ones.
+        if *just_loaded {
+            continue;
+        }
+        recorder
+            .diff
+            .tilemaps
+            .entry(key.clone())
+            .or_default()
+            .tiles
+            .insert(tile.index, Some(tile.clone().into()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{event::Events, system::RunSystemOnce, world::World},
+        math::{IVec2, UVec2},
+    };
+
+    use crate::{
+        math::GridRect,
+        tilemap::{
+            command::{
+                tilemap_command_applier, TilemapCommand, TilemapCommandApplied,
+                TilemapCommandConfig, TilemapCommandEvent, TilemapCommandFailed,
+            },
+            map::{TilemapName, TilemapStorage},
+            tile::{TileBuilder, TileLayer},
+        },
+    };
+
+    use super::{tilemap_diff_applier, tilemap_diff_recorder, TilemapDiffRecorder};
+
+    #[test]
+    fn test_diff() {
+        let mut world = World::new();
+        world.init_resource::<Events<TilemapCommandEvent>>();
+        world.init_resource::<Events<TilemapCommandFailed>>();
+        world.init_resource::<Events<TilemapCommandApplied>>();
+        world.insert_resource(TilemapCommandConfig {
+            bounds: Some(GridRect::new(IVec2::ZERO, UVec2::splat(8))),
+            ..Default::default()
+        });
+        world.init_resource::<TilemapDiffRecorder>();
+        world.resource_mut::<TilemapDiffRecorder>().start();
+
+        let tilemap = world.spawn(TilemapName("ground".to_string())).id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(16, tilemap));
+        let tile = TileBuilder::new().with_layer(0, TileLayer::no_flip(1));
+        for command in [
+            TilemapCommand::SetTile {
+                index: IVec2::new(1, 1),
+                tile: tile.clone(),
+            },
+            TilemapCommand::SetTile {
+                index: IVec2::new(2, 1),
+                tile: tile.clone(),
+            },
+            TilemapCommand::RemoveTile {
+                index: IVec2::new(2, 1),
+            },
+            // Rejected by the command applier.
+            TilemapCommand::SetTile {
+                index: IVec2::new(-1, 1),
+                tile: tile.clone(),
+            },
+        ] {
+            world.send_event(TilemapCommandEvent { tilemap, command });
+        }
+        world.run_system_once(tilemap_command_applier);
+        world.run_system_once(tilemap_diff_recorder);
+
+        let diff = world.resource::<TilemapDiffRecorder>().diff().clone();
+        assert_eq!(diff.len(), 2);
+        let ground = diff.get("ground").unwrap();
+        assert_eq!(ground.tiles[&IVec2::new(1, 1)], Some(tile));
+        assert_eq!(ground.tiles[&IVec2::new(2, 1)], None);
+        assert!(!ground.tiles.contains_key(&IVec2::new(-1, 1)));
+
+        // Reload the tilemap with a fresh recorder, like loading a save.
+        world.despawn(tilemap);
+        let mut recorder = TilemapDiffRecorder::default();
+        recorder.apply(diff);
+        world.insert_resource(recorder);
+
+        let tilemap = world.spawn(TilemapName("ground".to_string())).id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(16, tilemap));
+        world.run_system_once(tilemap_diff_applier);
+
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        assert!(storage.get(IVec2::new(1, 1)).is_some());
+        assert!(storage.get(IVec2::new(2, 1)).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+//! Height maps authored in LDtk or Tiled, uploaded as textures.
+//!
+//! They can be used as the mask of `WeatherSettings` with the `weather` feature,
+//! or bound to your own materials to react to the elevation.
+//! The bind group of [`TilemapPostEffect`](crate::render::post_processing::TilemapPostEffect)s
+//! doesn't include them.
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        world::{Command, World},
+    },
+    math::{UVec2, Vec2},
+    reflect::Reflect,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::Image,
+    },
+};
+
+/// The heights of a level or a map, one texel per tile.
+///
+/// The texture is `R32Float` and the first row is the top of the map,
+/// so it can be sampled directly using [`TilemapHeightMap::world_to_uv`].
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TilemapHeightMap {
+    pub texture: Handle<Image>,
+    /// The size in tiles.
+    pub size: UVec2,
+    /// The world position of the top left corner.
+    pub origin: Vec2,
+    /// The world size of a tile.
+    pub tile_size: Vec2,
+}
+
+impl TilemapHeightMap {
+    /// Create the height texture from row major heights, starting from the top left.
+    pub fn create_texture(heights: &[f32], size: UVec2) -> Image {
+        assert_eq!(
+            heights.len(),
+            (size.x * size.y) as usize,
+            "The count of heights doesn't match the size"
+        );
+
+        Image::new(
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            heights.iter().flat_map(|h| h.to_le_bytes()).collect(),
+            TextureFormat::R32Float,
+            RenderAssetUsages::all(),
+        )
+    }
+
+    /// Convert a world position into the uv on the height texture.
+    pub fn world_to_uv(&self, world: Vec2) -> Vec2 {
+        let grid = self.size.as_vec2() * self.tile_size;
+        Vec2::new(world.x - self.origin.x, self.origin.y - world.y) / grid
+    }
+}
+
+/// Upload the heights and insert the [`TilemapHeightMap`] to `target`.
+pub struct InsertHeightMap {
+    pub target: Entity,
+    /// Row major heights, starting from the top left.
+    pub heights: Vec<f32>,
+    pub size: UVec2,
+    pub origin: Vec2,
+    pub tile_size: Vec2,
+}
+
+impl Command for InsertHeightMap {
+    fn apply(self, world: &mut World) {
+        let texture = world
+            .resource_mut::<Assets<Image>>()
+            .add(TilemapHeightMap::create_texture(&self.heights, self.size));
+        if let Some(mut entity) = world.get_entity_mut(self.target) {
+            entity.insert(TilemapHeightMap {
+                texture,
+                size: self.size,
+                origin: self.origin,
+                tile_size: self.tile_size,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::Assets,
+        ecs::world::{Command, World},
+        math::{UVec2, Vec2},
+        render::texture::Image,
+    };
+
+    use super::{InsertHeightMap, TilemapHeightMap};
+
+    #[test]
+    fn test_height_map() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        let target = world.spawn_empty().id();
+
+        InsertHeightMap {
+            target,
+            heights: vec![0., 1., 2., 3., 4., 5.],
+            size: UVec2::new(3, 2),
+            origin: Vec2::new(-16., 32.),
+            tile_size: Vec2::splat(16.),
+        }
+        .apply(&mut world);
+
+        let height_map = world.get::<TilemapHeightMap>(target).unwrap().clone();
+        let image = world
+            .resource::<Assets<Image>>()
+            .get(&height_map.texture)
+            .unwrap();
+        assert_eq!(image.size(), UVec2::new(3, 2));
+        assert_eq!(&image.data[12..16], &3f32.to_le_bytes());
+
+        assert_eq!(height_map.world_to_uv(Vec2::new(-16., 32.)), Vec2::ZERO);
+        assert_eq!(height_map.world_to_uv(Vec2::new(32., 0.)), Vec2::ONE);
+    }
+}
@@ -1,32 +1,46 @@
-use std::{f32::consts::SQRT_2, fmt::Debug};
+use std::{
+    f32::consts::{FRAC_PI_2, SQRT_2},
+    fmt::Debug,
+};
 
 use bevy::{
     asset::{Asset, Handle},
     ecs::{
         component::Component,
-        query::Changed,
-        system::{Query, SystemParamItem},
+        observer::Trigger,
+        query::{Changed, With, Without},
+        system::{Query, Res, ResMut, Resource, SystemParamItem},
     },
-    math::{Mat2, Quat, Rect, URect, Vec4},
+    math::{EulerRot, Mat2, Quat, Rect, URect, Vec4},
     prelude::{Commands, Entity, IVec2, Image, UVec2, Vec2},
     reflect::Reflect,
     render::{
         render_asset::{PrepareAssetError, RenderAsset},
-        render_resource::FilterMode,
+        render_resource::{
+            AddressMode, Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+            TextureUsages,
+        },
     },
     sprite::TextureAtlasLayout,
-    transform::components::Transform,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
     utils::{HashMap, HashSet},
 };
+use thiserror::Error;
 
 use crate::{
-    math::{ext::RectFromTilemap, GridRect},
+    math::{ext::RectFromTilemap, CameraAabb2d, GridRect},
     tilemap::{
         buffers::TileBuilderBuffer,
         chunking::storage::{ChunkedStorage, EntityChunkedStorage},
-        despawn::{DespawnMe, DespawnedTilemap},
-        tile::{RawTileAnimation, TileAnimation, TileBuilder, TileRearrange, TileUpdater},
+        despawn::{DespawnMe, DespawnedTilemap, EverythingUnloaded},
+        replication::TileChangeOp,
+        tile::{
+            ClearTileLayer, MarkTilesChanged, RawTileAnimation, Tile, TileAnimation, TileBuilder,
+            TileLayer, TileRearrange, TileTexture, TileUpdater, UpdateTiles,
+        },
     },
+    DEFAULT_CHUNK_SIZE,
 };
 
 /// Defines the shape of tiles in a tilemap.
@@ -41,6 +55,8 @@ pub enum TilemapType {
 }
 
 /// Actually four directions.
+///
+/// Use [`TilemapTransform::angle`] for the other angles.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub enum TilemapRotation {
@@ -52,20 +68,40 @@ pub enum TilemapRotation {
 }
 
 /// A tilemap transform. Using the `Transform` component is meaningless.
-#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+///
+/// **Notice**: Scales are not applied to colliders of physics tilemaps.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serializing", serde(default))]
 pub struct TilemapTransform {
     pub translation: Vec2,
     pub z_index: f32,
     pub rotation: TilemapRotation,
+    /// Rotation in radians on top of `rotation`, in the same direction as `Cw90`.
+    ///
+    /// Keep it zero for quarter turns, as those are exact.
+    pub angle: f32,
+    /// Applied before rotating, so non-uniform scales are along the tilemap axes.
+    ///
+    /// **Notice**: Each component should be non-zero, otherwise
+    /// world positions can't be converted back to the tilemap.
+    pub scale: Vec2,
+}
+
+impl Default for TilemapTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
 }
 
 impl TilemapTransform {
-    /// The transform with no translation and rotation.
+    /// The transform with no translation, rotation and scale.
     pub const IDENTITY: Self = Self {
         translation: Vec2::ZERO,
         z_index: 0.,
         rotation: TilemapRotation::None,
+        angle: 0.,
+        scale: Vec2::ONE,
     };
 
     #[inline]
@@ -93,50 +129,131 @@ impl TilemapTransform {
         }
     }
 
+    #[inline]
+    pub fn with_rotation(mut self, rotation: TilemapRotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Rotate by `angle` radians on top of the quarter turns.
+    #[inline]
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    #[inline]
+    pub fn with_scale(mut self, scale: Vec2) -> Self {
+        assert!(
+            scale.cmpne(Vec2::ZERO).all(),
+            "Tilemap scale must be non-zero, got {}",
+            scale
+        );
+        self.scale = scale;
+        self
+    }
+
+    /// The whole rotation in radians, including the quarter turns.
+    #[inline]
+    pub fn get_angle(&self) -> f32 {
+        (self.rotation as u32 as f32).to_radians() + self.angle
+    }
+
     #[inline]
     pub fn transform_point(&self, point: Vec2) -> Vec2 {
-        self.apply_translation(self.apply_rotation(point))
+        self.apply_translation(self.transform_vector(point))
     }
 
-    pub fn transform_rect(&self, aabb: Rect) -> Rect {
-        let min = self.transform_point(aabb.min);
-        let max = self.transform_point(aabb.max);
+    /// Scale and rotate `vector` without translating it.
+    #[inline]
+    pub fn transform_vector(&self, vector: Vec2) -> Vec2 {
+        self.apply_rotation(vector * self.scale)
+    }
 
-        match self.rotation {
-            TilemapRotation::None => Rect { min, max },
-            TilemapRotation::Cw90 => Rect::new(max.x, min.y, min.x, max.y),
-            TilemapRotation::Cw180 => Rect::new(max.x, max.y, min.x, min.y),
-            TilemapRotation::Cw270 => Rect::new(min.x, max.y, max.x, min.y),
+    /// The inverse of `transform_point`.
+    #[inline]
+    pub fn inverse_transform_point(&self, point: Vec2) -> Vec2 {
+        let mut point = point - self.translation;
+        if self.angle != 0. {
+            point = Vec2::from_angle(-self.angle).rotate(point);
         }
+        let point = match self.rotation {
+            TilemapRotation::None => point,
+            TilemapRotation::Cw90 => Vec2::new(point.y, -point.x),
+            TilemapRotation::Cw180 => Vec2::new(-point.x, -point.y),
+            TilemapRotation::Cw270 => Vec2::new(-point.y, point.x),
+        };
+        // Collapsed axes map everything to zero instead of NaN.
+        Vec2::select(self.scale.cmpeq(Vec2::ZERO), Vec2::ZERO, point / self.scale)
+    }
+
+    /// Get the aabb of the transformed `aabb`.
+    pub fn transform_rect(&self, aabb: Rect) -> Rect {
+        [
+            aabb.min,
+            Vec2::new(aabb.max.x, aabb.min.y),
+            aabb.max,
+            Vec2::new(aabb.min.x, aabb.max.y),
+        ]
+        .into_iter()
+        .map(|corner| self.transform_point(corner))
+        .fold(
+            Rect::new(f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |rect, p| Rect {
+                min: rect.min.min(p),
+                max: rect.max.max(p),
+            },
+        )
     }
 
     #[inline]
     pub fn get_rotation_matrix(&self) -> Mat2 {
-        match self.rotation {
+        let quarter = match self.rotation {
             TilemapRotation::None => Mat2::from_cols_array(&[1., 0., 0., 1.]),
             TilemapRotation::Cw90 => Mat2::from_cols_array(&[0., 1., -1., 0.]),
             TilemapRotation::Cw180 => Mat2::from_cols_array(&[-1., 0., 0., -1.]),
             TilemapRotation::Cw270 => Mat2::from_cols_array(&[0., -1., 1., 0.]),
+        };
+        if self.angle == 0. {
+            quarter
+        } else {
+            Mat2::from_angle(self.angle) * quarter
         }
     }
 
+    /// The rotation matrix with the scale applied, which is what the renderer uses.
+    #[inline]
+    pub fn get_matrix(&self) -> Mat2 {
+        self.get_rotation_matrix() * Mat2::from_diagonal(self.scale)
+    }
+
     #[inline]
     pub fn get_rotation_quat(&self) -> Quat {
-        match self.rotation {
+        let quarter = match self.rotation {
             TilemapRotation::None => Quat::IDENTITY,
             TilemapRotation::Cw90 => Quat::from_xyzw(0., 0., SQRT_2 / 2., SQRT_2 / 2.),
             TilemapRotation::Cw180 => Quat::from_xyzw(0., 0., 1., 0.),
             TilemapRotation::Cw270 => Quat::from_xyzw(0., 0., SQRT_2 / 2., -SQRT_2 / 2.),
+        };
+        if self.angle == 0. {
+            quarter
+        } else {
+            Quat::from_rotation_z(self.angle) * quarter
         }
     }
 
     #[inline]
     pub fn apply_rotation(&self, point: Vec2) -> Vec2 {
-        match self.rotation {
+        let point = match self.rotation {
             TilemapRotation::None => point,
             TilemapRotation::Cw90 => Vec2::new(-point.y, point.x),
             TilemapRotation::Cw180 => Vec2::new(-point.x, -point.y),
             TilemapRotation::Cw270 => Vec2::new(point.y, -point.x),
+        };
+        if self.angle == 0. {
+            point
+        } else {
+            Vec2::from_angle(self.angle).rotate(point)
         }
     }
 
@@ -150,8 +267,8 @@ impl Into<Transform> for TilemapTransform {
     fn into(self) -> Transform {
         Transform {
             translation: self.translation.extend(self.z_index as f32),
-            rotation: Quat::from_rotation_z(self.rotation as u32 as f32),
-            ..Default::default()
+            rotation: self.get_rotation_quat(),
+            scale: self.scale.extend(1.),
         }
     }
 }
@@ -186,14 +303,116 @@ impl TilemapAxisFlip {
     }
 }
 
+/// How the tilemap textures are sampled.
+///
+/// Pixel art usually wants `nearest` with `ClampToEdge`, which is the default.
+/// Use `linear` for hi-res tilesets. Tiles are stored in separate layers of a
+/// texture array (unless the `atlas` feature is enabled), so clamping keeps linear
+/// filtering from bleeding into the neighbouring tiles.
+///
+/// **Notice**: Tilemap textures have no mipmaps, so `mipmap_filter` doesn't
+/// make a difference yet. `anisotropy_clamp` larger than 1 requires all the filters
+/// to be `Linear`, otherwise it's ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilemapSampler {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for TilemapSampler {
+    fn default() -> Self {
+        Self::nearest()
+    }
+}
+
+impl From<FilterMode> for TilemapSampler {
+    fn from(filter_mode: FilterMode) -> Self {
+        Self {
+            mag_filter: filter_mode,
+            min_filter: filter_mode,
+            mipmap_filter: filter_mode,
+            ..Self::nearest()
+        }
+    }
+}
+
+impl TilemapSampler {
+    /// Nearest filtering and clamped to edge.
+    pub fn nearest() -> Self {
+        Self {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            anisotropy_clamp: 1,
+        }
+    }
+
+    /// Linear filtering and clamped to edge.
+    pub fn linear() -> Self {
+        FilterMode::Linear.into()
+    }
+
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy_clamp: u16) -> Self {
+        self.anisotropy_clamp = anisotropy_clamp;
+        self
+    }
+
+    pub(crate) fn descriptor(&self) -> SamplerDescriptor<'static> {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .into_iter()
+            .all(|f| f == FilterMode::Linear);
+
+        SamplerDescriptor {
+            label: Some("tilemap_texture_array_sampler"),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: 0.,
+            lod_max_clamp: f32::MAX,
+            compare: None,
+            anisotropy_clamp: if all_linear {
+                self.anisotropy_clamp.max(1)
+            } else {
+                1
+            },
+            border_color: None,
+        }
+    }
+}
+
+/// The textures used by a tilemap.
+///
+/// Textures can have different tile sizes, like 16px terrain mixed with 32px props.
+/// The tiles from the textures with the smallest tile size are rendered at
+/// [`TileRenderSize`], and the others are scaled up proportionally, growing from the [`TilePivot`].
+///
+/// **Notice**: Mixing tile sizes requires the `atlas` feature. Tiles larger than the
+/// slots may be culled at the edges of the chunks.
 #[derive(Asset, Clone, Default, Debug, Reflect)]
 pub struct TilemapTextures {
     pub(crate) textures: Vec<TilemapTexture>,
     pub(crate) start_index: Vec<u32>,
     pub(crate) uv_scales: Vec<Vec2>,
+    /// The tile size of each texture relative to [`TilemapTextures::min_tile_size`].
+    pub(crate) tile_scales: Vec<Vec2>,
     pub(crate) max_size: UVec2,
     #[reflect(ignore)]
-    pub(crate) filter_mode: FilterMode,
+    pub(crate) sampler: TilemapSampler,
 }
 
 impl RenderAsset for TilemapTextures {
@@ -215,26 +434,73 @@ impl TilemapTextures {
     }
 
     pub fn new(textures: Vec<TilemapTexture>, filter_mode: FilterMode) -> Self {
-        let mut start_index = Vec::with_capacity(textures.len());
+        let mut textures = Self {
+            textures,
+            sampler: filter_mode.into(),
+            ..Default::default()
+        };
+        textures.update_layout();
+        textures
+    }
+
+    pub(crate) fn update_layout(&mut self) {
+        self.start_index.clear();
         let mut cur = 0;
         let mut max_size = UVec2::ZERO;
 
-        for tex in &textures {
-            start_index.push(cur);
+        for tex in &self.textures {
+            self.start_index.push(cur);
             cur += tex.tile_count();
             max_size = max_size.max(tex.desc.size);
         }
 
-        Self {
-            uv_scales: textures
-                .iter()
-                .map(|t| t.desc.size.as_vec2() / max_size.as_vec2())
-                .collect(),
-            textures,
-            start_index,
-            max_size,
-            filter_mode,
-        }
+        self.uv_scales = self
+            .textures
+            .iter()
+            .map(|t| t.desc.size.as_vec2() / max_size.as_vec2())
+            .collect();
+        let min_tile_size = self.min_tile_size().as_vec2();
+        self.tile_scales = self
+            .textures
+            .iter()
+            .map(|t| t.desc.tile_size.as_vec2() / min_tile_size)
+            .collect();
+        self.max_size = max_size;
+    }
+
+    /// The smallest tile size among the textures, on each axis.
+    /// Tiles of this size are rendered at [`TileRenderSize`].
+    pub fn min_tile_size(&self) -> UVec2 {
+        self.textures
+            .iter()
+            .map(|t| t.desc.tile_size)
+            .reduce(|a, b| a.min(b))
+            .unwrap_or(UVec2::ONE)
+    }
+
+    /// Returns `true` if all the textures have the same tile size.
+    pub fn is_uniform_tile_size(&self) -> bool {
+        self.textures
+            .windows(2)
+            .all(|w| w[0].desc.tile_size == w[1].desc.tile_size)
+    }
+
+    /// Returns `true` if some textures are waiting for `TilemapTexture::with_extrusion`.
+    /// They won't be rendered until then.
+    #[inline]
+    pub fn is_extruding(&self) -> bool {
+        self.textures.iter().any(|t| t.extrusion > 0)
+    }
+
+    /// Override the sampler, which is derived from the `filter_mode` by default.
+    pub fn with_sampler(mut self, sampler: TilemapSampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    #[inline]
+    pub fn sampler(&self) -> TilemapSampler {
+        self.sampler
     }
 
     pub fn assert_uniform_tile_size(&self) {
@@ -242,9 +508,10 @@ impl TilemapTextures {
             return;
         }
 
-        for t in &self.textures {
-            assert_eq!(t.desc.tile_size, self.textures[0].desc.tile_size);
-        }
+        assert!(
+            self.is_uniform_tile_size(),
+            "Textures with different tile sizes are only supported when rendering with the `atlas` feature."
+        );
     }
 
     pub fn total_tile_count(&self) -> u32 {
@@ -263,11 +530,108 @@ impl TilemapTextures {
 pub struct TilemapTexture {
     pub(crate) texture: Handle<Image>,
     pub(crate) desc: TilemapTextureDescriptor,
+    /// The padding to extrude once the image is loaded.
+    pub(crate) extrusion: u32,
 }
 
 impl TilemapTexture {
     pub fn new(texture: Handle<Image>, desc: TilemapTextureDescriptor) -> Self {
-        Self { texture, desc }
+        Self {
+            texture,
+            desc,
+            extrusion: 0,
+        }
+    }
+
+    /// Extrude the edge pixels of each tile by `padding` texels once the image is loaded,
+    /// which removes the seams between tiles when zooming with linear filtering.
+    ///
+    /// A new image is generated and the descriptor is updated to the padded layout.
+    ///
+    /// **Notice**: This only makes a difference with the `atlas` feature. Otherwise tiles are
+    /// stored in separate texture layers, and clamping in the `TilemapSampler` already
+    /// prevents bleeding.
+    pub fn with_extrusion(mut self, padding: u32) -> Self {
+        assert_eq!(
+            self.desc.padding, 0,
+            "The texture is already padded! Extrude the original texture instead."
+        );
+        self.extrusion = padding;
+        self
+    }
+
+    /// Generate the padded image. Only uncompressed formats are supported.
+    pub(crate) fn extrude(
+        &self,
+        image: &Image,
+    ) -> Result<(Image, TilemapTextureDescriptor), TilemapTextureError> {
+        let padding = self.extrusion;
+        let desc = self.desc;
+        let format = image.texture_descriptor.format;
+        let px_size = match format.block_copy_size(None) {
+            Some(size) if format.block_dimensions() == (1, 1) => size as usize,
+            _ => return Err(TilemapTextureError::UnsupportedFormat(format)),
+        };
+        if image.size() != desc.size
+            || image.data.len() != (desc.size.x * desc.size.y) as usize * px_size
+        {
+            return Err(TilemapTextureError::SizeMismatch {
+                image: image.size(),
+                desc: desc.size,
+            });
+        }
+
+        let padded_desc = TilemapTextureDescriptor {
+            size: desc.tile_count() * (desc.tile_size + 2 * padding),
+            tile_size: desc.tile_size,
+            padding,
+            ..Default::default()
+        };
+        let mut data = vec![0; (padded_desc.size.x * padded_desc.size.y) as usize * px_size];
+
+        let tile_count = desc.tile_count();
+        for index in 0..tile_count.x * tile_count.y {
+            let src = self.get_atlas_urect(index).min;
+            let dst = TilemapTexture {
+                desc: padded_desc,
+                ..Default::default()
+            }
+            .get_atlas_urect(index)
+            .min;
+
+            for y in 0..desc.tile_size.y + 2 * padding {
+                for x in 0..desc.tile_size.x + 2 * padding {
+                    // Clamp into the tile, so the edges are repeated.
+                    let src_px = src
+                        + UVec2::new(x, y)
+                            .saturating_sub(UVec2::splat(padding))
+                            .min(desc.tile_size - 1);
+                    let dst_px = dst + UVec2::new(x, y) - padding;
+                    let src_offset = (src_px.y * desc.size.x + src_px.x) as usize * px_size;
+                    let dst_offset = (dst_px.y * padded_desc.size.x + dst_px.x) as usize * px_size;
+                    data[dst_offset..dst_offset + px_size]
+                        .copy_from_slice(&image.data[src_offset..src_offset + px_size]);
+                }
+            }
+        }
+
+        let mut padded = Image::new(
+            Extent3d {
+                width: padded_desc.size.x,
+                height: padded_desc.size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+            image.asset_usage,
+        );
+        padded.sampler = image.sampler.clone();
+        padded
+            .texture_descriptor
+            .usage
+            .set(TextureUsages::COPY_SRC, true);
+        Ok((padded, padded_desc))
     }
 
     #[inline]
@@ -287,38 +651,39 @@ impl TilemapTexture {
 
     #[inline]
     pub fn tile_count(&self) -> u32 {
-        let t = self.desc.size / self.desc.tile_size;
+        let t = self.desc.tile_count();
         t.x * t.y
     }
 
     pub fn as_atlas_layout(&self) -> TextureAtlasLayout {
+        let tile_count = self.desc.tile_count();
         TextureAtlasLayout::from_grid(
             self.desc.tile_size,
-            self.desc.size.x,
-            self.desc.size.y,
-            Some(UVec2::ZERO),
-            Some(UVec2::ZERO),
+            tile_count.x,
+            tile_count.y,
+            Some(UVec2::splat(self.desc.padding * 2 + self.desc.spacing)),
+            Some(UVec2::splat(self.desc.padding + self.desc.margin)),
         )
     }
 
-    /// Get the atlas rect of a tile in uv coordinates.
+    /// Get the atlas rect of a tile in uv coordinates, excluding the padding and spacing.
     pub fn get_atlas_rect(&self, index: u32) -> Rect {
-        let tile_count = self.desc.size / self.desc.tile_size;
-        let tile_index = Vec2::new((index % tile_count.x) as f32, (index / tile_count.x) as f32);
-        let tile_size = self.desc.tile_size.as_vec2() / self.desc.size.as_vec2();
+        let urect = self.get_atlas_urect(index);
+        let size = self.desc.size.as_vec2();
         Rect {
-            min: tile_index * tile_size,
-            max: (tile_index + Vec2::ONE) * tile_size,
+            min: urect.min.as_vec2() / size,
+            max: (urect.max + 1).as_vec2() / size,
         }
     }
 
-    /// Get the atlas rect of a tile in pixel coordinates.
+    /// Get the atlas rect of a tile in pixel coordinates, excluding the padding and spacing.
     pub fn get_atlas_urect(&self, index: u32) -> URect {
-        let tile_count = self.desc.size / self.desc.tile_size;
+        let tile_count = self.desc.tile_count();
         let tile_index = UVec2::new(index % tile_count.x, index / tile_count.x);
+        let min = self.desc.margin + tile_index * self.desc.tile_stride() + self.desc.padding;
         URect {
-            min: tile_index * self.desc.tile_size,
-            max: (tile_index + 1) * self.desc.tile_size - 1,
+            min,
+            max: min + self.desc.tile_size - 1,
         }
     }
 }
@@ -326,12 +691,32 @@ impl TilemapTexture {
 #[derive(Component, Debug, Default, Clone)]
 pub struct WaitForTextureUsageChange;
 
+#[derive(Error, Debug, PartialEq)]
+pub enum TilemapTextureError {
+    #[error("Unsupported texture format: {0:?}, only uncompressed color formats can be extruded")]
+    UnsupportedFormat(TextureFormat),
+    #[error("The image size {image} doesn't match the descriptor size {desc}")]
+    SizeMismatch { image: UVec2, desc: UVec2 },
+}
+
 /// A descriptor for a tilemap texture.
-#[derive(Clone, Copy, Default, Debug, PartialEq, Reflect)]
+///
+/// Tiles are placed on a grid, starting `margin` texels away from the top left corner,
+/// with `spacing` texels between each other, like tilesets in Tiled and LDtk.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TilemapTextureDescriptor {
     pub(crate) size: UVec2,
     pub(crate) tile_size: UVec2,
+    /// The texels around each tile, which are not part of the tile.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) padding: u32,
+    /// The texels between the image borders and the tiles.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) margin: u32,
+    /// The texels between adjacent tiles.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) spacing: u32,
 }
 
 impl TilemapTextureDescriptor {
@@ -342,7 +727,70 @@ impl TilemapTextureDescriptor {
             "Invalid tilemap texture descriptor! The size must be divisible by the tile size!"
         );
 
-        Self { size, tile_size }
+        Self {
+            size,
+            tile_size,
+            ..Default::default()
+        }
+    }
+
+    /// Create a descriptor for images with margin and spacing.
+    ///
+    /// Texels that are not enough for a whole tile at the right and bottom are ignored.
+    pub fn new_with_spacing(size: UVec2, tile_size: UVec2, margin: u32, spacing: u32) -> Self {
+        Self {
+            size,
+            tile_size,
+            margin,
+            spacing,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn tile_size(&self) -> UVec2 {
+        self.tile_size
+    }
+
+    #[inline]
+    pub fn padding(&self) -> u32 {
+        self.padding
+    }
+
+    #[inline]
+    pub fn margin(&self) -> u32 {
+        self.margin
+    }
+
+    #[inline]
+    pub fn spacing(&self) -> u32 {
+        self.spacing
+    }
+
+    /// The distance between the origins of two adjacent tiles, including the padding and spacing.
+    #[inline]
+    pub fn tile_stride(&self) -> UVec2 {
+        self.tile_size + 2 * self.padding + self.spacing
+    }
+
+    /// The number of tiles in each axis.
+    #[inline]
+    pub fn tile_count(&self) -> UVec2 {
+        // The last tile doesn't need the spacing after it.
+        (self.size + self.spacing).saturating_sub(UVec2::splat(2 * self.margin))
+            / self.tile_stride()
+    }
+
+    /// The descriptor before padding.
+    ///
+    /// **Notice**: The margin and spacing are removed when padding, so they are not restored.
+    #[inline]
+    pub fn unpadded(&self) -> Self {
+        Self {
+            size: self.tile_count() * self.tile_size,
+            tile_size: self.tile_size,
+            ..Default::default()
+        }
     }
 }
 
@@ -428,6 +876,153 @@ impl TilemapAabbs {
     }
 }
 
+/// The defaults used when tilemaps are created by the importers and algorithms.
+/// Insert this at startup to change them for the whole project.
+///
+/// **Notice**: `Default` implementations like `TilemapStorage::default()` can't read resources,
+/// use [`EntiTilesDefaults::storage`] and [`EntiTilesDefaults::textures`] instead.
+#[derive(Resource, Debug, Clone, Reflect)]
+pub struct EntiTilesDefaults {
+    pub chunk_size: u32,
+    /// LDtk levels only use this if `LdtkLevelConfig::use_default_filter_mode` is set.
+    #[reflect(ignore)]
+    pub filter_mode: FilterMode,
+    /// The z distance between layers of imported maps.
+    /// `None` uses the spacing of each importer, which is 1 for LDtk and 0.1 for Tiled.
+    pub z_spacing: Option<f32>,
+}
+
+impl Default for EntiTilesDefaults {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            filter_mode: FilterMode::Nearest,
+            z_spacing: None,
+        }
+    }
+}
+
+impl EntiTilesDefaults {
+    /// Create a storage with the default chunk size.
+    #[inline]
+    pub fn storage(&self, binded_tilemap: Entity) -> TilemapStorage {
+        TilemapStorage::new(self.chunk_size, binded_tilemap)
+    }
+
+    /// Create textures with the default filter mode.
+    #[inline]
+    pub fn textures(&self, textures: Vec<TilemapTexture>) -> TilemapTextures {
+        TilemapTextures::new(textures, self.filter_mode)
+    }
+}
+
+/// Allocates the z ranges of the maps loaded by the importers, so the layers of
+/// maps loaded at the same time never collide.
+///
+/// Each map takes `layer_step * (layers + 1)`, placed at the lowest free z above `base`.
+/// The range is released when the map is unloaded, and reused by the next map that fits in.
+///
+/// **Notice**: Maps with a z override are placed where they're told to,
+/// but other maps will still avoid them.
+#[derive(Resource, Debug, Clone, Reflect)]
+pub struct TilemapZOrder {
+    pub base: f32,
+    /// The z distance between layers.
+    /// `None` falls back to [`EntiTilesDefaults::z_spacing`] and then the spacing of each importer.
+    pub layer_step: Option<f32>,
+    /// The owners and z ranges of the allocated maps, sorted by their starts.
+    #[reflect(ignore)]
+    pub(crate) ranges: Vec<(Entity, f32, f32)>,
+}
+
+impl Default for TilemapZOrder {
+    fn default() -> Self {
+        Self {
+            base: 0.,
+            layer_step: None,
+            ranges: Vec::new(),
+        }
+    }
+}
+
+impl TilemapZOrder {
+    pub fn new(base: f32, layer_step: f32) -> Self {
+        Self {
+            base,
+            layer_step: Some(layer_step),
+            ranges: Vec::new(),
+        }
+    }
+
+    /// The z distance between layers.
+    #[inline]
+    pub fn layer_step(&self, defaults: &EntiTilesDefaults, importer_spacing: f32) -> f32 {
+        self.layer_step
+            .or(defaults.z_spacing)
+            .unwrap_or(importer_spacing)
+    }
+
+    /// Allocate a range for `layers` layers and return the start of it.
+    ///
+    /// If `ovrd` is `Some`, the range starts there regardless of the other maps.
+    #[inline]
+    pub fn allocate(
+        &mut self,
+        owner: Entity,
+        layers: usize,
+        layer_step: f32,
+        ovrd: Option<f32>,
+    ) -> f32 {
+        self.allocate_from(owner, self.base, layers, layer_step, ovrd)
+    }
+
+    /// Same as [`allocate`](Self::allocate), but the range is placed at the lowest free z above `from`
+    /// instead of [`base`](Self::base).
+    pub fn allocate_from(
+        &mut self,
+        owner: Entity,
+        from: f32,
+        layers: usize,
+        layer_step: f32,
+        ovrd: Option<f32>,
+    ) -> f32 {
+        self.release(owner);
+        let len = (layers + 1) as f32 * layer_step;
+
+        let start = ovrd.unwrap_or_else(|| {
+            let mut cursor = from;
+            for (_, start, end) in &self.ranges {
+                if *start - cursor >= len {
+                    break;
+                }
+                cursor = cursor.max(*end);
+            }
+            cursor
+        });
+
+        let index = self.ranges.partition_point(|(_, s, _)| *s <= start);
+        self.ranges.insert(index, (owner, start, start + len));
+        start
+    }
+
+    /// Release the range of `owner`.
+    pub fn release(&mut self, owner: Entity) {
+        self.ranges.retain(|(e, _, _)| *e != owner);
+    }
+
+    /// Returns the z range of `owner`.
+    pub fn get(&self, owner: Entity) -> Option<(f32, f32)> {
+        self.ranges
+            .iter()
+            .find(|(e, _, _)| *e == owner)
+            .map(|(_, s, e)| (*s, *e))
+    }
+}
+
+pub fn z_order_unloader(_: Trigger<EverythingUnloaded>, mut z_order: ResMut<TilemapZOrder>) {
+    z_order.ranges.clear();
+}
+
 /// The tilemap's storage. It stores all the tiles in entity form.
 #[derive(Component, Debug, Clone, Reflect)]
 pub struct TilemapStorage {
@@ -435,6 +1030,7 @@ pub struct TilemapStorage {
     pub(crate) storage: EntityChunkedStorage,
     pub(crate) reserved: HashMap<IVec2, Rect>,
     pub(crate) calc_queue: HashSet<IVec2>,
+    pub(crate) claims: HashMap<IVec2, Entity>,
 }
 
 impl TilemapStorage {
@@ -454,6 +1050,7 @@ impl Default for TilemapStorage {
             storage: Default::default(),
             reserved: Default::default(),
             calc_queue: Default::default(),
+            claims: Default::default(),
         }
     }
 }
@@ -491,6 +1088,16 @@ impl TilemapStorage {
         self.storage.get_elem(index).cloned()
     }
 
+    /// Iterate over the tiles in a deterministic order.
+    ///
+    /// See [`ChunkedStorage::iter_sorted`] for the order.
+    #[inline]
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (IVec2, Entity)> + '_ {
+        self.storage
+            .iter_sorted()
+            .map(|(index, entity)| (index, *entity))
+    }
+
     /// Get a chunk.
     #[inline]
     pub fn get_chunk(&self, index: IVec2) -> Option<&Vec<Option<Entity>>> {
@@ -566,6 +1173,22 @@ impl TilemapStorage {
         }
     }
 
+    /// Rebuild the render chunk at `index` from the tiles in it.
+    ///
+    /// Only the tiles changed since the last frame are sent to the render world,
+    /// so call this after modifying the chunk without triggering change detection,
+    /// like using [`TilemapStorage::get_chunk_mut`] or `bypass_change_detection`,
+    /// or to bring back a chunk unloaded using [`ChunkUnload`](crate::render::chunk::ChunkUnload).
+    ///
+    /// **Notice**: Tiles removed from the chunk without despawning are still rendered.
+    /// Send [`ChunkUnload`](crate::render::chunk::ChunkUnload) and call this in the next frame
+    /// to rebuild the chunk from scratch.
+    pub fn mark_chunk_dirty(&self, commands: &mut Commands, index: IVec2) {
+        if let Some(chunk) = self.get_chunk(index) {
+            commands.add(MarkTilesChanged(chunk.iter().flatten().cloned().collect()));
+        }
+    }
+
     /// Remove all the tiles in the tilemap.
     pub fn remove_all(&mut self, commands: &mut Commands) {
         self.storage
@@ -577,6 +1200,19 @@ impl TilemapStorage {
             });
     }
 
+    /// Apply the ops in order.
+    pub fn apply_ops<'a>(
+        &mut self,
+        commands: &mut Commands,
+        ops: impl IntoIterator<Item = &'a TileChangeOp>,
+    ) {
+        ops.into_iter().for_each(|op| match op {
+            TileChangeOp::Set { index, tile } => self.set(commands, *index, tile.clone()),
+            TileChangeOp::Remove { index } => self.remove(commands, *index),
+            TileChangeOp::Fill { area, tile } => self.fill_rect(commands, *area, tile.clone()),
+        });
+    }
+
     /// Declare that a chunk is existent.
     ///
     /// Use `reserve_with_aabb` if you can provide the aabb.
@@ -719,7 +1355,123 @@ impl TilemapStorage {
         commands.insert_or_spawn_batch(batch);
     }
 
+    /// Clear the layer at `layer` of all the tiles in place, using a single command.
+    ///
+    /// The layers above keep their indices, and tiles are kept even if they become empty.
+    /// Animated tiles are not affected.
+    pub fn clear_layer(&self, commands: &mut Commands, layer: usize) {
+        commands.add(ClearTileLayer {
+            tiles: self.storage.iter_some().cloned().collect(),
+            layer,
+        });
+    }
+
+    /// Copy the layer at `layer` of all the tiles into a buffer. The layer is placed
+    /// at index 0 of the builders, and the tint and emissive of the tiles are kept.
+    ///
+    /// Animated tiles and the tiles without this layer are skipped.
+    pub fn extract_layer(&self, tiles_query: &Query<&Tile>, layer: usize) -> TileBuilderBuffer {
+        let mut buffer = TileBuilderBuffer::new();
+
+        for (index, entity) in self.iter_sorted() {
+            let Ok(tile) = tiles_query.get(entity) else {
+                continue;
+            };
+            let TileTexture::Static(tex) = &tile.texture else {
+                continue;
+            };
+            let Some(l) = tex.get(layer).filter(|l| **l != TileLayer::default()) else {
+                continue;
+            };
+
+            buffer.set(
+                index,
+                TileBuilder::new()
+                    .with_layer(0, *l)
+                    .with_tint(tile.tint)
+                    .with_emissive(tile.emissive),
+            );
+        }
+
+        buffer.recalculate_rect();
+        buffer
+    }
+
+    /// Returns `true` if the slot has neither a tile nor a claim.
+    #[inline]
+    pub fn is_empty(&self, index: IVec2) -> bool {
+        self.get(index).is_none() && !self.claims.contains_key(&index)
+    }
+
+    /// Returns `true` if no slots in the area have tiles or claims.
+    pub fn is_empty_rect(&self, area: GridRect) -> bool {
+        (area.origin.y..=area.dest.y)
+            .all(|y| (area.origin.x..=area.dest.x).all(|x| self.is_empty(IVec2 { x, y })))
+    }
+
+    /// `fill_rect()` only if the area `is_empty_rect()`. Returns whether the area is filled.
+    ///
+    /// The check and the fill happen at once, so systems placing things in the same frame
+    /// can't overlap each other.
+    pub fn fill_if_empty(
+        &mut self,
+        commands: &mut Commands,
+        area: GridRect,
+        tile_builder: TileBuilder,
+    ) -> bool {
+        if !self.is_empty_rect(area) {
+            return false;
+        }
+
+        self.fill_rect(commands, area, tile_builder);
+        true
+    }
+
+    /// Claim the area for `owner` if it `is_empty_rect()`. Returns whether the area is claimed.
+    ///
+    /// Claimed slots are treated as occupied, so you can hold a footprint
+    /// while the building is being placed or constructed. The claims don't
+    /// affect the tiles, use `release_claims()` or `release_rect()` to remove them.
+    ///
+    /// **Notice**: Claims are not removed when `owner` is despawned.
+    pub fn claim_rect(&mut self, area: GridRect, owner: Entity) -> bool {
+        if !self.is_empty_rect(area) {
+            return false;
+        }
+
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.claims.insert(IVec2 { x, y }, owner);
+            }
+        }
+        true
+    }
+
+    /// Get the owner of the claim at `index`.
+    #[inline]
+    pub fn get_claim(&self, index: IVec2) -> Option<Entity> {
+        self.claims.get(&index).cloned()
+    }
+
+    /// Remove the claims in the area, no matter who owns them.
+    pub fn release_rect(&mut self, area: GridRect) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.claims.remove(&IVec2 { x, y });
+            }
+        }
+    }
+
+    /// Remove all the claims of `owner`.
+    #[inline]
+    pub fn release_claims(&mut self, owner: Entity) {
+        self.claims.retain(|_, e| *e != owner);
+    }
+
     /// Simlar to `TilemapStorage::fill_rect()`.
+    ///
+    /// The tiles are updated in place by a single command, which is much cheaper
+    /// than calling `TilemapStorage::update()` for each of them.
     pub fn update_rect(&mut self, commands: &mut Commands, area: GridRect, updater: TileUpdater) {
         let mut batch = Vec::with_capacity(area.size());
 
@@ -731,10 +1483,12 @@ impl TilemapStorage {
             }
         }
 
-        commands.insert_or_spawn_batch(batch);
+        commands.add(UpdateTiles(batch));
     }
 
     /// Simlar to `TilemapStorage::fill_rect_custom()`.
+    ///
+    /// The tiles are updated in place like `TilemapStorage::update_rect()`.
     pub fn update_rect_custom(
         &mut self,
         commands: &mut Commands,
@@ -759,17 +1513,32 @@ impl TilemapStorage {
             }
         }
 
-        commands.insert_or_spawn_batch(batch);
+        commands.add(UpdateTiles(batch));
+    }
+
+    /// Update the tiles at the given indices in place, using a single command.
+    /// Empty slots are skipped.
+    pub fn update_many(
+        &mut self,
+        commands: &mut Commands,
+        updaters: impl IntoIterator<Item = (IVec2, TileUpdater)>,
+    ) {
+        let batch = updaters
+            .into_iter()
+            .filter_map(|(index, updater)| self.get(index).map(|entity| (entity, updater)))
+            .collect();
+
+        commands.add(UpdateTiles(batch));
     }
 }
 
 /// The tilemap's animation buffer.
 ///
-/// Its format is `[fps, atlas_index_1, ..., atlas_index_n, fps, atlas_index_1, ..., atlas_index_n, ...]`.
+/// Its format is `[length, mode, fps, atlas_index_1, ..., atlas_index_n, length, mode, fps, atlas_index_1, ..., atlas_index_n, ...]`.
 ///
 /// If `atlas` feature is enabled, then the format is
 ///
-/// `[fps, texture_index_1, atlas_index_1, ..., texture_index_n, atlas_index_n, fps, texture_index_1, atlas_index_1, ..., texture_index_n, atlas_index_n, ...]`
+/// `[length, mode, fps, texture_index_1, atlas_index_1, ..., texture_index_n, atlas_index_n, length, mode, fps, ...]`
 #[derive(Component, Debug, Clone, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TilemapAnimations(pub(crate) Vec<i32>);
@@ -783,10 +1552,14 @@ impl Default for TilemapAnimations {
 
 impl TilemapAnimations {
     /// Register a tile animation so you can use it in `TileBuilder::with_animation`.
+    ///
+    /// **Notice**: `fps` is clamped to at least 1.
     pub fn register(&mut self, anim: RawTileAnimation) -> TileAnimation {
-        self.0.push(anim.fps as i32);
-        let start = self.0.len() as u32;
         let length = anim.sequence.len() as u32;
+        let fps = anim.fps.max(1);
+        self.0
+            .extend([length as i32, anim.mode.as_shader_flag(), fps as i32]);
+        let start = self.0.len() as u32;
 
         #[cfg(not(feature = "atlas"))]
         self.0.extend(anim.sequence.into_iter().map(|i| i as i32));
@@ -800,13 +1573,239 @@ impl TilemapAnimations {
         TileAnimation {
             start,
             length,
-            fps: anim.fps,
+            fps,
+            mode: anim.mode,
+            start_time: 0.,
         }
     }
 }
 
+/// How animations advance in the chunks outside every camera.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Reflect)]
+pub enum OffscreenAnimation {
+    /// Keep animating as usual. Use this for gameplay relevant tilemaps.
+    Run,
+    /// Freeze the animations, and continue from where they stopped once visible again.
+    #[default]
+    Pause,
+    /// Only advance the animations every `interval` seconds.
+    Throttle(f32),
+}
+
+/// The animation clock of a chunk, stored as how far it is behind the tilemap clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ChunkAnimationClock {
+    pub(crate) lag: f32,
+    pending: f32,
+    visible: bool,
+    extracted_lag: f32,
+}
+
+impl ChunkAnimationClock {
+    /// Returns `true` if the chunk becomes visible with a lag that is not extracted yet.
+    fn tick(&mut self, offscreen: OffscreenAnimation, visible: bool, delta: f32) -> bool {
+        let shown = visible && !self.visible;
+        self.visible = visible;
+
+        match offscreen {
+            _ if visible => {
+                self.lag -= self.pending;
+                self.pending = 0.;
+            }
+            OffscreenAnimation::Run => {}
+            OffscreenAnimation::Pause => self.lag += delta,
+            OffscreenAnimation::Throttle(interval) => {
+                self.lag += delta;
+                self.pending += delta;
+                if self.pending >= interval {
+                    self.lag -= self.pending;
+                    self.pending = 0.;
+                }
+            }
+        }
+
+        if shown && self.lag != self.extracted_lag {
+            self.extracted_lag = self.lag;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Insert this to a tilemap to give each of its chunks an animation clock, which slows down
+/// or stops when the chunk is not visible.
+///
+/// **Notice**: Use [`TilemapAnimationLod::chunk_elapsed`] instead of `Time::elapsed_seconds`
+/// when starting animations on this tilemap. Chunks are rebuilt once when they become visible
+/// again after being paused.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+pub struct TilemapAnimationLod {
+    pub offscreen: OffscreenAnimation,
+    pub(crate) elapsed: f32,
+    #[reflect(ignore)]
+    pub(crate) chunks: HashMap<IVec2, ChunkAnimationClock>,
+}
+
+impl TilemapAnimationLod {
+    pub fn new(offscreen: OffscreenAnimation) -> Self {
+        Self {
+            offscreen,
+            ..Default::default()
+        }
+    }
+
+    /// The clock of the tilemap, which is always running.
+    /// Chunks that were paused are behind this.
+    #[inline]
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// The time that animations in this chunk are played at.
+    #[inline]
+    pub fn chunk_elapsed(&self, chunk_index: IVec2) -> f32 {
+        self.elapsed - self.chunk_lag(chunk_index)
+    }
+
+    #[inline]
+    pub(crate) fn chunk_lag(&self, chunk_index: IVec2) -> f32 {
+        self.chunks.get(&chunk_index).map_or(0., |c| c.lag)
+    }
+}
+
+pub fn animation_lod_updater(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(
+        &TilemapStorage,
+        &TilemapType,
+        &TilePivot,
+        &TilemapAxisFlip,
+        &TilemapSlotSize,
+        &TilemapTransform,
+        &mut TilemapAnimationLod,
+    )>,
+    cameras_query: Query<&CameraAabb2d>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    tilemaps_query.iter_mut().for_each(
+        |(storage, ty, tile_pivot, axis_flip, slot_size, transform, mut lod)| {
+            let lod = lod.as_mut();
+            lod.elapsed += delta;
+            lod.chunks
+                .retain(|index, _| storage.storage.chunks.contains_key(index));
+
+            for index in storage.storage.chunks.keys() {
+                let aabb = Rect::from_tilemap(
+                    *index,
+                    storage.storage.chunk_size,
+                    *ty,
+                    tile_pivot.0,
+                    *axis_flip,
+                    slot_size.0,
+                    *transform,
+                );
+                let visible = cameras_query
+                    .iter()
+                    .any(|cam| !cam.intersect(aabb).is_empty());
+
+                // The lag is applied to the start time of the animated tiles when extracting,
+                // so the chunk has to be extracted again.
+                let clock = lod.chunks.entry(*index).or_default();
+                if clock.tick(lod.offscreen, visible, delta) {
+                    storage.mark_chunk_dirty(&mut commands, *index);
+                }
+            }
+        },
+    );
+}
+
+/// Insert this to a tilemap to derive its [`TilemapTransform`] from the `GlobalTransform`
+/// every frame, so it can be parented to moving entities like platforms or ships.
+///
+/// The tilemap needs `Transform` and `GlobalTransform` to be part of the hierarchy,
+/// and you should move it through `Transform` instead. The `z_index` is kept as is.
+/// Colliders of [`PhysicsTilemap`](crate::tilemap::physics::PhysicsTilemap)s
+/// follow the tilemap like they do with `PhysicsTilemapFollow`.
+///
+/// **Notice**: This doesn't work together with [`TilemapParallax`].
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+pub struct SyncWithGlobalTransform;
+
+/// Insert this to a tilemap to make it scroll at a different speed than the camera,
+/// creating a fake 3D effect.
+///
+/// The tilemap is offset by `(camera - origin) * factor` on top of its [`TilemapTransform`].
+/// So a factor of 0 scrolls normally, 1 sticks to the camera, and negative factors
+/// scroll faster than the camera.
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+pub struct TilemapParallax {
+    pub factor: Vec2,
+    /// The camera position where the tilemap is not offset.
+    pub origin: Vec2,
+    /// Follow this camera instead of the first one.
+    pub camera: Option<Entity>,
+    pub(crate) offset: Vec2,
+}
+
+impl TilemapParallax {
+    pub fn new(factor: Vec2) -> Self {
+        Self {
+            factor,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_origin(mut self, origin: Vec2) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_camera(mut self, camera: Entity) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    /// The offset currently applied to the tilemap.
+    #[inline]
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+}
+
+pub fn parallax_updater(
+    mut tilemaps_query: Query<(&mut TilemapTransform, &mut TilemapParallax)>,
+    cameras_query: Query<&CameraAabb2d>,
+) {
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(mut transform, mut parallax)| {
+            let camera = match parallax.camera {
+                Some(camera) => cameras_query.get(camera).ok(),
+                None => cameras_query.iter().next(),
+            };
+            let Some(camera) = camera else {
+                return;
+            };
+
+            let offset = (camera.center() - parallax.origin) * parallax.factor;
+            if offset == parallax.offset {
+                return;
+            }
+
+            // Only apply the difference so the tilemap can still be moved by others.
+            transform.translation += offset - parallax.offset;
+            parallax.offset = offset;
+        });
+}
+
 pub fn transform_syncer(
-    mut tilemap_query: Query<(&TilemapTransform, &mut Transform), Changed<TilemapTransform>>,
+    mut tilemap_query: Query<
+        (&TilemapTransform, &mut Transform),
+        (Changed<TilemapTransform>, Without<SyncWithGlobalTransform>),
+    >,
 ) {
     tilemap_query
         .iter_mut()
@@ -815,6 +1814,33 @@ pub fn transform_syncer(
                 .translation
                 .extend(tilemap_transform.z_index as f32);
             transform.rotation = tilemap_transform.get_rotation_quat();
+            transform.scale = tilemap_transform.scale.extend(1.);
+        });
+}
+
+pub fn global_transform_syncer(
+    mut tilemap_query: Query<
+        (&GlobalTransform, &mut TilemapTransform),
+        (Changed<GlobalTransform>, With<SyncWithGlobalTransform>),
+    >,
+) {
+    tilemap_query
+        .iter_mut()
+        .for_each(|(global_transform, mut tilemap_transform)| {
+            let (scale, rotation, translation) = global_transform.to_scale_rotation_translation();
+
+            tilemap_transform.translation = translation.truncate();
+            // Snap to the nearest quarter turn and keep the rest in `angle`.
+            let angle = rotation.to_euler(EulerRot::ZYX).0;
+            let quarters = (angle / FRAC_PI_2).round();
+            tilemap_transform.rotation = match (quarters as i32).rem_euclid(4) {
+                0 => TilemapRotation::None,
+                1 => TilemapRotation::Cw90,
+                2 => TilemapRotation::Cw180,
+                _ => TilemapRotation::Cw270,
+            };
+            tilemap_transform.angle = angle - quarters * FRAC_PI_2;
+            tilemap_transform.scale = scale.truncate();
         });
 }
 
@@ -910,3 +1936,478 @@ pub fn tilemap_aabb_calculator(
         },
     );
 }
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        color::LinearRgba,
+        ecs::{
+            query::Changed,
+            system::{Commands, Query, RunSystemOnce},
+            world::World,
+        },
+        math::{IVec2, Quat, UVec2, Vec2, Vec3},
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+            texture::Image,
+        },
+        transform::components::{GlobalTransform, Transform},
+    };
+
+    use crate::{
+        math::GridRect,
+        tilemap::tile::{
+            RawTileAnimation, Tile, TileBuilder, TileLayer, TileLayerPosition, TileTexture,
+            TileUpdater,
+        },
+    };
+
+    use super::{
+        global_transform_syncer, ChunkAnimationClock, OffscreenAnimation, SyncWithGlobalTransform,
+        TilemapAnimations, TilemapRotation, TilemapStorage, TilemapTexture,
+        TilemapTextureDescriptor, TilemapTextureError, TilemapTextures, TilemapTransform,
+        TilemapZOrder,
+    };
+
+    #[test]
+    fn test_inverse_transform_point() {
+        let transform = TilemapTransform {
+            translation: Vec2::new(10., -30.),
+            rotation: TilemapRotation::Cw90,
+            ..Default::default()
+        }
+        .with_angle(0.6)
+        .with_scale(Vec2::new(1.5, 0.75));
+        let point = Vec2::new(3., -7.);
+        assert!(transform
+            .inverse_transform_point(transform.transform_point(point))
+            .abs_diff_eq(point, 1e-4));
+
+        // Collapsed axes don't produce NaN.
+        let collapsed = TilemapTransform {
+            scale: Vec2::new(0., 2.),
+            ..Default::default()
+        };
+        assert_eq!(
+            collapsed.inverse_transform_point(Vec2::new(5., 4.)),
+            Vec2::new(0., 2.)
+        );
+    }
+
+    #[test]
+    fn test_extrusion() {
+        // Two 2x1 tiles in a single row, each pixel has a unique red channel.
+        let image = Image::new(
+            Extent3d {
+                width: 4,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..4).flat_map(|r| [r, 0, 0, 255]).collect(),
+            TextureFormat::Rgba8Unorm,
+            RenderAssetUsages::default(),
+        );
+        let texture = TilemapTexture::new(
+            Default::default(),
+            TilemapTextureDescriptor::new(UVec2::new(4, 1), UVec2::new(2, 1)),
+        )
+        .with_extrusion(1);
+
+        // Compressed formats and images of other sizes are rejected.
+        let mut compressed = image.clone();
+        compressed.texture_descriptor.format = TextureFormat::Bc1RgbaUnorm;
+        assert_eq!(
+            texture.extrude(&compressed).err(),
+            Some(TilemapTextureError::UnsupportedFormat(
+                TextureFormat::Bc1RgbaUnorm
+            ))
+        );
+        assert_eq!(
+            texture.extrude(&Image::default()).err(),
+            Some(TilemapTextureError::SizeMismatch {
+                image: UVec2::ONE,
+                desc: UVec2::new(4, 1),
+            })
+        );
+
+        let (padded, desc) = texture.extrude(&image).unwrap();
+        assert_eq!(desc.size, UVec2::new(8, 3));
+        assert_eq!(desc.padding, 1);
+        assert_eq!(desc.tile_count(), UVec2::new(2, 1));
+
+        let padded_texture = TilemapTexture::new(Default::default(), desc);
+        assert_eq!(padded_texture.get_atlas_urect(1).min, UVec2::new(5, 1));
+        assert_eq!(padded_texture.tile_count(), 2);
+
+        let red = padded.data.chunks(4).map(|px| px[0]).collect::<Vec<_>>();
+        #[rustfmt::skip]
+        assert_eq!(red, [
+            0, 0, 1, 1, 2, 2, 3, 3,
+            0, 0, 1, 1, 2, 2, 3, 3,
+            0, 0, 1, 1, 2, 2, 3, 3,
+        ]);
+    }
+
+    #[test]
+    fn test_margin_and_spacing() {
+        // Two 2x1 tiles with 1 texel of margin and spacing, borders are 255.
+        #[rustfmt::skip]
+        let red: [u8; 21] = [
+            255, 255, 255, 255, 255, 255, 255,
+            255,   0,   1, 255,   2,   3, 255,
+            255, 255, 255, 255, 255, 255, 255,
+        ];
+        let image = Image::new(
+            Extent3d {
+                width: 7,
+                height: 3,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            red.into_iter().flat_map(|r| [r, 0, 0, 255]).collect(),
+            TextureFormat::Rgba8Unorm,
+            RenderAssetUsages::default(),
+        );
+        let desc =
+            TilemapTextureDescriptor::new_with_spacing(UVec2::new(7, 3), UVec2::new(2, 1), 1, 1);
+        assert_eq!(desc.tile_count(), UVec2::new(2, 1));
+
+        let texture = TilemapTexture::new(Default::default(), desc);
+        assert_eq!(texture.get_atlas_urect(0).min, UVec2::new(1, 1));
+        assert_eq!(texture.get_atlas_urect(1).min, UVec2::new(4, 1));
+        assert_eq!(
+            texture.as_atlas_layout().textures[1],
+            bevy::math::URect::new(4, 1, 6, 2)
+        );
+
+        // Margin and spacing are dropped when extruding.
+        let (padded, desc) = texture.with_extrusion(1).extrude(&image).unwrap();
+        assert_eq!(
+            desc,
+            TilemapTextureDescriptor {
+                size: UVec2::new(8, 3),
+                tile_size: UVec2::new(2, 1),
+                padding: 1,
+                ..Default::default()
+            }
+        );
+        let red = padded.data.chunks(4).map(|px| px[0]).collect::<Vec<_>>();
+        assert_eq!(&red[8..16], &[0, 0, 1, 1, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn test_tile_scales() {
+        let textures = TilemapTextures::new(
+            vec![
+                TilemapTexture::new(
+                    Default::default(),
+                    TilemapTextureDescriptor::new(UVec2::new(64, 64), UVec2::new(16, 16)),
+                ),
+                TilemapTexture::new(
+                    Default::default(),
+                    TilemapTextureDescriptor::new(UVec2::new(128, 64), UVec2::new(32, 16)),
+                ),
+            ],
+            bevy::render::render_resource::FilterMode::Nearest,
+        );
+
+        assert!(!textures.is_uniform_tile_size());
+        assert_eq!(textures.min_tile_size(), UVec2::new(16, 16));
+        assert_eq!(textures.tile_scales, vec![Vec2::ONE, Vec2::new(2., 1.)]);
+        assert_eq!(textures.uv_scales, vec![Vec2::new(0.5, 1.), Vec2::ONE]);
+    }
+
+    #[test]
+    fn test_occupancy() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        let builder = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(4, tilemap));
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                let mut storage = storages_query.get_mut(tilemap).unwrap();
+                let footprint = GridRect::new(IVec2::ZERO, UVec2::splat(2));
+                let overlapped = GridRect::new(IVec2::ONE, UVec2::splat(2));
+
+                assert!(storage.fill_if_empty(&mut commands, footprint, TileBuilder::new()));
+                assert!(!storage.fill_if_empty(&mut commands, overlapped, TileBuilder::new()));
+                assert!(storage.get(IVec2::splat(2)).is_none());
+
+                let claimed = GridRect::new(IVec2::new(2, 0), UVec2::splat(2));
+                assert!(storage.claim_rect(claimed, builder));
+                assert!(!storage.is_empty_rect(GridRect::new(IVec2::new(3, 1), UVec2::ONE)));
+                assert!(!storage.fill_if_empty(&mut commands, claimed, TileBuilder::new()));
+                assert_eq!(storage.get_claim(IVec2::new(3, 1)), Some(builder));
+
+                storage.release_claims(builder);
+                assert!(storage.fill_if_empty(&mut commands, claimed, TileBuilder::new()));
+            },
+        );
+    }
+
+    #[test]
+    fn test_sync_with_global_transform() {
+        let mut world = World::new();
+        let tilemap = world
+            .spawn((
+                TilemapTransform {
+                    z_index: 3.,
+                    ..Default::default()
+                },
+                GlobalTransform::from(
+                    Transform::from_translation(Vec3::new(10., 20., 5.))
+                        .with_rotation(Quat::from_rotation_z(100f32.to_radians()))
+                        .with_scale(Vec3::new(2., 0.5, 1.)),
+                ),
+                SyncWithGlobalTransform,
+            ))
+            .id();
+
+        world.run_system_once(global_transform_syncer);
+
+        let transform = world.get::<TilemapTransform>(tilemap).unwrap();
+        assert_eq!(transform.translation, Vec2::new(10., 20.));
+        assert_eq!(transform.z_index, 3.);
+        assert_eq!(transform.rotation, TilemapRotation::Cw90);
+        assert!((transform.get_angle().to_degrees() - 100.).abs() < 1e-3);
+        assert!(transform.scale.abs_diff_eq(Vec2::new(2., 0.5), 1e-5));
+    }
+
+    #[test]
+    fn test_z_order() {
+        let mut world = World::new();
+        let (a, b, c, d) = (
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+        );
+        let mut z_order = TilemapZOrder::new(10., 1.);
+
+        assert_eq!(z_order.allocate(a, 2, 1., None), 10.);
+        assert_eq!(z_order.allocate(b, 1, 1., None), 13.);
+        // Overridden maps are avoided as well.
+        assert_eq!(z_order.allocate(c, 0, 1., Some(15.)), 15.);
+        assert_eq!(z_order.allocate(d, 3, 1., None), 16.);
+
+        z_order.release(a);
+        assert_eq!(z_order.get(a), None);
+        // Reuse the gap left by `a`.
+        assert_eq!(z_order.allocate(a, 1, 1., None), 10.);
+        assert_eq!(z_order.get(d), Some((16., 20.)));
+        // Start above the given z, still avoiding the other maps.
+        assert_eq!(z_order.allocate_from(b, 12., 1, 1., None), 12.);
+        assert_eq!(z_order.allocate_from(c, 11., 0, 1., None), 14.);
+    }
+
+    #[test]
+    fn test_batched_update() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(4, tilemap));
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                storages_query.get_mut(tilemap).unwrap().fill_rect(
+                    &mut commands,
+                    GridRect::new(IVec2::ZERO, UVec2::splat(3)),
+                    TileBuilder::new().with_layer(0, TileLayer::no_flip(0)),
+                );
+            },
+        );
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                let mut storage = storages_query.get_mut(tilemap).unwrap();
+                storage.update_rect(
+                    &mut commands,
+                    GridRect::new(IVec2::ZERO, UVec2::splat(2)),
+                    TileUpdater::new().with_tint(LinearRgba::RED),
+                );
+                storage.update_many(
+                    &mut commands,
+                    [
+                        (
+                            IVec2::ONE,
+                            TileUpdater::new()
+                                .with_layer(TileLayerPosition::Top, TileLayer::no_flip(1)),
+                        ),
+                        // Empty
+                        (
+                            IVec2::splat(5),
+                            TileUpdater::new().with_tint(LinearRgba::BLUE),
+                        ),
+                    ],
+                );
+            },
+        );
+
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        let tile = |index| world.get::<Tile>(storage.get(index).unwrap()).unwrap();
+        assert_eq!(tile(IVec2::ZERO).tint, LinearRgba::RED);
+        assert_eq!(tile(IVec2::splat(2)).tint, LinearRgba::WHITE);
+        assert_eq!(
+            tile(IVec2::ONE).texture,
+            TileTexture::Static(vec![TileLayer::no_flip(0), TileLayer::no_flip(1)])
+        );
+        // No updater is left on the tiles.
+        assert!(world.query::<&TileUpdater>().iter(&world).next().is_none());
+    }
+
+    #[test]
+    fn test_mark_chunk_dirty() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(4, tilemap));
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                storages_query.get_mut(tilemap).unwrap().fill_rect(
+                    &mut commands,
+                    GridRect::new(IVec2::ZERO, UVec2::new(6, 1)),
+                    TileBuilder::new(),
+                );
+            },
+        );
+        world.clear_trackers();
+
+        world.run_system_once(
+            move |mut commands: Commands, storages_query: Query<&TilemapStorage>| {
+                storages_query
+                    .get(tilemap)
+                    .unwrap()
+                    .mark_chunk_dirty(&mut commands, IVec2::ZERO);
+            },
+        );
+
+        let mut changed = world
+            .query_filtered::<&Tile, Changed<Tile>>()
+            .iter(&world)
+            .map(|t| t.index)
+            .collect::<Vec<_>>();
+        changed.sort_by_key(|i| i.x);
+        assert_eq!(
+            changed,
+            (0..4).map(|x| IVec2::new(x, 0)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_zero_fps() {
+        let mut animations = TilemapAnimations::default();
+        let animation = animations.register(RawTileAnimation::from_atlas_indices(0, 0..4, 0));
+        assert_eq!(animation.duration(), 4.);
+        assert_eq!(animations.0[3], 1);
+    }
+
+    #[test]
+    fn test_chunk_animation_clock() {
+        let mut clock = ChunkAnimationClock::default();
+        assert!(!clock.tick(OffscreenAnimation::Pause, true, 1.));
+        assert!(!clock.tick(OffscreenAnimation::Pause, false, 1.));
+        assert!(!clock.tick(OffscreenAnimation::Pause, false, 1.));
+        assert_eq!(clock.lag, 2.);
+        // Extracted again once visible, and continues from where it stopped.
+        assert!(clock.tick(OffscreenAnimation::Pause, true, 1.));
+        assert!(!clock.tick(OffscreenAnimation::Pause, true, 1.));
+        assert_eq!(clock.lag, 2.);
+
+        let mut clock = ChunkAnimationClock::default();
+        clock.tick(OffscreenAnimation::Throttle(2.), false, 1.);
+        assert_eq!(clock.lag, 1.);
+        clock.tick(OffscreenAnimation::Throttle(2.), false, 1.);
+        assert_eq!(clock.lag, 0.);
+        clock.tick(OffscreenAnimation::Throttle(2.), false, 1.);
+        // Catches up when visible.
+        assert!(!clock.tick(OffscreenAnimation::Throttle(2.), true, 1.));
+        assert_eq!(clock.lag, 0.);
+
+        let mut clock = ChunkAnimationClock::default();
+        clock.tick(OffscreenAnimation::Run, false, 1.);
+        assert!(!clock.tick(OffscreenAnimation::Run, true, 1.));
+        assert_eq!(clock.lag, 0.);
+    }
+
+    #[test]
+    fn test_layer_ops() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(4, tilemap));
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                let mut storage = storages_query.get_mut(tilemap).unwrap();
+                storage.fill_rect(
+                    &mut commands,
+                    GridRect::new(IVec2::ZERO, UVec2::splat(2)),
+                    TileBuilder::new()
+                        .with_layer(0, TileLayer::no_flip(0))
+                        .with_layer(1, TileLayer::no_flip(1)),
+                );
+                storage.set(
+                    &mut commands,
+                    IVec2::new(2, 0),
+                    TileBuilder::new()
+                        .with_layer(0, TileLayer::no_flip(0))
+                        .with_layer(1, TileLayer::no_flip(1))
+                        .with_layer(2, TileLayer::no_flip(2)),
+                );
+            },
+        );
+
+        let decorations = world.run_system_once(
+            move |storages_query: Query<&TilemapStorage>, tiles_query: Query<&Tile>| {
+                storages_query
+                    .get(tilemap)
+                    .unwrap()
+                    .extract_layer(&tiles_query, 1)
+            },
+        );
+        assert_eq!(decorations.tiles.len(), 5);
+        assert_eq!(
+            decorations.get(IVec2::new(2, 0)),
+            Some(&TileBuilder::new().with_layer(0, TileLayer::no_flip(1)))
+        );
+
+        world.run_system_once(
+            move |mut commands: Commands, storages_query: Query<&TilemapStorage>| {
+                storages_query
+                    .get(tilemap)
+                    .unwrap()
+                    .clear_layer(&mut commands, 1);
+            },
+        );
+
+        let storage = world.get::<TilemapStorage>(tilemap).unwrap();
+        let texture = |index| {
+            world
+                .get::<Tile>(storage.get(index).unwrap())
+                .unwrap()
+                .texture
+                .clone()
+        };
+        assert_eq!(
+            texture(IVec2::ZERO),
+            TileTexture::Static(vec![TileLayer::no_flip(0)])
+        );
+        // The layer above keeps its index.
+        assert_eq!(
+            texture(IVec2::new(2, 0)),
+            TileTexture::Static(vec![
+                TileLayer::no_flip(0),
+                TileLayer::default(),
+                TileLayer::no_flip(2)
+            ])
+        );
+    }
+}
@@ -1,49 +1,127 @@
 use bevy::{
-    app::{Plugin, PostUpdate, PreUpdate, Update},
+    app::{FixedUpdate, Plugin, PostUpdate, PreUpdate, Update},
     asset::AssetApp,
+    ecs::schedule::{IntoSystemConfigs, SystemSet},
+    transform::TransformSystem,
 };
 
-use crate::tilemap::{
-    chunking::camera::{CameraChunkUpdater, CameraChunkUpdation},
-    map::{
-        TilePivot, TileRenderSize, TilemapAabbs, TilemapAnimations, TilemapLayerOpacities,
-        TilemapName, TilemapSlotSize, TilemapStorage, TilemapTexture, TilemapTextureDescriptor,
-        TilemapTextures, TilemapTransform, TilemapType,
+use crate::{
+    render::{
+        chunk::ChunkUnload,
+        material::{init_material_asset, StandardTilemapMaterial},
+    },
+    tilemap::{
+        chunking::{
+            camera::{CameraChunkSetUpdation, CameraChunkUpdater, CameraChunkUpdation},
+            hibernate::{HibernatedChunk, TilemapHibernation},
+            random_tick::RandomTick,
+        },
+        command::{
+            TilemapCommandApplied, TilemapCommandConfig, TilemapCommandEvent, TilemapCommandFailed,
+        },
+        diff::{TilemapDiff, TilemapDiffRecorder, TilemapLayerDiff},
+        height::TilemapHeightMap,
+        map::{
+            EntiTilesDefaults, OffscreenAnimation, SyncWithGlobalTransform, TilePivot,
+            TileRenderSize, TilemapAabbs, TilemapAnimationLod, TilemapAnimations,
+            TilemapLayerOpacities, TilemapName, TilemapParallax, TilemapSlotSize, TilemapStorage,
+            TilemapTexture, TilemapTextureDescriptor, TilemapTextures, TilemapTransform,
+            TilemapType, TilemapZOrder,
+        },
+        replay::{TilemapPlayer, TilemapRecorder},
+        replication::{TileChangeOp, TileChangeOps, TileChangeStream},
+        territory::{TerritoryBorders, TerritoryOverlay, TerritoryTilemap},
+        tile::{
+            LayerUpdater, OneShotTileAnimation, Tile, TileAnimation, TileAnimationMode,
+            TileBuilder, TileFlip, TileLayer, TileLayerPosition, TileTexture, TileUpdater,
+        },
+        trigger::{RegionEntered, RegionExited, TileTriggerActor, TileTriggerTilemap},
+        tween::{
+            TilemapEasing, TilemapTween, TilemapTweenFinished, TilemapTweenTarget, TilemapTweens,
+        },
     },
-    tile::{LayerUpdater, Tile, TileLayer, TileTexture, TileUpdater},
 };
 
 #[cfg(feature = "algorithm")]
 pub mod algorithm;
+#[cfg(feature = "audio")]
+pub mod audio;
 pub mod buffers;
 pub mod bundles;
 pub mod chunking;
+pub mod command;
 pub mod coordinates;
 pub mod despawn;
+pub mod diff;
+pub mod height;
 pub mod map;
 #[cfg(feature = "physics")]
 pub mod physics;
+pub mod picking;
+pub mod replay;
+pub mod replication;
+pub mod scripting;
+pub mod territory;
 pub mod tile;
+pub mod trigger;
+pub mod tween;
+
+/// The system sets of the tilemaps, to order your own systems against.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntiTilesTilemapSystems {
+    /// Updates [`TilemapTransform`]s from tweens and parallax in `Update`,
+    /// and from the `GlobalTransform`s of [`SyncWithGlobalTransform`] tilemaps in `PostUpdate`.
+    TransformUpdate,
+    /// Applies [`TileUpdater`]s and rearranges tiles in `Update`.
+    TileUpdate,
+}
 
 pub struct EntiTilesTilemapPlugin;
 
 impl Plugin for EntiTilesTilemapPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        // Territory overlays and tilemap bundles need the standard material
+        // even if the material plugin is disabled.
+        init_material_asset::<StandardTilemapMaterial>(app);
+
         app.add_systems(PreUpdate, despawn::despawn_component_remover)
             .add_systems(
                 Update,
                 (
-                    map::transform_syncer,
+                    (
+                        tween::tilemap_tweener,
+                        map::parallax_updater,
+                        map::transform_syncer,
+                    )
+                        .chain()
+                        .in_set(EntiTilesTilemapSystems::TransformUpdate),
                     map::queued_chunk_aabb_calculator,
                     map::tilemap_aabb_calculator,
-                    tile::tile_updater,
-                    tile::tile_rearranger,
+                    (tile::tile_updater, tile::tile_rearranger)
+                        .in_set(EntiTilesTilemapSystems::TileUpdate),
+                    (map::animation_lod_updater, tile::one_shot_animation_player).chain(),
+                    territory::territory_updater,
+                    trigger::trigger_updater,
                     chunking::camera::camera_chunk_update,
+                    chunking::hibernate::chunk_hibernator,
+                    (
+                        replay::tilemap_player,
+                        diff::tilemap_diff_applier,
+                        command::tilemap_command_applier,
+                        replay::tilemap_recorder,
+                        diff::tilemap_diff_recorder,
+                        replication::tile_change_streamer,
+                    )
+                        .chain(),
                 ),
             )
+            .add_systems(FixedUpdate, chunking::random_tick::random_ticker)
             .add_systems(
                 PostUpdate,
                 (
+                    map::global_transform_syncer
+                        .after(TransformSystem::TransformPropagate)
+                        .in_set(EntiTilesTilemapSystems::TransformUpdate),
                     despawn::despawn_tilemap,
                     despawn::despawn_tiles,
                     #[cfg(feature = "physics")]
@@ -51,10 +129,16 @@ impl Plugin for EntiTilesTilemapPlugin {
                 ),
             )
             .register_type::<TileLayer>()
+            .register_type::<TileFlip>()
+            .register_type::<TileLayerPosition>()
+            .register_type::<TileBuilder>()
             .register_type::<LayerUpdater>()
             .register_type::<TileUpdater>()
             .register_type::<Tile>()
             .register_type::<TileTexture>()
+            .register_type::<TileAnimation>()
+            .register_type::<TileAnimationMode>()
+            .register_type::<OneShotTileAnimation>()
             .register_type::<TilemapName>()
             .register_type::<TileRenderSize>()
             .register_type::<TilemapSlotSize>()
@@ -62,19 +146,61 @@ impl Plugin for EntiTilesTilemapPlugin {
             .register_type::<TilePivot>()
             .register_type::<TilemapLayerOpacities>()
             .register_type::<TilemapStorage>()
+            .register_type::<EntiTilesDefaults>()
+            .register_type::<TilemapZOrder>()
             .register_type::<TilemapAabbs>()
             .register_type::<TilemapTransform>()
+            .register_type::<TilemapParallax>()
+            .register_type::<SyncWithGlobalTransform>()
             .register_type::<TilemapTexture>()
             .register_type::<TilemapTextureDescriptor>()
             .register_type::<TilemapAnimations>()
+            .register_type::<TilemapAnimationLod>()
+            .register_type::<OffscreenAnimation>()
             .register_type::<CameraChunkUpdation>()
+            .register_type::<CameraChunkSetUpdation>()
+            .register_type::<RandomTick>()
+            .register_type::<TerritoryTilemap>()
+            .register_type::<TerritoryBorders>()
+            .register_type::<TerritoryOverlay>()
             .register_type::<CameraChunkUpdater>()
+            .register_type::<TilemapHibernation>()
+            .register_type::<HibernatedChunk>()
+            .register_type::<TilemapDiff>()
+            .register_type::<TilemapLayerDiff>()
+            .register_type::<TileChangeOp>()
+            .register_type::<TileChangeOps>()
+            .register_type::<TileChangeStream>()
+            .register_type::<TilemapHeightMap>()
+            .register_type::<TileTriggerTilemap>()
+            .register_type::<TileTriggerActor>()
+            .register_type::<TilemapTweens>()
+            .register_type::<TilemapTween>()
+            .register_type::<TilemapTweenTarget>()
+            .register_type::<TilemapEasing>()
             .init_asset::<TilemapTextures>()
-            .add_event::<CameraChunkUpdation>();
+            .add_event::<CameraChunkUpdation>()
+            .add_event::<CameraChunkSetUpdation>()
+            .add_event::<RandomTick>()
+            .add_event::<TilemapCommandEvent>()
+            .add_event::<TilemapCommandFailed>()
+            .add_event::<TilemapCommandApplied>()
+            .add_event::<RegionEntered>()
+            .add_event::<RegionExited>()
+            .add_event::<ChunkUnload>()
+            .add_event::<TilemapTweenFinished>()
+            .init_resource::<EntiTilesDefaults>()
+            .init_resource::<TilemapZOrder>()
+            .init_resource::<TilemapCommandConfig>()
+            .init_resource::<TilemapRecorder>()
+            .init_resource::<TilemapDiffRecorder>()
+            .init_resource::<TilemapPlayer>()
+            .observe(map::z_order_unloader)
+            .observe(chunking::hibernate::hibernation_unloader)
+            .observe(trigger::trigger_unloader)
+            .observe(replay::tilemap_recorder_unloader);
 
         #[cfg(feature = "algorithm")]
         app.add_plugins(algorithm::EntiTilesAlgorithmTilemapPlugin);
-        #[cfg(feature = "physics")]
-        app.add_plugins(physics::EntiTilesPhysicsTilemapPlugin);
     }
 }
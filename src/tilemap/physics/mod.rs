@@ -1,7 +1,14 @@
-use avian2d::prelude::{Collider, Friction, RigidBody};
+use avian2d::prelude::{Collider, CollisionLayers, Friction, Restitution, RigidBody};
 use bevy::{
-    app::{App, Plugin, Update},
-    ecs::{component::Component, entity::Entity, event::Event, system::Commands},
+    app::{App, Plugin, PostUpdate, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::Event,
+        query::{With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, EntityCommands},
+    },
     math::{IVec2, UVec2, Vec2},
     reflect::Reflect,
     utils::HashMap,
@@ -14,6 +21,8 @@ use crate::{
         chunking::storage::{
             ChunkedStorage, EntityChunkedStorage, PackedPhysicsTileChunkedStorage,
         },
+        map::SyncWithGlobalTransform,
+        EntiTilesTilemapSystems,
     },
 };
 
@@ -26,15 +35,34 @@ impl Plugin for EntiTilesPhysicsTilemapPlugin {
         app.add_systems(
             Update,
             (
-                systems::spawn_colliders,
+                (
+                    systems::spawn_colliders,
+                    systems::physics_tilemap_follower::<(
+                        With<PhysicsTilemapFollow>,
+                        Without<SyncWithGlobalTransform>,
+                    )>,
+                )
+                    .chain()
+                    .after(EntiTilesTilemapSystems::TransformUpdate)
+                    .after(EntiTilesTilemapSystems::TileUpdate),
                 systems::data_physics_tilemap_analyzer,
+                systems::physics_tilemap_rebuilder.before(systems::spawn_colliders),
             ),
+        )
+        // The transforms of synced tilemaps are only known after the propagation.
+        .add_systems(
+            PostUpdate,
+            systems::physics_tilemap_follower::<With<SyncWithGlobalTransform>>
+                .after(EntiTilesTilemapSystems::TransformUpdate),
         );
 
         app.register_type::<PhysicsTileSpawn>()
             .register_type::<PhysicsTilemap>()
+            .register_type::<PhysicsTilemapFollow>()
+            .register_type::<PhysicsTileIndex>()
             .register_type::<DataPhysicsTilemap>()
-            .register_type::<PhysicsTile>();
+            .register_type::<PhysicsTile>()
+            .register_type::<PhysicsCollisionLayers>();
 
         app.add_event::<PhysicsTileSpawn>();
     }
@@ -102,26 +130,66 @@ impl Into<PhysicsTile> for PackedPhysicsTile {
 impl Tiles for PackedPhysicsTile {}
 
 impl PackedPhysicsTile {
+    /// Spawn the collider along with a [`PhysicsTileIndex`] pointing to `index` on `tilemap`.
+    pub fn spawn_on_tilemap(
+        &self,
+        commands: &mut Commands,
+        tilemap: Entity,
+        index: IVec2,
+    ) -> Entity {
+        let entity = self.spawn(commands);
+        commands
+            .entity(entity)
+            .insert(PhysicsTileIndex { tilemap, index });
+        entity
+    }
+
     pub fn spawn(&self, commands: &mut Commands) -> Entity {
         let mut entity = commands.spawn(match self.collider.clone() {
             PhysicsCollider::Convex(verts) => Collider::convex_hull(verts).unwrap(),
             PhysicsCollider::Polyline(verts) => Collider::polyline(verts, None),
         });
-        if self.physics_tile.rigid_body {
-            entity.insert(RigidBody::Static);
-        }
-        if let Some(friction) = &self.physics_tile.friction {
-            entity.insert(Friction::new(*friction));
-        }
+        self.physics_tile.insert(&mut entity);
         entity.id()
     }
 }
 
+/// The collision layers of a physics tile, which are converted into avian `CollisionLayers`.
+///
+/// Each bit is a layer. Two colliders interact only if the memberships of each one
+/// overlap the filters of the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhysicsCollisionLayers {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl PhysicsCollisionLayers {
+    pub fn new(memberships: u32, filters: u32) -> Self {
+        Self {
+            memberships,
+            filters,
+        }
+    }
+}
+
+impl From<PhysicsCollisionLayers> for CollisionLayers {
+    fn from(value: PhysicsCollisionLayers) -> Self {
+        CollisionLayers::new(value.memberships, value.filters)
+    }
+}
+
 #[derive(Debug, Clone, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct PhysicsTile {
     pub rigid_body: bool,
     pub friction: Option<f32>,
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub restitution: Option<f32>,
+    /// Interacts with everything if `None`.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub collision_layers: Option<PhysicsCollisionLayers>,
 }
 
 impl Default for PhysicsTile {
@@ -129,6 +197,36 @@ impl Default for PhysicsTile {
         Self {
             rigid_body: true,
             friction: Default::default(),
+            restitution: Default::default(),
+            collision_layers: Default::default(),
+        }
+    }
+}
+
+impl PhysicsTile {
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = Some(restitution);
+        self
+    }
+
+    pub fn with_collision_layers(mut self, collision_layers: PhysicsCollisionLayers) -> Self {
+        self.collision_layers = Some(collision_layers);
+        self
+    }
+
+    /// Insert the physics components except the collider to the entity.
+    pub fn insert(&self, entity: &mut EntityCommands) {
+        if self.rigid_body {
+            entity.insert(RigidBody::Static);
+        }
+        if let Some(friction) = self.friction {
+            entity.insert(Friction::new(friction));
+        }
+        if let Some(restitution) = self.restitution {
+            entity.insert(Restitution::new(restitution));
+        }
+        if let Some(layers) = self.collision_layers {
+            entity.insert(CollisionLayers::from(layers));
         }
     }
 }
@@ -147,6 +245,8 @@ pub struct DataPhysicsTilemap {
     pub(crate) size: UVec2,
     pub(crate) air: i32,
     pub(crate) tiles: HashMap<i32, PhysicsTile>,
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) rebuild_delay: Option<f32>,
 }
 
 impl DataPhysicsTilemap {
@@ -180,6 +280,7 @@ impl DataPhysicsTilemap {
             size,
             air,
             tiles,
+            rebuild_delay: None,
         }
     }
 
@@ -203,9 +304,23 @@ impl DataPhysicsTilemap {
             size,
             air,
             tiles,
+            rebuild_delay: None,
         }
     }
 
+    /// Keep the data after the analysis, so the tilemap can be edited using
+    /// [`PhysicsTilemap::set_data`] and [`PhysicsTilemap::remove`].
+    ///
+    /// Edited chunks are analyzed again once they haven't been edited for `delay` seconds,
+    /// so a burst of edits only rebuilds the colliders once.
+    ///
+    /// **Notice**: Colliders won't be concatenated across chunks,
+    /// so there might be a few more colliders than a full analysis.
+    pub fn with_incremental_rebuild(mut self, delay: f32) -> Self {
+        self.rebuild_delay = Some(delay);
+        self
+    }
+
     /// Try to get the tile at the given index.
     ///
     /// This will return the air tile if the index is out of bounds.
@@ -230,12 +345,54 @@ impl DataPhysicsTilemap {
     }
 }
 
+/// Insert this along with [`PhysicsTilemap`] to make the colliders follow
+/// the [`TilemapTransform`](crate::tilemap::map::TilemapTransform), like moving platforms or ships.
+///
+/// Colliders will be spawned relative to the tilemap, and their positions and rotations
+/// are synchronized when the transform changes. Rigid bodies become kinematic instead of static,
+/// and are moved by their velocities, so the bodies standing on them are carried along.
+///
+/// **Notice**: Colliders spawned before inserting this are not relative to the tilemap,
+/// so insert this before spawning any physics tile. And the scale is only applied when
+/// spawning the colliders.
+#[derive(Component, Default, Debug, Clone, Copy, Reflect)]
+pub struct PhysicsTilemapFollow;
+
+/// Inserted on colliders spawned by a [`PhysicsTilemap`],
+/// so collision events can be translated back into tile indices.
+///
+/// **Notice**: Concatenated colliders use the index of their bottom left tile.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct PhysicsTileIndex {
+    pub tilemap: Entity,
+    pub index: IVec2,
+}
+
+/// The data kept from a [`DataPhysicsTilemap`] with incremental rebuild enabled.
+#[derive(Debug, Clone, Reflect)]
+pub(crate) struct RetainedPhysicsData {
+    pub(crate) data: ChunkedStorage<i32>,
+    pub(crate) air: i32,
+    pub(crate) tiles: HashMap<i32, PhysicsTile>,
+    pub(crate) delay: f32,
+    /// The chunks to rebuild and the seconds left before rebuilding.
+    pub(crate) dirty_chunks: HashMap<IVec2, f32>,
+}
+
 /// A tilemap with physics tiles.
+///
+/// With the `algorithm` feature, the tiles covered by spawned colliders are removed from the
+/// path tilemap of the same entity, so the paths passing through them are blocked.
 #[derive(Component, Debug, Clone, Reflect)]
 pub struct PhysicsTilemap {
     pub(crate) storage: EntityChunkedStorage,
     pub(crate) spawn_queue: Vec<(GridRect, PhysicsTile, Option<i32>)>,
+    /// Tiles with their colliders already built.
+    pub(crate) packed_spawn_queue: Vec<(IVec2, PackedPhysicsTile)>,
     pub(crate) data: PackedPhysicsTileChunkedStorage,
+    /// The inverse of `storage`.
+    pub(crate) colliders: HashMap<Entity, IVec2>,
+    pub(crate) retained: Option<RetainedPhysicsData>,
 }
 
 impl PhysicsTilemap {
@@ -246,7 +403,10 @@ impl PhysicsTilemap {
         PhysicsTilemap {
             storage: ChunkedStorage::default(),
             spawn_queue: Vec::new(),
+            packed_spawn_queue: Vec::new(),
             data: ChunkedStorage::default(),
+            colliders: HashMap::default(),
+            retained: None,
         }
     }
 
@@ -255,16 +415,109 @@ impl PhysicsTilemap {
         PhysicsTilemap {
             storage: ChunkedStorage::new(chunk_size),
             spawn_queue: Vec::new(),
+            packed_spawn_queue: Vec::new(),
             data: ChunkedStorage::new(chunk_size),
+            colliders: HashMap::default(),
+            retained: None,
         }
     }
 
+    /// Create a physics tilemap that respawns the saved tiles,
+    /// including their colliders, friction and other properties.
+    pub fn from_packed(data: &PackedPhysicsTileChunkedStorage) -> Self {
+        let mut tilemap = Self::new_with_chunk_size(data.chunk_size);
+        tilemap.packed_spawn_queue = data
+            .chunked_iter_some()
+            .map(|(chunk_index, in_chunk_index, tile)| {
+                (
+                    data.inverse_transform_index(chunk_index, in_chunk_index),
+                    tile.clone(),
+                )
+            })
+            .collect();
+        tilemap
+    }
+
+    /// Get the saved state of the tile at `index`.
+    ///
+    /// **Notice**: For concatenated colliders, only the bottom left tile has a state.
+    #[inline]
+    pub fn get_packed(&self, index: IVec2) -> Option<&PackedPhysicsTile> {
+        self.data.get_elem(index)
+    }
+
     /// Get a tile.
     #[inline]
     pub fn get(&self, index: IVec2) -> Option<Entity> {
         self.storage.get_elem(index).cloned()
     }
 
+    /// Get the index of the tile that `collider` belongs to.
+    #[inline]
+    pub fn get_tile_of_collider(&self, collider: Entity) -> Option<IVec2> {
+        self.colliders.get(&collider).copied()
+    }
+
+    /// Iterate over all the spawned tiles and their colliders.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (IVec2, Entity)> + '_ {
+        self.storage
+            .chunked_iter_some()
+            .map(|(chunk_index, in_chunk_index, collider)| {
+                (
+                    self.storage
+                        .inverse_transform_index(chunk_index, in_chunk_index),
+                    *collider,
+                )
+            })
+    }
+
+    /// Iterate over the spawned tiles inside `area`.
+    pub fn iter_tiles_in(&self, area: GridRect) -> impl Iterator<Item = (IVec2, Entity)> + '_ {
+        (area.origin.y..=area.dest.y)
+            .flat_map(move |y| (area.origin.x..=area.dest.x).map(move |x| IVec2 { x, y }))
+            .filter_map(|index| self.get(index).map(|collider| (index, collider)))
+    }
+
+    /// Returns the replaced collider.
+    pub(crate) fn set_collider(&mut self, index: IVec2, collider: Entity) -> Option<Entity> {
+        let old = self.storage.get_elem(index).copied();
+        if let Some(old) = old {
+            self.colliders.remove(&old);
+        }
+        self.storage.set_elem(index, collider);
+        self.colliders.insert(collider, index);
+        old
+    }
+
+    /// Get the integer representation of the tile at `index`.
+    ///
+    /// Returns `None` if the tilemap doesn't keep the data,
+    /// see [`DataPhysicsTilemap::with_incremental_rebuild`].
+    pub fn get_data(&self, index: IVec2) -> Option<i32> {
+        self.retained
+            .as_ref()
+            .map(|r| r.data.get_elem(index).copied().unwrap_or(r.air))
+    }
+
+    /// Set the integer representation of the tile at `index`,
+    /// and rebuild the colliders of the chunk later.
+    ///
+    /// **Notice**: This does nothing if the tilemap doesn't keep the data,
+    /// see [`DataPhysicsTilemap::with_incremental_rebuild`].
+    pub fn set_data(&mut self, index: IVec2, value: i32) {
+        let Some(retained) = &mut self.retained else {
+            return;
+        };
+
+        if value == retained.air {
+            retained.data.remove_elem(index);
+        } else {
+            retained.data.set_elem(index, value);
+        }
+        let chunk_index = retained.data.transform_index(index).0;
+        retained.dirty_chunks.insert(chunk_index, retained.delay);
+    }
+
     /// Set a tile. This actually queues the tile and it will be spawned later.
     #[inline]
     pub fn set(&mut self, index: IVec2, tile: PhysicsTile) {
@@ -272,12 +525,38 @@ impl PhysicsTilemap {
             .push((GridRect::from_min_max(index, index), tile, None));
     }
 
+    /// Set a tile with a custom collider. This actually queues the tile and it will be spawned later.
+    ///
+    /// The vertices are in world space, or relative to the tilemap
+    /// if [`PhysicsTilemapFollow`] is inserted.
+    #[inline]
+    pub fn set_custom(&mut self, index: IVec2, collider: PhysicsCollider, tile: PhysicsTile) {
+        self.packed_spawn_queue.push((
+            index,
+            PackedPhysicsTile {
+                parent: index,
+                collider,
+                physics_tile: tile,
+            },
+        ));
+    }
+
     /// Remove a tile.
+    ///
+    /// If the tilemap keeps the data, the colliders of the chunk will be rebuilt later,
+    /// so tiles in the middle of concatenated colliders can be removed as well.
     #[inline]
     pub fn remove(&mut self, commands: &mut Commands, index: IVec2) {
+        if let Some(air) = self.retained.as_ref().map(|r| r.air) {
+            self.set_data(index, air);
+            return;
+        }
+
         if let Some(entity) = self.storage.remove_elem(index) {
+            self.colliders.remove(&entity);
             commands.entity(entity).despawn();
         }
+        self.data.remove_elem(index);
     }
 
     /// Remove a chunk.
@@ -285,9 +564,16 @@ impl PhysicsTilemap {
     pub fn remove_chunk(&mut self, commands: &mut Commands, index: IVec2) {
         if let Some(chunk) = self.storage.remove_chunk(index) {
             chunk.into_iter().filter_map(|e| e).for_each(|entity| {
+                self.colliders.remove(&entity);
                 commands.entity(entity).despawn();
             });
         }
+        self.data.remove_chunk(index);
+
+        if let Some(retained) = &mut self.retained {
+            retained.data.remove_chunk(index);
+            retained.dirty_chunks.remove(&index);
+        }
     }
 
     /// Remove all tiles.
@@ -297,6 +583,13 @@ impl PhysicsTilemap {
             commands.entity(*entity).despawn();
         }
         self.storage.clear();
+        self.data.clear();
+        self.colliders.clear();
+
+        if let Some(retained) = &mut self.retained {
+            retained.data.clear();
+            retained.dirty_chunks.clear();
+        }
     }
 
     /// Fill a rectangle area with the same tile.
@@ -363,3 +656,128 @@ impl PhysicsTilemap {
             }));
     }
 }
+
+#[cfg(all(test, feature = "serializing"))]
+mod test {
+    use avian2d::prelude::{Collider, Friction, Restitution};
+    use bevy::{
+        app::{App, Update},
+        ecs::{
+            entity::Entity,
+            system::{Commands, Query, RunSystemOnce},
+        },
+        math::{IVec2, Vec2},
+    };
+
+    use crate::tilemap::{
+        chunking::storage::PackedPhysicsTileChunkedStorage,
+        map::{TilePivot, TilemapSlotSize, TilemapTransform, TilemapType},
+    };
+
+    use super::{systems, PhysicsCollider, PhysicsTile, PhysicsTileSpawn, PhysicsTilemap};
+
+    fn spawn_tilemap(app: &mut App, physics_tilemap: PhysicsTilemap) -> Entity {
+        let tilemap = app
+            .world_mut()
+            .spawn((
+                physics_tilemap,
+                TilemapType::Square,
+                TilemapTransform::default(),
+                TilePivot::default(),
+                TilemapSlotSize(Vec2::splat(16.)),
+            ))
+            .id();
+        app.update();
+        tilemap
+    }
+
+    fn friction(value: f32) -> PhysicsTile {
+        PhysicsTile {
+            friction: Some(value),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_physics_round_trip() {
+        let mut app = App::new();
+        app.add_event::<PhysicsTileSpawn>()
+            .add_systems(Update, systems::spawn_colliders);
+
+        let triangle = vec![
+            Vec2::new(32., 32.),
+            Vec2::new(48., 32.),
+            Vec2::new(40., 48.),
+        ];
+        let mut physics_tilemap = PhysicsTilemap::new_with_chunk_size(4);
+        physics_tilemap.set(IVec2::new(0, 0), friction(0.3));
+        physics_tilemap.set(
+            IVec2::new(5, 1),
+            PhysicsTile::default().with_restitution(0.8),
+        );
+        physics_tilemap.set(
+            IVec2::new(-3, 6),
+            PhysicsTile::default().with_restitution(0.2),
+        );
+        physics_tilemap.set_custom(
+            IVec2::new(2, 2),
+            PhysicsCollider::Convex(triangle.clone()),
+            friction(0.9),
+        );
+        let tilemap = spawn_tilemap(&mut app, physics_tilemap);
+
+        // Edit the tilemap at runtime.
+        app.world_mut()
+            .get_mut::<PhysicsTilemap>(tilemap)
+            .unwrap()
+            .set(IVec2::new(0, 0), friction(0.5));
+        app.world_mut().run_system_once(
+            move |mut commands: Commands, mut query: Query<&mut PhysicsTilemap>| {
+                query
+                    .get_mut(tilemap)
+                    .unwrap()
+                    .remove(&mut commands, IVec2::new(5, 1));
+            },
+        );
+        app.update();
+
+        let saved =
+            ron::to_string(&app.world().get::<PhysicsTilemap>(tilemap).unwrap().data).unwrap();
+        let loaded = ron::from_str::<PackedPhysicsTileChunkedStorage>(&saved).unwrap();
+        let loaded = spawn_tilemap(&mut app, PhysicsTilemap::from_packed(&loaded));
+
+        let world = app.world_mut();
+        assert_eq!(world.query::<&Collider>().iter(world).count(), 6);
+
+        let loaded = world.get::<PhysicsTilemap>(loaded).unwrap();
+        assert_eq!(ron::to_string(&loaded.data).unwrap(), saved);
+        assert!(loaded.get(IVec2::new(5, 1)).is_none());
+        assert!(matches!(
+            &loaded.get_packed(IVec2::new(2, 2)).unwrap().collider,
+            PhysicsCollider::Convex(verts) if *verts == triangle
+        ));
+
+        let collider = |index: IVec2| loaded.get(index).unwrap();
+        assert_eq!(
+            world
+                .get::<Friction>(collider(IVec2::new(0, 0)))
+                .unwrap()
+                .dynamic_coefficient,
+            0.5
+        );
+        assert_eq!(
+            world
+                .get::<Friction>(collider(IVec2::new(2, 2)))
+                .unwrap()
+                .dynamic_coefficient,
+            0.9
+        );
+        assert_eq!(
+            world
+                .get::<Restitution>(collider(IVec2::new(-3, 6)))
+                .unwrap()
+                .coefficient,
+            0.2
+        );
+    }
+}
@@ -1,7 +1,18 @@
+use std::f32::consts::{PI, TAU};
+
+use avian2d::prelude::{AngularVelocity, LinearVelocity, Position, RigidBody, Rotation};
 use bevy::{
-    ecs::{entity::Entity, event::EventWriter, system::Query},
-    math::UVec2,
+    ecs::{
+        change_detection::DetectChanges,
+        entity::{Entity, EntityHashSet},
+        event::EventWriter,
+        query::{Has, QueryFilter, With},
+        system::{Local, Query, Res},
+        world::Ref,
+    },
+    math::{IVec2, UVec2, Vec2},
     prelude::Commands,
+    time::Time,
 };
 
 use crate::{
@@ -9,10 +20,13 @@ use crate::{
     tilemap::{
         chunking::storage::ChunkedStorage,
         coordinates,
-        map::{TilePivot, TilemapSlotSize, TilemapTransform, TilemapType},
+        map::{
+            EntiTilesDefaults, SyncWithGlobalTransform, TilePivot, TilemapSlotSize,
+            TilemapTransform, TilemapType,
+        },
         physics::{
-            DataPhysicsTilemap, PackedPhysicsTile, PhysicsCollider, PhysicsTileSpawn,
-            PhysicsTilemap,
+            DataPhysicsTilemap, PackedPhysicsTile, PhysicsCollider, PhysicsTile, PhysicsTileSpawn,
+            PhysicsTilemap, PhysicsTilemapFollow, RetainedPhysicsData,
         },
     },
 };
@@ -26,17 +40,75 @@ pub fn spawn_colliders(
         &TilemapTransform,
         &TilePivot,
         &TilemapSlotSize,
+        Has<PhysicsTilemapFollow>,
+        Has<SyncWithGlobalTransform>,
     )>,
     mut spawn_event: EventWriter<PhysicsTileSpawn>,
+    #[cfg(feature = "algorithm")] mut path_tilemaps: Option<
+        bevy::ecs::system::ResMut<crate::algorithm::pathfinding::PathTilemaps>,
+    >,
 ) {
-    for (entity, mut physics_tilemap, ty, transform, tile_pivot, slot_size) in &mut tilemaps_query {
-        let PhysicsTilemap {
-            storage,
-            spawn_queue,
-            data,
-        } = &mut *physics_tilemap;
-
-        for (aabb, physics_tile, maybe_int_repr) in spawn_queue.drain(..) {
+    for (entity, mut physics_tilemap, ty, transform, tile_pivot, slot_size, follow, synced) in
+        &mut tilemaps_query
+    {
+        // Avoid triggering change detection when there's nothing to spawn.
+        if physics_tilemap.spawn_queue.is_empty() && physics_tilemap.packed_spawn_queue.is_empty() {
+            continue;
+        }
+
+        // Following colliders are positioned and rotated by `physics_tilemap_follower`,
+        // but only scaled here.
+        let transform = if follow || synced {
+            &TilemapTransform {
+                scale: transform.scale,
+                ..TilemapTransform::IDENTITY
+            }
+        } else {
+            transform
+        };
+
+        let physics_tilemap = &mut *physics_tilemap;
+        let spawn_queue = std::mem::take(&mut physics_tilemap.spawn_queue);
+        let packed_spawn_queue = std::mem::take(&mut physics_tilemap.packed_spawn_queue);
+
+        // Physics tiles are not walkable, so the paths passing through them are blocked.
+        #[cfg(feature = "algorithm")]
+        if let Some(path_tilemaps) = path_tilemaps.as_mut() {
+            path_tilemaps.with_mut(entity, |path_tilemap| {
+                spawn_queue
+                    .iter()
+                    .flat_map(|(aabb, _, _)| {
+                        (aabb.origin.y..=aabb.dest.y).flat_map(move |y| {
+                            (aabb.origin.x..=aabb.dest.x).map(move |x| IVec2::new(x, y))
+                        })
+                    })
+                    .chain(packed_spawn_queue.iter().map(|(index, _)| *index))
+                    .for_each(|index| {
+                        path_tilemap.remove(index);
+                    });
+            });
+        }
+
+        let mut spawn = |index: IVec2, packed_tile: PackedPhysicsTile, int_repr: Option<i32>| {
+            let tile_entity = packed_tile.spawn_on_tilemap(&mut commands, entity, index);
+
+            spawn_event.send(PhysicsTileSpawn {
+                tilemap: entity,
+                tile: tile_entity,
+                int_repr,
+            });
+
+            // Setting a tile twice replaces the previous collider.
+            if let Some(mut old) = physics_tilemap
+                .set_collider(index, tile_entity)
+                .and_then(|old| commands.get_entity(old))
+            {
+                old.despawn();
+            }
+            physics_tilemap.data.set_elem(index, packed_tile);
+        };
+
+        for (aabb, physics_tile, maybe_int_repr) in spawn_queue {
             let vertices = coordinates::get_tile_collider_world(
                 aabb.origin,
                 *ty,
@@ -56,97 +128,294 @@ pub fn spawn_colliders(
                 },
                 physics_tile,
             };
-            let tile_entity = packed_tile.spawn(&mut commands);
-
-            spawn_event.send(PhysicsTileSpawn {
-                tilemap: entity,
-                tile: tile_entity,
-                int_repr: maybe_int_repr,
-            });
+            spawn(aabb.origin, packed_tile, maybe_int_repr);
+        }
 
-            storage.set_elem(aabb.origin, tile_entity);
-            data.set_elem(aabb.origin, packed_tile);
+        for (index, packed_tile) in packed_spawn_queue {
+            spawn(index, packed_tile, None);
         }
     }
 }
 
-pub fn data_physics_tilemap_analyzer(
+/// Move the colliders of the tilemaps matching `F` with the tilemaps.
+pub fn physics_tilemap_follower<F: QueryFilter + 'static>(
     mut commands: Commands,
-    mut tilemaps_query: Query<(Entity, &mut DataPhysicsTilemap, Option<&mut PhysicsTilemap>)>,
+    tilemaps_query: Query<(Entity, Ref<TilemapTransform>, Ref<PhysicsTilemap>), F>,
+    rigid_bodies_query: Query<(), With<RigidBody>>,
+    mut kinematic_query: Query<(
+        &RigidBody,
+        &Position,
+        &Rotation,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+    )>,
+    mut moving: Local<EntityHashSet>,
+    time: Res<Time>,
 ) {
-    for (entity, mut data_tilemap, mut physics_tilemap) in &mut tilemaps_query {
-        let mut aabbs = Vec::new();
-        let size = data_tilemap.size;
-        let air = data_tilemap.air;
-
-        for y in 0..size.y {
-            for x in 0..size.x {
-                let cur = UVec2 { x, y };
+    let delta = time.delta_seconds();
 
-                let cur_i = {
-                    let i = data_tilemap.get_or_air(cur);
-                    if i == air {
-                        continue;
-                    }
-                    i
-                };
-
-                let mut d = UVec2 {
-                    x: if x == size.x - 1 { 0 } else { 1 },
-                    y: if y == size.y - 1 { 0 } else { 1 },
-                };
-                let mut dst = cur;
-                while d.x != 0 || d.y != 0 {
-                    for t_x in cur.x..=dst.x {
-                        if data_tilemap.get_or_air(UVec2::new(t_x, dst.y + d.y)) != cur_i {
-                            d.y = 0;
-                            break;
+    tilemaps_query
+        .iter()
+        .for_each(|(entity, transform, physics_tilemap)| {
+            if !transform.is_changed() && !physics_tilemap.is_changed() {
+                // Stop the bodies once the tilemap stops moving.
+                if moving.remove(&entity) {
+                    physics_tilemap.storage.iter_some().for_each(|collider| {
+                        if let Ok((_, _, _, mut linear, mut angular)) =
+                            kinematic_query.get_mut(*collider)
+                        {
+                            linear.0 = Vec2::ZERO;
+                            angular.0 = 0.;
                         }
-                    }
+                    });
+                }
+                return;
+            }
 
-                    for t_y in cur.y..=dst.y {
-                        if data_tilemap.get_or_air(UVec2::new(dst.x + d.x, t_y)) != cur_i {
-                            d.x = 0;
-                            break;
-                        }
-                    }
+            let position = Position(transform.translation);
+            let rotation = Rotation::radians(transform.get_angle());
 
-                    if d == UVec2::ONE
-                        && data_tilemap.get_or_air(UVec2::new(dst.x + 1, dst.y + 1)) != cur_i
-                    {
-                        d.y = 0;
+            physics_tilemap.storage.iter_some().for_each(|collider| {
+                // Kinematic bodies are moved by their velocities, so the bodies standing
+                // on them are carried along instead of being left behind.
+                if let Ok((RigidBody::Kinematic, cur_pos, cur_rot, mut linear, mut angular)) =
+                    kinematic_query.get_mut(*collider)
+                {
+                    if delta > 0. {
+                        linear.0 = (position.0 - cur_pos.0) / delta;
+                        let mut d_rot =
+                            (rotation.as_radians() - cur_rot.as_radians()).rem_euclid(TAU);
+                        if d_rot > PI {
+                            d_rot -= TAU;
+                        }
+                        angular.0 = d_rot / delta;
+                        moving.insert(entity);
+                        return;
                     }
+                }
 
-                    dst += d;
+                let mut collider_entity = commands.entity(*collider);
+                collider_entity.insert((position, rotation));
+                // Static bodies are not supposed to move.
+                if rigid_bodies_query.contains(*collider) {
+                    collider_entity.insert(RigidBody::Kinematic);
                 }
+            });
+        });
+}
 
-                for y in cur.y..=dst.y {
-                    for x in cur.x..=dst.x {
-                        data_tilemap.set(UVec2 { x, y }, air);
+pub fn data_physics_tilemap_analyzer(
+    mut commands: Commands,
+    mut tilemaps_query: Query<(Entity, &mut DataPhysicsTilemap, Option<&mut PhysicsTilemap>)>,
+    defaults: Res<EntiTilesDefaults>,
+) {
+    for (entity, mut data_tilemap, mut physics_tilemap) in &mut tilemaps_query {
+        let data_tilemap = &mut *data_tilemap;
+        let air = data_tilemap.air;
+
+        let (aabbs, retained) = if let Some(delay) = data_tilemap.rebuild_delay {
+            let chunk_size = physics_tilemap
+                .as_ref()
+                .map(|t| t.storage.chunk_size)
+                .unwrap_or(defaults.chunk_size);
+            let mut data = ChunkedStorage::new(chunk_size);
+            for y in 0..data_tilemap.size.y {
+                for x in 0..data_tilemap.size.x {
+                    let value = data_tilemap.get_or_air(UVec2 { x, y });
+                    if value != air {
+                        data.set_elem(IVec2::new(x as i32, y as i32) + data_tilemap.origin, value);
                     }
                 }
-
-                aabbs.push((
-                    GridRect::from_min_max(
-                        cur.as_ivec2() + data_tilemap.origin,
-                        dst.as_ivec2() + data_tilemap.origin,
-                    ),
-                    data_tilemap.get_tile(cur_i).unwrap_or_default(),
-                    Some(cur_i),
-                ));
             }
-        }
+
+            let retained = RetainedPhysicsData {
+                data,
+                air,
+                tiles: std::mem::take(&mut data_tilemap.tiles),
+                delay,
+                dirty_chunks: Default::default(),
+            };
+            let aabbs = retained
+                .data
+                .chunks
+                .keys()
+                .flat_map(|chunk_index| analyze_chunk(&retained, *chunk_index))
+                .collect();
+            (aabbs, Some(retained))
+        } else {
+            let aabbs = concat_tiles(&mut data_tilemap.data, data_tilemap.size, air)
+                .into_iter()
+                .map(|(min, max, value)| {
+                    (
+                        GridRect::from_min_max(
+                            min.as_ivec2() + data_tilemap.origin,
+                            max.as_ivec2() + data_tilemap.origin,
+                        ),
+                        data_tilemap.get_tile(value).unwrap_or_default(),
+                        Some(value),
+                    )
+                })
+                .collect();
+            (aabbs, None)
+        };
 
         if let Some(physics_tilemap) = &mut physics_tilemap {
             physics_tilemap.spawn_queue.extend(aabbs);
+            if retained.is_some() {
+                physics_tilemap.retained = retained;
+            }
         } else {
-            commands.entity(entity).insert(PhysicsTilemap {
-                storage: Default::default(),
-                spawn_queue: aabbs,
-                data: ChunkedStorage::default(),
-            });
+            let mut physics_tilemap = PhysicsTilemap::new();
+            physics_tilemap.spawn_queue = aabbs;
+            physics_tilemap.retained = retained;
+            commands.entity(entity).insert(physics_tilemap);
         }
 
         commands.entity(entity).remove::<DataPhysicsTilemap>();
     }
 }
+
+/// Rebuild the colliders of the chunks edited using `PhysicsTilemap::set_data`
+/// once they haven't been edited for a while.
+pub fn physics_tilemap_rebuilder(
+    mut commands: Commands,
+    mut tilemaps_query: Query<&mut PhysicsTilemap>,
+    time: Res<Time>,
+) {
+    for mut physics_tilemap in &mut tilemaps_query {
+        let ready = {
+            // Counting down shouldn't trigger change detection.
+            let Some(retained) = &mut physics_tilemap.bypass_change_detection().retained else {
+                continue;
+            };
+            if retained.dirty_chunks.is_empty() {
+                continue;
+            }
+
+            retained
+                .dirty_chunks
+                .values_mut()
+                .for_each(|t| *t -= time.delta_seconds());
+            let ready = retained
+                .dirty_chunks
+                .iter()
+                .filter_map(|(chunk_index, t)| (*t <= 0.).then_some(*chunk_index))
+                .collect::<Vec<_>>();
+            ready.iter().for_each(|chunk_index| {
+                retained.dirty_chunks.remove(chunk_index);
+            });
+            ready
+        };
+
+        if ready.is_empty() {
+            continue;
+        }
+
+        let physics_tilemap = &mut *physics_tilemap;
+        for chunk_index in ready {
+            if let Some(chunk) = physics_tilemap.storage.remove_chunk(chunk_index) {
+                chunk.into_iter().flatten().for_each(|collider| {
+                    physics_tilemap.colliders.remove(&collider);
+                    commands.entity(collider).despawn();
+                });
+            }
+            physics_tilemap.data.remove_chunk(chunk_index);
+
+            let aabbs = analyze_chunk(physics_tilemap.retained.as_ref().unwrap(), chunk_index);
+            physics_tilemap.spawn_queue.extend(aabbs);
+        }
+    }
+}
+
+fn analyze_chunk(
+    retained: &RetainedPhysicsData,
+    chunk_index: IVec2,
+) -> Vec<(GridRect, PhysicsTile, Option<i32>)> {
+    let Some(chunk) = retained.data.get_chunk(chunk_index) else {
+        return Vec::new();
+    };
+
+    let chunk_size = retained.data.chunk_size;
+    let mut data = chunk
+        .iter()
+        .map(|value| value.unwrap_or(retained.air))
+        .collect::<Vec<_>>();
+    let origin = chunk_index * chunk_size as i32;
+
+    concat_tiles(&mut data, UVec2::splat(chunk_size), retained.air)
+        .into_iter()
+        .map(|(min, max, value)| {
+            (
+                GridRect::from_min_max(min.as_ivec2() + origin, max.as_ivec2() + origin),
+                retained.tiles.get(&value).cloned().unwrap_or_default(),
+                Some(value),
+            )
+        })
+        .collect()
+}
+
+/// Greedily concatenate the adjacent tiles with the same value into rectangles.
+///
+/// Returns the min and max corners, and the value of each rectangle.
+/// Concatenated tiles are set to `air`.
+fn concat_tiles(data: &mut [i32], size: UVec2, air: i32) -> Vec<(UVec2, UVec2, i32)> {
+    let get_or_air = |data: &[i32], index: UVec2| {
+        if index.x >= size.x || index.y >= size.y {
+            air
+        } else {
+            data[(index.x + index.y * size.x) as usize]
+        }
+    };
+    let mut rects = Vec::new();
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let cur = UVec2 { x, y };
+
+            let cur_i = {
+                let i = get_or_air(data, cur);
+                if i == air {
+                    continue;
+                }
+                i
+            };
+
+            let mut d = UVec2 {
+                x: if x == size.x - 1 { 0 } else { 1 },
+                y: if y == size.y - 1 { 0 } else { 1 },
+            };
+            let mut dst = cur;
+            while d.x != 0 || d.y != 0 {
+                for t_x in cur.x..=dst.x {
+                    if get_or_air(data, UVec2::new(t_x, dst.y + d.y)) != cur_i {
+                        d.y = 0;
+                        break;
+                    }
+                }
+
+                for t_y in cur.y..=dst.y {
+                    if get_or_air(data, UVec2::new(dst.x + d.x, t_y)) != cur_i {
+                        d.x = 0;
+                        break;
+                    }
+                }
+
+                if d == UVec2::ONE && get_or_air(data, UVec2::new(dst.x + 1, dst.y + 1)) != cur_i {
+                    d.y = 0;
+                }
+
+                dst += d;
+            }
+
+            for y in cur.y..=dst.y {
+                for x in cur.x..=dst.x {
+                    data[(x + y * size.x) as usize] = air;
+                }
+            }
+
+            rects.push((cur, dst, cur_i));
+        }
+    }
+
+    rects
+}
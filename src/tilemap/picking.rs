@@ -0,0 +1,200 @@
+//! Find the tiles at a world position across all the tilemaps.
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        system::{Query, SystemParam},
+    },
+    math::{IVec2, Vec2},
+    render::view::InheritedVisibility,
+};
+
+use crate::tilemap::{
+    coordinates,
+    map::{
+        TilePivot, TilemapAxisFlip, TilemapSlotSize, TilemapStorage, TilemapTransform, TilemapType,
+    },
+};
+
+/// A tile found by [`TilemapRaycast`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilemapHit {
+    pub tilemap: Entity,
+    pub tile: Entity,
+    pub index: IVec2,
+    /// The `z_index` of the tilemap.
+    pub z_index: f32,
+}
+
+/// Picks the tiles at a world position across the visible tilemaps.
+///
+/// Tilemaps are ordered by [`TilemapTransform::z_index`], which is also where
+/// the LDtk and Tiled layers put their z, so the result matches what is on the screen.
+/// [`TilemapAxisFlip`] is taken into account, and so is
+/// [`TilemapParallax`](crate::tilemap::map::TilemapParallax) as it moves the `TilemapTransform`.
+///
+/// **Notice**: Tilemaps with equal `z_index` are not ordered.
+#[derive(SystemParam)]
+pub struct TilemapRaycast<'w, 's> {
+    tilemaps_query: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static TilemapStorage,
+            &'static TilemapType,
+            &'static TilemapTransform,
+            &'static TilePivot,
+            &'static TilemapSlotSize,
+            Option<&'static TilemapAxisFlip>,
+            &'static InheritedVisibility,
+        ),
+    >,
+}
+
+impl<'w, 's> TilemapRaycast<'w, 's> {
+    /// Get the top-most tile at `world`.
+    pub fn pick(&self, world: Vec2) -> Option<TilemapHit> {
+        self.hits(world)
+            .max_by(|a, b| a.z_index.total_cmp(&b.z_index))
+    }
+
+    /// Get all the tiles at `world`, from top to bottom.
+    pub fn pick_all(&self, world: Vec2) -> Vec<TilemapHit> {
+        let mut hits = self.hits(world).collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.z_index.total_cmp(&a.z_index));
+        hits
+    }
+
+    fn hits(&self, world: Vec2) -> impl Iterator<Item = TilemapHit> + '_ {
+        self.tilemaps_query.iter().filter_map(
+            move |(tilemap, storage, ty, transform, pivot, slot_size, axis_flip, visibility)| {
+                if !visibility.get() {
+                    return None;
+                }
+
+                let index = coordinates::flip_index(
+                    coordinates::world_to_index(world, *ty, transform, pivot.0, slot_size.0),
+                    axis_flip.copied().unwrap_or_default(),
+                );
+                storage.get(index).map(|tile| TilemapHit {
+                    tilemap,
+                    tile,
+                    index,
+                    z_index: transform.z_index,
+                })
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            system::{Commands, Query, RunSystemOnce},
+            world::World,
+        },
+        math::{IVec2, Vec2},
+        render::view::InheritedVisibility,
+    };
+
+    use crate::tilemap::{
+        map::{
+            TilePivot, TilemapAxisFlip, TilemapSlotSize, TilemapStorage, TilemapTransform,
+            TilemapType,
+        },
+        tile::TileBuilder,
+    };
+
+    use super::TilemapRaycast;
+
+    #[test]
+    fn test_pick_top_most() {
+        let mut world = World::new();
+        let tilemaps = [(0., true), (2., true), (1., true), (3., false)].map(|(z, visible)| {
+            let tilemap = world.spawn_empty().id();
+            world.entity_mut(tilemap).insert((
+                TilemapStorage::new(4, tilemap),
+                TilemapType::Square,
+                TilemapTransform {
+                    z_index: z,
+                    ..Default::default()
+                },
+                TilePivot::default(),
+                TilemapSlotSize(Vec2::splat(16.)),
+                if visible {
+                    InheritedVisibility::VISIBLE
+                } else {
+                    InheritedVisibility::HIDDEN
+                },
+            ));
+            tilemap
+        });
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                for (i, tilemap) in tilemaps.into_iter().enumerate() {
+                    let mut storage = storages_query.get_mut(tilemap).unwrap();
+                    storage.set(&mut commands, IVec2::ZERO, TileBuilder::new());
+                    // Only the bottom tilemap has a tile at (1, 0).
+                    if i == 0 {
+                        storage.set(&mut commands, IVec2::X, TileBuilder::new());
+                    }
+                }
+            },
+        );
+
+        let (top, all, single) = world.run_system_once(|raycast: TilemapRaycast| {
+            (
+                raycast.pick(Vec2::splat(8.)),
+                raycast.pick_all(Vec2::splat(8.)),
+                raycast.pick(Vec2::new(24., 8.)),
+            )
+        });
+
+        assert_eq!(top.map(|hit| hit.tilemap), Some(tilemaps[1]));
+        assert_eq!(
+            all.iter().map(|hit| hit.tilemap).collect::<Vec<_>>(),
+            vec![tilemaps[1], tilemaps[2], tilemaps[0]]
+        );
+        assert_eq!(
+            single.map(|hit| (hit.tilemap, hit.index)),
+            Some((tilemaps[0], IVec2::X))
+        );
+        assert!(world
+            .run_system_once(|raycast: TilemapRaycast| raycast.pick(Vec2::new(-8., 8.)))
+            .is_none());
+    }
+
+    #[test]
+    fn test_pick_flipped() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        world.entity_mut(tilemap).insert((
+            TilemapStorage::new(4, tilemap),
+            TilemapType::Square,
+            TilemapTransform::default(),
+            TilePivot::default(),
+            TilemapSlotSize(Vec2::splat(16.)),
+            TilemapAxisFlip::X,
+            InheritedVisibility::VISIBLE,
+        ));
+
+        world.run_system_once(
+            move |mut commands: Commands, mut storages_query: Query<&mut TilemapStorage>| {
+                let mut storage = storages_query.get_mut(tilemap).unwrap();
+                storage.set(&mut commands, IVec2::new(1, 0), TileBuilder::new());
+            },
+        );
+
+        let (flipped, unflipped) = world.run_system_once(|raycast: TilemapRaycast| {
+            (
+                raycast.pick(Vec2::new(-24., 8.)),
+                raycast.pick(Vec2::new(24., 8.)),
+            )
+        });
+        assert_eq!(flipped.map(|hit| hit.index), Some(IVec2::new(1, 0)));
+        assert!(unflipped.is_none());
+    }
+}
@@ -0,0 +1,328 @@
+//! Record tilemap mutations and replay them later.
+//!
+//! Mutations are stored as [`TilemapCommandEvent`]s with timestamps, so a recording
+//! can be serialized, attached to bug reports, and re-applied by [`TilemapPlayer`].
+
+use bevy::{
+    ecs::{
+        entity::{Entity, EntityHashMap},
+        event::{EventReader, EventWriter},
+        observer::Trigger,
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::IVec2,
+    reflect::Reflect,
+    time::Time,
+};
+
+use crate::tilemap::{
+    command::{TilemapCommand, TilemapCommandEvent},
+    despawn::EverythingUnloaded,
+    tile::Tile,
+};
+
+/// A mutation and the time it happened, in seconds since the recording started.
+#[derive(Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapRecord {
+    pub time: f32,
+    pub event: TilemapCommandEvent,
+}
+
+/// A list of mutations ordered by time.
+#[derive(Debug, Clone, Default, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapRecording {
+    pub records: Vec<TilemapRecord>,
+}
+
+impl TilemapRecording {
+    /// The time of the last record.
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.records.last().map(|r| r.time).unwrap_or_default()
+    }
+
+    /// Serialize into a compact ron string.
+    #[cfg(feature = "serializing")]
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::to_string(self)
+    }
+
+    #[cfg(feature = "serializing")]
+    pub fn from_ron(s: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(s)
+    }
+}
+
+/// Records the [`TilemapCommandEvent`]s sent while recording.
+#[derive(Resource, Debug, Default)]
+pub struct TilemapRecorder {
+    /// Also record the tiles that are changed or removed without commands,
+    /// like the ones edited through `TilemapStorage` directly.
+    ///
+    /// **Notice**: When enabled, tile changes are recorded as `SetTile` and `RemoveTile`
+    /// commands, and the tile commands sent are not recorded again.
+    pub capture_tile_changes: bool,
+    pub(crate) recording: bool,
+    pub(crate) just_started: bool,
+    pub(crate) elapsed: f32,
+    pub(crate) recorded: TilemapRecording,
+    /// The tilemaps and indices of the tracked tiles, to record their removals.
+    pub(crate) tile_indices: EntityHashMap<(Entity, IVec2)>,
+}
+
+impl TilemapRecorder {
+    /// Start a new recording. The previous one is discarded.
+    pub fn start(&mut self) {
+        self.recording = true;
+        self.just_started = true;
+        self.elapsed = 0.;
+        self.recorded.records.clear();
+    }
+
+    /// Stop recording and take the recorded mutations.
+    pub fn stop(&mut self) -> TilemapRecording {
+        self.recording = false;
+        std::mem::take(&mut self.recorded)
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    #[inline]
+    pub fn recorded(&self) -> &TilemapRecording {
+        &self.recorded
+    }
+
+    fn record(&mut self, tilemap: Entity, command: TilemapCommand) {
+        self.recorded.records.push(TilemapRecord {
+            time: self.elapsed,
+            event: TilemapCommandEvent { tilemap, command },
+        });
+    }
+}
+
+/// Replays a [`TilemapRecording`] by sending the recorded commands again.
+#[derive(Resource, Debug)]
+pub struct TilemapPlayer {
+    /// The playback speed.
+    pub speed: f32,
+    /// Maps the tilemaps in the recording to the current ones,
+    /// as entities are usually different between sessions.
+    /// Tilemaps that are not in this map are used directly.
+    pub remap: EntityHashMap<Entity>,
+    pub(crate) recording: Option<TilemapRecording>,
+    pub(crate) elapsed: f32,
+    pub(crate) cursor: usize,
+}
+
+impl Default for TilemapPlayer {
+    fn default() -> Self {
+        Self {
+            speed: 1.,
+            remap: Default::default(),
+            recording: None,
+            elapsed: 0.,
+            cursor: 0,
+        }
+    }
+}
+
+impl TilemapPlayer {
+    /// Start playing the recording from the beginning.
+    pub fn play(&mut self, recording: TilemapRecording) {
+        self.recording = Some(recording);
+        self.elapsed = 0.;
+        self.cursor = 0;
+    }
+
+    /// Stop playing and take the recording back.
+    pub fn stop(&mut self) -> Option<TilemapRecording> {
+        self.recording.take()
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// The playback time in seconds.
+    #[inline]
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+}
+
+pub fn tilemap_recorder(
+    time: Res<Time>,
+    mut recorder: ResMut<TilemapRecorder>,
+    mut command_events: EventReader<TilemapCommandEvent>,
+    changed_tiles_query: Query<(Entity, &Tile), Changed<Tile>>,
+    tiles_query: Query<(Entity, &Tile)>,
+    mut removed_tiles: RemovedComponents<Tile>,
+) {
+    if !recorder.recording {
+        command_events.clear();
+        removed_tiles.clear();
+        recorder.tile_indices.clear();
+        return;
+    }
+
+    if recorder.just_started {
+        recorder.just_started = false;
+        if recorder.capture_tile_changes {
+            recorder.tile_indices.extend(
+                tiles_query
+                    .iter()
+                    .map(|(entity, tile)| (entity, (tile.tilemap_id, tile.index))),
+            );
+        }
+    } else {
+        recorder.elapsed += time.delta_seconds();
+    }
+
+    for ev in command_events.read() {
+        let is_tile_command = matches!(
+            ev.command,
+            TilemapCommand::SetTile { .. }
+                | TilemapCommand::RemoveTile { .. }
+                | TilemapCommand::FillRect { .. }
+        );
+        #[cfg(feature = "serializing")]
+        let is_tile_command =
+            is_tile_command || matches!(ev.command, TilemapCommand::SpawnPattern { .. });
+
+        if !(recorder.capture_tile_changes && is_tile_command) {
+            recorder.record(ev.tilemap, ev.command.clone());
+        }
+    }
+
+    if !recorder.capture_tile_changes {
+        return;
+    }
+
+    for entity in removed_tiles.read() {
+        if let Some((tilemap, index)) = recorder.tile_indices.remove(&entity) {
+            recorder.record(tilemap, TilemapCommand::RemoveTile { index });
+        }
+    }
+
+    for (entity, tile) in changed_tiles_query.iter() {
+        recorder
+            .tile_indices
+            .insert(entity, (tile.tilemap_id, tile.index));
+        recorder.record(
+            tile.tilemap_id,
+            TilemapCommand::SetTile {
+                index: tile.index,
+                tile: tile.clone().into(),
+            },
+        );
+    }
+}
+
+/// Forget the tiles, so unloading them is not recorded as removals.
+pub fn tilemap_recorder_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut recorder: ResMut<TilemapRecorder>,
+) {
+    recorder.tile_indices.clear();
+}
+
+pub fn tilemap_player(
+    time: Res<Time>,
+    mut player: ResMut<TilemapPlayer>,
+    mut command_events: EventWriter<TilemapCommandEvent>,
+) {
+    let player = &mut *player;
+    let Some(recording) = &player.recording else {
+        return;
+    };
+
+    player.elapsed += time.delta_seconds() * player.speed;
+    while let Some(record) = recording
+        .records
+        .get(player.cursor)
+        .filter(|r| r.time <= player.elapsed)
+    {
+        let mut ev = record.event.clone();
+        if let Some(tilemap) = player.remap.get(&ev.tilemap) {
+            ev.tilemap = *tilemap;
+        }
+        command_events.send(ev);
+        player.cursor += 1;
+    }
+
+    if player.cursor >= recording.records.len() {
+        player.recording = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{event::Events, schedule::Schedule, system::RunSystemOnce, world::World},
+        math::IVec2,
+        time::Time,
+    };
+
+    use crate::tilemap::{
+        command::{TilemapCommand, TilemapCommandEvent},
+        tile::TileBuilder,
+    };
+
+    use super::{tilemap_player, tilemap_recorder, TilemapPlayer, TilemapRecorder};
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Events<TilemapCommandEvent>>();
+        world.init_resource::<TilemapRecorder>();
+        world.init_resource::<TilemapPlayer>();
+
+        let recorded_tilemap = world.spawn_empty().id();
+        let tilemap = world.spawn_empty().id();
+        world.resource_mut::<TilemapRecorder>().start();
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems(tilemap_recorder);
+        for x in 0..3 {
+            world.send_event(TilemapCommandEvent {
+                tilemap: recorded_tilemap,
+                command: TilemapCommand::SetTile {
+                    index: IVec2::new(x, 0),
+                    tile: TileBuilder::new(),
+                },
+            });
+            schedule.run(&mut world);
+            world.resource_mut::<Events<TilemapCommandEvent>>().update();
+        }
+
+        let recording = world.resource_mut::<TilemapRecorder>().stop();
+        assert_eq!(recording.records.len(), 3);
+        world.resource_mut::<Events<TilemapCommandEvent>>().clear();
+
+        let mut player = world.resource_mut::<TilemapPlayer>();
+        player.remap.insert(recorded_tilemap, tilemap);
+        player.play(recording);
+        world.run_system_once(tilemap_player);
+
+        assert!(!world.resource::<TilemapPlayer>().is_playing());
+        let replayed = world
+            .resource_mut::<Events<TilemapCommandEvent>>()
+            .drain()
+            .collect::<Vec<_>>();
+        assert_eq!(replayed.len(), 3);
+        assert!(replayed.iter().all(|ev| ev.tilemap == tilemap));
+        assert!(matches!(
+            replayed[2].command,
+            TilemapCommand::SetTile { index, .. } if index == IVec2::new(2, 0)
+        ));
+    }
+}
@@ -0,0 +1,377 @@
+//! Stream tile edits as serializable ops, so networked games can mirror them
+//! without syncing whole chunks.
+//!
+//! This is transport agnostic. Send the [`TileChangeOps`] taken from
+//! [`TileChangeStream::drain_outgoing`] over bevy_replicon, renet or anything else,
+//! and hand them to [`TileChangeStream::receive`] on the other side.
+
+use std::collections::BTreeMap;
+
+use bevy::{
+    ecs::{
+        component::Component,
+        event::EventReader,
+        system::{Commands, Query, Res},
+    },
+    log::warn,
+    math::IVec2,
+    reflect::Reflect,
+};
+use thiserror::Error;
+
+use crate::{
+    math::GridRect,
+    tilemap::{
+        command::{
+            TilemapCommand, TilemapCommandApplied, TilemapCommandConfig, TilemapCommandError,
+        },
+        map::TilemapStorage,
+        tile::TileBuilder,
+    },
+};
+
+/// A tile edit that can be applied using [`TilemapStorage::apply_ops`].
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileChangeOp {
+    Set { index: IVec2, tile: TileBuilder },
+    Remove { index: IVec2 },
+    Fill { area: GridRect, tile: TileBuilder },
+}
+
+impl TileChangeOp {
+    /// Convert a command into an op. Returns `None` for the commands
+    /// that are not about color tiles.
+    pub fn from_command(command: &TilemapCommand) -> Option<Self> {
+        match command {
+            TilemapCommand::SetTile { index, tile } => Some(Self::Set {
+                index: *index,
+                tile: tile.clone(),
+            }),
+            TilemapCommand::RemoveTile { index } => Some(Self::Remove { index: *index }),
+            TilemapCommand::FillRect { area, tile } => Some(Self::Fill {
+                area: *area,
+                tile: tile.clone(),
+            }),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Check if the op is allowed by `config`, like the equivalent command.
+    pub fn validate(&self, config: &TilemapCommandConfig) -> Result<(), TilemapCommandError> {
+        match self {
+            Self::Set { index, .. } | Self::Remove { index } => config.validate_index(*index),
+            Self::Fill { area, .. } => config.validate_area(*area),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TileChangeError {
+    #[error("Tick {tick} is too far ahead of the next incoming tick {next_incoming}")]
+    TooFarAhead { tick: u64, next_incoming: u64 },
+}
+
+/// The ops happened in a single tick, in the order they should be applied.
+///
+/// Ticks of a stream start from 0 and have no gaps.
+#[derive(Debug, Clone, Default, PartialEq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileChangeOps {
+    pub tick: u64,
+    pub ops: Vec<TileChangeOp>,
+}
+
+/// Add this to a tilemap to stream its tile edits.
+///
+/// Tile commands sent as [`TilemapCommandEvent`](crate::tilemap::command::TilemapCommandEvent)s
+/// are converted into ops once they are applied.
+/// Use [`TileChangeStream::push`] for the tiles edited through [`TilemapStorage`] directly.
+///
+/// Received ops are applied in the order of their ticks, even if they arrive out of order.
+///
+/// **Notice**: Received ops are applied directly instead of through commands,
+/// so they won't be streamed again. They are still validated against [`TilemapCommandConfig`].
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TileChangeStream {
+    pub(crate) tick: u64,
+    pub(crate) pending: Vec<TileChangeOp>,
+    pub(crate) outgoing: Vec<TileChangeOps>,
+    pub(crate) next_incoming: u64,
+    pub(crate) max_ticks_ahead: u64,
+    #[reflect(ignore)]
+    pub(crate) incoming: BTreeMap<u64, Vec<TileChangeOp>>,
+}
+
+impl Default for TileChangeStream {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            pending: Vec::new(),
+            outgoing: Vec::new(),
+            next_incoming: 0,
+            max_ticks_ahead: 256,
+            incoming: BTreeMap::new(),
+        }
+    }
+}
+
+impl TileChangeStream {
+    /// Received ops are rejected if their ticks are more than `max_ticks_ahead`
+    /// ahead of [`TileChangeStream::next_incoming`]. Default is 256.
+    #[inline]
+    pub fn with_max_ticks_ahead(mut self, max_ticks_ahead: u64) -> Self {
+        self.max_ticks_ahead = max_ticks_ahead;
+        self
+    }
+
+    /// The tick of the next outgoing ops.
+    #[inline]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The tick of the next incoming ops to be applied.
+    #[inline]
+    pub fn next_incoming(&self) -> u64 {
+        self.next_incoming
+    }
+
+    /// Stream an op that is applied without commands.
+    #[inline]
+    pub fn push(&mut self, op: TileChangeOp) {
+        self.pending.push(op);
+    }
+
+    /// Take the ops that should be sent.
+    #[inline]
+    pub fn drain_outgoing(&mut self) -> Vec<TileChangeOps> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Queue the received ops. Ops of the ticks that are already applied are ignored.
+    pub fn receive(&mut self, ops: TileChangeOps) -> Result<(), TileChangeError> {
+        if ops.tick > self.next_incoming.saturating_add(self.max_ticks_ahead) {
+            return Err(TileChangeError::TooFarAhead {
+                tick: ops.tick,
+                next_incoming: self.next_incoming,
+            });
+        }
+
+        if ops.tick >= self.next_incoming {
+            self.incoming.insert(ops.tick, ops.ops);
+        }
+        Ok(())
+    }
+
+    /// Start receiving from `tick`, like when joining a game in progress
+    /// after the whole tilemap is synced.
+    pub fn resync(&mut self, tick: u64) {
+        self.next_incoming = tick;
+        self.incoming.retain(|t, _| *t >= tick);
+    }
+}
+
+pub fn tile_change_streamer(
+    mut commands: Commands,
+    config: Res<TilemapCommandConfig>,
+    mut applied_events: EventReader<TilemapCommandApplied>,
+    mut tilemaps_query: Query<(&mut TilemapStorage, &mut TileChangeStream)>,
+) {
+    for TilemapCommandApplied(ev) in applied_events.read() {
+        let Ok((_, mut stream)) = tilemaps_query.get_mut(ev.tilemap) else {
+            continue;
+        };
+
+        if let Some(op) = TileChangeOp::from_command(&ev.command) {
+            stream.push(op);
+        }
+    }
+
+    tilemaps_query
+        .iter_mut()
+        .for_each(|(mut storage, mut stream)| {
+            if !stream.pending.is_empty() {
+                let ops = TileChangeOps {
+                    tick: stream.tick,
+                    ops: std::mem::take(&mut stream.pending),
+                };
+                stream.outgoing.push(ops);
+                stream.tick += 1;
+            }
+
+            let stream = &mut *stream;
+            while let Some(ops) = stream.incoming.remove(&stream.next_incoming) {
+                storage.apply_ops(
+                    &mut commands,
+                    ops.iter().filter(|op| match op.validate(&config) {
+                        Ok(_) => true,
+                        Err(error) => {
+                            warn!("Rejected received tile change op: {}", error);
+                            false
+                        }
+                    }),
+                );
+                stream.next_incoming += 1;
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        ecs::{
+            event::Events,
+            schedule::{IntoSystemConfigs, Schedule},
+            world::World,
+        },
+        math::{IVec2, UVec2},
+    };
+
+    use crate::{
+        math::GridRect,
+        tilemap::{
+            command::{
+                tilemap_command_applier, TilemapCommand, TilemapCommandApplied,
+                TilemapCommandConfig, TilemapCommandEvent, TilemapCommandFailed,
+            },
+            map::TilemapStorage,
+            tile::{TileBuilder, TileLayer},
+        },
+    };
+
+    use super::{
+        tile_change_streamer, TileChangeError, TileChangeOp, TileChangeOps, TileChangeStream,
+    };
+
+    #[test]
+    fn test_tile_change_stream() {
+        let mut world = World::new();
+        world.init_resource::<Events<TilemapCommandEvent>>();
+        world.init_resource::<Events<TilemapCommandFailed>>();
+        world.init_resource::<Events<TilemapCommandApplied>>();
+        world.insert_resource(TilemapCommandConfig {
+            max_area: 16,
+            bounds: Some(GridRect::new(IVec2::splat(-8), UVec2::splat(16))),
+            ..Default::default()
+        });
+        let mut schedule = Schedule::default();
+        schedule.add_systems((tilemap_command_applier, tile_change_streamer).chain());
+
+        let server = world.spawn(TileChangeStream::default()).id();
+        world
+            .entity_mut(server)
+            .insert(TilemapStorage::new(16, server));
+        let client = world.spawn(TileChangeStream::default()).id();
+        world
+            .entity_mut(client)
+            .insert(TilemapStorage::new(16, client));
+
+        let tile = TileBuilder::new().with_layer(0, TileLayer::no_flip(1));
+        world.send_event(TilemapCommandEvent {
+            tilemap: server,
+            command: TilemapCommand::FillRect {
+                area: GridRect::new(IVec2::ZERO, UVec2::splat(2)),
+                tile: tile.clone(),
+            },
+        });
+        schedule.run(&mut world);
+        world.send_event(TilemapCommandEvent {
+            tilemap: server,
+            command: TilemapCommand::RemoveTile { index: IVec2::ZERO },
+        });
+        // Rejected by the command applier, so it's not streamed.
+        world.send_event(TilemapCommandEvent {
+            tilemap: server,
+            command: TilemapCommand::RemoveTile {
+                index: IVec2::splat(10),
+            },
+        });
+        schedule.run(&mut world);
+
+        let outgoing = world
+            .get_mut::<TileChangeStream>(server)
+            .unwrap()
+            .drain_outgoing();
+        assert_eq!(
+            outgoing.iter().map(|ops| ops.tick).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            outgoing[1].ops,
+            vec![TileChangeOp::Remove { index: IVec2::ZERO }]
+        );
+
+        // Arrives out of order.
+        let mut stream = world.get_mut::<TileChangeStream>(client).unwrap();
+        stream.receive(outgoing[1].clone()).unwrap();
+        schedule.run(&mut world);
+        assert!(world
+            .get::<TilemapStorage>(client)
+            .unwrap()
+            .get(IVec2::ONE)
+            .is_none());
+
+        let mut stream = world.get_mut::<TileChangeStream>(client).unwrap();
+        stream.receive(outgoing[0].clone()).unwrap();
+        schedule.run(&mut world);
+
+        let storage = world.get::<TilemapStorage>(client).unwrap();
+        assert!(storage.get(IVec2::ZERO).is_none());
+        assert!(storage.get(IVec2::ONE).is_some());
+
+        // Already applied.
+        let mut stream = world.get_mut::<TileChangeStream>(client).unwrap();
+        assert_eq!(stream.next_incoming(), 2);
+        stream.receive(TileChangeOps::default()).unwrap();
+        assert!(stream.incoming.is_empty());
+        assert!(stream.drain_outgoing().is_empty());
+
+        // Too far in the future.
+        assert_eq!(
+            stream.receive(TileChangeOps {
+                tick: 1000,
+                ops: Vec::new(),
+            }),
+            Err(TileChangeError::TooFarAhead {
+                tick: 1000,
+                next_incoming: 2,
+            })
+        );
+
+        // Ops beyond the limits are not applied.
+        stream
+            .receive(TileChangeOps {
+                tick: 2,
+                ops: vec![
+                    TileChangeOp::Fill {
+                        area: GridRect::new(IVec2::ZERO, UVec2::splat(1000)),
+                        tile: tile.clone(),
+                    },
+                    TileChangeOp::Set {
+                        index: IVec2::splat(100),
+                        tile: tile.clone(),
+                    },
+                    TileChangeOp::Set {
+                        index: IVec2::splat(3),
+                        tile,
+                    },
+                ],
+            })
+            .unwrap();
+        schedule.run(&mut world);
+
+        let storage = world.get::<TilemapStorage>(client).unwrap();
+        assert!(storage.get(IVec2::splat(2)).is_none());
+        assert!(storage.get(IVec2::splat(100)).is_none());
+        assert!(storage.get(IVec2::splat(3)).is_some());
+        assert_eq!(
+            world
+                .get::<TileChangeStream>(client)
+                .unwrap()
+                .next_incoming(),
+            3
+        );
+    }
+}
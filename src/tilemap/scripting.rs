@@ -0,0 +1,174 @@
+//! Non-generic functions to edit tilemaps using reflected values.
+//!
+//! These only take `&mut World` and `&dyn Reflect`, so they can be exposed to
+//! scripting languages directly. Tiles can be passed as [`TileBuilder`]s, or
+//! any dynamic value that represents one, like a `DynamicStruct`.
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        system::{Commands, Query, SystemState},
+        world::World,
+    },
+    math::IVec2,
+    reflect::{FromReflect, Reflect},
+};
+use thiserror::Error;
+
+use crate::{
+    math::GridRect,
+    tilemap::{
+        map::TilemapStorage,
+        tile::{LayerUpdater, TileBuilder, TileLayer, TileLayerPosition, TileUpdater},
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum TileReflectError {
+    #[error("Entity {0:?} is not a tilemap")]
+    NotTilemap(Entity),
+    #[error("Failed to convert `{0}` into `TileBuilder`")]
+    InvalidTile(String),
+    #[error("Failed to convert `{0}` into `TileLayer`")]
+    InvalidLayer(String),
+}
+
+/// Convert a reflected value into a [`TileBuilder`].
+pub fn tile_from_reflect(tile: &dyn Reflect) -> Result<TileBuilder, TileReflectError> {
+    TileBuilder::from_reflect(tile)
+        .ok_or_else(|| TileReflectError::InvalidTile(tile.reflect_type_path().to_string()))
+}
+
+/// Convert a reflected value into a [`TileLayer`].
+pub fn layer_from_reflect(layer: &dyn Reflect) -> Result<TileLayer, TileReflectError> {
+    TileLayer::from_reflect(layer)
+        .ok_or_else(|| TileReflectError::InvalidLayer(layer.reflect_type_path().to_string()))
+}
+
+/// Get the tile entity at `index`.
+pub fn get_tile(
+    world: &World,
+    tilemap: Entity,
+    index: IVec2,
+) -> Result<Option<Entity>, TileReflectError> {
+    world
+        .get::<TilemapStorage>(tilemap)
+        .map(|storage| storage.get(index))
+        .ok_or(TileReflectError::NotTilemap(tilemap))
+}
+
+pub fn set_tile(
+    world: &mut World,
+    tilemap: Entity,
+    index: IVec2,
+    tile: &dyn Reflect,
+) -> Result<(), TileReflectError> {
+    let tile = tile_from_reflect(tile)?;
+    with_storage(world, tilemap, |commands, storage| {
+        storage.set(commands, index, tile)
+    })
+}
+
+pub fn fill_rect(
+    world: &mut World,
+    tilemap: Entity,
+    area: GridRect,
+    tile: &dyn Reflect,
+) -> Result<(), TileReflectError> {
+    let tile = tile_from_reflect(tile)?;
+    with_storage(world, tilemap, |commands, storage| {
+        storage.fill_rect(commands, area, tile)
+    })
+}
+
+/// Replace the layer at `layer_index` of the tile at `index`, keeping the other layers.
+pub fn set_tile_layer(
+    world: &mut World,
+    tilemap: Entity,
+    index: IVec2,
+    layer_index: usize,
+    layer: &dyn Reflect,
+) -> Result<(), TileReflectError> {
+    let layer = layer_from_reflect(layer)?;
+    with_storage(world, tilemap, |commands, storage| {
+        storage.update(
+            commands,
+            index,
+            TileUpdater {
+                layer: Some(LayerUpdater {
+                    position: TileLayerPosition::Index(layer_index),
+                    layer,
+                }),
+                ..Default::default()
+            },
+        )
+    })
+}
+
+pub fn remove_tile(
+    world: &mut World,
+    tilemap: Entity,
+    index: IVec2,
+) -> Result<(), TileReflectError> {
+    with_storage(world, tilemap, |commands, storage| {
+        storage.remove(commands, index)
+    })
+}
+
+fn with_storage(
+    world: &mut World,
+    tilemap: Entity,
+    f: impl FnOnce(&mut Commands, &mut TilemapStorage),
+) -> Result<(), TileReflectError> {
+    let mut state = SystemState::<(Commands, Query<&mut TilemapStorage>)>::new(world);
+    let (mut commands, mut storages_query) = state.get_mut(world);
+    let mut storage = storages_query
+        .get_mut(tilemap)
+        .map_err(|_| TileReflectError::NotTilemap(tilemap))?;
+
+    f(&mut commands, &mut storage);
+    state.apply(world);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{ecs::world::World, math::IVec2, reflect::Reflect};
+
+    use crate::tilemap::{
+        map::TilemapStorage,
+        tile::{Tile, TileBuilder, TileLayer, TileTexture},
+    };
+
+    #[test]
+    fn test_set_tile_by_reflection() {
+        let mut world = World::new();
+        let tilemap = world.spawn_empty().id();
+        world
+            .entity_mut(tilemap)
+            .insert(TilemapStorage::new(16, tilemap));
+
+        // A dynamic representation, like what scripting layers would pass in.
+        let tile = TileBuilder::new()
+            .with_layer(0, TileLayer::no_flip(3))
+            .clone_value();
+        super::set_tile(&mut world, tilemap, IVec2::new(-2, 5), &*tile).unwrap();
+
+        let entity = super::get_tile(&world, tilemap, IVec2::new(-2, 5))
+            .unwrap()
+            .unwrap();
+        let TileTexture::Static(layers) = &world.get::<Tile>(entity).unwrap().texture else {
+            panic!("Expected a static tile!");
+        };
+        assert_eq!(layers[0].atlas_index, 3);
+
+        super::remove_tile(&mut world, tilemap, IVec2::new(-2, 5)).unwrap();
+        assert!(super::get_tile(&world, tilemap, IVec2::new(-2, 5))
+            .unwrap()
+            .is_none());
+
+        assert!(super::set_tile(&mut world, tilemap, IVec2::ZERO, &0u32).is_err());
+        let not_tilemap = world.spawn_empty().id();
+        assert!(super::get_tile(&world, not_tilemap, IVec2::ZERO).is_err());
+    }
+}
@@ -0,0 +1,504 @@
+use bevy::{
+    asset::Assets,
+    color::{Alpha, LinearRgba},
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        entity::{Entity, EntityHashMap},
+        query::{Changed, Has, Or},
+        removal_detection::RemovedComponents,
+        system::{Commands, Local, Query, ResMut},
+    },
+    log::warn,
+    math::{IVec2, Vec2},
+    reflect::Reflect,
+    utils::HashMap,
+};
+
+use crate::{
+    math::GridRect,
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        bundles::StandardPureColorTilemapBundle,
+        chunking::storage::ChunkedStorage,
+        map::{
+            TilePivot, TileRenderSize, TilemapAxisFlip, TilemapName, TilemapSlotSize,
+            TilemapStorage, TilemapTransform, TilemapType,
+        },
+        tile::TileBuilder,
+    },
+    DEFAULT_CHUNK_SIZE,
+};
+
+pub type TerritoryOwner = u32;
+
+/// The owner of each tile. Insert this to a tilemap to track territories.
+///
+/// Borders will be traced into [`TerritoryBorders`] when this changes.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TerritoryTilemap {
+    pub(crate) storage: ChunkedStorage<TerritoryOwner>,
+    pub(crate) colors: HashMap<TerritoryOwner, LinearRgba>,
+}
+
+impl Default for TerritoryTilemap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerritoryTilemap {
+    pub fn new() -> Self {
+        Self::new_with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn new_with_chunk_size(chunk_size: u32) -> Self {
+        Self {
+            storage: ChunkedStorage::new(chunk_size),
+            colors: HashMap::default(),
+        }
+    }
+
+    /// Set the color used to fill the territory of `owner` in the overlay,
+    /// and to draw its borders in debug mode.
+    pub fn with_owner_color(mut self, owner: TerritoryOwner, color: LinearRgba) -> Self {
+        self.set_owner_color(owner, color);
+        self
+    }
+
+    #[inline]
+    pub fn set_owner_color(&mut self, owner: TerritoryOwner, color: LinearRgba) {
+        self.colors.insert(owner, color);
+    }
+
+    #[inline]
+    pub fn owner_color(&self, owner: TerritoryOwner) -> Option<LinearRgba> {
+        self.colors.get(&owner).copied()
+    }
+
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<TerritoryOwner> {
+        self.storage.get_elem(index).copied()
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: IVec2, owner: TerritoryOwner) {
+        self.storage.set_elem(index, owner);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, index: IVec2) -> Option<TerritoryOwner> {
+        self.storage.remove_elem(index)
+    }
+
+    pub fn fill_rect(&mut self, area: GridRect, owner: TerritoryOwner) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set(IVec2 { x, y }, owner);
+            }
+        }
+    }
+
+    /// Iterate over all the owned tiles.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, TerritoryOwner)> + '_ {
+        self.storage
+            .chunked_iter_some()
+            .map(|(chunk_index, in_chunk_index, owner)| {
+                (
+                    self.storage
+                        .inverse_transform_index(chunk_index, in_chunk_index),
+                    *owner,
+                )
+            })
+    }
+
+    /// Trace the borders of each territory into closed loops of grid corners.
+    ///
+    /// Outer borders are counter-clockwise and holes are clockwise.
+    pub fn trace_borders(&self) -> HashMap<TerritoryOwner, Vec<Vec<IVec2>>> {
+        // Directed edges along the boundaries, keyed by owner and start corner.
+        let mut edges = HashMap::<TerritoryOwner, HashMap<IVec2, Vec<IVec2>>>::default();
+
+        self.iter().for_each(|(index, owner)| {
+            let owner_edges = edges.entry(owner).or_default();
+            [
+                (IVec2::NEG_Y, IVec2::ZERO, IVec2::X),
+                (IVec2::X, IVec2::X, IVec2::ONE),
+                (IVec2::Y, IVec2::ONE, IVec2::Y),
+                (IVec2::NEG_X, IVec2::Y, IVec2::ZERO),
+            ]
+            .into_iter()
+            .filter(|(dir, _, _)| self.get(index + *dir) != Some(owner))
+            .for_each(|(_, from, to)| {
+                owner_edges
+                    .entry(index + from)
+                    .or_default()
+                    .push(index + to);
+            });
+        });
+
+        edges
+            .into_iter()
+            .map(|(owner, mut owner_edges)| {
+                let mut loops = Vec::new();
+
+                while let Some(&start) = owner_edges.keys().next() {
+                    let mut cur = start;
+                    let mut polyline = Vec::new();
+
+                    while let Some(next) = Self::take_edge(&mut owner_edges, cur) {
+                        polyline.push(cur);
+                        cur = next;
+                        if cur == start {
+                            break;
+                        }
+                    }
+
+                    loops.push(Self::simplify(polyline));
+                }
+
+                (owner, loops)
+            })
+            .collect()
+    }
+
+    fn take_edge(edges: &mut HashMap<IVec2, Vec<IVec2>>, from: IVec2) -> Option<IVec2> {
+        let targets = edges.get_mut(&from)?;
+        let to = targets.pop();
+        if targets.is_empty() {
+            edges.remove(&from);
+        }
+        to
+    }
+
+    /// Remove the corners in the middle of straight lines.
+    fn simplify(polyline: Vec<IVec2>) -> Vec<IVec2> {
+        let n = polyline.len();
+        if n < 3 {
+            return polyline;
+        }
+
+        (0..n)
+            .filter(|&i| {
+                let prev = polyline[(i + n - 1) % n];
+                let next = polyline[(i + 1) % n];
+                (polyline[i] - prev).perp_dot(next - polyline[i]) != 0
+            })
+            .map(|i| polyline[i])
+            .collect()
+    }
+}
+
+/// The borders of territories in world space, traced from [`TerritoryTilemap`].
+///
+/// Each border is a closed polyline, which means the last point connects to the first one.
+/// The pivot, axis flip and transform of the tilemap are taken into account, and outer borders
+/// stay counter-clockwise even if the tilemap is flipped.
+///
+/// **Notice**: Only square tilemaps are supported.
+#[derive(Component, Default, Debug, Clone, Reflect)]
+pub struct TerritoryBorders {
+    pub(crate) borders: HashMap<TerritoryOwner, Vec<Vec<Vec2>>>,
+}
+
+impl TerritoryBorders {
+    #[inline]
+    pub fn get(&self, owner: TerritoryOwner) -> Option<&Vec<Vec<Vec2>>> {
+        self.borders.get(&owner)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&TerritoryOwner, &Vec<Vec<Vec2>>)> {
+        self.borders.iter()
+    }
+}
+
+/// Insert this along with [`TerritoryTilemap`] to fill the territories with
+/// translucent colors, using a pure color tilemap placed above this tilemap.
+///
+/// The overlay tilemap is despawned once either of them is removed.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TerritoryOverlay {
+    pub opacity: f32,
+    pub(crate) overlay: Option<Entity>,
+}
+
+impl Default for TerritoryOverlay {
+    fn default() -> Self {
+        Self::new(0.4)
+    }
+}
+
+impl TerritoryOverlay {
+    pub fn new(opacity: f32) -> Self {
+        Self {
+            opacity,
+            overlay: None,
+        }
+    }
+
+    /// The pure color tilemap that fills the territories.
+    #[inline]
+    pub fn overlay(&self) -> Option<Entity> {
+        self.overlay
+    }
+}
+
+/// Get the world position of a grid corner, where corner `(x, y)` is the bottom left corner
+/// of the tile at `(x, y)` before flipping.
+fn corner_to_world(
+    corner: IVec2,
+    transform: &TilemapTransform,
+    pivot: Vec2,
+    slot_size: Vec2,
+    render_size: Vec2,
+    axis_flip: TilemapAxisFlip,
+) -> Vec2 {
+    // Same as the mesh origin in `square.wgsl`, which mirrors the slots around the origin.
+    transform
+        .transform_point(corner.as_vec2() * axis_flip.as_vec2() * slot_size - pivot * render_size)
+}
+
+pub fn territory_updater(
+    mut commands: Commands,
+    mut tilemaps_query: Query<
+        (
+            Entity,
+            &TerritoryTilemap,
+            &TilemapType,
+            &TilemapTransform,
+            &TilePivot,
+            &TilemapSlotSize,
+            &TileRenderSize,
+            &TilemapAxisFlip,
+            Option<&mut TerritoryOverlay>,
+        ),
+        Or<(Changed<TerritoryTilemap>, Changed<TerritoryOverlay>)>,
+    >,
+    territories_query: Query<(Has<TerritoryTilemap>, Has<TerritoryOverlay>)>,
+    mut storages_query: Query<&mut TilemapStorage>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut removed_territories: RemovedComponents<TerritoryTilemap>,
+    mut removed_overlays: RemovedComponents<TerritoryOverlay>,
+    mut overlays: Local<EntityHashMap<Entity>>,
+) {
+    for entity in removed_territories
+        .read()
+        .chain(removed_overlays.read())
+        .collect::<Vec<_>>()
+    {
+        let (is_territory, has_overlay) = territories_query.get(entity).unwrap_or_default();
+
+        if !has_overlay || !is_territory {
+            if let Some(mut overlay) = overlays
+                .remove(&entity)
+                .and_then(|overlay| storages_query.get_mut(overlay).ok())
+            {
+                overlay.despawn(&mut commands);
+            }
+        }
+
+        if !is_territory {
+            if let Some(mut entity) = commands.get_entity(entity) {
+                entity.remove::<TerritoryBorders>();
+            }
+        }
+    }
+
+    tilemaps_query.iter_mut().for_each(
+        |(entity, territory, ty, transform, pivot, slot_size, render_size, axis_flip, overlay)| {
+            if *ty != TilemapType::Square {
+                warn!("Territory borders are only supported on square tilemaps!");
+            } else {
+                // Flipping one of the axes reverses the winding.
+                let reversed = axis_flip.contains(TilemapAxisFlip::X)
+                    != axis_flip.contains(TilemapAxisFlip::Y);
+                let borders = territory
+                    .trace_borders()
+                    .into_iter()
+                    .map(|(owner, loops)| {
+                        (
+                            owner,
+                            loops
+                                .into_iter()
+                                .map(|l| {
+                                    let mut l = l
+                                        .into_iter()
+                                        .map(|corner| {
+                                            corner_to_world(
+                                                corner,
+                                                transform,
+                                                pivot.0,
+                                                slot_size.0,
+                                                render_size.0,
+                                                *axis_flip,
+                                            )
+                                        })
+                                        .collect::<Vec<_>>();
+                                    if reversed {
+                                        l.reverse();
+                                    }
+                                    l
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect();
+
+                commands.entity(entity).insert(TerritoryBorders { borders });
+            }
+
+            let Some(mut overlay) = overlay else {
+                return;
+            };
+
+            // Respawn the overlay as territories usually don't change frequently.
+            if let Some(mut old) = overlay
+                .overlay
+                .and_then(|old| storages_query.get_mut(old).ok())
+            {
+                old.despawn(&mut commands);
+            }
+
+            let overlay_entity = commands.spawn_empty().id();
+            let mut storage = TilemapStorage::new(territory.storage.chunk_size, overlay_entity);
+            territory.iter().for_each(|(index, owner)| {
+                let Some(color) = territory.owner_color(owner) else {
+                    return;
+                };
+
+                storage.set(
+                    &mut commands,
+                    index,
+                    TileBuilder::new().with_tint(color.with_alpha(color.alpha * overlay.opacity)),
+                );
+            });
+
+            commands
+                .entity(overlay_entity)
+                .insert(StandardPureColorTilemapBundle {
+                    name: TilemapName("territory_overlay".to_string()),
+                    tile_render_size: *render_size,
+                    slot_size: *slot_size,
+                    ty: *ty,
+                    tile_pivot: *pivot,
+                    storage,
+                    transform: TilemapTransform {
+                        z_index: transform.z_index + 0.1,
+                        ..*transform
+                    },
+                    axis_flip: *axis_flip,
+                    material: materials.add(StandardTilemapMaterial::default()),
+                    ..Default::default()
+                });
+
+            // Bypass change detection, otherwise the overlay will be respawned every frame.
+            overlay.bypass_change_detection().overlay = Some(overlay_entity);
+            overlays.insert(entity, overlay_entity);
+        },
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        asset::Assets,
+        ecs::world::World,
+        math::{IVec2, UVec2, Vec2},
+    };
+
+    use crate::{
+        math::GridRect,
+        render::material::StandardTilemapMaterial,
+        tilemap::{
+            despawn::DespawnMe,
+            map::{
+                TilePivot, TileRenderSize, TilemapAxisFlip, TilemapSlotSize, TilemapTransform,
+                TilemapType,
+            },
+        },
+    };
+
+    use super::{territory_updater, TerritoryBorders, TerritoryOverlay, TerritoryTilemap};
+
+    #[test]
+    fn test_trace_borders() {
+        let mut territory = TerritoryTilemap::new();
+        territory.fill_rect(GridRect::new(IVec2::ZERO, UVec2::splat(2)), 1);
+
+        let borders = territory.trace_borders();
+        let loops = &borders[&1];
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].len(), 4);
+        assert!([
+            IVec2::ZERO,
+            IVec2::new(2, 0),
+            IVec2::splat(2),
+            IVec2::new(0, 2)
+        ]
+        .iter()
+        .all(|c| loops[0].contains(c)));
+
+        // A ring with a hole in the middle.
+        let mut territory = TerritoryTilemap::new();
+        territory.fill_rect(GridRect::new(IVec2::splat(-1), UVec2::splat(3)), 1);
+        territory.remove(IVec2::ZERO);
+
+        let borders = territory.trace_borders();
+        let mut lens = borders[&1].iter().map(|l| l.len()).collect::<Vec<_>>();
+        lens.sort();
+        assert_eq!(lens, vec![4, 4]);
+    }
+
+    #[test]
+    fn test_territory_updater() {
+        let mut world = World::new();
+        world.init_resource::<Assets<StandardTilemapMaterial>>();
+
+        let mut territory = TerritoryTilemap::new();
+        territory.fill_rect(GridRect::new(IVec2::ZERO, UVec2::splat(2)), 1);
+        let tilemap = world
+            .spawn((
+                territory,
+                TilemapType::Square,
+                TilemapTransform::default(),
+                TilePivot(Vec2::splat(0.5)),
+                TilemapSlotSize(Vec2::splat(16.)),
+                TileRenderSize(Vec2::splat(16.)),
+                TilemapAxisFlip::X,
+                TerritoryOverlay::default(),
+            ))
+            .id();
+        let updater = world.register_system(territory_updater);
+        world.run_system(updater).unwrap();
+
+        // Mirrored around the origin and shifted by the pivot, still counter-clockwise.
+        let border = world
+            .get::<TerritoryBorders>(tilemap)
+            .unwrap()
+            .get(1)
+            .unwrap()[0]
+            .clone();
+        let area = border
+            .iter()
+            .zip(border.iter().cycle().skip(1))
+            .map(|(a, b)| a.perp_dot(*b))
+            .sum::<f32>();
+        assert!(area > 0.);
+        assert!(border.contains(&Vec2::new(-8., -8.)));
+        assert!(border.contains(&Vec2::new(-40., 24.)));
+
+        let overlay = world
+            .get::<TerritoryOverlay>(tilemap)
+            .unwrap()
+            .overlay()
+            .unwrap();
+        world.entity_mut(tilemap).remove::<TerritoryOverlay>();
+        world.run_system(updater).unwrap();
+        assert!(world.get::<DespawnMe>(overlay).is_some());
+
+        world.entity_mut(tilemap).remove::<TerritoryTilemap>();
+        world.run_system(updater).unwrap();
+        assert!(world.get::<TerritoryBorders>(tilemap).is_none());
+    }
+}
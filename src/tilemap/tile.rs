@@ -1,25 +1,39 @@
 use bevy::{
     color::LinearRgba,
-    ecs::system::{ParallelCommands, Query},
-    math::IVec2,
+    ecs::{
+        change_detection::DetectChangesMut,
+        system::{ParallelCommands, Query, Res},
+        world::{Command, World},
+    },
+    math::{IVec2, Vec2},
     prelude::{Component, Entity},
-    reflect::Reflect,
-    render::render_resource::ShaderType,
+    reflect::{std_traits::ReflectDefault, Reflect},
+    time::Time,
 };
 
-use crate::tilemap::{buffers::Tiles, map::TilemapStorage};
+use crate::tilemap::{
+    buffers::Tiles,
+    map::{TilemapAnimationLod, TilemapStorage},
+};
 
 /// A tile layer. This is the logical representation of a tile layer.
 /// Not all the layers you added to a tile will be taken into consideration
 /// when rendering. Only the top 4 layers will be rendered.
-#[derive(Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Default)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileLayer {
     #[cfg(feature = "atlas")]
     pub texture_index: i32,
     pub atlas_index: i32,
-    #[reflect(ignore)]
     pub flip: TileFlip,
+    /// Multiplies the render size of this layer, for big tiles spanning multiple slots.
+    /// The layer grows from the [`TilePivot`](crate::tilemap::map::TilePivot).
+    ///
+    /// **Notice**: Big tiles may be culled at the edges of the chunks,
+    /// and the multiplier is ignored when baking.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub render_scale: Option<Vec2>,
 }
 
 impl Default for TileLayer {
@@ -30,49 +44,53 @@ impl Default for TileLayer {
             texture_index: -1,
             atlas_index: -1,
             flip: Default::default(),
+            render_scale: None,
         }
     }
 }
 
 impl TileLayer {
+    /// Create a layer no matter whether `atlas` feature is enabled.
+    ///
+    /// **Notice**: `texture_index` is ignored when `atlas` feature is disabled,
+    /// and `atlas_index` will be considered as the global index among all the textures.
     #[inline]
-    pub fn no_flip(atlas_index: i32) -> Self {
+    #[allow(unused_variables)]
+    pub fn new(texture_index: i32, atlas_index: i32, flip: TileFlip) -> Self {
         Self {
-            atlas_index,
             #[cfg(feature = "atlas")]
-            texture_index: 0,
-            flip: TileFlip::NONE,
+            texture_index,
+            atlas_index,
+            flip,
+            render_scale: None,
         }
     }
 
+    /// Render this layer `scale` times larger than the other tiles.
+    #[inline]
+    pub fn with_render_scale(mut self, scale: Vec2) -> Self {
+        self.render_scale = Some(scale);
+        self
+    }
+
+    #[inline]
+    pub fn no_flip(atlas_index: i32) -> Self {
+        Self::new(0, atlas_index, TileFlip::NONE)
+    }
+
     #[inline]
     pub fn flip_h(atlas_index: i32) -> Self {
-        Self {
-            atlas_index,
-            #[cfg(feature = "atlas")]
-            texture_index: 0,
-            flip: TileFlip::HORIZONTAL,
-        }
+        Self::new(0, atlas_index, TileFlip::HORIZONTAL)
     }
 
     #[inline]
     pub fn flip_v(atlas_index: i32) -> Self {
-        Self {
-            atlas_index,
-            #[cfg(feature = "atlas")]
-            texture_index: 0,
-            flip: TileFlip::VERTICAL,
-        }
+        Self::new(0, atlas_index, TileFlip::VERTICAL)
     }
 
     #[inline]
     pub fn flip_both(atlas_index: i32) -> Self {
-        Self {
-            atlas_index,
-            #[cfg(feature = "atlas")]
-            texture_index: 0,
-            flip: TileFlip::BOTH,
-        }
+        Self::new(0, atlas_index, TileFlip::BOTH)
     }
 }
 
@@ -80,38 +98,22 @@ impl TileLayer {
 impl TileLayer {
     #[inline]
     pub fn no_flip_at(texture_index: i32, atlas_index: i32) -> Self {
-        Self {
-            texture_index,
-            atlas_index,
-            flip: TileFlip::NONE,
-        }
+        Self::new(texture_index, atlas_index, TileFlip::NONE)
     }
 
     #[inline]
     pub fn flip_h_at(texture_index: i32, atlas_index: i32) -> Self {
-        Self {
-            texture_index,
-            atlas_index,
-            flip: TileFlip::HORIZONTAL,
-        }
+        Self::new(texture_index, atlas_index, TileFlip::HORIZONTAL)
     }
 
     #[inline]
     pub fn flip_v_at(texture_index: i32, atlas_index: i32) -> Self {
-        Self {
-            texture_index,
-            atlas_index,
-            flip: TileFlip::VERTICAL,
-        }
+        Self::new(texture_index, atlas_index, TileFlip::VERTICAL)
     }
 
     #[inline]
     pub fn flip_both_at(texture_index: i32, atlas_index: i32) -> Self {
-        Self {
-            texture_index,
-            atlas_index,
-            flip: TileFlip::BOTH,
-        }
+        Self::new(texture_index, atlas_index, TileFlip::BOTH)
     }
 }
 
@@ -137,9 +139,123 @@ pub struct TileUpdater {
     pub tint: Option<LinearRgba>,
 }
 
+impl LayerUpdater {
+    pub fn new(position: TileLayerPosition, layer: TileLayer) -> Self {
+        Self { position, layer }
+    }
+}
+
+impl TileUpdater {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put `layer` at `position`. Animated tiles won't be affected.
+    pub fn with_layer(mut self, position: TileLayerPosition, layer: TileLayer) -> Self {
+        self.layer = Some(LayerUpdater::new(position, layer));
+        self
+    }
+
+    /// Replace the tint of the entire tile.
+    pub fn with_tint(mut self, tint: LinearRgba) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+
+    /// Apply the changes to the tile.
+    pub fn apply(&self, tile: &mut Tile) {
+        if let Some(layer) = &self.layer {
+            if let TileTexture::Static(ref mut tex) = tile.texture {
+                match layer.position {
+                    TileLayerPosition::Top => {
+                        tex.push(layer.layer);
+                    }
+                    TileLayerPosition::Bottom => {
+                        tex.insert(0, layer.layer);
+                    }
+                    TileLayerPosition::Index(i) => {
+                        if i >= tex.len() {
+                            tex.resize(i + 1, TileLayer::default());
+                        }
+                        tex[i] = layer.layer;
+                    }
+                }
+            }
+        }
+        if let Some(color) = self.tint {
+            tile.tint = color;
+        }
+    }
+}
+
+/// Update tiles in place using a single command, instead of inserting
+/// a [`TileUpdater`] into each of them.
+///
+/// This is what [`TilemapStorage::update_rect`] and [`TilemapStorage::update_many`] use.
+/// Entities that are not tiles are skipped.
+pub struct UpdateTiles(pub Vec<(Entity, TileUpdater)>);
+
+impl Command for UpdateTiles {
+    fn apply(self, world: &mut World) {
+        for (entity, updater) in self.0 {
+            if let Some(mut tile) = world.get_mut::<Tile>(entity) {
+                updater.apply(&mut tile);
+            }
+        }
+    }
+}
+
+/// Mark the tiles as changed so they are extracted to the render world again.
+///
+/// This is what [`TilemapStorage::mark_chunk_dirty`] uses.
+/// Entities that are not tiles are skipped.
+pub struct MarkTilesChanged(pub Vec<Entity>);
+
+impl Command for MarkTilesChanged {
+    fn apply(self, world: &mut World) {
+        for entity in self.0 {
+            if let Some(mut tile) = world.get_mut::<Tile>(entity) {
+                tile.set_changed();
+            }
+        }
+    }
+}
+
+/// Clear a single layer of the tiles in place. The layers above keep their indices.
+///
+/// This is what [`TilemapStorage::clear_layer`] uses. Animated tiles are skipped.
+pub struct ClearTileLayer {
+    pub tiles: Vec<Entity>,
+    pub layer: usize,
+}
+
+impl Command for ClearTileLayer {
+    fn apply(self, world: &mut World) {
+        for entity in self.tiles {
+            let Some(mut tile) = world.get_mut::<Tile>(entity) else {
+                continue;
+            };
+            // Check before mutating so untouched tiles are not marked as changed.
+            if !matches!(&tile.texture, TileTexture::Static(tex)
+                if tex.get(self.layer).is_some_and(|l| *l != TileLayer::default()))
+            {
+                continue;
+            }
+
+            if let TileTexture::Static(ref mut tex) = tile.texture {
+                tex[self.layer] = TileLayer::default();
+                while tex.last() == Some(&TileLayer::default()) {
+                    tex.pop();
+                }
+            }
+        }
+    }
+}
+
 bitflags::bitflags! {
     /// The flip of a tile.
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+    #[reflect_value(Debug, Default)]
     #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
     pub struct TileFlip: u32 {
         const NONE = 0b00;
@@ -156,21 +272,31 @@ impl Default for TileFlip {
 }
 
 /// A tile builder. This is used to create a tile.
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[reflect(Default)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileBuilder {
     pub(crate) texture: TileTexture,
     pub(crate) tint: LinearRgba,
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) emissive: f32,
 }
 
 impl Tiles for TileBuilder {}
 
+impl Default for TileBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TileBuilder {
     /// Create a new tile builder.
     pub fn new() -> Self {
         Self {
             texture: TileTexture::Static(Vec::new()),
             tint: LinearRgba::WHITE,
+            emissive: 0.,
         }
     }
 
@@ -180,6 +306,15 @@ impl TileBuilder {
         self
     }
 
+    /// Make the tile glow. Default is 0, which means not emissive.
+    ///
+    /// On hdr cameras, the color is multiplied by `1 + intensity` so bloom can pick it up.
+    /// Otherwise, the tile is only brightened as colors can't exceed 1.
+    pub fn with_emissive(mut self, intensity: f32) -> Self {
+        self.emissive = intensity;
+        self
+    }
+
     /// Set the specific layer of the tile.
     ///
     /// You don't need to worry about the index of the layer. If the index is greater than the current
@@ -218,19 +353,78 @@ impl TileBuilder {
             index,
             texture: self.texture.clone(),
             tint: self.tint,
+            emissive: self.emissive,
+        }
+    }
+}
+
+/// How the frames of a tile animation are played.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileAnimationMode {
+    #[default]
+    Loop,
+    /// Play forward and then backward, repeatedly.
+    PingPong,
+    /// Play only once and then stay on the last frame.
+    Once,
+}
+
+impl TileAnimationMode {
+    /// The value that will be written into the animation buffer.
+    #[inline]
+    pub(crate) fn as_shader_flag(self) -> i32 {
+        match self {
+            TileAnimationMode::Loop => 0,
+            TileAnimationMode::PingPong => 1,
+            TileAnimationMode::Once => 2,
         }
     }
 }
 
 /// A tile animation. This is actually information about the position of the animation
 /// in the tilemap animation buffer. So it's cheap to clone.
-#[derive(ShaderType, Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileAnimation {
     pub(crate) start: u32,
     /// When `atlas` feature is enabled, the length **WON'T** doubled!!
     pub(crate) length: u32,
     pub(crate) fps: u32,
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) mode: TileAnimationMode,
+    /// The elapsed seconds when the animation started playing.
+    #[cfg_attr(feature = "serializing", serde(default))]
+    pub(crate) start_time: f32,
+}
+
+impl TileAnimation {
+    #[inline]
+    pub fn mode(&self) -> TileAnimationMode {
+        self.mode
+    }
+
+    /// Start the animation at the given time, which should be `Time::elapsed_seconds`,
+    /// or [`TilemapAnimationLod::chunk_elapsed`] if the tilemap has one.
+    ///
+    /// This is useful for `Once` animations, as they will only be visible
+    /// in a short period after `start_time`.
+    #[inline]
+    pub fn started_at(mut self, start_time: f32) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// The duration of a single play in seconds.
+    ///
+    /// For `PingPong` animations, this is the time going forward and back.
+    pub fn duration(&self) -> f32 {
+        let frames = match self.mode {
+            TileAnimationMode::Loop | TileAnimationMode::Once => self.length,
+            TileAnimationMode::PingPong => (self.length * 2).saturating_sub(2).max(1),
+        };
+        frames as f32 / self.fps.max(1) as f32
+    }
 }
 
 /// A raw tile animation. This is contains the full information of a tile animation.
@@ -242,10 +436,40 @@ pub struct RawTileAnimation {
     #[cfg(feature = "atlas")]
     pub sequence: Vec<(u32, u32)>,
     pub fps: u32,
+    pub mode: TileAnimationMode,
+}
+
+impl RawTileAnimation {
+    /// Create an animation no matter whether `atlas` feature is enabled.
+    ///
+    /// **Notice**: `texture_index` is ignored when `atlas` feature is disabled.
+    #[allow(unused_variables)]
+    pub fn from_atlas_indices(
+        texture_index: u32,
+        atlas_indices: impl IntoIterator<Item = u32>,
+        fps: u32,
+    ) -> Self {
+        Self {
+            #[cfg(not(feature = "atlas"))]
+            sequence: atlas_indices.into_iter().collect(),
+            #[cfg(feature = "atlas")]
+            sequence: atlas_indices
+                .into_iter()
+                .map(|a| (texture_index, a))
+                .collect(),
+            fps,
+            mode: TileAnimationMode::Loop,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: TileAnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
 }
 
 /// A tile texture. This is either a static texture or an animation.
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, PartialEq, Reflect)]
 #[cfg_attr(feature = "serializing", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileTexture {
     Static(Vec<TileLayer>),
@@ -261,6 +485,7 @@ pub struct Tile {
     pub index: IVec2,
     pub texture: TileTexture,
     pub tint: LinearRgba,
+    pub emissive: f32,
 }
 
 impl Tiles for Tile {}
@@ -270,6 +495,29 @@ impl Into<TileBuilder> for Tile {
         TileBuilder {
             texture: self.texture,
             tint: self.tint,
+            emissive: self.emissive,
+        }
+    }
+}
+
+/// Play an animation on the tile once, and then land on the static `end` layers.
+///
+/// Insert this to a tile entity, for example, to open a chest.
+/// The animation should be registered in the `TilemapAnimations` of the tilemap,
+/// and it's recommended to use `TileAnimationMode::Once`.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct OneShotTileAnimation {
+    pub animation: TileAnimation,
+    pub end: Vec<TileLayer>,
+    finish_at: Option<f32>,
+}
+
+impl OneShotTileAnimation {
+    pub fn new(animation: TileAnimation, end: Vec<TileLayer>) -> Self {
+        Self {
+            animation,
+            end,
+            finish_at: None,
         }
     }
 }
@@ -287,27 +535,7 @@ pub fn tile_updater(
     tiles_query
         .par_iter_mut()
         .for_each(|(entity, mut tile, updater)| {
-            if let Some(layer) = &updater.layer {
-                if let TileTexture::Static(ref mut tex) = tile.texture {
-                    match layer.position {
-                        TileLayerPosition::Top => {
-                            tex.push(layer.layer);
-                        }
-                        TileLayerPosition::Bottom => {
-                            tex.insert(0, layer.layer);
-                        }
-                        TileLayerPosition::Index(i) => {
-                            if i >= tex.len() {
-                                tex.resize(i + 1, TileLayer::default());
-                            }
-                            tex[i] = layer.layer;
-                        }
-                    }
-                }
-            }
-            if let Some(color) = updater.tint {
-                tile.tint = color;
-            }
+            updater.apply(&mut tile);
             commands.command_scope(|mut c| {
                 c.entity(entity).remove::<TileUpdater>();
             });
@@ -328,3 +556,35 @@ pub(crate) fn tile_rearranger(
             })
         });
 }
+
+pub fn one_shot_animation_player(
+    commands: ParallelCommands,
+    mut tiles_query: Query<(Entity, &mut Tile, &mut OneShotTileAnimation)>,
+    lods_query: Query<&TilemapAnimationLod>,
+    time: Res<Time>,
+) {
+    tiles_query
+        .par_iter_mut()
+        .for_each(|(entity, mut tile, mut one_shot)| {
+            let now = lods_query
+                .get(tile.tilemap_id)
+                .map_or(time.elapsed_seconds(), |lod| {
+                    lod.chunk_elapsed(tile.chunk_index)
+                });
+
+            match one_shot.finish_at {
+                None => {
+                    let animation = one_shot.animation.started_at(now);
+                    tile.texture = TileTexture::Animated(animation);
+                    one_shot.finish_at = Some(now + animation.duration());
+                }
+                Some(finish_at) if now >= finish_at => {
+                    tile.texture = TileTexture::Static(one_shot.end.clone());
+                    commands.command_scope(|mut c| {
+                        c.entity(entity).remove::<OneShotTileAnimation>();
+                    });
+                }
+                _ => {}
+            }
+        });
+}
@@ -0,0 +1,323 @@
+//! Sensor-only regions on tilemaps, like door triggers and cutscene zones,
+//! without spawning colliders or requiring a physics engine.
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        observer::Trigger,
+        system::Query,
+    },
+    log::warn,
+    math::IVec2,
+    reflect::Reflect,
+    transform::components::GlobalTransform,
+    utils::HashMap,
+};
+
+use crate::{
+    math::GridRect,
+    tilemap::{
+        chunking::storage::ChunkedStorage,
+        coordinates,
+        despawn::EverythingUnloaded,
+        map::{TilePivot, TilemapAxisFlip, TilemapSlotSize, TilemapTransform, TilemapType},
+    },
+    DEFAULT_CHUNK_SIZE,
+};
+
+pub type TriggerRegionId = u32;
+
+/// The trigger region of each tile. Insert this to a tilemap to track actors.
+///
+/// Entities with [`TileTriggerActor`] receive [`RegionEntered`] and [`RegionExited`]
+/// when they move between regions.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct TileTriggerTilemap {
+    pub(crate) storage: ChunkedStorage<TriggerRegionId>,
+}
+
+impl Default for TileTriggerTilemap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TileTriggerTilemap {
+    pub fn new() -> Self {
+        Self::new_with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn new_with_chunk_size(chunk_size: u32) -> Self {
+        Self {
+            storage: ChunkedStorage::new(chunk_size),
+        }
+    }
+
+    /// Create the regions from row major `data` like LDtk IntGrid layers,
+    /// the first row is the top one. `origin` is the index of the bottom left tile.
+    ///
+    /// Tiles with the value of `empty` don't belong to any region.
+    /// Negative values are not valid region ids, so those tiles are skipped as well.
+    pub fn from_data(origin: IVec2, data: &[i32], width: u32, empty: i32) -> Self {
+        let width = width as i32;
+        let height = data.len() as i32 / width;
+        let mut trigger = Self::new();
+        let mut negative = 0;
+        data.iter()
+            .enumerate()
+            .filter(|(_, value)| **value != empty)
+            .for_each(|(i, value)| {
+                let Ok(region) = TriggerRegionId::try_from(*value) else {
+                    negative += 1;
+                    return;
+                };
+                let (x, y) = (i as i32 % width, i as i32 / width);
+                trigger.set(origin + IVec2::new(x, height - 1 - y), region);
+            });
+
+        if negative > 0 {
+            warn!(
+                "Skipped {} tiles with negative values, which can't be trigger regions.",
+                negative
+            );
+        }
+        trigger
+    }
+
+    #[inline]
+    pub fn get(&self, index: IVec2) -> Option<TriggerRegionId> {
+        self.storage.get_elem(index).copied()
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: IVec2, region: TriggerRegionId) {
+        self.storage.set_elem(index, region);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, index: IVec2) -> Option<TriggerRegionId> {
+        self.storage.remove_elem(index)
+    }
+
+    pub fn fill_rect(&mut self, area: GridRect, region: TriggerRegionId) {
+        for y in area.origin.y..=area.dest.y {
+            for x in area.origin.x..=area.dest.x {
+                self.set(IVec2 { x, y }, region);
+            }
+        }
+    }
+}
+
+/// Entities with this component are tracked by all the [`TileTriggerTilemap`]s.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+pub struct TileTriggerActor {
+    /// The region the actor is in, on each tilemap.
+    pub(crate) regions: HashMap<Entity, TriggerRegionId>,
+}
+
+impl TileTriggerActor {
+    /// Get the region the actor is currently in on `tilemap`.
+    pub fn region(&self, tilemap: Entity) -> Option<TriggerRegionId> {
+        self.regions.get(&tilemap).copied()
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionEntered {
+    pub actor: Entity,
+    pub tilemap: Entity,
+    pub region: TriggerRegionId,
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionExited {
+    pub actor: Entity,
+    pub tilemap: Entity,
+    pub region: TriggerRegionId,
+}
+
+/// Exits all the regions, as the tilemaps are gone.
+pub fn trigger_unloader(
+    _: Trigger<EverythingUnloaded>,
+    mut actors_query: Query<(Entity, &mut TileTriggerActor)>,
+    mut exited: EventWriter<RegionExited>,
+) {
+    actors_query
+        .iter_mut()
+        .for_each(|(actor, mut actor_regions)| {
+            actor_regions.regions.drain().for_each(|(tilemap, region)| {
+                exited.send(RegionExited {
+                    actor,
+                    tilemap,
+                    region,
+                });
+            });
+        });
+}
+
+pub fn trigger_updater(
+    tilemaps_query: Query<(
+        Entity,
+        &TileTriggerTilemap,
+        &TilemapType,
+        &TilemapTransform,
+        &TilePivot,
+        &TilemapSlotSize,
+        Option<&TilemapAxisFlip>,
+    )>,
+    mut actors_query: Query<(Entity, &GlobalTransform, &mut TileTriggerActor)>,
+    mut entered: EventWriter<RegionEntered>,
+    mut exited: EventWriter<RegionExited>,
+) {
+    actors_query
+        .iter_mut()
+        .for_each(|(actor, actor_transform, mut actor_regions)| {
+            let position = actor_transform.translation().truncate();
+
+            tilemaps_query.iter().for_each(
+                |(tilemap, trigger, ty, transform, pivot, slot_size, axis_flip)| {
+                    let index = coordinates::flip_index(
+                        coordinates::world_to_index(position, *ty, transform, pivot.0, slot_size.0),
+                        axis_flip.copied().unwrap_or_default(),
+                    );
+
+                    let current = trigger.get(index);
+                    let previous = actor_regions.region(tilemap);
+                    if current == previous {
+                        return;
+                    }
+
+                    if let Some(region) = previous {
+                        exited.send(RegionExited {
+                            actor,
+                            tilemap,
+                            region,
+                        });
+                    }
+                    if let Some(region) = current {
+                        entered.send(RegionEntered {
+                            actor,
+                            tilemap,
+                            region,
+                        });
+                        actor_regions.regions.insert(tilemap, region);
+                    } else {
+                        actor_regions.regions.remove(&tilemap);
+                    }
+                },
+            );
+
+            // Exit the regions of the tilemaps that no longer exist.
+            actor_regions.regions.retain(|tilemap, region| {
+                let exists = tilemaps_query.contains(*tilemap);
+                if !exists {
+                    exited.send(RegionExited {
+                        actor,
+                        tilemap: *tilemap,
+                        region: *region,
+                    });
+                }
+                exists
+            });
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::{
+        app::App,
+        ecs::event::Events,
+        math::{IVec2, UVec2, Vec2},
+        transform::components::{GlobalTransform, Transform},
+    };
+
+    use crate::{
+        math::GridRect,
+        tilemap::{
+            map::{TilePivot, TilemapSlotSize, TilemapTransform, TilemapType},
+            trigger::{trigger_updater, RegionEntered, RegionExited},
+        },
+    };
+
+    use super::{TileTriggerActor, TileTriggerTilemap};
+
+    #[test]
+    fn test_from_data() {
+        // The first row is the top one.
+        let trigger = TileTriggerTilemap::from_data(IVec2::ZERO, &[0, 3, -1, 0], 2, 0);
+        assert_eq!(trigger.get(IVec2::new(1, 1)), Some(3));
+        assert_eq!(trigger.get(IVec2::new(0, 0)), None);
+        assert_eq!(trigger.get(IVec2::new(1, 0)), None);
+    }
+
+    #[test]
+    fn test_trigger() {
+        let mut app = App::new();
+        app.add_event::<RegionEntered>()
+            .add_event::<RegionExited>()
+            .add_systems(bevy::app::Update, trigger_updater);
+
+        let mut trigger = TileTriggerTilemap::new();
+        trigger.fill_rect(GridRect::new(IVec2::new(2, 0), UVec2::new(2, 2)), 7);
+        let tilemap = app
+            .world_mut()
+            .spawn((
+                trigger,
+                TilemapType::Square,
+                TilemapTransform::default(),
+                TilePivot::default(),
+                TilemapSlotSize(Vec2::splat(16.)),
+            ))
+            .id();
+        let actor = app
+            .world_mut()
+            .spawn((
+                GlobalTransform::from(Transform::from_xyz(8., 8., 0.)),
+                TileTriggerActor::default(),
+            ))
+            .id();
+
+        let mut move_and_update = |x: f32| {
+            *app.world_mut().get_mut::<GlobalTransform>(actor).unwrap() =
+                Transform::from_xyz(x, 8., 0.).into();
+            app.update();
+            (
+                app.world_mut()
+                    .resource_mut::<Events<RegionEntered>>()
+                    .drain()
+                    .collect::<Vec<_>>(),
+                app.world_mut()
+                    .resource_mut::<Events<RegionExited>>()
+                    .drain()
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(move_and_update(8.), (vec![], vec![]));
+        assert_eq!(
+            move_and_update(40.),
+            (
+                vec![RegionEntered {
+                    actor,
+                    tilemap,
+                    region: 7
+                }],
+                vec![]
+            )
+        );
+        assert_eq!(move_and_update(60.), (vec![], vec![]));
+        assert_eq!(
+            move_and_update(80.),
+            (
+                vec![],
+                vec![RegionExited {
+                    actor,
+                    tilemap,
+                    region: 7
+                }]
+            )
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Animate whole tilemaps, like fading them out in cutscenes or moving platforms around.
+//!
+//! `AnimationClip`s in bevy can only animate `Transform`s for now, so tilemaps are
+//! animated using [`TilemapTweens`] instead. [`TilemapTransform`] and
+//! [`TilemapLayerOpacities`] still derive `Reflect`, so they can be driven by
+//! reflection based tools too.
+
+use bevy::{
+    asset::{Assets, Handle},
+    color::{LinearRgba, Mix},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        system::{Query, Res, ResMut},
+    },
+    math::{Vec2, Vec4},
+    reflect::Reflect,
+    time::Time,
+};
+
+use crate::{
+    render::material::StandardTilemapMaterial,
+    tilemap::{
+        map::{TilemapLayerOpacities, TilemapTransform},
+        tile::TileAnimationMode,
+    },
+};
+
+/// How the progress of a tween is mapped before interpolating.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum TilemapEasing {
+    #[default]
+    Linear,
+    QuadraticIn,
+    QuadraticOut,
+    QuadraticInOut,
+    SmoothStep,
+}
+
+impl TilemapEasing {
+    pub fn ease(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            TilemapEasing::Linear => t,
+            TilemapEasing::QuadraticIn => t * t,
+            TilemapEasing::QuadraticOut => t * (2. - t),
+            TilemapEasing::QuadraticInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(2) / 2.
+                }
+            }
+            TilemapEasing::SmoothStep => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+/// The property animated by a [`TilemapTween`].
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum TilemapTweenTarget {
+    /// [`TilemapTransform::translation`].
+    Translation {
+        from: Vec2,
+        to: Vec2,
+    },
+    /// [`TilemapTransform::scale`].
+    Scale {
+        from: Vec2,
+        to: Vec2,
+    },
+    /// [`TilemapTransform::angle`], in radians.
+    Rotation {
+        from: f32,
+        to: f32,
+    },
+    LayerOpacities {
+        from: Vec4,
+        to: Vec4,
+    },
+    /// The tint of the [`StandardTilemapMaterial`] of the tilemap.
+    ///
+    /// **Notice**: Materials are assets, so all the tilemaps sharing the material are affected.
+    MaterialTint {
+        from: LinearRgba,
+        to: LinearRgba,
+    },
+}
+
+/// Animate a property of the tilemap from one value to another.
+#[derive(Debug, Clone, Reflect)]
+pub struct TilemapTween {
+    pub target: TilemapTweenTarget,
+    /// In seconds.
+    pub duration: f32,
+    pub easing: TilemapEasing,
+    pub mode: TileAnimationMode,
+    pub elapsed: f32,
+    pub paused: bool,
+}
+
+impl TilemapTween {
+    /// Play the tween once, see [`TilemapTween::with_mode`] for repeating it.
+    pub fn new(target: TilemapTweenTarget, duration: f32) -> Self {
+        Self {
+            target,
+            duration,
+            easing: TilemapEasing::default(),
+            mode: TileAnimationMode::Once,
+            elapsed: 0.,
+            paused: false,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: TilemapEasing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: TileAnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The eased progress from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        let t = if self.duration > 0. {
+            self.elapsed / self.duration
+        } else {
+            1.
+        };
+
+        self.easing.ease(match self.mode {
+            TileAnimationMode::Loop => t.fract(),
+            TileAnimationMode::PingPong => 1. - (t % 2. - 1.).abs(),
+            TileAnimationMode::Once => t.min(1.),
+        })
+    }
+
+    /// Returns `true` if the tween is played once and reached the end.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.mode == TileAnimationMode::Once && self.elapsed >= self.duration
+    }
+}
+
+/// The tweens playing on a tilemap. Finished ones are removed,
+/// and a [`TilemapTweenFinished`] is sent for each of them.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+pub struct TilemapTweens(pub Vec<TilemapTween>);
+
+impl TilemapTweens {
+    pub fn with_tween(mut self, tween: TilemapTween) -> Self {
+        self.0.push(tween);
+        self
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct TilemapTweenFinished {
+    pub tilemap: Entity,
+    pub target: TilemapTweenTarget,
+}
+
+pub fn tilemap_tweener(
+    mut tilemaps_query: Query<(
+        Entity,
+        &mut TilemapTweens,
+        Option<&mut TilemapTransform>,
+        Option<&mut TilemapLayerOpacities>,
+        Option<&Handle<StandardTilemapMaterial>>,
+    )>,
+    mut materials: ResMut<Assets<StandardTilemapMaterial>>,
+    mut finished: EventWriter<TilemapTweenFinished>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    tilemaps_query.iter_mut().for_each(
+        |(tilemap, mut tweens, mut transform, mut opacities, material)| {
+            if tweens.0.is_empty() {
+                return;
+            }
+
+            for tween in tweens.0.iter_mut().filter(|t| !t.paused) {
+                tween.elapsed += delta;
+                let t = tween.progress();
+
+                match tween.target {
+                    TilemapTweenTarget::Translation { from, to } => {
+                        if let Some(transform) = transform.as_mut() {
+                            transform.translation = from.lerp(to, t);
+                        }
+                    }
+                    TilemapTweenTarget::Scale { from, to } => {
+                        if let Some(transform) = transform.as_mut() {
+                            transform.scale = from.lerp(to, t);
+                        }
+                    }
+                    TilemapTweenTarget::Rotation { from, to } => {
+                        if let Some(transform) = transform.as_mut() {
+                            transform.angle = from + (to - from) * t;
+                        }
+                    }
+                    TilemapTweenTarget::LayerOpacities { from, to } => {
+                        if let Some(opacities) = opacities.as_mut() {
+                            opacities.0 = from.lerp(to, t);
+                        }
+                    }
+                    TilemapTweenTarget::MaterialTint { from, to } => {
+                        if let Some(material) = material.and_then(|m| materials.get_mut(m)) {
+                            material.tint = from.mix(&to, t);
+                        }
+                    }
+                }
+            }
+
+            tweens.0.retain(|tween| {
+                let done = tween.is_finished();
+                if done {
+                    finished.send(TilemapTweenFinished {
+                        tilemap,
+                        target: tween.target,
+                    });
+                }
+                !done
+            });
+        },
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::{
+        app::App,
+        asset::Assets,
+        color::LinearRgba,
+        ecs::{event::Events, system::RunSystemOnce},
+        math::{Vec2, Vec4},
+        time::Time,
+    };
+
+    use crate::{
+        render::material::StandardTilemapMaterial,
+        tilemap::{
+            map::{TilemapLayerOpacities, TilemapTransform},
+            tile::TileAnimationMode,
+        },
+    };
+
+    use super::{
+        tilemap_tweener, TilemapEasing, TilemapTween, TilemapTweenFinished, TilemapTweenTarget,
+        TilemapTweens,
+    };
+
+    #[test]
+    fn test_tween_progress() {
+        let tween = |mode, elapsed| TilemapTween {
+            elapsed,
+            ..TilemapTween::new(TilemapTweenTarget::Rotation { from: 0., to: 1. }, 2.)
+                .with_mode(mode)
+        };
+
+        assert_eq!(tween(TileAnimationMode::Once, 1.).progress(), 0.5);
+        assert_eq!(tween(TileAnimationMode::Once, 3.).progress(), 1.);
+        assert_eq!(tween(TileAnimationMode::Loop, 3.).progress(), 0.5);
+        assert_eq!(tween(TileAnimationMode::PingPong, 3.).progress(), 0.5);
+        assert_eq!(tween(TileAnimationMode::PingPong, 2.5).progress(), 0.75);
+        assert!(tween(TileAnimationMode::Once, 2.).is_finished());
+        assert!(!tween(TileAnimationMode::Loop, 2.).is_finished());
+
+        assert_eq!(TilemapEasing::QuadraticIn.ease(0.5), 0.25);
+        assert_eq!(TilemapEasing::QuadraticInOut.ease(0.5), 0.5);
+        assert_eq!(TilemapEasing::SmoothStep.ease(1.), 1.);
+    }
+
+    #[test]
+    fn test_tilemap_tweener() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Assets<StandardTilemapMaterial>>()
+            .add_event::<TilemapTweenFinished>();
+
+        let world = app.world_mut();
+        let material = world
+            .resource_mut::<Assets<StandardTilemapMaterial>>()
+            .add(StandardTilemapMaterial::default());
+        let tilemap = world
+            .spawn((
+                TilemapTransform::default(),
+                TilemapLayerOpacities::default(),
+                material.clone(),
+                TilemapTweens::default()
+                    .with_tween(TilemapTween::new(
+                        TilemapTweenTarget::Translation {
+                            from: Vec2::ZERO,
+                            to: Vec2::new(10., 0.),
+                        },
+                        2.,
+                    ))
+                    .with_tween(TilemapTween::new(
+                        TilemapTweenTarget::LayerOpacities {
+                            from: Vec4::ONE,
+                            to: Vec4::ZERO,
+                        },
+                        4.,
+                    ))
+                    .with_tween(TilemapTween::new(
+                        TilemapTweenTarget::MaterialTint {
+                            from: LinearRgba::WHITE,
+                            to: LinearRgba::BLACK,
+                        },
+                        4.,
+                    )),
+            ))
+            .id();
+
+        let advance = |app: &mut App| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs(1));
+            app.world_mut().run_system_once(tilemap_tweener);
+        };
+
+        advance(&mut app);
+        let world = app.world();
+        assert_eq!(
+            world.get::<TilemapTransform>(tilemap).unwrap().translation,
+            Vec2::new(5., 0.)
+        );
+        assert_eq!(
+            world.get::<TilemapLayerOpacities>(tilemap).unwrap().0,
+            Vec4::splat(0.75)
+        );
+        assert_eq!(
+            world
+                .resource::<Assets<StandardTilemapMaterial>>()
+                .get(&material)
+                .unwrap()
+                .tint,
+            LinearRgba::rgb(0.75, 0.75, 0.75)
+        );
+
+        // The translation tween is finished and removed.
+        advance(&mut app);
+        let world = app.world_mut();
+        assert_eq!(
+            world.get::<TilemapTransform>(tilemap).unwrap().translation,
+            Vec2::new(10., 0.)
+        );
+        assert_eq!(world.get::<TilemapTweens>(tilemap).unwrap().0.len(), 2);
+        assert_eq!(
+            world
+                .resource_mut::<Events<TilemapTweenFinished>>()
+                .drain()
+                .map(|ev| ev.tilemap)
+                .collect::<Vec<_>>(),
+            vec![tilemap]
+        );
+    }
+}
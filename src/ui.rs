@@ -0,0 +1,495 @@
+//! Draw tilemaps inside bevy_ui layout nodes, like inventory grids, minimaps and tile pickers.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{AssetId, Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Changed,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    log::warn,
+    math::{URect, UVec2, Vec2},
+    reflect::Reflect,
+    render::texture::Image,
+    sprite::{
+        BorderRect, ImageScaleMode, SliceScaleMode, TextureAtlas, TextureAtlasLayout, TextureSlicer,
+    },
+    time::Time,
+    ui::{
+        node_bundles::{ImageBundle, NodeBundle},
+        Display, RepeatedGridTrack, Style, UiImage, Val,
+    },
+    utils::HashMap,
+};
+
+use crate::tilemap::{
+    map::{TilemapTexture, TilemapTextureDescriptor},
+    tile::{TileAnimationMode, TileFlip},
+};
+
+pub struct EntiTilesUiPlugin;
+
+impl Plugin for EntiTilesUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (ui_tilemap_builder, ui_tile_animator, ui_tile_panel_builder),
+        )
+        .register_type::<UiTile>()
+        .register_type::<UiTileAnimation>()
+        .register_type::<UiTilemap>()
+        .register_type::<UiAnimatedTile>()
+        .register_type::<UiTilePanel>()
+        .init_resource::<UiTilemapAtlases>();
+    }
+}
+
+/// An animation of a [`UiTile`]. The sequence contains atlas indices.
+#[derive(Debug, Clone, Default, PartialEq, Reflect)]
+pub struct UiTileAnimation {
+    pub sequence: Vec<u32>,
+    pub fps: u32,
+    pub mode: TileAnimationMode,
+}
+
+impl UiTileAnimation {
+    pub fn new(sequence: impl IntoIterator<Item = u32>, fps: u32) -> Self {
+        Self {
+            sequence: sequence.into_iter().collect(),
+            fps,
+            mode: TileAnimationMode::Loop,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: TileAnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The atlas index to display after `elapsed` seconds.
+    pub fn frame(&self, elapsed: f32) -> u32 {
+        let len = self.sequence.len();
+        if len == 0 {
+            return 0;
+        }
+
+        let frame = (elapsed * self.fps as f32) as usize;
+        let i = match self.mode {
+            TileAnimationMode::Loop => frame % len,
+            TileAnimationMode::Once => frame.min(len - 1),
+            TileAnimationMode::PingPong => {
+                let period = (len * 2).saturating_sub(2).max(1);
+                let f = frame % period;
+                if f < len {
+                    f
+                } else {
+                    period - f
+                }
+            }
+        };
+        self.sequence[i]
+    }
+}
+
+/// A tile in a [`UiTilemap`].
+#[derive(Debug, Clone, Default, PartialEq, Reflect)]
+pub struct UiTile {
+    pub atlas_index: u32,
+    pub flip: TileFlip,
+    /// Overrides `atlas_index` if set.
+    pub animation: Option<UiTileAnimation>,
+}
+
+impl UiTile {
+    pub fn new(atlas_index: u32) -> Self {
+        Self {
+            atlas_index,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_flip(mut self, flip: TileFlip) -> Self {
+        self.flip = flip;
+        self
+    }
+
+    pub fn with_animation(mut self, animation: UiTileAnimation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+}
+
+/// A grid of tiles drawn inside a ui node. Add this along with a `NodeBundle`.
+///
+/// The tiles are spawned as image nodes under the node, using the texture as an atlas.
+/// Index `(0, 0)` is the top left tile.
+///
+/// **Notice**: The whole grid is respawned when this component changes,
+/// so it's meant for small grids that don't change every frame.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct UiTilemap {
+    pub(crate) texture: TilemapTexture,
+    pub(crate) size: UVec2,
+    pub(crate) tile_size: Vec2,
+    pub(crate) tiles: Vec<Option<UiTile>>,
+}
+
+impl UiTilemap {
+    /// Create an empty grid. Tiles are displayed in their size in the texture by default.
+    pub fn new(texture: TilemapTexture, size: UVec2) -> Self {
+        Self {
+            tile_size: texture.desc().tile_size.as_vec2(),
+            texture,
+            size,
+            tiles: vec![None; (size.x * size.y) as usize],
+        }
+    }
+
+    /// Set the size of each tile in logical pixels.
+    pub fn with_tile_size(mut self, tile_size: Vec2) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    #[inline]
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    #[inline]
+    pub fn texture(&self) -> &TilemapTexture {
+        &self.texture
+    }
+
+    #[inline]
+    pub fn get(&self, index: UVec2) -> Option<&UiTile> {
+        self.linear_index(index)
+            .and_then(|i| self.tiles[i].as_ref())
+    }
+
+    /// Set a tile. Indices out of the grid are ignored.
+    #[inline]
+    pub fn set(&mut self, index: UVec2, tile: UiTile) {
+        if let Some(i) = self.linear_index(index) {
+            self.tiles[i] = Some(tile);
+        }
+    }
+
+    #[inline]
+    pub fn remove(&mut self, index: UVec2) {
+        if let Some(i) = self.linear_index(index) {
+            self.tiles[i] = None;
+        }
+    }
+
+    /// Fill the whole grid with `tile`.
+    pub fn fill(&mut self, tile: UiTile) {
+        self.tiles.fill(Some(tile));
+    }
+
+    #[inline]
+    fn linear_index(&self, index: UVec2) -> Option<usize> {
+        if index.x < self.size.x && index.y < self.size.y {
+            Some((index.y * self.size.x + index.x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// The animation of a spawned ui tile.
+///
+/// **Notice**: Animations restart when the [`UiTilemap`] is modified, as the tiles are respawned.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct UiAnimatedTile {
+    pub animation: UiTileAnimation,
+    /// When the animation started, in seconds since the app started.
+    pub start: f32,
+}
+
+/// A nine-slice panel made of a 3x3 block of tiles in a texture.
+/// Add this along with an `ImageBundle`, and the image is set automatically.
+///
+/// The corner tiles keep their size, while the edge and center tiles are scaled
+/// according to the scale mode to fill the node.
+///
+/// **Notice**: The padding and spacing of the texture are not skipped inside the block,
+/// so use textures without them to avoid seams.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct UiTilePanel {
+    pub(crate) texture: TilemapTexture,
+    pub(crate) origin: u32,
+    pub(crate) scale_mode: SliceScaleMode,
+    pub(crate) corner_scale: f32,
+}
+
+impl UiTilePanel {
+    /// Create a panel using the block whose top left tile is `origin` in the atlas.
+    pub fn new(texture: TilemapTexture, origin: u32) -> Self {
+        Self {
+            texture,
+            origin,
+            scale_mode: SliceScaleMode::Stretch,
+            corner_scale: 1.,
+        }
+    }
+
+    /// Set how the edge and center tiles are scaled.
+    pub fn with_scale_mode(mut self, scale_mode: SliceScaleMode) -> Self {
+        self.scale_mode = scale_mode;
+        self
+    }
+
+    /// Set the maximum scale of the corner tiles.
+    pub fn with_corner_scale(mut self, corner_scale: f32) -> Self {
+        self.corner_scale = corner_scale;
+        self
+    }
+
+    /// The rect of the 3x3 block in the texture in pixels.
+    /// Returns `None` if the block is out of the texture.
+    pub fn block_rect(&self) -> Option<URect> {
+        let tile_count = self.texture.desc().tile_count();
+        let origin = UVec2::new(self.origin % tile_count.x, self.origin / tile_count.x);
+        if origin.x + 3 > tile_count.x || origin.y + 3 > tile_count.y {
+            return None;
+        }
+
+        let last = self.origin + 2 + 2 * tile_count.x;
+        Some(URect {
+            min: self.texture.get_atlas_urect(self.origin).min,
+            max: self.texture.get_atlas_urect(last).max + 1,
+        })
+    }
+}
+
+/// The atlas layouts of the textures used by ui tilemaps and panels.
+///
+/// The same image can be sliced differently, so layouts are keyed by the descriptors as well.
+#[derive(Resource, Default)]
+pub struct UiTilemapAtlases {
+    pub(crate) grids:
+        HashMap<(AssetId<Image>, TilemapTextureDescriptor), Handle<TextureAtlasLayout>>,
+    pub(crate) panels:
+        HashMap<(AssetId<Image>, TilemapTextureDescriptor, u32), Handle<TextureAtlasLayout>>,
+}
+
+pub fn ui_tilemap_builder(
+    mut commands: Commands,
+    tilemaps_query: Query<(Entity, &UiTilemap), Changed<UiTilemap>>,
+    mut atlases: ResMut<UiTilemapAtlases>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    time: Res<Time>,
+) {
+    tilemaps_query.iter().for_each(|(entity, tilemap)| {
+        let layout = atlases
+            .grids
+            .entry((tilemap.texture.handle().id(), *tilemap.texture.desc()))
+            .or_insert_with(|| layouts.add(tilemap.texture.as_atlas_layout()))
+            .clone();
+        let tile_style = Style {
+            width: Val::Px(tilemap.tile_size.x),
+            height: Val::Px(tilemap.tile_size.y),
+            ..Default::default()
+        };
+
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .with_children(|root| {
+                root.spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Grid,
+                        grid_template_columns: RepeatedGridTrack::px(
+                            tilemap.size.x as u16,
+                            tilemap.tile_size.x,
+                        ),
+                        grid_template_rows: RepeatedGridTrack::px(
+                            tilemap.size.y as u16,
+                            tilemap.tile_size.y,
+                        ),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|grid| {
+                    tilemap.tiles.iter().for_each(|tile| {
+                        let Some(tile) = tile else {
+                            grid.spawn(NodeBundle {
+                                style: tile_style.clone(),
+                                ..Default::default()
+                            });
+                            return;
+                        };
+
+                        let index = tile
+                            .animation
+                            .as_ref()
+                            .map(|anim| anim.frame(0.))
+                            .unwrap_or(tile.atlas_index);
+                        let mut tile_entity = grid.spawn((
+                            ImageBundle {
+                                style: tile_style.clone(),
+                                image: UiImage {
+                                    texture: tilemap.texture.handle().clone(),
+                                    flip_x: tile.flip.contains(TileFlip::HORIZONTAL),
+                                    flip_y: tile.flip.contains(TileFlip::VERTICAL),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            TextureAtlas {
+                                layout: layout.clone(),
+                                index: index as usize,
+                            },
+                        ));
+                        if let Some(anim) = &tile.animation {
+                            tile_entity.insert(UiAnimatedTile {
+                                animation: anim.clone(),
+                                start: time.elapsed_seconds(),
+                            });
+                        }
+                    });
+                });
+            });
+    });
+}
+
+pub fn ui_tile_panel_builder(
+    mut commands: Commands,
+    panels_query: Query<(Entity, &UiTilePanel, Option<&UiImage>), Changed<UiTilePanel>>,
+    mut atlases: ResMut<UiTilemapAtlases>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    panels_query.iter().for_each(|(entity, panel, image)| {
+        let Some(rect) = panel.block_rect() else {
+            warn!(
+                "The 3x3 block at {} of {:?} is out of the texture.",
+                panel.origin, entity
+            );
+            return;
+        };
+
+        let layout = atlases
+            .panels
+            .entry((
+                panel.texture.handle().id(),
+                *panel.texture.desc(),
+                panel.origin,
+            ))
+            .or_insert_with(|| {
+                let mut layout = TextureAtlasLayout::new_empty(panel.texture.desc().size);
+                layout.add_texture(rect);
+                layouts.add(layout)
+            })
+            .clone();
+        let tile_size = panel.texture.desc().tile_size.as_vec2();
+
+        commands.entity(entity).insert((
+            UiImage {
+                texture: panel.texture.handle().clone(),
+                ..image.cloned().unwrap_or_default()
+            },
+            TextureAtlas { layout, index: 0 },
+            ImageScaleMode::Sliced(TextureSlicer {
+                border: BorderRect::rectangle(tile_size.x, tile_size.y),
+                center_scale_mode: panel.scale_mode,
+                sides_scale_mode: panel.scale_mode,
+                max_corner_scale: panel.corner_scale,
+            }),
+        ));
+    });
+}
+
+pub fn ui_tile_animator(
+    mut tiles_query: Query<(&UiAnimatedTile, &mut TextureAtlas)>,
+    time: Res<Time>,
+) {
+    let elapsed = time.elapsed_seconds();
+    tiles_query.iter_mut().for_each(|(anim, mut atlas)| {
+        let index = anim.animation.frame(elapsed - anim.start) as usize;
+        if atlas.index != index {
+            atlas.index = index;
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::{
+        asset::Handle,
+        ecs::{system::RunSystemOnce, world::World},
+        math::{URect, UVec2},
+        sprite::TextureAtlas,
+        time::Time,
+    };
+
+    use crate::tilemap::{
+        map::{TilemapTexture, TilemapTextureDescriptor},
+        tile::TileAnimationMode,
+    };
+
+    use super::{ui_tile_animator, UiAnimatedTile, UiTileAnimation, UiTilePanel};
+
+    #[test]
+    fn test_ui_tile_animation() {
+        let anim = UiTileAnimation::new([3, 4, 5], 2);
+        assert_eq!(anim.frame(0.), 3);
+        assert_eq!(anim.frame(1.), 5);
+        assert_eq!(anim.frame(1.5), 3);
+
+        let anim = anim.with_mode(TileAnimationMode::PingPong);
+        assert_eq!(
+            (0..6)
+                .map(|f| anim.frame(f as f32 / 2.))
+                .collect::<Vec<_>>(),
+            vec![3, 4, 5, 4, 3, 4]
+        );
+
+        let anim = anim.with_mode(TileAnimationMode::Once);
+        assert_eq!(anim.frame(10.), 5);
+    }
+
+    #[test]
+    fn test_ui_tile_animator() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(5.5));
+        world.insert_resource(time);
+
+        // Spawned at 5 seconds, so it's still playing.
+        let tile = world
+            .spawn((
+                UiAnimatedTile {
+                    animation: UiTileAnimation::new([3, 4, 5], 2)
+                        .with_mode(TileAnimationMode::Once),
+                    start: 5.,
+                },
+                TextureAtlas::default(),
+            ))
+            .id();
+
+        world.run_system_once(ui_tile_animator);
+        assert_eq!(world.get::<TextureAtlas>(tile).unwrap().index, 4);
+    }
+
+    #[test]
+    fn test_panel_block_rect() {
+        let texture = TilemapTexture::new(
+            Handle::default(),
+            TilemapTextureDescriptor::new(UVec2::new(64, 48), UVec2::splat(8)),
+        );
+
+        assert_eq!(
+            UiTilePanel::new(texture.clone(), 9).block_rect(),
+            Some(URect::new(8, 8, 32, 32))
+        );
+        assert_eq!(UiTilePanel::new(texture.clone(), 6).block_rect(), None);
+        assert_eq!(UiTilePanel::new(texture, 32).block_rect(), None);
+    }
+}
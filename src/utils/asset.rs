@@ -1,5 +1,18 @@
 use std::path::{Component, Path, PathBuf};
 
+use bevy::{
+    app::App,
+    asset::{io::AssetSourceId, Asset, AssetApp, AssetPath as BevyAssetPath, Assets},
+};
+
+/// Initialize `Assets<A>` if no other plugin has done it, so systems using the assets
+/// can still run when the plugin that owns them, like the `RenderPlugin`, is absent.
+pub(crate) fn init_asset_if_missing<A: Asset>(app: &mut App) {
+    if !app.world().contains_resource::<Assets<A>>() {
+        app.init_asset::<A>();
+    }
+}
+
 pub trait AssetPath {
     fn to_asset_path(&self) -> PathBuf;
 }
@@ -17,7 +30,7 @@ impl AssetPath for PathBuf {
 }
 
 /// Converts a path to an asset path.
-/// 
+///
 /// # Example
 /// ```rust
 /// assert_eq!(to_asset_path("C:\\Project\\assets\\project\\../test_image.png", "test_image.png"));
@@ -46,6 +59,23 @@ pub fn to_asset_path(path: impl AsRef<Path>) -> PathBuf {
     result
 }
 
+/// Make the path load from the given asset source, or the default one if `source` is `None`.
+///
+/// This is used to load the dependencies of a map from where the map itself is loaded,
+/// so maps from custom sources like web servers can find their textures.
+pub fn with_source(path: impl Into<PathBuf>, source: Option<&str>) -> BevyAssetPath<'static> {
+    let path = BevyAssetPath::from(path.into());
+    match source {
+        Some(source) => path.with_source(AssetSourceId::from(source.to_string())),
+        None => path,
+    }
+}
+
+/// The name of the asset source the path belongs to. `None` for the default source.
+pub fn source_of(path: &BevyAssetPath) -> Option<String> {
+    path.source().as_str().map(ToOwned::to_owned)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -56,4 +86,12 @@ mod test {
             "C:\\Project\\assets\\project\\../test_image.png"
         ));
     }
+
+    #[test]
+    fn test_with_source() {
+        let path = with_source("maps/level.png", Some("remote"));
+        assert_eq!(source_of(&path).as_deref(), Some("remote"));
+        assert_eq!(path.path(), Path::new("maps/level.png"));
+        assert_eq!(source_of(&with_source("maps/level.png", None)), None);
+    }
 }
@@ -0,0 +1,78 @@
+use bevy::reflect::Reflect;
+
+/// Selects layers of LDtk levels and Tiled maps by their names or classes, so heavy
+/// layers like decorations can be skipped on low-end platforms or servers.
+///
+/// Skipped layers still take their z, so the loaded ones are placed the same as
+/// loading the whole map.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Reflect)]
+pub enum LayerFilter {
+    #[default]
+    All,
+    /// Only load the layers whose name or class is in the list.
+    Only(Vec<String>),
+    /// Load all the layers except the ones whose name or class is in the list.
+    Except(Vec<String>),
+}
+
+impl LayerFilter {
+    fn matches(names: &[String], name: &str, class: &str) -> bool {
+        names
+            .iter()
+            .any(|n| n == name || (!class.is_empty() && n == class))
+    }
+
+    /// Returns `true` if the layer should be loaded.
+    ///
+    /// Empty classes are ignored, as LDtk layers don't have classes.
+    pub fn selects(&self, name: &str, class: &str) -> bool {
+        match self {
+            LayerFilter::All => true,
+            LayerFilter::Only(names) => Self::matches(names, name, class),
+            LayerFilter::Except(names) => !Self::matches(names, name, class),
+        }
+    }
+
+    /// Returns the filter for the layers inside the group,
+    /// or `None` if the whole group should be skipped.
+    ///
+    /// Filtering a group filters all the layers inside it.
+    pub fn enter_group(&self, name: &str, class: &str) -> Option<&Self> {
+        match self {
+            LayerFilter::All => Some(self),
+            LayerFilter::Only(names) if Self::matches(names, name, class) => {
+                Some(&LayerFilter::All)
+            }
+            LayerFilter::Only(_) => Some(self),
+            LayerFilter::Except(_) => self.selects(name, class).then_some(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LayerFilter;
+
+    #[test]
+    fn test_layer_filter() {
+        let only = LayerFilter::Only(vec!["Collision".to_string(), "Props".to_string()]);
+        assert!(only.selects("Collision", ""));
+        assert!(only.selects("Trees", "Props"));
+        assert!(!only.selects("Decoration", ""));
+        // Selecting a group selects everything inside.
+        assert_eq!(only.enter_group("Props", ""), Some(&LayerFilter::All));
+        assert_eq!(only.enter_group("Ground", ""), Some(&only));
+
+        let except = LayerFilter::Except(vec!["Decoration".to_string()]);
+        assert!(except.selects("Collision", ""));
+        assert!(!except.selects("Grass", "Decoration"));
+        assert_eq!(except.enter_group("Decoration", ""), None);
+        assert_eq!(except.enter_group("Ground", ""), Some(&except));
+
+        // LDtk layers only have identifiers.
+        let only = LayerFilter::Only(vec!["Collisions".to_string()]);
+        assert!(only.selects("Collisions", ""));
+        assert!(!only.selects("Entities", ""));
+        assert!(LayerFilter::All.selects("Decoration", ""));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod asset;
+pub mod filter;
 pub mod mesh;